@@ -10,6 +10,7 @@ use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use simplelog::{ColorChoice, TermLogger, TerminalMode};
+use url::Url;
 
 use darkfi::{
     blockchain::{rocks::columns, Rocks, RocksColumn},
@@ -75,10 +76,11 @@ pub struct CashierdConfig {
     pub client_wallet_password: String,
     /// Path to database
     pub database_path: String,
-    /// Geth IPC endpoint
-    pub geth_socket: String,
-    /// Geth passphrase
-    pub geth_passphrase: String,
+    /// Websocket endpoint of the Ethereum node's web3 JSON-RPC
+    pub web3_url: Url,
+    /// Confirmations an Ethereum deposit needs before it is credited
+    #[serde(default = "default_eth_confirmations")]
+    pub eth_confirmations: u32,
     /// The configured networks to use
     pub networks: Vec<FeatureNetwork>,
 }
@@ -101,6 +103,10 @@ pub struct CliCashierd {
     pub refresh: bool,
 }
 
+fn default_eth_confirmations() -> u32 {
+    12
+}
+
 const CONFIG_FILE_CONTENTS: &[u8] = include_bytes!("../cashierd_config.toml");
 
 fn handle_bridge_error(error_code: u32) -> Result<()> {
@@ -207,13 +213,8 @@ impl Cashierd {
 
                     let _bridge = self.bridge.clone();
 
-                    let passphrase = self.config.geth_passphrase.clone();
-
-                    let mut eth_client = EthClient::new(
-                        &network.blockchain,
-                        expand_path(&self.config.geth_socket)?.to_str().unwrap(),
-                        &passphrase,
-                    );
+                    let mut eth_client =
+                        EthClient::new(self.config.web3_url.clone(), self.config.eth_confirmations);
 
                     eth_client.setup_keypair(self.cashier_wallet.clone(), &network.keypair).await?;
 
@@ -236,6 +237,16 @@ impl Cashierd {
 
                     _bridge.add_clients(NetworkName::Bitcoin, btc_client).await?;
                 }
+
+                #[cfg(feature = "xmr")]
+                NetworkName::Monero => {
+                    debug!(target: "CASHIER DAEMON", "Adding monero network");
+                    use cashierd::service::XmrClient;
+
+                    let xmr_client = XmrClient::new(&network.blockchain)?;
+
+                    self.bridge.clone().add_clients(NetworkName::Monero, xmr_client).await?;
+                }
                 _ => {}
             }
         }
@@ -384,6 +395,8 @@ impl Cashierd {
             }
             #[cfg(feature = "btc")]
             NetworkName::Bitcoin => Ok(None),
+            #[cfg(feature = "xmr")]
+            NetworkName::Monero => Ok(None),
             _ => Err(Error::NotSupportedNetwork),
         }
     }
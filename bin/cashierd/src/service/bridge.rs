@@ -2,13 +2,12 @@ use async_std::sync::{Arc, Mutex};
 
 use async_executor::Executor;
 use async_trait::async_trait;
-use futures::stream::{FuturesUnordered, StreamExt};
 use fxhash::FxHashMap;
 use log::{debug, error};
 
 use darkfi::{
     crypto::{keypair::PublicKey, types::*},
-    util::NetworkName,
+    util::{serial::deserialize, NetworkName},
     wallet::cashierdb::TokenKey,
     Error, Result,
 };
@@ -48,12 +47,20 @@ pub struct BridgeSubscribtion {
     pub receiver: async_channel::Receiver<BridgeResponse>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TokenSubscribtion {
     pub private_key: Vec<u8>,
     pub public_key: String,
 }
 
+/// A confirmed deposit to a watched address.
+#[derive(Debug)]
+pub struct Deposit {
+    pub token_id: DrkTokenId,
+    pub amount: u64,
+    pub decimals: u16,
+}
+
 #[derive(Debug)]
 pub struct TokenNotification {
     pub network: NetworkName,
@@ -64,55 +71,38 @@ pub struct TokenNotification {
 }
 
 pub struct Bridge {
-    clients: Mutex<FxHashMap<NetworkName, Arc<dyn NetworkClient + Send + Sync>>>,
-    notifiers: FuturesUnordered<async_channel::Receiver<TokenNotification>>,
+    clients: Mutex<FxHashMap<NetworkName, Arc<dyn ChainWatcher>>>,
+    /// Deposits confirmed on any network, to mint wrapped notes for
+    notify_channel:
+        (async_channel::Sender<TokenNotification>, async_channel::Receiver<TokenNotification>),
 }
 
 impl Bridge {
     pub fn new() -> Arc<Self> {
         Arc::new(Self {
             clients: Mutex::new(FxHashMap::default()),
-            notifiers: FuturesUnordered::new(),
+            notify_channel: async_channel::unbounded(),
         })
     }
 
     pub async fn add_clients(
         self: Arc<Self>,
         network: NetworkName,
-        client: Arc<dyn NetworkClient + Send + Sync>,
+        client: Arc<dyn ChainWatcher>,
     ) -> Result<()> {
         debug!(target: "BRIDGE", "Adding new client");
 
-        let client2 = client.clone();
-        let notifier = client2.get_notifier().await?;
-
-        if !notifier.is_closed() {
-            self.notifiers.push(notifier);
-        }
-
-        self.clients.lock().await.insert(network, client.clone());
+        self.clients.lock().await.insert(network, client);
 
         Ok(())
     }
 
     pub async fn listen(self: Arc<Self>) -> Option<Result<TokenNotification>> {
-        if !self.notifiers.is_empty() {
-            debug!(target: "BRIDGE", "Start listening for new notifications");
-            let notification = self
-                .notifiers
-                .iter()
-                .map(|n| n.recv())
-                .collect::<FuturesUnordered<async_channel::Recv<TokenNotification>>>()
-                .next()
-                .await
-                .map(|o| o.map_err(Error::from));
-
-            debug!(target: "BRIDGE", "Stop listening for new notifications");
-
-            notification
-        } else {
-            None
-        }
+        debug!(target: "BRIDGE", "Start listening for new notifications");
+        let notification = self.notify_channel.1.recv().await.map_err(Error::from);
+        debug!(target: "BRIDGE", "Stop listening for new notifications");
+
+        Some(notification)
     }
 
     pub async fn subscribe(
@@ -132,6 +122,40 @@ impl Bridge {
         BridgeSubscribtion { sender, receiver }
     }
 
+    /// Subscribe to the deposits to the address of `key`, forwarding them
+    /// as notifications for `drk_pub_key`.
+    async fn watch_deposits(
+        self: Arc<Self>,
+        network: NetworkName,
+        client: Arc<dyn ChainWatcher>,
+        key: TokenSubscribtion,
+        drk_pub_key: PublicKey,
+        mint: Option<String>,
+        executor: Arc<Executor<'_>>,
+    ) -> Result<()> {
+        let deposits = client.subscribe_deposits(key, mint, executor.clone()).await?;
+        let notify = self.notify_channel.0.clone();
+
+        executor
+            .spawn(async move {
+                while let Ok(deposit) = deposits.recv().await {
+                    let notification = TokenNotification {
+                        network: network.clone(),
+                        token_id: deposit.token_id,
+                        drk_pub_key,
+                        received_balance: deposit.amount,
+                        decimals: deposit.decimals,
+                    };
+                    if notify.send(notification).await.is_err() {
+                        break
+                    }
+                }
+            })
+            .detach();
+
+        Ok(())
+    }
+
     async fn listen_for_new_subscription(
         self: Arc<Self>,
         req: async_channel::Receiver<BridgeRequests>,
@@ -160,7 +184,7 @@ impl Bridge {
             mint_address = None;
         }
 
-        let client: Arc<dyn NetworkClient + Send + Sync>;
+        let client: Arc<dyn ChainWatcher>;
         // avoid deadlock
         {
             let c = &self.clients.lock().await[&network];
@@ -170,50 +194,51 @@ impl Bridge {
         let res: BridgeResponse;
 
         match req.payload {
-            BridgeRequestsPayload::Watch(val) => match val {
-                Some(token_key) => {
-                    let pub_key = client
-                        .subscribe_with_keypair(
-                            token_key.secret_key,
-                            token_key.public_key,
+            BridgeRequestsPayload::Watch(val) => {
+                // Keys of an address we already gave out are reused
+                let key = match val {
+                    Some(token_key) => deserialize(&token_key.public_key).map(|public_key| {
+                        (TokenSubscribtion { private_key: token_key.secret_key, public_key }, false)
+                    }),
+                    None => client.new_deposit_key().map(|key| (key, true)),
+                };
+
+                let result = match key {
+                    Ok((key, is_new)) => self
+                        .clone()
+                        .watch_deposits(
+                            network,
+                            client,
+                            key.clone(),
                             drk_pub_key,
                             mint_address,
                             executor,
                         )
-                        .await;
+                        .await
+                        .map(|()| (key, is_new)),
+                    Err(e) => Err(e),
+                };
 
-                    if pub_key.is_err() {
-                        error!(target: "BRIDGE", "{}", pub_key.unwrap_err().to_string());
-                        res = BridgeResponse {
-                            error: BridgeResponseError::BridgeWatchSubscribtionError,
-                            payload: BridgeResponsePayload::Empty,
-                        };
-                    } else {
-                        res = BridgeResponse {
-                            error: BridgeResponseError::NoError,
-                            payload: BridgeResponsePayload::Address(pub_key?),
-                        };
-                    }
-                }
-                None => {
-                    let sub = client.subscribe(drk_pub_key, mint_address, executor).await;
-                    if sub.is_err() {
-                        error!(target: "BRIDGE", "{}", sub.unwrap_err().to_string());
-                        res = BridgeResponse {
+                res = match result {
+                    Ok((key, true)) => BridgeResponse {
+                        error: BridgeResponseError::NoError,
+                        payload: BridgeResponsePayload::Watch(key),
+                    },
+                    Ok((key, false)) => BridgeResponse {
+                        error: BridgeResponseError::NoError,
+                        payload: BridgeResponsePayload::Address(key.public_key),
+                    },
+                    Err(e) => {
+                        error!(target: "BRIDGE", "{}", e.to_string());
+                        BridgeResponse {
                             error: BridgeResponseError::BridgeWatchSubscribtionError,
                             payload: BridgeResponsePayload::Empty,
-                        };
-                    } else {
-                        let sub = sub?;
-                        res = BridgeResponse {
-                            error: BridgeResponseError::NoError,
-                            payload: BridgeResponsePayload::Watch(sub),
-                        };
+                        }
                     }
-                }
-            },
+                };
+            }
             BridgeRequestsPayload::Send(addr, amount) => {
-                let result = client.send(addr, mint_address, amount).await;
+                let result = client.send_withdrawal(addr, mint_address, amount).await;
 
                 if result.is_err() {
                     error!(target: "BRIDGE", "{}", result.unwrap_err().to_string());
@@ -236,28 +261,32 @@ impl Bridge {
     }
 }
 
+/// An external network the cashier takes deposits from and pays out
+/// withdrawals on. Supporting a new network is implementing this trait
+/// and adding its [`NetworkName`] variant.
 #[async_trait]
-pub trait NetworkClient {
-    async fn subscribe(
-        self: Arc<Self>,
-        drk_pub_key: PublicKey,
-        mint: Option<String>,
-        executor: Arc<Executor<'_>>,
-    ) -> Result<TokenSubscribtion>;
-
-    // should check if the keypair in not already subscribed
-    async fn subscribe_with_keypair(
+pub trait ChainWatcher: Send + Sync {
+    /// Confirmations a deposit needs before wrapped notes are minted for it.
+    fn confirmations(&self) -> u32;
+
+    /// Create a fresh keypair for a deposit address.
+    fn new_deposit_key(&self) -> Result<TokenSubscribtion>;
+
+    /// Start watching the deposit address of `key` for deposits of `mint`,
+    /// or of the native coin. Deposits are sent on the returned channel
+    /// once they have [`ChainWatcher::confirmations`], after which the
+    /// implementation moves them into the main wallet. The channel closes
+    /// when the address stops being watched.
+    async fn subscribe_deposits(
         self: Arc<Self>,
-        private_key: Vec<u8>,
-        public_key: Vec<u8>,
-        drk_pub_key: PublicKey,
+        key: TokenSubscribtion,
         mint: Option<String>,
         executor: Arc<Executor<'_>>,
-    ) -> Result<String>;
-
-    async fn get_notifier(self: Arc<Self>) -> Result<async_channel::Receiver<TokenNotification>>;
+    ) -> Result<async_channel::Receiver<Deposit>>;
 
-    async fn send(
+    /// Pay a withdrawal of `amount`, with DarkFi's 8 decimals, of `mint` or
+    /// of the native coin from the main wallet.
+    async fn send_withdrawal(
         self: Arc<Self>,
         address: Vec<u8>,
         mint: Option<String>,
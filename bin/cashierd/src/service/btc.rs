@@ -40,9 +40,9 @@ use secp256k1::{
     All, Message as BtcMessage, Secp256k1,
 };
 
-use super::bridge::{NetworkClient, TokenNotification, TokenSubscribtion};
+use super::bridge::{ChainWatcher, Deposit, TokenSubscribtion};
 use darkfi::{
-    crypto::{keypair::PublicKey as DrkPublicKey, token_id::generate_id2},
+    crypto::token_id::generate_id2,
     util::{
         expand_path, load_keypair_to_str,
         serial::{deserialize, serialize, Decodable, Encodable},
@@ -59,6 +59,9 @@ pub type PrivKey = BtcPrivKey;
 
 const KEYPAIR_LENGTH: usize = SECRET_KEY_SIZE + PUBLIC_KEY_SIZE;
 
+/// Confirmations a deposit needs before it is credited
+const BTC_CONFIRMATIONS: u32 = 2;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct BlockHeight(u32);
 
@@ -320,8 +323,6 @@ impl Client {
 pub struct BtcClient {
     main_account: Account,
     client: Arc<Mutex<Client>>,
-    notify_channel:
-        (async_channel::Sender<TokenNotification>, async_channel::Receiver<TokenNotification>),
     network: Network,
 }
 impl BtcClient {
@@ -356,8 +357,6 @@ impl BtcClient {
                 .map_err(|e| BtcFailed::DecodeAndEncodeError(e.to_string()))?;
         }
 
-        let notify_channel = async_channel::unbounded();
-
         let (network, url) = match network {
             "mainnet" => (Network::Bitcoin, "ssl://electrum.blockstream.info:50002"),
             "testnet" => (Network::Testnet, "ssl://electrum.blockstream.info:60002"),
@@ -371,7 +370,6 @@ impl BtcClient {
         Ok(Arc::new(Self {
             main_account,
             client: Arc::new(Mutex::new(Client::new(url)?)),
-            notify_channel,
             network,
         }))
     }
@@ -379,7 +377,7 @@ impl BtcClient {
     async fn handle_subscribe_request(
         self: Arc<Self>,
         btc_keys: Account,
-        drk_pub_key: DrkPublicKey,
+        deposits: async_channel::Sender<Deposit>,
     ) -> BtcResult<()> {
         let client = self.client.clone();
 
@@ -408,12 +406,9 @@ impl BtcClient {
             last_status = Some(print_status_change(&script, last_status, new_status));
 
             match new_status {
-                ScriptStatus::Unseen => continue,
-                ScriptStatus::InMempool => break,
+                ScriptStatus::Unseen | ScriptStatus::InMempool => continue,
                 ScriptStatus::Confirmed(inner) => {
-                    //Only break when confirmations happen
-                    let confirmations = inner.confirmations();
-                    if confirmations > 1 {
+                    if inner.meets_target(BTC_CONFIRMATIONS) {
                         break
                     }
                 }
@@ -430,24 +425,19 @@ impl BtcClient {
         let cur_balance: GetBalanceRes =
             client.lock().await.electrum.script_get_balance(&script)?;
 
-        let send_notification = self.notify_channel.0.clone();
-        //FIXME: dev
-        if cur_balance.unconfirmed < prev_balance.unconfirmed {
+        if cur_balance.confirmed < prev_balance.confirmed {
             return Err(BtcFailed::Notification("New balance is less than previous balance".into()))
         }
-        //Just check unconfirmed for now
         let amnt = cur_balance.confirmed - prev_balance.confirmed;
         let ui_amnt = amnt;
-        send_notification
-            .send(TokenNotification {
-                network: NetworkName::Bitcoin,
+        deposits
+            .send(Deposit {
                 // is btc an acceptable token name?
                 token_id: generate_id2(
                     "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
                     &NetworkName::Bitcoin,
                 )?,
-                drk_pub_key,
-                received_balance: amnt as u64,
+                amount: amnt as u64,
                 decimals: 8,
             })
             .await
@@ -534,63 +524,47 @@ impl BtcClient {
 }
 
 #[async_trait]
-impl NetworkClient for BtcClient {
-    async fn subscribe(
-        self: Arc<Self>,
-        drk_pub_key: DrkPublicKey,
-        _mint: Option<String>,
-        executor: Arc<Executor<'_>>,
-    ) -> Result<TokenSubscribtion> {
+impl ChainWatcher for BtcClient {
+    fn confirmations(&self) -> u32 {
+        BTC_CONFIRMATIONS
+    }
+
+    fn new_deposit_key(&self) -> Result<TokenSubscribtion> {
         // Generate bitcoin keys
         let keypair = Keypair::new();
         let btc_keys = Account::new(&keypair, self.network);
-        let private_key = serialize(&keypair);
-        let public_key = btc_keys.address.to_string();
-
-        // start scheduler for checking balance
-        trace!(target: "BRIDGE BITCOIN", "Subscribing for deposit");
-
-        executor
-            .spawn(async move {
-                let result = self.handle_subscribe_request(btc_keys, drk_pub_key).await;
-                if let Err(e) = result {
-                    error!(target: "BTC BRIDGE SUBSCRIPTION","{}", e.to_string());
-                }
-            })
-            .detach();
-
-        Ok(TokenSubscribtion { private_key, public_key })
+        Ok(TokenSubscribtion {
+            private_key: serialize(&keypair),
+            public_key: btc_keys.address.to_string(),
+        })
     }
 
-    async fn subscribe_with_keypair(
+    async fn subscribe_deposits(
         self: Arc<Self>,
-        private_key: Vec<u8>,
-        _public_key: Vec<u8>,
-        drk_pub_key: DrkPublicKey,
+        key: TokenSubscribtion,
         _mint: Option<String>,
         executor: Arc<Executor<'_>>,
-    ) -> Result<String> {
-        let keypair: Keypair = deserialize(&private_key)?;
+    ) -> Result<async_channel::Receiver<Deposit>> {
+        let keypair: Keypair = deserialize(&key.private_key)?;
         let btc_keys = Account::new(&keypair, self.network);
-        let public_key = btc_keys.address.to_string();
+        let (deposits, receiver) = async_channel::bounded(1);
+
+        // start scheduler for checking balance
+        trace!(target: "BRIDGE BITCOIN", "Subscribing for deposit");
 
         executor
             .spawn(async move {
-                let result = self.handle_subscribe_request(btc_keys, drk_pub_key).await;
+                let result = self.handle_subscribe_request(btc_keys, deposits).await;
                 if let Err(e) = result {
                     error!(target: "BTC BRIDGE SUBSCRIPTION","{}", e.to_string());
                 }
             })
             .detach();
 
-        Ok(public_key)
-    }
-
-    async fn get_notifier(self: Arc<Self>) -> Result<async_channel::Receiver<TokenNotification>> {
-        Ok(self.notify_channel.1.clone())
+        Ok(receiver)
     }
 
-    async fn send(
+    async fn send_withdrawal(
         self: Arc<Self>,
         address: Vec<u8>,
         _mint: Option<String>,
@@ -1,23 +1,31 @@
 use std::convert::TryInto;
 
 use async_executor::Executor;
+use async_native_tls::TlsConnector;
 use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
 use hash_db::Hasher;
 use keccak_hasher::KeccakHasher;
 use lazy_static::lazy_static;
-use log::{debug, error, info, trace};
-use num_bigint::{BigUint, RandBigInt};
+use log::{debug, error, info, trace, warn};
+use num_bigint::BigUint;
+use secp256k1::{Message as SecpMessage, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tungstenite::Message;
 use url::Url;
 
-use super::bridge::{NetworkClient, TokenNotification, TokenSubscribtion};
+use super::bridge::{ChainWatcher, Deposit, TokenSubscribtion};
 
 use darkfi::{
-    crypto::{keypair::PublicKey, token_id::generate_id2},
-    rpc::{jsonrpc, jsonrpc::JsonResult},
+    crypto::token_id::generate_id2,
+    rpc::{
+        jsonrpc::{JsonRequest, JsonResult},
+        websockets,
+    },
     util::{
+        expand_path, load_keypair_to_str,
         parse::truncate,
         serial::{deserialize, serialize, Decodable, Encodable},
         sleep, NetworkName,
@@ -28,12 +36,49 @@ use darkfi::{
 
 pub const ETH_NATIVE_TOKEN_ID: &str = "0x0000000000000000000000000000000000000000";
 
+/// Gas used by a plain ETH transfer
+const ETH_TRANSFER_GAS: u64 = 21000;
+
+/// Decimals of ETH amounts, in wei
+const ETH_DECIMALS: u16 = 18;
+
+/// Decimals of the amounts notified to the bridge, in gwei, so that they
+/// fit in a u64
+const ETH_NOTIFY_DECIMALS: u16 = 9;
+
+/// Seconds between two polls of a deposit address, about a block
+const ETH_POLL_INTERVAL: u64 = 15;
+
+/// Seconds after which an unfunded deposit address stops being watched
+const ETH_DEPOSIT_TIMEOUT: u64 = 60 * 60;
+
+/// Hex encoded private key, with the address it controls.
 #[derive(Clone, Debug)]
 pub struct Keypair {
     pub private_key: String,
     pub public_key: String,
 }
 
+impl Keypair {
+    pub fn random() -> Self {
+        let secret = SecretKey::new(&mut rand::thread_rng());
+        Self { private_key: hex::encode(secret.secret_bytes()), public_key: address_of(&secret) }
+    }
+
+    pub fn from_private_key(private_key: &str) -> EthResult<Self> {
+        let private_key = private_key.trim().trim_start_matches("0x").to_string();
+        let keypair = Self { public_key: String::new(), private_key };
+        let public_key = address_of(&keypair.secret_key()?);
+        Ok(Self { public_key, ..keypair })
+    }
+
+    fn secret_key(&self) -> EthResult<SecretKey> {
+        let bytes = hex::decode(&self.private_key)
+            .map_err(|e| EthFailed::DecodeAndEncodeError(e.to_string()))?;
+        SecretKey::from_slice(&bytes).map_err(|e| EthFailed::DecodeAndEncodeError(e.to_string()))
+    }
+}
+
 impl Encodable for Keypair {
     fn encode<S: std::io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -127,13 +172,77 @@ fn to_eth_hex(val: BigUint) -> String {
     format!("0x{}", h.trim_start_matches('0'))
 }
 
-/// Generate a 256-bit ETH private key.
-pub fn generate_privkey() -> String {
-    let mut rng = rand::thread_rng();
-    let token = rng.gen_bigint(256);
-    let token_bytes = token.to_bytes_le().1;
-    let key = KeccakHasher::hash(&token_bytes);
-    hex::encode(key)
+fn parse_eth_hex(val: &Value) -> EthResult<BigUint> {
+    let hex = match val.as_str() {
+        Some(v) => v.trim_start_matches("0x"),
+        None => return Err(EthFailed::ParseError(format!("Expected hex quantity: {}", val))),
+    };
+    if hex.is_empty() {
+        return Ok(BigUint::from(0u64))
+    }
+    BigUint::parse_bytes(hex.as_bytes(), 16)
+        .ok_or_else(|| EthFailed::ParseError(format!("Invalid hex quantity: {}", val)))
+}
+
+fn parse_eth_u64(val: &Value) -> EthResult<u64> {
+    let digits = parse_eth_hex(val)?.to_u64_digits();
+    match digits.len() {
+        0 => Ok(0),
+        1 => Ok(digits[0]),
+        _ => Err(EthFailed::ParseError(format!("Quantity overflows u64: {}", val))),
+    }
+}
+
+/// Parse a hex encoded address into its 20 bytes.
+pub fn parse_address(address: &str) -> EthResult<[u8; 20]> {
+    let bytes = hex::decode(address.trim_start_matches("0x"))
+        .map_err(|_| EthFailed::BadEthAddress(address.to_string()))?;
+    bytes.try_into().map_err(|_| EthFailed::BadEthAddress(address.to_string()))
+}
+
+/// Address controlled by a private key: the last 20 bytes of the keccak256
+/// hash of its uncompressed public key.
+pub fn address_of(secret: &SecretKey) -> String {
+    let public = secp256k1::PublicKey::from_secret_key(&Secp256k1::signing_only(), secret);
+    let hash = KeccakHasher::hash(&public.serialize_uncompressed()[1..]);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+fn rlp_length(out: &mut Vec<u8>, offset: u8, len: usize) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let be = len.to_be_bytes();
+        let be: Vec<u8> = be.iter().copied().skip_while(|b| *b == 0).collect();
+        out.push(offset + 55 + be.len() as u8);
+        out.extend_from_slice(&be);
+    }
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec()
+    }
+    let mut out = vec![];
+    rlp_length(&mut out, 0x80, bytes.len());
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Integers are encoded big endian without leading zeros, zero as the
+/// empty string.
+fn rlp_uint(value: &BigUint) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let bytes: Vec<u8> = bytes.into_iter().skip_while(|b| *b == 0).collect();
+    rlp_bytes(&bytes)
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut out = vec![];
+    rlp_length(&mut out, 0xc0, payload.len());
+    out.extend_from_slice(&payload);
+    out
 }
 
 #[allow(non_snake_case)]
@@ -185,65 +294,115 @@ impl EthTx {
     }
 }
 
-// JSON-RPC interface to Geth.
-// https://eth.wiki/json-rpc/API
-// https://geth.ethereum.org/docs/rpc/
-//
-// geth can be started with: $ geth --ropsten --syncmode light
-// It should then show an Unix socket endpoint like so:
-// INFO [10-25|19:47:32.845] IPC endpoint opened: url=/home/x/.ethereum/ropsten/geth.ipc
-//
+/// Legacy transaction, signed by the cashier with replay protection
+/// (EIP-155) and broadcast raw, so the node doesn't need to hold any key.
+#[derive(Debug, Clone)]
+pub struct SignedEthTx {
+    pub nonce: u64,
+    pub gas_price: BigUint,
+    pub gas: u64,
+    pub to: [u8; 20],
+    pub value: BigUint,
+    pub data: Vec<u8>,
+}
+
+impl SignedEthTx {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(&BigUint::from(self.nonce)),
+            rlp_uint(&self.gas_price),
+            rlp_uint(&BigUint::from(self.gas)),
+            rlp_bytes(&self.to),
+            rlp_uint(&self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    /// RLP encoding of the transaction signed with the given key, ready
+    /// for `eth_sendRawTransaction`.
+    pub fn sign(&self, secret: &SecretKey, chain_id: u64) -> Vec<u8> {
+        let zero = BigUint::from(0u64);
+
+        let mut fields = self.fields();
+        fields.push(rlp_uint(&BigUint::from(chain_id)));
+        fields.push(rlp_uint(&zero));
+        fields.push(rlp_uint(&zero));
+        let hash = KeccakHasher::hash(&rlp_list(&fields));
+
+        let message = SecpMessage::from_slice(&hash).unwrap();
+        let signature = Secp256k1::signing_only().sign_ecdsa_recoverable(&message, secret);
+        let (recovery_id, rs) = signature.serialize_compact();
+        let v = chain_id * 2 + 35 + recovery_id.to_i32() as u64;
+
+        let mut fields = self.fields();
+        fields.push(rlp_uint(&BigUint::from(v)));
+        fields.push(rlp_uint(&BigUint::from_bytes_be(&rs[..32])));
+        fields.push(rlp_uint(&BigUint::from_bytes_be(&rs[32..])));
+        rlp_list(&fields)
+    }
+}
+
+/// Bridge client talking to an Ethereum node over its web3 JSON-RPC
+/// websocket endpoint.
+///
+/// Each deposit is made to a fresh address, whose balance is watched at
+/// the configured depth. Once a deposit has enough confirmations, wrapped
+/// notes are minted for it, and it is swept into the main account which
+/// pays out the withdrawals. All transactions are signed by the cashier.
 pub struct EthClient {
     pub main_keypair: Keypair,
-    passphrase: String,
-    socket_path: String,
+    web3_url: Url,
+    confirmations: u32,
+    chain_id: u64,
     subscriptions: Arc<Mutex<Vec<String>>>,
-    notify_channel:
-        (async_channel::Sender<TokenNotification>, async_channel::Receiver<TokenNotification>),
+    /// Held while sending from the main account, so that concurrent
+    /// withdrawals don't pick the same nonce
+    send_lock: Mutex<()>,
 }
 
 impl EthClient {
-    pub fn new(_network: &str, socket_path: &str, passphrase: &str) -> Self {
-        let notify_channel = async_channel::unbounded();
-
+    pub fn new(web3_url: Url, confirmations: u32) -> Self {
         let subscriptions = Arc::new(Mutex::new(Vec::new()));
 
         let main_keypair = Keypair { public_key: "".into(), private_key: "".into() };
 
         Self {
             main_keypair,
-            passphrase: passphrase.into(),
-            socket_path: socket_path.into(),
+            web3_url,
+            confirmations: confirmations.max(1),
+            chain_id: 0,
             subscriptions,
-            notify_channel,
+            send_lock: Mutex::new(()),
         }
     }
 
+    /// Load the main account from the given key file, holding a hex encoded
+    /// private key, or from the wallet, creating one if there is none. Also
+    /// fetches the chain ID transactions are signed for.
     pub async fn setup_keypair(
         &mut self,
         cashier_wallet: Arc<CashierDb>,
-        _keypair_path: &str,
+        keypair_path: &str,
     ) -> Result<()> {
         let main_keypair: Keypair;
 
         let main_keypairs = cashier_wallet.get_main_keys(&NetworkName::Ethereum).await?;
 
-        if main_keypairs.is_empty() {
-            let main_private_key = generate_privkey();
-            let main_public_key =
-                self.import_privkey(&main_private_key).await?.as_str().unwrap().to_string();
+        if !keypair_path.is_empty() {
+            let private_key = load_keypair_to_str(expand_path(keypair_path)?)?;
+            main_keypair = Keypair::from_private_key(&private_key)?;
+        } else if main_keypairs.is_empty() {
+            main_keypair = Keypair::random();
 
             cashier_wallet
                 .put_main_keys(
                     &TokenKey {
-                        secret_key: serialize(&main_private_key),
-                        public_key: serialize(&main_public_key),
+                        secret_key: serialize(&main_keypair.private_key),
+                        public_key: serialize(&main_keypair.public_key),
                     },
                     &NetworkName::Ethereum,
                 )
                 .await?;
-
-            main_keypair = Keypair { private_key: main_private_key, public_key: main_public_key };
         } else {
             let last_keypair = &main_keypairs[main_keypairs.len() - 1];
 
@@ -253,59 +412,84 @@ impl EthClient {
             }
         }
 
+        info!(target: "ETH BRIDGE", "Main ETH account: {}", main_keypair.public_key);
         self.main_keypair = main_keypair;
 
+        self.chain_id = parse_eth_u64(&self.request("eth_chainId", json!([])).await?)?;
+        info!(target: "ETH BRIDGE", "Connected to chain {}", self.chain_id);
+
         Ok(())
     }
 
-    async fn send_eth_to_main_wallet(&self, acc: &str, amount: BigUint) -> Result<()> {
-        info!(target: "ETH BRIDGE", "Sending eth to main wallet");
+    /// Sign a transfer of `value` from the given account and broadcast it,
+    /// returning the transaction hash.
+    async fn transfer(&self, from: &Keypair, to: &str, value: BigUint) -> EthResult<Value> {
+        let nonce =
+            self.request("eth_getTransactionCount", json!([from.public_key, "pending"])).await?;
+        let tx = SignedEthTx {
+            nonce: parse_eth_u64(&nonce)?,
+            gas_price: self.gas_price().await?,
+            gas: ETH_TRANSFER_GAS,
+            to: parse_address(to)?,
+            value,
+            data: vec![],
+        };
 
-        let tx =
-            EthTx::new(acc, &self.main_keypair.public_key, None, None, Some(amount), None, None);
+        let raw = tx.sign(&from.secret_key()?, self.chain_id);
+        self.request("eth_sendRawTransaction", json!([format!("0x{}", hex::encode(raw))])).await
+    }
 
-        self.send_transaction(&tx, &self.passphrase).await?;
+    /// Move a confirmed deposit into the main account, minus the fee.
+    async fn sweep_to_main_wallet(&self, keypair: &Keypair, amount: BigUint) -> EthResult<()> {
+        let fee = self.gas_price().await? * ETH_TRANSFER_GAS;
+        if amount <= fee {
+            warn!(target: "ETH BRIDGE", "Deposit to {} too small to sweep", keypair.public_key);
+            return Ok(())
+        }
+
+        info!(target: "ETH BRIDGE", "Sweeping deposit of {} into main wallet", keypair.public_key);
+        let tx_hash = self.transfer(keypair, &self.main_keypair.public_key, amount - fee).await?;
+        debug!(target: "ETH BRIDGE", "Sweep transaction: {}", tx_hash);
 
         Ok(())
     }
 
     async fn handle_subscribe_request(
         self: Arc<Self>,
-        addr: String,
-        drk_pub_key: PublicKey,
+        keypair: Keypair,
+        deposits: async_channel::Sender<Deposit>,
     ) -> Result<()> {
-        if self.subscriptions.lock().await.contains(&addr) {
-            return Ok(())
+        let addr = keypair.public_key.clone();
+        {
+            let mut subscriptions = self.subscriptions.lock().await;
+            if subscriptions.contains(&addr) {
+                return Ok(())
+            }
+            subscriptions.push(addr.clone());
         }
 
-        let decimals = 18;
-
-        let prev_balance = self.get_current_balance(&addr, None).await?;
+        let prev_balance = self.get_confirmed_balance(&addr).await?;
 
         let mut current_balance;
 
-        let iter_interval = 1;
         let mut sub_iter = 0;
 
         loop {
-            if sub_iter > 60 * 10 {
-                // 10 minutes
+            if sub_iter > ETH_DEPOSIT_TIMEOUT {
                 self.unsubscribe(&addr).await;
                 return Err(EthFailed::Custom("Deposit for expired".to_string()).into())
             }
 
-            sub_iter += iter_interval;
-            sleep(iter_interval).await;
+            sub_iter += ETH_POLL_INTERVAL;
+            sleep(ETH_POLL_INTERVAL).await;
 
-            current_balance = self.get_current_balance(&addr, None).await?;
+            current_balance = self.get_confirmed_balance(&addr).await?;
 
             if current_balance != prev_balance {
                 break
             }
         }
 
-        let send_notification = self.notify_channel.0.clone();
-
         self.unsubscribe(&addr).await;
 
         if current_balance < prev_balance {
@@ -316,23 +500,26 @@ impl EthClient {
 
         let received_balance = current_balance - prev_balance;
 
-        let received_balance_ui = received_balance.clone() / u64::pow(10, decimals as u32);
+        let notified_balance = received_balance.clone() /
+            BigUint::from(10u64).pow((ETH_DECIMALS - ETH_NOTIFY_DECIMALS) as u32);
+        let notified_balance = match notified_balance.to_u64_digits().as_slice() {
+            [] => 0,
+            [v] => *v,
+            _ => return Err(EthFailed::Custom("Deposit overflows u64".to_string()).into()),
+        };
 
-        send_notification
-            .send(TokenNotification {
-                network: NetworkName::Ethereum,
+        deposits
+            .send(Deposit {
                 token_id: generate_id2(ETH_NATIVE_TOKEN_ID, &NetworkName::Ethereum)?,
-                drk_pub_key,
-                // TODO FIX
-                received_balance: received_balance.to_u64_digits()[0],
-                decimals: decimals as u16,
+                amount: notified_balance,
+                decimals: ETH_NOTIFY_DECIMALS,
             })
             .await
             .map_err(Error::from)?;
 
-        self.send_eth_to_main_wallet(&addr, received_balance).await?;
+        info!(target: "ETH BRIDGE", "Received {} wei on {}", received_balance, addr);
 
-        info!(target: "ETH BRIDGE", "Received {} eth", received_balance_ui );
+        self.sweep_to_main_wallet(&keypair, received_balance).await?;
 
         Ok(())
     }
@@ -346,141 +533,110 @@ impl EthClient {
         }
     }
 
-    async fn request(&self, r: jsonrpc::JsonRequest) -> EthResult<Value> {
-        debug!(target: "ETH RPC", "--> {}", serde_json::to_string(&r)?);
-        let url = Url::parse(&format!("unix://{}", self.socket_path)).map_err(Error::from)?;
-        let reply: JsonResult =
-            match jsonrpc::send_request(&url, json!(r), None).await.map_err(EthFailed::from) {
-                Ok(v) => v,
-                Err(e) => return Err(e),
-            };
+    /// Send a request to the web3 endpoint over a fresh websocket and wait
+    /// for its reply.
+    async fn request(&self, method: &str, params: Value) -> EthResult<Value> {
+        let req = JsonRequest::new(method, params);
+        debug!(target: "ETH RPC", "--> {}", serde_json::to_string(&req)?);
+
+        let tls = TlsConnector::new();
+        let (mut stream, _) = websockets::connect(self.web3_url.as_str(), tls).await?;
+        stream
+            .send(Message::text(serde_json::to_string(&req)?))
+            .await
+            .map_err(|e| EthFailed::RpcError(e.to_string()))?;
+
+        let reply = loop {
+            match stream.next().await {
+                Some(Ok(Message::Text(text))) => break text,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(EthFailed::RpcError(e.to_string())),
+                None => return Err(EthFailed::RpcError("Connection closed".to_string())),
+            }
+        };
+        let _ = stream.close().await;
 
-        match reply {
-            JsonResult::Resp(r) => {
+        match serde_json::from_str::<JsonResult>(&reply)? {
+            JsonResult::Response(r) => {
                 debug!(target: "ETH RPC", "<-- {}", serde_json::to_string(&r)?);
                 Ok(r.result)
             }
 
-            JsonResult::Err(e) => {
+            JsonResult::Error(e) => {
                 debug!(target: "ETH RPC", "<-- {}", serde_json::to_string(&e)?);
                 Err(EthFailed::RpcError(e.error.message.to_string()))
             }
 
-            JsonResult::Notif(n) => {
+            JsonResult::Notification(n) => {
                 debug!(target: "ETH RPC", "<-- {}", serde_json::to_string(&n)?);
                 Err(EthFailed::RpcError("Unexpected reply".to_string()))
             }
         }
     }
 
-    pub async fn import_privkey(&self, key: &str) -> EthResult<Value> {
-        let req = jsonrpc::request(json!("personal_importRawKey"), json!([key, self.passphrase]));
-        Ok(self.request(req).await?)
+    pub async fn block_number(&self) -> EthResult<u64> {
+        parse_eth_u64(&self.request("eth_blockNumber", json!([])).await?)
     }
 
-    /*
-    pub async fn estimate_gas(&self, tx: &EthTx) -> Result<Value> {
-    let req = jsonrpc::request(json!("eth_estimateGas"), json!([tx]));
-    Ok(self.request(req).await?)
+    pub async fn gas_price(&self) -> EthResult<BigUint> {
+        parse_eth_hex(&self.request("eth_gasPrice", json!([])).await?)
     }
-    */
 
-    pub async fn block_number(&self) -> EthResult<Value> {
-        let req = jsonrpc::request(json!("eth_blockNumber"), json!([]));
-        Ok(self.request(req).await?)
-    }
-
-    pub async fn get_eth_balance(&self, acc: &str, block: &str) -> EthResult<Value> {
-        let req = jsonrpc::request(json!("eth_getBalance"), json!([acc, block]));
-        Ok(self.request(req).await?)
+    pub async fn get_eth_balance(&self, acc: &str, block: &str) -> EthResult<BigUint> {
+        parse_eth_hex(&self.request("eth_getBalance", json!([acc, block])).await?)
     }
 
     pub async fn get_erc20_balance(&self, acc: &str, mint: &str) -> EthResult<Value> {
         let tx = EthTx::new(acc, mint, None, None, None, Some(erc20_balanceof_data(acc)), None);
-        let req = jsonrpc::request(json!("eth_call"), json!([tx, "latest"]));
-        Ok(self.request(req).await?)
-    }
-
-    pub async fn get_current_balance(&self, acc: &str, _mint: Option<&str>) -> EthResult<BigUint> {
-        // Latest known block, used to calculate present balance.
-        let block = self.block_number().await?;
-        let block = block.as_str().unwrap();
-
-        // Native ETH balance
-        let hexbalance = self.get_eth_balance(acc, block).await?;
-        let hexbalance = hexbalance.as_str().unwrap().trim_start_matches("0x");
-        let balance = BigUint::parse_bytes(hexbalance.as_bytes(), 16).unwrap();
-
-        Ok(balance)
+        self.request("eth_call", json!([tx, "latest"])).await
     }
 
-    pub async fn send_transaction(&self, tx: &EthTx, passphrase: &str) -> EthResult<Value> {
-        let req = jsonrpc::request(json!("personal_sendTransaction"), json!([tx, passphrase]));
-        Ok(self.request(req).await?)
+    /// Balance of the account as of the newest block with the configured
+    /// number of confirmations.
+    pub async fn get_confirmed_balance(&self, acc: &str) -> EthResult<BigUint> {
+        let tip = self.block_number().await?;
+        let block = (tip + 1).saturating_sub(self.confirmations as u64);
+        self.get_eth_balance(acc, &format!("0x{:x}", block)).await
     }
 }
 
 #[async_trait]
-impl NetworkClient for EthClient {
-    async fn subscribe(
-        self: Arc<Self>,
-        drk_pub_key: PublicKey,
-        _mint_address: Option<String>,
-        executor: Arc<Executor<'_>>,
-    ) -> Result<TokenSubscribtion> {
-        let private_key = generate_privkey();
-
-        let addr = self.import_privkey(&private_key).await?;
-
-        let address: String = if addr.as_str().is_some() {
-            addr.as_str().unwrap().to_string()
-        } else {
-            return Err(Error::from(EthFailed::ImportPrivateError))
-        };
-
-        let addr_cloned = address.clone();
-        executor
-            .spawn(async move {
-                let result = self.handle_subscribe_request(addr_cloned, drk_pub_key).await;
-                if let Err(e) = result {
-                    error!(target: "ETH BRIDGE SUBSCRIPTION","{}", e.to_string());
-                }
-            })
-            .detach();
-
-        let private_key: Vec<u8> = serialize(&private_key);
+impl ChainWatcher for EthClient {
+    fn confirmations(&self) -> u32 {
+        self.confirmations
+    }
 
-        Ok(TokenSubscribtion { private_key, public_key: address })
+    fn new_deposit_key(&self) -> Result<TokenSubscribtion> {
+        let keypair = Keypair::random();
+        Ok(TokenSubscribtion {
+            private_key: serialize(&keypair.private_key),
+            public_key: keypair.public_key,
+        })
     }
 
-    async fn subscribe_with_keypair(
+    async fn subscribe_deposits(
         self: Arc<Self>,
-        _private_key: Vec<u8>,
-        public_key: Vec<u8>,
-        drk_pub_key: PublicKey,
+        key: TokenSubscribtion,
         _mint_address: Option<String>,
         executor: Arc<Executor<'_>>,
-    ) -> Result<String> {
-        let public_key: String = deserialize(&public_key)?;
+    ) -> Result<async_channel::Receiver<Deposit>> {
+        let keypair =
+            Keypair { private_key: deserialize(&key.private_key)?, public_key: key.public_key };
+        let (deposits, receiver) = async_channel::bounded(1);
 
-        let address = public_key.clone();
         executor
             .spawn(async move {
-                let result = self.handle_subscribe_request(address, drk_pub_key).await;
+                let result = self.handle_subscribe_request(keypair, deposits).await;
                 if let Err(e) = result {
                     error!(target: "ETH BRIDGE SUBSCRIPTION","{}", e.to_string());
                 }
             })
             .detach();
 
-        Ok(public_key)
-    }
-
-    async fn get_notifier(self: Arc<Self>) -> Result<async_channel::Receiver<TokenNotification>> {
-        Ok(self.notify_channel.1.clone())
+        Ok(receiver)
     }
 
-    async fn send(
+    async fn send_withdrawal(
         self: Arc<Self>,
         address: Vec<u8>,
         _mint: Option<String>,
@@ -489,22 +645,19 @@ impl NetworkClient for EthClient {
         // Recipient address
         let dest: String = deserialize(&address)?;
 
-        let decimals = 18;
-
         // reverse truncate
-        let amount = truncate(amount, decimals as u16, 8)?;
+        let amount = BigUint::from(truncate(amount, ETH_DECIMALS, 8)?);
+
+        let _guard = self.send_lock.lock().await;
 
-        let tx = EthTx::new(
-            &self.main_keypair.public_key,
-            &dest,
-            None,
-            None,
-            Some(BigUint::from(amount)),
-            None,
-            None,
-        );
+        let fee = self.gas_price().await? * ETH_TRANSFER_GAS;
+        let balance = self.get_eth_balance(&self.main_keypair.public_key, "latest").await?;
+        if balance < amount.clone() + fee {
+            return Err(EthFailed::MainAccountNotEnoughValue.into())
+        }
 
-        self.send_transaction(&tx, &self.passphrase).await?;
+        let tx_hash = self.transfer(&self.main_keypair, &dest, amount).await?;
+        info!(target: "ETH BRIDGE", "Withdrawal to {} sent: {}", dest, tx_hash);
 
         Ok(())
     }
@@ -568,4 +721,20 @@ mod tests {
 
         assert_eq!(erc20_transfer_data(recipient, amnt), "0xa9059cbb0000000000000000000000005b7b3b499fb69c40c365343cb0dc842fe8c23887000000000000000000000000000000000000000000000001e27786570c272000");
     }
+
+    #[test]
+    fn test_signed_tx() {
+        // Example transaction of EIP-155
+        let tx = SignedEthTx {
+            nonce: 9,
+            gas_price: BigUint::from(20_000_000_000u64),
+            gas: ETH_TRANSFER_GAS,
+            to: parse_address("0x3535353535353535353535353535353535353535").unwrap(),
+            value: BigUint::from(1_000_000_000_000_000_000u64),
+            data: vec![],
+        };
+        let keypair = Keypair::from_private_key(&"46".repeat(32)).unwrap();
+
+        assert_eq!(hex::encode(tx.sign(&keypair.secret_key().unwrap(), 1)), "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83");
+    }
 }
@@ -14,3 +14,8 @@ pub use sol::{SolClient, SolFailed, SolResult};
 pub mod eth;
 #[cfg(feature = "eth")]
 pub use eth::{EthClient, EthFailed, EthResult};
+
+#[cfg(feature = "xmr")]
+pub mod xmr;
+#[cfg(feature = "xmr")]
+pub use xmr::XmrClient;
@@ -21,10 +21,10 @@ use solana_sdk::{
 use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
 use tungstenite::Message;
 
-use super::bridge::{NetworkClient, TokenNotification, TokenSubscribtion};
+use super::bridge::{ChainWatcher, Deposit, TokenSubscribtion};
 
 use darkfi::{
-    crypto::{keypair::PublicKey, token_id::generate_id2},
+    crypto::token_id::generate_id2,
     rpc::{jsonrpc, jsonrpc::JsonResult, websockets, websockets::WsStream},
     util::{
         expand_path, load_keypair_to_str,
@@ -38,6 +38,9 @@ use darkfi::{
 
 pub const SOL_NATIVE_TOKEN_ID: &str = "So11111111111111111111111111111111111111112";
 
+/// Deposits are only notified once finalized, which takes 32 confirmed blocks
+const SOL_CONFIRMATIONS: u32 = 32;
+
 struct SolKeypair(Keypair);
 struct SolPubkey(Pubkey);
 
@@ -51,8 +54,6 @@ pub struct SolClient {
     main_keypair: Keypair,
     // Subscriptions vector of pubkey
     subscriptions: Arc<Mutex<Vec<Pubkey>>>,
-    notify_channel:
-        (async_channel::Sender<TokenNotification>, async_channel::Receiver<TokenNotification>),
     rpc_server: &'static str,
     wss_server: &'static str,
 }
@@ -63,8 +64,6 @@ impl SolClient {
         network: &str,
         keypair_path: &str,
     ) -> Result<Arc<Self>> {
-        let notify_channel = async_channel::unbounded();
-
         let main_keypair: SolKeypair;
 
         let main_keypairs = cashier_wallet.get_main_keys(&NetworkName::Solana).await?;
@@ -108,7 +107,6 @@ impl SolClient {
         Ok(Arc::new(Self {
             main_keypair: main_keypair.0,
             subscriptions: Arc::new(Mutex::new(Vec::new())),
-            notify_channel,
             rpc_server,
             wss_server,
         }))
@@ -128,7 +126,7 @@ impl SolClient {
     async fn handle_subscribe_request(
         self: Arc<Self>,
         keypair: Keypair,
-        drk_pub_key: PublicKey,
+        deposits: async_channel::Sender<Deposit>,
         mint: Option<Pubkey>,
     ) -> SolResult<()> {
         trace!(target: "SOL BRIDGE", "handle_subscribe_request()");
@@ -256,8 +254,6 @@ impl SolClient {
             }
         }
 
-        let send_notification = self.notify_channel.0.clone();
-
         let self2 = self.clone();
         self2.unsubscribe(&mut write, &pubkey, &sub_id).await?;
 
@@ -270,12 +266,10 @@ impl SolClient {
         if mint.is_some() {
             let ui_amnt = amnt / u64::pow(10, decimals as u32);
 
-            send_notification
-                .send(TokenNotification {
-                    network: NetworkName::Solana,
+            deposits
+                .send(Deposit {
                     token_id: generate_id2(&mint.unwrap().to_string(), &NetworkName::Solana)?,
-                    drk_pub_key,
-                    received_balance: amnt,
+                    amount: amnt,
                     decimals: decimals as u16,
                 })
                 .await
@@ -286,12 +280,10 @@ impl SolClient {
         } else {
             let ui_amnt = lamports_to_sol(amnt);
 
-            send_notification
-                .send(TokenNotification {
-                    network: NetworkName::Solana,
+            deposits
+                .send(Deposit {
                     token_id: generate_id2(SOL_NATIVE_TOKEN_ID, &NetworkName::Solana)?,
-                    drk_pub_key,
-                    received_balance: amnt,
+                    amount: amnt,
                     decimals: decimals as u16,
                 })
                 .await
@@ -435,77 +427,50 @@ impl SolClient {
 }
 
 #[async_trait]
-impl NetworkClient for SolClient {
-    async fn subscribe(
-        self: Arc<Self>,
-        drk_pub_key: PublicKey,
-        mint_address: Option<String>,
-        executor: Arc<Executor<'_>>,
-    ) -> Result<TokenSubscribtion> {
-        let keypair = SolKeypair(Keypair::new());
+impl ChainWatcher for SolClient {
+    fn confirmations(&self) -> u32 {
+        SOL_CONFIRMATIONS
+    }
 
+    fn new_deposit_key(&self) -> Result<TokenSubscribtion> {
+        let keypair = SolKeypair(Keypair::new());
         let public_key = keypair.0.pubkey().to_string();
-        let private_key = serialize(&keypair);
-
-        let mint = self.check_mint_address(mint_address)?;
-
-        let rpc = RpcClient::new(self.rpc_server.to_string());
-
-        if !self.check_main_account_balance(&rpc)? {
-            warn!(target: "SOL BRIDGE", "Main account has no enough funds");
-            return Err(Error::from(SolFailed::MainAccountNotEnoughValue))
-        }
-
-        executor
-            .spawn(async move {
-                let result = self.handle_subscribe_request(keypair.0, drk_pub_key, mint).await;
-                if let Err(e) = result {
-                    error!(target: "SOL BRIDGE SUBSCRIPTION","{}", e.to_string());
-                }
-            })
-            .detach();
-
-        Ok(TokenSubscribtion { private_key, public_key })
+        Ok(TokenSubscribtion { private_key: serialize(&keypair), public_key })
     }
 
     // in solana case private key it's the same as keypair
-    async fn subscribe_with_keypair(
+    async fn subscribe_deposits(
         self: Arc<Self>,
-        private_key: Vec<u8>,
-        _public_key: Vec<u8>,
-        drk_pub_key: PublicKey,
+        key: TokenSubscribtion,
         mint_address: Option<String>,
         executor: Arc<Executor<'_>>,
-    ) -> Result<String> {
-        let keypair: Keypair = deserialize::<SolKeypair>(&private_key)?.0;
-
-        let public_key = keypair.pubkey().to_string();
+    ) -> Result<async_channel::Receiver<Deposit>> {
+        let keypair: Keypair = deserialize::<SolKeypair>(&key.private_key)?.0;
 
         let mint = self.check_mint_address(mint_address)?;
 
         let rpc = RpcClient::new(self.rpc_server.to_string());
 
         if !self.check_main_account_balance(&rpc)? {
+            warn!(target: "SOL BRIDGE", "Main account has no enough funds");
             return Err(Error::from(SolFailed::MainAccountNotEnoughValue))
         }
 
+        let (deposits, receiver) = async_channel::bounded(1);
+
         executor
             .spawn(async move {
-                let result = self.handle_subscribe_request(keypair, drk_pub_key, mint).await;
+                let result = self.handle_subscribe_request(keypair, deposits, mint).await;
                 if let Err(e) = result {
                     error!(target: "SOL BRIDGE SUBSCRIPTION","{}", e.to_string());
                 }
             })
             .detach();
 
-        Ok(public_key)
-    }
-
-    async fn get_notifier(self: Arc<Self>) -> Result<async_channel::Receiver<TokenNotification>> {
-        Ok(self.notify_channel.1.clone())
+        Ok(receiver)
     }
 
-    async fn send(
+    async fn send_withdrawal(
         self: Arc<Self>,
         address: Vec<u8>,
         mint: Option<String>,
@@ -0,0 +1,78 @@
+use async_executor::Executor;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use log::info;
+use url::Url;
+
+use super::bridge::{ChainWatcher, Deposit, TokenSubscribtion};
+use darkfi::{Error, Result};
+
+/// Monero reaches finality later than Bitcoin, outputs are also locked
+/// for 10 blocks before they can be spent.
+pub const XMR_CONFIRMATIONS: u32 = 10;
+
+/// Monero bridge, driving a `monero-wallet-rpc` instance holding the main
+/// wallet.
+///
+/// Unlike the other networks, deposits don't get their own keypairs: each
+/// one is given a subaddress of the main wallet (`create_address`), and
+/// incoming transfers are matched to it by subaddress index
+/// (`get_transfers`), so there is no sweep to the main wallet either.
+/// Withdrawals are a plain `transfer` from the main account.
+pub struct XmrClient {
+    wallet_rpc: Url,
+}
+
+impl XmrClient {
+    pub fn new(network: &str) -> Result<Arc<Self>> {
+        // Default ports of monero-wallet-rpc for each network
+        let port = match network {
+            "mainnet" => 18082,
+            "stagenet" => 38082,
+            "testnet" => 28082,
+            _ => return Err(Error::UnsupportedCoinNetwork),
+        };
+
+        let wallet_rpc = Url::parse(&format!("http://127.0.0.1:{}/json_rpc", port))?;
+        info!(target: "XMR BRIDGE", "Monero wallet RPC: {}", wallet_rpc);
+
+        Ok(Arc::new(Self { wallet_rpc }))
+    }
+
+    fn not_implemented(&self, method: &str) -> Error {
+        Error::CashierError(format!(
+            "Monero {} over {} is not implemented",
+            method, self.wallet_rpc
+        ))
+    }
+}
+
+#[async_trait]
+impl ChainWatcher for XmrClient {
+    fn confirmations(&self) -> u32 {
+        XMR_CONFIRMATIONS
+    }
+
+    fn new_deposit_key(&self) -> Result<TokenSubscribtion> {
+        // TODO: The private key would be the subaddress index
+        Err(self.not_implemented("create_address"))
+    }
+
+    async fn subscribe_deposits(
+        self: Arc<Self>,
+        _key: TokenSubscribtion,
+        _mint: Option<String>,
+        _executor: Arc<Executor<'_>>,
+    ) -> Result<async_channel::Receiver<Deposit>> {
+        Err(self.not_implemented("get_transfers"))
+    }
+
+    async fn send_withdrawal(
+        self: Arc<Self>,
+        _address: Vec<u8>,
+        _mint: Option<String>,
+        _amount: u64,
+    ) -> Result<()> {
+        Err(self.not_implemented("transfer"))
+    }
+}
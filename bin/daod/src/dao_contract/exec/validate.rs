@@ -14,7 +14,7 @@ use std::any::{Any, TypeId};
 
 use crate::{
     dao_contract,
-    dao_contract::HashableBase,
+    dao_contract::{HashableBase, MAX_PROPOSAL_ASSETS},
     demo::{CallDataBase, StateRegistry, Transaction, UpdateBase},
     money_contract,
 };
@@ -49,6 +49,18 @@ pub enum Error {
 
     #[error("InvalidVoteCommit")]
     InvalidVoteCommit,
+
+    #[error("InvalidNumberOfAssets")]
+    InvalidNumberOfAssets,
+
+    #[error("VotingNotEnded")]
+    VotingNotEnded,
+
+    #[error("InvalidSpendHook")]
+    InvalidSpendHook,
+
+    #[error("TransferNotApplied")]
+    TransferNotApplied,
 }
 
 impl From<DarkFiError> for Error {
@@ -57,41 +69,65 @@ impl From<DarkFiError> for Error {
     }
 }
 
+/// Treasury payout of one of the proposal's assets
 #[derive(Clone, SerialEncodable, SerialDecodable)]
-pub struct CallData {
-    pub proposal: pallas::Base,
+pub struct ExecAsset {
     pub coin_0: pallas::Base,
     pub coin_1: pallas::Base,
-    pub win_votes_commit: pallas::Point,
-    pub total_votes_commit: pallas::Point,
     pub input_value_commit: pallas::Point,
 }
 
+#[derive(Clone, SerialEncodable, SerialDecodable)]
+pub struct CallData {
+    pub proposal: pallas::Base,
+    /// Last slot of the proposal's voting window, committed to in its bulla
+    pub end_slot: u64,
+    /// One payout for each of the proposal's assets
+    pub assets: Vec<ExecAsset>,
+    /// Commit to the tally of each option
+    pub option_votes_commits: Vec<pallas::Point>,
+    pub total_votes_commit: pallas::Point,
+}
+
 impl CallDataBase for CallData {
     fn zk_public_values(&self) -> Vec<(String, Vec<DrkCircuitField>)> {
-        let win_votes_coords = self.win_votes_commit.to_affine().coordinates().unwrap();
+        let mut zk_publics = vec![self.proposal, pallas::Base::from(self.end_slot)];
+
+        // Used asset slots come first, the empty ones only reveal zeros
+        for i in 0..MAX_PROPOSAL_ASSETS {
+            match self.assets.get(i) {
+                Some(asset) => {
+                    let input_value_coords =
+                        asset.input_value_commit.to_affine().coordinates().unwrap();
+                    zk_publics.extend([
+                        pallas::Base::from(1),
+                        asset.coin_0,
+                        asset.coin_1,
+                        *input_value_coords.x(),
+                        *input_value_coords.y(),
+                    ]);
+                }
+                None => zk_publics.extend([pallas::Base::from(0); 5]),
+            }
+        }
+
+        for option_votes_commit in &self.option_votes_commits {
+            let option_votes_coords = option_votes_commit.to_affine().coordinates().unwrap();
+            zk_publics.push(*option_votes_coords.x());
+            zk_publics.push(*option_votes_coords.y());
+        }
 
         let total_votes_coords = self.total_votes_commit.to_affine().coordinates().unwrap();
 
-        let input_value_coords = self.input_value_commit.to_affine().coordinates().unwrap();
-
-        vec![(
-            "dao-exec".to_string(),
-            vec![
-                self.proposal,
-                self.coin_0,
-                self.coin_1,
-                *win_votes_coords.x(),
-                *win_votes_coords.y(),
-                *total_votes_coords.x(),
-                *total_votes_coords.y(),
-                *input_value_coords.x(),
-                *input_value_coords.y(),
-                *super::FUNC_ID,
-                pallas::Base::from(0),
-                pallas::Base::from(0),
-            ],
-        )]
+        zk_publics.extend([
+            *total_votes_coords.x(),
+            *total_votes_coords.y(),
+            *super::FUNC_ID,
+            pallas::Base::from(0),
+            pallas::Base::from(0),
+        ]);
+
+        vec![("dao-exec".to_string(), zk_publics)]
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -117,52 +153,79 @@ pub fn state_transition(
     // This will be inside wasm so unwrap is fine.
     let call_data = call_data.unwrap();
 
-    // Enforce tx has correct format:
-    // 1. There should only be 2 func_call's
-    if parent_tx.func_calls.len() != 2 {
-        return Err(Error::InvalidNumberOfFuncCalls)
+    // The proposal can only be executed once voting has ended. The proof
+    // checks it met quorum, so proposals which expired without meeting it
+    // can never be executed.
+    if states.current_slot <= call_data.end_slot {
+        return Err(Error::VotingNotEnded)
     }
 
-    // 2. func_call_index == 1
-    if func_call_index != 1 {
-        return Err(Error::InvalidIndex)
-    }
-
-    // 3. First item should be a Money::transfer() calldata
-    if parent_tx.func_calls[0].func_id != "Money::transfer()" {
-        return Err(Error::InvalidCallData)
+    // Enforce tx has correct format:
+    // 1. There should be one Money::transfer() func_call per asset, then ours
+    let num_assets = call_data.assets.len();
+    if num_assets == 0 || num_assets > MAX_PROPOSAL_ASSETS {
+        return Err(Error::InvalidNumberOfAssets)
     }
-
-    let money_transfer_call_data = parent_tx.func_calls[0].call_data.as_any();
-    let money_transfer_call_data =
-        money_transfer_call_data.downcast_ref::<money_contract::transfer::validate::CallData>();
-    let money_transfer_call_data = money_transfer_call_data.unwrap();
-    assert_eq!(
-        money_transfer_call_data.type_id(),
-        TypeId::of::<money_contract::transfer::validate::CallData>()
-    );
-
-    // 4. Money::transfer() has exactly 2 outputs
-    if money_transfer_call_data.outputs.len() != 2 {
-        return Err(Error::InvalidNumberOfOutputs)
+    if parent_tx.func_calls.len() != num_assets + 1 {
+        return Err(Error::InvalidNumberOfFuncCalls)
     }
 
-    // Checks:
-    // 1. Check both coins in Money::transfer() are equal to our coin_0, coin_1
-    if money_transfer_call_data.outputs[0].revealed.coin != Coin(call_data.coin_0) {
-        return Err(Error::InvalidOutput)
-    }
-    if money_transfer_call_data.outputs[1].revealed.coin != Coin(call_data.coin_1) {
-        return Err(Error::InvalidOutput)
+    // 2. func_call_index is the last one
+    if func_call_index != num_assets {
+        return Err(Error::InvalidIndex)
     }
 
-    // 2. sum of Money::transfer() calldata input_value_commits == our input value commit
-    let mut input_value_commits = pallas::Point::identity();
-    for input in &money_transfer_call_data.inputs {
-        input_value_commits += input.revealed.value_commit;
-    }
-    if input_value_commits != call_data.input_value_commit {
-        return Err(Error::InvalidValueCommit)
+    for (transfer_func_call, asset) in parent_tx.func_calls.iter().zip(&call_data.assets) {
+        // 3. Items before ours should be Money::transfer() calldata
+        if transfer_func_call.func_id != *money_contract::transfer::FUNC_ID {
+            return Err(Error::InvalidCallData)
+        }
+
+        let money_transfer_call_data = transfer_func_call.call_data.as_any();
+        let money_transfer_call_data =
+            money_transfer_call_data.downcast_ref::<money_contract::transfer::validate::CallData>();
+        let money_transfer_call_data = money_transfer_call_data.unwrap();
+        assert_eq!(
+            money_transfer_call_data.type_id(),
+            TypeId::of::<money_contract::transfer::validate::CallData>()
+        );
+
+        // 4. Money::transfer() has exactly 2 outputs
+        if money_transfer_call_data.outputs.len() != 2 {
+            return Err(Error::InvalidNumberOfOutputs)
+        }
+
+        // Checks:
+        // 1. Check both coins in Money::transfer() are equal to the asset's coin_0, coin_1
+        if money_transfer_call_data.outputs[0].revealed.coin != Coin(asset.coin_0) {
+            return Err(Error::InvalidOutput)
+        }
+        if money_transfer_call_data.outputs[1].revealed.coin != Coin(asset.coin_1) {
+            return Err(Error::InvalidOutput)
+        }
+
+        // 2. sum of Money::transfer() calldata input_value_commits == the asset's input value commit
+        let mut input_value_commits = pallas::Point::identity();
+        for input in &money_transfer_call_data.inputs {
+            input_value_commits += input.revealed.value_commit;
+        }
+        if input_value_commits != asset.input_value_commit {
+            return Err(Error::InvalidValueCommit)
+        }
+
+        // 3. The value comes from coins only we can spend, and the transfer
+        // spending them was applied before our call
+        let money_state = states
+            .lookup::<money_contract::State>(&"Money".to_string())
+            .expect("Return type is not of type State");
+        for input in &money_transfer_call_data.inputs {
+            if input.revealed.spend_hook != *super::FUNC_ID {
+                return Err(Error::InvalidSpendHook)
+            }
+            if !money_state.nullifier_exists(&input.revealed.nullifier) {
+                return Err(Error::TransferNotApplied)
+            }
+        }
     }
 
     // 3. get the ProposalVote from DAO::State
@@ -171,8 +234,8 @@ pub fn state_transition(
         .expect("Return type is not of type State");
     let proposal_votes = state.proposal_votes.get(&HashableBase(call_data.proposal)).unwrap();
 
-    // 4. check win/total_vote_commit is the same as in ProposalVote
-    if proposal_votes.vote_commits != call_data.win_votes_commit {
+    // 4. check the tally of each option is the same as in ProposalVote
+    if proposal_votes.vote_commits != call_data.option_votes_commits {
         return Err(Error::InvalidVoteCommit)
     }
     // 5. also check total_vote_commit
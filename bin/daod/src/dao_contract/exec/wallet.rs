@@ -15,25 +15,33 @@ use darkfi::{
 
 use crate::{
     dao_contract::{
-        exec::validate::CallData,
+        exec::validate::{CallData, ExecAsset},
         propose::wallet::{DaoParams, Proposal},
+        MAX_PROPOSAL_ASSETS, MAX_VOTE_OPTIONS,
     },
     demo::{FuncCall, ZkContractInfo, ZkContractTable},
 };
 
+/// The treasury coin spent to pay out one of the proposal's assets,
+/// and the change sent back to the DAO.
+pub struct BuilderAssetInput {
+    pub dao_serial: pallas::Base,
+    pub dao_coin_blind: pallas::Base,
+    pub input_value: u64,
+    pub input_value_blind: pallas::Scalar,
+}
+
 pub struct Builder {
     pub proposal: Proposal,
     pub dao: DaoParams,
-    pub win_votes: u64,
-    pub total_votes: u64,
-    pub win_votes_blind: pallas::Scalar,
+    /// Tally of each option, [`MAX_VOTE_OPTIONS`] of them
+    pub option_votes: Vec<u64>,
+    pub option_votes_blinds: Vec<pallas::Scalar>,
     pub total_votes_blind: pallas::Scalar,
     pub user_serial: pallas::Base,
     pub user_coin_blind: pallas::Base,
-    pub dao_serial: pallas::Base,
-    pub dao_coin_blind: pallas::Base,
-    pub input_value: u64,
-    pub input_value_blind: pallas::Scalar,
+    /// One input for each of the proposal's assets, in the same order
+    pub assets: Vec<BuilderAssetInput>,
     pub hook_dao_exec: pallas::Base,
     pub signature_secret: SecretKey,
 }
@@ -41,12 +49,14 @@ pub struct Builder {
 impl Builder {
     pub fn build(self, zk_bins: &ZkContractTable) -> FuncCall {
         debug!(target: "dao_contract::exec::wallet::Builder", "build()");
-        debug!(target: "dao_contract::exec::wallet", "proposalserial{:?}", self.proposal.serial);
         let mut proofs = vec![];
 
         let proposal_dest_coords = self.proposal.dest.0.to_affine().coordinates().unwrap();
 
-        let proposal_amount = pallas::Base::from(self.proposal.amount);
+        let proposal_assets = self.proposal.assets_hash();
+        let proposal_num_options = pallas::Base::from(self.proposal.num_options);
+        let proposal_start_slot = pallas::Base::from(self.proposal.start_slot);
+        let proposal_end_slot = pallas::Base::from(self.proposal.end_slot);
 
         let dao_proposer_limit = pallas::Base::from(self.dao.proposer_limit);
         let dao_quorum = pallas::Base::from(self.dao.quorum);
@@ -56,8 +66,6 @@ impl Builder {
 
         let user_spend_hook = pallas::Base::from(0);
         let user_data = pallas::Base::from(0);
-        let input_value = pallas::Base::from(self.input_value);
-        let change = input_value - proposal_amount;
 
         let dao_bulla = poseidon_hash::<8>([
             dao_proposer_limit,
@@ -74,45 +82,100 @@ impl Builder {
         let proposal_bulla = poseidon_hash::<8>([
             *proposal_dest_coords.x(),
             *proposal_dest_coords.y(),
-            proposal_amount,
-            self.proposal.serial,
-            self.proposal.token_id,
+            proposal_assets,
             dao_bulla,
-            self.proposal.blind,
-            // @tmp-workaround
+            proposal_num_options,
+            proposal_start_slot,
+            proposal_end_slot,
             self.proposal.blind,
         ]);
 
-        let coin_0 = poseidon_hash::<8>([
-            *proposal_dest_coords.x(),
-            *proposal_dest_coords.y(),
-            proposal_amount,
-            self.proposal.token_id,
-            self.proposal.serial,
-            user_spend_hook,
-            user_data,
-            self.proposal.blind,
-        ]);
+        // Each asset pays out coin_0 to the proposal destination, and
+        // sends the change of the treasury input back to the DAO as coin_1.
+        assert_eq!(self.assets.len(), self.proposal.assets.len());
+        let zero = pallas::Base::from(0);
+        let mut asset_witnesses = vec![];
+        let mut asset_publics = vec![];
+        let mut exec_assets = vec![];
+        for i in 0..MAX_PROPOSAL_ASSETS {
+            let (asset, input) = match (self.proposal.assets.get(i), self.assets.get(i)) {
+                (Some(asset), Some(input)) => (asset, input),
+                _ => {
+                    // Empty asset slots only reveal zeros
+                    asset_witnesses.extend([
+                        Witness::Base(Value::known(zero)),
+                        Witness::Base(Value::known(zero)),
+                        Witness::Base(Value::known(zero)),
+                        Witness::Base(Value::known(zero)),
+                        Witness::Scalar(Value::known(pallas::Scalar::from(0))),
+                    ]);
+                    asset_publics.extend([zero; 5]);
+                    continue
+                }
+            };
 
-        let coin_1 = poseidon_hash::<8>([
-            *dao_pubkey_coords.x(),
-            *dao_pubkey_coords.y(),
-            change,
-            self.proposal.token_id,
-            self.dao_serial,
-            self.hook_dao_exec,
-            proposal_bulla,
-            self.dao_coin_blind,
-        ]);
+            let amount = pallas::Base::from(asset.amount);
+            let input_value = pallas::Base::from(input.input_value);
+            let change = input_value - amount;
 
-        let win_votes_commit = pedersen_commitment_u64(self.win_votes, self.win_votes_blind);
-        let win_votes_coords = win_votes_commit.to_affine().coordinates().unwrap();
+            let coin_0 = poseidon_hash::<9>([
+                *proposal_dest_coords.x(),
+                *proposal_dest_coords.y(),
+                amount,
+                asset.token_id,
+                asset.serial,
+                user_spend_hook,
+                user_data,
+                pallas::Base::from(0),
+                self.proposal.blind,
+            ]);
 
-        let total_votes_commit = pedersen_commitment_u64(self.total_votes, self.total_votes_blind);
-        let total_votes_coords = total_votes_commit.to_affine().coordinates().unwrap();
+            let coin_1 = poseidon_hash::<9>([
+                *dao_pubkey_coords.x(),
+                *dao_pubkey_coords.y(),
+                change,
+                asset.token_id,
+                input.dao_serial,
+                self.hook_dao_exec,
+                proposal_bulla,
+                pallas::Base::from(0),
+                input.dao_coin_blind,
+            ]);
+
+            let input_value_commit =
+                pedersen_commitment_u64(input.input_value, input.input_value_blind);
+            let input_value_coords = input_value_commit.to_affine().coordinates().unwrap();
+
+            asset_witnesses.extend([
+                Witness::Base(Value::known(pallas::Base::from(1))),
+                Witness::Base(Value::known(input.dao_serial)),
+                Witness::Base(Value::known(input.dao_coin_blind)),
+                Witness::Base(Value::known(input_value)),
+                Witness::Scalar(Value::known(input.input_value_blind)),
+            ]);
+            asset_publics.extend([
+                pallas::Base::from(1),
+                coin_0,
+                coin_1,
+                *input_value_coords.x(),
+                *input_value_coords.y(),
+            ]);
+            exec_assets.push(ExecAsset { coin_0, coin_1, input_value_commit });
+        }
+
+        assert_eq!(self.option_votes.len(), MAX_VOTE_OPTIONS);
+        assert_eq!(self.option_votes_blinds.len(), MAX_VOTE_OPTIONS);
 
-        let input_value_commit = pedersen_commitment_u64(self.input_value, self.input_value_blind);
-        let input_value_coords = input_value_commit.to_affine().coordinates().unwrap();
+        let option_votes_commits: Vec<pallas::Point> = self
+            .option_votes
+            .iter()
+            .zip(&self.option_votes_blinds)
+            .map(|(votes, blind)| pedersen_commitment_u64(*votes, *blind))
+            .collect();
+
+        let total_votes = self.option_votes.iter().sum();
+        let total_votes_commit = pedersen_commitment_u64(total_votes, self.total_votes_blind);
+        let total_votes_coords = total_votes_commit.to_affine().coordinates().unwrap();
 
         let zk_info = zk_bins.lookup(&"dao-exec".to_string()).unwrap();
         let zk_info = if let ZkContractInfo::Binary(info) = zk_info {
@@ -123,13 +186,16 @@ impl Builder {
 
         let zk_bin = zk_info.bincode.clone();
 
-        let prover_witnesses = vec![
+        let mut prover_witnesses = vec![
             // proposal params
             Witness::Base(Value::known(*proposal_dest_coords.x())),
             Witness::Base(Value::known(*proposal_dest_coords.y())),
-            Witness::Base(Value::known(proposal_amount)),
-            Witness::Base(Value::known(self.proposal.serial)),
-            Witness::Base(Value::known(self.proposal.token_id)),
+        ];
+        prover_witnesses.extend(self.proposal.asset_witnesses());
+        prover_witnesses.extend([
+            Witness::Base(Value::known(proposal_num_options)),
+            Witness::Base(Value::known(proposal_start_slot)),
+            Witness::Base(Value::known(proposal_end_slot)),
             Witness::Base(Value::known(self.proposal.blind)),
             // DAO params
             Witness::Base(Value::known(dao_proposer_limit)),
@@ -139,38 +205,42 @@ impl Builder {
             Witness::Base(Value::known(*dao_pubkey_coords.x())),
             Witness::Base(Value::known(*dao_pubkey_coords.y())),
             Witness::Base(Value::known(self.dao.bulla_blind)),
-            // votes
-            Witness::Base(Value::known(pallas::Base::from(self.win_votes))),
-            Witness::Base(Value::known(pallas::Base::from(self.total_votes))),
-            Witness::Scalar(Value::known(self.win_votes_blind)),
+        ]);
+        // votes
+        for votes in &self.option_votes {
+            prover_witnesses.push(Witness::Base(Value::known(pallas::Base::from(*votes))));
+        }
+        for blind in &self.option_votes_blinds {
+            prover_witnesses.push(Witness::Scalar(Value::known(*blind)));
+        }
+        prover_witnesses.extend([
             Witness::Scalar(Value::known(self.total_votes_blind)),
             // outputs + inputs
             Witness::Base(Value::known(self.user_serial)),
             Witness::Base(Value::known(self.user_coin_blind)),
-            Witness::Base(Value::known(self.dao_serial)),
-            Witness::Base(Value::known(self.dao_coin_blind)),
-            Witness::Base(Value::known(input_value)),
-            Witness::Scalar(Value::known(self.input_value_blind)),
+        ]);
+        prover_witnesses.extend(asset_witnesses);
+        prover_witnesses.extend([
             // misc
             Witness::Base(Value::known(self.hook_dao_exec)),
             Witness::Base(Value::known(user_spend_hook)),
             Witness::Base(Value::known(user_data)),
-        ];
+        ]);
 
-        let public_inputs = vec![
-            proposal_bulla,
-            coin_0,
-            coin_1,
-            *win_votes_coords.x(),
-            *win_votes_coords.y(),
+        let mut public_inputs = vec![proposal_bulla, proposal_end_slot];
+        public_inputs.extend(asset_publics);
+        for option_votes_commit in &option_votes_commits {
+            let option_votes_coords = option_votes_commit.to_affine().coordinates().unwrap();
+            public_inputs.push(*option_votes_coords.x());
+            public_inputs.push(*option_votes_coords.y());
+        }
+        public_inputs.extend([
             *total_votes_coords.x(),
             *total_votes_coords.y(),
-            *input_value_coords.x(),
-            *input_value_coords.y(),
             self.hook_dao_exec,
             user_spend_hook,
             user_data,
-        ];
+        ]);
 
         let circuit = ZkCircuit::new(prover_witnesses, zk_bin);
         debug!(target: "example_contract::foo::wallet::Builder", "input_proof Proof::create()");
@@ -181,16 +251,15 @@ impl Builder {
 
         let call_data = CallData {
             proposal: proposal_bulla,
-            coin_0,
-            coin_1,
-            win_votes_commit,
+            end_slot: self.proposal.end_slot,
+            assets: exec_assets,
+            option_votes_commits,
             total_votes_commit,
-            input_value_commit,
         };
 
         FuncCall {
             contract_id: "DAO".to_string(),
-            func_id: "DAO::exec()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs,
         }
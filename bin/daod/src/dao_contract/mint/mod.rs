@@ -1,3 +1,8 @@
+use once_cell::sync::Lazy;
+use pasta_curves::pallas;
+
+pub static FUNC_ID: Lazy<pallas::Base> = Lazy::new(|| pallas::Base::from(107));
+
 pub mod validate;
 /// This is an anonymous contract function that mutates the internal DAO state.
 ///
@@ -97,7 +97,7 @@ impl Builder {
         let call_data = CallData { dao_bulla };
         FuncCall {
             contract_id: "DAO".to_string(),
-            func_id: "DAO::mint()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs: vec![mint_proof],
         }
@@ -9,4 +9,17 @@ pub mod exec;
 
 pub mod state;
 
+/// Maximum number of options a proposal can be voted on with. The vote
+/// and exec circuits commit to a tally for each of them, and the tallies
+/// of options past the proposal's `num_options` stay at zero.
+///
+/// This is a hard limit of the circuits, not a parameter: the propose
+/// circuit rejects proposals with more options, and the wallet refuses
+/// to build them. Raising it means adding tallies to the circuits.
+pub const MAX_VOTE_OPTIONS: usize = 4;
+
+/// Maximum number of treasury assets a proposal can send. Each asset is
+/// paid out by its own `Money::transfer()` call in the exec transaction.
+pub const MAX_PROPOSAL_ASSETS: usize = 2;
+
 pub use state::{DaoBulla, HashableBase, State};
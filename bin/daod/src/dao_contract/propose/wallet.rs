@@ -20,7 +20,10 @@ use darkfi::{
 };
 
 use crate::{
-    dao_contract::propose::validate::{CallData, Header, Input},
+    dao_contract::{
+        propose::validate::{CallData, Header, Input},
+        MAX_PROPOSAL_ASSETS, MAX_VOTE_OPTIONS,
+    },
     demo::{FuncCall, ZkContractInfo, ZkContractTable},
     money_contract, note,
 };
@@ -41,14 +44,68 @@ pub struct BuilderInput {
 }
 
 #[derive(SerialEncodable, SerialDecodable, Clone)]
-pub struct Proposal {
-    pub dest: PublicKey,
+pub struct ProposalAsset {
+    pub token_id: pallas::Base,
     pub amount: u64,
+    /// Serial of the coin paying out this asset
     pub serial: pallas::Base,
-    pub token_id: pallas::Base,
+}
+
+#[derive(SerialEncodable, SerialDecodable, Clone)]
+pub struct Proposal {
+    pub dest: PublicKey,
+    /// Treasury assets sent to `dest`, between 1 and [`MAX_PROPOSAL_ASSETS`]
+    /// of them, each of a different token.
+    pub assets: Vec<ProposalAsset>,
+    /// Number of options voters choose from, between 2 and
+    /// [`MAX_VOTE_OPTIONS`]. Option 0 executes the proposal.
+    pub num_options: u64,
+    /// First slot votes are accepted in
+    pub start_slot: u64,
+    /// Last slot votes are accepted in. The proposal can only be
+    /// executed after it, and only if it met quorum.
+    pub end_slot: u64,
     pub blind: pallas::Base,
 }
 
+impl Proposal {
+    /// The assets padded to [`MAX_PROPOSAL_ASSETS`] slots as
+    /// `(amount, token_id, serial)`, with the empty slots all zeros.
+    pub fn asset_slots(&self) -> Vec<(pallas::Base, pallas::Base, pallas::Base)> {
+        assert!(!self.assets.is_empty());
+        assert!(self.assets.len() <= MAX_PROPOSAL_ASSETS);
+
+        let zero = pallas::Base::from(0);
+        let mut slots = vec![(zero, zero, zero); MAX_PROPOSAL_ASSETS];
+        for (slot, asset) in slots.iter_mut().zip(&self.assets) {
+            *slot = (pallas::Base::from(asset.amount), asset.token_id, asset.serial);
+        }
+        slots
+    }
+
+    /// Hash of the asset slots, committed to in the proposal bulla.
+    pub fn assets_hash(&self) -> pallas::Base {
+        let mut elements = [pallas::Base::from(0); 3 * MAX_PROPOSAL_ASSETS];
+        for (i, (amount, token_id, serial)) in self.asset_slots().into_iter().enumerate() {
+            elements[3 * i] = amount;
+            elements[3 * i + 1] = token_id;
+            elements[3 * i + 2] = serial;
+        }
+        poseidon_hash(elements)
+    }
+
+    /// Witnesses for the asset slots, in the order of the circuits' proposal params.
+    pub fn asset_witnesses(&self) -> Vec<Witness> {
+        let mut witnesses = vec![];
+        for (amount, token_id, serial) in self.asset_slots() {
+            witnesses.push(Witness::Base(Value::known(amount)));
+            witnesses.push(Witness::Base(Value::known(token_id)));
+            witnesses.push(Witness::Base(Value::known(serial)));
+        }
+        witnesses
+    }
+}
+
 #[derive(Clone)]
 pub struct DaoParams {
     pub proposer_limit: u64,
@@ -116,7 +173,7 @@ impl Builder {
             let public_key = PublicKey::from_secret(input.secret);
             let coords = public_key.0.to_affine().coordinates().unwrap();
 
-            let coin = poseidon_hash::<8>([
+            let coin = poseidon_hash::<9>([
                 *coords.x(),
                 *coords.y(),
                 pallas::Base::from(note.value),
@@ -124,6 +181,7 @@ impl Builder {
                 note.serial,
                 pallas::Base::from(0),
                 pallas::Base::from(0),
+                pallas::Base::from(0),
                 note.coin_blind,
             ]);
 
@@ -182,7 +240,18 @@ impl Builder {
         let proposal_dest_x = *proposal_dest_coords.x();
         let proposal_dest_y = *proposal_dest_coords.y();
 
-        let proposal_amount = pallas::Base::from(self.proposal.amount);
+        for (i, asset) in self.proposal.assets.iter().enumerate() {
+            assert!(self.proposal.assets[..i].iter().all(|a| a.token_id != asset.token_id));
+        }
+        let proposal_assets = self.proposal.assets_hash();
+
+        assert!(self.proposal.num_options >= 2);
+        assert!(self.proposal.num_options <= MAX_VOTE_OPTIONS as u64);
+        let proposal_num_options = pallas::Base::from(self.proposal.num_options);
+
+        assert!(self.proposal.start_slot < self.proposal.end_slot);
+        let proposal_start_slot = pallas::Base::from(self.proposal.start_slot);
+        let proposal_end_slot = pallas::Base::from(self.proposal.end_slot);
 
         let dao_proposer_limit = pallas::Base::from(self.dao.proposer_limit);
         let dao_quorum = pallas::Base::from(self.dao.quorum);
@@ -207,12 +276,11 @@ impl Builder {
         let proposal_bulla = poseidon_hash::<8>([
             proposal_dest_x,
             proposal_dest_y,
-            proposal_amount,
-            self.proposal.serial,
-            self.proposal.token_id,
+            proposal_assets,
             dao_bulla,
-            self.proposal.blind,
-            // @tmp-workaround
+            proposal_num_options,
+            proposal_start_slot,
+            proposal_end_slot,
             self.proposal.blind,
         ]);
 
@@ -223,7 +291,7 @@ impl Builder {
             panic!("Not binary info")
         };
         let zk_bin = zk_info.bincode.clone();
-        let prover_witnesses = vec![
+        let mut prover_witnesses = vec![
             // Proposers total number of gov tokens
             Witness::Base(Value::known(total_funds)),
             Witness::Scalar(Value::known(total_funds_blinds)),
@@ -232,9 +300,12 @@ impl Builder {
             // proposal params
             Witness::Base(Value::known(proposal_dest_x)),
             Witness::Base(Value::known(proposal_dest_y)),
-            Witness::Base(Value::known(proposal_amount)),
-            Witness::Base(Value::known(self.proposal.serial)),
-            Witness::Base(Value::known(self.proposal.token_id)),
+        ];
+        prover_witnesses.extend(self.proposal.asset_witnesses());
+        prover_witnesses.extend([
+            Witness::Base(Value::known(proposal_num_options)),
+            Witness::Base(Value::known(proposal_start_slot)),
+            Witness::Base(Value::known(proposal_end_slot)),
             Witness::Base(Value::known(self.proposal.blind)),
             // DAO params
             Witness::Base(Value::known(dao_proposer_limit)),
@@ -246,7 +317,7 @@ impl Builder {
             Witness::Base(Value::known(self.dao.bulla_blind)),
             Witness::Uint32(Value::known(dao_leaf_position.try_into().unwrap())),
             Witness::MerklePath(Value::known(self.dao_merkle_path.try_into().unwrap())),
-        ];
+        ]);
         let public_inputs = vec![
             token_commit,
             self.dao_merkle_root.0,
@@ -274,7 +345,7 @@ impl Builder {
 
         FuncCall {
             contract_id: "DAO".to_string(),
-            func_id: "DAO::propose()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs,
         }
@@ -3,19 +3,21 @@ use pasta_curves::{
     group::{ff::PrimeField, Group},
     pallas,
 };
-use std::{any::Any, collections::HashMap, hash::Hasher};
+use std::{collections::HashMap, hash::Hasher};
 
 use darkfi::{
     crypto::{constants::MERKLE_DEPTH, merkle_node::MerkleNode, nullifier::Nullifier},
     util::serial::{Encodable, SerialDecodable, SerialEncodable},
 };
 
+use super::MAX_VOTE_OPTIONS;
+
 #[derive(Clone, SerialEncodable, SerialDecodable)]
 pub struct DaoBulla(pub pallas::Base);
 
 type MerkleTree = BridgeTree<MerkleNode, MERKLE_DEPTH>;
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq)]
 pub struct HashableBase(pub pallas::Base);
 
 impl std::hash::Hash for HashableBase {
@@ -25,10 +27,10 @@ impl std::hash::Hash for HashableBase {
     }
 }
 
+#[derive(Clone)]
 pub struct ProposalVotes {
-    // TODO: might be more logical to have 'yes_vote_commits' and 'no_vote_commits'
-    /// Weighted vote commits
-    pub vote_commits: pallas::Point,
+    /// Weighted vote commits, tallied for each option
+    pub vote_commits: Vec<pallas::Point>,
     /// All value staked in the vote
     pub value_commits: pallas::Point,
     /// Vote nullifiers
@@ -42,6 +44,7 @@ impl ProposalVotes {
 }
 
 /// This DAO state is for all DAOs on the network. There should only be a single instance.
+#[derive(Clone)]
 pub struct State {
     dao_bullas: Vec<DaoBulla>,
     pub dao_tree: MerkleTree,
@@ -54,7 +57,7 @@ pub struct State {
 }
 
 impl State {
-    pub fn new() -> Box<dyn Any> {
+    pub fn new() -> Box<Self> {
         Box::new(Self {
             dao_bullas: Vec::new(),
             dao_tree: MerkleTree::new(100),
@@ -81,7 +84,7 @@ impl State {
         self.proposal_votes.insert(
             HashableBase(bulla),
             ProposalVotes {
-                vote_commits: pallas::Point::identity(),
+                vote_commits: vec![pallas::Point::identity(); MAX_VOTE_OPTIONS],
                 value_commits: pallas::Point::identity(),
                 vote_nulls: Vec::new(),
             },
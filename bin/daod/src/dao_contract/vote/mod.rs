@@ -1,2 +1,7 @@
+use once_cell::sync::Lazy;
+use pasta_curves::pallas;
+
+pub static FUNC_ID: Lazy<pallas::Base> = Lazy::new(|| pallas::Base::from(109));
+
 pub mod validate;
 pub mod wallet;
@@ -15,7 +15,7 @@ use pasta_curves::{
 use std::any::{Any, TypeId};
 
 use crate::{
-    dao_contract::State as DaoState,
+    dao_contract::{State as DaoState, MAX_VOTE_OPTIONS},
     demo::{CallDataBase, StateRegistry, Transaction, UpdateBase},
     money_contract::state::State as MoneyState,
     note::EncryptedNote2,
@@ -38,6 +38,12 @@ pub enum Error {
     #[error("Signature verification failed")]
     SignatureVerifyFailed,
 
+    #[error("Invalid number of vote commits")]
+    InvalidVoteCommits,
+
+    #[error("Voting is not open for this proposal")]
+    VotingClosed,
+
     #[error("DarkFi error: {0}")]
     DarkFiError(String),
 }
@@ -81,21 +87,23 @@ impl CallDataBase for CallData {
             ));
         }
 
-        let vote_commit_coords = self.header.vote_commit.to_affine().coordinates().unwrap();
+        let mut main_publics = vec![
+            self.header.token_commit,
+            self.header.proposal_bulla,
+            pallas::Base::from(self.header.start_slot),
+            pallas::Base::from(self.header.end_slot),
+        ];
+        for vote_commit in &self.header.vote_commits {
+            let vote_commit_coords = vote_commit.to_affine().coordinates().unwrap();
+            main_publics.push(*vote_commit_coords.x());
+            main_publics.push(*vote_commit_coords.y());
+        }
 
         let value_commit_coords = total_value_commit.to_affine().coordinates().unwrap();
+        main_publics.push(*value_commit_coords.x());
+        main_publics.push(*value_commit_coords.y());
 
-        zk_publics.push((
-            "dao-vote-main".to_string(),
-            vec![
-                self.header.token_commit,
-                self.header.proposal_bulla,
-                *vote_commit_coords.x(),
-                *vote_commit_coords.y(),
-                *value_commit_coords.x(),
-                *value_commit_coords.y(),
-            ],
-        ));
+        zk_publics.push(("dao-vote-main".to_string(), main_publics));
 
         zk_publics
     }
@@ -117,7 +125,11 @@ impl CallDataBase for CallData {
 pub struct Header {
     pub token_commit: pallas::Base,
     pub proposal_bulla: pallas::Base,
-    pub vote_commit: pallas::Point,
+    /// Voting window of the proposal, committed to in its bulla
+    pub start_slot: u64,
+    pub end_slot: u64,
+    /// Weighted vote commit for each option
+    pub vote_commits: Vec<pallas::Point>,
     pub enc_note: EncryptedNote2,
 }
 
@@ -152,6 +164,18 @@ pub fn state_transition(
     }
     let votes_info = votes_info.unwrap();
 
+    // Votes are only accepted inside the proposal's voting window
+    if states.current_slot < call_data.header.start_slot ||
+        states.current_slot > call_data.header.end_slot
+    {
+        return Err(Error::VotingClosed)
+    }
+
+    // There's a vote commit for every option the circuit supports
+    if call_data.header.vote_commits.len() != MAX_VOTE_OPTIONS {
+        return Err(Error::InvalidVoteCommits)
+    }
+
     // Check the merkle roots for the input coins are valid
     let mut vote_nulls = Vec::new();
     let mut total_value_commit = pallas::Point::identity();
@@ -177,7 +201,7 @@ pub fn state_transition(
     Ok(Box::new(Update {
         proposal_bulla: call_data.header.proposal_bulla,
         vote_nulls,
-        vote_commit: call_data.header.vote_commit,
+        vote_commits: call_data.header.vote_commits.clone(),
         value_commit: total_value_commit,
     }))
 }
@@ -186,7 +210,7 @@ pub fn state_transition(
 pub struct Update {
     proposal_bulla: pallas::Base,
     vote_nulls: Vec<Nullifier>,
-    pub vote_commit: pallas::Point,
+    pub vote_commits: Vec<pallas::Point>,
     pub value_commit: pallas::Point,
 }
 
@@ -194,7 +218,9 @@ impl UpdateBase for Update {
     fn apply(mut self: Box<Self>, states: &mut StateRegistry) {
         let state = states.lookup_mut::<DaoState>(&"DAO".to_string()).unwrap();
         let votes_info = state.lookup_proposal_votes_mut(self.proposal_bulla).unwrap();
-        votes_info.vote_commits += self.vote_commit;
+        for (tally, vote_commit) in votes_info.vote_commits.iter_mut().zip(self.vote_commits) {
+            *tally += vote_commit;
+        }
         votes_info.value_commits += self.value_commit;
         votes_info.vote_nulls.append(&mut self.vote_nulls);
     }
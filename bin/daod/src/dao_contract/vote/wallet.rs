@@ -23,6 +23,7 @@ use crate::{
     dao_contract::{
         propose::wallet::{DaoParams, Proposal},
         vote::validate::{CallData, Header, Input},
+        MAX_VOTE_OPTIONS,
     },
     demo::{FuncCall, ZkContractInfo, ZkContractTable},
     money_contract, note,
@@ -40,8 +41,10 @@ pub struct Note {
 #[derive(SerialEncodable, SerialDecodable)]
 // All info needed for vote and value commits
 pub struct Vote {
-    pub vote_option: bool,
-    pub vote_option_blind: pallas::Scalar,
+    /// Index of the chosen option, below the proposal's `num_options`
+    pub vote_option: u64,
+    /// Blinds for the vote commit of each option, [`MAX_VOTE_OPTIONS`] of them
+    pub vote_option_blinds: Vec<pallas::Scalar>,
 }
 
 pub struct BuilderInput {
@@ -112,7 +115,7 @@ impl Builder {
             let public_key = PublicKey::from_secret(input.secret);
             let coords = public_key.0.to_affine().coordinates().unwrap();
 
-            let coin = poseidon_hash::<8>([
+            let coin = poseidon_hash::<9>([
                 *coords.x(),
                 *coords.y(),
                 pallas::Base::from(note.value),
@@ -120,6 +123,7 @@ impl Builder {
                 note.serial,
                 pallas::Base::from(0),
                 pallas::Base::from(0),
+                pallas::Base::from(0),
                 note.coin_blind,
             ]);
 
@@ -181,7 +185,10 @@ impl Builder {
 
         let proposal_dest_coords = self.proposal.dest.0.to_affine().coordinates().unwrap();
 
-        let proposal_amount = pallas::Base::from(self.proposal.amount);
+        let proposal_assets = self.proposal.assets_hash();
+        let proposal_num_options = pallas::Base::from(self.proposal.num_options);
+        let proposal_start_slot = pallas::Base::from(self.proposal.start_slot);
+        let proposal_end_slot = pallas::Base::from(self.proposal.end_slot);
 
         let dao_proposer_limit = pallas::Base::from(self.dao.proposer_limit);
         let dao_quorum = pallas::Base::from(self.dao.quorum);
@@ -206,24 +213,29 @@ impl Builder {
             *proposal_dest_coords.y(),
             //proposal_dest_x,
             //proposal_dest_y,
-            proposal_amount,
-            self.proposal.serial,
-            self.proposal.token_id,
+            proposal_assets,
             dao_bulla,
-            self.proposal.blind,
-            // @tmp-workaround
+            proposal_num_options,
+            proposal_start_slot,
+            proposal_end_slot,
             self.proposal.blind,
         ]);
 
-        let vote = self.vote.vote_option as u64;
-        assert!(vote == 0 || vote == 1);
+        assert!(self.vote.vote_option < self.proposal.num_options);
+        assert_eq!(self.vote.vote_option_blinds.len(), MAX_VOTE_OPTIONS);
 
-        let weighted_vote = vote * value;
+        // One-hot encoding of the chosen option. Every option gets a commit
+        // to its weighted vote, so the chosen one can't be told apart.
+        let mut vote_options = vec![];
+        let mut vote_commits = vec![];
+        for (i, vote_option_blind) in self.vote.vote_option_blinds.iter().enumerate() {
+            let vote = (i as u64 == self.vote.vote_option) as u64;
+            let weighted_vote = vote * value;
 
-        let vote_commit = pedersen_commitment_u64(weighted_vote, self.vote.vote_option_blind);
-        debug!(target: "demo::dao_contract::vote::wallet::Builder", "vote commit: {:?}", vote_commit);
-        let vote_coords = vote_commit.to_affine().coordinates().unwrap();
-        let vote = pallas::Base::from(vote);
+            vote_options.push(pallas::Base::from(vote));
+            vote_commits.push(pedersen_commitment_u64(weighted_vote, *vote_option_blind));
+        }
+        debug!(target: "demo::dao_contract::vote::wallet::Builder", "vote commits: {:?}", vote_commits);
 
         let value_commit = pedersen_commitment_u64(value, value_blind);
         let value_coords = value_commit.to_affine().coordinates().unwrap();
@@ -237,13 +249,16 @@ impl Builder {
         };
         let zk_bin = zk_info.bincode.clone();
 
-        let prover_witnesses = vec![
+        let mut prover_witnesses = vec![
             // proposal params
             Witness::Base(Value::known(*proposal_dest_coords.x())),
             Witness::Base(Value::known(*proposal_dest_coords.y())),
-            Witness::Base(Value::known(proposal_amount)),
-            Witness::Base(Value::known(self.proposal.serial)),
-            Witness::Base(Value::known(self.proposal.token_id)),
+        ];
+        prover_witnesses.extend(self.proposal.asset_witnesses());
+        prover_witnesses.extend([
+            Witness::Base(Value::known(proposal_num_options)),
+            Witness::Base(Value::known(proposal_start_slot)),
+            Witness::Base(Value::known(proposal_end_slot)),
             Witness::Base(Value::known(self.proposal.blind)),
             // DAO params
             Witness::Base(Value::known(dao_proposer_limit)),
@@ -253,24 +268,31 @@ impl Builder {
             Witness::Base(Value::known(*dao_pubkey_coords.x())),
             Witness::Base(Value::known(*dao_pubkey_coords.y())),
             Witness::Base(Value::known(self.dao.bulla_blind)),
-            // Vote
-            Witness::Base(Value::known(vote)),
-            Witness::Scalar(Value::known(self.vote.vote_option_blind)),
+        ]);
+        // Vote
+        for vote_option in &vote_options {
+            prover_witnesses.push(Witness::Base(Value::known(*vote_option)));
+        }
+        for vote_option_blind in &self.vote.vote_option_blinds {
+            prover_witnesses.push(Witness::Scalar(Value::known(*vote_option_blind)));
+        }
+        prover_witnesses.extend([
             // Total number of gov tokens allocated
             Witness::Base(Value::known(value_base)),
             Witness::Scalar(Value::known(value_blind)),
             // gov token
             Witness::Base(Value::known(gov_token_blind)),
-        ];
+        ]);
 
-        let public_inputs = vec![
-            token_commit,
-            proposal_bulla,
-            *vote_coords.x(),
-            *vote_coords.y(),
-            *value_coords.x(),
-            *value_coords.y(),
-        ];
+        let mut public_inputs =
+            vec![token_commit, proposal_bulla, proposal_start_slot, proposal_end_slot];
+        for vote_commit in &vote_commits {
+            let vote_coords = vote_commit.to_affine().coordinates().unwrap();
+            public_inputs.push(*vote_coords.x());
+            public_inputs.push(*vote_coords.y());
+        }
+        public_inputs.push(*value_coords.x());
+        public_inputs.push(*value_coords.y());
 
         let circuit = ZkCircuit::new(prover_witnesses, zk_bin);
 
@@ -283,13 +305,20 @@ impl Builder {
         let note = Note { vote: self.vote, value, value_blind };
         let enc_note = note::encrypt(&note, &self.vote_keypair.public).unwrap();
 
-        let header = Header { token_commit, proposal_bulla, vote_commit, enc_note };
+        let header = Header {
+            token_commit,
+            proposal_bulla,
+            start_slot: self.proposal.start_slot,
+            end_slot: self.proposal.end_slot,
+            vote_commits,
+            enc_note,
+        };
 
         let call_data = CallData { header, inputs };
 
         FuncCall {
             contract_id: "DAO".to_string(),
-            func_id: "DAO::vote()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs,
         }
@@ -164,7 +164,9 @@ fn sign(signature_secrets: Vec<SecretKey>) -> Vec<Signature> {
 
 // These would normally be a hash or sth
 type ContractId = String;
-type FuncId = String;
+/// Identifies the contract function a call is made to. Each function
+/// defines its own as `FUNC_ID`, which coins also use as their spend hook.
+type FuncId = pallas::Base;
 
 //#[derive(Clone, SerialEncodable)]
 pub struct FuncCall {
@@ -197,15 +199,39 @@ pub trait CallDataBase {
     fn signature_public_keys(&self) -> Vec<PublicKey>;
 }
 
-type GenericContractState = Box<dyn Any>;
+/// State of a contract, held in the [`StateRegistry`]. States get cloned
+/// into the overlay a transaction is applied on.
+pub trait ContractState: Any {
+    fn clone_box(&self) -> Box<dyn ContractState>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any + Clone> ContractState for T {
+    fn clone_box(&self) -> Box<dyn ContractState> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+type GenericContractState = Box<dyn ContractState>;
 
 pub struct StateRegistry {
     pub states: HashMap<ContractId, GenericContractState>,
+    /// Current blockchain slot the transactions are validated in
+    pub current_slot: u64,
 }
 
 impl StateRegistry {
     fn new() -> Self {
-        Self { states: HashMap::new() }
+        Self { states: HashMap::new(), current_slot: 0 }
     }
 
     fn register(&mut self, contract_id: ContractId, state: GenericContractState) {
@@ -214,12 +240,73 @@ impl StateRegistry {
     }
 
     pub fn lookup_mut<'a, S: 'static>(&'a mut self, contract_id: &ContractId) -> Option<&'a mut S> {
-        self.states.get_mut(contract_id).and_then(|state| state.downcast_mut())
+        self.states.get_mut(contract_id).and_then(|state| state.as_any_mut().downcast_mut())
     }
 
     pub fn lookup<'a, S: 'static>(&'a self, contract_id: &ContractId) -> Option<&'a S> {
-        self.states.get(contract_id).and_then(|state| state.downcast_ref())
+        self.states.get(contract_id).and_then(|state| state.as_any().downcast_ref())
     }
+
+    /// Apply all the function calls of a transaction, or none of them.
+    ///
+    /// Calls are validated and applied in order on an overlay of the
+    /// states, so each call sees the updates of the calls before it, like
+    /// the coins spent and created by a `Money::transfer()` funding a
+    /// `DAO::exec()`. The overlay replaces the states once every call
+    /// succeeded.
+    pub fn apply_tx(&mut self, tx: &Transaction) -> Result<()> {
+        let mut overlay = self.clone();
+
+        for idx in 0..tx.func_calls.len() {
+            let update = state_transition(&overlay, idx, tx)?;
+            update.apply(&mut overlay);
+        }
+
+        *self = overlay;
+        Ok(())
+    }
+}
+
+impl Clone for StateRegistry {
+    fn clone(&self) -> Self {
+        let states =
+            self.states.iter().map(|(id, state)| (id.clone(), state.clone_box())).collect();
+        Self { states, current_slot: self.current_slot }
+    }
+}
+
+/// Validate a function call against the state with the state transition
+/// of its function, selected by `func_id`.
+fn state_transition(
+    states: &StateRegistry,
+    idx: usize,
+    tx: &Transaction,
+) -> Result<Box<dyn UpdateBase>> {
+    let func_id = tx.func_calls[idx].func_id;
+
+    let update = if func_id == *example_contract::foo::FUNC_ID {
+        debug!(target: "demo", "example_contract::foo::state_transition()");
+        example_contract::foo::validate::state_transition(states, idx, tx)?
+    } else if func_id == *money_contract::transfer::FUNC_ID {
+        debug!(target: "demo", "money_contract::transfer::state_transition()");
+        money_contract::transfer::validate::state_transition(states, idx, tx)?
+    } else if func_id == *dao_contract::mint::FUNC_ID {
+        debug!(target: "demo", "dao_contract::mint::state_transition()");
+        dao_contract::mint::validate::state_transition(states, idx, tx)?
+    } else if func_id == *dao_contract::propose::FUNC_ID {
+        debug!(target: "demo", "dao_contract::propose::state_transition()");
+        dao_contract::propose::validate::state_transition(states, idx, tx)?
+    } else if func_id == *dao_contract::vote::FUNC_ID {
+        debug!(target: "demo", "dao_contract::vote::state_transition()");
+        dao_contract::vote::validate::state_transition(states, idx, tx)?
+    } else if func_id == *dao_contract::exec::FUNC_ID {
+        debug!(target: "demo", "dao_contract::exec::state_transition()");
+        dao_contract::exec::validate::state_transition(states, idx, tx)?
+    } else {
+        return Err(format!("Unknown function for call {}", idx).into())
+    };
+
+    Ok(update)
 }
 
 pub trait UpdateBase {
@@ -257,22 +344,7 @@ pub async fn example() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "Example::foo()" {
-            debug!("example_contract::foo::state_transition()");
-
-            let update = example_contract::foo::validate::state_transition(&states, idx, &tx)
-                .expect("example_contract::foo::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -393,24 +465,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        // So then the verifier will lookup the corresponding state_transition and apply
-        // functions based off the func_id
-        if func_call.func_id == "DAO::mint()" {
-            debug!("dao_contract::mint::state_transition()");
-
-            let update = dao_contract::mint::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::mint::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
 
@@ -488,24 +543,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        // So then the verifier will lookup the corresponding state_transition and apply
-        // functions based off the func_id
-        if func_call.func_id == "Money::transfer()" {
-            debug!("money_contract::transfer::state_transition()");
-
-            let update = money_contract::transfer::validate::state_transition(&states, idx, &tx)
-                .expect("money_contract::transfer::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -522,7 +560,7 @@ pub async fn demo() -> Result<()> {
     // Check the actual coin received is valid before accepting it
 
     let coords = dao_keypair.public.0.to_affine().coordinates().unwrap();
-    let coin = poseidon_hash::<8>([
+    let coin = poseidon_hash::<9>([
         *coords.x(),
         *coords.y(),
         DrkValue::from(treasury_note.value),
@@ -530,6 +568,7 @@ pub async fn demo() -> Result<()> {
         treasury_note.serial,
         treasury_note.spend_hook,
         treasury_note.user_data,
+        pallas::Base::from(0),
         treasury_note.coin_blind,
     ]);
     assert_eq!(coin, dao_recv_coin.coin.0);
@@ -614,24 +653,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        // So then the verifier will lookup the corresponding state_transition and apply
-        // functions based off the func_id
-        if func_call.func_id == "Money::transfer()" {
-            debug!("money_contract::transfer::state_transition()");
-
-            let update = money_contract::transfer::validate::state_transition(&states, idx, &tx)
-                .expect("money_contract::transfer::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -654,7 +676,7 @@ pub async fn demo() -> Result<()> {
             assert_eq!(note.user_data, pallas::Base::from(0));
 
             let coords = key.public.0.to_affine().coordinates().unwrap();
-            let coin = poseidon_hash::<8>([
+            let coin = poseidon_hash::<9>([
                 *coords.x(),
                 *coords.y(),
                 DrkValue::from(note.value),
@@ -662,6 +684,7 @@ pub async fn demo() -> Result<()> {
                 note.serial,
                 note.spend_hook,
                 note.user_data,
+                pallas::Base::from(0),
                 note.coin_blind,
             ]);
             assert_eq!(coin, recv_coin.coin.0);
@@ -738,9 +761,16 @@ pub async fn demo() -> Result<()> {
 
     let proposal = dao_contract::propose::wallet::Proposal {
         dest: user_keypair.public,
-        amount: 1000,
-        serial: pallas::Base::random(&mut OsRng),
-        token_id: xdrk_token_id,
+        assets: vec![dao_contract::propose::wallet::ProposalAsset {
+            token_id: xdrk_token_id,
+            amount: 1000,
+            serial: pallas::Base::random(&mut OsRng),
+        }],
+        // 0: yes, 1: no, 2: abstain
+        num_options: 3,
+        // Voting opens now, and lasts for 10 slots
+        start_slot: states.current_slot,
+        end_slot: states.current_slot + 10,
         blind: pallas::Base::random(&mut OsRng),
     };
 
@@ -760,22 +790,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "DAO::propose()" {
-            debug!(target: "demo", "dao_contract::propose::state_transition()");
-
-            let update = dao_contract::propose::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::propose::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -802,8 +817,10 @@ pub async fn demo() -> Result<()> {
     };
     debug!(target: "demo", "Proposal now active!");
     debug!(target: "demo", "  destination: {:?}", proposal.dest);
-    debug!(target: "demo", "  amount: {}", proposal.amount);
-    debug!(target: "demo", "  token_id: {:?}", proposal.token_id);
+    for asset in &proposal.assets {
+        debug!(target: "demo", "  amount: {}", asset.amount);
+        debug!(target: "demo", "  token_id: {:?}", asset.token_id);
+    }
     debug!(target: "demo", "  dao_bulla: {:?}", dao_bulla.0);
     debug!(target: "demo", "Proposal bulla: {:?}", proposal_bulla);
 
@@ -856,9 +873,9 @@ pub async fn demo() -> Result<()> {
         signature_secret,
     };
 
-    let vote_option: bool = true;
+    let vote_option: u64 = 0;
 
-    assert!(vote_option == true || vote_option == false);
+    assert!(vote_option < proposal.num_options);
 
     // We create a new keypair to encrypt the vote.
     let vote_keypair_1 = Keypair::random(&mut OsRng);
@@ -867,7 +884,9 @@ pub async fn demo() -> Result<()> {
         inputs: vec![input],
         vote: dao_contract::vote::wallet::Vote {
             vote_option,
-            vote_option_blind: pallas::Scalar::random(&mut OsRng),
+            vote_option_blinds: (0..dao_contract::MAX_VOTE_OPTIONS)
+                .map(|_| pallas::Scalar::random(&mut OsRng))
+                .collect(),
         },
         vote_keypair: vote_keypair_1,
         proposal: proposal.clone(),
@@ -881,22 +900,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "DAO::vote()" {
-            debug!(target: "demo", "dao_contract::vote::state_transition()");
-
-            let update = dao_contract::vote::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::vote::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -942,9 +946,9 @@ pub async fn demo() -> Result<()> {
         signature_secret,
     };
 
-    let vote_option: bool = false;
+    let vote_option: u64 = 1;
 
-    assert!(vote_option == true || vote_option == false);
+    assert!(vote_option < proposal.num_options);
 
     // We create a new keypair to encrypt the vote.
     let vote_keypair_2 = Keypair::random(&mut OsRng);
@@ -953,7 +957,9 @@ pub async fn demo() -> Result<()> {
         inputs: vec![input],
         vote: dao_contract::vote::wallet::Vote {
             vote_option,
-            vote_option_blind: pallas::Scalar::random(&mut OsRng),
+            vote_option_blinds: (0..dao_contract::MAX_VOTE_OPTIONS)
+                .map(|_| pallas::Scalar::random(&mut OsRng))
+                .collect(),
         },
         vote_keypair: vote_keypair_2,
         proposal: proposal.clone(),
@@ -967,22 +973,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "DAO::vote()" {
-            debug!(target: "demo", "dao_contract::vote::state_transition()");
-
-            let update = dao_contract::vote::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::vote::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -1028,9 +1019,9 @@ pub async fn demo() -> Result<()> {
         signature_secret,
     };
 
-    let vote_option: bool = true;
+    let vote_option: u64 = 0;
 
-    assert!(vote_option == true || vote_option == false);
+    assert!(vote_option < proposal.num_options);
 
     // We create a new keypair to encrypt the vote.
     let vote_keypair_3 = Keypair::random(&mut OsRng);
@@ -1039,7 +1030,9 @@ pub async fn demo() -> Result<()> {
         inputs: vec![input],
         vote: dao_contract::vote::wallet::Vote {
             vote_option,
-            vote_option_blind: pallas::Scalar::random(&mut OsRng),
+            vote_option_blinds: (0..dao_contract::MAX_VOTE_OPTIONS)
+                .map(|_| pallas::Scalar::random(&mut OsRng))
+                .collect(),
         },
         vote_keypair: vote_keypair_3,
         proposal: proposal.clone(),
@@ -1053,22 +1046,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "DAO::vote()" {
-            debug!(target: "demo", "dao_contract::vote::state_transition()");
-
-            let update = dao_contract::vote::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::vote::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     tx.zk_verify(&zk_bins);
     tx.verify_sigs();
@@ -1094,8 +1072,8 @@ pub async fn demo() -> Result<()> {
     debug!(target: "demo", "  vote_option: {}", vote_note_3.vote.vote_option);
     debug!(target: "demo", "  value: {}", vote_note_3.value);
 
-    // Every votes produces a semi-homomorphic encryption of their vote.
-    // Which is either yes or no
+    // Every votes produces a semi-homomorphic encryption of their vote,
+    // with one commit per option
     // We copy the state tree for the governance token so coins can be used
     // to vote on other proposals at the same time.
     // With their vote, they produce a ZK proof + nullifier
@@ -1103,12 +1081,12 @@ pub async fn demo() -> Result<()> {
     // voting period.
     // (that's if we want votes to be hidden during voting)
 
-    let mut win_votes = 0;
+    let mut option_votes = vec![0; dao_contract::MAX_VOTE_OPTIONS];
     let mut total_votes = 0;
-    let mut total_vote_blinds = pallas::Scalar::from(0);
+    let mut option_vote_blinds = vec![pallas::Scalar::from(0); dao_contract::MAX_VOTE_OPTIONS];
     let mut total_value_blinds = pallas::Scalar::from(0);
     let mut total_value_commit = pallas::Point::identity();
-    let mut total_vote_commit = pallas::Point::identity();
+    let mut option_vote_commits = vec![pallas::Point::identity(); dao_contract::MAX_VOTE_OPTIONS];
 
     // We were previously saving votes to a Vec<Update> for testing.
     // However since Update is now UpdateBase it gets moved into update.apply().
@@ -1127,36 +1105,45 @@ pub async fn demo() -> Result<()> {
         total_value_commit += value_commit;
         total_value_blinds += note.value_blind;
 
-        let vote_commit = pedersen_commitment_u64(
-            note.vote.vote_option as u64 * note.value,
-            note.vote.vote_option_blind,
-        );
-
-        //assert!(update.vote_commit == vote_commit);
+        for (option, vote_option_blind) in note.vote.vote_option_blinds.iter().enumerate() {
+            let weighted_vote = if option as u64 == note.vote.vote_option { note.value } else { 0 };
+            let vote_commit = pedersen_commitment_u64(weighted_vote, *vote_option_blind);
 
-        total_vote_commit += vote_commit;
-        total_vote_blinds += note.vote.vote_option_blind;
+            //assert!(update.vote_commits[option] == vote_commit);
 
-        let vote_option = note.vote.vote_option;
-
-        if vote_option {
-            win_votes += note.value;
+            option_vote_commits[option] += vote_commit;
+            option_vote_blinds[option] += vote_option_blind;
+            option_votes[option] += weighted_vote;
         }
+
         total_votes += note.value;
-        let vote_result: String = if vote_option { "yes".to_string() } else { "no".to_string() };
+        let vote_result = match note.vote.vote_option {
+            0 => "yes",
+            1 => "no",
+            _ => "abstain",
+        };
 
         debug!("Voter {} voted {}", i, vote_result);
     }
 
-    debug!("Outcome = {} / {}", win_votes, total_votes);
+    debug!("Outcome = {:?} / {}", option_votes, total_votes);
 
     assert!(total_value_commit == pedersen_commitment_u64(total_votes, total_value_blinds));
-    assert!(total_vote_commit == pedersen_commitment_u64(win_votes, total_vote_blinds));
+    for option in 0..dao_contract::MAX_VOTE_OPTIONS {
+        assert!(
+            option_vote_commits[option] ==
+                pedersen_commitment_u64(option_votes[option], option_vote_blinds[option])
+        );
+    }
 
     ///////////////////////////////////////////////////
     // Execute the vote
     ///////////////////////////////////////////////////
 
+    // The proposal can only be executed once its voting window is over
+    states.current_slot = proposal.end_slot + 1;
+    debug!(target: "demo", "Voting ended, now at slot {}", states.current_slot);
+
     //// Wallet
 
     // Used to export user_data from this coin so it can be accessed by DAO::exec()
@@ -1199,7 +1186,7 @@ pub async fn demo() -> Result<()> {
                 value: 1000,
                 token_id: xdrk_token_id,
                 public: user_keypair.public,
-                serial: proposal.serial,
+                serial: proposal.assets[0].serial,
                 coin_blind: proposal.blind,
                 spend_hook: pallas::Base::from(0),
                 user_data: pallas::Base::from(0),
@@ -1222,16 +1209,17 @@ pub async fn demo() -> Result<()> {
     let builder = dao_contract::exec::wallet::Builder {
         proposal,
         dao: dao_params,
-        win_votes,
-        total_votes,
-        win_votes_blind: total_vote_blinds,
+        option_votes,
+        option_votes_blinds: option_vote_blinds,
         total_votes_blind: total_value_blinds,
         user_serial,
         user_coin_blind,
-        dao_serial,
-        dao_coin_blind,
-        input_value,
-        input_value_blind,
+        assets: vec![dao_contract::exec::wallet::BuilderAssetInput {
+            dao_serial,
+            dao_coin_blind,
+            input_value,
+            input_value_blind,
+        }],
         hook_dao_exec: *dao_contract::exec::FUNC_ID,
         signature_secret: exec_signature_secret,
     };
@@ -1265,28 +1253,7 @@ pub async fn demo() -> Result<()> {
 
     //// Validator
 
-    let mut updates = vec![];
-    // Validate all function calls in the tx
-    for (idx, func_call) in tx.func_calls.iter().enumerate() {
-        if func_call.func_id == "DAO::exec()" {
-            debug!("dao_contract::exec::state_transition()");
-
-            let update = dao_contract::exec::validate::state_transition(&states, idx, &tx)
-                .expect("dao_contract::exec::validate::state_transition() failed!");
-            updates.push(update);
-        } else if func_call.func_id == "Money::transfer()" {
-            debug!("money_contract::transfer::state_transition()");
-
-            let update = money_contract::transfer::validate::state_transition(&states, idx, &tx)
-                .expect("money_contract::transfer::validate::state_transition() failed!");
-            updates.push(update);
-        }
-    }
-
-    // Atomically apply all changes
-    for update in updates {
-        update.apply(&mut states);
-    }
+    states.apply_tx(&tx)?;
 
     // Other stuff
     tx.zk_verify(&zk_bins);
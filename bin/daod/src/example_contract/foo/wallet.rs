@@ -66,7 +66,7 @@ impl Builder {
 
         FuncCall {
             contract_id: "Example".to_string(),
-            func_id: "Example::foo()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs,
         }
@@ -1,13 +1,12 @@
-use std::any::Any;
-
 use pasta_curves::pallas;
 
+#[derive(Clone)]
 pub struct State {
     pub public_values: Vec<pallas::Base>,
 }
 
 impl State {
-    pub fn new() -> Box<dyn Any> {
+    pub fn new() -> Box<Self> {
         Box::new(Self { public_values: Vec::new() })
     }
 
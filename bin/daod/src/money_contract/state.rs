@@ -13,12 +13,14 @@ use crate::note::EncryptedNote2;
 
 type MerkleTree = BridgeTree<MerkleNode, MERKLE_DEPTH>;
 
+#[derive(Clone)]
 pub struct OwnCoin {
     pub coin: Coin,
     pub note: transfer::wallet::Note,
     pub leaf_position: incrementalmerkletree::Position,
 }
 
+#[derive(Clone)]
 pub struct WalletCache {
     // Normally this would be a HashMap, but SecretKey is not Hash-able
     cache: Vec<(SecretKey, Vec<OwnCoin>)>,
@@ -64,6 +66,7 @@ impl WalletCache {
 }
 
 /// The state machine, held in memory.
+#[derive(Clone)]
 pub struct State {
     /// The entire Merkle tree state
     pub tree: MerkleTree,
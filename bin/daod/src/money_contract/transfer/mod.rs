@@ -1,3 +1,8 @@
+use once_cell::sync::Lazy;
+use pasta_curves::pallas;
+
+pub static FUNC_ID: Lazy<pallas::Base> = Lazy::new(|| pallas::Base::from(100));
+
 pub mod validate;
 pub mod wallet;
 pub use wallet::{Builder, BuilderClearInputInfo, BuilderInputInfo, BuilderOutputInfo, Note};
@@ -120,10 +120,7 @@ pub fn state_transition(
                     continue
                 }
 
-                // TODO: we need to change these to pallas::Base
-                // temporary workaround for now
-                // if func_call.func_id == spend_hook ...
-                if func_call.func_id == "DAO::exec()" {
+                if &func_call.func_id == spend_hook {
                     is_found = true;
                     break
                 }
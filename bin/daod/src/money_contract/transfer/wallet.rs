@@ -140,6 +140,7 @@ impl Builder {
                 note.spend_hook,
                 note.user_data,
                 input.user_data_blind,
+                0,
                 note.coin_blind,
                 input.secret,
                 input.leaf_position,
@@ -185,6 +186,7 @@ impl Builder {
                 serial,
                 output.spend_hook,
                 output.user_data,
+                0,
                 coin_blind,
                 output.public,
             )?;
@@ -211,7 +213,7 @@ impl Builder {
 
         Ok(FuncCall {
             contract_id: "Money".to_string(),
-            func_id: "Money::transfer()".to_string(),
+            func_id: *super::FUNC_ID,
             call_data: Box::new(call_data),
             proofs,
         })
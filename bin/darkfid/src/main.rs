@@ -1,10 +1,10 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use async_executor::Executor;
 use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use futures_lite::future;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use serde_derive::Deserialize;
 use structopt::StructOpt;
 use structopt_toml::StructOptToml;
@@ -14,27 +14,32 @@ use darkfi::{
     async_daemonize, cli_desc,
     consensus::{
         proto::{
-            ProtocolParticipant, ProtocolProposal, ProtocolSync, ProtocolSyncConsensus, ProtocolTx,
-            ProtocolVote,
+            ProtocolFilter, ProtocolParticipant, ProtocolProposal, ProtocolRejoin, ProtocolSync,
+            ProtocolSyncConsensus, ProtocolTx, ProtocolVote,
         },
-        state::ValidatorStatePtr,
-        task::{block_sync_task, proposal_task},
-        ValidatorState, MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP,
-        TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP,
+        state::{ValidatorStatePtr, TREE_CHECKPOINT_INTERVAL},
+        task::{
+            block_sync_task, clock_sync_task, proposal_task, tx_rebroadcast_task,
+            CLOCK_SYNC_INTERVAL,
+        },
+        ClockSync, ClockSyncPtr, NoteScanner, NoteScannerPtr, StateSnapshot, ValidatorState,
+        MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
+        TESTNET_GENESIS_TIMESTAMP,
     },
     crypto::{address::Address, keypair::PublicKey},
     net,
     net::P2pPtr,
     node::Client,
     rpc::{
+        auth::RpcAuth,
         jsonrpc::{
             ErrorCode::{InvalidParams, MethodNotFound},
             JsonError, JsonRequest, JsonResult,
         },
-        server::{listen_and_serve, RequestHandler},
+        server::{listen_and_serve, listen_and_serve_tls, RequestHandler, RpcLimits},
     },
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         expand_path,
         path::get_config_path,
         time::check_clock,
@@ -43,9 +48,6 @@ use darkfi::{
     Error, Result,
 };
 
-mod error;
-use error::{server_error, RpcError};
-
 const CONFIG_FILE: &str = "darkfid_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../darkfid_config.toml");
 
@@ -73,6 +75,14 @@ struct Args {
     /// Password for the wallet database
     wallet_pass: String,
 
+    #[structopt(long)]
+    /// Restore the wallet keys from the mnemonic in the given file
+    wallet_restore: Option<String>,
+
+    #[structopt(long, default_value = "1")]
+    /// Number of keys to derive when restoring the wallet from a mnemonic
+    wallet_restore_keys: u32,
+
     #[structopt(long, default_value = "~/.config/darkfi/darkfid_blockchain")]
     /// Path to blockchain database
     database: String,
@@ -81,6 +91,26 @@ struct Args {
     /// JSON-RPC listen URL
     rpc_listen: Url,
 
+    #[structopt(long)]
+    /// PEM certificate chain for serving JSON-RPC over TLS
+    rpc_tls_cert: Option<String>,
+
+    #[structopt(long)]
+    /// PKCS#8 PEM private key for serving JSON-RPC over TLS
+    rpc_tls_key: Option<String>,
+
+    #[structopt(long, default_value = "64")]
+    /// JSON-RPC requests processed at once, across all connections
+    rpc_max_requests: usize,
+
+    #[structopt(long, default_value = "8")]
+    /// JSON-RPC requests of a connection's batch processed at once
+    rpc_max_connection_requests: usize,
+
+    #[structopt(long, default_value = "60")]
+    /// Seconds a JSON-RPC request may run before it's cancelled
+    rpc_request_timeout: u64,
+
     #[structopt(long)]
     /// P2P accept addresses for the consensus protocol (repeatable flag)
     consensus_p2p_accept: Vec<Url>,
@@ -129,6 +159,22 @@ struct Args {
     /// Connect to seed for the syncing protocol (repeatable flag)
     sync_p2p_seed: Vec<Url>,
 
+    #[structopt(long, default_value = "600")]
+    /// Seconds an orphan transaction is held for while waiting for its Merkle root
+    orphan_window: u64,
+
+    #[structopt(long, default_value = "60")]
+    /// Seconds between rebroadcasts of our own unconfirmed transactions
+    tx_rebroadcast_interval: u64,
+
+    #[structopt(long)]
+    /// Prune block bodies older than this many slots, keeping headers and state
+    prune_depth: Option<u64>,
+
+    #[structopt(long)]
+    /// Bootstrap an empty blockchain from the given state snapshot file
+    import_snapshot: Option<String>,
+
     #[structopt(long)]
     /// Whitelisted cashier address (repeatable flag)
     cashier_pub: Vec<String>,
@@ -152,10 +198,14 @@ pub struct Darkfid {
     sync_p2p: Option<P2pPtr>,
     client: Arc<Client>,
     validator_state: ValidatorStatePtr,
+    clock_sync: ClockSyncPtr,
+    scanner: NoteScannerPtr,
+    rpc_auth: Option<RpcAuth>,
 }
 
 // JSON-RPC methods
 mod rpc_blockchain;
+mod rpc_invoice;
 mod rpc_misc;
 mod rpc_tx;
 mod rpc_wallet;
@@ -172,23 +222,58 @@ impl RequestHandler for Darkfid {
         match req.method.as_str() {
             Some("ping") => return self.pong(req.id, params).await,
             Some("clock") => return self.clock(req.id, params).await,
+            Some("clock.offset") => return self.clock_offset(req.id, params).await,
             Some("blockchain.get_slot") => return self.get_slot(req.id, params).await,
+            Some("blockchain.get_slot_range") => return self.get_slot_range(req.id, params).await,
             Some("blockchain.merkle_roots") => return self.merkle_roots(req.id, params).await,
+            Some("blockchain.get_merkle_proof") => {
+                return self.get_merkle_proof(req.id, params).await
+            }
+            Some("blockchain.export_snapshot") => return self.export_snapshot(req.id, params).await,
+            Some("blockchain.get_token") => return self.get_token(req.id, params).await,
             Some("tx.transfer") => return self.transfer(req.id, params).await,
+            Some("tx.transfer_many") => return self.transfer_many(req.id, params).await,
+            Some("tx.lock") => return self.lock(req.id, params).await,
+            Some("tx.submit_evidence") => return self.submit_evidence(req.id, params).await,
+            Some("tx.estimate_fee") => return self.estimate_fee(req.id, params).await,
+            Some("invoice.create") => return self.create_invoice(req.id, params).await,
+            Some("invoice.parse") => return self.parse_invoice(req.id, params).await,
+            Some("invoice.pay") => return self.pay_invoice(req.id, params).await,
             Some("wallet.keygen") => return self.keygen(req.id, params).await,
+            Some("wallet.new_diversified_addr") => {
+                return self.new_diversified_addr(req.id, params).await
+            }
             Some("wallet.get_addrs") => return self.get_addrs(req.id, params).await,
             Some("wallet.export_keypair") => return self.export_keypair(req.id, params).await,
+            Some("wallet.export_view_key") => return self.export_view_key(req.id, params).await,
+            Some("wallet.export_mnemonic") => return self.export_mnemonic(req.id, params).await,
             Some("wallet.import_keypair") => return self.import_keypair(req.id, params).await,
             Some("wallet.set_default_address") => {
                 return self.set_default_address(req.id, params).await
             }
             Some("wallet.get_balances") => return self.get_balances(req.id, params).await,
+            Some("wallet.get_locked_balances") => {
+                return self.get_locked_balances(req.id, params).await
+            }
             Some("wallet.get_coins_valtok") => return self.get_coins_valtok(req.id, params).await,
+            Some("wallet.get_history") => return self.get_history(req.id, params).await,
+            Some("wallet.get_coin") => return self.get_coin(req.id, params).await,
             Some("wallet.get_merkle_path") => return self.get_merkle_path(req.id, params).await,
             Some("wallet.decrypt_note") => return self.decrypt_note(req.id, params).await,
+            Some("wallet.set_birthday") => return self.set_birthday(req.id, params).await,
+            Some("wallet.rescan") => return self.rescan(req.id, params).await,
+            Some("wallet.rescan_status") => return self.rescan_status(req.id, params).await,
             Some(_) | None => return JsonError::new(MethodNotFound, None, req.id).into(),
         }
     }
+
+    fn auth(&self) -> Option<&RpcAuth> {
+        self.rpc_auth.as_ref()
+    }
+
+    fn limits(&self) -> RpcLimits {
+        self.rpc_limits.clone()
+    }
 }
 
 impl Darkfid {
@@ -196,10 +281,14 @@ impl Darkfid {
         validator_state: ValidatorStatePtr,
         consensus_p2p: Option<P2pPtr>,
         sync_p2p: Option<P2pPtr>,
+        clock_sync: ClockSyncPtr,
+        rpc_auth: Option<RpcAuth>,
+        rpc_limits: RpcLimits,
     ) -> Result<Self> {
         debug!("Waiting for validator state lock");
         let client = validator_state.read().await.client.clone();
         debug!("Released validator state lock");
+        let scanner = NoteScanner::new(validator_state.clone());
 
         Ok(Self {
             synced: Mutex::new(false),
@@ -207,6 +296,10 @@ impl Darkfid {
             sync_p2p,
             client,
             validator_state,
+            clock_sync,
+            scanner,
+            rpc_auth,
+            rpc_limits,
         })
     }
 }
@@ -245,6 +338,13 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     // Initialize or load wallet
     let wallet = init_wallet(&args.wallet_path, &args.wallet_pass).await?;
 
+    // Restore the wallet keys from a mnemonic backup
+    if let Some(path) = &args.wallet_restore {
+        let mnemonic = std::fs::read_to_string(expand_path(path)?)?;
+        wallet.restore_from_mnemonic(mnemonic.trim(), args.wallet_restore_keys).await?;
+        info!("Restored wallet keys from mnemonic");
+    }
+
     // Initialize or open sled database
     let db_path = format!("{}/{}", expand_path(&args.database)?.to_str().unwrap(), args.chain);
     let sled_db = sled::open(&db_path)?;
@@ -289,6 +389,23 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
         faucet_pubkeys,
     )
     .await?;
+    state.write().await.orphans.set_window(Duration::from_secs(args.orphan_window));
+    if let Some(path) = args.import_snapshot {
+        let snapshot = StateSnapshot::import(&expand_path(&path)?)?;
+        state.write().await.import_snapshot(snapshot).await?;
+    }
+    if let Some(depth) = args.prune_depth {
+        // Blocks after the last Merkle tree checkpoint are needed on restart
+        if depth < TREE_CHECKPOINT_INTERVAL {
+            warn!(
+                "Prune depth raised to the tree checkpoint interval ({})",
+                TREE_CHECKPOINT_INTERVAL
+            );
+        }
+        let depth = depth.max(TREE_CHECKPOINT_INTERVAL);
+        info!("Pruning block bodies older than {} slots", depth);
+        state.write().await.blockchain.set_pruning(depth)?;
+    }
 
     let sync_p2p = {
         info!("Registering block sync P2P protocols...");
@@ -324,6 +441,14 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
             })
             .await;
 
+        let _state = state.clone();
+        registry
+            .register(net::SESSION_ALL, move |channel, _| {
+                let state = _state.clone();
+                async move { ProtocolFilter::init(channel, state).await.unwrap() }
+            })
+            .await;
+
         Some(p2p)
     };
 
@@ -352,6 +477,14 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
                 })
                 .await;
 
+            let _state = state.clone();
+            registry
+                .register(net::SESSION_ALL, move |channel, p2p| {
+                    let state = _state.clone();
+                    async move { ProtocolRejoin::init(channel, state, p2p).await.unwrap() }
+                })
+                .await;
+
             let _state = state.clone();
             registry
                 .register(net::SESSION_ALL, move |channel, p2p| {
@@ -386,13 +519,54 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
         }
     };
 
+    // Clock drift detection against consensus peers, and NTP if enabled
+    let clock_peers = [&args.consensus_peer_rpc[..], &args.consensus_seed_rpc[..]].concat();
+    let clock_sync = ClockSync::new(clock_peers, args.clock_sync);
+
+    // API tokens restricting the JSON-RPC methods, if any are configured
+    let cfg_path = get_config_path(args.config.clone(), CONFIG_FILE)?;
+    let rpc_auth = RpcAuth::from_config(&std::fs::read_to_string(cfg_path)?)?;
+    if rpc_auth.is_none() {
+        info!("No JSON-RPC tokens configured, all methods are open");
+    }
+    let rpc_limits = RpcLimits {
+        max_requests: args.rpc_max_requests,
+        max_connection_requests: args.rpc_max_connection_requests,
+        request_timeout: Duration::from_secs(args.rpc_request_timeout),
+        ..RpcLimits::default()
+    };
+
     // Initialize program state
-    let darkfid = Darkfid::new(state.clone(), consensus_p2p.clone(), sync_p2p.clone()).await?;
+    let darkfid = Darkfid::new(
+        state.clone(),
+        consensus_p2p.clone(),
+        sync_p2p.clone(),
+        clock_sync.clone(),
+        rpc_auth,
+        rpc_limits,
+    )
+    .await?;
     let darkfid = Arc::new(darkfid);
 
     // JSON-RPC server
     info!("Starting JSON-RPC server");
-    ex.spawn(listen_and_serve(args.rpc_listen, darkfid.clone())).detach();
+    match (args.rpc_tls_cert, args.rpc_tls_key) {
+        (Some(cert), Some(key)) => {
+            let cert = expand_path(&cert)?;
+            let key = expand_path(&key)?;
+            let _darkfid = darkfid.clone();
+            let _rpc_listen = args.rpc_listen.clone();
+            ex.spawn(async move { listen_and_serve_tls(_rpc_listen, &cert, &key, _darkfid).await })
+                .detach();
+        }
+        (None, None) => {
+            ex.spawn(listen_and_serve(args.rpc_listen, darkfid.clone())).detach();
+        }
+        _ => {
+            error!("Both rpc_tls_cert and rpc_tls_key need to be set to enable TLS");
+            return Err(Error::ConfigInvalid)
+        }
+    }
 
     info!("Starting sync P2P network");
     sync_p2p.clone().unwrap().start(ex.clone()).await?;
@@ -408,11 +582,22 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     info!("Waiting for sync P2P outbound connections");
     sync_p2p.clone().unwrap().wait_for_outbound().await?;
 
-    match block_sync_task(sync_p2p.clone().unwrap(), state.clone()).await {
+    match block_sync_task(sync_p2p.clone().unwrap(), state.clone(), ex.clone()).await {
         Ok(()) => *darkfid.synced.lock().await = true,
         Err(e) => error!("Failed syncing blockchain: {}", e),
     }
 
+    info!("Starting note scanner task");
+    ex.spawn(darkfid.scanner.clone().run()).detach();
+
+    info!("Starting transaction rebroadcast task");
+    ex.spawn(tx_rebroadcast_task(
+        sync_p2p.clone().unwrap(),
+        state.clone(),
+        args.tx_rebroadcast_interval,
+    ))
+    .detach();
+
     // Consensus protocol
     if args.consensus && *darkfid.synced.lock().await {
         info!("Starting consensus P2P network");
@@ -429,6 +614,9 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
         info!("Waiting for consensus P2P outbound connections");
         consensus_p2p.clone().unwrap().wait_for_outbound().await?;
 
+        info!("Starting clock sync task");
+        ex.spawn(clock_sync_task(clock_sync, CLOCK_SYNC_INTERVAL)).detach();
+
         info!("Starting consensus protocol task");
         ex.spawn(proposal_task(consensus_p2p.unwrap(), sync_p2p.unwrap(), state)).detach();
     } else {
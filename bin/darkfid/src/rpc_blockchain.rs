@@ -1,16 +1,26 @@
 use log::{debug, error};
+use pasta_curves::group::ff::PrimeField;
 use serde_json::{json, Value};
 
 use darkfi::{
-    crypto::merkle_node::MerkleNode,
+    consensus::state::MerkleLeaf,
+    crypto::{merkle_node::MerkleNode, token_id},
+    node::token_registry::{token_registry_id, IssuancePolicy, TokenRegistry},
     rpc::jsonrpc::{
-        ErrorCode::{InternalError, InvalidParams},
-        JsonError, JsonResponse, JsonResult,
+        server_error, ErrorCode::InvalidParams, JsonError, JsonResponse, JsonResult,
+        ServerErrorCode,
+    },
+    runtime::import::ChainStateReader,
+    util::{
+        expand_path,
+        serial::{deserialize, serialize},
     },
 };
 
 use super::Darkfid;
-use crate::{server_error, RpcError};
+
+/// Maximum number of slots that can be queried at once
+const MAX_SLOT_RANGE: u64 = 100;
 
 impl Darkfid {
     // RPCAPI:
@@ -33,12 +43,12 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching block by slot: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
         if blocks.is_empty() {
-            return server_error(RpcError::UnknownSlot, id)
+            return server_error(ServerErrorCode::UnknownSlot, id)
         }
 
         // TODO: Return block as JSON
@@ -46,6 +56,39 @@ impl Darkfid {
         JsonResponse::new(json!(true), id).into()
     }
 
+    // RPCAPI:
+    // Queries the blockchain database for the blocks in the given slot range,
+    // from the first slot inclusive to the second one exclusive. At most
+    // `MAX_SLOT_RANGE` slots can be queried at once.
+    // Returns the hashes of the found blocks upon success.
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_slot_range", "params": [0, 10], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": ["1a2b...", ...], "id": 1}
+    pub async fn get_slot_range(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 2 || !params[0].is_u64() || !params[1].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let start = params[0].as_u64().unwrap();
+        let end = params[1].as_u64().unwrap();
+        if end < start || end - start > MAX_SLOT_RANGE {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let blocks =
+            match self.validator_state.read().await.blockchain.get_blocks_by_slot_range(start, end)
+            {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Failed fetching blocks by slot range: {}", e);
+                    return JsonError::from_error(&e, id).into()
+                }
+            };
+
+        let hashes: Vec<String> =
+            blocks.iter().map(|block| block.header.headerhash().to_hex().to_string()).collect();
+        JsonResponse::new(json!(hashes), id).into()
+    }
+
     // RPCAPI:
     // Queries the blockchain database for all available merkle roots.
     // --> {"jsonrpc": "2.0", "method": "blockchain.merkle_roots", "params": [], "id": 1}
@@ -56,10 +99,145 @@ impl Darkfid {
                 Ok(v) => v,
                 Err(e) => {
                     error!("Failed getting merkle roots from rootstore: {}", e);
-                    return JsonError::new(InternalError, None, id).into()
+                    return JsonError::from_error(&e, id).into()
                 }
             };
 
         JsonResponse::new(json!(roots), id).into()
     }
+
+    // RPCAPI:
+    // Writes a snapshot of the canonical state at the last finalized block
+    // to the given file path. New nodes can bootstrap from it using the
+    // `--import-snapshot` flag.
+    // --> {"jsonrpc": "2.0", "method": "blockchain.export_snapshot", "params": ["/tmp/snap"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 1}
+    pub async fn export_snapshot(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let path = match expand_path(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(_) => return JsonError::new(InvalidParams, None, id).into(),
+        };
+
+        let snapshot = match self.validator_state.read().await.snapshot().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed taking state snapshot: {}", e);
+                return JsonError::from_error(&e, id).into()
+            }
+        };
+
+        if let Err(e) = snapshot.export(&path) {
+            error!("Failed exporting state snapshot: {}", e);
+            return JsonError::from_error(&e, id).into()
+        }
+
+        JsonResponse::new(json!(true), id).into()
+    }
+
+    // RPCAPI:
+    // Build a Merkle inclusion proof for a coin of the blockchain, given by its
+    // leaf position in the Merkle tree or by its base58 encoded commitment.
+    // Returns the coin's leaf position, the current Merkle root and the
+    // authentication path to it, so the coin can be spent by a prover that
+    // doesn't hold the node's state.
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_merkle_proof", "params": [3], "id": 1}
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_merkle_proof", "params": ["coin..."], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"position": 3, "root": "9a1b...", "path": ["f091uf1...", ...]}, "id": 1}
+    pub async fn get_merkle_proof(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !(params[0].is_u64() || params[0].is_string()) {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let leaf = match params[0].as_u64() {
+            Some(v) => MerkleLeaf::Position((v as usize).into()),
+            None => {
+                let bytes = match bs58::decode(params[0].as_str().unwrap()).into_vec() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!("get_merkle_proof(): Failed decoding base58 string: {}", e);
+                        return server_error(ServerErrorCode::ParseError, id)
+                    }
+                };
+
+                match deserialize(&bytes) {
+                    Ok(v) => MerkleLeaf::Coin(v),
+                    Err(e) => {
+                        error!("get_merkle_proof(): Failed deserializing coin: {}", e);
+                        return server_error(ServerErrorCode::ParseError, id)
+                    }
+                }
+            }
+        };
+
+        let (position, path, root) =
+            match self.validator_state.read().await.merkle_inclusion_proof(leaf).await {
+                Ok(v) => v,
+                Err(darkfi::Error::CoinNotFound) => {
+                    return server_error(ServerErrorCode::UnknownCoin, id)
+                }
+                Err(e) => {
+                    error!("get_merkle_proof(): Failed building Merkle proof: {}", e);
+                    return JsonError::from_error(&e, id).into()
+                }
+            };
+
+        let path: Vec<String> =
+            path.iter().map(|x| bs58::encode(serialize(x)).into_string()).collect();
+        let ret = json!({
+            "position": usize::from(position),
+            "root": bs58::encode(serialize(&root)).into_string(),
+            "path": path,
+        });
+        JsonResponse::new(ret, id).into()
+    }
+
+    // RPCAPI:
+    // Looks up a token in the token registry, by its base58 encoded token ID
+    // or by its ticker.
+    // Returns the token's metadata upon success, with the decimals its
+    // amounts are displayed with and how its supply is issued.
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_token", "params": ["WBTC"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"token_id": "9a1b...", "ticker": "WBTC", "decimals": 8, "issuance": {"bridged": "Bitcoin"}}, "id": 1}
+    pub async fn get_token(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let reader = ChainStateReader::new(
+            &self.validator_state.read().await.blockchain,
+            token_registry_id(),
+        );
+
+        // Tickers are too short to be token IDs
+        let query = params[0].as_str().unwrap();
+        let token = match token_id::parse_b58(query) {
+            Ok(token_id) => TokenRegistry::get_token(&reader, &token_id),
+            Err(_) => TokenRegistry::get_token_by_ticker(&reader, query),
+        };
+
+        let token = match token {
+            Ok(Some(v)) => v,
+            Ok(None) => return server_error(ServerErrorCode::UnknownToken, id),
+            Err(e) => {
+                error!("get_token(): Failed reading token registry: {}", e);
+                return JsonError::from_error(&e, id).into()
+            }
+        };
+
+        let issuance = match token.issuance {
+            IssuancePolicy::Bridged(network) => json!({ "bridged": network.to_string() }),
+            IssuancePolicy::Native { max_supply } => json!({ "native": max_supply }),
+        };
+        let ret = json!({
+            "token_id": bs58::encode(token.token_id.to_repr()).into_string(),
+            "ticker": token.ticker,
+            "decimals": token.decimals,
+            "issuance": issuance,
+        });
+        JsonResponse::new(ret, id).into()
+    }
 }
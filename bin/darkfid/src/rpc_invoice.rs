@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use log::error;
+use pasta_curves::group::ff::PrimeField;
+use serde_json::{json, Value};
+
+use darkfi::{
+    crypto::{invoice::Invoice, token_id},
+    node::client::TransactionRecipient,
+    rpc::jsonrpc::{
+        server_error, ErrorCode::InvalidParams, JsonError, JsonResponse, JsonResult,
+        ServerErrorCode,
+    },
+    util::time::Timestamp,
+    Error,
+};
+
+use super::Darkfid;
+
+impl Darkfid {
+    // RPCAPI:
+    // Creates an invoice requesting a given amount of some token, payable to
+    // a new diversified address of the wallet before it expires.
+    // Returns the encoded invoice, along with the payload of its QR code.
+    //
+    // * `token_id` -> ID of the token requested
+    // * `12345` -> Amount in `u64` requested
+    // * `memo` -> Memo the payment will carry, e.g. an order number
+    // * `3600` -> Seconds until the invoice expires
+    //
+    // --> {"jsonrpc": "2.0", "method": "invoice.create", "params": ["token_id", 12345, "memo", 3600], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"invoice": "drkinv1...", "qr": "DARKFI:DRKINV1..."}, "id": 1}
+    pub async fn create_invoice(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 4 ||
+            !params[0].is_string() ||
+            !params[1].is_u64() ||
+            !params[2].is_string() ||
+            !params[3].is_i64()
+        {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let token_id = match token_id::parse_b58(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("create_invoice(): Failed parsing Token ID from string: {}", e);
+                return server_error(ServerErrorCode::ParseError, id)
+            }
+        };
+
+        let amount = params[1].as_u64().unwrap();
+        let memo = params[2].as_str().unwrap().to_string();
+        let expiry = match params[3].as_i64().unwrap() {
+            v if v > 0 => Timestamp(Timestamp::current_time().0 + v),
+            _ => return JsonError::new(InvalidParams, None, id).into(),
+        };
+
+        match self.client.create_invoice(token_id, amount, memo, expiry).await {
+            Ok(invoice) => JsonResponse::new(
+                json!({"invoice": invoice.to_string(), "qr": invoice.to_qr_payload()}),
+                id,
+            )
+            .into(),
+            Err(e) => {
+                error!("create_invoice(): Failed creating invoice: {}", e);
+                JsonError::from_error(&e, id).into()
+            }
+        }
+    }
+
+    // RPCAPI:
+    // Decodes an invoice or the payload of its QR code, checking it was
+    // signed by its recipient. Expired invoices are returned as such.
+    //
+    // --> {"jsonrpc": "2.0", "method": "invoice.parse", "params": ["drkinv1..."], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"address": "1DarkFi...", "token_id": "...", "amount": 12345, "memo": "memo", "expiry": 1650000000, "expired": false}, "id": 1}
+    pub async fn parse_invoice(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let invoice = match Invoice::from_str(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("parse_invoice(): Failed parsing invoice: {}", e);
+                return server_error(ServerErrorCode::InvalidInvoice, id)
+            }
+        };
+
+        let request = &invoice.request;
+        let expired = matches!(invoice.verify(), Err(Error::InvoiceExpired));
+        JsonResponse::new(
+            json!({
+                "address": request.address.to_string(),
+                "token_id": bs58::encode(request.token_id.to_repr()).into_string(),
+                "amount": request.amount,
+                "memo": request.memo,
+                "expiry": request.expiry.0,
+                "expired": expired,
+            }),
+            id,
+        )
+        .into()
+    }
+
+    // RPCAPI:
+    // Pays an invoice, sending the requested amount to its address with its
+    // memo. Expired invoices are refused. Returns a transaction ID upon success.
+    //
+    // * `drkinv1...` -> Invoice, or the payload of its QR code
+    // * `10` -> Optional fee in `u64` paid for the transaction's inclusion, on top of the amount
+    //
+    // --> {"jsonrpc": "2.0", "method": "invoice.pay", "params": ["drkinv1...", 10], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "txID...", "id": 1}
+    pub async fn pay_invoice(&self, id: Value, params: &[Value]) -> JsonResult {
+        if !(params.len() == 1 || params.len() == 2) ||
+            !params[0].is_string() ||
+            (params.len() == 2 && !params[1].is_u64())
+        {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let fee = params.get(1).map_or(0, |v| v.as_u64().unwrap());
+
+        let invoice = match Invoice::from_str(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("pay_invoice(): Failed parsing invoice: {}", e);
+                return server_error(ServerErrorCode::InvalidInvoice, id)
+            }
+        };
+
+        if invoice.verify().is_err() {
+            error!("pay_invoice(): Invoice expired");
+            return server_error(ServerErrorCode::InvoiceExpired, id)
+        }
+
+        if !(*self.synced.lock().await) {
+            error!("pay_invoice(): Blockchain is not yet synced");
+            return server_error(ServerErrorCode::NotYetSynced, id)
+        }
+
+        let request = invoice.request;
+        let recipient = TransactionRecipient {
+            address: request.address,
+            value: request.amount,
+            memo: request.memo.into_bytes(),
+            lock_slot: 0,
+        };
+
+        let tx = match self
+            .client
+            .build_multi_transaction(
+                vec![recipient],
+                fee,
+                request.token_id,
+                false,
+                self.validator_state.read().await.state_machine.clone(),
+            )
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("pay_invoice(): Failed building transaction: {}", e);
+                return server_error(ServerErrorCode::TxBuildFail, id)
+            }
+        };
+
+        self.submit_tx(tx, id).await
+    }
+}
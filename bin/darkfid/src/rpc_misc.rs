@@ -23,4 +23,13 @@ impl Darkfid {
     pub async fn clock(&self, id: Value, _params: &[Value]) -> JsonResult {
         JsonResponse::new(json!(Timestamp::current_time()), id).into()
     }
+
+    // RPCAPI:
+    // Returns the last measured offset of the system clock against the
+    // consensus peers (and NTP, if enabled), in milliseconds.
+    // --> {"jsonrpc": "2.0", "method": "clock.offset", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"offset_ms": 120, "max_drift_ms": 4000, ...}, "id": 1}
+    pub async fn clock_offset(&self, id: Value, _params: &[Value]) -> JsonResult {
+        JsonResponse::new(self.clock_sync.get_info().await, id).into()
+    }
 }
@@ -4,13 +4,17 @@ use log::{error, warn};
 use serde_json::{json, Value};
 
 use darkfi::{
-    crypto::{address::Address, keypair::PublicKey, token_id},
-    rpc::jsonrpc::{ErrorCode::InvalidParams, JsonError, JsonResponse, JsonResult},
+    crypto::{address::PaymentAddress, token_id},
+    node::client::TransactionRecipient,
+    rpc::jsonrpc::{
+        server_error, ErrorCode::InvalidParams, JsonError, JsonResponse, JsonResult,
+        ServerErrorCode,
+    },
+    tx::Transaction,
     util::serial::serialize,
 };
 
 use super::Darkfid;
-use crate::{server_error, RpcError};
 
 impl Darkfid {
     // RPCAPI:
@@ -20,40 +24,35 @@ impl Darkfid {
     // * `dest_addr` -> Recipient's DarkFi address
     // * `token_id` -> ID of the token to send
     // * `12345` -> Amount in `u64` of the funds to send
+    // * `10` -> Optional fee in `u64` paid for the transaction's inclusion, on top of the amount, DRK only
     //
-    // --> {"jsonrpc": "2.0", "method": "tx.transfer", "params": ["dest_addr", "token_id", 12345], "id": 1}
+    // --> {"jsonrpc": "2.0", "method": "tx.transfer", "params": ["dest_addr", "token_id", 12345, 10], "id": 1}
     // <-- {"jsonrpc": "2.0", "result": "txID...", "id": 1}
     pub async fn transfer(&self, id: Value, params: &[Value]) -> JsonResult {
-        if params.len() != 3 ||
+        if !(params.len() == 3 || params.len() == 4) ||
             !params[0].is_string() ||
             !params[1].is_string() ||
-            !params[2].is_u64()
+            !params[2].is_u64() ||
+            (params.len() == 4 && !params[3].is_u64())
         {
             return JsonError::new(InvalidParams, None, id).into()
         }
 
         if !(*self.synced.lock().await) {
             error!("transfer(): Blockchain is not yet synced");
-            return server_error(RpcError::NotYetSynced, id)
+            return server_error(ServerErrorCode::NotYetSynced, id)
         }
 
         let address = params[0].as_str().unwrap();
         let token = params[1].as_str().unwrap();
         let amount = params[2].as_u64().unwrap();
+        let fee = params.get(3).map_or(0, |v| v.as_u64().unwrap());
 
-        let address = match Address::from_str(address) {
+        let address = match PaymentAddress::from_str(address) {
             Ok(v) => v,
             Err(e) => {
                 error!("transfer(): Failed parsing address from string: {}", e);
-                return server_error(RpcError::InvalidAddressParam, id)
-            }
-        };
-
-        let pubkey = match PublicKey::try_from(address) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("transfer(): Failed parsing PublicKey from Address: {}", e);
-                return server_error(RpcError::ParseError, id)
+                return server_error(ServerErrorCode::InvalidAddressParam, id)
             }
         };
 
@@ -61,15 +60,16 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("transfer(): Failed parsing Token ID from string: {}", e);
-                return server_error(RpcError::ParseError, id)
+                return server_error(ServerErrorCode::ParseError, id)
             }
         };
 
         let tx = match self
             .client
             .build_transaction(
-                pubkey,
+                address,
                 amount,
+                fee,
                 token_id,
                 false,
                 self.validator_state.read().await.state_machine.clone(),
@@ -79,16 +79,248 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("transfer(): Failed building transaction: {}", e);
-                return server_error(RpcError::TxBuildFail, id)
+                return server_error(ServerErrorCode::TxBuildFail, id)
+            }
+        };
+
+        self.submit_tx(tx, id).await
+    }
+
+    // RPCAPI:
+    // Transfer some token to multiple recipients in a single transaction,
+    // each payment carrying its own memo. Returns a transaction ID upon success.
+    //
+    // * `token_id` -> ID of the token to send
+    // * `dest_addr` -> Recipient's DarkFi address
+    // * `12345` -> Amount in `u64` of the funds to send to the recipient
+    // * `memo` -> Optional memo for the recipient, encrypted in their note
+    // * `10` -> Optional fee in `u64` paid for the transaction's inclusion, DRK only
+    //
+    // --> {"jsonrpc": "2.0", "method": "tx.transfer_many", "params": ["token_id", [["dest_addr", 12345, "memo"], ["dest_addr", 678]], 10], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "txID...", "id": 1}
+    pub async fn transfer_many(&self, id: Value, params: &[Value]) -> JsonResult {
+        if !(params.len() == 2 || params.len() == 3) ||
+            !params[0].is_string() ||
+            !params[1].is_array() ||
+            (params.len() == 3 && !params[2].is_u64())
+        {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let fee = params.get(2).map_or(0, |v| v.as_u64().unwrap());
+
+        let mut recipients = vec![];
+        for recipient in params[1].as_array().unwrap() {
+            let recipient = match recipient.as_array() {
+                Some(v) if v.len() == 2 || v.len() == 3 => v,
+                _ => return JsonError::new(InvalidParams, None, id).into(),
+            };
+
+            if !recipient[0].is_string() ||
+                !recipient[1].is_u64() ||
+                (recipient.len() == 3 && !recipient[2].is_string())
+            {
+                return JsonError::new(InvalidParams, None, id).into()
+            }
+
+            let address = match PaymentAddress::from_str(recipient[0].as_str().unwrap()) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("transfer_many(): Failed parsing address from string: {}", e);
+                    return server_error(ServerErrorCode::InvalidAddressParam, id)
+                }
+            };
+
+            let value = recipient[1].as_u64().unwrap();
+            let memo = match recipient.get(2) {
+                Some(v) => v.as_str().unwrap().as_bytes().to_vec(),
+                None => vec![],
+            };
+
+            recipients.push(TransactionRecipient { address, value, memo, lock_slot: 0 });
+        }
+
+        if !(*self.synced.lock().await) {
+            error!("transfer_many(): Blockchain is not yet synced");
+            return server_error(ServerErrorCode::NotYetSynced, id)
+        }
+
+        let token_id = match token_id::parse_b58(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("transfer_many(): Failed parsing Token ID from string: {}", e);
+                return server_error(ServerErrorCode::ParseError, id)
+            }
+        };
+
+        let tx = match self
+            .client
+            .build_multi_transaction(
+                recipients,
+                fee,
+                token_id,
+                false,
+                self.validator_state.read().await.state_machine.clone(),
+            )
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("transfer_many(): Failed building transaction: {}", e);
+                return server_error(ServerErrorCode::TxBuildFail, id)
+            }
+        };
+
+        self.submit_tx(tx, id).await
+    }
+
+    // RPCAPI:
+    // Lock some of the wallet's funds until the given slot, by paying them
+    // to the wallet's main address in a time-locked coin. Locked coins can't
+    // be spent before the slot, e.g. while staked. Returns a transaction ID
+    // upon success.
+    //
+    // * `token_id` -> ID of the token to lock
+    // * `12345` -> Amount in `u64` of the funds to lock
+    // * `5000` -> Slot until which the funds are locked
+    // * `10` -> Optional fee in `u64` paid for the transaction's inclusion, on top of the amount, DRK only
+    //
+    // --> {"jsonrpc": "2.0", "method": "tx.lock", "params": ["token_id", 12345, 5000, 10], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "txID...", "id": 1}
+    pub async fn lock(&self, id: Value, params: &[Value]) -> JsonResult {
+        if !(params.len() == 3 || params.len() == 4) ||
+            !params[0].is_string() ||
+            !params[1].is_u64() ||
+            !params[2].is_u64() ||
+            (params.len() == 4 && !params[3].is_u64())
+        {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let amount = params[1].as_u64().unwrap();
+        let lock_slot = params[2].as_u64().unwrap();
+        let fee = params.get(3).map_or(0, |v| v.as_u64().unwrap());
+
+        if !(*self.synced.lock().await) {
+            error!("lock(): Blockchain is not yet synced");
+            return server_error(ServerErrorCode::NotYetSynced, id)
+        }
+
+        let token_id = match token_id::parse_b58(params[0].as_str().unwrap()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("lock(): Failed parsing Token ID from string: {}", e);
+                return server_error(ServerErrorCode::ParseError, id)
+            }
+        };
+
+        let tx = match self
+            .client
+            .build_lock_transaction(
+                amount,
+                fee,
+                token_id,
+                lock_slot,
+                self.validator_state.read().await.state_machine.clone(),
+            )
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("lock(): Failed building transaction: {}", e);
+                return server_error(ServerErrorCode::TxBuildFail, id)
+            }
+        };
+
+        self.submit_tx(tx, id).await
+    }
+
+    // RPCAPI:
+    // Submit the equivocation evidence observed by the node, to get the
+    // leaders that signed two different proposals for the same slot slashed.
+    // The wallet's DRK funds only pay the fee. Returns a transaction ID upon
+    // success.
+    //
+    // * `10` -> Fee in `u64` DRK paid for the transaction's inclusion
+    //
+    // --> {"jsonrpc": "2.0", "method": "tx.submit_evidence", "params": [10], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "txID...", "id": 1}
+    pub async fn submit_evidence(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let fee = params[0].as_u64().unwrap();
+
+        if !(*self.synced.lock().await) {
+            error!("submit_evidence(): Blockchain is not yet synced");
+            return server_error(ServerErrorCode::NotYetSynced, id)
+        }
+
+        let (evidence, state_machine) = {
+            let state = self.validator_state.read().await;
+            (state.evidence.clone(), state.state_machine.clone())
+        };
+
+        if evidence.is_empty() {
+            return server_error(ServerErrorCode::NotFound, id)
+        }
+
+        let tx = match self
+            .client
+            .build_evidence_transaction(evidence.clone(), fee, state_machine)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("submit_evidence(): Failed building transaction: {}", e);
+                return server_error(ServerErrorCode::TxBuildFail, id)
             }
         };
 
+        self.validator_state.write().await.evidence.retain(|e| !evidence.contains(e));
+        self.submit_tx(tx, id).await
+    }
+
+    // RPCAPI:
+    // Suggest a fee rate for a transaction to get included within the given
+    // number of slots, based on the fees of the transactions in recent blocks.
+    // Returns the fee per kilobyte of serialized transaction, so the fee to pay
+    // is the rate times the transaction's size in bytes, divided by 1000.
+    //
+    // --> {"jsonrpc": "2.0", "method": "tx.estimate_fee", "params": [3], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": 1500, "id": 1}
+    pub async fn estimate_fee(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let target_slots = params[0].as_u64().unwrap();
+        if target_slots == 0 {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        match self.validator_state.read().await.estimate_fee(target_slots) {
+            Ok(v) => JsonResponse::new(json!(v), id).into(),
+            Err(e) => {
+                error!("estimate_fee(): Failed estimating fee: {}", e);
+                JsonError::from_error(&e, id).into()
+            }
+        }
+    }
+
+    /// Keep track of a locally built transaction, broadcast it, and
+    /// return its ID as the JSON-RPC response.
+    pub(super) async fn submit_tx(&self, tx: Transaction, id: Value) -> JsonResult {
+        // Keep track of the transaction so it gets rebroadcast until included
+        self.validator_state.write().await.append_local_tx(tx.clone()).await;
+
         if let Some(sync_p2p) = &self.sync_p2p {
             match sync_p2p.broadcast(tx.clone()).await {
                 Ok(()) => {}
                 Err(e) => {
-                    error!("transfer(): Failed broadcasting transaction: {}", e);
-                    return server_error(RpcError::TxBroadcastFail, id)
+                    error!("submit_tx(): Failed broadcasting transaction: {}", e);
+                    return server_error(ServerErrorCode::TxBroadcastFail, id)
                 }
             }
         } else {
@@ -6,20 +6,25 @@ use serde_json::{json, Value};
 
 use darkfi::{
     crypto::{
-        address::Address,
+        address::PaymentAddress,
+        coin::Coin,
         keypair::{Keypair, PublicKey, SecretKey},
+        note::{EncryptedNote, Note},
         token_id,
     },
     node::State,
     rpc::jsonrpc::{
-        ErrorCode::{InternalError, InvalidParams, ParseError},
-        JsonError, JsonResponse, JsonResult,
+        server_error,
+        ErrorCode::{InvalidParams, ParseError},
+        JsonError, JsonResponse, JsonResult, ServerErrorCode,
     },
     util::serial::{deserialize, serialize},
 };
 
 use super::Darkfid;
-use crate::{server_error, RpcError};
+
+/// Most history entries returned in a page by `wallet.get_history`
+const MAX_HISTORY_PAGE: u64 = 100;
 
 impl Darkfid {
     // RPCAPI:
@@ -31,7 +36,23 @@ impl Darkfid {
             Ok(a) => JsonResponse::new(json!(a.to_string()), id).into(),
             Err(e) => {
                 error!("Failed creating keypair: {}", e);
-                server_error(RpcError::Keygen, id)
+                server_error(ServerErrorCode::Keygen, id)
+            }
+        }
+    }
+
+    // RPCAPI:
+    // Derives a new diversified address of the default keypair. Diversified
+    // addresses can't be linked to each other, while coins sent to any of
+    // them are received by the wallet.
+    // --> {"jsonrpc": "2.0", "method": "wallet.new_diversified_addr", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "1DarkFi...", "id": 1}
+    pub async fn new_diversified_addr(&self, id: Value, _params: &[Value]) -> JsonResult {
+        match self.client.new_diversified_address().await {
+            Ok(a) => JsonResponse::new(json!(a.to_string()), id).into(),
+            Err(e) => {
+                error!("Failed deriving diversified address: {}", e);
+                server_error(ServerErrorCode::Keygen, id)
             }
         }
     }
@@ -49,7 +70,7 @@ impl Darkfid {
         let mut fetch_all = false;
         for i in params {
             if !i.is_i64() {
-                return server_error(RpcError::Nan, id)
+                return server_error(ServerErrorCode::Nan, id)
             }
 
             if i.as_i64() == Some(-1) {
@@ -58,7 +79,7 @@ impl Darkfid {
             }
 
             if i.as_i64() < Some(-1) {
-                return server_error(RpcError::LessThanNegOne, id)
+                return server_error(ServerErrorCode::LessThanNegOne, id)
             }
         }
 
@@ -66,21 +87,22 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching keypairs: {}", e);
-                return server_error(RpcError::KeypairFetch, id)
+                return server_error(ServerErrorCode::KeypairFetch, id)
             }
         };
 
         let mut ret = vec![];
 
         if fetch_all {
-            ret = keypairs.iter().map(|x| Some(Address::from(x.public).to_string())).collect()
+            ret =
+                keypairs.iter().map(|x| Some(PaymentAddress::new(&x.secret).to_string())).collect()
         } else {
             for i in params {
                 // This cast is safe on 64bit since we've already sorted out
                 // all negative cases above.
                 let idx = i.as_i64().unwrap() as usize;
                 if let Some(kp) = keypairs.get(idx) {
-                    ret.push(Some(Address::from(kp.public).to_string()));
+                    ret.push(Some(PaymentAddress::new(&kp.secret).to_string()));
                 } else {
                     ret.push(None)
                 }
@@ -104,7 +126,7 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching keypairs: {}", e);
-                return server_error(RpcError::KeypairFetch, id)
+                return server_error(ServerErrorCode::KeypairFetch, id)
             }
         };
 
@@ -112,7 +134,49 @@ impl Darkfid {
             return JsonResponse::new(json!(kp.secret.to_bytes()), id).into()
         }
 
-        server_error(RpcError::KeypairNotFound, id)
+        server_error(ServerErrorCode::KeypairNotFound, id)
+    }
+
+    // RPCAPI:
+    // Exports the mnemonic the wallet's keys are derived from, which is
+    // all that's needed to restore them, except for imported keypairs.
+    // --> {"jsonrpc": "2.0", "method": "wallet.export_mnemonic", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "word1 word2 ...", "id": 1}
+    pub async fn export_mnemonic(&self, id: Value, _params: &[Value]) -> JsonResult {
+        match self.client.get_mnemonic().await {
+            Ok(v) => JsonResponse::new(json!(v), id).into(),
+            Err(e) => {
+                error!("Failed fetching wallet mnemonic: {}", e);
+                JsonError::from_error(&e, id).into()
+            }
+        }
+    }
+
+    // RPCAPI:
+    // Exports the incoming viewing key of the given keypair index.
+    // The view key decrypts the notes of incoming coins, e.g. for an
+    // auditor using `wallet.decrypt_note`, but can't be used to spend them.
+    // --> {"jsonrpc": "2.0", "method": "wallet.export_view_key", "params": [0], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "viewkey...", "id": 1}
+    pub async fn export_view_key(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let keypairs = match self.client.get_keypairs().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed fetching keypairs: {}", e);
+                return server_error(ServerErrorCode::KeypairFetch, id)
+            }
+        };
+
+        if let Some(kp) = keypairs.get(params[0].as_u64().unwrap() as usize) {
+            let view_key = bs58::encode(kp.secret.view_key().to_bytes()).into_string();
+            return JsonResponse::new(json!(view_key), id).into()
+        }
+
+        server_error(ServerErrorCode::KeypairNotFound, id)
     }
 
     // RPCAPI:
@@ -129,7 +193,7 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed parsing secret key from string: {}", e);
-                return server_error(RpcError::InvalidKeypair, id)
+                return server_error(ServerErrorCode::InvalidKeypair, id)
             }
         };
 
@@ -137,19 +201,19 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed parsing secret key from string: {}", e);
-                return server_error(RpcError::InvalidKeypair, id)
+                return server_error(ServerErrorCode::InvalidKeypair, id)
             }
         };
 
         let public = PublicKey::from_secret(secret);
         let keypair = Keypair { secret, public };
-        let address = Address::from(public).to_string();
+        let address = PaymentAddress::new(&secret).to_string();
 
         match self.client.put_keypair(&keypair).await {
             Ok(()) => {}
             Err(e) => {
                 error!("Failed inserting keypair into wallet: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
@@ -172,12 +236,12 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching keypairs: {}", e);
-                return server_error(RpcError::KeypairFetch, id)
+                return server_error(ServerErrorCode::KeypairFetch, id)
             }
         };
 
         if keypairs.len() as u64 != idx - 1 {
-            return server_error(RpcError::KeypairNotFound, id)
+            return server_error(ServerErrorCode::KeypairNotFound, id)
         }
 
         let kp = keypairs[idx as usize];
@@ -185,7 +249,7 @@ impl Darkfid {
             Ok(()) => {}
             Err(e) => {
                 error!("Failed setting default keypair: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
@@ -193,31 +257,154 @@ impl Darkfid {
     }
 
     // RPCAPI:
-    // Queries the wallet for known tokens with active balances.
-    // Returns a map of balances, indexed by the token ID.
+    // Queries the wallet for the balances of its known tokens at the
+    // current slot, indexed by the token ID. `confirmed` is spendable,
+    // `unconfirmed` is spent by transactions not in a block yet, and
+    // `locked` is time-locked past the current slot.
     // --> {"jsonrpc": "2.0", "method": "wallet.get_balances", "params": [], "id": 1}
-    // <-- {"jsonrpc": "2.0", "result": [{"1Foobar...": 100}, {...}]", "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"1Foobar...": {"confirmed": 100, "unconfirmed": 0, "locked": 20}}, "id": 1}
     pub async fn get_balances(&self, id: Value, _params: &[Value]) -> JsonResult {
-        let balances = match self.client.get_balances().await {
+        let slot = self.validator_state.read().await.current_slot();
+        let balances = match self.client.get_token_balances(slot).await {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching balances from wallet: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
-        // k: token_id, v: [amount]
-        let mut ret: FxHashMap<String, u64> = FxHashMap::default();
-
-        for balance in balances.list {
+        let mut ret: FxHashMap<String, Value> = FxHashMap::default();
+        for balance in balances {
             let token_id = bs58::encode(balance.token_id.to_repr()).into_string();
-            let mut amount = balance.value;
+            ret.insert(
+                token_id,
+                json!({
+                    "confirmed": balance.confirmed,
+                    "unconfirmed": balance.unconfirmed,
+                    "locked": balance.locked,
+                }),
+            );
+        }
+
+        JsonResponse::new(json!(ret), id).into()
+    }
+
+    // RPCAPI:
+    // Queries the wallet history of received and spent coins, newest
+    // first. Takes the cursor returned with the previous page, or `null`
+    // for the latest entries, and the number of entries to return, up to
+    // 100. The cursor of the next page is `null` once the history is done.
+    // --> {"jsonrpc": "2.0", "method": "wallet.get_history", "params": [null, 20], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"entries": [{"id": 12, "coin": "Coin...", "kind": "receive", "value": 100, "token_id": "1Foobar...", "slot": 42}, ...], "next_cursor": 11}, "id": 1}
+    pub async fn get_history(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 2 ||
+            !(params[0].is_null() || params[0].is_u64()) ||
+            !params[1].is_u64() ||
+            params[1].as_u64().unwrap() == 0
+        {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let cursor = params[0].as_u64();
+        let limit = params[1].as_u64().unwrap().min(MAX_HISTORY_PAGE) as u32;
+
+        let entries = match self.client.get_history(cursor, limit).await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed fetching history from wallet: {}", e);
+                return JsonError::from_error(&e, id).into()
+            }
+        };
+
+        // A short page is the last one
+        let next_cursor = match entries.last() {
+            Some(entry) if entries.len() == limit as usize => Some(entry.id),
+            _ => None,
+        };
+
+        let entries: Vec<Value> = entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.id,
+                    "coin": bs58::encode(serialize(&entry.coin)).into_string(),
+                    "kind": entry.kind.as_str(),
+                    "value": entry.value,
+                    "token_id": bs58::encode(entry.token_id.to_repr()).into_string(),
+                    "slot": entry.slot,
+                })
+            })
+            .collect();
+
+        JsonResponse::new(json!({ "entries": entries, "next_cursor": next_cursor }), id).into()
+    }
+
+    // RPCAPI:
+    // Queries the wallet for one of its coins, given its base58 encoding,
+    // spent or not. Returns `null` for coins the wallet doesn't own.
+    // --> {"jsonrpc": "2.0", "method": "wallet.get_coin", "params": ["Coin..."], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"value": 100, "token_id": "1Foobar...", "lock_slot": 0, "memo": "", "is_spent": true, "received_slot": 42, "spent_slot": 50}, "id": 1}
+    pub async fn get_coin(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let coin: Coin = match bs58::decode(params[0].as_str().unwrap()).into_vec() {
+            Ok(bytes) => match deserialize(&bytes) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("get_coin(): Failed deserializing coin: {}", e);
+                    return JsonError::new(ParseError, None, id).into()
+                }
+            },
+            Err(e) => {
+                error!("get_coin(): Failed decoding base58 string: {}", e);
+                return JsonError::new(ParseError, None, id).into()
+            }
+        };
+
+        let info = match self.client.get_coin(&coin).await {
+            Ok(Some(v)) => v,
+            Ok(None) => return JsonResponse::new(Value::Null, id).into(),
+            Err(e) => {
+                error!("Failed fetching coin from wallet: {}", e);
+                return JsonError::from_error(&e, id).into()
+            }
+        };
+
+        let note = &info.own_coin.note;
+        let ret = json!({
+            "value": note.value,
+            "token_id": bs58::encode(note.token_id.to_repr()).into_string(),
+            "lock_slot": note.lock_slot,
+            "memo": String::from_utf8_lossy(&note.memo),
+            "is_spent": info.is_spent,
+            "received_slot": info.received_slot,
+            "spent_slot": info.spent_slot,
+        });
+        JsonResponse::new(ret, id).into()
+    }
 
-            if let Some(prev) = ret.get(&token_id) {
-                amount += prev;
+    // RPCAPI:
+    // Queries the wallet for its time-locked funds, not spendable at the
+    // current slot. Returns a map of `[amount, lock_slot]` pairs, indexed
+    // by the token ID.
+    // --> {"jsonrpc": "2.0", "method": "wallet.get_locked_balances", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"1Foobar...": [[100, 5000], [20, 6000]]}, "id": 1}
+    pub async fn get_locked_balances(&self, id: Value, _params: &[Value]) -> JsonResult {
+        let balances = match self.client.get_balances().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed fetching balances from wallet: {}", e);
+                return JsonError::from_error(&e, id).into()
             }
+        };
+        let slot = self.validator_state.read().await.current_slot();
 
-            ret.insert(token_id, amount);
+        let mut ret: FxHashMap<String, Vec<(u64, u64)>> = FxHashMap::default();
+        for balance in balances.locked(slot) {
+            let token_id = bs58::encode(balance.token_id.to_repr()).into_string();
+            ret.entry(token_id).or_default().push((balance.value, balance.lock_slot));
         }
 
         JsonResponse::new(json!(ret), id).into()
@@ -252,7 +439,7 @@ impl Darkfid {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed fetching coins by valtok from wallet: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
@@ -287,11 +474,15 @@ impl Darkfid {
 
     // RPCAPI:
     // Try to decrypt a given encrypted note with the secret keys
-    // found in the wallet.
-    // --> {"jsonrpc": "2.0", "method": "wallet.decrypt_note", params": [ciphertext], "id": 1}
+    // found in the wallet, or with the given base58 encoded view key.
+    // --> {"jsonrpc": "2.0", "method": "wallet.decrypt_note", params": [ciphertext, "viewkey..."], "id": 1}
     // <-- {"jsonrpc": "2.0", "result": "base58_encoded_plain_note", "id": 1}
     pub async fn decrypt_note(&self, id: Value, params: &[Value]) -> JsonResult {
-        if params.len() != 1 || !params[0].is_string() {
+        if params.is_empty() ||
+            params.len() > 2 ||
+            !params[0].is_string() ||
+            (params.len() == 2 && !params[1].is_string())
+        {
             return JsonError::new(InvalidParams, None, id).into()
         }
 
@@ -303,29 +494,121 @@ impl Darkfid {
             }
         };
 
-        let enc_note = match deserialize(&bytes) {
+        let enc_note: EncryptedNote = match deserialize(&bytes) {
             Ok(v) => v,
             Err(e) => {
                 error!("decrypt_note(): Failed deserializing bytes into EncryptedNote: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
-        let keypairs = match self.client.get_keypairs().await {
+        if params.len() == 2 {
+            let view_key = match bs58::decode(params[1].as_str().unwrap()).into_vec() {
+                Ok(v) if v.len() == 32 => SecretKey::from_bytes(v.try_into().unwrap()),
+                _ => Err(darkfi::Error::SecretKeyFromBytes),
+            };
+
+            let view_key = match view_key {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("decrypt_note(): Failed parsing view key: {}", e);
+                    return JsonError::new(ParseError, None, id).into()
+                }
+            };
+
+            let note: Note = match enc_note.decrypt(&view_key) {
+                Ok(v) => v,
+                Err(_) => return server_error(ServerErrorCode::DecryptionFailed, id),
+            };
+
+            let s = bs58::encode(&serialize(&note)).into_string();
+            return JsonResponse::new(json!(s), id).into()
+        }
+
+        let secrets = match self.client.get_scanning_secrets().await {
             Ok(v) => v,
             Err(e) => {
-                error!("decrypt_note(): Failed fetching keypairs: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                error!("decrypt_note(): Failed fetching secret keys: {}", e);
+                return JsonError::from_error(&e, id).into()
             }
         };
 
-        for kp in keypairs {
-            if let Some(note) = State::try_decrypt_note(&enc_note, kp.secret) {
+        for secret in secrets {
+            if let Some(note) = State::try_decrypt_note(&enc_note, secret) {
                 let s = bs58::encode(&serialize(&note)).into_string();
                 return JsonResponse::new(json!(s), id).into()
             }
         }
 
-        server_error(RpcError::DecryptionFailed, id)
+        server_error(ServerErrorCode::DecryptionFailed, id)
+    }
+
+    // RPCAPI:
+    // Sets the wallet birthday, the slot it was created at. Blocks before
+    // it are not scanned for the wallet's notes.
+    // Returns `true` upon success.
+    // --> {"jsonrpc": "2.0", "method": "wallet.set_birthday", "params": [5000], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 1}
+    pub async fn set_birthday(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        if let Err(e) = self.client.wallet.set_birthday_slot(params[0].as_u64().unwrap()).await {
+            error!("Failed setting wallet birthday: {}", e);
+            return JsonError::from_error(&e, id).into()
+        }
+
+        JsonResponse::new(json!(true), id).into()
+    }
+
+    // RPCAPI:
+    // Rescans the finalized blocks from the given slot on for the wallet's
+    // notes, in the background. Slots before the wallet birthday are
+    // skipped. Progress is reported by `wallet.rescan_status`.
+    // Returns `true` once the rescan is queued.
+    // --> {"jsonrpc": "2.0", "method": "wallet.rescan", "params": [0], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 1}
+    pub async fn rescan(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        if let Err(e) = self.scanner.rescan(params[0].as_u64().unwrap()).await {
+            error!("Failed queueing wallet rescan: {}", e);
+            return JsonError::from_error(&e, id).into()
+        }
+
+        JsonResponse::new(json!(true), id).into()
+    }
+
+    // RPCAPI:
+    // Queries the progress of the current or last wallet scan, along with
+    // the wallet birthday and the slot the wallet is scanned up to.
+    // --> {"jsonrpc": "2.0", "method": "wallet.rescan_status", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"running": true, "from_slot": 0, "to_slot": 900, "scanned_slot": 450, "coins_found": 3, "birthday_slot": 0, "synced_slot": 880}, "id": 1}
+    pub async fn rescan_status(&self, id: Value, _params: &[Value]) -> JsonResult {
+        let progress = self.scanner.progress().await;
+
+        let wallet = &self.client.wallet;
+        let (birthday_slot, synced_slot) =
+            match (wallet.get_birthday_slot().await, wallet.get_scanned_slot().await) {
+                (Ok(birthday), Ok(scanned)) => (birthday, scanned),
+                (Err(e), _) | (_, Err(e)) => {
+                    error!("Failed fetching wallet scan state: {}", e);
+                    return JsonError::from_error(&e, id).into()
+                }
+            };
+
+        let ret = json!({
+            "running": progress.running,
+            "from_slot": progress.from_slot,
+            "to_slot": progress.to_slot,
+            "scanned_slot": progress.scanned_slot,
+            "coins_found": progress.coins_found,
+            "birthday_slot": birthday_slot,
+            "synced_slot": synced_slot,
+        });
+        JsonResponse::new(ret, id).into()
     }
 }
@@ -12,7 +12,7 @@ use darkfi::{
     cli_desc,
     crypto::{
         burn_proof::{create_burn_proof, verify_burn_proof},
-        keypair::{PublicKey, SecretKey},
+        keypair::SecretKey,
         mint_proof::{create_mint_proof, verify_mint_proof},
         note::{EncryptedNote, Note},
         proof::{ProvingKey, VerifyingKey},
@@ -166,13 +166,7 @@ async fn init_swap(
 
     // Fetch our default address
     let our_addr = rpc.wallet_address().await?;
-    let our_pubk = match PublicKey::try_from(our_addr) {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("Error converting our address into PublicKey: {}", e);
-            exit(1);
-        }
-    };
+    let our_pubk = our_addr.public;
 
     // Build ZK proving keys
     let pb = progress_bar("Building proving key for the Mint contract");
@@ -203,6 +197,7 @@ async fn init_swap(
         recv_serial,
         spend_hook,
         user_data,
+        0,
         recv_coin_blind,
         our_pubk,
     )?;
@@ -236,6 +231,7 @@ async fn init_swap(
         spend_hook,
         user_data,
         user_data_blind,
+        coin.note.lock_slot,
         coin.note.coin_blind,
         coin.secret,
         coin.leaf_position,
@@ -252,10 +248,11 @@ async fn init_swap(
         coin_blind: recv_coin_blind,
         value_blind: recv_value_blind,
         token_blind: recv_token_blind,
+        lock_slot: 0,
         // Here we store our secret key we used for signing
         memo: signature_secret.to_bytes().to_vec(),
     };
-    let encrypted_note = note.encrypt(&our_pubk)?;
+    let encrypted_note = note.encrypt(&our_addr.view_public)?;
 
     // Pack proofs together with pedersen commitment openings so
     // counterparty can verify correctness.
@@ -441,7 +438,17 @@ async fn join(endpoint: Url, d0: PartialSwapData, d1: PartialSwapData) -> Result
     };
     let outputs = vec![output0, output1];
 
-    let partial_tx = PartialTransaction { clear_inputs: vec![], inputs, outputs };
+    let partial_tx = PartialTransaction {
+        clear_inputs: vec![],
+        inputs,
+        outputs,
+        deploys: vec![],
+        calls: vec![],
+        gas_limit: 0,
+        fee: 0,
+        fee_token_blind: None,
+        escrow: None,
+    };
     let mut unsigned_tx_data = vec![];
     partial_tx.encode(&mut unsigned_tx_data)?;
 
@@ -502,7 +509,18 @@ async fn join(endpoint: Url, d0: PartialSwapData, d1: PartialSwapData) -> Result
         exit(1);
     }
 
-    let tx = Transaction { clear_inputs: vec![], inputs, outputs: partial_tx.outputs };
+    let tx = Transaction {
+        clear_inputs: vec![],
+        inputs,
+        outputs: partial_tx.outputs,
+        deploys: partial_tx.deploys,
+        calls: partial_tx.calls,
+        call_signatures: vec![],
+        gas_limit: partial_tx.gas_limit,
+        fee: partial_tx.fee,
+        fee_token_blind: partial_tx.fee_token_blind,
+        escrow: partial_tx.escrow,
+    };
     Ok(tx)
 }
 
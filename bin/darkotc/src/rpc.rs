@@ -4,7 +4,7 @@ use serde_json::json;
 
 use darkfi::{
     crypto::{
-        address::Address,
+        address::PaymentAddress,
         merkle_node::MerkleNode,
         note::{EncryptedNote, Note},
         OwnCoin,
@@ -32,7 +32,7 @@ impl Rpc {
 
         for i in rep.as_object().unwrap().keys() {
             if i == token_id {
-                if let Some(balance) = rep[i].as_u64() {
+                if let Some(balance) = rep[i]["confirmed"].as_u64() {
                     return Ok(balance)
                 }
 
@@ -45,7 +45,7 @@ impl Rpc {
     }
 
     /// Fetch default wallet address from the darkfid RPC endpoint.
-    pub async fn wallet_address(&self) -> Result<Address> {
+    pub async fn wallet_address(&self) -> Result<PaymentAddress> {
         let req = JsonRequest::new("wallet.get_addrs", json!([0_i64]));
         let rep = self.rpc_client.request(req).await?;
 
@@ -54,7 +54,7 @@ impl Rpc {
             exit(1);
         }
 
-        match Address::from_str(rep[0].as_str().unwrap()) {
+        match PaymentAddress::from_str(rep[0].as_str().unwrap()) {
             Ok(v) => Ok(v),
             Err(e) => {
                 eprintln!(
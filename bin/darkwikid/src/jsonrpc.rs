@@ -56,7 +56,7 @@ impl JsonRpcInterface {
 
         if let Err(e) = res {
             error!("Failed to update: {}", e);
-            return JsonError::new(ErrorCode::InternalError, None, id).into()
+            return JsonError::from_error(&e, id).into()
         }
 
         let response = self.receiver.recv().await.unwrap();
@@ -76,7 +76,7 @@ impl JsonRpcInterface {
 
         if let Err(e) = res {
             error!("Failed to restore: {}", e);
-            return JsonError::new(ErrorCode::InternalError, None, id).into()
+            return JsonError::from_error(&e, id).into()
         }
 
         let response = self.receiver.recv().await.unwrap();
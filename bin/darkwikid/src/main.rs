@@ -28,7 +28,7 @@ use darkfi::{
     raft::{NetMsg, ProtocolRaft, Raft, RaftSettings},
     rpc::server::listen_and_serve,
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         expand_path,
         file::{load_file, load_json_file, save_file, save_json_file},
         path::get_config_path,
@@ -13,7 +13,7 @@ use darkfi::{
     rpc::{client::RpcClient, jsonrpc::JsonRequest},
     util::{
         cli::{get_log_config, get_log_level, progress_bar},
-        encode_base10, NetworkName,
+        decode_token_amount, encode_token_amount, NetworkName,
     },
     Result,
 };
@@ -30,6 +30,10 @@ struct Args {
     /// darkfid JSON-RPC endpoint
     endpoint: Url,
 
+    #[clap(long)]
+    /// darkfid JSON-RPC API token
+    token: Option<String>,
+
     #[clap(subcommand)]
     command: DrkSubcommand,
 }
@@ -94,6 +98,49 @@ enum DrkSubcommand {
         #[clap(short, long)]
         token_id: String,
     },
+
+    /// Payment requests
+    Invoice {
+        #[clap(subcommand)]
+        command: InvoiceSubcommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum InvoiceSubcommand {
+    /// Create an invoice paid to a new address of the wallet
+    Create {
+        /// Amount requested
+        amount: String,
+
+        /// Token ID
+        #[clap(short, long)]
+        token_id: String,
+
+        /// Memo the payment will carry
+        #[clap(short, long, default_value = "")]
+        memo: String,
+
+        /// Seconds until the invoice expires
+        #[clap(short, long, default_value = "3600")]
+        expiry: i64,
+    },
+
+    /// Decode an invoice and show its terms
+    Parse {
+        /// Invoice, or the payload of its QR code
+        invoice: String,
+    },
+
+    /// Pay an invoice
+    Pay {
+        /// Invoice, or the payload of its QR code
+        invoice: String,
+
+        /// Fee paid for the transaction's inclusion, on top of the amount
+        #[clap(short, long, default_value = "0")]
+        fee: u64,
+    },
 }
 
 struct Drk {
@@ -160,6 +207,16 @@ impl Drk {
         Ok(())
     }
 
+    /// Look up the ticker and decimals of a token in the token registry.
+    async fn token_info(&self, token_id: &str) -> Option<(String, u16)> {
+        let req = JsonRequest::new("blockchain.get_token", json!([token_id]));
+        let rep = self.rpc_client.request(req).await.ok()?;
+
+        let ticker = rep["ticker"].as_str()?.to_string();
+        let decimals = u16::try_from(rep["decimals"].as_u64()?).ok()?;
+        Some((ticker, decimals))
+    }
+
     async fn wallet_balance(&self) -> Result<()> {
         let req = JsonRequest::new("wallet.get_balances", json!([]));
         let rep = self.rpc_client.request(req).await?;
@@ -171,11 +228,28 @@ impl Drk {
 
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Token ID", "Balance"]);
+        table.set_titles(row!["Token ID", "Ticker", "Balance", "Unconfirmed"]);
 
         for i in rep.as_object().unwrap().keys() {
-            if let Some(balance) = rep[i].as_u64() {
-                table.add_row(row![i, encode_base10(balance, 8)]);
+            if let (Some(balance), Some(unconfirmed)) =
+                (rep[i]["confirmed"].as_u64(), rep[i]["unconfirmed"].as_u64())
+            {
+                // Unregistered tokens are shown with the default 8 decimals
+                let (ticker, decimals) =
+                    self.token_info(i).await.unwrap_or_else(|| ("-".to_string(), 8));
+
+                match (
+                    encode_token_amount(balance, decimals),
+                    encode_token_amount(unconfirmed, decimals),
+                ) {
+                    (Ok(amount), Ok(unconfirmed)) => {
+                        table.add_row(row![i, ticker, amount, unconfirmed])
+                    }
+                    (Err(e), _) | (_, Err(e)) => {
+                        eprintln!("Found invalid decimals for token \"{}\": {}", i, e);
+                        continue
+                    }
+                };
                 continue
             }
 
@@ -188,6 +262,44 @@ impl Drk {
             println!("{}", table);
         }
 
+        self.wallet_locked_balance().await
+    }
+
+    async fn wallet_locked_balance(&self) -> Result<()> {
+        let req = JsonRequest::new("wallet.get_locked_balances", json!([]));
+        let rep = self.rpc_client.request(req).await?;
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row!["Token ID", "Ticker", "Locked", "Until slot"]);
+
+        for (i, locks) in rep.as_object().into_iter().flatten() {
+            let (ticker, decimals) =
+                self.token_info(i).await.unwrap_or_else(|| ("-".to_string(), 8));
+
+            for lock in locks.as_array().into_iter().flatten() {
+                let (value, lock_slot) = match (lock[0].as_u64(), lock[1].as_u64()) {
+                    (Some(value), Some(lock_slot)) => (value, lock_slot),
+                    _ => {
+                        eprintln!("Found invalid locked balance data for key \"{}\"", i);
+                        continue
+                    }
+                };
+
+                match encode_token_amount(value, decimals) {
+                    Ok(amount) => table.add_row(row![i, ticker, amount, lock_slot]),
+                    Err(e) => {
+                        eprintln!("Found invalid decimals for token \"{}\": {}", i, e);
+                        continue
+                    }
+                };
+            }
+        }
+
+        if !table.is_empty() {
+            println!("\nTime-locked:\n{}", table);
+        }
+
         Ok(())
     }
 
@@ -224,6 +336,54 @@ impl Drk {
         println!("Success! Transaction ID: {}", rep);
         Ok(())
     }
+
+    async fn invoice_create(
+        &self,
+        amount: String,
+        token_id: String,
+        memo: String,
+        expiry: i64,
+    ) -> Result<()> {
+        let decimals = self.token_info(&token_id).await.map_or(8, |(_, decimals)| decimals);
+        let amount = decode_token_amount(&amount, decimals)?;
+
+        let req = JsonRequest::new("invoice.create", json!([token_id, amount, memo, expiry]));
+        let rep = self.rpc_client.request(req).await?;
+
+        println!("Invoice: {}", rep["invoice"].as_str().unwrap_or_default());
+        println!("QR code payload: {}", rep["qr"].as_str().unwrap_or_default());
+        Ok(())
+    }
+
+    async fn invoice_parse(&self, invoice: String) -> Result<()> {
+        let req = JsonRequest::new("invoice.parse", json!([invoice]));
+        let rep = self.rpc_client.request(req).await?;
+
+        let token_id = rep["token_id"].as_str().unwrap_or_default();
+        let (ticker, decimals) =
+            self.token_info(token_id).await.unwrap_or_else(|| ("-".to_string(), 8));
+        let amount = encode_token_amount(rep["amount"].as_u64().unwrap_or_default(), decimals)?;
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.add_row(row!["Address", rep["address"].as_str().unwrap_or_default()]);
+        table.add_row(row!["Token ID", token_id]);
+        table.add_row(row!["Ticker", ticker]);
+        table.add_row(row!["Amount", amount]);
+        table.add_row(row!["Memo", rep["memo"].as_str().unwrap_or_default()]);
+        table.add_row(row!["Expiry", rep["expiry"]]);
+        table.add_row(row!["Expired", rep["expired"]]);
+        println!("{}", table);
+        Ok(())
+    }
+
+    async fn invoice_pay(&self, invoice: String, fee: u64) -> Result<()> {
+        let req = JsonRequest::new("invoice.pay", json!([invoice, fee]));
+        let rep = self.rpc_client.request(req).await?;
+
+        println!("Success! Transaction ID: {}", rep);
+        Ok(())
+    }
 }
 
 #[async_std::main]
@@ -235,6 +395,9 @@ async fn main() -> Result<()> {
     TermLogger::init(log_level, log_config, TerminalMode::Mixed, ColorChoice::Auto)?;
 
     let rpc_client = RpcClient::new(args.endpoint).await?;
+    if let Some(token) = &args.token {
+        rpc_client.auth(token).await?;
+    }
     let drk = Drk { rpc_client };
 
     match args.command {
@@ -268,6 +431,14 @@ async fn main() -> Result<()> {
         DrkSubcommand::Transfer { recipient, amount, network, token_id } => {
             drk.tx_transfer(network, token_id, recipient, amount).await
         }
+
+        DrkSubcommand::Invoice { command } => match command {
+            InvoiceSubcommand::Create { amount, token_id, memo, expiry } => {
+                drk.invoice_create(amount, token_id, memo, expiry).await
+            }
+            InvoiceSubcommand::Parse { invoice } => drk.invoice_parse(invoice).await,
+            InvoiceSubcommand::Pay { invoice, fee } => drk.invoice_pay(invoice, fee).await,
+        },
     }?;
 
     drk.close_connection().await
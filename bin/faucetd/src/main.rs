@@ -20,19 +20,24 @@ use darkfi::{
         ValidatorState, ValidatorStatePtr, MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP,
         TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP,
     },
-    crypto::{address::Address, keypair::PublicKey, token_id},
+    crypto::{
+        address::{Address, PaymentAddress},
+        keypair::PublicKey,
+        token_id,
+    },
     net,
     net::P2pPtr,
     node::Client,
     rpc::{
         jsonrpc::{
-            ErrorCode::{InternalError, InvalidParams, MethodNotFound},
-            JsonError, JsonRequest, JsonResponse, JsonResult,
+            server_error,
+            ErrorCode::{InvalidParams, MethodNotFound},
+            JsonError, JsonRequest, JsonResponse, JsonResult, ServerErrorCode,
         },
         server::{listen_and_serve, RequestHandler},
     },
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         decode_base10, expand_path,
         path::get_config_path,
         serial::serialize,
@@ -42,9 +47,6 @@ use darkfi::{
     Error, Result,
 };
 
-mod error;
-use error::{server_error, RpcError};
-
 const CONFIG_FILE: &str = "faucetd_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../faucetd_config.toml");
 
@@ -179,36 +181,31 @@ impl Faucetd {
 
         if !(*self.synced.lock().await) {
             error!("airdrop(): Blockchain is not yet synced");
-            return JsonError::new(InternalError, None, id).into()
+            return server_error(ServerErrorCode::NotYetSynced, id)
         }
 
-        let address = match Address::from_str(params[0].as_str().unwrap()) {
+        let payment_address = match PaymentAddress::from_str(params[0].as_str().unwrap()) {
             Ok(v) => v,
             Err(_) => {
                 error!("airdrop(): Failed parsing address from string");
-                return server_error(RpcError::ParseError, id)
+                return server_error(ServerErrorCode::FaucetParseError, id)
             }
         };
 
-        let pubkey = match PublicKey::try_from(address) {
-            Ok(v) => v,
-            Err(_) => {
-                error!("airdrop(): Failed parsing PublicKey from Address");
-                return server_error(RpcError::ParseError, id)
-            }
-        };
+        // Airdrops are rate limited per spend key
+        let address = Address::from(payment_address.public);
 
         let amount = params[1].as_f64().unwrap().to_string();
         let amount = match decode_base10(&amount, 8, true) {
             Ok(v) => v,
             Err(_) => {
                 error!("airdrop(): Failed parsing amount from string");
-                return server_error(RpcError::ParseError, id)
+                return server_error(ServerErrorCode::FaucetParseError, id)
             }
         };
 
         if amount > self.airdrop_limit {
-            return server_error(RpcError::AmountExceedsLimit, id)
+            return server_error(ServerErrorCode::AmountExceedsLimit, id)
         }
 
         // Here we allow the faucet to mint arbitrary token IDs.
@@ -217,7 +214,7 @@ impl Faucetd {
             Ok(v) => v,
             Err(_) => {
                 error!("airdrop(): Failed parsing token id from string");
-                return server_error(RpcError::ParseError, id)
+                return server_error(ServerErrorCode::FaucetParseError, id)
             }
         };
 
@@ -226,7 +223,7 @@ impl Faucetd {
         let map = self.airdrop_map.lock().await;
         if let Some(last_airdrop) = map.get(&address) {
             if now - last_airdrop <= self.airdrop_timeout {
-                return server_error(RpcError::TimeLimitReached, id)
+                return server_error(ServerErrorCode::TimeLimitReached, id)
             }
         };
         drop(map);
@@ -234,8 +231,9 @@ impl Faucetd {
         let tx = match self
             .client
             .build_transaction(
-                pubkey,
+                payment_address,
                 amount,
+                0,
                 token_id,
                 true,
                 self.validator_state.read().await.state_machine.clone(),
@@ -245,7 +243,7 @@ impl Faucetd {
             Ok(v) => v,
             Err(e) => {
                 error!("airdrop(): Failed building transaction: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         };
 
@@ -254,7 +252,7 @@ impl Faucetd {
             Ok(()) => {}
             Err(e) => {
                 error!("airdrop(): Failed broadcasting transaction: {}", e);
-                return JsonError::new(InternalError, None, id).into()
+                return JsonError::from_error(&e, id).into()
             }
         }
 
@@ -412,7 +410,7 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     info!("Waiting for sync P2P outbound connections");
     sync_p2p.clone().wait_for_outbound().await?;
 
-    match block_sync_task(sync_p2p, state.clone()).await {
+    match block_sync_task(sync_p2p, state.clone(), ex.clone()).await {
         Ok(()) => *faucetd.synced.lock().await = true,
         Err(e) => error!("Failed syncing blockchain: {}", e),
     }
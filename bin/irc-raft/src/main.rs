@@ -18,7 +18,7 @@ use darkfi::{
     raft::{NetMsg, ProtocolRaft, Raft},
     rpc::server::listen_and_serve,
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         path::{expand_path, get_config_path},
     },
     Error, Result,
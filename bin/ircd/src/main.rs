@@ -19,7 +19,7 @@ use darkfi::{
     rpc::server::listen_and_serve,
     system::{Subscriber, SubscriberPtr},
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         expand_path,
         path::get_config_path,
     },
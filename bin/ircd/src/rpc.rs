@@ -6,6 +6,7 @@ use url::Url;
 use darkfi::{
     net,
     rpc::{
+        dnet,
         jsonrpc::{ErrorCode, JsonError, JsonRequest, JsonResponse, JsonResult},
         server::RequestHandler,
     },
@@ -25,6 +26,10 @@ impl RequestHandler for JsonRpcInterface {
 
         debug!(target: "RPC", "--> {}", serde_json::to_string(&req).unwrap());
 
+        if req.method.as_str().map_or(false, dnet::is_dnet_method) {
+            return dnet::handle_request(&self.p2p, req).await
+        }
+
         match req.method.as_str() {
             Some("ping") => self.pong(req.id, req.params).await,
             Some("get_info") => self.get_info(req.id, req.params).await,
@@ -18,8 +18,7 @@ use darkfi::{
         server::{listen_and_serve, RequestHandler},
     },
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
-        expand_path,
+        cli::{get_log_level, spawn_config},
         path::get_config_path,
     },
     Result,
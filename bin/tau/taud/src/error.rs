@@ -1,4 +1,4 @@
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use darkfi::rpc::jsonrpc::{ErrorCode, JsonError, JsonResponse, JsonResult};
 
@@ -40,17 +40,23 @@ pub fn to_json_result(res: TaudResult<Value>, id: Value) -> JsonResult {
                 JsonError::new(ErrorCode::InvalidParams, Some("invalid task id".into()), id).into()
             }
             TaudError::InvalidData(e) | TaudError::SerdeJsonError(e) => {
-                JsonError::new(ErrorCode::InvalidParams, Some(e), id).into()
+                let data = json!({ "error": e });
+                JsonError::with_data(
+                    ErrorCode::InvalidParams,
+                    Some("invalid task data".into()),
+                    data,
+                    id,
+                )
+                .into()
             }
             TaudError::InvalidDueTime => {
                 JsonError::new(ErrorCode::InvalidParams, Some("invalid due time".into()), id).into()
             }
             TaudError::EncryptionError(e) => {
-                JsonError::new(ErrorCode::InternalError, Some(e), id).into()
-            }
-            TaudError::Darkfi(e) => {
-                JsonError::new(ErrorCode::InternalError, Some(e.to_string()), id).into()
+                let data = json!({ "error": e });
+                JsonError::with_data(ErrorCode::InternalError, None, data, id).into()
             }
+            TaudError::Darkfi(e) => JsonError::from_error(&e, id).into(),
         },
     }
 }
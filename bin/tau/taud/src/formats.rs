@@ -0,0 +1,164 @@
+use chrono::{TimeZone, Utc};
+
+use darkfi::util::Timestamp;
+
+use crate::task_info::TaskInfo;
+
+/// Columns of the Markdown board, by task state
+const BOARD_COLUMNS: [(&str, &str); 4] =
+    [("open", "Open"), ("start", "Started"), ("pause", "Paused"), ("stop", "Stopped")];
+
+fn format_date(date: Timestamp) -> String {
+    Utc.timestamp(date.0, 0).format("%Y-%m-%d").to_string()
+}
+
+/// Render tasks as a Markdown board, with a checklist per state. Stopped
+/// tasks are checked.
+pub fn to_markdown(workspace: &str, tasks: &[TaskInfo]) -> String {
+    let mut board = format!("# {}\n", workspace);
+
+    for (state, column) in BOARD_COLUMNS {
+        let mut column_tasks: Vec<&TaskInfo> =
+            tasks.iter().filter(|t| t.get_state() == state).collect();
+        if column_tasks.is_empty() {
+            continue
+        }
+        column_tasks.sort_by_key(|t| t.get_id());
+
+        board.push_str(&format!("\n## {}\n\n", column));
+        for task in column_tasks {
+            let check = if state == "stop" { "x" } else { " " };
+            let mut line = format!("- [{}] #{} {}", check, task.get_id(), task.get_title());
+            for assign in task.get_assign() {
+                line.push_str(&format!(" @{}", assign));
+            }
+            for project in task.get_project() {
+                line.push_str(&format!(" +{}", project));
+            }
+            if let Some(due) = task.get_due() {
+                line.push_str(&format!(" (due {})", format_date(due)));
+            }
+            board.push_str(&line);
+            board.push('\n');
+        }
+    }
+
+    board
+}
+
+/// Parse the checklist items of a Markdown document, as their text and
+/// whether they are checked. Any other line is ignored.
+pub fn parse_markdown_checklist(markdown: &str) -> Vec<(String, bool)> {
+    let mut items = vec![];
+
+    for line in markdown.lines() {
+        let line = line.trim_start();
+        let item = match line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        let (checked, title) = match item.get(..3) {
+            Some("[ ]") => (false, &item[3..]),
+            Some("[x]") | Some("[X]") => (true, &item[3..]),
+            _ => continue,
+        };
+
+        let title = title.trim();
+        if !title.is_empty() {
+            items.push((title.to_string(), checked));
+        }
+    }
+
+    items
+}
+
+/// Escape a value of an iCalendar text property (RFC 5545 3.3.11)
+fn ical_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Append a content line, folded to lines of at most 75 octets
+/// (RFC 5545 3.1)
+fn ical_line(ical: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            ical.push_str("\r\n ");
+            len = 1;
+        }
+        ical.push(c);
+        len += c.len_utf8();
+    }
+    ical.push_str("\r\n");
+}
+
+fn ical_datetime(date: Timestamp) -> String {
+    Utc.timestamp(date.0, 0).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Render tasks as an iCalendar with a VTODO per task, so tasks with a due
+/// date show up in calendar applications.
+pub fn to_icalendar(tasks: &[TaskInfo]) -> String {
+    let mut ical = String::new();
+    ical_line(&mut ical, "BEGIN:VCALENDAR");
+    ical_line(&mut ical, "VERSION:2.0");
+    ical_line(&mut ical, "PRODID:-//darkfi//tau//EN");
+
+    for task in tasks {
+        let status = match task.get_state().as_str() {
+            "start" => "IN-PROCESS",
+            "stop" => "COMPLETED",
+            _ => "NEEDS-ACTION",
+        };
+
+        ical_line(&mut ical, "BEGIN:VTODO");
+        ical_line(&mut ical, &format!("UID:{}@tau", task.ref_id));
+        ical_line(&mut ical, &format!("DTSTAMP:{}", ical_datetime(task.get_created_at())));
+        ical_line(&mut ical, &format!("SUMMARY:{}", ical_escape(task.get_title())));
+        if !task.get_desc().is_empty() {
+            ical_line(&mut ical, &format!("DESCRIPTION:{}", ical_escape(task.get_desc())));
+        }
+        if let Some(due) = task.get_due() {
+            ical_line(&mut ical, &format!("DUE:{}", ical_datetime(due)));
+        }
+        if !task.get_project().is_empty() {
+            let categories: Vec<String> =
+                task.get_project().iter().map(|p| ical_escape(p)).collect();
+            ical_line(&mut ical, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+        ical_line(&mut ical, &format!("STATUS:{}", status));
+        ical_line(&mut ical, "END:VTODO");
+    }
+
+    ical_line(&mut ical, "END:VCALENDAR");
+    ical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_checklist_test() {
+        let markdown =
+            "# Board\n\n## Open\n\n- [ ] write docs\n  * [x] fix build \n- not a task\n- [ ]\n";
+        let items = parse_markdown_checklist(markdown);
+        assert_eq!(items, vec![("write docs".to_string(), false), ("fix build".to_string(), true)]);
+    }
+
+    #[test]
+    fn ical_line_folding_test() {
+        let mut ical = String::new();
+        ical_line(&mut ical, &format!("SUMMARY:{}", "a".repeat(100)));
+        for line in ical.split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(ical.replace("\r\n ", ""), format!("SUMMARY:{}\r\n", "a".repeat(100)));
+        assert_eq!(ical_escape("a,b;c\nd"), "a\\,b\\;c\\nd");
+    }
+}
@@ -1,15 +1,19 @@
 use async_std::sync::{Arc, Mutex};
-use std::{fs::create_dir_all, path::PathBuf};
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
 
 use async_trait::async_trait;
-use fxhash::FxHashMap;
-use log::{debug, warn};
+use crypto_box::{rand_core::OsRng, SecretKey};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use darkfi::{
     net,
     rpc::{
+        dnet,
         jsonrpc::{ErrorCode, JsonError, JsonRequest, JsonResult},
         server::RequestHandler,
     },
@@ -18,18 +22,22 @@ use darkfi::{
 };
 
 use crate::{
+    encrypt_task,
     error::{to_json_result, TaudError, TaudResult},
+    formats::{parse_markdown_checklist, to_icalendar, to_markdown},
     month_tasks::MonthTasks,
     task_info::{Comment, TaskInfo},
-    util::Workspace,
+    util::{find_free_id, save_rotated_key, WorkspacesPtr},
+    EncryptedTask, KeyRotation, SyncPayload,
 };
 
 pub struct JsonRpcInterface {
     dataset_path: PathBuf,
     notify_queue_sender: async_channel::Sender<TaskInfo>,
+    encrypted_queue_sender: async_channel::Sender<EncryptedTask>,
     nickname: String,
     workspace: Arc<Mutex<String>>,
-    configured_ws: FxHashMap<String, Workspace>,
+    configured_ws: WorkspacesPtr,
     p2p: net::P2pPtr,
 }
 
@@ -50,6 +58,10 @@ impl RequestHandler for JsonRpcInterface {
             return JsonError::new(ErrorCode::InvalidParams, None, req.id).into()
         }
 
+        if req.method.as_str().map_or(false, dnet::is_dnet_method) {
+            return dnet::handle_request(&self.p2p, req).await
+        }
+
         let params = req.params.as_array().unwrap();
 
         let rep = match req.method.as_str() {
@@ -58,11 +70,17 @@ impl RequestHandler for JsonRpcInterface {
             Some("update") => self.update(params).await,
             Some("set_state") => self.set_state(params).await,
             Some("set_comment") => self.set_comment(params).await,
+            Some("add_comment") => self.add_comment(params).await,
+            Some("get_events") => self.get_events(params).await,
             Some("get_task_by_id") => self.get_task_by_id(params).await,
             Some("switch_ws") => self.switch_ws(params).await,
             Some("get_ws") => self.get_ws(params).await,
+            Some("rotate_workspace_key") => self.rotate_workspace_key(params).await,
             Some("export") => self.export_to(params).await,
             Some("import") => self.import_from(params).await,
+            Some("export_markdown") => self.export_markdown(params).await,
+            Some("export_ical") => self.export_ical(params).await,
+            Some("import_markdown") => self.import_markdown(params).await,
             Some("get_stop_tasks") => self.get_stop_tasks(params).await,
             Some("ping") => self.pong(params).await,
             Some("get_info") => self.get_info(params).await,
@@ -77,12 +95,21 @@ impl JsonRpcInterface {
     pub fn new(
         dataset_path: PathBuf,
         notify_queue_sender: async_channel::Sender<TaskInfo>,
+        encrypted_queue_sender: async_channel::Sender<EncryptedTask>,
         nickname: String,
         workspace: Arc<Mutex<String>>,
-        configured_ws: FxHashMap<String, Workspace>,
+        configured_ws: WorkspacesPtr,
         p2p: net::P2pPtr,
     ) -> Self {
-        Self { dataset_path, nickname, workspace, configured_ws, notify_queue_sender, p2p }
+        Self {
+            dataset_path,
+            nickname,
+            workspace,
+            configured_ws,
+            notify_queue_sender,
+            encrypted_queue_sender,
+            p2p,
+        }
     }
 
     // RPCAPI:
@@ -219,6 +246,61 @@ impl JsonRpcInterface {
         Ok(json!(true))
     }
 
+    // RPCAPI:
+    // Add a comment to a task, optionally as a reply to another one, and
+    // returns the id of the new comment.
+    // --> {"jsonrpc": "2.0", "method": "add_comment", "params": [task_id, comment_content, parent_id], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "comment_id", "id": 1}
+    async fn add_comment(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::add_comment() params {:?}", params);
+
+        if params.len() != 2 && params.len() != 3 {
+            return Err(TaudError::InvalidData("len of params should be 2 or 3".into()))
+        }
+
+        let comment_content: String = serde_json::from_value(params[1].clone())?;
+        let parent: Option<String> = match params.get(2) {
+            Some(parent) => serde_json::from_value(parent.clone())?,
+            None => None,
+        };
+        let ws = self.workspace.lock().await.clone();
+
+        let mut task: TaskInfo = self.load_task_by_id(&params[0], ws)?;
+        let comment = match parent {
+            Some(parent) => {
+                if !task.has_comment(&parent) {
+                    return Err(TaudError::InvalidData("Unknown parent comment".into()))
+                }
+                Comment::reply(&comment_content, &self.nickname, &parent)
+            }
+            None => Comment::new(&comment_content, &self.nickname),
+        };
+        let comment_id = comment.get_id().to_string();
+        task.set_comment(comment);
+        task.set_event("comment", &self.nickname, &comment_content);
+
+        self.notify_queue_sender.send(task).await.map_err(Error::from)?;
+
+        Ok(json!(comment_id))
+    }
+
+    // RPCAPI:
+    // Get the activity log of a task, oldest event first.
+    // --> {"jsonrpc": "2.0", "method": "get_events", "params": [task_id], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": [{"action": "..", "author": "..", "content": "..", "timestamp": ..}, ...], "id": 1}
+    async fn get_events(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::get_events() params {:?}", params);
+
+        if params.len() != 1 {
+            return Err(TaudError::InvalidData("len of params should be 1".into()))
+        }
+        let ws = self.workspace.lock().await.clone();
+
+        let task: TaskInfo = self.load_task_by_id(&params[0], ws)?;
+
+        Ok(json!(task.get_events()))
+    }
+
     // RPCAPI:
     // Get a task by id.
     // --> {"jsonrpc": "2.0", "method": "get_task_by_id", "params": [task_id], "id": 1}
@@ -272,7 +354,7 @@ impl JsonRpcInterface {
         let ws = params[0].as_str().unwrap().to_string();
         let mut s = self.workspace.lock().await;
 
-        if self.configured_ws.contains_key(&ws) {
+        if self.configured_ws.read().unwrap().contains_key(&ws) {
             *s = ws
         } else {
             warn!("Workspace \"{}\" is not configured", ws);
@@ -292,6 +374,45 @@ impl JsonRpcInterface {
         Ok(json!(ws))
     }
 
+    // RPCAPI:
+    // Rotate the key of the current workspace, so members removed from it
+    // can't decrypt its new tasks. Active tasks are sent again under the new
+    // key, and members still using the old one are told to import it.
+    // Returns the new secret, to be shared with the remaining members.
+    // --> {"jsonrpc": "2.0", "method": "rotate_workspace_key", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "secret", "id": 1}
+    async fn rotate_workspace_key(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::rotate_workspace_key() params {:?}", params);
+        let ws = self.workspace.lock().await.clone();
+
+        let secret = SecretKey::generate(&mut OsRng);
+        let marker = {
+            let mut workspaces = self.configured_ws.write().unwrap();
+            let ws_info = match workspaces.get_mut(&ws) {
+                Some(ws_info) => ws_info,
+                None => return Err(TaudError::InvalidData("Workspace is not configured".into())),
+            };
+            let old_box = match &ws_info.encryption {
+                Some(old_box) => old_box,
+                None => return Err(TaudError::InvalidData("Workspace is not encrypted".into())),
+            };
+
+            let rotation = SyncPayload::KeyRotation(KeyRotation::new(&secret));
+            let marker = encrypt_task(&rotation, &ws, old_box, &mut OsRng)?;
+            ws_info.rotate(&secret);
+            marker
+        };
+        save_rotated_key(&self.dataset_path, &ws, &secret)?;
+        info!(target: "tau", "Rotated the key of workspace {}", ws);
+
+        self.encrypted_queue_sender.send(marker).await.map_err(Error::from)?;
+        for task in MonthTasks::load_current_tasks(&self.dataset_path, ws, false)? {
+            self.notify_queue_sender.send(task).await.map_err(Error::from)?;
+        }
+
+        Ok(json!(bs58::encode(secret.as_bytes()).into_string()))
+    }
+
     // RPCAPI:
     // Export tasks.
     // --> {"jsonrpc": "2.0", "method": "export_to", "params": [path], "id": 1}
@@ -347,6 +468,94 @@ impl JsonRpcInterface {
         Ok(json!(true))
     }
 
+    // RPCAPI:
+    // Export the tasks of the current workspace to a Markdown board.
+    // --> {"jsonrpc": "2.0", "method": "export_markdown", "params": [path], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "true", "id": 1}
+    async fn export_markdown(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::export_markdown() params {:?}", params);
+
+        let path = Self::path_param(params)?;
+        let ws = self.workspace.lock().await.clone();
+        let tasks = MonthTasks::load_current_tasks(&self.dataset_path, ws.clone(), true)?;
+
+        write(path, to_markdown(&ws, &tasks)).map_err(Error::from)?;
+        Ok(json!(true))
+    }
+
+    // RPCAPI:
+    // Export the tasks of the current workspace to an iCalendar file, as
+    // VTODO components.
+    // --> {"jsonrpc": "2.0", "method": "export_ical", "params": [path], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "true", "id": 1}
+    async fn export_ical(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::export_ical() params {:?}", params);
+
+        let path = Self::path_param(params)?;
+        let ws = self.workspace.lock().await.clone();
+        let tasks = MonthTasks::load_current_tasks(&self.dataset_path, ws, true)?;
+
+        write(path, to_icalendar(&tasks)).map_err(Error::from)?;
+        Ok(json!(true))
+    }
+
+    // RPCAPI:
+    // Import the items of a Markdown checklist as tasks of the current
+    // workspace. Checked items are imported as stopped tasks. Returns the
+    // ids of the new tasks.
+    // --> {"jsonrpc": "2.0", "method": "import_markdown", "params": [path], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": [task_id, ...], "id": 1}
+    async fn import_markdown(&self, params: &[Value]) -> TaudResult<Value> {
+        debug!(target: "tau", "JsonRpc::import_markdown() params {:?}", params);
+
+        let path = Self::path_param(params)?;
+        let markdown = read_to_string(path).map_err(Error::from)?;
+        let ws = self.workspace.lock().await.clone();
+
+        // Tasks are only saved once synced, so reserve their ids here
+        let mut task_ids: Vec<u32> =
+            MonthTasks::load_current_tasks(&self.dataset_path, ws.clone(), false)?
+                .iter()
+                .map(|t| t.get_id())
+                .collect();
+
+        let mut new_ids = vec![];
+        for (title, checked) in parse_markdown_checklist(&markdown) {
+            let mut task = TaskInfo::new(
+                ws.clone(),
+                &title,
+                "",
+                &self.nickname,
+                None,
+                None,
+                &self.dataset_path,
+            )?;
+            let id = find_free_id(&task_ids);
+            task.set_id(id);
+            task_ids.push(id);
+            if checked {
+                task.set_state("stop");
+                task.set_event("state", &self.nickname, "stop");
+            }
+
+            new_ids.push(id);
+            self.notify_queue_sender.send(task).await.map_err(Error::from)?;
+        }
+
+        Ok(json!(new_ids))
+    }
+
+    fn path_param(params: &[Value]) -> TaudResult<PathBuf> {
+        if params.len() != 1 {
+            return Err(TaudError::InvalidData("len of params should be 1".into()))
+        }
+
+        match params[0].as_str() {
+            Some(path) => Ok(expand_path(path)?),
+            None => Err(TaudError::InvalidData("Invalid path".into())),
+        }
+    }
+
     fn load_task_by_id(&self, task_id: &Value, ws: String) -> TaudResult<TaskInfo> {
         let task_id: u64 = serde_json::from_value(task_id.clone())?;
         let tasks = MonthTasks::load_current_tasks(&self.dataset_path, ws, false)?;
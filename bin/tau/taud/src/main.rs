@@ -3,6 +3,8 @@ use std::{
     env,
     fs::{create_dir_all, remove_dir_all},
     io::stdin,
+    path::Path,
+    sync::RwLock,
 };
 
 use async_executor::Executor;
@@ -17,19 +19,23 @@ use smol::future;
 use structopt_toml::StructOptToml;
 
 use darkfi::{
-    async_daemonize, net,
+    async_daemonize,
+    event_graph::{Event, EventGraph, EventGraphPtr, EventPut, ProtocolEventGraph},
+    net,
     raft::{NetMsg, ProtocolRaft, Raft, RaftSettings},
     rpc::server::listen_and_serve,
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         expand_path,
         path::get_config_path,
         serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+        Timestamp,
     },
     Error, Result,
 };
 
 mod error;
+mod formats;
 mod jsonrpc;
 mod month_tasks;
 mod settings;
@@ -41,7 +47,7 @@ use crate::{
     jsonrpc::JsonRpcInterface,
     settings::{Args, CONFIG_FILE, CONFIG_FILE_CONTENTS},
     task_info::TaskInfo,
-    util::{parse_workspaces, Workspace},
+    util::{load_rotated_keys, parse_workspaces, WorkspacesPtr},
 };
 
 #[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
@@ -51,73 +57,248 @@ pub struct EncryptedTask {
     payload: Vec<u8>,
 }
 
-fn encrypt_task(
-    task: &TaskInfo,
-    workspace: &String,
+/// Announces that the key of a workspace was replaced. Encrypted with the
+/// replaced key, so members still using it learn they have to import the
+/// new one.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct KeyRotation {
+    /// Public key of the new secret
+    public_key: [u8; 32],
+    timestamp: Timestamp,
+}
+
+impl KeyRotation {
+    pub fn new(secret: &SecretKey) -> Self {
+        Self { public_key: *secret.public_key().as_bytes(), timestamp: Timestamp::current_time() }
+    }
+}
+
+/// Plaintext of an [`EncryptedTask`]
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub enum SyncPayload {
+    Task(TaskInfo),
+    KeyRotation(KeyRotation),
+}
+
+pub fn encrypt_task(
+    payload: &SyncPayload,
+    workspace: &str,
     salsa_box: &SalsaBox,
     rng: &mut crypto_box::rand_core::OsRng,
 ) -> TaudResult<EncryptedTask> {
     debug!("start encrypting task");
 
     let nonce = SalsaBox::generate_nonce(rng);
-    let payload = &serialize(task)[..];
+    let payload = &serialize(payload)[..];
     let payload = salsa_box.encrypt(&nonce, payload)?;
 
     let nonce = nonce.to_vec();
     Ok(EncryptedTask { workspace: workspace.to_string(), nonce, payload })
 }
 
-fn decrypt_task(encrypt_task: &EncryptedTask, salsa_box: &SalsaBox) -> TaudResult<TaskInfo> {
+fn decrypt_task(encrypt_task: &EncryptedTask, salsa_box: &SalsaBox) -> TaudResult<SyncPayload> {
     debug!("start decrypting task");
 
     let nonce = encrypt_task.nonce.as_slice();
     let decrypted_task = salsa_box.decrypt(nonce.into(), &encrypt_task.payload[..])?;
 
-    let task = deserialize(&decrypted_task)?;
+    let payload = deserialize(&decrypted_task)?;
 
-    Ok(task)
+    Ok(payload)
+}
+
+/// Encrypt a task with the current key of its workspace, if the workspace
+/// is configured with one.
+fn seal_task(
+    task: &TaskInfo,
+    workspaces: &WorkspacesPtr,
+    rng: &mut crypto_box::rand_core::OsRng,
+) -> TaudResult<Option<EncryptedTask>> {
+    let workspaces = workspaces.read().unwrap();
+    let salsa_box = match workspaces.get(&task.workspace).and_then(|ws| ws.encryption.as_ref()) {
+        Some(salsa_box) => salsa_box,
+        None => return Ok(None),
+    };
+
+    let payload = SyncPayload::Task(task.clone());
+    Ok(Some(encrypt_task(&payload, &task.workspace, salsa_box, rng)?))
+}
+
+/// Decrypt a task received from peers, with the current key of its
+/// workspace or one it replaced. Tasks under a replaced key are only taken
+/// in when missing locally, so members removed by a key rotation can't
+/// overwrite tasks anymore.
+fn open_task(
+    recv: &EncryptedTask,
+    workspaces: &WorkspacesPtr,
+    datastore_path: &Path,
+) -> Option<TaskInfo> {
+    let workspaces = workspaces.read().unwrap();
+    let ws_info = workspaces.get(&recv.workspace)?;
+
+    let mut payload = None;
+    if let Some(salsa_box) = &ws_info.encryption {
+        payload = decrypt_task(recv, salsa_box).ok().map(|p| (p, true));
+    }
+    if payload.is_none() {
+        payload = ws_info
+            .old_encryption
+            .iter()
+            .find_map(|salsa_box| decrypt_task(recv, salsa_box).ok())
+            .map(|p| (p, false));
+    }
+
+    match payload {
+        Some((SyncPayload::Task(task), true)) => Some(task),
+        Some((SyncPayload::Task(task), false)) => {
+            if TaskInfo::load(&task.ref_id, datastore_path).is_ok() {
+                info!("Ignoring update of task {} under a replaced key", task.ref_id);
+                return None
+            }
+            Some(task)
+        }
+        Some((SyncPayload::KeyRotation(rotation), true)) => {
+            warn!(
+                target: "tau",
+                "Key of workspace {} was rotated to public key {}, import the new secret to keep receiving its tasks",
+                recv.workspace,
+                bs58::encode(rotation.public_key).into_string()
+            );
+            None
+        }
+        // We already moved on from the rotated key
+        Some((SyncPayload::KeyRotation(_), false)) => None,
+        None => {
+            info!("unable to decrypt the task of workspace {}", recv.workspace);
+            None
+        }
+    }
 }
 
 async fn start_sync_loop(
     commits_received: Arc<Mutex<Vec<String>>>,
     broadcast_rcv: async_channel::Receiver<TaskInfo>,
+    encrypted_rcv: async_channel::Receiver<EncryptedTask>,
     raft_msgs_sender: async_channel::Sender<EncryptedTask>,
     commits_recv: async_channel::Receiver<EncryptedTask>,
     datastore_path: std::path::PathBuf,
-    configured_ws: FxHashMap<String, Workspace>,
+    configured_ws: WorkspacesPtr,
     mut rng: crypto_box::rand_core::OsRng,
 ) -> TaudResult<()> {
     loop {
         select! {
             task = broadcast_rcv.recv().fuse() => {
                 let tk = task.map_err(Error::from)?;
-                if configured_ws.contains_key(&tk.workspace) {
-                    let ws_info = configured_ws.get(&tk.workspace).unwrap();
-                    if let Some(salsa_box) = &ws_info.encryption {
-                        let encrypted_task = encrypt_task(&tk, &tk.workspace, salsa_box, &mut rng)?;
-                        info!(target: "tau", "Send the task: ref: {}", tk.ref_id);
-                        raft_msgs_sender.send(encrypted_task).await.map_err(Error::from)?;
-                    }
+                if let Some(encrypted_task) = seal_task(&tk, &configured_ws, &mut rng)? {
+                    info!(target: "tau", "Send the task: ref: {}", tk.ref_id);
+                    raft_msgs_sender.send(encrypted_task).await.map_err(Error::from)?;
                 }
             }
+            encrypted_task = encrypted_rcv.recv().fuse() => {
+                let encrypted_task = encrypted_task.map_err(Error::from)?;
+                raft_msgs_sender.send(encrypted_task).await.map_err(Error::from)?;
+            }
             task = commits_recv.recv().fuse() => {
                 let recv = task.map_err(Error::from)?;
-                if configured_ws.contains_key(&recv.workspace) {
-                    let ws_info = configured_ws.get(&recv.workspace).unwrap();
-                    if let Some(salsa_box) = &ws_info.encryption {
-                        let task = decrypt_task(&recv, salsa_box);
-                        if let Err(e) = task {
-                            info!("unable to decrypt the task: {}", e);
-                            continue
-                        }
-
-                        let task = task.unwrap();
-                        if !commits_received.lock().await.contains(&task.ref_id) {
-                            commits_received.lock().await.push(task.ref_id.clone());
-                        }
-                        info!(target: "tau", "Save the task: ref: {}", task.ref_id);
-                        task.save(&datastore_path)?;
+                let mut task = match open_task(&recv, &configured_ws, &datastore_path) {
+                    Some(task) => task,
+                    None => continue,
+                };
+
+                if let Ok(local) = TaskInfo::load(&task.ref_id, &datastore_path) {
+                    task.merge_log(&local);
+                }
+                if !commits_received.lock().await.contains(&task.ref_id) {
+                    commits_received.lock().await.push(task.ref_id.clone());
+                }
+                info!(target: "tau", "Save the task: ref: {}", task.ref_id);
+                task.save(&datastore_path)?;
+            }
+        }
+    }
+}
+
+/// Sync loop used instead of Raft when tasks are synced through the event
+/// graph. Local updates become new events, and the events received from
+/// peers are applied with last-writer-wins: an update only replaces a
+/// task if its event comes later in the graph order than the event of the
+/// update applied before. Comments and events are merged either way, so
+/// none are lost to a concurrent update.
+async fn start_event_graph_sync_loop(
+    commits_received: Arc<Mutex<Vec<String>>>,
+    broadcast_rcv: async_channel::Receiver<TaskInfo>,
+    encrypted_rcv: async_channel::Receiver<EncryptedTask>,
+    graph: EventGraphPtr<EncryptedTask>,
+    p2p: net::P2pPtr,
+    events_rcv: async_channel::Receiver<Event<EncryptedTask>>,
+    datastore_path: std::path::PathBuf,
+    configured_ws: WorkspacesPtr,
+    mut rng: crypto_box::rand_core::OsRng,
+) -> TaudResult<()> {
+    // Order key of the event each task was last updated by
+    let mut last_updates: FxHashMap<String, (u64, [u8; 32])> = FxHashMap::default();
+
+    let apply_event = |event: &Event<EncryptedTask>,
+                       last_updates: &mut FxHashMap<String, (u64, [u8; 32])>|
+     -> TaudResult<Option<TaskInfo>> {
+        let mut task = match open_task(&event.payload, &configured_ws, &datastore_path) {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+
+        let local = TaskInfo::load(&task.ref_id, &datastore_path).ok();
+
+        let key = event.order_key();
+        if let Some(last) = last_updates.get(&task.ref_id) {
+            if *last > key {
+                debug!(target: "tau", "Skip outdated update of task: ref: {}", task.ref_id);
+                if let Some(mut local) = local {
+                    local.merge_log(&task);
+                    local.save(&datastore_path)?;
+                }
+                return Ok(None)
+            }
+        }
+        last_updates.insert(task.ref_id.clone(), key);
+
+        if let Some(local) = local {
+            task.merge_log(&local);
+        }
+        task.save(&datastore_path)?;
+        Ok(Some(task))
+    };
+
+    // Replay the graph, so concurrent updates received before a restart
+    // are resolved the same way as on the other peers
+    let events = graph.lock().await.ordered();
+    for event in &events {
+        apply_event(event, &mut last_updates)?;
+    }
+    info!(target: "tau", "Replayed {} events", events.len());
+
+    loop {
+        select! {
+            task = broadcast_rcv.recv().fuse() => {
+                let tk = task.map_err(Error::from)?;
+                if let Some(encrypted_task) = seal_task(&tk, &configured_ws, &mut rng)? {
+                    info!(target: "tau", "Send the task: ref: {}", tk.ref_id);
+                    let event = graph.lock().await.new_event(encrypted_task)?;
+                    last_updates.insert(tk.ref_id.clone(), event.order_key());
+                    p2p.broadcast(EventPut(event)).await?;
+                }
+            }
+            encrypted_task = encrypted_rcv.recv().fuse() => {
+                let encrypted_task = encrypted_task.map_err(Error::from)?;
+                let event = graph.lock().await.new_event(encrypted_task)?;
+                p2p.broadcast(EventPut(event)).await?;
+            }
+            event = events_rcv.recv().fuse() => {
+                let event = event.map_err(Error::from)?;
+                if let Some(task) = apply_event(&event, &mut last_updates)? {
+                    if !commits_received.lock().await.contains(&task.ref_id) {
+                        commits_received.lock().await.push(task.ref_id.clone());
                     }
+                    info!(target: "tau", "Save the task: ref: {}", task.ref_id);
                 }
             }
         }
@@ -171,7 +352,8 @@ async fn realmain(settings: Args, executor: Arc<Executor<'_>>) -> Result<()> {
 
     // Pick up workspace settings from the TOML configuration
     let cfg_path = get_config_path(settings.config, CONFIG_FILE)?;
-    let configured_ws = parse_workspaces(&cfg_path)?;
+    let mut configured_ws = parse_workspaces(&cfg_path)?;
+    load_rotated_keys(&datastore_path, &mut configured_ws)?;
 
     // start at the first configured workspace
     let workspace = if let Some(key) = configured_ws.keys().next() {
@@ -181,41 +363,66 @@ async fn realmain(settings: Args, executor: Arc<Executor<'_>>) -> Result<()> {
         return Ok(())
     };
 
-    //
-    // Raft
-    //
     let net_settings = settings.net;
-    let seen_net_msgs = Arc::new(Mutex::new(FxHashMap::default()));
-
-    let datastore_raft = datastore_path.join("tau.db");
-    let raft_settings = RaftSettings { datastore_path: datastore_raft, ..RaftSettings::default() };
-
-    let mut raft = Raft::<EncryptedTask>::new(raft_settings, seen_net_msgs.clone())?;
-    let raft_id = raft.id();
-
     let commits_received: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
 
     let (broadcast_snd, broadcast_rcv) = async_channel::unbounded::<TaskInfo>();
+    // Payloads the RPC interface already encrypted, like key rotation markers
+    let (encrypted_snd, encrypted_rcv) = async_channel::unbounded::<EncryptedTask>();
+    let configured_ws = Arc::new(RwLock::new(configured_ws));
 
     //
     // P2p setup
     //
-    let (p2p_send_channel, p2p_recv_channel) = async_channel::unbounded::<NetMsg>();
-
     let p2p = net::P2p::new(net_settings.into()).await;
     let p2p = p2p.clone();
     let registry = p2p.protocol_registry();
 
-    registry
-        .register(net::SESSION_ALL, move |channel, p2p| {
-            let raft_id = raft_id.clone();
-            let sender = p2p_send_channel.clone();
-            let seen_net_msgs_cloned = seen_net_msgs.clone();
-            async move {
-                ProtocolRaft::init(raft_id, channel, sender, p2p, seen_net_msgs_cloned).await
-            }
-        })
-    .await;
+    //
+    // Sync through either Raft or the event graph
+    //
+    let mut raft = None;
+    let mut event_graph = None;
+    if settings.event_graph {
+        let sled_db = sled::open(datastore_path.join("tau_events.db"))?;
+        let graph = Arc::new(Mutex::new(EventGraph::<EncryptedTask>::new(&sled_db)?));
+        let (events_snd, events_rcv) = async_channel::unbounded::<Event<EncryptedTask>>();
+
+        let _graph = graph.clone();
+        registry
+            .register(net::SESSION_ALL, move |channel, p2p| {
+                let graph = _graph.clone();
+                let sender = events_snd.clone();
+                async move { ProtocolEventGraph::init(channel, graph, p2p, sender).await.unwrap() }
+            })
+            .await;
+
+        event_graph = Some((graph, events_rcv));
+    } else {
+        let seen_net_msgs = Arc::new(Mutex::new(FxHashMap::default()));
+
+        let datastore_raft = datastore_path.join("tau.db");
+        let raft_settings =
+            RaftSettings { datastore_path: datastore_raft, ..RaftSettings::default() };
+
+        let raft_node = Raft::<EncryptedTask>::new(raft_settings, seen_net_msgs.clone())?;
+        let raft_id = raft_node.id();
+
+        let (p2p_send_channel, p2p_recv_channel) = async_channel::unbounded::<NetMsg>();
+
+        registry
+            .register(net::SESSION_ALL, move |channel, p2p| {
+                let raft_id = raft_id.clone();
+                let sender = p2p_send_channel.clone();
+                let seen_net_msgs_cloned = seen_net_msgs.clone();
+                async move {
+                    ProtocolRaft::init(raft_id, channel, sender, p2p, seen_net_msgs_cloned).await
+                }
+            })
+            .await;
+
+        raft = Some((raft_node, p2p_recv_channel));
+    }
 
     p2p.clone().start(executor.clone()).await?;
 
@@ -229,6 +436,7 @@ async fn realmain(settings: Args, executor: Arc<Executor<'_>>) -> Result<()> {
     let rpc_interface = Arc::new(JsonRpcInterface::new(
         datastore_path.clone(),
         broadcast_snd,
+        encrypted_snd,
         nickname.unwrap(),
         workspace,
         configured_ws.clone(),
@@ -249,10 +457,32 @@ async fn realmain(settings: Args, executor: Arc<Executor<'_>>) -> Result<()> {
     })
     .unwrap();
 
+    if let Some((graph, events_rcv)) = event_graph {
+        executor
+            .spawn(start_event_graph_sync_loop(
+                commits_received.clone(),
+                broadcast_rcv,
+                encrypted_rcv,
+                graph,
+                p2p.clone(),
+                events_rcv,
+                datastore_path,
+                configured_ws,
+                rng,
+            ))
+            .detach();
+
+        shutdown.recv().await?;
+        return Ok(())
+    }
+
+    let (mut raft, p2p_recv_channel) = raft.unwrap();
+
     executor
         .spawn(start_sync_loop(
             commits_received.clone(),
             broadcast_rcv,
+            encrypted_rcv,
             raft.sender(),
             raft.receiver(),
             datastore_path,
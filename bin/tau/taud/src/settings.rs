@@ -37,4 +37,7 @@ pub struct Args {
     /// Current display name    
     #[structopt(long)]
     pub nickname: Option<String>,
+    /// Sync tasks through an event graph instead of Raft
+    #[structopt(long)]
+    pub event_graph: bool,
 }
@@ -20,7 +20,7 @@ use crate::{
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, SerialEncodable, SerialDecodable, PartialEq, Eq)]
-struct TaskEvent {
+pub struct TaskEvent {
     action: String,
     author: String,
     content: String,
@@ -35,6 +35,11 @@ impl TaskEvent {
 
 #[derive(Clone, Debug, Serialize, Deserialize, SerialDecodable, SerialEncodable, PartialEq, Eq)]
 pub struct Comment {
+    #[serde(default)]
+    id: String,
+    /// Id of the comment this one replies to
+    #[serde(default)]
+    parent: Option<String>,
     content: String,
     author: String,
     timestamp: Timestamp,
@@ -43,11 +48,21 @@ pub struct Comment {
 impl Comment {
     pub fn new(content: &str, author: &str) -> Self {
         Self {
+            id: gen_id(30),
+            parent: None,
             content: content.into(),
             author: author.into(),
             timestamp: Timestamp::current_time(),
         }
     }
+
+    pub fn reply(content: &str, author: &str, parent: &str) -> Self {
+        Self { parent: Some(parent.into()), ..Self::new(content, author) }
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -173,6 +188,35 @@ impl TaskInfo {
         self.id
     }
 
+    pub fn set_id(&mut self, id: u32) {
+        debug!(target: "tau", "TaskInfo::set_id()");
+        self.id = id;
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn get_desc(&self) -> &str {
+        &self.desc
+    }
+
+    pub fn get_assign(&self) -> &[String] {
+        &self.assign.0
+    }
+
+    pub fn get_project(&self) -> &[String] {
+        &self.project.0
+    }
+
+    pub fn get_due(&self) -> Option<Timestamp> {
+        self.due
+    }
+
+    pub fn get_created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
     pub fn set_title(&mut self, title: &str) {
         debug!(target: "tau", "TaskInfo::set_title()");
         self.title = title.into();
@@ -198,6 +242,42 @@ impl TaskInfo {
         self.comments.0.push(c);
     }
 
+    pub fn has_comment(&self, id: &str) -> bool {
+        self.comments.0.iter().any(|c| c.id == id)
+    }
+
+    pub fn get_events(&self) -> &[TaskEvent] {
+        &self.events.0
+    }
+
+    /// Merge the comments and events of another copy of this task into this
+    /// one. Both logs are append-only, so replicas that updated the task
+    /// concurrently keep each other's entries rather than overwriting them.
+    pub fn merge_log(&mut self, other: &TaskInfo) {
+        debug!(target: "tau", "TaskInfo::merge_log()");
+        for comment in &other.comments.0 {
+            if !self.comments.0.contains(comment) {
+                self.comments.0.push(comment.clone());
+            }
+        }
+        for event in &other.events.0 {
+            if !self.events.0.contains(event) {
+                self.events.0.push(event.clone());
+            }
+        }
+
+        // Ties are broken on content so every replica ends up in the same order
+        self.comments.0.sort_by(|a, b| (a.timestamp.0, &a.id).cmp(&(b.timestamp.0, &b.id)));
+        self.events.0.sort_by(|a, b| {
+            (a.timestamp.0, &a.author, &a.action, &a.content).cmp(&(
+                b.timestamp.0,
+                &b.author,
+                &b.action,
+                &b.content,
+            ))
+        });
+    }
+
     pub fn set_rank(&mut self, r: Option<f32>) {
         debug!(target: "tau", "TaskInfo::set_rank()");
         self.rank = r;
@@ -1,19 +1,55 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 
 use fxhash::FxHashMap;
 use log::info;
 
-use darkfi::Result;
+use darkfi::{
+    util::file::{load_json_file, save_json_file},
+    Error, Result,
+};
 
 #[derive(Clone)]
 pub struct Workspace {
+    /// Box of the current key, tasks are encrypted with it
     pub encryption: Option<crypto_box::SalsaBox>,
+    /// Boxes of the keys the current one replaced, newest first. Only used
+    /// to decrypt the workspace history.
+    pub old_encryption: Vec<crypto_box::SalsaBox>,
 }
 
 impl Workspace {
     pub fn new() -> Result<Self> {
-        Ok(Self { encryption: None })
+        Ok(Self { encryption: None, old_encryption: vec![] })
     }
+
+    /// Make `secret` the current key, keeping the previous one for history.
+    pub fn rotate(&mut self, secret: &crypto_box::SecretKey) {
+        if let Some(old) = self.encryption.take() {
+            self.old_encryption.insert(0, old);
+        }
+        self.encryption = Some(crypto_box::SalsaBox::new(&secret.public_key(), secret));
+    }
+}
+
+/// Configured workspaces, shared with the RPC interface so key rotations
+/// apply to the sync loop.
+pub type WorkspacesPtr = Arc<RwLock<FxHashMap<String, Workspace>>>;
+
+fn secret_from_str(s: &str) -> Result<crypto_box::SecretKey> {
+    let bytes: [u8; 32] = match bs58::decode(s).into_vec()?.try_into() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(Error::ParseFailed("Invalid workspace secret")),
+    };
+    Ok(crypto_box::SecretKey::from(bytes))
+}
+
+fn salsa_box(s: &str) -> Result<crypto_box::SalsaBox> {
+    let secret = secret_from_str(s)?;
+    Ok(crypto_box::SalsaBox::new(&secret.public_key(), &secret))
 }
 
 /// Parse the configuration file for any configured workspaces and return
@@ -31,14 +67,16 @@ pub fn parse_workspaces(config_file: &PathBuf) -> Result<FxHashMap<String, Works
                 if ws.1.as_table().unwrap().contains_key("secret") {
                     // Build the NaCl box
                     let s = ws.1["secret"].as_str().unwrap();
-                    let bytes: [u8; 32] = bs58::decode(s).into_vec()?.try_into().unwrap();
-                    let secret = crypto_box::SecretKey::from(bytes);
-                    let public = secret.public_key();
-                    let msg_box = crypto_box::SalsaBox::new(&public, &secret);
-                    workspace_info.encryption = Some(msg_box);
+                    workspace_info.encryption = Some(salsa_box(s)?);
                     info!("Instantiated NaCl box for workspace {}", ws.0);
                 }
 
+                if let Some(old_secrets) = ws.1.get("old_secrets").and_then(|v| v.as_array()) {
+                    for s in old_secrets.iter().filter_map(|s| s.as_str()) {
+                        workspace_info.old_encryption.push(salsa_box(s)?);
+                    }
+                }
+
                 ret.insert(ws.0.to_string(), workspace_info);
             }
         }
@@ -47,6 +85,49 @@ pub fn parse_workspaces(config_file: &PathBuf) -> Result<FxHashMap<String, Works
     Ok(ret)
 }
 
+fn keyring_path(dataset_path: &Path) -> PathBuf {
+    dataset_path.join("workspace_keys.json")
+}
+
+/// Apply the key rotations made on this node, which are kept in the
+/// datastore as they postdate the configuration file.
+pub fn load_rotated_keys(
+    dataset_path: &Path,
+    workspaces: &mut FxHashMap<String, Workspace>,
+) -> Result<()> {
+    let path = keyring_path(dataset_path);
+    if !path.exists() {
+        return Ok(())
+    }
+
+    let keyring: BTreeMap<String, Vec<String>> = load_json_file(&path)?;
+    for (ws, secrets) in keyring {
+        if let Some(workspace) = workspaces.get_mut(&ws) {
+            for s in secrets {
+                workspace.rotate(&secret_from_str(&s)?);
+            }
+            info!("Loaded rotated keys of workspace {}", ws);
+        }
+    }
+
+    Ok(())
+}
+
+/// Record a key rotation of a workspace in the datastore.
+pub fn save_rotated_key(
+    dataset_path: &Path,
+    workspace: &str,
+    secret: &crypto_box::SecretKey,
+) -> Result<()> {
+    let path = keyring_path(dataset_path);
+    let mut keyring: BTreeMap<String, Vec<String>> =
+        if path.exists() { load_json_file(&path)? } else { BTreeMap::new() };
+
+    let encoded = bs58::encode(secret.as_bytes()).into_string();
+    keyring.entry(workspace.to_string()).or_default().push(encoded);
+    save_json_file(&path, &keyring)
+}
+
 pub fn find_free_id(task_ids: &[u32]) -> u32 {
     for i in 1.. {
         if !task_ids.contains(&i) {
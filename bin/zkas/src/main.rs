@@ -8,6 +8,7 @@ use clap::Parser as ClapParser;
 
 use darkfi::{
     cli_desc,
+    zk::debug::{trace_circuit, witnesses_from_json},
     zkas::{Analyzer, Compiler, Lexer, Parser, ZkBinary},
 };
 
@@ -34,6 +35,14 @@ struct Args {
     #[clap(short = 'e')]
     examine: bool,
 
+    /// Debug the circuit with the JSON witnesses in <FILE>
+    #[clap(short = 'w', value_name = "FILE")]
+    witnesses: Option<String>,
+
+    /// Number of rows (2^k) of the circuit when debugging
+    #[clap(short = 'k', default_value = "13")]
+    k: u32,
+
     /// ZK script to compile
     input: String,
 }
@@ -118,4 +127,65 @@ fn main() {
         let zkbin = ZkBinary::decode(&bincode).unwrap();
         println!("{:#?}", zkbin);
     }
+
+    if let Some(witnesses_file) = args.witnesses {
+        debug_circuit(&bincode, &witnesses_file, args.k);
+    }
+}
+
+/// Execute the circuit with the given witnesses, printing every executed
+/// opcode along with the computed public inputs, and report the first
+/// unsatisfied constraint.
+fn debug_circuit(bincode: &[u8], witnesses_file: &str, k: u32) {
+    let zkbin = ZkBinary::decode(bincode).unwrap();
+
+    let json = match read_to_string(witnesses_file) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: Failed reading from \"{}\". {}", witnesses_file, e);
+            exit(1);
+        }
+    };
+
+    let witnesses = match witnesses_from_json(&zkbin, &json) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: Failed parsing witnesses. {}", e);
+            exit(1);
+        }
+    };
+
+    let trace = match trace_circuit(k, &zkbin, witnesses) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: Failed executing circuit. {}", e);
+            exit(1);
+        }
+    };
+
+    for (step, entry) in trace.entries.iter().enumerate() {
+        println!("[{}] {:?}", step, entry.opcode);
+        for arg in &entry.args {
+            println!("    arg: {}", arg);
+        }
+        if let Some((index, value)) = &entry.result {
+            println!("    => stack[{}] = {}", index, value);
+        }
+        if let Some(public_input) = &entry.public_input {
+            println!("    => public input: {:?}", public_input);
+        }
+    }
+
+    println!("Public inputs:");
+    for (i, public_input) in trace.public_inputs.iter().enumerate() {
+        println!("    [{}] {:?}", i, public_input);
+    }
+
+    match trace.failure {
+        Some(failure) => {
+            eprintln!("Error: Unsatisfied constraint: {}", failure);
+            exit(1);
+        }
+        None => println!("All constraints satisfied"),
+    }
 }
@@ -1,6 +1,9 @@
 use borsh::BorshSerialize;
 use darkfi::{
-    runtime::{util::serialize_payload, vm_runtime::Runtime},
+    runtime::{
+        util::serialize_payload,
+        vm_runtime::{Runtime, DEFAULT_GAS_LIMIT},
+    },
     Result,
 };
 use pasta_curves::pallas;
@@ -10,7 +13,7 @@ use smart_contract::Args;
 #[test]
 fn run_contract() -> Result<()> {
     let wasm_bytes = std::fs::read("smart_contract.wasm")?;
-    let mut runtime = Runtime::new(&wasm_bytes)?;
+    let mut runtime = Runtime::new(&wasm_bytes, DEFAULT_GAS_LIMIT)?;
 
     let args = Args { a: pallas::Base::from(777), b: pallas::Base::from(666) };
     let payload = args.try_to_vec()?;
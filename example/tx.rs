@@ -1,9 +1,12 @@
 // Example transaction flow
+use std::sync::Arc;
+
 use incrementalmerkletree::{bridgetree::BridgeTree, Tree};
 use pasta_curves::{group::ff::Field, pallas};
 use rand::rngs::OsRng;
 
 use darkfi::{
+    blockchain::{ContractStateOverlay, ContractStateStore},
     crypto::{
         constants::MERKLE_DEPTH,
         keypair::{Keypair, PublicKey, SecretKey},
@@ -13,10 +16,14 @@ use darkfi::{
         proof::{ProvingKey, VerifyingKey},
         OwnCoin, OwnCoins,
     },
-    node::state::{state_transition, ProgramState, StateUpdate},
+    node::{
+        state::{state_transition, ProgramState, StateUpdate},
+        CircuitTable, Dispatcher,
+    },
+    runtime::import::StateReader,
     tx::builder::{
-        TransactionBuilder, TransactionBuilderClearInputInfo, TransactionBuilderInputInfo,
-        TransactionBuilderOutputInfo,
+        ProvingKeys, TransactionBuilder, TransactionBuilderClearInputInfo,
+        TransactionBuilderInputInfo, TransactionBuilderOutputInfo,
     },
     zk::circuit::{BurnContract, MintContract},
     Result,
@@ -50,6 +57,35 @@ struct MemoryState {
 
     /// List of all our secret keys
     secrets: Vec<SecretKey>,
+
+    /// Key/value state of contracts
+    contract_state: ContractStateOverlay,
+
+    /// Contract call dispatcher, without any contracts
+    dispatcher: Dispatcher,
+
+    /// Registered circuits, none in this example
+    circuits: CircuitTable,
+}
+
+/// Gives contracts access to their own state only.
+struct ContractStateReader {
+    contract_state: ContractStateOverlay,
+    contract_id: [u8; 32],
+}
+
+impl StateReader for ContractStateReader {
+    fn merkle_root_exists(&self, _root: &[u8; 32]) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn nullifier_exists(&self, _nullifier: &[u8; 32]) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.contract_state.get(&self.contract_id, key)
+    }
 }
 
 impl ProgramState for MemoryState {
@@ -69,6 +105,30 @@ impl ProgramState for MemoryState {
         self.nullifiers.iter().any(|n| n == nullifier)
     }
 
+    fn contract_exists(&self, _contract_id: &[u8; 32]) -> bool {
+        false
+    }
+
+    fn contract_bincode(&self, _contract_id: &[u8; 32]) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn contract_state(&self) -> ContractStateOverlay {
+        self.contract_state.clone()
+    }
+
+    fn state_reader(
+        &self,
+        contract_state: ContractStateOverlay,
+        contract_id: [u8; 32],
+    ) -> Arc<dyn StateReader> {
+        Arc::new(ContractStateReader { contract_state, contract_id })
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
     fn mint_vk(&self) -> &VerifyingKey {
         &self.mint_vk
     }
@@ -76,6 +136,14 @@ impl ProgramState for MemoryState {
     fn burn_vk(&self) -> &VerifyingKey {
         &self.burn_vk
     }
+
+    fn circuits(&self) -> &CircuitTable {
+        &self.circuits
+    }
+
+    fn slot(&self) -> u64 {
+        0
+    }
 }
 
 impl MemoryState {
@@ -83,6 +151,9 @@ impl MemoryState {
         // Extend our list of nullifiers with the ones from the update
         self.nullifiers.append(&mut update.nullifiers);
 
+        // Write the contract state changes
+        self.contract_state.apply(&update.state_writes);
+
         // Update merkle tree and witnesses
         for (coin, enc_note) in update.coins.into_iter().zip(update.enc_notes.into_iter()) {
             // Add the new coins to the Merkle tree
@@ -129,6 +200,9 @@ fn main() -> Result<()> {
     let mint_vk = VerifyingKey::build(11, &MintContract::default());
     let burn_vk = VerifyingKey::build(11, &BurnContract::default());
 
+    let db = sled::Config::new().temporary(true).open()?;
+    let contract_state = ContractStateOverlay::new(ContractStateStore::new(&db)?);
+
     let mut state = MemoryState {
         tree: BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100),
         merkle_roots: vec![],
@@ -139,27 +213,32 @@ fn main() -> Result<()> {
         cashier_signature_public,
         faucet_signature_public,
         secrets: vec![keypair.secret],
+        contract_state,
+        dispatcher: Dispatcher::default(),
+        circuits: CircuitTable::default(),
     };
 
     let token_id = pallas::Base::random(&mut OsRng);
 
-    let builder = TransactionBuilder {
-        clear_inputs: vec![TransactionBuilderClearInputInfo {
-            value: 110,
-            token_id,
-            signature_secret: cashier_signature_secret,
-        }],
-        inputs: vec![],
-        outputs: vec![TransactionBuilderOutputInfo {
-            value: 110,
-            token_id,
-            public: keypair.public,
-        }],
-    };
+    let mut builder = TransactionBuilder::new();
+    builder.add_clear_input(TransactionBuilderClearInputInfo {
+        value: 110,
+        token_id,
+        signature_secret: cashier_signature_secret,
+    });
+    builder.add_output(TransactionBuilderOutputInfo {
+        value: 110,
+        token_id,
+        public: keypair.public,
+        view_public: keypair.public,
+        memo: vec![],
+        lock_slot: 0,
+    });
 
     let mint_pk = ProvingKey::build(11, &MintContract::default());
     let burn_pk = ProvingKey::build(11, &BurnContract::default());
-    let tx = builder.build(&mint_pk, &burn_pk)?;
+    let prover = ProvingKeys { mint_pk, burn_pk };
+    let tx = builder.build_with_prover(&prover)?;
 
     tx.verify(&state.mint_vk, &state.burn_vk)?;
 
@@ -175,22 +254,23 @@ fn main() -> Result<()> {
     let root = state.tree.root(0).unwrap();
     let merkle_path = state.tree.authentication_path(leaf_position, &root).unwrap();
 
-    let builder = TransactionBuilder {
-        clear_inputs: vec![],
-        inputs: vec![TransactionBuilderInputInfo {
-            leaf_position,
-            merkle_path,
-            secret: keypair.secret,
-            note: note.clone(),
-        }],
-        outputs: vec![TransactionBuilderOutputInfo {
-            value: 110,
-            token_id,
-            public: keypair.public,
-        }],
-    };
-
-    let tx = builder.build(&mint_pk, &burn_pk)?;
+    let mut builder = TransactionBuilder::new();
+    builder.add_input(TransactionBuilderInputInfo {
+        leaf_position,
+        merkle_path,
+        secret: keypair.secret,
+        note: note.clone(),
+    });
+    builder.add_output(TransactionBuilderOutputInfo {
+        value: 110,
+        token_id,
+        public: keypair.public,
+        view_public: keypair.public,
+        memo: vec![],
+        lock_slot: 0,
+    });
+
+    let tx = builder.build_with_prover(&prover)?;
 
     let update = state_transition(&state, tx)?;
     state.apply(update);
@@ -11,26 +11,24 @@ use url::Url;
 
 use darkfi::{
     async_daemonize, cli_desc,
-    dht::{waiting_for_response, Dht, DhtPtr},
+    dht::{self, Dht, DhtPtr},
     net,
     rpc::{
         jsonrpc::{
+            server_error,
             ErrorCode::{InvalidParams, MethodNotFound},
-            JsonError, JsonRequest, JsonResponse, JsonResult,
+            JsonError, JsonRequest, JsonResponse, JsonResult, ServerErrorCode,
         },
         server::{listen_and_serve, RequestHandler},
     },
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         path::get_config_path,
         serial::serialize,
-        expand_path,
     },
     Result,
 };
 
-mod error;
-use error::{server_error, RpcError};
 const CONFIG_FILE: &str = "dhtd_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../dhtd_config.toml");
 
@@ -97,80 +95,63 @@ impl Dhtd {
         let key = params[0].to_string();
         let key_hash = blake3::hash(&serialize(&key));
 
-        // We execute this sequence to prevent lock races between threads
-        // Verify key exists
-        let exists = self.dht.read().await.contains_key(key_hash.clone());
-        if let None = exists {
-            info!("Did not find key: {}", key);
-            return server_error(RpcError::UnknownKey, id).into()
-        }
-
-        // Check if key is local or shoud query network
-        let local = exists.unwrap();
-        if local {
-            match self.dht.read().await.get(key_hash.clone()) {
-                Some(value) => {
-                    let string = std::str::from_utf8(&value).unwrap().to_string();
-                    return JsonResponse::new(json!((key, string)), id).into()
-                }
-                None => {
-                    info!("Did not find key: {}", key);
-                    return server_error(RpcError::UnknownKey, id).into()
-                }
-            }
+        // Check if key is local or should query network
+        if let Some(value) = self.dht.read().await.get(key_hash) {
+            let string = std::str::from_utf8(value).unwrap().to_string();
+            return JsonResponse::new(json!((key, string)), id).into()
         }
 
         info!("Key doesn't exist locally, querring network...");
-        if let Err(e) = self.dht.read().await.request_key(key_hash).await {
-            error!("Failed to query key: {}", e);
-            return server_error(RpcError::QueryFailed, id).into()
-        }
-
-        info!("Waiting response...");
-        match waiting_for_response(self.dht.clone()).await {
-            Ok(response) => {
-                match response {
-                    Some(resp) => {
-                        info!("Key found!");
-                        // Optionally, we insert the key to our local map
-                        if let Err(e) =
-                            self.dht.write().await.insert(resp.key, resp.value.clone()).await
-                        {
-                            error!("Failed to insert key: {}", e);
-                            return server_error(RpcError::KeyInsertFail, id)
-                        }
-                        let string = std::str::from_utf8(&resp.value).unwrap().to_string();
-                        JsonResponse::new(json!((key, string)), id).into()
-                    }
-                    None => {
-                        info!("Did not find key: {}", key);
-                        server_error(RpcError::UnknownKey, id).into()
-                    }
+        match dht::get_value(self.dht.clone(), key_hash).await {
+            Ok(Some(record)) => {
+                info!("Key found!");
+                // Optionally, we cache the record in our local map until it expires
+                let value = record.value.clone();
+                if let Err(e) = dht::cache(self.dht.clone(), record).await {
+                    error!("Failed to insert key: {}", e);
+                    return server_error(ServerErrorCode::KeyInsertFail, id)
                 }
+                let string = std::str::from_utf8(&value).unwrap().to_string();
+                JsonResponse::new(json!((key, string)), id).into()
+            }
+            Ok(None) => {
+                info!("Did not find key: {}", key);
+                server_error(ServerErrorCode::UnknownKey, id).into()
             }
             Err(e) => {
-                error!("Error while waiting network response: {}", e);
-                server_error(RpcError::WaitingNetworkError, id).into()
+                error!("Failed to query key: {}", e);
+                server_error(ServerErrorCode::QueryFailed, id).into()
             }
         }
     }
 
     // RPCAPI:
-    // Insert key value pair in dht.
-    // --> {"jsonrpc": "2.0", "method": "insert", "params": ["key", "value"], "id": 1}
+    // Insert key value pair in dht, with an optional TTL in seconds.
+    // The entry is republished until it gets removed.
+    // --> {"jsonrpc": "2.0", "method": "insert", "params": ["key", "value", ttl], "id": 1}
     // <-- {"jsonrpc": "2.0", "result": "(key, value)", "id": 1}
     async fn insert(&self, id: Value, params: &[Value]) -> JsonResult {
-        if params.len() != 2 || !params[0].is_string() || !params[1].is_string() {
+        if params.len() < 2 ||
+            params.len() > 3 ||
+            !params[0].is_string() ||
+            !params[1].is_string() ||
+            (params.len() == 3 && !params[2].is_u64())
+        {
             return JsonError::new(InvalidParams, None, id).into()
         }
 
         let key = params[0].to_string();
         let key_hash = blake3::hash(&serialize(&key));
         let value = params[1].to_string();
+        let ttl = match params.get(2) {
+            Some(ttl) => ttl.as_u64().unwrap(),
+            None => dht::DEFAULT_TTL,
+        };
 
-        if let Err(e) = self.dht.write().await.insert(key_hash, value.as_bytes().to_vec()).await {
+        let value_bytes = value.as_bytes().to_vec();
+        if let Err(e) = dht::insert(self.dht.clone(), key_hash, value_bytes, ttl).await {
             error!("Failed to insert key: {}", e);
-            return server_error(RpcError::KeyInsertFail, id)
+            return server_error(ServerErrorCode::KeyInsertFail, id)
         }
 
         JsonResponse::new(json!((key, value)), id).into()
@@ -189,7 +170,7 @@ impl Dhtd {
         let key_hash = blake3::hash(&serialize(&key));
 
         // Check if key value pair existed and act accordingly
-        let result = self.dht.write().await.remove(key_hash).await;
+        let result = dht::remove(self.dht.clone(), key_hash).await;
         match result {
             Ok(option) => match option {
                 Some(k) => {
@@ -198,12 +179,12 @@ impl Dhtd {
                 }
                 None => {
                     info!("Did not find key: {}", key);
-                    server_error(RpcError::UnknownKey, id).into()
+                    server_error(ServerErrorCode::UnknownKey, id).into()
                 }
             },
             Err(e) => {
                 error!("Failed to remove key: {}", e);
-                server_error(RpcError::KeyRemoveFail, id)
+                server_error(ServerErrorCode::KeyRemoveFail, id)
             }
         }
     }
@@ -273,7 +254,7 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     let p2p = net::P2p::new(network_settings).await;
 
     // Initialize daemon dht
-    let dht = Dht::new(None, p2p.clone(), shutdown.clone(), ex.clone()).await?;
+    let dht = Dht::new(None, p2p.clone(), ex.clone()).await?;
 
     // Initialize daemon
     let dhtd = Dhtd::new(dht.clone()).await?;
@@ -12,17 +12,19 @@ use url::Url;
 
 use darkfi::{
     async_daemonize, cli_desc,
-    dht::{waiting_for_response, Dht, DhtPtr},
+    dht::{self, Dht, DhtPtr},
     net,
     rpc::{
+        dnet,
         jsonrpc::{
+            server_error,
             ErrorCode::{InvalidParams, MethodNotFound},
-            JsonError, JsonRequest, JsonResponse, JsonResult,
+            JsonError, JsonRequest, JsonResponse, JsonResult, ServerErrorCode,
         },
         server::{listen_and_serve, RequestHandler},
     },
     util::{
-        cli::{get_log_config, get_log_level, spawn_config},
+        cli::{get_log_level, spawn_config},
         expand_path,
         path::get_config_path,
         serial::serialize,
@@ -30,8 +32,6 @@ use darkfi::{
     Result,
 };
 
-mod error;
-use error::{server_error, RpcError};
 const CONFIG_FILE: &str = "fud_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../fud_config.toml");
 
@@ -100,23 +100,22 @@ impl Fud {
         }
 
         let entries = fs::read_dir(&self.folder).unwrap();
-        {
-            let mut lock = self.dht.write().await;
 
-            // Sync lookup map with network
-            if let Err(e) = lock.sync_lookup_map().await {
-                error!("Failed to sync lookup map: {}", e);
-            }
+        // Join the dht network
+        if let Err(e) = dht::bootstrap(self.dht.clone()).await {
+            error!("Failed to bootstrap dht: {}", e);
+        }
 
-            for entry in entries {
-                let e = entry.unwrap();
-                let name = String::from(e.file_name().to_str().unwrap());
-                info!("Entry: {}", name);
-                let key_hash = blake3::hash(&serialize(&name));
-                let value: Vec<u8> = std::fs::read(e.path()).unwrap();
-                if let Err(e) = lock.insert(key_hash, value).await {
-                    error!("Failed to insert key: {}", e);
-                }
+        for entry in entries {
+            let e = entry.unwrap();
+            let name = String::from(e.file_name().to_str().unwrap());
+            info!("Entry: {}", name);
+            let key_hash = blake3::hash(&serialize(&name));
+            let value: Vec<u8> = std::fs::read(e.path()).unwrap();
+            if let Err(e) =
+                dht::insert_file(self.dht.clone(), key_hash, value, dht::DEFAULT_TTL).await
+            {
+                error!("Failed to insert key: {}", e);
             }
         }
 
@@ -127,23 +126,20 @@ impl Fud {
     async fn disconnect(&self) -> Result<()> {
         debug!("Peer disconnecting, signaling network");
 
-        {
-            let mut lock = self.dht.write().await;
-            let records = lock.map.clone();
-            for key in records.keys() {
-                let result = lock.remove(*key).await;
-                match result {
-                    Ok(option) => match option {
-                        Some(k) => {
-                            debug!("Hash key removed: {}", k);
-                        }
-                        None => {
-                            warn!("Did not find key: {}", key);
-                        }
-                    },
-                    Err(e) => {
-                        error!("Failed to remove key: {}", e);
+        let records = self.dht.read().await.map.clone();
+        for key in records.keys() {
+            let result = dht::remove(self.dht.clone(), *key).await;
+            match result {
+                Ok(option) => match option {
+                    Some(k) => {
+                        debug!("Hash key removed: {}", k);
                     }
+                    None => {
+                        warn!("Did not find key: {}", key);
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to remove key: {}", e);
                 }
             }
         }
@@ -197,51 +193,50 @@ impl Fud {
         info!("Sync process started");
 
         let entries = fs::read_dir(&self.folder).unwrap();
-        {
-            let mut lock = self.dht.write().await;
-            let records = lock.map.clone();
-            let mut entries_hashes = HashSet::new();
-
-            // We iterate files for new records
-            for entry in entries {
-                let e = entry.unwrap();
-                let name = String::from(e.file_name().to_str().unwrap());
-                info!("Entry: {}", name);
-                let key_hash = blake3::hash(&serialize(&name));
-                entries_hashes.insert(key_hash);
-
-                if records.contains_key(&key_hash) {
-                    continue
-                }
+        let records = self.dht.read().await.map.clone();
+        let mut entries_hashes = HashSet::new();
 
-                let value: Vec<u8> = std::fs::read(e.path()).unwrap();
-                if let Err(e) = lock.insert(key_hash, value).await {
-                    error!("Failed to insert key: {}", e);
-                    return server_error(RpcError::KeyInsertFail, id)
-                }
+        // We iterate files for new records
+        for entry in entries {
+            let e = entry.unwrap();
+            let name = String::from(e.file_name().to_str().unwrap());
+            info!("Entry: {}", name);
+            let key_hash = blake3::hash(&serialize(&name));
+            entries_hashes.insert(key_hash);
+
+            if records.contains_key(&key_hash) {
+                continue
             }
 
-            // We check records for removed files
-            let records = lock.map.clone();
-            for key in records.keys() {
-                if entries_hashes.contains(key) {
-                    continue
-                }
+            let value: Vec<u8> = std::fs::read(e.path()).unwrap();
+            if let Err(e) =
+                dht::insert_file(self.dht.clone(), key_hash, value, dht::DEFAULT_TTL).await
+            {
+                error!("Failed to insert key: {}", e);
+                return server_error(ServerErrorCode::KeyInsertFail, id)
+            }
+        }
 
-                let result = lock.remove(*key).await;
-                match result {
-                    Ok(option) => match option {
-                        Some(k) => {
-                            debug!("Hash key removed: {}", k);
-                        }
-                        None => {
-                            warn!("Did not find key: {}", key);
-                        }
-                    },
-                    Err(e) => {
-                        error!("Failed to remove key: {}", e);
-                        return server_error(RpcError::KeyRemoveFail, id)
+        // We check records for removed files
+        let records = self.dht.read().await.map.clone();
+        for key in records.keys() {
+            if entries_hashes.contains(key) {
+                continue
+            }
+
+            let result = dht::remove(self.dht.clone(), *key).await;
+            match result {
+                Ok(option) => match option {
+                    Some(k) => {
+                        debug!("Hash key removed: {}", k);
                     }
+                    None => {
+                        warn!("Did not find key: {}", key);
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to remove key: {}", e);
+                    return server_error(ServerErrorCode::KeyRemoveFail, id)
                 }
             }
         }
@@ -262,62 +257,35 @@ impl Fud {
         let key = params[0].as_str().unwrap().to_string();
         let key_hash = blake3::hash(&serialize(&key));
 
-        // We execute this sequence to prevent lock races between threads
-        // Verify key exists
-        let exists = self.dht.read().await.contains_key(key_hash.clone());
-        if let None = exists {
-            info!("Did not find key: {}", key);
-            return server_error(RpcError::UnknownKey, id).into()
-        }
-
         // Check if key is local or should query network
         let path = self.folder.join(key.clone());
-        let local = exists.unwrap();
-        if local {
-            match self.dht.read().await.get(key_hash.clone()) {
-                Some(_) => return JsonResponse::new(json!(path), id).into(),
-                None => {
-                    info!("Did not find key: {}", key);
-                    return server_error(RpcError::UnknownKey, id).into()
-                }
-            }
+        if self.dht.read().await.get(key_hash).is_some() {
+            return JsonResponse::new(json!(path), id).into()
         }
 
         info!("Key doesn't exist locally, querring network...");
-        if let Err(e) = self.dht.read().await.request_key(key_hash).await {
-            error!("Failed to query key: {}", e);
-            return server_error(RpcError::QueryFailed, id).into()
-        }
+        match dht::get_file(self.dht.clone(), key_hash).await {
+            Ok(Some((record, value))) => {
+                info!("Key found!");
+                // Optionally, we cache the file in our local map until it expires
+                if let Err(e) = dht::cache_file(self.dht.clone(), record, value.clone()).await {
+                    error!("Failed to insert key: {}", e);
+                    return server_error(ServerErrorCode::KeyInsertFail, id)
+                }
 
-        info!("Waiting response...");
-        match waiting_for_response(self.dht.clone()).await {
-            Ok(response) => {
-                match response {
-                    Some(resp) => {
-                        info!("Key found!");
-                        // Optionally, we insert the key to our local map
-                        if let Err(e) =
-                            self.dht.write().await.insert(resp.key, resp.value.clone()).await
-                        {
-                            error!("Failed to insert key: {}", e);
-                            return server_error(RpcError::KeyInsertFail, id)
-                        }
-
-                        if let Err(e) = std::fs::write(path.clone(), resp.value) {
-                            error!("Failed to generate file for key: {}", e);
-                            return server_error(RpcError::FileGenerationFail, id)
-                        }
-                        JsonResponse::new(json!(path), id).into()
-                    }
-                    None => {
-                        info!("Did not find key: {}", key);
-                        server_error(RpcError::UnknownKey, id).into()
-                    }
+                if let Err(e) = std::fs::write(path.clone(), value) {
+                    error!("Failed to generate file for key: {}", e);
+                    return server_error(ServerErrorCode::FileGenerationFail, id)
                 }
+                JsonResponse::new(json!(path), id).into()
+            }
+            Ok(None) => {
+                info!("Did not find key: {}", key);
+                server_error(ServerErrorCode::UnknownKey, id).into()
             }
             Err(e) => {
-                error!("Error while waiting network response: {}", e);
-                server_error(RpcError::WaitingNetworkError, id).into()
+                error!("Failed to query key: {}", e);
+                server_error(ServerErrorCode::QueryFailed, id).into()
             }
         }
     }
@@ -347,6 +315,11 @@ impl RequestHandler for Fud {
             return JsonError::new(InvalidParams, None, req.id).into()
         }
 
+        if req.method.as_str().map_or(false, dnet::is_dnet_method) {
+            let p2p = self.dht.read().await.p2p.clone();
+            return dnet::handle_request(&p2p, req).await
+        }
+
         let params = req.params.as_array().unwrap();
 
         match req.method.as_str() {
@@ -384,7 +357,7 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     let p2p = net::P2p::new(network_settings).await;
 
     // Initialize daemon dht
-    let dht = Dht::new(None, p2p.clone(), shutdown.clone(), ex.clone()).await?;
+    let dht = Dht::new(None, p2p.clone(), ex.clone()).await?;
 
     // Initialize daemon
     let folder = expand_path(&args.folder)?;
@@ -9,8 +9,7 @@ use darkfi::{
     blockchain::Blockchain,
     consensus::{TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP},
     crypto::{
-        constants::MERKLE_DEPTH,
-        keypair::{PublicKey, SecretKey},
+        address::PaymentAddress, constants::MERKLE_DEPTH, keypair::SecretKey,
         merkle_node::MerkleNode,
     },
     node::{
@@ -57,8 +56,7 @@ async fn main() -> darkfi::Result<()> {
     let path = folder.to_owned() + "/wallet.db";
     let wallet = init_wallet(&path, &pass).await?;
     let client = Arc::new(Client::new(wallet.clone()).await?);
-    let address = wallet.get_default_address().await?;
-    let pubkey = PublicKey::try_from(address)?;
+    let address = PaymentAddress::new(&wallet.get_default_keypair().await?.secret);
     pb.finish();
 
     let pb = progress_bar("Initializing sled database...");
@@ -107,15 +105,16 @@ async fn main() -> darkfi::Result<()> {
     println!("  Applying {} transactions...", txs);
     for i in 0..txs {
         println!("    tx {}", i);
-        let tx =
-            match client.build_transaction(pubkey, amount, token_id, true, state_arc.clone()).await
-            {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("Failed building transaction: {}", e);
-                    return Err(e.into())
-                }
-            };
+        let tx = match client
+            .build_transaction(address, amount, 0, token_id, true, state_arc.clone())
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Failed building transaction: {}", e);
+                return Err(e.into())
+            }
+        };
 
         let update = match state_transition(&mem_state, tx.clone()) {
             Ok(v) => v,
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS diversified_keys(
+	public BLOB PRIMARY KEY NOT NULL,
+	secret BLOB NOT NULL,
+	parent BLOB NOT NULL,
+	diversifier INTEGER NOT NULL
+);
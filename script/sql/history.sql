@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS history(
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	coin BLOB NOT NULL,
+	kind TEXT NOT NULL,
+	value BLOB NOT NULL,
+	token_id BLOB NOT NULL,
+	slot INTEGER NOT NULL,
+	UNIQUE(coin, kind)
+);
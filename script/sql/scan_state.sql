@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS scan_state(
+	id INTEGER PRIMARY KEY CHECK (id = 0),
+	birthday_slot INTEGER NOT NULL,
+	scanned_slot INTEGER NOT NULL
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS seed(
+	mnemonic TEXT NOT NULL,
+	next_index INTEGER NOT NULL
+);
@@ -0,0 +1,223 @@
+use crate::{
+    tx::Transaction,
+    util::serial::{serialize, SerialDecodable, SerialEncodable},
+};
+
+/// Golomb-Rice parameter: number of low bits of each delta written verbatim
+const FILTER_P: u8 = 19;
+/// Inverse of the false positive rate of a filter match
+const FILTER_M: u64 = 784931;
+
+/// Compact block filter, a Golomb-coded set over the coins created and the
+/// nullifiers revealed in a block, in the style of BIP158.
+///
+/// Light wallets download the filters instead of the full blocks, and only
+/// fetch the blocks whose filter matches one of their coins or nullifiers.
+/// A match is a false positive with probability `1/FILTER_M`, and there
+/// are no false negatives.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct BlockFilter {
+    /// Number of items in the set
+    pub n: u64,
+    /// Golomb-Rice coded deltas of the sorted item hashes
+    pub data: Vec<u8>,
+}
+
+impl BlockFilter {
+    /// Build the filter of the block with the given headerhash over `items`.
+    pub fn new(block_hash: &blake3::Hash, items: &[Vec<u8>]) -> Self {
+        let mut items = items.to_vec();
+        items.sort_unstable();
+        items.dedup();
+
+        let n = items.len() as u64;
+        let mut values: Vec<u64> =
+            items.iter().map(|item| hash_to_range(block_hash, item, n * FILTER_M)).collect();
+        values.sort_unstable();
+
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values {
+            writer.write_golomb(value - last);
+            last = value;
+        }
+
+        Self { n, data: writer.finish() }
+    }
+
+    /// Build the filter of the block with the given headerhash and transactions.
+    pub fn from_txs(block_hash: &blake3::Hash, txs: &[Transaction]) -> Self {
+        Self::new(block_hash, &filter_items(txs))
+    }
+
+    /// Check if any of the given items may be in the filter of the block
+    /// with the given headerhash. `false` means none of them are.
+    pub fn match_any(&self, block_hash: &blake3::Hash, items: &[Vec<u8>]) -> bool {
+        if self.n == 0 || items.is_empty() {
+            return false
+        }
+
+        let mut queries: Vec<u64> =
+            items.iter().map(|item| hash_to_range(block_hash, item, self.n * FILTER_M)).collect();
+        queries.sort_unstable();
+        let mut queries = queries.into_iter().peekable();
+
+        // Walk the set and the sorted queries side by side
+        let mut reader = BitReader { bytes: &self.data, pos: 0 };
+        let mut value = 0;
+        for _ in 0..self.n {
+            value += match reader.read_golomb() {
+                Some(v) => v,
+                None => return false,
+            };
+
+            while queries.next_if(|query| *query < value).is_some() {}
+
+            match queries.peek() {
+                Some(query) if *query == value => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    /// Hash of the serialized filter.
+    pub fn hash(&self) -> blake3::Hash {
+        blake3::hash(&serialize(self))
+    }
+
+    /// Filter header of the block, committing to its filter and to the
+    /// filter header of the previous block. Light clients compare the
+    /// header chains served by different peers before trusting a filter.
+    pub fn header(&self, prev_header: &blake3::Hash) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.hash().as_bytes());
+        hasher.update(prev_header.as_bytes());
+        hasher.finalize()
+    }
+}
+
+/// Items a block's filter is built over: the serialized coins of the
+/// transactions' outputs and the serialized nullifiers of their inputs.
+pub fn filter_items(txs: &[Transaction]) -> Vec<Vec<u8>> {
+    let mut items = vec![];
+    for tx in txs {
+        for input in &tx.inputs {
+            items.push(serialize(&input.revealed.nullifier));
+        }
+        for output in &tx.outputs {
+            items.push(serialize(&output.revealed.coin));
+        }
+    }
+    items
+}
+
+/// Map an item uniformly onto `0..f`, keyed by the block's headerhash so
+/// collisions don't carry over between blocks.
+fn hash_to_range(block_hash: &blake3::Hash, item: &[u8], f: u64) -> u64 {
+    let hash = blake3::keyed_hash(block_hash.as_bytes(), item);
+    let h = u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap());
+    ((h as u128 * f as u128) >> 64) as u64
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        self.current = (self.current << 1) | bit as u8;
+        self.used += 1;
+        if self.used == 8 {
+            self.bytes.push(self.current);
+            self.current = 0;
+            self.used = 0;
+        }
+    }
+
+    fn write_golomb(&mut self, value: u64) {
+        for _ in 0..(value >> FILTER_P) {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+
+        for i in (0..FILTER_P).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.bytes.push(self.current << (8 - self.used));
+        }
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Position in bits
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1 == 1;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn read_golomb(&mut self) -> Option<u64> {
+        let mut quotient = 0;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+
+        let mut remainder = 0;
+        for _ in 0..FILTER_P {
+            remainder = (remainder << 1) | self.read_bit()? as u64;
+        }
+
+        Some((quotient << FILTER_P) | remainder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_filter() {
+        let block_hash = blake3::hash(b"block");
+        let items: Vec<Vec<u8>> = (0..500u64).map(|i| i.to_le_bytes().to_vec()).collect();
+        let filter = BlockFilter::new(&block_hash, &items);
+        assert_eq!(filter.n, 500);
+
+        // No false negatives
+        for item in &items {
+            assert!(filter.match_any(&block_hash, &[item.clone()]));
+        }
+
+        // False positives are rare
+        let others: Vec<Vec<u8>> = (500..10500u64).map(|i| i.to_le_bytes().to_vec()).collect();
+        let false_positives =
+            others.iter().filter(|item| filter.match_any(&block_hash, &[(*item).clone()])).count();
+        assert!(false_positives < 5);
+        assert!(filter.match_any(&block_hash, &[others[0].clone(), items[42].clone()]));
+
+        // Nothing matches an empty filter
+        let empty = BlockFilter::from_txs(&block_hash, &[]);
+        assert!(!empty.match_any(&block_hash, &items));
+
+        // Headers chain the filters
+        let genesis = blake3::Hash::from([0; 32]);
+        assert_ne!(filter.header(&genesis), empty.header(&genesis));
+        assert_ne!(filter.header(&genesis), filter.header(&empty.header(&genesis)));
+    }
+}
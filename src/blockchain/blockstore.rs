@@ -131,6 +131,19 @@ impl BlockStore {
         Ok(())
     }
 
+    /// Remove the blocks of the given headerhashes from the store. With sled,
+    /// the operation is done as a batch.
+    pub fn remove(&self, headerhashes: &[blake3::Hash]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for hash in headerhashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
     /// Check if the blockstore contains a given headerhash.
     pub fn contains(&self, headerhash: &blake3::Hash) -> Result<bool> {
         Ok(self.0.contains_key(headerhash.as_bytes())?)
@@ -259,24 +272,47 @@ impl BlockOrderStore {
         Ok(slots)
     }
 
-    /// Fetch n hashes after given slot. In the iteration, if a slot is not
-    /// found, the iteration stops and the function returns what it has found
-    /// so far in the `BlockOrderStore`.
+    /// Fetch n hashes after given slot, in ascending slot order.
     pub fn get_after(&self, slot: u64, n: u64) -> Result<Vec<blake3::Hash>> {
         let mut ret = vec![];
+        let start = match slot.checked_add(1) {
+            Some(v) => v,
+            None => return Ok(ret),
+        };
 
-        let mut key = slot;
-        let mut counter = 0;
-        while counter <= n {
-            if let Some(found) = self.0.get_gt(key.to_be_bytes())? {
-                let key_bytes: [u8; 8] = found.0.as_ref().try_into().unwrap();
-                key = u64::from_be_bytes(key_bytes);
-                let header_hash = deserialize(&found.1)?;
-                ret.push(header_hash);
-                counter += 1;
-                continue
-            }
-            break
+        for found in self.0.range(start.to_be_bytes()..).take(n as usize) {
+            let (_, value) = found?;
+            let hash_bytes: [u8; 32] = value.as_ref().try_into().unwrap();
+            ret.push(blake3::Hash::from(hash_bytes));
+        }
+
+        Ok(ret)
+    }
+
+    /// Iterate over the store in ascending slot order, yielding tuples of
+    /// (`slot`, `headerhash`).
+    pub fn iter(&self) -> impl Iterator<Item = Result<(u64, blake3::Hash)>> {
+        self.0.iter().map(|found| {
+            let (key, value) = found?;
+            let slot_bytes: [u8; 8] = key.as_ref().try_into().unwrap();
+            let hash_bytes: [u8; 32] = value.as_ref().try_into().unwrap();
+            Ok((u64::from_be_bytes(slot_bytes), blake3::Hash::from(hash_bytes)))
+        })
+    }
+
+    /// Fetch the slots and headerhashes in the slot range `start..end`,
+    /// in ascending slot order.
+    pub fn get_range(&self, start: u64, end: u64) -> Result<Vec<(u64, blake3::Hash)>> {
+        let mut ret = vec![];
+        if start >= end {
+            return Ok(ret)
+        }
+
+        for found in self.0.range(start.to_be_bytes()..end.to_be_bytes()) {
+            let (key, value) = found?;
+            let slot_bytes: [u8; 8] = key.as_ref().try_into().unwrap();
+            let hash_bytes: [u8; 32] = value.as_ref().try_into().unwrap();
+            ret.push((u64::from_be_bytes(slot_bytes), blake3::Hash::from(hash_bytes)));
         }
 
         Ok(ret)
@@ -0,0 +1,91 @@
+/// Bloom filter over byte string keys, answering whether a key may be in a
+/// set, or is definitely not in it. Used to avoid database reads for the
+/// common case of looking up keys that don't exist.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    /// Filter bits
+    bits: Vec<u64>,
+    /// Number of bit positions each key sets
+    hashes: u32,
+    /// Number of keys the filter was sized for
+    capacity: usize,
+    /// Number of keys inserted
+    len: usize,
+}
+
+impl BloomFilter {
+    /// Create an empty filter sized for `capacity` keys, with the given
+    /// false positive rate.
+    pub fn new(capacity: usize, fp_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let hashes = ((num_bits / capacity as f64) * ln2).round().max(1.0) as u32;
+        let words = (num_bits as usize + 63) / 64;
+
+        Self { bits: vec![0; words], hashes, capacity, len: 0 }
+    }
+
+    /// Bit positions of a key, derived with double hashing from its BLAKE3 hash.
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = blake3::hash(key);
+        let bytes = hash.as_bytes();
+        let h1 = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let num_bits = self.bits.len() as u64 * 64;
+
+        (0..self.hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    /// Add a key to the filter.
+    pub fn insert(&mut self, key: &[u8]) {
+        let positions: Vec<usize> = self.positions(key).collect();
+        for pos in positions {
+            self.bits[pos / 64] |= 1 << (pos % 64);
+        }
+        self.len += 1;
+    }
+
+    /// Check if a key may be in the set. `false` means it definitely isn't.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        self.positions(key).all(|pos| self.bits[pos / 64] & (1 << (pos % 64)) != 0)
+    }
+
+    /// Number of keys inserted.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if more keys were inserted than the filter was sized for,
+    /// meaning its false positive rate is degrading.
+    pub fn is_saturated(&self) -> bool {
+        self.len > self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000u64 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!(!filter.is_saturated());
+
+        // No false negatives
+        assert!((0..1000u64).all(|i| filter.may_contain(&i.to_le_bytes())));
+
+        // False positives stay around the configured rate
+        let false_positives =
+            (1000..11000u64).filter(|i| filter.may_contain(&i.to_le_bytes())).count();
+        assert!(false_positives < 300);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use crate::{consensus::Header, Error, Result};
+
+/// Hardcoded `(slot, blockhash)` pairs of the canonical chain.
+/// Blocks at or before the last checkpoint can never be reorged, and
+/// headers up to it are validated against these during initial sync.
+const CHECKPOINTS: &[(u64, [u8; 32])] = &[];
+
+/// Set of known-good blocks of the canonical chain, ordered by slot.
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoints(BTreeMap<u64, blake3::Hash>);
+
+impl Checkpoints {
+    /// Create a checkpoint set from the given `(slot, blockhash)` pairs.
+    pub fn new(checkpoints: &[(u64, blake3::Hash)]) -> Self {
+        Self(checkpoints.iter().cloned().collect())
+    }
+
+    /// Checkpoints hardcoded in this release.
+    pub fn hardcoded() -> Self {
+        Self(CHECKPOINTS.iter().map(|(slot, hash)| (*slot, blake3::Hash::from(*hash))).collect())
+    }
+
+    /// Retrieve the checkpointed block hash of the given slot, if any.
+    pub fn get(&self, slot: u64) -> Option<&blake3::Hash> {
+        self.0.get(&slot)
+    }
+
+    /// Retrieve the last checkpoint slot and hash.
+    pub fn last(&self) -> Option<(u64, blake3::Hash)> {
+        self.0.iter().next_back().map(|(slot, hash)| (*slot, *hash))
+    }
+
+    /// Check if the given slot is at or before the last checkpoint.
+    pub fn covers(&self, slot: u64) -> bool {
+        match self.last() {
+            Some((last_slot, _)) => slot <= last_slot,
+            None => false,
+        }
+    }
+
+    /// Verify a block hash against the checkpoint of its slot, if one exists.
+    pub fn verify(&self, slot: u64, hash: &blake3::Hash) -> Result<()> {
+        match self.get(slot) {
+            Some(checkpoint) if checkpoint != hash => Err(Error::CheckpointMismatch(slot)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate a sequence of headers extending the block `previous`.
+    /// Each header must point to the one before it, have a greater slot,
+    /// and match any checkpoint on the way. This doesn't touch transactions,
+    /// so a bad batch gets rejected before any state transition runs.
+    pub fn verify_headers(&self, previous: (u64, blake3::Hash), headers: &[Header]) -> Result<()> {
+        let (mut prev_slot, mut prev_hash) = previous;
+
+        for header in headers {
+            if header.state != prev_hash || header.slot <= prev_slot {
+                return Err(Error::BlockNotExtending(header.slot))
+            }
+
+            let hash = header.headerhash();
+            self.verify(header.slot, &hash)?;
+
+            prev_slot = header.slot;
+            prev_hash = hash;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::time::Timestamp;
+
+    fn header(previous: blake3::Hash, slot: u64) -> Header {
+        Header { slot, ..Header::genesis_header(Timestamp(0), previous) }
+    }
+
+    #[test]
+    fn test_checkpoint_headers() {
+        let genesis = blake3::hash(b"genesis");
+        let first = header(genesis, 1);
+        let second = header(first.headerhash(), 2);
+
+        let checkpoints = Checkpoints::new(&[(2, second.headerhash())]);
+        assert!(checkpoints.covers(2));
+        assert!(!checkpoints.covers(3));
+        assert!(checkpoints.verify_headers((0, genesis), &[first.clone(), second]).is_ok());
+
+        // Wrong block in a checkpointed slot
+        let forked = header(first.headerhash(), 2);
+        let forked = Header { timestamp: Timestamp(1), ..forked };
+        assert!(checkpoints.verify_headers((0, genesis), &[first.clone(), forked]).is_err());
+
+        // Broken chain
+        let unlinked = header(genesis, 2);
+        assert!(checkpoints.verify_headers((1, first.headerhash()), &[unlinked]).is_err());
+    }
+}
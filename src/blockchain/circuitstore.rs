@@ -0,0 +1,92 @@
+use crate::{
+    util::serial::{deserialize, SerialDecodable, SerialEncodable},
+    Result,
+};
+
+use super::ContractStateStore;
+
+/// Hashed into the ID the circuit registry contract is registered under.
+const CIRCUIT_REGISTRY_DOMAIN: &[u8] = b"DarkFi:CircuitRegistry";
+
+/// Prefix of the state keys holding circuits, by name and version.
+const CIRCUIT_KEY_PREFIX: &[u8] = b"circuit:";
+
+/// Prefix of the state keys holding the latest version of each circuit.
+const VERSION_KEY_PREFIX: &[u8] = b"version:";
+
+/// ID of the native circuit registry contract.
+pub fn circuit_registry_id() -> [u8; 32] {
+    *blake3::hash(CIRCUIT_REGISTRY_DOMAIN).as_bytes()
+}
+
+/// A zkas circuit registered on chain. Every version of a circuit stays
+/// registered, so proofs are verified against the exact circuit they
+/// were made for. A version replaces the one before it at its activation
+/// slot, after which proofs of the previous version are only accepted
+/// for a transition window.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct CircuitRecord {
+    /// Name the circuit is registered under, like `dao-exec`
+    pub name: String,
+    /// Version of the circuit, counting up from 0 for each name
+    pub version: u32,
+    /// `k` parameter the circuit keys are built with
+    pub k_param: u32,
+    /// First slot proofs of this version are accepted in
+    pub activation_slot: u64,
+    /// Compiled zkas binary of the circuit
+    pub zkas_bincode: Vec<u8>,
+}
+
+impl CircuitRecord {
+    /// State key of a circuit version. Names can't hold a NUL byte, so
+    /// the key of a name is never the prefix of another's.
+    pub fn key(name: &str, version: u32) -> Vec<u8> {
+        [CIRCUIT_KEY_PREFIX, name.as_bytes(), &[0], &version.to_be_bytes()].concat()
+    }
+
+    /// State key of the latest version of a circuit.
+    pub fn version_key(name: &str) -> Vec<u8> {
+        [VERSION_KEY_PREFIX, name.as_bytes()].concat()
+    }
+}
+
+/// The `CircuitStore` gives access to the zkas circuits registered on
+/// chain. Circuits are registered by transactions calling the circuit
+/// registry contract, so they are kept in its state in the
+/// [`ContractStateStore`], and every node agrees on which circuits and
+/// versions exist as of a block.
+#[derive(Clone)]
+pub struct CircuitStore(ContractStateStore);
+
+impl CircuitStore {
+    /// Opens the `CircuitStore` on top of the given contract state.
+    pub fn new(contract_state: ContractStateStore) -> Self {
+        Self(contract_state)
+    }
+
+    /// Retrieve a version of the circuit registered under `name`.
+    pub fn get(&self, name: &str, version: u32) -> Result<Option<CircuitRecord>> {
+        match self.0.get(&circuit_registry_id(), &CircuitRecord::key(name, version))? {
+            Some(bytes) => Ok(Some(deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the latest version of the circuit registered under `name`.
+    pub fn get_latest(&self, name: &str) -> Result<Option<CircuitRecord>> {
+        match self.0.get(&circuit_registry_id(), &CircuitRecord::version_key(name))? {
+            Some(bytes) => self.get(name, deserialize(&bytes)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve all the registered circuits, ordered by name and version.
+    pub fn get_all(&self) -> Result<Vec<CircuitRecord>> {
+        let mut ret = vec![];
+        for (_, bytes) in self.0.scan(&circuit_registry_id(), CIRCUIT_KEY_PREFIX)? {
+            ret.push(deserialize(&bytes)?);
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    util::serial::{SerialDecodable, SerialEncodable},
+    Result,
+};
+
+const SLED_CONTRACTS_TREE: &[u8] = b"_contracts";
+const SLED_CONTRACT_STATE_TREE: &[u8] = b"_contractstate";
+
+/// The `ContractStore` is a `sled` tree storing the wasm bincode of all
+/// deployed contracts. The key is the contract ID, while the value is the
+/// bincode.
+#[derive(Clone)]
+pub struct ContractStore(sled::Tree);
+
+impl ContractStore {
+    /// Opens a new or existing `ContractStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_CONTRACTS_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a slice of `(contract_id, wasm_bincode)` pairs into the store.
+    /// With sled, the operation is done as a batch.
+    pub fn insert(&self, contracts: &[([u8; 32], Vec<u8>)]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for (contract_id, wasm_bincode) in contracts {
+            batch.insert(contract_id.to_vec(), wasm_bincode.clone());
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Retrieve the wasm bincode of the given contract, if it's deployed.
+    pub fn get(&self, contract_id: &[u8; 32]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.get(contract_id)?.map(|v| v.to_vec()))
+    }
+
+    /// Check if a contract with the given ID is deployed.
+    pub fn contains(&self, contract_id: &[u8; 32]) -> Result<bool> {
+        Ok(self.0.contains_key(contract_id)?)
+    }
+
+    /// Retrieve all deployed contracts as `(contract_id, wasm_bincode)` pairs.
+    /// Be careful as this will try to load everything in memory.
+    pub fn get_all(&self) -> Result<Vec<([u8; 32], Vec<u8>)>> {
+        let mut contracts = vec![];
+        for item in self.0.iter() {
+            let (key, value) = item?;
+            contracts.push((key.as_ref().try_into().unwrap(), value.to_vec()));
+        }
+        Ok(contracts)
+    }
+}
+
+/// The `ContractStateStore` is a `sled` tree storing the key/value state
+/// of deployed contracts. Each contract has its own keyspace: the key is
+/// the 32-byte contract ID followed by the contract-chosen key.
+#[derive(Clone)]
+pub struct ContractStateStore(sled::Tree);
+
+impl ContractStateStore {
+    /// Opens a new or existing `ContractStateStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_CONTRACT_STATE_TREE)?;
+        Ok(Self(tree))
+    }
+
+    fn scoped_key(contract_id: &[u8; 32], key: &[u8]) -> Vec<u8> {
+        let mut scoped = Vec::with_capacity(32 + key.len());
+        scoped.extend_from_slice(contract_id);
+        scoped.extend_from_slice(key);
+        scoped
+    }
+
+    /// Insert a value under the given key of a contract's state.
+    pub fn insert(&self, contract_id: &[u8; 32], key: &[u8], value: &[u8]) -> Result<()> {
+        self.0.insert(Self::scoped_key(contract_id, key), value)?;
+        Ok(())
+    }
+
+    /// Retrieve the value under the given key of a contract's state, if any.
+    pub fn get(&self, contract_id: &[u8; 32], key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.get(Self::scoped_key(contract_id, key))?.map(|v| v.to_vec()))
+    }
+
+    /// Remove the given key from a contract's state.
+    pub fn remove(&self, contract_id: &[u8; 32], key: &[u8]) -> Result<()> {
+        self.0.remove(Self::scoped_key(contract_id, key))?;
+        Ok(())
+    }
+
+    /// Retrieve all key/value pairs of a contract's state whose key starts
+    /// with `prefix`, ordered by key.
+    pub fn scan(&self, contract_id: &[u8; 32], prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut ret = vec![];
+        for item in self.0.scan_prefix(Self::scoped_key(contract_id, prefix)) {
+            let (key, value) = item?;
+            ret.push((key[32..].to_vec(), value.to_vec()));
+        }
+        Ok(ret)
+    }
+
+    /// Retrieve the state of all contracts, as writes that would restore it.
+    /// Be careful as this will try to load everything in memory.
+    pub fn get_all(&self) -> Result<Vec<StateWrite>> {
+        let mut writes = vec![];
+        for item in self.0.iter() {
+            let (key, value) = item?;
+            writes.push(StateWrite {
+                contract_id: key[..32].try_into().unwrap(),
+                key: key[32..].to_vec(),
+                value: Some(value.to_vec()),
+            });
+        }
+        Ok(writes)
+    }
+
+    /// Apply a slice of [`StateWrite`] to the store. With sled, the
+    /// operation is done as a batch, so either all writes land or none.
+    pub fn apply(&self, writes: &[StateWrite]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for write in writes {
+            let key = Self::scoped_key(&write.contract_id, &write.key);
+            match &write.value {
+                Some(value) => batch.insert(key, value.clone()),
+                None => batch.remove(key),
+            }
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+}
+
+/// A single write to a contract's key/value state. A `None` value
+/// removes the key.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct StateWrite {
+    pub contract_id: [u8; 32],
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+
+/// Uncommitted changes to contract state, layered over a [`ContractStateStore`].
+///
+/// Reads see the overlay's own writes first and fall back to the store.
+/// Contract calls in unfinalized blocks write into an overlay, whose
+/// writes get applied to the store once the block is finalized. When
+/// switching forks, the overlay is dropped and the store is untouched.
+#[derive(Clone)]
+pub struct ContractStateOverlay {
+    store: ContractStateStore,
+    writes: BTreeMap<([u8; 32], Vec<u8>), Option<Vec<u8>>>,
+}
+
+impl ContractStateOverlay {
+    /// Create an empty overlay on top of the given store.
+    pub fn new(store: ContractStateStore) -> Self {
+        Self { store, writes: BTreeMap::new() }
+    }
+
+    /// Retrieve the value under the given key of a contract's state, if any.
+    pub fn get(&self, contract_id: &[u8; 32], key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.writes.get(&(*contract_id, key.to_vec())) {
+            Some(value) => Ok(value.clone()),
+            None => self.store.get(contract_id, key),
+        }
+    }
+
+    /// Insert a value under the given key of a contract's state.
+    pub fn insert(&mut self, contract_id: &[u8; 32], key: &[u8], value: &[u8]) {
+        self.writes.insert((*contract_id, key.to_vec()), Some(value.to_vec()));
+    }
+
+    /// Remove the given key from a contract's state.
+    pub fn remove(&mut self, contract_id: &[u8; 32], key: &[u8]) {
+        self.writes.insert((*contract_id, key.to_vec()), None);
+    }
+
+    /// Apply a slice of [`StateWrite`] to the overlay.
+    pub fn apply(&mut self, writes: &[StateWrite]) {
+        for write in writes {
+            self.writes.insert((write.contract_id, write.key.clone()), write.value.clone());
+        }
+    }
+
+    /// Retrieve the uncommitted writes, ordered by contract ID and key.
+    pub fn writes(&self) -> Vec<StateWrite> {
+        self.writes
+            .iter()
+            .map(|((contract_id, key), value)| StateWrite {
+                contract_id: *contract_id,
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect()
+    }
+
+    /// Write the uncommitted changes to the store, emptying the overlay.
+    pub fn commit(&mut self) -> Result<()> {
+        self.store.apply(&self.writes())?;
+        self.writes.clear();
+        Ok(())
+    }
+
+    /// Discard the uncommitted changes.
+    pub fn rollback(&mut self) {
+        self.writes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_state_overlay() -> Result<()> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let store = ContractStateStore::new(&db)?;
+        let (a, b) = ([0u8; 32], [1u8; 32]);
+        store.insert(&a, b"key", b"old")?;
+
+        let mut overlay = ContractStateOverlay::new(store.clone());
+        overlay.insert(&a, b"key", b"new");
+        overlay.insert(&b, b"key", b"other");
+        assert_eq!(overlay.get(&a, b"key")?, Some(b"new".to_vec()));
+        assert_eq!(store.get(&a, b"key")?, Some(b"old".to_vec()));
+        assert_eq!(store.get(&b, b"key")?, None);
+
+        // Forks don't touch the store
+        overlay.rollback();
+        assert_eq!(overlay.get(&a, b"key")?, Some(b"old".to_vec()));
+
+        overlay.remove(&a, b"key");
+        overlay.insert(&b, b"key", b"other");
+        overlay.commit()?;
+        assert_eq!(store.get(&a, b"key")?, None);
+        assert_eq!(store.get(&b, b"key")?, Some(b"other".to_vec()));
+
+        Ok(())
+    }
+}
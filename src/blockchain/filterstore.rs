@@ -0,0 +1,82 @@
+use super::blockfilter::BlockFilter;
+use crate::{
+    consensus::Header,
+    util::{
+        serial::{deserialize, serialize},
+        time::Timestamp,
+    },
+    Error, Result,
+};
+
+const SLED_FILTER_TREE: &[u8] = b"_block_filters";
+
+/// The `FilterStore` is a `sled` tree storing the compact filter of every
+/// block along with its filter header, where the key is the block's
+/// headerhash, and value is the serialized filter and filter header.
+/// Filters are kept for pruned blocks too, so light wallets can still
+/// find out which blocks concern them.
+#[derive(Clone)]
+pub struct FilterStore(sled::Tree);
+
+impl FilterStore {
+    /// Opens a new or existing `FilterStore` on the given sled database.
+    pub fn new(db: &sled::Db, genesis_ts: Timestamp, genesis_data: blake3::Hash) -> Result<Self> {
+        let tree = db.open_tree(SLED_FILTER_TREE)?;
+        let store = Self(tree);
+
+        // In case the store is empty, initialize it with the genesis block's
+        // filter, whose header chains to the zero hash.
+        if store.0.is_empty() {
+            let genesis_hash = Header::genesis_header(genesis_ts, genesis_data).headerhash();
+            let filter = BlockFilter::from_txs(&genesis_hash, &[]);
+            let header = filter.header(&blake3::Hash::from([0; 32]));
+            store.insert(&[genesis_hash], &[(filter, header)])?;
+        }
+
+        Ok(store)
+    }
+
+    /// Insert the given filters and filter headers under the given
+    /// headerhashes. With sled, the operation is done as a batch.
+    pub fn insert(
+        &self,
+        headerhashes: &[blake3::Hash],
+        filters: &[(BlockFilter, blake3::Hash)],
+    ) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for (hash, filter) in headerhashes.iter().zip(filters) {
+            batch.insert(hash.as_bytes(), serialize(filter));
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Fetch the filters and filter headers of the given headerhashes.
+    /// The resulting vector contains `Option`, which is `Some` if the filter
+    /// was found in the store, and otherwise it is `None`, if it has not.
+    /// The second parameter is a boolean which tells the function to fail in
+    /// case at least one filter was not found.
+    pub fn get(
+        &self,
+        headerhashes: &[blake3::Hash],
+        strict: bool,
+    ) -> Result<Vec<Option<(BlockFilter, blake3::Hash)>>> {
+        let mut ret = Vec::with_capacity(headerhashes.len());
+
+        for hash in headerhashes {
+            if let Some(found) = self.0.get(hash.as_bytes())? {
+                ret.push(Some(deserialize(&found)?));
+            } else {
+                if strict {
+                    let s = hash.to_hex().as_str().to_string();
+                    return Err(Error::BlockFilterNotFound(s))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+}
@@ -1,14 +1,32 @@
 use log::debug;
 
 use crate::{
-    consensus::{Block, BlockInfo},
+    consensus::{Block, BlockInfo, Header},
     util::time::Timestamp,
-    Result,
+    Error, Result,
 };
 
+pub mod blockfilter;
+pub use blockfilter::BlockFilter;
+
 pub mod blockstore;
 pub use blockstore::{BlockOrderStore, BlockStore, HeaderStore};
 
+pub mod bloom;
+pub use bloom::BloomFilter;
+
+pub mod checkpoints;
+pub use checkpoints::Checkpoints;
+
+pub mod circuitstore;
+pub use circuitstore::{CircuitRecord, CircuitStore};
+
+pub mod filterstore;
+pub use filterstore::FilterStore;
+
+pub mod contractstore;
+pub use contractstore::{ContractStateOverlay, ContractStateStore, ContractStore, StateWrite};
+
 pub mod metadatastore;
 pub use metadatastore::StreamletMetadataStore;
 
@@ -18,6 +36,9 @@ pub use nfstore::NullifierStore;
 pub mod rootstore;
 pub use rootstore::RootStore;
 
+pub mod treestore;
+pub use treestore::TreeStore;
+
 pub mod txstore;
 pub use txstore::TxStore;
 
@@ -33,10 +54,24 @@ pub struct Blockchain {
     pub transactions: TxStore,
     /// Streamlet metadata sled tree
     pub streamlet_metadata: StreamletMetadataStore,
+    /// Compact block filters sled tree
+    pub filters: FilterStore,
     /// Nullifiers sled tree
     pub nullifiers: NullifierStore,
     /// Merkle roots sled tree
     pub merkle_roots: RootStore,
+    /// Merkle tree checkpoints sled tree
+    pub merkle_trees: TreeStore,
+    /// Deployed contracts sled tree
+    pub contracts: ContractStore,
+    /// Contract key/value state sled tree
+    pub contract_state: ContractStateStore,
+    /// zkas circuits registered on chain, kept in the contract state
+    pub circuits: CircuitStore,
+    /// Known-good blocks the chain can't be reorged past
+    pub checkpoints: Checkpoints,
+    /// Number of most recent slots whose block bodies are kept, if pruning
+    pub prune_depth: Option<u64>,
 }
 
 impl Blockchain {
@@ -47,8 +82,14 @@ impl Blockchain {
         let order = BlockOrderStore::new(db, genesis_ts, genesis_data)?;
         let streamlet_metadata = StreamletMetadataStore::new(db, genesis_ts, genesis_data)?;
         let transactions = TxStore::new(db)?;
+        let filters = FilterStore::new(db, genesis_ts, genesis_data)?;
         let nullifiers = NullifierStore::new(db)?;
         let merkle_roots = RootStore::new(db)?;
+        let merkle_trees = TreeStore::new(db)?;
+        let contracts = ContractStore::new(db)?;
+        let contract_state = ContractStateStore::new(db)?;
+        let circuits = CircuitStore::new(contract_state.clone());
+        let checkpoints = Checkpoints::hardcoded();
 
         Ok(Self {
             headers,
@@ -56,11 +97,59 @@ impl Blockchain {
             order,
             transactions,
             streamlet_metadata,
+            filters,
             nullifiers,
             merkle_roots,
+            merkle_trees,
+            contracts,
+            contract_state,
+            circuits,
+            checkpoints,
+            prune_depth: None,
         })
     }
 
+    /// Enable pruning mode, keeping block bodies only for the last `depth`
+    /// slots. Bodies already past that depth are discarded right away.
+    pub fn set_pruning(&mut self, depth: u64) -> Result<()> {
+        self.prune_depth = Some(depth);
+        let (last, _) = self.last()?;
+        self.prune_bodies(1, (last + 1).saturating_sub(depth))
+    }
+
+    /// Slot below which block bodies have been pruned, or 0 if the full
+    /// chain is kept.
+    pub fn pruned_slot(&self) -> Result<u64> {
+        match self.prune_depth {
+            Some(depth) => Ok((self.last()?.0 + 1).saturating_sub(depth)),
+            None => Ok(0),
+        }
+    }
+
+    /// Discard the bodies of the blocks in the slot range `start..end`,
+    /// meaning their block records and transactions. Headers, block order,
+    /// streamlet metadata, Merkle roots and nullifiers are retained, so the
+    /// chain can still be verified and extended. The genesis block is never
+    /// pruned.
+    fn prune_bodies(&self, start: u64, end: u64) -> Result<()> {
+        let slots = self.order.get_range(start.max(1), end)?;
+        let hashes: Vec<blake3::Hash> = slots.iter().map(|(_, hash)| *hash).collect();
+
+        if hashes.is_empty() {
+            return Ok(())
+        }
+
+        debug!("prune_bodies(): Pruning {} block bodies", hashes.len());
+        let mut txs = vec![];
+        for block in self.blocks.get(&hashes, false)?.into_iter().flatten() {
+            txs.extend(block.txs);
+        }
+
+        self.transactions.remove(&txs)?;
+        self.blocks.remove(&hashes)?;
+        Ok(())
+    }
+
     /// Insert a given slice of [`BlockInfo`] into the blockchain database.
     /// This functions wraps all the logic of separating the block into specific
     /// data that can be fed into the different trees of the database.
@@ -68,8 +157,11 @@ impl Blockchain {
     /// were given and appended to the ledger.
     pub fn add(&self, blocks: &[BlockInfo]) -> Result<Vec<blake3::Hash>> {
         let mut ret = Vec::with_capacity(blocks.len());
+        let (previous, _) = self.last()?;
 
         for block in blocks {
+            self.checkpoints.verify(block.header.slot, &block.header.headerhash())?;
+
             // Store transactions
             let tx_hashes = self.transactions.insert(&block.txs)?;
 
@@ -87,10 +179,29 @@ impl Blockchain {
             // Store streamlet metadata
             self.streamlet_metadata.insert(&[headerhash[0]], &[block.sm.clone()])?;
 
+            // Store compact block filter, chained to the previous block's.
+            // Blocks imported from a state snapshot start a new chain.
+            let prev_header = match self.filters.get(&[block.header.state], false)?.remove(0) {
+                Some((_, header)) => header,
+                None => blake3::Hash::from([0; 32]),
+            };
+            let filter = BlockFilter::from_txs(&headerhash[0], &block.txs);
+            let filter_header = filter.header(&prev_header);
+            self.filters.insert(&[headerhash[0]], &[(filter, filter_header)])?;
+
             // NOTE: The nullifiers and Merkle roots are applied in the state
             // transition apply function.
         }
 
+        // Discard the bodies that fell out of the pruning window
+        if let Some(depth) = self.prune_depth {
+            let (last, _) = self.last()?;
+            self.prune_bodies(
+                (previous + 1).saturating_sub(depth),
+                (last + 1).saturating_sub(depth),
+            )?;
+        }
+
         Ok(ret)
     }
 
@@ -143,13 +254,72 @@ impl Blockchain {
         self.get_blocks_by_hash(&hashes)
     }
 
-    /// Retrieve n blocks after given start slot.
-    pub fn get_blocks_after(&self, slot: u64, n: u64) -> Result<Vec<BlockInfo>> {
-        debug!("get_blocks_after(): {} -> {}", slot, n);
-        let hashes = self.order.get_after(slot, n)?;
+    /// Retrieve [`BlockInfo`]s in the slot range `start..end`, in ascending slot order.
+    pub fn get_blocks_by_slot_range(&self, start: u64, end: u64) -> Result<Vec<BlockInfo>> {
+        debug!("get_blocks_by_slot_range(): {} -> {}", start, end);
+        let slots = self.order.get_range(start, end)?;
+        let hashes: Vec<blake3::Hash> = slots.into_iter().map(|(_, hash)| hash).collect();
         self.get_blocks_by_hash(&hashes)
     }
 
+    /// Retrieve the compact filters and filter headers of the blocks in the
+    /// slot range `start..end`, along with their slots and headerhashes, in
+    /// ascending slot order.
+    pub fn get_filters_by_slot_range(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, blake3::Hash, BlockFilter, blake3::Hash)>> {
+        debug!("get_filters_by_slot_range(): {} -> {}", start, end);
+        let slots = self.order.get_range(start, end)?;
+        let hashes: Vec<blake3::Hash> = slots.iter().map(|(_, hash)| *hash).collect();
+        let filters = self.filters.get(&hashes, true)?;
+
+        let mut ret = Vec::with_capacity(slots.len());
+        for ((slot, hash), filter) in slots.into_iter().zip(filters) {
+            let (filter, header) = filter.unwrap();
+            ret.push((slot, hash, filter, header));
+        }
+
+        Ok(ret)
+    }
+
+    /// Retrieve at most `limit` blocks following the given block, which must
+    /// be part of the canonical blockchain.
+    pub fn get_blocks_after(&self, hash: &blake3::Hash, limit: u64) -> Result<Vec<BlockInfo>> {
+        debug!("get_blocks_after(): {} -> {}", hash, limit);
+        let header = self.headers.get(&[*hash], true)?[0].clone().unwrap();
+        if self.order.get(&[header.slot], true)?[0] != Some(*hash) {
+            return Err(Error::BlockNotFound(hash.to_hex().as_str().to_string()))
+        }
+
+        let hashes = self.order.get_after(header.slot, limit)?;
+        self.get_blocks_by_hash(&hashes)
+    }
+
+    /// Iterate over the blocks of the canonical blockchain, in ascending slot
+    /// order. Blocks are loaded from the database one at a time, as the
+    /// iterator advances.
+    pub fn iter(&self) -> impl Iterator<Item = Result<BlockInfo>> + '_ {
+        self.order.iter().map(move |found| {
+            let (_, hash) = found?;
+            Ok(self.get_blocks_by_hash(&[hash])?.remove(0))
+        })
+    }
+
+    /// Retrieve n headers after given start slot, whose block must have the
+    /// given hash.
+    pub fn get_headers_after(&self, slot: u64, hash: &blake3::Hash, n: u64) -> Result<Vec<Header>> {
+        debug!("get_headers_after(): {} -> {}", slot, n);
+        if self.order.get(&[slot], false)?[0] != Some(*hash) {
+            return Err(Error::BlockNotFound(hash.to_hex().as_str().to_string()))
+        }
+
+        let hashes = self.order.get_after(slot, n)?;
+        let headers = self.headers.get(&hashes, true)?;
+        Ok(headers.into_iter().map(|x| x.unwrap()).collect())
+    }
+
     /// Retrieve the last block slot and hash.
     pub fn last(&self) -> Result<(u64, blake3::Hash)> {
         self.order.get_last()
@@ -1,3 +1,8 @@
+use std::sync::{Arc, RwLock};
+
+use log::debug;
+
+use super::bloom::BloomFilter;
 use crate::{
     crypto::nullifier::Nullifier,
     util::serial::{deserialize, serialize},
@@ -6,18 +11,45 @@ use crate::{
 
 const SLED_NULLIFIER_TREE: &[u8] = b"_nullifiers";
 
+/// Minimum number of nullifiers the bloom filter is sized for
+const NULLIFIER_FILTER_CAPACITY: usize = 100_000;
+/// False positive rate of the bloom filter
+const NULLIFIER_FILTER_FP_RATE: f64 = 0.001;
+
 /// The `NullifierStore` is a `sled` tree storing all the nullifiers seen
 /// in existing blocks. The key is the nullifier itself, while the value
 /// is an empty vector that's not used. As a sidenote, perhaps we could
 /// hold the transaction hash where the nullifier was seen in the value.
+///
+/// An in-memory bloom filter over the stored nullifiers is kept alongside,
+/// so looking up an unspent nullifier usually doesn't touch the disk.
 #[derive(Clone)]
-pub struct NullifierStore(sled::Tree);
+pub struct NullifierStore {
+    tree: sled::Tree,
+    filter: Arc<RwLock<BloomFilter>>,
+}
 
 impl NullifierStore {
-    /// Opens a new or existing `NullifierStore` on the given sled database.
+    /// Opens a new or existing `NullifierStore` on the given sled database,
+    /// building the bloom filter from the stored nullifiers.
     pub fn new(db: &sled::Db) -> Result<Self> {
         let tree = db.open_tree(SLED_NULLIFIER_TREE)?;
-        Ok(Self(tree))
+        let filter = Self::build_filter(&tree)?;
+        Ok(Self { tree, filter: Arc::new(RwLock::new(filter)) })
+    }
+
+    /// Build a bloom filter over all nullifiers in the given tree, sized
+    /// for twice their number so it can keep growing for a while.
+    fn build_filter(tree: &sled::Tree) -> Result<BloomFilter> {
+        let capacity = (tree.len() * 2).max(NULLIFIER_FILTER_CAPACITY);
+        debug!("NullifierStore: Building bloom filter for {} nullifiers", tree.len());
+
+        let mut filter = BloomFilter::new(capacity, NULLIFIER_FILTER_FP_RATE);
+        for key in tree.iter().keys() {
+            filter.insert(&key?);
+        }
+
+        Ok(filter)
     }
 
     /// Insert a slice of [`Nullifier`] into the store. With sled, the
@@ -30,13 +62,28 @@ impl NullifierStore {
             batch.insert(serialize(nf), vec![] as Vec<u8>);
         }
 
-        self.0.apply_batch(batch)?;
+        self.tree.apply_batch(batch)?;
+
+        let mut filter = self.filter.write().unwrap();
+        for nf in nfs {
+            filter.insert(&serialize(nf));
+        }
+
+        if filter.is_saturated() {
+            *filter = Self::build_filter(&self.tree)?;
+        }
+
         Ok(())
     }
 
     /// Check if the nullifierstore contains a given nullifier.
     pub fn contains(&self, nullifier: &Nullifier) -> Result<bool> {
-        Ok(self.0.contains_key(serialize(nullifier))?)
+        let key = serialize(nullifier);
+        if !self.filter.read().unwrap().may_contain(&key) {
+            return Ok(false)
+        }
+
+        Ok(self.tree.contains_key(key)?)
     }
 
     /// Retrieve all nullifiers from the store.
@@ -44,7 +91,7 @@ impl NullifierStore {
     pub fn get_all(&self) -> Result<Vec<Nullifier>> {
         let mut nullifiers = vec![];
 
-        for nullifier in self.0.iter() {
+        for nullifier in self.tree.iter() {
             let (key, _) = nullifier.unwrap();
             let nullifier = deserialize(&key)?;
             nullifiers.push(nullifier);
@@ -0,0 +1,100 @@
+use incrementalmerkletree::bridgetree::BridgeTree;
+
+use crate::{
+    crypto::{constants::MERKLE_DEPTH, merkle_node::MerkleNode},
+    Result,
+};
+
+const SLED_TREE_CHECKPOINTS_TREE: &[u8] = b"_merkletrees";
+
+/// Number of latest tree checkpoints kept in the store
+const TREE_CHECKPOINTS_KEPT: usize = 2;
+
+/// Older checkpoints are thinned out to one per this many slots, which
+/// wallet scans start from instead of replaying the whole chain.
+const TREE_ARCHIVE_INTERVAL: u64 = 10_000;
+
+/// The `TreeStore` is a `sled` tree storing checkpoints of the coins Merkle
+/// tree. The key is the slot of the last block applied to the tree, while
+/// the value is the bincode-serialized tree.
+#[derive(Clone)]
+pub struct TreeStore(sled::Tree);
+
+impl TreeStore {
+    /// Opens a new or existing `TreeStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_TREE_CHECKPOINTS_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a checkpoint of the Merkle tree at the given slot. The last
+    /// few checkpoints are kept, and older ones are removed except for the
+    /// first one of every [`TREE_ARCHIVE_INTERVAL`] slots.
+    pub fn insert(&self, slot: u64, tree: &BridgeTree<MerkleNode, MERKLE_DEPTH>) -> Result<()> {
+        let serialized = bincode::serde::encode_to_vec(tree, bincode::config::legacy())?;
+        self.0.insert(slot.to_be_bytes(), serialized)?;
+
+        let keys = self.0.iter().keys().collect::<sled::Result<Vec<_>>>()?;
+        let archived = keys.len().saturating_sub(TREE_CHECKPOINTS_KEPT);
+
+        let mut batch = sled::Batch::default();
+        let mut last_window = None;
+        for key in keys.into_iter().take(archived) {
+            let window = Self::parse_slot(&key) / TREE_ARCHIVE_INTERVAL;
+            if last_window == Some(window) {
+                batch.remove(key);
+            }
+            last_window = Some(window);
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    fn parse_slot(key: &[u8]) -> u64 {
+        let slot_bytes: [u8; 8] = key.try_into().unwrap();
+        u64::from_be_bytes(slot_bytes)
+    }
+
+    /// Fetch the last checkpoint at or before the given slot, in the form
+    /// of a tuple (`slot`, `tree`).
+    pub fn get_at_or_before(
+        &self,
+        slot: u64,
+    ) -> Result<Option<(u64, BridgeTree<MerkleNode, MERKLE_DEPTH>)>> {
+        match self.0.range(..=slot.to_be_bytes()).next_back() {
+            Some(found) => {
+                let (key, value) = found?;
+                let (tree, _) =
+                    bincode::serde::decode_from_slice(&value, bincode::config::legacy())?;
+                Ok(Some((Self::parse_slot(&key), tree)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the slot of the last checkpoint in the store, if any.
+    pub fn last_slot(&self) -> Result<Option<u64>> {
+        match self.0.last()? {
+            Some((key, _)) => {
+                let slot_bytes: [u8; 8] = key.as_ref().try_into().unwrap();
+                Ok(Some(u64::from_be_bytes(slot_bytes)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the last checkpoint in the store, in the form of a tuple
+    /// (`slot`, `tree`).
+    pub fn get_last(&self) -> Result<Option<(u64, BridgeTree<MerkleNode, MERKLE_DEPTH>)>> {
+        match self.0.last()? {
+            Some((key, value)) => {
+                let slot_bytes: [u8; 8] = key.as_ref().try_into().unwrap();
+                let (tree, _) =
+                    bincode::serde::decode_from_slice(&value, bincode::config::legacy())?;
+                Ok(Some((u64::from_be_bytes(slot_bytes), tree)))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -40,6 +40,19 @@ impl TxStore {
         Ok(ret)
     }
 
+    /// Remove the given tx hashes from the txstore. With sled, the
+    /// operation is done as a batch.
+    pub fn remove(&self, txids: &[blake3::Hash]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for txid in txids {
+            batch.remove(txid.as_bytes());
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
     /// Check if the txstore contains a given transaction hash.
     pub fn contains(&self, txid: &blake3::Hash) -> Result<bool> {
         Ok(self.0.contains_key(txid.as_bytes())?)
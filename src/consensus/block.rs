@@ -8,6 +8,7 @@ use super::{
     Metadata, StreamletMetadata, BLOCK_INFO_MAGIC_BYTES, BLOCK_MAGIC_BYTES, BLOCK_VERSION,
 };
 use crate::{
+    blockchain::BlockFilter,
     crypto::{
         address::Address, constants::MERKLE_DEPTH, keypair::Keypair, merkle_node::MerkleNode,
         schnorr::SchnorrSecret,
@@ -15,9 +16,10 @@ use crate::{
     net,
     tx::Transaction,
     util::{
-        serial::{serialize, SerialDecodable, SerialEncodable},
+        serial::{decode_canonical, serialize, BytesReader, SerialDecodable, SerialEncodable},
         time::Timestamp,
     },
+    Result,
 };
 
 /// This struct represents a tuple of the form (version, state, epoch, slot, timestamp, merkle_root).
@@ -33,7 +35,7 @@ pub struct Header {
     pub slot: u64,
     /// Block creation timestamp
     pub timestamp: Timestamp,
-    /// Root of the transaction hashes merkle tree
+    /// Root of the Merkle tree of the coins created by the block transactions
     pub root: MerkleNode,
 }
 
@@ -51,10 +53,20 @@ impl Header {
 
     /// Generate the genesis block.
     pub fn genesis_header(genesis_ts: Timestamp, genesis_data: blake3::Hash) -> Self {
-        let tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100);
-        let root = tree.root(0).unwrap();
+        Self::new(genesis_data, 0, 0, genesis_ts, Self::txs_root(&[]))
+    }
 
-        Self::new(genesis_data, 0, 0, genesis_ts, root)
+    /// Calculate the root of the Merkle tree of the coins created by the
+    /// given transactions, which the header of their block commits to.
+    pub fn txs_root(txs: &[Transaction]) -> MerkleNode {
+        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100);
+        for tx in txs {
+            for output in &tx.outputs {
+                tree.append(&MerkleNode::from_coin(&output.revealed.coin));
+                tree.witness();
+            }
+        }
+        tree.root(0).unwrap()
     }
 
     /// Calculate the header hash
@@ -143,6 +155,11 @@ impl net::Message for BlockInfo {
     fn name() -> &'static str {
         "blockinfo"
     }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
 }
 
 /// Auxiliary structure used for blockchain syncing
@@ -156,6 +173,209 @@ impl net::Message for BlockResponse {
     fn name() -> &'static str {
         "blockresponse"
     }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
+}
+
+/// Maximum number of block bodies requested in a single [`BodyOrder`].
+pub const BODY_ORDER_MAX: usize = 10;
+
+/// Sync handshake, advertising the slot below which the sender has pruned
+/// block bodies, so peers request bodies from nodes that still have them.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SyncInfoOrder {
+    /// Slot below which block bodies are pruned, 0 if the node keeps them all
+    pub pruned: u64,
+}
+
+impl net::Message for SyncInfoOrder {
+    fn name() -> &'static str {
+        "syncinfoorder"
+    }
+}
+
+impl net::Request for SyncInfoOrder {
+    type Response = SyncInfoResponse;
+}
+
+/// Response to the sync handshake, advertising the responder's pruned slot.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SyncInfoResponse {
+    /// Slot below which block bodies are pruned, 0 if the node keeps them all
+    pub pruned: u64,
+}
+
+impl net::Message for SyncInfoResponse {
+    fn name() -> &'static str {
+        "syncinforesponse"
+    }
+}
+
+/// Headers-first sync request for the headers following the given block.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct HeaderOrder {
+    /// Slot UID
+    pub slot: u64,
+    /// Block headerhash of that slot
+    pub block: blake3::Hash,
+}
+
+impl net::Message for HeaderOrder {
+    fn name() -> &'static str {
+        "headerorder"
+    }
+}
+
+impl net::Request for HeaderOrder {
+    type Response = HeaderResponse;
+}
+
+/// Auxiliary structure used for headers-first syncing
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct HeaderResponse {
+    /// Response headers, in slot order.
+    pub headers: Vec<Header>,
+}
+
+impl net::Message for HeaderResponse {
+    fn name() -> &'static str {
+        "headerresponse"
+    }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
+}
+
+/// Headers-first sync request for the full blocks of the given headerhashes.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct BodyOrder {
+    /// Block headerhashes, at most [`BODY_ORDER_MAX`]
+    pub blocks: Vec<blake3::Hash>,
+}
+
+impl net::Message for BodyOrder {
+    fn name() -> &'static str {
+        "bodyorder"
+    }
+}
+
+impl net::Request for BodyOrder {
+    type Response = BodyResponse;
+}
+
+/// Auxiliary structure used for headers-first syncing
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct BodyResponse {
+    /// Response blocks, in the requested order.
+    pub blocks: Vec<BlockInfo>,
+}
+
+impl net::Message for BodyResponse {
+    fn name() -> &'static str {
+        "bodyresponse"
+    }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
+}
+
+/// Maximum number of slots requested in a single [`FilterOrder`] or
+/// [`FilterHeaderOrder`].
+pub const FILTER_ORDER_MAX: u64 = 1000;
+
+/// Light client request for the compact filters of the blocks in the slot
+/// range `start..end`.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FilterOrder {
+    /// First slot, inclusive
+    pub start: u64,
+    /// Last slot, exclusive
+    pub end: u64,
+}
+
+impl net::Message for FilterOrder {
+    fn name() -> &'static str {
+        "filterorder"
+    }
+}
+
+impl net::Request for FilterOrder {
+    type Response = FilterResponse;
+}
+
+/// Compact filter of a block, along with its position in the chain.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SlotFilter {
+    /// Slot UID
+    pub slot: u64,
+    /// Block headerhash of that slot
+    pub block: blake3::Hash,
+    /// Compact filter of the block
+    pub filter: BlockFilter,
+}
+
+/// Auxiliary structure used for light client syncing
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FilterResponse {
+    /// Filters of the requested blocks, in slot order.
+    pub filters: Vec<SlotFilter>,
+}
+
+impl net::Message for FilterResponse {
+    fn name() -> &'static str {
+        "filterresponse"
+    }
+}
+
+/// Light client request for the filter headers of the blocks in the slot
+/// range `start..end`.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FilterHeaderOrder {
+    /// First slot, inclusive
+    pub start: u64,
+    /// Last slot, exclusive
+    pub end: u64,
+}
+
+impl net::Message for FilterHeaderOrder {
+    fn name() -> &'static str {
+        "filterheaderorder"
+    }
+}
+
+impl net::Request for FilterHeaderOrder {
+    type Response = FilterHeaderResponse;
+}
+
+/// Filter header of a block, along with its position in the chain.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SlotFilterHeader {
+    /// Slot UID
+    pub slot: u64,
+    /// Block headerhash of that slot
+    pub block: blake3::Hash,
+    /// Filter header of the block
+    pub header: blake3::Hash,
+}
+
+/// Auxiliary structure used for light client syncing
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FilterHeaderResponse {
+    /// Filter headers of the requested blocks, in slot order.
+    pub headers: Vec<SlotFilterHeader>,
+}
+
+impl net::Message for FilterHeaderResponse {
+    fn name() -> &'static str {
+        "filterheaderresponse"
+    }
 }
 
 /// This struct represents a block proposal, used for consensus.
@@ -203,6 +423,11 @@ impl net::Message for BlockProposal {
     fn name() -> &'static str {
         "proposal"
     }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
 }
 
 impl From<BlockProposal> for BlockInfo {
@@ -0,0 +1,120 @@
+use async_std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use log::{debug, info, warn};
+use serde_json::json;
+use url::Url;
+
+use super::state::DELTA;
+use crate::{
+    util::time::{ntp_time_millis, peer_clock},
+    Error, Result,
+};
+
+/// Fraction of the slot length the local clock is allowed to drift by,
+/// before the node risks missing its leader slots.
+const MAX_DRIFT_DIVISOR: i64 = 10;
+
+/// Slot length in milliseconds.
+const SLOT_MILLIS: i64 = 2 * DELTA as i64 * 1000;
+
+/// Atomic pointer to the clock synchronization service.
+pub type ClockSyncPtr = Arc<ClockSync>;
+
+/// Clock synchronization service measuring the offset of the local clock
+/// against peers and, optionally, NTP. Nodes whose clock drifts too far
+/// from the network's compute the wrong current slot, and silently miss
+/// their leader slots.
+pub struct ClockSync {
+    /// Peers JSON-RPC endpoints to query
+    peers: Vec<Url>,
+    /// Also query the NTP pool
+    ntp: bool,
+    /// Last measured offset in milliseconds, network time minus local time
+    offset: Mutex<Option<i64>>,
+}
+
+impl ClockSync {
+    pub fn new(peers: Vec<Url>, ntp: bool) -> ClockSyncPtr {
+        Arc::new(Self { peers, ntp, offset: Mutex::new(None) })
+    }
+
+    /// Maximum tolerated clock offset, in milliseconds.
+    pub fn max_drift() -> i64 {
+        SLOT_MILLIS / MAX_DRIFT_DIVISOR
+    }
+
+    /// Measure the local clock offset as the median of the offsets against
+    /// every reachable time source, and warn if it exceeds the tolerated drift.
+    pub async fn measure(&self) -> Result<i64> {
+        let mut samples = vec![];
+
+        for peer in &self.peers {
+            match peer_offset(peer).await {
+                Ok(offset) => samples.push(offset),
+                Err(e) => debug!("ClockSync::measure(): Failed querying peer {}: {}", peer, e),
+            }
+        }
+
+        if self.ntp {
+            match ntp_offset().await {
+                Ok(offset) => samples.push(offset),
+                Err(e) => debug!("ClockSync::measure(): Failed querying NTP: {}", e),
+            }
+        }
+
+        if samples.is_empty() {
+            return Err(Error::InvalidClock)
+        }
+
+        samples.sort_unstable();
+        let offset = samples[samples.len() / 2];
+        *self.offset.lock().await = Some(offset);
+
+        if offset.abs() > Self::max_drift() {
+            warn!(
+                "consensus: Local clock is off by {} ms, more than the {} ms tolerated. Leader slots may be missed!",
+                offset,
+                Self::max_drift()
+            );
+        } else {
+            info!("consensus: Local clock offset is {} ms", offset);
+        }
+
+        Ok(offset)
+    }
+
+    /// Last measured clock offset, in milliseconds.
+    pub async fn offset(&self) -> Option<i64> {
+        *self.offset.lock().await
+    }
+
+    pub async fn get_info(&self) -> serde_json::Value {
+        let offset = self.offset().await;
+        json!({
+            "offset_ms": offset,
+            "max_drift_ms": Self::max_drift(),
+            "drifting": offset.map(|o| o.abs() > Self::max_drift()),
+            "sources": self.peers.len() + self.ntp as usize,
+        })
+    }
+}
+
+/// Offset against a peer, correcting for the request round trip.
+async fn peer_offset(peer: &Url) -> Result<i64> {
+    let start = Utc::now().timestamp_millis();
+    let peer_time = peer_clock(peer).await?;
+    let end = Utc::now().timestamp_millis();
+
+    // Peers report whole seconds, so aim for the middle of that second
+    Ok(peer_time.0 * 1000 + 500 - (start + end) / 2)
+}
+
+/// Offset against the NTP pool, correcting for the request round trip.
+async fn ntp_offset() -> Result<i64> {
+    let start = Utc::now().timestamp_millis();
+    let ntp_time = ntp_time_millis().await?;
+    let end = Utc::now().timestamp_millis();
+
+    Ok(ntp_time - (start + end) / 2)
+}
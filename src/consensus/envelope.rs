@@ -0,0 +1,149 @@
+use std::io;
+
+use super::{Participant, Rejoin, ValidatorState, Vote};
+use crate::{
+    crypto::{
+        keypair::{PublicKey, SecretKey},
+        schnorr::{batch_verify, SchnorrSecret, Signature},
+    },
+    net,
+    util::serial::{serialize, Decodable, Encodable},
+    Result,
+};
+
+/// Consensus message that has to travel inside a [`SignedMessage`].
+pub trait ConsensusMessage: net::Message + Clone {
+    /// Public key the sender of this message is expected to sign it with,
+    /// if it is known to the node.
+    fn signer(&self, state: &ValidatorState) -> Option<PublicKey>;
+
+    /// Signatures the sender made inside the message, along with the
+    /// messages they sign. They're verified in one batch with the envelope.
+    fn inner_signatures(&self) -> Vec<(Vec<u8>, &Signature)> {
+        vec![]
+    }
+}
+
+impl ConsensusMessage for Participant {
+    fn signer(&self, _state: &ValidatorState) -> Option<PublicKey> {
+        Some(self.public_key)
+    }
+}
+
+impl ConsensusMessage for Rejoin {
+    fn signer(&self, _state: &ValidatorState) -> Option<PublicKey> {
+        Some(self.public_key)
+    }
+}
+
+impl ConsensusMessage for Vote {
+    fn signer(&self, state: &ValidatorState) -> Option<PublicKey> {
+        if let Some(participant) = state.consensus.participants.get(&self.address) {
+            return Some(participant.public_key)
+        }
+
+        state
+            .consensus
+            .pending_participants
+            .iter()
+            .find(|p| p.address == self.address)
+            .map(|p| p.public_key)
+    }
+
+    fn inner_signatures(&self) -> Vec<(Vec<u8>, &Signature)> {
+        vec![(serialize(&self.proposal), &self.vote)]
+    }
+}
+
+/// Envelope carrying a consensus message along with the slot and epoch it
+/// was sent in, signed by its sender over the canonical serialization of
+/// all three. Binding the message to a slot lets nodes refuse replays of
+/// old messages. It goes on the wire under the name of the wrapped message.
+#[derive(Debug, Clone)]
+pub struct SignedMessage<M: ConsensusMessage> {
+    /// Slot the message was sent in
+    pub slot: u64,
+    /// Epoch of that slot
+    pub epoch: u64,
+    pub payload: M,
+    /// Sender public key
+    pub public_key: PublicKey,
+    /// Signature over the slot, epoch and payload
+    pub signature: Signature,
+}
+
+impl<M: ConsensusMessage> SignedMessage<M> {
+    /// Sign a message sent in the given slot and epoch.
+    pub fn new(
+        secret: &SecretKey,
+        public_key: PublicKey,
+        slot: u64,
+        epoch: u64,
+        payload: M,
+    ) -> Self {
+        let signature = secret.sign(&Self::signed_bytes(slot, epoch, &payload));
+        Self { slot, epoch, payload, public_key, signature }
+    }
+
+    /// Canonical serialization the signature is made over.
+    fn signed_bytes(slot: u64, epoch: u64, payload: &M) -> Vec<u8> {
+        let mut bytes = vec![];
+        // Writing to a vector can't fail
+        slot.encode(&mut bytes).unwrap();
+        epoch.encode(&mut bytes).unwrap();
+        payload.encode(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Verify the signature, and the ones inside the message, against the
+    /// envelope public key, all in a single batch.
+    pub fn verify_signature(&self) -> bool {
+        let signed_bytes = Self::signed_bytes(self.slot, self.epoch, &self.payload);
+        let inner = self.payload.inner_signatures();
+
+        let mut batch = vec![(&self.public_key, &signed_bytes[..], &self.signature)];
+        for (message, signature) in &inner {
+            batch.push((&self.public_key, &message[..], *signature));
+        }
+        batch_verify(&batch)
+    }
+
+    /// Hash identifying this message, used to detect replays.
+    pub fn hash(&self) -> blake3::Hash {
+        blake3::hash(&Self::signed_bytes(self.slot, self.epoch, &self.payload))
+    }
+}
+
+impl<M: ConsensusMessage> net::Message for SignedMessage<M> {
+    fn name() -> &'static str {
+        M::name()
+    }
+
+    fn priority() -> net::MessagePriority {
+        M::priority()
+    }
+}
+
+impl<M: ConsensusMessage> Encodable for SignedMessage<M> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.slot.encode(&mut s)?;
+        len += self.epoch.encode(&mut s)?;
+        len += self.payload.encode(&mut s)?;
+        len += self.public_key.encode(&mut s)?;
+        len += self.signature.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl<M: ConsensusMessage> Decodable for SignedMessage<M> {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            slot: Decodable::decode(&mut d)?,
+            epoch: Decodable::decode(&mut d)?,
+            payload: Decodable::decode(&mut d)?,
+            public_key: Decodable::decode(&mut d)?,
+            signature: Decodable::decode(&mut d)?,
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+};
+
+use log::info;
+
+use super::Participant;
+use crate::{
+    crypto::address::Address,
+    util::serial::{SerialDecodable, SerialEncodable},
+};
+
+/// Keeps the participant distribution frozen at the start of each epoch.
+/// Leader election during an epoch only considers the snapshot, so nodes
+/// joining or leaving mid-epoch don't affect eligibility until the next one.
+#[derive(Debug, Clone, Default, SerialEncodable, SerialDecodable)]
+pub struct EpochManager {
+    /// Epoch the snapshot was taken for
+    pub epoch: u64,
+    /// Participants eligible for leader election during the epoch
+    pub snapshot: BTreeMap<Address, Participant>,
+}
+
+impl EpochManager {
+    /// Snapshot the given participants if `epoch` is past the snapshot one,
+    /// or if no snapshot exists yet, which happens when the network starts.
+    /// Returns `true` if a new snapshot was taken.
+    pub fn update(&mut self, epoch: u64, participants: &BTreeMap<Address, Participant>) -> bool {
+        if epoch <= self.epoch && !self.snapshot.is_empty() {
+            return false
+        }
+
+        info!("consensus: Snapshotting {} participants for epoch {}", participants.len(), epoch);
+        self.epoch = epoch;
+        self.snapshot = participants.clone();
+        true
+    }
+
+    /// Find the leader of the given slot among the snapshot participants.
+    /// The slot number is hashed with DefaultHasher, because it produces a
+    /// number which then can be reduced modulo the participants count.
+    pub fn slot_leader(&self, slot: u64) -> Option<&Participant> {
+        if self.snapshot.is_empty() {
+            return None
+        }
+
+        let mut hasher = DefaultHasher::new();
+        slot.hash(&mut hasher);
+        let pos = hasher.finish() % (self.snapshot.len() as u64);
+        // Since BTreeMap orders by key in asceding order, each node will have
+        // the same key in calculated position.
+        self.snapshot.values().nth(pos as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::keypair::Keypair;
+    use rand::rngs::OsRng;
+
+    fn participants(n: usize) -> BTreeMap<Address, Participant> {
+        (0..n)
+            .map(|_| {
+                let public = Keypair::random(&mut OsRng).public;
+                let address = Address::from(public);
+                (address, Participant::new(public, address, 0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_epoch_snapshot() {
+        let mut manager = EpochManager::default();
+        assert!(manager.slot_leader(0).is_none());
+
+        let mut live = participants(3);
+        assert!(manager.update(0, &live));
+
+        // Stake movements inside the epoch don't change eligibility
+        live.extend(participants(2));
+        assert!(!manager.update(0, &live));
+        assert_eq!(manager.snapshot.len(), 3);
+        let leader = manager.slot_leader(5).unwrap().address;
+        assert!(manager.snapshot.contains_key(&leader));
+
+        // They take effect on the next epoch
+        assert!(manager.update(1, &live));
+        assert_eq!(manager.snapshot.len(), 5);
+    }
+}
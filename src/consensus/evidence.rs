@@ -0,0 +1,217 @@
+use log::debug;
+
+use super::{BlockProposal, Header};
+use crate::{
+    crypto::{
+        address::Address,
+        keypair::PublicKey,
+        schnorr::{SchnorrPublic, Signature},
+    },
+    node::dispatch::{Dispatcher, NativeContract, Writes},
+    runtime::import::StateReader,
+    tx::ContractCall,
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    Error::{InvalidEvidence, ParticipantAlreadySlashed, SlashingInvalidCall},
+    Result,
+};
+
+/// Hashed into the ID the slashing contract is registered under.
+const SLASHING_DOMAIN: &[u8] = b"DarkFi:Slashing";
+
+/// ID of the native slashing contract.
+pub fn slashing_id() -> [u8; 32] {
+    *blake3::hash(SLASHING_DOMAIN).as_bytes()
+}
+
+/// Functions of the slashing contract, used as the `func_id` of its calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SlashingFunction {
+    Equivocation = 0x00,
+}
+
+impl TryFrom<u8> for SlashingFunction {
+    type Error = crate::Error;
+
+    fn try_from(b: u8) -> Result<Self> {
+        match b {
+            0x00 => Ok(Self::Equivocation),
+            _ => Err(SlashingInvalidCall(format!("Unknown function {}", b))),
+        }
+    }
+}
+
+/// Block header along with the signature its proposer made over it.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SignedHeader {
+    pub header: Header,
+    pub signature: Signature,
+}
+
+impl From<&BlockProposal> for SignedHeader {
+    fn from(proposal: &BlockProposal) -> Self {
+        Self {
+            header: proposal.block.header.clone(),
+            signature: proposal.block.metadata.signature.clone(),
+        }
+    }
+}
+
+/// Proof that a leader signed two different proposals for the same slot.
+/// Anyone holding both proposals can submit it, to get the leader slashed.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct EquivocationEvidence {
+    /// Public key of the equivocating leader
+    pub public_key: PublicKey,
+    pub first: SignedHeader,
+    pub second: SignedHeader,
+}
+
+impl EquivocationEvidence {
+    pub fn new(public_key: PublicKey, first: SignedHeader, second: SignedHeader) -> Self {
+        Self { public_key, first, second }
+    }
+
+    /// Address of the participant the evidence is against.
+    pub fn offender(&self) -> Address {
+        Address::from(self.public_key)
+    }
+
+    /// Check both headers are for the same slot, differ, and are signed by
+    /// the offender.
+    pub fn verify(&self) -> Result<()> {
+        if self.first.header.slot != self.second.header.slot {
+            return Err(InvalidEvidence("Headers are for different slots".to_string()))
+        }
+
+        let first_hash = self.first.header.headerhash();
+        let second_hash = self.second.header.headerhash();
+        if first_hash == second_hash {
+            return Err(InvalidEvidence("Headers are the same".to_string()))
+        }
+
+        if !self.public_key.verify(first_hash.as_bytes(), &self.first.signature) ||
+            !self.public_key.verify(second_hash.as_bytes(), &self.second.signature)
+        {
+            return Err(InvalidEvidence("Header signature could not be verified".to_string()))
+        }
+
+        Ok(())
+    }
+
+    /// Call submitting the evidence. It needs no signature, so any node can
+    /// submit it.
+    pub fn call(&self) -> ContractCall {
+        ContractCall {
+            contract_id: slashing_id(),
+            func_id: SlashingFunction::Equivocation as u8,
+            call_data: serialize(self),
+            proofs: vec![],
+            signature_public: vec![],
+        }
+    }
+}
+
+/// Native contract recording the participants slashed for misbehaving.
+///
+/// Its state maps the address of every slashed participant to the slot
+/// they misbehaved in. As the state is part of the canonical chain, every
+/// node evicts slashed participants at the same point, and refuses to let
+/// them back into the participant set.
+pub struct Slashing;
+
+impl Slashing {
+    /// Read the slot a participant got slashed for, if they did.
+    pub fn get_slashed(state: &dyn StateReader, address: &Address) -> Result<Option<u64>> {
+        match state.get(&address.0)? {
+            Some(bytes) => Ok(Some(deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Register the functions of the contract with the dispatcher.
+    pub fn register(dispatcher: &mut Dispatcher) {
+        dispatcher.register_function(
+            slashing_id(),
+            SlashingFunction::Equivocation as u8,
+            "Slashing::equivocation()",
+            |_, evidence, state, _| Self::equivocation(evidence, state),
+        );
+    }
+
+    fn equivocation(evidence: EquivocationEvidence, state: &dyn StateReader) -> Result<Writes> {
+        evidence.verify()?;
+
+        let offender = evidence.offender();
+        if Self::get_slashed(state, &offender)?.is_some() {
+            return Err(ParticipantAlreadySlashed(offender.to_string()))
+        }
+
+        debug!(target: "slashing", "Slashing {} for equivocating", offender);
+        Ok(vec![(offender.0.to_vec(), serialize(&evidence.first.header.slot))])
+    }
+}
+
+impl NativeContract for Slashing {
+    fn execute(&self, call: &ContractCall, state: &dyn StateReader, _slot: u64) -> Result<Writes> {
+        match SlashingFunction::try_from(call.func_id)? {
+            SlashingFunction::Equivocation => {
+                Self::equivocation(deserialize(&call.call_data)?, state)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{
+        crypto::{keypair::Keypair, merkle_node::MerkleNode, schnorr::SchnorrSecret},
+        node::dispatch::MemoryState,
+        util::time::Timestamp,
+    };
+
+    fn signed_header(keypair: &Keypair, slot: u64, root: u64) -> SignedHeader {
+        let header = Header::new(
+            blake3::hash(b"parent"),
+            0,
+            slot,
+            Timestamp(0),
+            MerkleNode(pasta_curves::pallas::Base::from(root)),
+        );
+        let signature = keypair.secret.sign(header.headerhash().as_bytes());
+        SignedHeader { header, signature }
+    }
+
+    #[test]
+    fn test_equivocation_slashing() {
+        let leader = Keypair::random(&mut OsRng);
+        let slashing = Slashing;
+        let state = MemoryState::default();
+
+        // Same header twice, and headers of different slots, aren't equivocation
+        let first = signed_header(&leader, 5, 1);
+        let evidence = EquivocationEvidence::new(leader.public, first.clone(), first.clone());
+        assert!(slashing.execute(&evidence.call(), &state, 0).is_err());
+        let evidence =
+            EquivocationEvidence::new(leader.public, first.clone(), signed_header(&leader, 6, 2));
+        assert!(slashing.execute(&evidence.call(), &state, 0).is_err());
+
+        // Headers must be signed by the offender
+        let other = Keypair::random(&mut OsRng);
+        let evidence =
+            EquivocationEvidence::new(leader.public, first.clone(), signed_header(&other, 5, 2));
+        assert!(slashing.execute(&evidence.call(), &state, 0).is_err());
+
+        let evidence =
+            EquivocationEvidence::new(leader.public, first, signed_header(&leader, 5, 2));
+        let writes = slashing.execute(&evidence.call(), &state, 0).unwrap();
+        state.apply(writes);
+        assert_eq!(Slashing::get_slashed(&state, &evidence.offender()).unwrap(), Some(5));
+
+        // A participant is only slashed once
+        assert!(slashing.execute(&evidence.call(), &state, 0).is_err());
+    }
+}
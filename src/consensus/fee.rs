@@ -0,0 +1,89 @@
+use crate::{node::mempool::fee_rate, tx::Transaction, util::serial::Encodable, Result};
+
+/// Maximum total serialized size of the transactions of a block
+pub const MAX_BLOCK_TXS_SIZE: usize = 1024 * 1024;
+/// Number of recent slots looked at for fee estimation
+pub const FEE_ESTIMATION_SLOTS: u64 = 100;
+/// Blocks filled below this percentage of [`MAX_BLOCK_TXS_SIZE`] had room
+/// for every pending transaction, so any fee would have been included.
+const FULL_BLOCK_PERCENT: usize = 90;
+/// Share of the past slot windows, in percent, in which a transaction
+/// paying the estimated fee rate would have been included.
+const ESTIMATE_CONFIDENCE_PERCENT: usize = 90;
+
+/// Total serialized size of the given transactions.
+pub fn txs_size(txs: &[Transaction]) -> Result<usize> {
+    let mut size = 0;
+    for tx in txs {
+        size += tx.encode(&mut std::io::sink())?;
+    }
+    Ok(size)
+}
+
+/// Lowest fee rate a transaction had to pay to get into a block with the
+/// given transactions. Blocks that were not full accepted any fee.
+pub fn block_min_fee_rate(txs: &[Transaction]) -> Result<u64> {
+    let mut size = 0;
+    let mut min_rate = u64::MAX;
+    for tx in txs {
+        let tx_size = tx.encode(&mut std::io::sink())?;
+        size += tx_size;
+        min_rate = min_rate.min(fee_rate(tx.fee, tx_size));
+    }
+
+    if size * 100 < MAX_BLOCK_TXS_SIZE * FULL_BLOCK_PERCENT {
+        return Ok(0)
+    }
+
+    Ok(min_rate)
+}
+
+/// Suggest a fee rate, in fee per kilobyte, for a transaction to get
+/// included within `target_slots` slots. `min_rates` holds the minimum
+/// included fee rate of each recent slot in slot order, or `None` for
+/// slots without a block.
+///
+/// For every window of `target_slots` consecutive slots, a transaction
+/// would have been included if it paid the lowest of the window's minimum
+/// rates. The estimate is the rate that got in for most of the windows.
+pub fn estimate_fee_rate(min_rates: &[Option<u64>], target_slots: u64) -> u64 {
+    let target = (target_slots.max(1) as usize).min(min_rates.len().max(1));
+
+    let mut window_rates: Vec<u64> = min_rates
+        .windows(target)
+        .filter_map(|window| window.iter().flatten().min().copied())
+        .collect();
+
+    if window_rates.is_empty() {
+        return 0
+    }
+
+    window_rates.sort_unstable();
+    let idx = (window_rates.len() * ESTIMATE_CONFIDENCE_PERCENT / 100).min(window_rates.len() - 1);
+    window_rates[idx]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_fee_rate() {
+        assert_eq!(estimate_fee_rate(&[], 1), 0);
+        assert_eq!(estimate_fee_rate(&[None, None], 1), 0);
+        assert_eq!(estimate_fee_rate(&[Some(0); 10], 3), 0);
+
+        let rates: Vec<Option<u64>> = (1..=10).map(Some).collect();
+        // Getting in at the next slot takes outbidding most blocks
+        assert_eq!(estimate_fee_rate(&rates, 1), 10);
+        // Waiting longer lets the cheapest block of the window do
+        assert_eq!(estimate_fee_rate(&rates, 5), 6);
+        assert_eq!(estimate_fee_rate(&rates, 100), 1);
+
+        // Empty slots don't include anything
+        let rates = [Some(50), None, Some(10), None];
+        assert_eq!(estimate_fee_rate(&rates, 1), 50);
+        assert_eq!(estimate_fee_rate(&rates, 2), 50);
+        assert_eq!(estimate_fee_rate(&rates, 3), 10);
+    }
+}
@@ -0,0 +1,43 @@
+use super::ProposalChain;
+
+/// Event emitted when the node switches its tip to a competing fork chain.
+/// Block hashes are listed from the canonical blockchain towards the tip.
+#[derive(Debug, Clone)]
+pub struct ReorgEvent {
+    /// Last block hash of the previous tip
+    pub old_tip: blake3::Hash,
+    /// Last block hash of the new tip
+    pub new_tip: blake3::Hash,
+    /// Blocks of the previous tip that are no longer part of it
+    pub rolled_back: Vec<blake3::Hash>,
+    /// Blocks of the new tip that weren't part of the previous one
+    pub applied: Vec<blake3::Hash>,
+}
+
+impl ReorgEvent {
+    /// Number of blocks rolled back.
+    pub fn depth(&self) -> usize {
+        self.rolled_back.len()
+    }
+}
+
+/// Streamlet fork-choice rule: among the fork chains, pick the longest
+/// notarized one. Ties go to the chain seen first. Returns the chain
+/// index, or `None` if no fork chain is notarized and the canonical
+/// blockchain is the tip.
+pub fn fork_choice(chains: &[ProposalChain]) -> Option<usize> {
+    let mut best = None;
+    let mut length = 0;
+    for (i, chain) in chains.iter().enumerate() {
+        if chain.notarized() && chain.proposals.len() > length {
+            best = Some(i);
+            length = chain.proposals.len();
+        }
+    }
+    best
+}
+
+/// Length of the common prefix of two chains given by their block hashes.
+pub fn common_prefix_len(a: &[blake3::Hash], b: &[blake3::Hash]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
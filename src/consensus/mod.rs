@@ -8,19 +8,54 @@ pub use metadata::{Metadata, StreamletMetadata};
 
 /// Consensus participant
 pub mod participant;
-pub use participant::Participant;
+pub use participant::{Participant, Rejoin};
+
+/// Epoch participant snapshots
+pub mod epoch;
+pub use epoch::EpochManager;
 
 /// Consensus vote
 pub mod vote;
 pub use vote::Vote;
 
+/// Clock drift detection
+pub mod clock;
+pub use clock::{ClockSync, ClockSyncPtr};
+
+/// Signed consensus message envelope
+pub mod envelope;
+pub use envelope::{ConsensusMessage, SignedMessage};
+
+/// Fork tracking and fork-choice rule
+pub mod fork;
+pub use fork::ReorgEvent;
+
+/// Block size limit and fee estimation
+pub mod fee;
+
+/// Slashing evidence for misbehaving participants
+pub mod evidence;
+pub use evidence::{EquivocationEvidence, Slashing};
+
+/// Validator state snapshots
+pub mod snapshot;
+pub use snapshot::StateSnapshot;
+
 /// Consensus state
 pub mod state;
 pub use state::{ValidatorState, ValidatorStatePtr};
 
+/// Wallet note scanning
+pub mod scanner;
+pub use scanner::{NoteScanner, NoteScannerPtr, ScanProgress};
+
 /// Utility functions and types
 use crate::util::time::Timestamp;
 
+/// Deterministic consensus simulations
+#[cfg(test)]
+pub mod testing;
+
 /// P2P net protocols
 pub mod proto;
 
@@ -30,4 +30,56 @@ impl net::Message for Participant {
     fn name() -> &'static str {
         "participant"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::High
+    }
+}
+
+/// Message broadcast every slot by a node that got removed from the
+/// participant set, to prove its liveness and re-enter it.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct Rejoin {
+    /// Node public key
+    pub public_key: PublicKey,
+    /// Node wallet address
+    pub address: Address,
+}
+
+impl Rejoin {
+    pub fn new(public_key: PublicKey, address: Address) -> Self {
+        Self { public_key, address }
+    }
+}
+
+impl net::Message for Rejoin {
+    fn name() -> &'static str {
+        "rejoin"
+    }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::High
+    }
+}
+
+/// Liveness record of a node trying to rejoin the participant set.
+#[derive(Debug, Clone)]
+pub struct RejoinRecord {
+    /// Node public key
+    pub public_key: PublicKey,
+    /// First slot of the current streak of rejoin messages
+    pub first_slot: u64,
+    /// Last slot a rejoin message was received for
+    pub last_slot: u64,
+}
+
+impl RejoinRecord {
+    pub fn new(public_key: PublicKey, slot: u64) -> Self {
+        Self { public_key, first_slot: slot, last_slot: slot }
+    }
+
+    /// Number of consecutive slots the node proved liveness for.
+    pub fn streak(&self) -> u64 {
+        self.last_slot - self.first_slot + 1
+    }
 }
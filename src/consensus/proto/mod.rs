@@ -2,6 +2,10 @@
 mod protocol_participant;
 pub use protocol_participant::ProtocolParticipant;
 
+/// Participant rejoin protocol
+mod protocol_rejoin;
+pub use protocol_rejoin::ProtocolRejoin;
+
 /// Block proposal protocol
 mod protocol_proposal;
 pub use protocol_proposal::ProtocolProposal;
@@ -21,3 +25,7 @@ pub use protocol_sync::ProtocolSync;
 /// Validator consensus sync protocol
 mod protocol_sync_consensus;
 pub use protocol_sync_consensus::ProtocolSyncConsensus;
+
+/// Compact block filter protocol for light wallets
+mod protocol_filter;
+pub use protocol_filter::ProtocolFilter;
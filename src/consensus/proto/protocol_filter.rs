@@ -0,0 +1,110 @@
+use async_executor::Executor;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{
+    consensus::{
+        block::{
+            FilterHeaderOrder, FilterHeaderResponse, FilterOrder, FilterResponse, SlotFilter,
+            SlotFilterHeader, FILTER_ORDER_MAX,
+        },
+        ValidatorStatePtr,
+    },
+    net::{
+        request::serve_requests, ChannelPtr, ProtocolBase, ProtocolBasePtr, ProtocolJobsManager,
+        ProtocolJobsManagerPtr,
+    },
+    Error, Result,
+};
+
+/// Serves compact block filters and filter headers to light wallets,
+/// so they can sync without downloading full blocks.
+pub struct ProtocolFilter {
+    channel: ChannelPtr,
+    jobsman: ProtocolJobsManagerPtr,
+    state: ValidatorStatePtr,
+}
+
+impl ProtocolFilter {
+    pub async fn init(channel: ChannelPtr, state: ValidatorStatePtr) -> Result<ProtocolBasePtr> {
+        Ok(Arc::new(Self {
+            channel: channel.clone(),
+            jobsman: ProtocolJobsManager::new("FilterProtocol", channel),
+            state,
+        }))
+    }
+
+    async fn handle_receive_filter_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFilter::handle_receive_filter_request() [START]");
+        let state = self.state.clone();
+        serve_requests(self.channel.clone(), move |order: FilterOrder| {
+            let state = state.clone();
+            async move {
+                debug!("ProtocolFilter::handle_receive_filter_request() received {:?}", order);
+                if order.end.saturating_sub(order.start) > FILTER_ORDER_MAX {
+                    return Err(Error::MalformedPacket)
+                }
+                let filters = state
+                    .read()
+                    .await
+                    .blockchain
+                    .get_filters_by_slot_range(order.start, order.end)?
+                    .into_iter()
+                    .map(|(slot, block, filter, _)| SlotFilter { slot, block, filter })
+                    .collect();
+                Ok(FilterResponse { filters })
+            }
+        })
+        .await
+    }
+
+    async fn handle_receive_filter_header_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFilter::handle_receive_filter_header_request() [START]");
+        let state = self.state.clone();
+        serve_requests(self.channel.clone(), move |order: FilterHeaderOrder| {
+            let state = state.clone();
+            async move {
+                debug!(
+                    "ProtocolFilter::handle_receive_filter_header_request() received {:?}",
+                    order
+                );
+                if order.end.saturating_sub(order.start) > FILTER_ORDER_MAX {
+                    return Err(Error::MalformedPacket)
+                }
+                let headers = state
+                    .read()
+                    .await
+                    .blockchain
+                    .get_filters_by_slot_range(order.start, order.end)?
+                    .into_iter()
+                    .map(|(slot, block, _, header)| SlotFilterHeader { slot, block, header })
+                    .collect();
+                Ok(FilterHeaderResponse { headers })
+            }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ProtocolBase for ProtocolFilter {
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!("ProtocolFilter::start() [START]");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_filter_request(), executor.clone())
+            .await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_filter_header_request(), executor.clone())
+            .await;
+        debug!("ProtocolFilter::start() [END]");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolFilter"
+    }
+}
@@ -6,7 +6,7 @@ use log::{debug, error};
 use url::Url;
 
 use crate::{
-    consensus::{Participant, ValidatorStatePtr},
+    consensus::{Participant, SignedMessage, ValidatorStatePtr},
     net::{
         ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
         ProtocolJobsManager, ProtocolJobsManagerPtr,
@@ -15,7 +15,7 @@ use crate::{
 };
 
 pub struct ProtocolParticipant {
-    participant_sub: MessageSubscription<Participant>,
+    participant_sub: MessageSubscription<SignedMessage<Participant>>,
     jobsman: ProtocolJobsManagerPtr,
     state: ValidatorStatePtr,
     p2p: P2pPtr,
@@ -30,9 +30,9 @@ impl ProtocolParticipant {
     ) -> Result<ProtocolBasePtr> {
         debug!("Adding ProtocolParticipant to the protocol registry");
         let msg_subsystem = channel.get_message_subsystem();
-        msg_subsystem.add_dispatch::<Participant>().await;
+        msg_subsystem.add_dispatch::<SignedMessage<Participant>>().await;
 
-        let participant_sub = channel.subscribe_msg::<Participant>().await?;
+        let participant_sub = channel.subscribe_msg::<SignedMessage<Participant>>().await?;
         let channel_address = channel.address();
 
         Ok(Arc::new(Self {
@@ -60,7 +60,12 @@ impl ProtocolParticipant {
 
             let participant_copy = (*participant).clone();
 
-            if self.state.write().await.append_participant(participant_copy.clone()) {
+            if !self.state.write().await.verify_message(&participant_copy) {
+                debug!("ProtocolParticipant::handle_receive_participant(): Dropping unverified participant");
+                continue
+            }
+
+            if self.state.write().await.append_participant(participant_copy.payload.clone()) {
                 if let Err(e) =
                     self.p2p.broadcast_with_exclude(participant_copy, &exclude_list).await
                 {
@@ -91,6 +91,7 @@ impl ProtocolProposal {
             };
 
             // Broadcast vote
+            let vote = self.state.read().await.sign_message(vote);
             if let Err(e) = self.p2p.broadcast(vote).await {
                 error!("ProtocolProposal::handle_receive_proposal(): vote broadcast fail: {}", e);
             }
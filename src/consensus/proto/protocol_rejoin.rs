@@ -0,0 +1,90 @@
+use async_std::sync::Arc;
+
+use async_executor::Executor;
+use async_trait::async_trait;
+use log::{debug, error};
+use url::Url;
+
+use crate::{
+    consensus::{Rejoin, SignedMessage, ValidatorStatePtr},
+    net::{
+        ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
+        ProtocolJobsManager, ProtocolJobsManagerPtr,
+    },
+    Result,
+};
+
+pub struct ProtocolRejoin {
+    rejoin_sub: MessageSubscription<SignedMessage<Rejoin>>,
+    jobsman: ProtocolJobsManagerPtr,
+    state: ValidatorStatePtr,
+    p2p: P2pPtr,
+    channel_address: Url,
+}
+
+impl ProtocolRejoin {
+    pub async fn init(
+        channel: ChannelPtr,
+        state: ValidatorStatePtr,
+        p2p: P2pPtr,
+    ) -> Result<ProtocolBasePtr> {
+        debug!("Adding ProtocolRejoin to the protocol registry");
+        let msg_subsystem = channel.get_message_subsystem();
+        msg_subsystem.add_dispatch::<SignedMessage<Rejoin>>().await;
+
+        let rejoin_sub = channel.subscribe_msg::<SignedMessage<Rejoin>>().await?;
+        let channel_address = channel.address();
+
+        Ok(Arc::new(Self {
+            rejoin_sub,
+            jobsman: ProtocolJobsManager::new("RejoinProtocol", channel),
+            state,
+            p2p,
+            channel_address,
+        }))
+    }
+
+    async fn handle_receive_rejoin(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolRejoin::handle_receive_rejoin() [START]");
+        let exclude_list = vec![self.channel_address.clone()];
+        loop {
+            let rejoin = match self.rejoin_sub.receive().await {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("ProtocolRejoin::handle_receive_rejoin(): recv error: {}", e);
+                    continue
+                }
+            };
+
+            debug!("ProtocolRejoin::handle_receive_rejoin() recv: {:?}", rejoin);
+
+            let rejoin_copy = (*rejoin).clone();
+
+            if !self.state.write().await.verify_message(&rejoin_copy) {
+                debug!("ProtocolRejoin::handle_receive_rejoin(): Dropping unverified rejoin");
+                continue
+            }
+
+            if self.state.write().await.receive_rejoin(&rejoin_copy.payload, rejoin_copy.slot) {
+                if let Err(e) = self.p2p.broadcast_with_exclude(rejoin_copy, &exclude_list).await {
+                    error!("ProtocolRejoin::handle_receive_rejoin(): p2p broadcast failed: {}", e);
+                };
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProtocolBase for ProtocolRejoin {
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!("ProtocolRejoin::start() [START]");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman.clone().spawn(self.clone().handle_receive_rejoin(), executor.clone()).await;
+        debug!("ProtocolRejoin::start() [END]");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolRejoin"
+    }
+}
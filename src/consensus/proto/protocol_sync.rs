@@ -5,18 +5,23 @@ use log::{debug, error, info};
 
 use crate::{
     consensus::{
-        block::{BlockInfo, BlockOrder, BlockResponse},
+        block::{
+            BlockInfo, BlockOrder, BlockResponse, BodyOrder, BodyResponse, HeaderOrder,
+            HeaderResponse, SyncInfoOrder, SyncInfoResponse, BODY_ORDER_MAX,
+        },
         ValidatorStatePtr,
     },
     net::{
-        ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
-        ProtocolJobsManager, ProtocolJobsManagerPtr,
+        request::serve_requests, ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase,
+        ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr,
     },
-    Result,
+    Error, Result,
 };
 
 // Constant defining how many blocks we send during syncing.
 const BATCH: u64 = 10;
+// Constant defining how many headers we send during headers-first syncing.
+const HEADERS_BATCH: u64 = 500;
 
 pub struct ProtocolSync {
     channel: ChannelPtr,
@@ -67,14 +72,17 @@ impl ProtocolSync {
             debug!("ProtocolSync::handle_receive_request() received {:?}", order);
 
             // Extra validations can be added here
-            let key = order.slot;
-            let blocks = match self.state.read().await.blockchain.get_blocks_after(key, BATCH) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("ProtocolSync::handle_receive_request(): get_blocks_after fail: {}", e);
-                    continue
-                }
-            };
+            let blocks =
+                match self.state.read().await.blockchain.get_blocks_after(&order.block, BATCH) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!(
+                            "ProtocolSync::handle_receive_request(): get_blocks_after fail: {}",
+                            e
+                        );
+                        continue
+                    }
+                };
             debug!("ProtocolSync::handle_receive_request(): Found {} blocks", blocks.len());
 
             let response = BlockResponse { blocks };
@@ -84,6 +92,59 @@ impl ProtocolSync {
         }
     }
 
+    async fn handle_receive_sync_info_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolSync::handle_receive_sync_info_request() [START]");
+        let state = self.state.clone();
+        serve_requests(self.channel.clone(), move |order: SyncInfoOrder| {
+            let state = state.clone();
+            async move {
+                debug!("ProtocolSync::handle_receive_sync_info_request() received {:?}", order);
+                let pruned = state.read().await.blockchain.pruned_slot()?;
+                Ok(SyncInfoResponse { pruned })
+            }
+        })
+        .await
+    }
+
+    async fn handle_receive_header_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolSync::handle_receive_header_request() [START]");
+        let state = self.state.clone();
+        serve_requests(self.channel.clone(), move |order: HeaderOrder| {
+            let state = state.clone();
+            async move {
+                debug!("ProtocolSync::handle_receive_header_request() received {:?}", order);
+                // Only serve peers following our chain
+                let headers = state.read().await.blockchain.get_headers_after(
+                    order.slot,
+                    &order.block,
+                    HEADERS_BATCH,
+                )?;
+                Ok(HeaderResponse { headers })
+            }
+        })
+        .await
+    }
+
+    async fn handle_receive_body_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolSync::handle_receive_body_request() [START]");
+        let state = self.state.clone();
+        serve_requests(self.channel.clone(), move |order: BodyOrder| {
+            let state = state.clone();
+            async move {
+                debug!(
+                    "ProtocolSync::handle_receive_body_request(): {} blocks",
+                    order.blocks.len()
+                );
+                if order.blocks.len() > BODY_ORDER_MAX {
+                    return Err(Error::MalformedPacket)
+                }
+                let blocks = state.read().await.blockchain.get_blocks_by_hash(&order.blocks)?;
+                Ok(BodyResponse { blocks })
+            }
+        })
+        .await
+    }
+
     async fn handle_receive_block(self: Arc<Self>) -> Result<()> {
         // Consensus-mode enabled nodes have already performed these steps,
         // during proposal finalization.
@@ -140,6 +201,18 @@ impl ProtocolBase for ProtocolSync {
         debug!("ProtocolSync::start() [START]");
         self.jobsman.clone().start(executor.clone());
         self.jobsman.clone().spawn(self.clone().handle_receive_request(), executor.clone()).await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_sync_info_request(), executor.clone())
+            .await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_header_request(), executor.clone())
+            .await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_body_request(), executor.clone())
+            .await;
         self.jobsman.clone().spawn(self.clone().handle_receive_block(), executor.clone()).await;
         debug!("ProtocolSync::start() [END]");
         Ok(())
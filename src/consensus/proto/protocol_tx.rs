@@ -13,6 +13,7 @@ use crate::{
         ProtocolJobsManager, ProtocolJobsManagerPtr,
     },
     tx::Transaction,
+    util::serial::{decode_canonical, BytesReader},
     Result,
 };
 
@@ -28,6 +29,11 @@ impl net::Message for Transaction {
     fn name() -> &'static str {
         "tx"
     }
+
+    /// Hashed by consensus, so only its canonical encoding is accepted.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        decode_canonical(reader)
+    }
 }
 
 impl ProtocolTx {
@@ -66,7 +72,7 @@ impl ProtocolTx {
 
             let tx_copy = (*tx).clone();
 
-            // Nodes use unconfirmed_txs vector as seen_txs pool.
+            // Nodes use the mempool as seen_txs pool.
             if self.state.write().await.append_tx(tx_copy.clone()).await {
                 if let Err(e) = self.p2p.broadcast_with_exclude(tx_copy, &exclude_list).await {
                     error!("handle_receive_tx(): p2p broadcast fail: {}", e);
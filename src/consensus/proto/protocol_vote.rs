@@ -6,7 +6,7 @@ use log::{debug, error};
 use url::Url;
 
 use crate::{
-    consensus::{ValidatorStatePtr, Vote},
+    consensus::{SignedMessage, ValidatorStatePtr, Vote},
     net::{
         ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
         ProtocolJobsManager, ProtocolJobsManagerPtr,
@@ -15,7 +15,7 @@ use crate::{
 };
 
 pub struct ProtocolVote {
-    vote_sub: MessageSubscription<Vote>,
+    vote_sub: MessageSubscription<SignedMessage<Vote>>,
     jobsman: ProtocolJobsManagerPtr,
     state: ValidatorStatePtr,
     sync_p2p: P2pPtr,
@@ -32,9 +32,9 @@ impl ProtocolVote {
     ) -> Result<ProtocolBasePtr> {
         debug!("Adding ProtocolVote to the protocol registry");
         let msg_subsystem = channel.get_message_subsystem();
-        msg_subsystem.add_dispatch::<Vote>().await;
+        msg_subsystem.add_dispatch::<SignedMessage<Vote>>().await;
 
-        let vote_sub = channel.subscribe_msg::<Vote>().await?;
+        let vote_sub = channel.subscribe_msg::<SignedMessage<Vote>>().await?;
         let channel_address = channel.address();
 
         Ok(Arc::new(Self {
@@ -63,8 +63,14 @@ impl ProtocolVote {
 
             let vote_copy = (*vote).clone();
 
+            // The envelope and vote signatures are verified in one batch
+            if !self.state.write().await.verify_message(&vote_copy) {
+                debug!("handle_receive_vote(): Dropping unverified vote");
+                continue
+            }
+
             let (voted, to_broadcast) =
-                match self.state.write().await.receive_vote(&vote_copy).await {
+                match self.state.write().await.receive_verified_vote(&vote_copy.payload).await {
                     Ok(v) => v,
                     Err(e) => {
                         error!("handle_receive_vote(): receive_vote() fail: {}", e);
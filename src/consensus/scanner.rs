@@ -0,0 +1,259 @@
+use async_std::sync::{Arc, Mutex};
+use incrementalmerkletree::{bridgetree::BridgeTree, Tree};
+use log::{debug, error, info};
+
+use super::{BlockInfo, ValidatorStatePtr};
+use crate::{
+    crypto::{
+        coin::Coin, constants::MERKLE_DEPTH, keypair::SecretKey, merkle_node::MerkleNode,
+        nullifier::Nullifier, OwnCoin,
+    },
+    node::State,
+    Error, Result,
+};
+
+/// Progress of the current or last wallet scan.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub running: bool,
+    /// First slot scanned, never before the wallet birthday
+    pub from_slot: u64,
+    /// Last finalized slot when the scan started
+    pub to_slot: u64,
+    /// Last slot scanned so far
+    pub scanned_slot: u64,
+    /// Coins of the wallet found so far
+    pub coins_found: u64,
+}
+
+/// Number of slots of blocks read per batch while scanning. The
+/// validator state is only locked while a batch is read.
+const SCAN_BATCH_SIZE: u64 = 100;
+
+pub type NoteScannerPtr = Arc<NoteScanner>;
+
+/// Scans finalized blocks for notes sent to the wallet, in the background.
+///
+/// Blocks are scanned by the canonical state as they get finalized, and
+/// the wallet keeps the slot it was scanned up to. The scanner catches
+/// up from there on startup, and rescans from a given slot on request,
+/// like after importing keys. Blocks before the wallet birthday are
+/// skipped, as they can't hold coins of the wallet.
+pub struct NoteScanner {
+    validator_state: ValidatorStatePtr,
+    progress: Mutex<ScanProgress>,
+    rescan_sender: async_channel::Sender<u64>,
+    rescan_receiver: async_channel::Receiver<u64>,
+}
+
+impl NoteScanner {
+    pub fn new(validator_state: ValidatorStatePtr) -> NoteScannerPtr {
+        let (rescan_sender, rescan_receiver) = async_channel::unbounded();
+        Arc::new(Self {
+            validator_state,
+            progress: Mutex::new(ScanProgress::default()),
+            rescan_sender,
+            rescan_receiver,
+        })
+    }
+
+    /// Queue a rescan of the blocks from `from_slot` on.
+    pub async fn rescan(&self, from_slot: u64) -> Result<()> {
+        self.rescan_sender.send(from_slot).await?;
+        Ok(())
+    }
+
+    pub async fn progress(&self) -> ScanProgress {
+        self.progress.lock().await.clone()
+    }
+
+    /// Catch up with the blocks finalized since the wallet was last
+    /// scanned, then serve rescan requests. Failed scans are logged, and
+    /// can be requested again.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let (scanned, last) = {
+            let validator_state = self.validator_state.read().await;
+            let scanned = validator_state.client.wallet.get_scanned_slot().await?;
+            (scanned, validator_state.blockchain.last()?.0)
+        };
+        if scanned < last {
+            info!("scanner: Catching up with blocks {}..={}", scanned + 1, last);
+            if let Err(e) = self.scan(scanned + 1).await {
+                error!("scanner: Failed catching up: {}", e);
+            }
+            self.progress.lock().await.running = false;
+        }
+
+        loop {
+            let from_slot = self.rescan_receiver.recv().await?;
+            if let Err(e) = self.scan(from_slot).await {
+                error!("scanner: Failed rescanning from slot {}: {}", from_slot, e);
+            }
+            self.progress.lock().await.running = false;
+        }
+    }
+
+    /// Scan the finalized blocks from `from_slot` on for notes of the
+    /// wallet. As coins are only spendable when witnessed in the Merkle
+    /// tree, the tree is rebuilt from the last checkpoint before the
+    /// scanned blocks and the wallet's unspent coins, witnessing the coins
+    /// found along with the ones the wallet already has, and replaces the
+    /// canonical one.
+    ///
+    /// Blocks are read in batches, locking the validator state only while
+    /// reading each. The last batch is scanned holding the canonical state,
+    /// so no block gets finalized before the tree replaces it.
+    async fn scan(&self, from_slot: u64) -> Result<()> {
+        let wallet = self.validator_state.read().await.client.wallet.clone();
+        let secret_keys = wallet.get_scanning_secrets().await?;
+        let from_slot = from_slot.max(wallet.get_birthday_slot().await?);
+
+        // Unspent coins must stay witnessed, so the tree is replayed from
+        // before the first of them
+        let known: Vec<Coin> = wallet.get_own_coins().await?.iter().map(|c| c.coin).collect();
+        let replay_slot = match wallet.get_unspent_received_slot().await? {
+            Some(slot) => slot.min(from_slot),
+            None => from_slot,
+        };
+
+        let (checkpoint, to_slot) = {
+            let validator_state = self.validator_state.read().await;
+            let checkpoint = match replay_slot.checked_sub(1) {
+                Some(slot) => validator_state.blockchain.merkle_trees.get_at_or_before(slot)?,
+                None => None,
+            };
+            (checkpoint, validator_state.blockchain.last()?.0)
+        };
+        let mut scan = match checkpoint {
+            Some((slot, tree)) => Scan::new(slot + 1, from_slot, tree),
+            None => Scan::new(0, from_slot, BridgeTree::new(100)),
+        };
+
+        *self.progress.lock().await = ScanProgress {
+            running: true,
+            from_slot,
+            to_slot,
+            scanned_slot: from_slot.saturating_sub(1),
+            coins_found: 0,
+        };
+        info!("scanner: Scanning blocks {}..={} from slot {}", from_slot, to_slot, scan.next_slot);
+
+        loop {
+            let validator_state = self.validator_state.read().await;
+            let (last, _) = validator_state.blockchain.last()?;
+            let end = scan.next_slot.saturating_add(SCAN_BATCH_SIZE);
+
+            if end <= last {
+                let blocks =
+                    validator_state.blockchain.get_blocks_by_slot_range(scan.next_slot, end)?;
+                drop(validator_state);
+                scan.blocks(blocks, &secret_keys, &known);
+                scan.next_slot = end;
+                self.update_progress(&scan).await;
+                continue
+            }
+
+            let mut state = validator_state.state_machine.lock().await;
+            let blocks =
+                validator_state.blockchain.get_blocks_by_slot_range(scan.next_slot, last + 1)?;
+            scan.blocks(blocks, &secret_keys, &known);
+            self.update_progress(&scan).await;
+
+            // Blocks bootstrapped from a snapshot or pruned can't be replayed
+            if scan.tree.root(0) != state.tree.root(0) {
+                return Err(Error::MerkleTreeIncomplete)
+            }
+
+            for (own_coin, slot) in scan.own_coins {
+                wallet.put_own_coin(own_coin, slot).await?;
+            }
+            for (nullifiers, slot) in scan.spends {
+                wallet.confirm_nullifiers(&nullifiers, slot).await?;
+            }
+
+            state.tree = scan.tree;
+            validator_state.blockchain.merkle_trees.insert(last, &state.tree)?;
+            wallet.set_scanned_slot(last).await?;
+            drop(state);
+
+            let progress = self.progress().await;
+            info!("scanner: Scanned up to slot {}, found {} coins", last, progress.coins_found);
+            return Ok(())
+        }
+    }
+
+    async fn update_progress(&self, scan: &Scan) {
+        let mut progress = self.progress.lock().await;
+        progress.coins_found = scan.own_coins.len() as u64;
+        if let Some(slot) = scan.scanned_slot {
+            progress.scanned_slot = slot;
+        }
+    }
+}
+
+/// Merkle tree being rebuilt by a scan, and the wallet writes waiting for
+/// it to be checked against the canonical one.
+struct Scan {
+    /// Slot of the next block to apply to the tree
+    next_slot: u64,
+    /// Blocks from this slot on are scanned for notes
+    from_slot: u64,
+    /// Last slot scanned for notes
+    scanned_slot: Option<u64>,
+    tree: BridgeTree<MerkleNode, MERKLE_DEPTH>,
+    own_coins: Vec<(OwnCoin, u64)>,
+    spends: Vec<(Vec<Nullifier>, u64)>,
+}
+
+impl Scan {
+    fn new(next_slot: u64, from_slot: u64, tree: BridgeTree<MerkleNode, MERKLE_DEPTH>) -> Self {
+        Self { next_slot, from_slot, scanned_slot: None, tree, own_coins: vec![], spends: vec![] }
+    }
+
+    /// Apply the given blocks to the tree, trial-decrypting the notes of
+    /// the ones from `from_slot` on with `secret_keys`. The coins found
+    /// and the `known` ones are witnessed.
+    fn blocks(&mut self, blocks: Vec<BlockInfo>, secret_keys: &[SecretKey], known: &[Coin]) {
+        for block in blocks {
+            let slot = block.header.slot;
+            let scanned = slot >= self.from_slot;
+
+            let mut nullifiers: Vec<Nullifier> = vec![];
+            for tx in block.txs {
+                nullifiers.extend(tx.inputs.iter().map(|input| input.revealed.nullifier));
+
+                for output in tx.outputs {
+                    let coin = output.revealed.coin;
+                    self.tree.append(&MerkleNode::from_coin(&coin));
+
+                    let found = match scanned {
+                        true => secret_keys.iter().find_map(|secret| {
+                            State::try_decrypt_note(&output.enc_note, *secret)
+                                .map(|note| (*secret, note))
+                        }),
+                        false => None,
+                    };
+
+                    match found {
+                        Some((secret, note)) => {
+                            debug!("scanner: Found a coin in slot {}: amount {}", slot, note.value);
+                            let leaf_position = self.tree.witness().unwrap();
+                            let nullifier = Nullifier::new(secret, note.serial);
+                            let own_coin = OwnCoin { coin, note, secret, nullifier, leaf_position };
+                            self.own_coins.push((own_coin, slot));
+                        }
+                        None if known.contains(&coin) => {
+                            self.tree.witness();
+                        }
+                        None => {}
+                    }
+                }
+            }
+
+            if scanned {
+                self.spends.push((nullifiers, slot));
+                self.scanned_slot = Some(slot);
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use log::info;
+
+use super::{BlockInfo, Participant};
+use crate::{
+    blockchain::StateWrite,
+    crypto::{address::Address, merkle_node::MerkleNode, nullifier::Nullifier},
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    Error, Result,
+};
+
+/// Magic bytes prepended to snapshot files
+const SNAPSHOT_MAGIC_BYTES: [u8; 4] = [0x53, 0x4e, 0x41, 0x50];
+
+/// Full validator state at the last finalized block, which a new node can
+/// bootstrap from instead of replaying every block since genesis.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct StateSnapshot {
+    /// Last finalized block
+    pub block: BlockInfo,
+    /// Merkle tree of all coins, bincode-encoded
+    pub tree: Vec<u8>,
+    /// All previous and the current Merkle roots
+    pub merkle_roots: Vec<MerkleNode>,
+    /// Nullifiers of all spent coins
+    pub nullifiers: Vec<Nullifier>,
+    /// Wasm bincode of all deployed contracts, by contract ID
+    pub contracts: Vec<([u8; 32], Vec<u8>)>,
+    /// Key/value state of all contracts, including the circuit registry
+    pub contract_state: Vec<StateWrite>,
+    /// Validators participating in the consensus
+    pub participants: BTreeMap<Address, Participant>,
+}
+
+impl StateSnapshot {
+    /// Write the snapshot to the given file, followed by a BLAKE3 checksum
+    /// of its contents.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let mut bytes = SNAPSHOT_MAGIC_BYTES.to_vec();
+        bytes.extend(serialize(self));
+        let checksum = blake3::hash(&bytes);
+        bytes.extend(checksum.as_bytes());
+
+        fs::write(path, &bytes)?;
+        info!("Exported state snapshot of slot {} to {:?}", self.block.header.slot, path);
+        Ok(())
+    }
+
+    /// Read a snapshot from the given file, verifying its checksum.
+    pub fn import(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < SNAPSHOT_MAGIC_BYTES.len() + blake3::OUT_LEN ||
+            bytes[..SNAPSHOT_MAGIC_BYTES.len()] != SNAPSHOT_MAGIC_BYTES
+        {
+            return Err(Error::SnapshotInvalid)
+        }
+
+        let (contents, checksum) = bytes.split_at(bytes.len() - blake3::OUT_LEN);
+        if blake3::hash(contents).as_bytes()[..] != checksum[..] {
+            return Err(Error::SnapshotChecksumMismatch)
+        }
+
+        let snapshot: Self = deserialize(&contents[SNAPSHOT_MAGIC_BYTES.len()..])?;
+        info!("Imported state snapshot of slot {} from {:?}", snapshot.block.header.slot, path);
+        Ok(snapshot)
+    }
+}
@@ -1,8 +1,7 @@
 // TODO: Use sets instead of vectors where possible.
 use std::{
-    collections::{hash_map::DefaultHasher, BTreeMap},
-    hash::{Hash, Hasher},
-    time::Duration,
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
 };
 
 use async_std::sync::{Arc, Mutex, RwLock};
@@ -13,29 +12,42 @@ use log::{debug, error, info, warn};
 use rand::rngs::OsRng;
 
 use super::{
-    Block, BlockInfo, BlockProposal, Header, Metadata, Participant, ProposalChain,
-    StreamletMetadata, Vote,
+    envelope::{ConsensusMessage, SignedMessage},
+    evidence::{slashing_id, SignedHeader},
+    fee::{
+        block_min_fee_rate, estimate_fee_rate, txs_size, FEE_ESTIMATION_SLOTS, MAX_BLOCK_TXS_SIZE,
+    },
+    fork::{common_prefix_len, fork_choice},
+    participant::RejoinRecord,
+    Block, BlockInfo, BlockProposal, EpochManager, EquivocationEvidence, Header, Metadata,
+    Participant, ProposalChain, Rejoin, ReorgEvent, Slashing, StateSnapshot, StreamletMetadata,
+    Vote,
 };
 use crate::{
     blockchain::Blockchain,
     crypto::{
         address::Address,
+        coin::Coin,
         constants::MERKLE_DEPTH,
         keypair::{PublicKey, SecretKey},
         merkle_node::MerkleNode,
-        schnorr::{SchnorrPublic, SchnorrSecret},
+        schnorr::{batch_verify, SchnorrPublic, SchnorrSecret, Signature},
     },
     net,
     node::{
-        state::{state_transition, ProgramState, StateUpdate},
-        Client, MemoryState, State,
+        mempool::DEFAULT_MEMPOOL_SIZE,
+        state::{state_transition, state_transition_verified, ProgramState, StateUpdate},
+        CircuitRegistry, CircuitTable, Client, Dispatcher, MemoryState, Mempool, State,
+        TokenIssuance, TokenRegistry,
     },
-    tx::Transaction,
+    swap::htlc::{htlc_contract_id, HtlcContract},
+    system::{Subscriber, SubscriberPtr, Subscription},
+    tx::{verify_transactions, Transaction},
     util::{
         serial::{serialize, Encodable, SerialDecodable, SerialEncodable},
         time::Timestamp,
     },
-    Result,
+    Error, Result, VerifyFailed,
 };
 
 /// `2 * DELTA` represents slot time
@@ -44,6 +56,25 @@ pub const DELTA: u64 = 20;
 pub const EPOCH_SLOTS: u64 = 10;
 /// Quarantine duration, in slots
 pub const QUARANTINE_DURATION: u64 = 5;
+/// Number of consecutive slots a removed participant has to prove
+/// liveness for, before re-entering the participant set
+pub const REJOIN_LIVENESS_SLOTS: u64 = 3;
+/// Number of slots between Merkle tree checkpoints
+pub const TREE_CHECKPOINT_INTERVAL: u64 = 100;
+/// Number of slots a signed consensus message is accepted for, before and
+/// after the slot it was sent in
+pub const MESSAGE_SLOT_TOLERANCE: u64 = 1;
+/// Number of slots the leader proposal headers are kept for, to catch
+/// leaders equivocating
+pub const EQUIVOCATION_WINDOW: u64 = 10;
+
+/// Coin of the canonical state to build a Merkle inclusion proof for
+pub enum MerkleLeaf {
+    /// Coin at the given leaf position of the tree
+    Position(incrementalmerkletree::Position),
+    /// Coin with the given commitment
+    Coin(Coin),
+}
 
 /// This struct represents the information required by the consensus algorithm
 #[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
@@ -63,6 +94,8 @@ pub struct ConsensusState {
     pub pending_participants: Vec<Participant>,
     /// Last slot participants where refreshed
     pub refreshed: u64,
+    /// Participants snapshot of the current epoch, used for leader election
+    pub epoch: EpochManager,
 }
 
 impl ConsensusState {
@@ -78,8 +111,40 @@ impl ConsensusState {
             participants: BTreeMap::new(),
             pending_participants: vec![],
             refreshed: 0,
+            epoch: EpochManager::default(),
         })
     }
+
+    /// Verify the signatures of all votes held, against the participant
+    /// keys, in a single batch. Votes from unknown participants fail it.
+    pub fn verify_votes(&self) -> bool {
+        let votes = self
+            .proposals
+            .iter()
+            .flat_map(|chain| chain.proposals.iter())
+            .flat_map(|proposal| proposal.block.sm.votes.iter())
+            .chain(self.orphan_votes.iter());
+
+        let mut keys = vec![];
+        let mut messages = vec![];
+        let mut signatures = vec![];
+        for vote in votes {
+            match self.participants.get(&vote.address) {
+                Some(participant) => keys.push(participant.public_key),
+                None => return false,
+            }
+            messages.push(serialize(&vote.proposal));
+            signatures.push(&vote.vote);
+        }
+
+        let batch: Vec<(&PublicKey, &[u8], &Signature)> = keys
+            .iter()
+            .zip(&messages)
+            .zip(signatures)
+            .map(|((key, message), signature)| (key, &message[..], signature))
+            .collect();
+        batch_verify(&batch)
+    }
 }
 
 /// Auxiliary structure used for consensus syncing.
@@ -128,9 +193,29 @@ pub struct ValidatorState {
     /// Client providing wallet access
     pub client: Arc<Client>,
     /// Pending transactions
-    pub unconfirmed_txs: Vec<Transaction>,
+    pub mempool: Mempool,
+    /// Transactions waiting for unknown Merkle roots
+    pub orphans: OrphanPool,
+    /// Block hashes of the notarized fork chain picked by the fork-choice rule
+    pub tip: Vec<blake3::Hash>,
+    /// State after applying a fork chain on top of the canonical blockchain,
+    /// by hash of the chain's last block
+    pub fork_states: HashMap<blake3::Hash, MemoryState>,
+    /// Reorg events subscriber
+    pub reorg_subscriber: SubscriberPtr<ReorgEvent>,
+    /// Liveness records of removed participants trying to rejoin
+    pub rejoining: BTreeMap<Address, RejoinRecord>,
+    /// Hashes of signed consensus messages received, by slot
+    pub seen_messages: BTreeMap<u64, HashSet<blake3::Hash>>,
+    /// First leader proposal header received, by slot
+    pub seen_headers: BTreeMap<u64, SignedHeader>,
+    /// Equivocation evidence observed by the node, waiting to be submitted
+    pub evidence: Vec<EquivocationEvidence>,
     /// Participating start slot
     pub participating: Option<u64>,
+    /// Slot the node is pinned to instead of the wall clock one, used by
+    /// simulations to advance slots at will
+    pub virtual_slot: Option<u64>,
 }
 
 impl ValidatorState {
@@ -146,18 +231,38 @@ impl ValidatorState {
         let public = PublicKey::from_secret(secret);
         let consensus = ConsensusState::new(genesis_ts, genesis_data)?;
         let blockchain = Blockchain::new(db, genesis_ts, genesis_data)?;
-        let unconfirmed_txs = vec![];
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_SIZE);
+        let orphans = OrphanPool::new(DEFAULT_ORPHAN_WINDOW, DEFAULT_ORPHAN_COUNT);
         let participating = None;
 
         let address = client.wallet.get_default_address().await?;
+        let tree = Self::load_tree(&blockchain, &client).await?;
+
+        // Cashiers mint the wrapped tokens, so they govern the registry
+        let mut dispatcher = Dispatcher::default();
+        TokenRegistry::new(cashier_pubkeys.clone()).register(&mut dispatcher);
+        TokenIssuance::new(cashier_pubkeys.clone()).register(&mut dispatcher);
+        Slashing::register(&mut dispatcher);
+        CircuitRegistry::new(cashier_pubkeys.clone()).register(&mut dispatcher);
+        dispatcher.register_native(htlc_contract_id(), Arc::new(HtlcContract));
+
+        let slot = blockchain.last()?.0;
+        let mut circuits = CircuitTable::default();
+        circuits.load(&blockchain.circuits, slot)?;
+
         let state_machine = Arc::new(Mutex::new(State {
-            tree: client.get_tree().await?,
+            tree,
             merkle_roots: blockchain.merkle_roots.clone(),
             nullifiers: blockchain.nullifiers.clone(),
+            contracts: blockchain.contracts.clone(),
+            contract_state: blockchain.contract_state.clone(),
+            dispatcher,
+            circuits,
             cashier_pubkeys,
             faucet_pubkeys,
             mint_vk: Lazy::new(),
             burn_vk: Lazy::new(),
+            slot,
         }));
 
         // Create zk proof verification keys
@@ -172,16 +277,99 @@ impl ValidatorState {
             blockchain,
             state_machine,
             client,
-            unconfirmed_txs,
+            mempool,
+            orphans,
+            tip: vec![],
+            fork_states: HashMap::new(),
+            reorg_subscriber: Subscriber::new(),
+            rejoining: BTreeMap::new(),
+            seen_messages: BTreeMap::new(),
+            seen_headers: BTreeMap::new(),
+            evidence: vec![],
             participating,
+            virtual_slot: None,
         }));
 
         Ok(state)
     }
 
+    /// Load the coins Merkle tree from its last checkpoint, and replay the
+    /// blocks finalized after it. Without any checkpoint, the tree kept in
+    /// the wallet is considered up to date and checkpointed.
+    async fn load_tree(
+        blockchain: &Blockchain,
+        client: &Client,
+    ) -> Result<BridgeTree<MerkleNode, MERKLE_DEPTH>> {
+        let (last, _) = blockchain.last()?;
+        let (slot, tree) = match blockchain.merkle_trees.get_last()? {
+            Some(v) => v,
+            None => {
+                info!("consensus: No Merkle tree checkpoint found, using wallet tree");
+                let tree = client.get_tree().await?;
+                blockchain.merkle_trees.insert(last, &tree)?;
+                return Ok(tree)
+            }
+        };
+
+        if slot >= last {
+            return Ok(tree)
+        }
+
+        info!("consensus: Replaying blocks {}..={} onto Merkle tree checkpoint", slot + 1, last);
+        let secret_keys = client.get_scanning_secrets().await?;
+        let mut state = State {
+            tree,
+            merkle_roots: blockchain.merkle_roots.clone(),
+            nullifiers: blockchain.nullifiers.clone(),
+            contracts: blockchain.contracts.clone(),
+            contract_state: blockchain.contract_state.clone(),
+            dispatcher: Dispatcher::default(),
+            circuits: CircuitTable::default(),
+            cashier_pubkeys: vec![],
+            faucet_pubkeys: vec![],
+            mint_vk: Lazy::new(),
+            burn_vk: Lazy::new(),
+            slot: last,
+        };
+        for block in blockchain.get_blocks_by_slot_range(slot + 1, last + 1)? {
+            for tx in &block.txs {
+                state.replay_tx(tx, &secret_keys);
+            }
+        }
+
+        blockchain.merkle_trees.insert(last, &state.tree)?;
+        Ok(state.tree)
+    }
+
+    /// Checkpoint the coins Merkle tree, if [`TREE_CHECKPOINT_INTERVAL`]
+    /// slots have passed since the last checkpoint.
+    pub async fn checkpoint_tree(&self) -> Result<()> {
+        let (last, _) = self.blockchain.last()?;
+        if let Some(slot) = self.blockchain.merkle_trees.last_slot()? {
+            if last < slot + TREE_CHECKPOINT_INTERVAL {
+                return Ok(())
+            }
+        }
+
+        debug!("checkpoint_tree(): Checkpointing Merkle tree at slot {}", last);
+        let state = self.state_machine.lock().await;
+        self.blockchain.merkle_trees.insert(last, &state.tree)
+    }
+
     /// The node retrieves a transaction, validates its state transition,
-    /// and appends it to the unconfirmed transactions list.
+    /// and admits it to the mempool. Transactions referencing unknown Merkle
+    /// roots are held in the orphan pool instead.
     pub async fn append_tx(&mut self, tx: Transaction) -> bool {
+        self.admit_tx(tx, false).await
+    }
+
+    /// Admit a transaction created by this node, so it gets rebroadcast
+    /// until it is included in a block.
+    pub async fn append_local_tx(&mut self, tx: Transaction) -> bool {
+        self.admit_tx(tx, true).await
+    }
+
+    async fn admit_tx(&mut self, tx: Transaction, local: bool) -> bool {
         let tx_hash = blake3::hash(&serialize(&tx));
         let tx_in_txstore = match self.blockchain.transactions.contains(&tx_hash) {
             Ok(v) => v,
@@ -191,7 +379,7 @@ impl ValidatorState {
             }
         };
 
-        if self.unconfirmed_txs.contains(&tx) || tx_in_txstore {
+        if self.mempool.contains(&tx_hash) || self.orphans.contains(&tx_hash) || tx_in_txstore {
             debug!("append_tx(): We have already seen this tx.");
             return false
         }
@@ -199,19 +387,77 @@ impl ValidatorState {
         debug!("append_tx(): Starting state transition validation");
         let canon_state_clone = self.state_machine.lock().await.clone();
         let mem_state = MemoryState::new(canon_state_clone);
-        match self.validate_state_transitions(mem_state, &[tx.clone()]) {
+        let slot = self.current_slot();
+        match self.validate_state_transitions(mem_state, &[tx.clone()], slot) {
             Ok(_) => debug!("append_tx(): State transition valid"),
+            Err(Error::VerifyFailed(VerifyFailed::InvalidMerkle(_))) => {
+                debug!("append_tx(): Unknown Merkle root, holding tx as orphan");
+                self.orphans.insert(tx, Instant::now());
+                return false
+            }
             Err(e) => {
                 warn!("append_tx(): State transition fail: {}", e);
                 return false
             }
         }
 
-        debug!("append_tx(): Appended tx to mempool");
-        self.unconfirmed_txs.push(tx);
+        let fee = tx.fee;
+        let result =
+            if local { self.mempool.insert_local(tx, fee) } else { self.mempool.insert(tx, fee) };
+        match result {
+            Ok(evicted) => {
+                debug!("append_tx(): Appended tx to mempool, evicted {} txs", evicted.len())
+            }
+            Err(e) => {
+                warn!("append_tx(): Mempool admission fail: {}", e);
+                return false
+            }
+        }
         true
     }
 
+    /// Validate the orphan transactions again against the current canonical
+    /// state, moving the ones that became valid to the mempool. Orphans held
+    /// for longer than the orphan window are dropped.
+    pub async fn process_orphans(&mut self) {
+        self.orphans.expire();
+        if self.orphans.is_empty() {
+            return
+        }
+
+        let canon_state_clone = self.state_machine.lock().await.clone();
+        let slot = self.current_slot();
+        for (tx, received) in self.orphans.drain() {
+            let mem_state = MemoryState::new(canon_state_clone.clone());
+            let fee = tx.fee;
+            match self.validate_state_transitions(mem_state, &[tx.clone()], slot) {
+                Ok(_) => match self.mempool.insert(tx, fee) {
+                    Ok(_) => debug!("process_orphans(): Orphan tx appended to mempool"),
+                    Err(e) => debug!("process_orphans(): Mempool admission fail: {}", e),
+                },
+                Err(Error::VerifyFailed(VerifyFailed::InvalidMerkle(_))) => {
+                    self.orphans.insert(tx, received);
+                }
+                Err(e) => debug!("process_orphans(): Dropping invalid orphan tx: {}", e),
+            }
+        }
+    }
+
+    /// Suggest a fee rate, in fee per kilobyte, for a transaction to get
+    /// included within `target_slots` slots, based on the fee rates of the
+    /// transactions included in the last [`FEE_ESTIMATION_SLOTS`] slots.
+    pub fn estimate_fee(&self, target_slots: u64) -> Result<u64> {
+        let (last_slot, _) = self.blockchain.last()?;
+        let start = (last_slot + 1).saturating_sub(FEE_ESTIMATION_SLOTS);
+
+        let mut min_rates = vec![None; (last_slot + 1 - start) as usize];
+        for block in self.blockchain.get_blocks_by_slot_range(start, last_slot + 1)? {
+            min_rates[(block.header.slot - start) as usize] = Some(block_min_fee_rate(&block.txs)?);
+        }
+
+        Ok(estimate_fee_rate(&min_rates, target_slots))
+    }
+
     /// Calculates the epoch of the provided slot.
     /// Epoch duration is configured using the `EPOCH_SLOTS` value.
     pub fn slot_epoch(&self, slot: u64) -> u64 {
@@ -221,6 +467,9 @@ impl ValidatorState {
     /// Calculates current slot, based on elapsed time from the genesis block.
     /// Slot duration is configured using the `DELTA` value.
     pub fn current_slot(&self) -> u64 {
+        if let Some(slot) = self.virtual_slot {
+            return slot
+        }
         self.consensus.genesis_ts.elapsed() / (2 * DELTA)
     }
 
@@ -265,19 +514,16 @@ impl ValidatorState {
     }
 
     /// Find slot leader, using a simple hash method.
-    /// Leader calculation is based on how many nodes are participating
-    /// in the network.
+    /// Leader calculation is based on the participants snapshot of the
+    /// current epoch, falling back to the live participants if no snapshot
+    /// was taken yet.
     pub fn slot_leader(&mut self) -> Participant {
         let slot = self.current_slot();
-        // DefaultHasher is used to hash the slot number
-        // because it produces a number string which then can be modulated by the len.
-        // blake3 produces alphanumeric
-        let mut hasher = DefaultHasher::new();
-        slot.hash(&mut hasher);
-        let pos = hasher.finish() % (self.consensus.participants.len() as u64);
-        // Since BTreeMap orders by key in asceding order, each node will have
-        // the same key in calculated position.
-        self.consensus.participants.iter().nth(pos as usize).unwrap().1.clone()
+        if self.consensus.epoch.snapshot.is_empty() {
+            let epoch = self.slot_epoch(slot);
+            self.consensus.epoch.update(epoch, &self.consensus.participants);
+        }
+        self.consensus.epoch.slot_leader(slot).unwrap().clone()
     }
 
     /// Check if we're the current slot leader
@@ -294,15 +540,21 @@ impl ValidatorState {
         let (prev_hash, index) = self.longest_notarized_chain_last_hash().unwrap();
         let unproposed_txs = self.unproposed_txs(index);
 
-        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100);
-        for tx in &unproposed_txs {
-            for output in &tx.outputs {
-                tree.append(&MerkleNode::from_coin(&output.revealed.coin));
-                tree.witness();
+        // Transactions come in fee rate order, so the best paying ones
+        // fill the block first.
+        let mut block_size = 0;
+        let mut block_txs = vec![];
+        for tx in unproposed_txs {
+            let size = tx.encode(&mut std::io::sink())?;
+            if block_size + size > MAX_BLOCK_TXS_SIZE {
+                continue
             }
+            block_size += size;
+            block_txs.push(tx);
         }
-        let root = tree.root(0).unwrap();
+        let unproposed_txs = block_txs;
 
+        let root = Header::txs_root(&unproposed_txs);
         let header =
             Header::new(prev_hash, self.slot_epoch(slot), slot, Timestamp::current_time(), root);
 
@@ -319,7 +571,7 @@ impl ValidatorState {
     /// Retrieve all unconfirmed transactions not proposed in previous blocks
     /// of provided index chain.
     pub fn unproposed_txs(&self, index: i64) -> Vec<Transaction> {
-        let mut unproposed_txs = self.unconfirmed_txs.clone();
+        let mut unproposed_txs: Vec<Transaction> = self.mempool.iter().cloned().collect();
 
         // If index is -1 (canonical blockchain) a new fork will be generated,
         // therefore all unproposed transactions can be included in the proposal.
@@ -344,26 +596,100 @@ impl ValidatorState {
     /// Finds the longest fully notarized blockchain the node holds and
     /// returns the last block hash and the chain index.
     pub fn longest_notarized_chain_last_hash(&self) -> Result<(blake3::Hash, i64)> {
-        let mut longest_notarized_chain: Option<ProposalChain> = None;
-        let mut length = 0;
-        let mut index = -1;
-
-        if !self.consensus.proposals.is_empty() {
-            for (i, chain) in self.consensus.proposals.iter().enumerate() {
-                if chain.notarized() && chain.proposals.len() > length {
-                    longest_notarized_chain = Some(chain.clone());
-                    length = chain.proposals.len();
-                    index = i as i64;
-                }
+        match fork_choice(&self.consensus.proposals) {
+            Some(index) => {
+                let chain = &self.consensus.proposals[index];
+                Ok((chain.proposals.last().unwrap().block.header.headerhash(), index as i64))
+            }
+            None => Ok((self.blockchain.last()?.1, -1)),
+        }
+    }
+
+    /// Subscribe to reorg events, emitted whenever the node switches its
+    /// tip to a competing fork chain.
+    pub async fn subscribe_reorgs(&self) -> Subscription<ReorgEvent> {
+        self.reorg_subscriber.clone().subscribe().await
+    }
+
+    /// Apply the fork-choice rule and switch the tip to the best notarized
+    /// fork chain. The new chain's state is built on top of the cached state
+    /// of its longest known prefix, so usually only the newly notarized block
+    /// is applied. If the previous tip is not a prefix of the new one, a
+    /// [`ReorgEvent`] is emitted to subscribers.
+    pub async fn update_tip(&mut self) -> Result<()> {
+        let chain = match fork_choice(&self.consensus.proposals) {
+            Some(index) => self.consensus.proposals[index].proposals.clone(),
+            None => vec![],
+        };
+        let new_tip: Vec<blake3::Hash> =
+            chain.iter().map(|p| p.block.header.headerhash()).collect();
+
+        // Blocks finalized since the last update are part of the canonical
+        // blockchain now, and no longer of the tip.
+        let mut old_tip = vec![];
+        for hash in &self.tip {
+            if !self.blockchain.headers.contains(hash)? {
+                old_tip.push(*hash);
             }
         }
 
-        let hash = match longest_notarized_chain {
-            Some(chain) => chain.proposals.last().unwrap().block.header.headerhash(),
-            None => self.blockchain.last()?.1,
+        if new_tip == old_tip {
+            self.tip = new_tip;
+            return Ok(())
+        }
+
+        // A block hash commits to its whole chain, so the state cached for
+        // any of the new tip's blocks is the state of that prefix.
+        let cached = new_tip
+            .iter()
+            .rposition(|hash| self.fork_states.contains_key(hash))
+            .map(|i| (i + 1, self.fork_states[&new_tip[i]].clone()));
+        let (applied, mut mem_state) = match cached {
+            Some(v) => v,
+            None => (0, MemoryState::new(self.state_machine.lock().await.clone())),
         };
 
-        Ok((hash, index))
+        debug!("update_tip(): Applying {} blocks on top of cached state", chain.len() - applied);
+        for proposal in &chain[applied..] {
+            let slot = proposal.block.header.slot;
+            for update in
+                self.validate_state_transitions(mem_state.clone(), &proposal.block.txs, slot)?
+            {
+                mem_state.apply(update);
+            }
+        }
+
+        let common = common_prefix_len(&old_tip, &new_tip);
+        if common < old_tip.len() {
+            let canonical = self.blockchain.last()?.1;
+            let event = ReorgEvent {
+                old_tip: *old_tip.last().unwrap(),
+                new_tip: new_tip.last().copied().unwrap_or(canonical),
+                rolled_back: old_tip[common..].to_vec(),
+                applied: new_tip[common..].to_vec(),
+            };
+            info!(
+                "consensus: Reorg of depth {} from {} to {}",
+                event.depth(),
+                event.old_tip,
+                event.new_tip
+            );
+            self.reorg_subscriber.notify(event).await;
+        }
+
+        if let Some(tip) = new_tip.last() {
+            self.fork_states.insert(*tip, mem_state);
+        }
+        self.tip = new_tip;
+
+        // Forget the states of chains that were dropped or finalized
+        let proposals = &self.consensus.proposals;
+        self.fork_states.retain(|hash, _| {
+            proposals
+                .iter()
+                .any(|c| c.proposals.iter().any(|p| p.block.header.headerhash() == *hash))
+        });
+        Ok(())
     }
 
     /// Receive the proposed block, verify its sender (slot leader),
@@ -399,9 +725,45 @@ impl ValidatorState {
             return Ok(None)
         }
 
+        if self.detect_equivocation(&leader, proposal) {
+            return Ok(None)
+        }
+
+        if txs_size(&proposal.block.txs)? > MAX_BLOCK_TXS_SIZE {
+            warn!("Proposal from ({}) exceeds the block size", proposal.block.metadata.address);
+            return Ok(None)
+        }
+
         self.vote(proposal).await
     }
 
+    /// Check if the leader already signed a different proposal for the slot
+    /// of the given one. If so, equivocation evidence is kept so it can be
+    /// submitted on chain, and the proposal must not be voted on.
+    fn detect_equivocation(&mut self, leader: &Participant, proposal: &BlockProposal) -> bool {
+        let oldest = self.current_slot().saturating_sub(EQUIVOCATION_WINDOW);
+        self.seen_headers = self.seen_headers.split_off(&oldest);
+
+        let signed = SignedHeader::from(proposal);
+        let seen = match self.seen_headers.get(&signed.header.slot) {
+            Some(seen) => seen.clone(),
+            None => {
+                self.seen_headers.insert(signed.header.slot, signed);
+                return false
+            }
+        };
+
+        if seen.header == signed.header {
+            return false
+        }
+
+        warn!("consensus: Leader ({}) equivocated in slot {}", leader.address, signed.header.slot);
+        if !self.evidence.iter().any(|e| e.offender() == leader.address) {
+            self.evidence.push(EquivocationEvidence::new(leader.public_key, seen, signed));
+        }
+        true
+    }
+
     /// Given a proposal, the node finds which blockchain it extends.
     /// If the proposal extends the canonical blockchain, a new fork chain
     /// is created. The node votes on the proposal only if it extends the
@@ -449,10 +811,17 @@ impl ValidatorState {
         }
 
         debug!("vote(): Starting state transition validation");
-        let canon_state_clone = self.state_machine.lock().await.clone();
-        let mem_state = MemoryState::new(canon_state_clone);
+        // Proposals extending a known fork are validated on top of its state
+        let mem_state = match self.fork_states.get(&proposal.block.header.state) {
+            Some(fork_state) => fork_state.clone(),
+            None => MemoryState::new(self.state_machine.lock().await.clone()),
+        };
 
-        match self.validate_state_transitions(mem_state, &proposal.block.txs) {
+        match self.validate_state_transitions(
+            mem_state,
+            &proposal.block.txs,
+            proposal.block.header.slot,
+        ) {
             Ok(_) => {
                 debug!("vote(): State transition valid")
             }
@@ -536,6 +905,24 @@ impl ValidatorState {
     /// Finally, we check if the notarization of the proposal can finalize
     /// parent proposals in its chain.
     pub async fn receive_vote(&mut self, vote: &Vote) -> Result<(bool, Option<Vec<BlockInfo>>)> {
+        self.refresh_participants()?;
+        if let Some(participant) = self.consensus.participants.get(&vote.address) {
+            if !participant.public_key.verify(&serialize(&vote.proposal), &vote.vote) {
+                warn!("consensus: Voter ({}), signature couldn't be verified", vote.address);
+                return Ok((false, None))
+            }
+        }
+
+        self.receive_verified_vote(vote).await
+    }
+
+    /// Receive a vote whose signature was already verified, like the ones
+    /// of envelopes checked with [`Self::verify_message`], which batches it
+    /// with the envelope signature.
+    pub async fn receive_verified_vote(
+        &mut self,
+        vote: &Vote,
+    ) -> Result<(bool, Option<Vec<BlockInfo>>)> {
         let current_slot = self.current_slot();
         // Node hasn't started participating
         match self.participating {
@@ -561,18 +948,6 @@ impl ValidatorState {
                     return Ok((false, None))
                 }
 
-                let mut encoded_proposal = vec![];
-
-                if let Err(e) = vote.proposal.encode(&mut encoded_proposal) {
-                    error!("consensus: Proposal encoding failed: {:?}", e);
-                    return Ok((false, None))
-                };
-
-                if !participant.public_key.verify(&encoded_proposal, &vote.vote) {
-                    warn!("consensus: Voter ({}), signature couldn't be verified", va);
-                    return Ok((false, None))
-                }
-
                 // Updating participant vote
                 match participant.voted {
                     Some(voted) => {
@@ -632,6 +1007,10 @@ impl ValidatorState {
                     return Err(e)
                 }
             }
+
+            if let Err(e) = self.update_tip().await {
+                error!("consensus: Updating tip failed: {}", e);
+            }
         }
 
         Ok((true, Some(to_broadcast)))
@@ -654,14 +1033,10 @@ impl ValidatorState {
         Ok(None)
     }
 
-    /// Remove provided transactions vector from unconfirmed_txs if they exist.
+    /// Remove provided transactions vector from the mempool if they exist,
+    /// along with any mempool transactions they conflict with.
     pub fn remove_txs(&mut self, transactions: Vec<Transaction>) -> Result<()> {
-        for tx in transactions {
-            if let Some(pos) = self.unconfirmed_txs.iter().position(|txs| *txs == tx) {
-                self.unconfirmed_txs.remove(pos);
-            }
-        }
-
+        self.mempool.remove_txs(&transactions);
         Ok(())
     }
 
@@ -724,10 +1099,13 @@ impl ValidatorState {
             debug!(target: "consensus", "Applying state transition for finalized block");
             let canon_state_clone = self.state_machine.lock().await.clone();
             let mem_st = MemoryState::new(canon_state_clone);
-            let state_updates = self.validate_state_transitions(mem_st, &proposal.txs)?;
-            self.update_canon_state(state_updates, None).await?;
+            let state_updates =
+                self.validate_state_transitions(mem_st, &proposal.txs, proposal.header.slot)?;
+            self.update_canon_state(state_updates, proposal.header.slot, None).await?;
             self.remove_txs(proposal.txs.clone())?;
         }
+        self.process_orphans().await;
+        self.checkpoint_tree().await?;
 
         let last_block = *blockhashes.last().unwrap();
         let last_slot = finalized.last().unwrap().header.slot;
@@ -759,9 +1137,51 @@ impl ValidatorState {
         Ok(finalized)
     }
 
+    /// Wrap a consensus message in an envelope signed for the current slot.
+    pub fn sign_message<M: ConsensusMessage>(&self, payload: M) -> SignedMessage<M> {
+        let slot = self.current_slot();
+        SignedMessage::new(&self.secret, self.public, slot, self.slot_epoch(slot), payload)
+    }
+
+    /// Verify a signed consensus message before its payload is processed.
+    /// The message must be signed by its expected sender, for a slot close
+    /// to the current one and that slot's epoch, and not have been seen before.
+    pub fn verify_message<M: ConsensusMessage>(&mut self, msg: &SignedMessage<M>) -> bool {
+        let current = self.current_slot();
+        if msg.slot + MESSAGE_SLOT_TOLERANCE < current ||
+            msg.slot > current + MESSAGE_SLOT_TOLERANCE
+        {
+            warn!("consensus: {} for slot {} received in slot {}", M::name(), msg.slot, current);
+            return false
+        }
+
+        if msg.epoch != self.slot_epoch(msg.slot) {
+            warn!("consensus: {} epoch {} doesn't match slot {}", M::name(), msg.epoch, msg.slot);
+            return false
+        }
+
+        if msg.payload.signer(self) != Some(msg.public_key) || !msg.verify_signature() {
+            warn!("consensus: {} signature could not be verified", M::name());
+            return false
+        }
+
+        // Forget messages too old to be accepted anymore
+        let oldest = current.saturating_sub(MESSAGE_SLOT_TOLERANCE);
+        self.seen_messages = self.seen_messages.split_off(&oldest);
+
+        if !self.seen_messages.entry(msg.slot).or_default().insert(msg.hash()) {
+            debug!("consensus: Replayed {} for slot {}", M::name(), msg.slot);
+            return false
+        }
+
+        true
+    }
+
     /// Append a new participant to the pending participants list.
     pub fn append_participant(&mut self, participant: Participant) -> bool {
-        if self.consensus.pending_participants.contains(&participant) {
+        if self.consensus.pending_participants.contains(&participant) ||
+            self.is_slashed(&participant.address)
+        {
             return false
         }
 
@@ -769,6 +1189,70 @@ impl ValidatorState {
         true
     }
 
+    /// Check if the given node got slashed in the canonical state.
+    pub fn is_slashed(&self, address: &Address) -> bool {
+        match self.blockchain.contract_state.get(&slashing_id(), &address.0) {
+            Ok(v) => v.is_some(),
+            Err(e) => {
+                error!("consensus: Failed reading slashing state: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Check if the node is in the participant set, or about to enter it.
+    pub fn is_participant(&self) -> bool {
+        self.consensus.participants.contains_key(&self.address) ||
+            self.consensus.pending_participants.iter().any(|p| p.address == self.address)
+    }
+
+    /// Record a rejoin message sent for the given slot. Once the node has
+    /// proved liveness for [`REJOIN_LIVENESS_SLOTS`] consecutive slots, it
+    /// is appended to the pending participants list. Returns `false` if the
+    /// message brought nothing new and shouldn't be relayed.
+    pub fn receive_rejoin(&mut self, rejoin: &Rejoin, slot: u64) -> bool {
+        if self.consensus.participants.contains_key(&rejoin.address) ||
+            self.consensus.pending_participants.iter().any(|p| p.address == rejoin.address)
+        {
+            debug!("receive_rejoin(): {} is already a participant", rejoin.address);
+            return false
+        }
+
+        if self.is_slashed(&rejoin.address) {
+            debug!("receive_rejoin(): {} is slashed", rejoin.address);
+            return false
+        }
+
+        let streak = match self.rejoining.get_mut(&rejoin.address) {
+            Some(record) if record.public_key == rejoin.public_key && slot <= record.last_slot => {
+                // Already accounted for
+                return false
+            }
+            Some(record)
+                if record.public_key == rejoin.public_key && slot == record.last_slot + 1 =>
+            {
+                record.last_slot = slot;
+                record.streak()
+            }
+            _ => {
+                // New node or broken streak, start over
+                self.rejoining.insert(rejoin.address, RejoinRecord::new(rejoin.public_key, slot));
+                1
+            }
+        };
+
+        if streak < REJOIN_LIVENESS_SLOTS {
+            debug!("receive_rejoin(): {} proved liveness for {} slots", rejoin.address, streak);
+            return true
+        }
+
+        info!("consensus: Participant {} rejoined after proving liveness", rejoin.address);
+        self.rejoining.remove(&rejoin.address);
+        let participant = Participant::new(rejoin.public_key, rejoin.address, self.current_slot());
+        self.append_participant(participant);
+        true
+    }
+
     /// Refresh the participants map, to retain only the active ones.
     /// Active nodes are considered those that joined previous slot
     /// or on the slot the last proposal was generated, either voted
@@ -797,6 +1281,24 @@ impl ValidatorState {
 
         self.consensus.pending_participants = vec![];
 
+        // Slashed participants are evicted for good, and their evidence
+        // doesn't need submitting anymore
+        let slashed: Vec<Address> =
+            self.consensus.participants.keys().filter(|a| self.is_slashed(a)).copied().collect();
+        for address in slashed {
+            warn!("refresh_participants(): Removing slashed participant: {:?}", address);
+            self.consensus.participants.remove(&address);
+        }
+        let evidence = std::mem::take(&mut self.evidence);
+        self.evidence = evidence.into_iter().filter(|e| !self.is_slashed(&e.offender())).collect();
+
+        // Participants distribution is frozen for leader election at epoch start
+        let epoch = self.slot_epoch(current);
+        self.consensus.epoch.update(epoch, &self.consensus.participants);
+
+        // Rejoin records decay once their node stops proving liveness
+        self.rejoining.retain(|_, record| record.last_slot + QUARANTINE_DURATION >= current);
+
         let mut inactive = Vec::new();
         let mut last_slot = self.last_slot()?;
 
@@ -907,9 +1409,12 @@ impl ValidatorState {
             participants: BTreeMap::new(),
             pending_participants: vec![],
             refreshed: 0,
+            epoch: EpochManager::default(),
         };
 
         self.consensus = consensus;
+        self.tip = vec![];
+        self.fork_states.clear();
         Ok(())
     }
 
@@ -926,7 +1431,7 @@ impl ValidatorState {
         let mut mem_state = MemoryState::new(canon_state_clone);
         for block in blocks {
             let mut state_updates =
-                self.validate_state_transitions(mem_state.clone(), &block.txs)?;
+                self.validate_state_transitions(mem_state.clone(), &block.txs, block.header.slot)?;
 
             for update in &state_updates {
                 mem_state.apply(update.clone());
@@ -937,11 +1442,16 @@ impl ValidatorState {
         debug!("receive_blocks(): All state transitions passed");
 
         debug!("receive_blocks(): Updating canon state");
-        self.update_canon_state(canon_updates, None).await?;
+        let last_slot = blocks.iter().map(|block| block.header.slot).max().unwrap_or_default();
+        self.update_canon_state(canon_updates, last_slot, None).await?;
 
         debug!("receive_blocks(): Appending blocks to ledger");
         self.blockchain.add(blocks)?;
 
+        debug!("receive_blocks(): Processing orphan transactions");
+        self.process_orphans().await;
+        self.checkpoint_tree().await?;
+
         Ok(())
     }
 
@@ -961,10 +1471,19 @@ impl ValidatorState {
             }
         };
 
+        // Blocks up to the last checkpoint are final, and only come in through block sync
+        if self.blockchain.checkpoints.covers(block.header.slot) {
+            warn!(
+                "receive_finalized_block(): Block in slot {} is behind the last checkpoint",
+                block.header.slot
+            );
+            return Err(Error::ReorgPastCheckpoint(block.header.slot))
+        }
+
         debug!("receive_finalized_block(): Executing state transitions");
         self.receive_blocks(&[block.clone()]).await?;
 
-        debug!("receive_finalized_block(): Removing block transactions from unconfirmed_txs");
+        debug!("receive_finalized_block(): Removing block transactions from mempool");
         self.remove_txs(block.txs.clone())?;
 
         Ok(true)
@@ -995,24 +1514,60 @@ impl ValidatorState {
             return Ok(())
         }
 
+        // Headers up to the last checkpoint are validated against it before
+        // executing any state transitions, so a peer serving a fork is caught early.
+        let checkpointed: Vec<Header> = new_blocks
+            .iter()
+            .filter(|block| self.blockchain.checkpoints.covers(block.header.slot))
+            .map(|block| block.header.clone())
+            .collect();
+        if !checkpointed.is_empty() {
+            debug!("receive_sync_blocks(): Validating {} checkpointed headers", checkpointed.len());
+            self.blockchain.checkpoints.verify_headers(self.blockchain.last()?, &checkpointed)?;
+        }
+
         debug!("receive_sync_blocks(): Executing state transitions");
         self.receive_blocks(&new_blocks[..]).await?;
 
         Ok(())
     }
 
-    /// Validate state transitions for given transactions and state and
-    /// return a vector of [`StateUpdate`]
+    /// Validate state transitions for given transactions and state at the
+    /// given slot, and return a vector of [`StateUpdate`]
     pub fn validate_state_transitions(
         &self,
         state: MemoryState,
         txs: &[Transaction],
+        slot: u64,
     ) -> Result<Vec<StateUpdate>> {
         let mut ret = vec![];
         let mut st = state;
+        st.slot = slot;
+
+        // Proofs don't depend on the state, so the ones of a block's worth of
+        // transactions are verified all at once, before running the state
+        // transitions in order. Single transactions go through the usual
+        // path, so the cheap state checks run before the proofs.
+        let verified = txs.len() > 1;
+        if verified {
+            debug!("validate_state_transitions(): Verifying {} transactions", txs.len());
+            let results = verify_transactions(txs, st.mint_vk(), st.burn_vk());
+            for (i, result) in results.into_iter().enumerate() {
+                if let Err(e) = result {
+                    warn!("validate_state_transition(): Failed verifying tx {}: {}", i, e);
+                    return Err(VerifyFailed::ProofVerifyFailed(e.to_string()).into())
+                }
+            }
+        }
 
         for (i, tx) in txs.iter().enumerate() {
-            let update = match state_transition(&st, tx.clone()) {
+            let result = if verified {
+                state_transition_verified(&st, tx.clone())
+            } else {
+                state_transition(&st, tx.clone())
+            };
+
+            let update = match result {
                 Ok(v) => v,
                 Err(e) => {
                     warn!("validate_state_transition(): Failed for tx {}: {}", i, e);
@@ -1026,26 +1581,151 @@ impl ValidatorState {
         Ok(ret)
     }
 
-    /// Apply a vector of [`StateUpdate`] to the canonical state.
+    /// Apply a vector of [`StateUpdate`] to the canonical state. The
+    /// updates come from the blocks up to `slot`, which the wallet is then
+    /// scanned up to.
     pub async fn update_canon_state(
         &self,
         updates: Vec<StateUpdate>,
+        slot: u64,
         notify: Option<async_channel::Sender<(PublicKey, u64)>>,
     ) -> Result<()> {
-        let secret_keys: Vec<SecretKey> =
-            self.client.get_keypairs().await?.iter().map(|x| x.secret).collect();
+        let secret_keys = self.client.get_scanning_secrets().await?;
+        let birthday = self.client.wallet.get_birthday_slot().await?;
 
         debug!("update_canon_state(): Acquiring state machine lock");
         let mut state = self.state_machine.lock().await;
         for update in updates {
-            state
-                .apply(update, secret_keys.clone(), notify.clone(), self.client.wallet.clone())
-                .await?;
+            // Blocks before the wallet's birthday can't hold its coins
+            let keys = if update.slot < birthday { vec![] } else { secret_keys.clone() };
+            state.apply(update, keys, notify.clone(), self.client.wallet.clone()).await?;
         }
+        self.client.wallet.set_scanned_slot(slot).await?;
         drop(state);
         debug!("update_canon_state(): Dropped state machine lock");
 
         debug!("update_canon_state(): Successfully applied state updates");
         Ok(())
     }
+
+    /// Build a Merkle inclusion proof for a coin of the canonical state, so
+    /// it can be spent by a prover that doesn't hold the whole tree. Our
+    /// tree only witnesses our own coins, so the proof is built on a tree
+    /// rebuilt from the coins of all finalized blocks.
+    /// Returns the coin's leaf position, its authentication path and the root.
+    pub async fn merkle_inclusion_proof(
+        &self,
+        leaf: MerkleLeaf,
+    ) -> Result<(incrementalmerkletree::Position, Vec<MerkleNode>, MerkleNode)> {
+        // Hold the state so no block gets finalized while replaying
+        let state = self.state_machine.lock().await;
+
+        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(1);
+        let mut position = None;
+        let mut leaves = 0;
+        for block in self.blockchain.iter() {
+            for tx in block?.txs {
+                for output in tx.outputs {
+                    let coin = output.revealed.coin;
+                    tree.append(&MerkleNode::from_coin(&coin));
+
+                    let found = match &leaf {
+                        MerkleLeaf::Position(pos) => usize::from(*pos) == leaves,
+                        MerkleLeaf::Coin(c) => position.is_none() && *c == coin,
+                    };
+                    if found {
+                        position = tree.witness();
+                    }
+                    leaves += 1;
+                }
+            }
+        }
+
+        let position = match position {
+            Some(v) => v,
+            None => return Err(Error::CoinNotFound),
+        };
+
+        // Blocks bootstrapped from a snapshot or pruned can't be replayed
+        let root = tree.root(0).unwrap();
+        if root != state.tree.root(0).unwrap() {
+            return Err(Error::MerkleTreeIncomplete)
+        }
+        drop(state);
+
+        let path = tree.authentication_path(position, &root).unwrap();
+        Ok((position, path, root))
+    }
+
+    /// Take a snapshot of the canonical state at the last finalized block.
+    pub async fn snapshot(&self) -> Result<StateSnapshot> {
+        let (_, last) = self.blockchain.last()?;
+        let block = self.blockchain.get_blocks_by_hash(&[last])?.remove(0);
+
+        let state = self.state_machine.lock().await;
+        let tree = bincode::serde::encode_to_vec(&state.tree, bincode::config::legacy())?;
+        let merkle_roots = state.merkle_roots.get_all()?;
+        let nullifiers = state.nullifiers.get_all()?;
+        drop(state);
+
+        let contracts = self.blockchain.contracts.get_all()?;
+        let contract_state = self.blockchain.contract_state.get_all()?;
+        let participants = self.consensus.participants.clone();
+        Ok(StateSnapshot {
+            block,
+            tree,
+            merkle_roots,
+            nullifiers,
+            contracts,
+            contract_state,
+            participants,
+        })
+    }
+
+    /// Bootstrap the canonical state from a snapshot. The blockchain must
+    /// not contain any blocks apart from genesis, and the snapshot block
+    /// must be a trusted checkpoint. Blocks following the snapshot one can
+    /// then be synced as usual.
+    pub async fn import_snapshot(&mut self, snapshot: StateSnapshot) -> Result<()> {
+        if self.blockchain.last()?.0 != 0 {
+            return Err(Error::SnapshotBlockchainNotEmpty)
+        }
+
+        let slot = snapshot.block.header.slot;
+        let hash = snapshot.block.header.headerhash();
+        if self.blockchain.checkpoints.get(slot) != Some(&hash) {
+            return Err(Error::SnapshotUntrusted(slot))
+        }
+
+        // The checkpointed header commits to the coins created in its
+        // block, and the restored tree must have produced a root the
+        // snapshot vouches for.
+        if Header::txs_root(&snapshot.block.txs) != snapshot.block.header.root {
+            return Err(Error::SnapshotRootMismatch)
+        }
+
+        let (tree, _): (BridgeTree<MerkleNode, MERKLE_DEPTH>, usize) =
+            bincode::serde::decode_from_slice(&snapshot.tree, bincode::config::legacy())?;
+        match tree.root(0) {
+            Some(root) if snapshot.merkle_roots.contains(&root) => {}
+            _ => return Err(Error::SnapshotRootMismatch),
+        }
+
+        self.blockchain.add(&[snapshot.block.clone()])?;
+        self.blockchain.contracts.insert(&snapshot.contracts)?;
+        self.blockchain.contract_state.apply(&snapshot.contract_state)?;
+
+        let mut state = self.state_machine.lock().await;
+        state.merkle_roots.insert(&snapshot.merkle_roots)?;
+        state.nullifiers.insert(&snapshot.nullifiers)?;
+        self.blockchain.merkle_trees.insert(slot, &tree)?;
+        state.tree = tree;
+        state.circuits.load(&self.blockchain.circuits, slot)?;
+        state.slot = slot;
+        drop(state);
+
+        self.consensus.participants = snapshot.participants;
+        info!("consensus: Bootstrapped state from snapshot of slot {}", slot);
+        Ok(())
+    }
 }
@@ -1,55 +1,47 @@
+use std::time::Duration;
+
+use async_executor::Executor;
+use async_std::sync::Arc;
+use futures::future::join_all;
+
 use crate::{
     consensus::{
-        block::{BlockOrder, BlockResponse},
-        ValidatorStatePtr,
+        block::{BlockInfo, BodyOrder, HeaderOrder, SyncInfoOrder, BODY_ORDER_MAX},
+        Header, ValidatorStatePtr,
     },
-    net, Result,
+    net::{self, ChannelPtr, Requester, RequesterPtr},
+    Error, Result,
 };
 use log::{debug, info, warn};
 
+/// Time to wait for a peer to answer a sync request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// async task used for block syncing.
-pub async fn block_sync_task(p2p: net::P2pPtr, state: ValidatorStatePtr) -> Result<()> {
+///
+/// Sync is performed headers-first: the header chain is downloaded from one
+/// peer and validated against the checkpoints, then the block bodies are
+/// fetched in parallel from all connected peers still keeping them, and
+/// appended in order.
+pub async fn block_sync_task(
+    p2p: net::P2pPtr,
+    state: ValidatorStatePtr,
+    executor: Arc<Executor<'_>>,
+) -> Result<()> {
     info!("Starting blockchain sync...");
-    // we retrieve p2p network connected channels, so we can use it to
-    // parallelize downloads.
-    // Using len here because is_empty() uses unstable library feature
-    // called 'exact_size_is_empty'.
-    if p2p.channels().lock().await.values().len() != 0 {
-        // Currently we will just use the last channel
-        let channel = p2p.channels().lock().await.values().last().unwrap().clone();
-
-        // Communication setup
-        let msg_subsystem = channel.get_message_subsystem();
-        msg_subsystem.add_dispatch::<BlockResponse>().await;
-        let response_sub = channel.subscribe_msg::<BlockResponse>().await?;
-
-        // Node sends the last known block hash of the canonical blockchain
-        // and loops until the response is the same block (used to utilize
-        // batch requests).
-        let mut last = state.read().await.blockchain.last()?;
-        info!("Last known block: {:?} - {:?}", last.0, last.1);
-
-        loop {
-            // Node creates a `BlockOrder` and sends it
-            let order = BlockOrder { slot: last.0, block: last.1 };
-            channel.send(order).await?;
-
-            // Node stores response data.
-            let resp = response_sub.receive().await?;
-
-            // Verify and store retrieved blocks
-            debug!("block_sync_task(): Processing received blocks");
-            state.write().await.receive_sync_blocks(&resp.blocks).await?;
-
-            let last_received = state.read().await.blockchain.last()?;
-            info!("Last received block: {:?} - {:?}", last_received.0, last_received.1);
-
-            if last == last_received {
-                break
-            }
+    let channels: Vec<ChannelPtr> = p2p.channels().lock().await.values().cloned().collect();
+
+    if let Some(channel) = channels.last() {
+        // Currently we will just use the last channel for headers
+        let headers = download_headers(channel.clone(), &state, executor.clone()).await?;
+        info!("Downloaded {} headers", headers.len());
 
-            last = last_received;
+        if !headers.is_empty() {
+            download_blocks(channels, &state, &headers, executor).await?;
         }
+
+        let last = state.read().await.blockchain.last()?;
+        info!("Last received block: {:?} - {:?}", last.0, last.1);
     } else {
         warn!("Node is not connected to other nodes");
     }
@@ -57,3 +49,155 @@ pub async fn block_sync_task(p2p: net::P2pPtr, state: ValidatorStatePtr) -> Resu
     info!("Blockchain synced!");
     Ok(())
 }
+
+/// Download the header chain following our last known block from a single
+/// peer, validating each batch as it arrives.
+async fn download_headers(
+    channel: ChannelPtr,
+    state: &ValidatorStatePtr,
+    executor: Arc<Executor<'_>>,
+) -> Result<Vec<Header>> {
+    let requester = Requester::<HeaderOrder>::new(channel, REQUEST_TIMEOUT, executor).await?;
+
+    // Node sends the last known block hash of the canonical blockchain
+    // and loops until no more headers are returned.
+    let mut last = state.read().await.blockchain.last()?;
+    info!("Last known block: {:?} - {:?}", last.0, last.1);
+    if let Some(checkpoint) = state.read().await.blockchain.checkpoints.last() {
+        info!("Last checkpoint: {:?} - {:?}", checkpoint.0, checkpoint.1);
+    }
+
+    let mut headers = vec![];
+    loop {
+        let resp = requester.request(HeaderOrder { slot: last.0, block: last.1 }).await?;
+        if resp.headers.is_empty() {
+            break
+        }
+
+        debug!("download_headers(): Validating {} headers", resp.headers.len());
+        state.read().await.blockchain.checkpoints.verify_headers(last, &resp.headers)?;
+
+        let tip = resp.headers.last().unwrap();
+        last = (tip.slot, tip.headerhash());
+        headers.extend(resp.headers);
+    }
+
+    Ok(headers)
+}
+
+/// Exchange pruned slots with a peer. Peers predating the handshake don't
+/// prune, so they're assumed to keep all block bodies.
+async fn sync_handshake(
+    channel: ChannelPtr,
+    pruned: u64,
+    executor: Arc<Executor<'_>>,
+) -> Result<u64> {
+    let address = channel.address();
+    let requester = Requester::<SyncInfoOrder>::new(channel, REQUEST_TIMEOUT, executor).await?;
+
+    match requester.request(SyncInfoOrder { pruned }).await {
+        Ok(resp) => {
+            debug!("sync_handshake(): Peer {} pruned bodies below slot {}", address, resp.pruned);
+            Ok(resp.pruned)
+        }
+        Err(e) => {
+            warn!("sync_handshake(): No sync info from peer {}, assuming archival: {}", address, e);
+            Ok(0)
+        }
+    }
+}
+
+/// Fetch the blocks of the given headers in parallel from all peers, and
+/// append them to the blockchain in order.
+async fn download_blocks(
+    channels: Vec<ChannelPtr>,
+    state: &ValidatorStatePtr,
+    headers: &[Header],
+    executor: Arc<Executor<'_>>,
+) -> Result<()> {
+    let pruned = state.read().await.blockchain.pruned_slot()?;
+    let handshakes =
+        channels.iter().map(|channel| sync_handshake(channel.clone(), pruned, executor.clone()));
+    let handshakes = join_all(handshakes).await;
+
+    let mut requesters = vec![];
+    for (channel, handshake) in channels.into_iter().zip(handshakes) {
+        let address = channel.address();
+        let peer_pruned = match handshake {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("download_blocks(): Skipping peer {}: {}", address, e);
+                continue
+            }
+        };
+
+        match Requester::<BodyOrder>::new(channel, REQUEST_TIMEOUT, executor.clone()).await {
+            Ok(requester) => requesters.push((requester, peer_pruned)),
+            Err(e) => warn!("download_blocks(): Skipping peer {}: {}", address, e),
+        }
+    }
+
+    if requesters.is_empty() {
+        return Err(Error::ChannelStopped)
+    }
+
+    // Each round requests one batch from every peer at once, and the
+    // received blocks are then validated and appended batch by batch.
+    let batches: Vec<&[Header]> = headers.chunks(BODY_ORDER_MAX).collect();
+    for round in batches.chunks(requesters.len()) {
+        let fetches =
+            round.iter().enumerate().map(|(i, batch)| fetch_blocks(&requesters, i, batch));
+
+        for blocks in join_all(fetches).await {
+            let blocks = blocks?;
+            debug!("download_blocks(): Processing {} received blocks", blocks.len());
+            state.write().await.receive_sync_blocks(&blocks).await?;
+        }
+
+        let last = state.read().await.blockchain.last()?;
+        info!("Synced up to block: {:?} - {:?}", last.0, last.1);
+    }
+
+    Ok(())
+}
+
+/// Fetch the blocks of a batch of headers, starting with the peer at index
+/// `first` and moving on to the next one if it fails. Peers that pruned the
+/// bodies of the batch are skipped.
+async fn fetch_blocks(
+    requesters: &[(RequesterPtr<BodyOrder>, u64)],
+    first: usize,
+    headers: &[Header],
+) -> Result<Vec<BlockInfo>> {
+    let order = BodyOrder { blocks: headers.iter().map(|h| h.headerhash()).collect() };
+    let slot = headers[0].slot;
+    let mut result = Err(Error::BlockBodiesPruned(slot));
+
+    for i in 0..requesters.len() {
+        let (requester, pruned) = &requesters[(first + i) % requesters.len()];
+        if slot < *pruned {
+            continue
+        }
+
+        result = match requester.request(order.clone()).await {
+            Ok(resp) => {
+                if resp.blocks.len() == headers.len() &&
+                    resp.blocks
+                        .iter()
+                        .zip(headers)
+                        .all(|(block, header)| &block.header == header)
+                {
+                    return Ok(resp.blocks)
+                }
+                Err(Error::BlockBodiesMismatch)
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = &result {
+            warn!("fetch_blocks(): Request to peer failed, retrying with the next one: {}", e);
+        }
+    }
+
+    result
+}
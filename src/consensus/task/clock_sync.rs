@@ -0,0 +1,16 @@
+use log::error;
+
+use crate::{consensus::clock::ClockSyncPtr, util::sleep};
+
+/// Seconds between clock offset measurements.
+pub const CLOCK_SYNC_INTERVAL: u64 = 600;
+
+/// async task used for periodically measuring the local clock offset.
+pub async fn clock_sync_task(clock: ClockSyncPtr, interval: u64) {
+    loop {
+        if let Err(e) = clock.measure().await {
+            error!("consensus: Failed measuring clock offset: {}", e);
+        }
+        sleep(interval).await;
+    }
+}
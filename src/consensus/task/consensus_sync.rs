@@ -35,8 +35,13 @@ pub async fn consensus_sync_task(p2p: P2pPtr, state: ValidatorStatePtr) -> Resul
                 warn!("Retrieved consensus state from a new node, retrying...");
                 continue
             }
+            if !response.consensus.verify_votes() {
+                warn!("Retrieved consensus state with invalid votes, retrying...");
+                continue
+            }
             // Node stores response data.
             state.write().await.consensus = response.consensus.clone();
+            state.write().await.update_tip().await?;
 
             break
         }
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use async_executor::Executor;
+use async_std::sync::Arc;
+use log::{debug, info};
+
+use crate::{
+    consensus::block::{
+        BlockInfo, BodyOrder, FilterHeaderOrder, FilterOrder, BODY_ORDER_MAX, FILTER_ORDER_MAX,
+    },
+    net::{ChannelPtr, Requester},
+    node::Client,
+    Error, Result,
+};
+
+/// Time to wait for a peer to answer a sync request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// async task used by light wallets to sync the slot range `start..end`
+/// through compact block filters, without downloading every block.
+///
+/// The filter headers and filters of the range are fetched from the peer,
+/// and each filter is checked to chain to the previous filter header,
+/// starting from `prev_header`, the filter header of the last block synced
+/// before. Only the blocks whose filter matches the wallet's coins or
+/// nullifiers are downloaded in full.
+///
+/// Returns the matching blocks, and the filter header to resume from.
+pub async fn light_sync_task(
+    channel: ChannelPtr,
+    client: Arc<Client>,
+    start: u64,
+    end: u64,
+    prev_header: blake3::Hash,
+    executor: Arc<Executor<'_>>,
+) -> Result<(Vec<BlockInfo>, blake3::Hash)> {
+    info!("Starting light sync of slots {} -> {}", start, end);
+    let header_requester =
+        Requester::<FilterHeaderOrder>::new(channel.clone(), REQUEST_TIMEOUT, executor.clone())
+            .await?;
+    let filter_requester =
+        Requester::<FilterOrder>::new(channel.clone(), REQUEST_TIMEOUT, executor.clone()).await?;
+    let body_requester = Requester::<BodyOrder>::new(channel, REQUEST_TIMEOUT, executor).await?;
+
+    let items = client.get_filter_items().await?;
+    let mut prev_header = prev_header;
+    let mut matched = vec![];
+
+    let mut batch_start = start;
+    while batch_start < end {
+        let batch_end = (batch_start + FILTER_ORDER_MAX).min(end);
+        let headers = header_requester
+            .request(FilterHeaderOrder { start: batch_start, end: batch_end })
+            .await?
+            .headers;
+        let filters = filter_requester
+            .request(FilterOrder { start: batch_start, end: batch_end })
+            .await?
+            .filters;
+
+        if headers.len() != filters.len() {
+            return Err(Error::MalformedPacket)
+        }
+
+        for (header, filter) in headers.iter().zip(&filters) {
+            if header.slot != filter.slot ||
+                header.block != filter.block ||
+                filter.filter.header(&prev_header) != header.header
+            {
+                return Err(Error::BlockFilterMismatch(filter.slot))
+            }
+            prev_header = header.header;
+
+            if filter.filter.match_any(&filter.block, &items) {
+                debug!("light_sync_task(): Filter of slot {} matches", filter.slot);
+                matched.push(filter.block);
+            }
+        }
+
+        batch_start = batch_end;
+    }
+
+    info!("Light sync matched {} blocks", matched.len());
+    let mut blocks = vec![];
+    for batch in matched.chunks(BODY_ORDER_MAX) {
+        let resp = body_requester.request(BodyOrder { blocks: batch.to_vec() }).await?;
+        if resp.blocks.len() != batch.len() ||
+            resp.blocks.iter().zip(batch).any(|(block, hash)| block.header.headerhash() != *hash)
+        {
+            return Err(Error::BlockBodiesMismatch)
+        }
+        blocks.extend(resp.blocks);
+    }
+
+    Ok((blocks, prev_header))
+}
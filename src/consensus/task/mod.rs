@@ -3,8 +3,17 @@
 mod block_sync;
 pub use block_sync::block_sync_task;
 
+mod clock_sync;
+pub use clock_sync::{clock_sync_task, CLOCK_SYNC_INTERVAL};
+
 mod consensus_sync;
 pub use consensus_sync::consensus_sync_task;
 
+mod light_sync;
+pub use light_sync::light_sync_task;
+
 mod proposal;
 pub use proposal::proposal_task;
+
+mod tx_rebroadcast;
+pub use tx_rebroadcast::tx_rebroadcast_task;
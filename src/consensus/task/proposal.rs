@@ -4,7 +4,7 @@ use log::{debug, error, info};
 
 use super::consensus_sync_task;
 use crate::{
-    consensus::{Participant, ValidatorStatePtr},
+    consensus::{Participant, Rejoin, ValidatorStatePtr},
     net::P2pPtr,
     util::sleep,
 };
@@ -45,6 +45,7 @@ pub async fn proposal_task(consensus_p2p: P2pPtr, sync_p2p: P2pPtr, state: Valid
     let participant = Participant::new(public, address, cur_slot);
     state.write().await.append_participant(participant.clone());
 
+    let participant = state.read().await.sign_message(participant);
     match consensus_p2p.broadcast(participant).await {
         Ok(()) => info!("consensus: Participation message broadcasted successfully."),
         Err(e) => error!("Failed broadcasting consensus participation: {}", e),
@@ -67,6 +68,20 @@ pub async fn proposal_task(consensus_p2p: P2pPtr, sync_p2p: P2pPtr, state: Valid
             Err(e) => error!("Failed refreshing consensus participants: {}", e),
         }
 
+        // If the node got removed from the participants set while offline,
+        // it proves its liveness every slot until it's let back in.
+        if !state.read().await.is_participant() {
+            let rejoin = Rejoin::new(public, address);
+            let cur_slot = state.read().await.current_slot();
+            state.write().await.receive_rejoin(&rejoin, cur_slot);
+
+            let rejoin = state.read().await.sign_message(rejoin);
+            match consensus_p2p.broadcast(rejoin).await {
+                Ok(()) => info!("consensus: Rejoin message broadcasted successfully."),
+                Err(e) => error!("Failed broadcasting consensus rejoin: {}", e),
+            }
+        }
+
         // Node checks if it's the slot leader to generate a new proposal
         // for that slot.
         let result = if state.write().await.is_slot_leader() {
@@ -136,6 +151,7 @@ pub async fn proposal_task(consensus_p2p: P2pPtr, sync_p2p: P2pPtr, state: Valid
         }
 
         // Broadcast leader vote
+        let vote = state.read().await.sign_message(vote);
         match consensus_p2p.broadcast(vote).await {
             Ok(()) => info!("consensus: Leader vote broadcasted successfully"),
             Err(e) => error!("consensus: Failed broadcasting leader vote: {}", e),
@@ -0,0 +1,23 @@
+use log::{debug, error};
+
+use crate::{consensus::ValidatorStatePtr, net::P2pPtr, util::sleep};
+
+/// async task used for periodically rebroadcasting the transactions this node
+/// created, until they get included in a block.
+pub async fn tx_rebroadcast_task(p2p: P2pPtr, state: ValidatorStatePtr, interval: u64) {
+    loop {
+        sleep(interval).await;
+
+        let txs = state.read().await.mempool.local_txs();
+        if txs.is_empty() {
+            continue
+        }
+
+        debug!("tx_rebroadcast_task(): Rebroadcasting {} unconfirmed transactions", txs.len());
+        for tx in txs {
+            if let Err(e) = p2p.broadcast(tx).await {
+                error!("tx_rebroadcast_task(): p2p broadcast fail: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::BTreeMap;
+
+use async_std::sync::Arc;
+use log::debug;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{BlockProposal, Participant, SignedMessage, ValidatorState, ValidatorStatePtr, Vote};
+use crate::{
+    crypto::{address::Address, keypair::Keypair},
+    node::Client,
+    util::time::Timestamp,
+    wallet::walletdb::WalletDb,
+    Result,
+};
+
+/// Ticks a simulated slot is divided in. Message latencies are counted in
+/// ticks.
+pub const TICKS_PER_SLOT: u64 = 10;
+
+/// Password of the in-memory wallets of the simulated validators
+const WALLET_PASS: &str = "simulation";
+
+/// Parameters of a simulated network.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Seed the validator keys, message latencies and drops are drawn from
+    pub seed: u64,
+    /// Number of validators
+    pub nodes: usize,
+    /// Maximum latency of a message, in ticks. Latencies are drawn
+    /// uniformly from `1..=max_latency`.
+    pub max_latency: u64,
+    /// Probability of a message getting dropped
+    pub drop_rate: f64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self { seed: 0, nodes: 4, max_latency: 1, drop_rate: 0.0 }
+    }
+}
+
+/// Consensus message travelling on the [`MessageBus`].
+#[derive(Debug, Clone)]
+pub enum SimMessage {
+    Proposal(BlockProposal),
+    Vote(SignedMessage<Vote>),
+}
+
+/// In-memory message bus connecting the simulated validators. Messages go
+/// straight from their sender to every other node, each copy with its own
+/// latency, unless it gets dropped or the nodes are partitioned apart.
+pub struct MessageBus {
+    rng: StdRng,
+    max_latency: u64,
+    drop_rate: f64,
+    /// Current tick
+    pub tick: u64,
+    /// Messages in flight and their recipient, by delivery tick and send order
+    queue: BTreeMap<(u64, u64), (usize, SimMessage)>,
+    /// Number of messages sent so far
+    sent: u64,
+    /// Partition group of every node
+    groups: Vec<usize>,
+}
+
+impl MessageBus {
+    pub fn new(rng: StdRng, nodes: usize, max_latency: u64, drop_rate: f64) -> Self {
+        Self {
+            rng,
+            max_latency,
+            drop_rate,
+            tick: 0,
+            queue: BTreeMap::new(),
+            sent: 0,
+            groups: vec![0; nodes],
+        }
+    }
+
+    /// Split the network in the given groups of nodes, which can't reach
+    /// each other. Nodes left out of every group end up together in one
+    /// more group. Messages already in flight are still delivered.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        self.groups = vec![0; self.groups.len()];
+        for (i, group) in groups.iter().enumerate() {
+            for node in group.iter() {
+                self.groups[*node] = i + 1;
+            }
+        }
+    }
+
+    /// Reconnect all nodes.
+    pub fn heal(&mut self) {
+        self.groups = vec![0; self.groups.len()];
+    }
+
+    /// Queue a message for delivery to `to`. Latency and drop are drawn
+    /// for every message, so the random stream doesn't depend on the
+    /// partitions.
+    pub fn send(&mut self, from: usize, to: usize, msg: SimMessage) {
+        let dropped = self.drop_rate > 0.0 && self.rng.gen_bool(self.drop_rate);
+        let latency = self.rng.gen_range(1..=self.max_latency);
+        if dropped || self.groups[from] != self.groups[to] {
+            debug!(target: "consensus::testing", "Dropping message from {} to {}", from, to);
+            return
+        }
+
+        self.queue.insert((self.tick + latency, self.sent), (to, msg));
+        self.sent += 1;
+    }
+
+    /// Queue a message for delivery to every node but its sender.
+    pub fn broadcast(&mut self, from: usize, msg: SimMessage) {
+        for to in 0..self.groups.len() {
+            if to != from {
+                self.send(from, to, msg.clone());
+            }
+        }
+    }
+
+    /// Take the messages due for delivery by the current tick, in delivery
+    /// order.
+    pub fn deliver(&mut self) -> Vec<(usize, SimMessage)> {
+        let later = self.queue.split_off(&(self.tick + 1, 0));
+        let due = std::mem::replace(&mut self.queue, later);
+        due.into_values().collect()
+    }
+}
+
+/// Simulated network of validators running Streamlet over a [`MessageBus`],
+/// with virtual slots advanced by [`Simulation::run_slot`]. All the
+/// validators know each other from genesis, and everything random is drawn
+/// from the configured seed, so a run can be replayed.
+pub struct Simulation {
+    pub nodes: Vec<ValidatorStatePtr>,
+    pub bus: MessageBus,
+    /// Current virtual slot
+    pub slot: u64,
+}
+
+impl Simulation {
+    pub async fn new(config: SimConfig) -> Result<Self> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let genesis_ts = Timestamp(0);
+        let genesis_data = blake3::hash(b"darkfi_simulation");
+
+        let mut nodes = Vec::with_capacity(config.nodes);
+        let mut participants = Vec::with_capacity(config.nodes);
+        for _ in 0..config.nodes {
+            let keypair = Keypair::random(&mut rng);
+            let wallet = WalletDb::new("sqlite::memory:", WALLET_PASS).await?;
+            wallet.put_keypair(&keypair).await?;
+            wallet.set_default_keypair(&keypair.public).await?;
+            let client = Arc::new(Client::new(wallet).await?);
+
+            let db = sled::Config::new().temporary(true).open()?;
+            let state =
+                ValidatorState::new(&db, genesis_ts, genesis_data, client, vec![], vec![]).await?;
+
+            let mut validator = state.write().await;
+            validator.secret = keypair.secret;
+            validator.public = keypair.public;
+            validator.virtual_slot = Some(0);
+            validator.participating = Some(1);
+            drop(validator);
+
+            participants.push(Participant::new(keypair.public, Address::from(keypair.public), 0));
+            nodes.push(state);
+        }
+
+        for node in &nodes {
+            let mut validator = node.write().await;
+            for participant in &participants {
+                validator.consensus.participants.insert(participant.address, participant.clone());
+            }
+        }
+
+        let bus = MessageBus::new(rng, config.nodes, config.max_latency, config.drop_rate);
+        Ok(Self { nodes, bus, slot: 0 })
+    }
+
+    /// Advance all the validators to the next slot, let its leader propose,
+    /// and deliver the messages due during the slot.
+    pub async fn run_slot(&mut self) {
+        self.slot += 1;
+        for node in &self.nodes {
+            let mut validator = node.write().await;
+            validator.virtual_slot = Some(self.slot);
+            if let Err(e) = validator.refresh_participants() {
+                debug!(target: "consensus::testing", "Failed refreshing participants: {}", e);
+            }
+        }
+
+        for i in 0..self.nodes.len() {
+            let node = self.nodes[i].clone();
+            let mut validator = node.write().await;
+            if !validator.is_slot_leader() {
+                continue
+            }
+
+            let proposal = match validator.propose() {
+                Ok(Some(v)) => v,
+                Ok(None) => continue,
+                Err(e) => {
+                    debug!(target: "consensus::testing", "Node {} failed proposing: {}", i, e);
+                    continue
+                }
+            };
+
+            let vote = validator.receive_proposal(&proposal).await;
+            drop(validator);
+            self.bus.broadcast(i, SimMessage::Proposal(proposal));
+            if let Ok(Some(vote)) = vote {
+                self.cast_vote(i, vote).await;
+            }
+        }
+
+        for _ in 0..TICKS_PER_SLOT {
+            self.bus.tick += 1;
+            for (to, msg) in self.bus.deliver() {
+                self.handle(to, msg).await;
+            }
+        }
+    }
+
+    /// Run the given number of slots.
+    pub async fn run_slots(&mut self, slots: u64) {
+        for _ in 0..slots {
+            self.run_slot().await;
+        }
+    }
+
+    /// Count a node's own vote, and send it to the others.
+    async fn cast_vote(&mut self, node: usize, vote: Vote) {
+        let mut validator = self.nodes[node].write().await;
+        if let Err(e) = validator.receive_vote(&vote).await {
+            debug!(target: "consensus::testing", "Node {} failed voting: {}", node, e);
+        }
+        let vote = validator.sign_message(vote);
+        drop(validator);
+        self.bus.broadcast(node, SimMessage::Vote(vote));
+    }
+
+    /// Process a message delivered to a node, the way its protocols would.
+    async fn handle(&mut self, node: usize, msg: SimMessage) {
+        let state = self.nodes[node].clone();
+        match msg {
+            SimMessage::Proposal(proposal) => {
+                let vote = state.write().await.receive_proposal(&proposal).await;
+                match vote {
+                    Ok(Some(vote)) => self.cast_vote(node, vote).await,
+                    Ok(None) => {}
+                    Err(e) => {
+                        debug!(target: "consensus::testing", "Node {} proposal error: {}", node, e)
+                    }
+                }
+            }
+            SimMessage::Vote(vote) => {
+                let mut validator = state.write().await;
+                if !validator.verify_message(&vote) {
+                    return
+                }
+                if let Err(e) = validator.receive_verified_vote(&vote.payload).await {
+                    debug!(target: "consensus::testing", "Node {} vote error: {}", node, e);
+                }
+            }
+        }
+    }
+
+    /// Slots and hashes of the blocks a node finalized, genesis included.
+    pub async fn finalized(&self, node: usize) -> Result<Vec<(u64, blake3::Hash)>> {
+        let validator = self.nodes[node].read().await;
+        validator
+            .blockchain
+            .iter()
+            .map(|block| block.map(|b| (b.header.slot, b.header.headerhash())))
+            .collect()
+    }
+
+    /// Check no two nodes finalized conflicting blocks, that is the
+    /// blockchain of every node is a prefix of the longest one.
+    pub async fn check_safety(&self) -> Result<bool> {
+        let mut chains = Vec::with_capacity(self.nodes.len());
+        for node in 0..self.nodes.len() {
+            chains.push(self.finalized(node).await?);
+        }
+
+        let longest = chains.iter().max_by_key(|c| c.len()).unwrap();
+        Ok(chains.iter().all(|c| longest.starts_with(c)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of blocks every node finalized, genesis excluded.
+    async fn min_finalized(sim: &Simulation) -> Result<usize> {
+        let mut min = usize::MAX;
+        for node in 0..sim.nodes.len() {
+            min = min.min(sim.finalized(node).await?.len() - 1);
+        }
+        Ok(min)
+    }
+
+    #[async_std::test]
+    async fn test_liveness() -> Result<()> {
+        let mut sim = Simulation::new(SimConfig::default()).await?;
+        sim.run_slots(10).await;
+
+        assert!(sim.check_safety().await?);
+        assert!(min_finalized(&sim).await? >= 5);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_lossy_network() -> Result<()> {
+        let config = SimConfig { seed: 42, max_latency: 3, drop_rate: 0.05, ..Default::default() };
+
+        // Runs with the same seed are replays of each other
+        let mut slots = vec![];
+        for _ in 0..2 {
+            let mut sim = Simulation::new(config.clone()).await?;
+            sim.run_slots(12).await;
+            assert!(sim.check_safety().await?);
+
+            let mut finalized = vec![];
+            for node in 0..sim.nodes.len() {
+                let blocks = sim.finalized(node).await?;
+                finalized.push(blocks.into_iter().map(|(slot, _)| slot).collect::<Vec<_>>());
+            }
+            slots.push(finalized);
+        }
+        assert_eq!(slots[0], slots[1]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_partition() -> Result<()> {
+        let mut sim = Simulation::new(SimConfig { seed: 7, ..Default::default() }).await?;
+        sim.run_slots(3).await;
+
+        // Neither half can notarize on its own, so nothing conflicting
+        // gets finalized
+        sim.bus.partition(&[&[0, 1], &[2, 3]]);
+        sim.run_slots(3).await;
+        assert!(sim.check_safety().await?);
+        let before = min_finalized(&sim).await?;
+
+        // Progress resumes once the network heals
+        sim.bus.heal();
+        sim.run_slots(10).await;
+        assert!(sim.check_safety().await?);
+        assert!(min_finalized(&sim).await? > before);
+        Ok(())
+    }
+}
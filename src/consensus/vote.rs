@@ -27,4 +27,8 @@ impl net::Message for Vote {
     fn name() -> &'static str {
         "vote"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::High
+    }
 }
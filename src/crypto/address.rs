@@ -3,13 +3,15 @@ use std::{io, str::FromStr};
 use sha2::Digest;
 
 use crate::{
-    crypto::keypair::PublicKey,
-    util::serial::{Decodable, Encodable, ReadExt, WriteExt},
+    crypto::keypair::{PublicKey, SecretKey},
+    util::serial::{Decodable, Encodable, ReadExt, SerialDecodable, SerialEncodable, WriteExt},
     Error, Result,
 };
 
 enum AddressType {
     Payment = 0,
+    /// Payment address carrying an incoming viewing key
+    Viewable = 1,
 }
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
@@ -81,6 +83,77 @@ impl From<PublicKey> for Address {
     }
 }
 
+/// Address to receive payments at. Coins are sent to its spend public key,
+/// while the notes of the coins are encrypted to the public key of its
+/// incoming viewing key, so that the view key can be given to an auditor
+/// without granting spend capability.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, SerialEncodable, SerialDecodable)]
+pub struct PaymentAddress {
+    pub public: PublicKey,
+    pub view_public: PublicKey,
+}
+
+impl PaymentAddress {
+    /// Create the payment address of the given spend secret key.
+    pub fn new(secret: &SecretKey) -> Self {
+        Self {
+            public: PublicKey::from_secret(*secret),
+            view_public: PublicKey::from_secret(secret.view_key()),
+        }
+    }
+
+    /// Create the diversified address with the given index, one of many
+    /// unlinkable receive addresses that can be derived from `secret`.
+    /// Coins sent to it are spendable with `secret.diversify(diversifier)`.
+    pub fn diversified(secret: &SecretKey, diversifier: u64) -> Self {
+        Self::new(&secret.diversify(diversifier))
+    }
+
+    fn checksum(payload: &[u8]) -> Vec<u8> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(payload);
+        hasher.finalize()[..4].to_vec()
+    }
+}
+
+impl std::fmt::Display for PaymentAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut address = vec![AddressType::Viewable as u8];
+        address.extend_from_slice(&self.public.to_bytes());
+        address.extend_from_slice(&self.view_public.to_bytes());
+        let mut checksum = Self::checksum(&address);
+        address.append(&mut checksum);
+
+        write!(f, "{}", bs58::encode(address).into_string())
+    }
+}
+
+impl FromStr for PaymentAddress {
+    type Err = Error;
+
+    /// Plain addresses are accepted as well, in which case notes are
+    /// encrypted to the spend public key.
+    fn from_str(address: &str) -> Result<Self> {
+        let bytes = match bs58::decode(&address).into_vec() {
+            Ok(v) => v,
+            Err(_) => return Err(Error::InvalidAddress),
+        };
+
+        if bytes.len() != 69 || bytes[0] != AddressType::Viewable as u8 {
+            let public = PublicKey::try_from(Address::from_str(address)?)?;
+            return Ok(Self { public, view_public: public })
+        }
+
+        if Self::checksum(&bytes[..65]) != bytes[65..] {
+            return Err(Error::InvalidAddress)
+        }
+
+        let public = PublicKey::from_bytes(&bytes[1..33].try_into().unwrap())?;
+        let view_public = PublicKey::from_bytes(&bytes[33..65].try_into().unwrap())?;
+        Ok(Self { public, view_public })
+    }
+}
+
 impl Encodable for Address {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         s.write_slice(&self.0)?;
@@ -115,6 +188,23 @@ mod tests {
         let from_str = Address::from_str(&address_str)?;
         assert_eq!(from_str, address);
 
+        // payment addresses
+        let payment = PaymentAddress::new(&keypair.secret);
+        assert_eq!(payment.public, keypair.public);
+        assert_ne!(payment.view_public, keypair.public);
+        assert_eq!(PaymentAddress::from_str(&payment.to_string())?, payment);
+        let plain = PaymentAddress::from_str(&address_str)?;
+        assert_eq!(plain.view_public, keypair.public);
+
+        // diversified addresses
+        let div0 = PaymentAddress::diversified(&keypair.secret, 0);
+        let div1 = PaymentAddress::diversified(&keypair.secret, 1);
+        assert_ne!(div0, payment);
+        assert_ne!(div0, div1);
+        assert_eq!(div1, PaymentAddress::diversified(&keypair.secret, 1));
+        let diversified = Keypair::new(keypair.secret.diversify(1));
+        assert_eq!(diversified.public, div1.public);
+
         Ok(())
     }
 }
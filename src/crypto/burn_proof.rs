@@ -34,6 +34,8 @@ pub struct BurnRevealedValues {
     pub spend_hook: DrkSpendHook,
     pub user_data_enc: DrkUserDataEnc,
     pub signature_public: PublicKey,
+    /// Slot until which the spent coin was locked
+    pub lock_slot: u64,
 }
 
 impl BurnRevealedValues {
@@ -51,6 +53,7 @@ impl BurnRevealedValues {
         spend_hook: DrkSpendHook,
         user_data: DrkUserData,
         user_data_blind: DrkUserDataBlind,
+        lock_slot: u64,
         signature_secret: SecretKey,
     ) -> Self {
         let nullifier = poseidon_hash::<2>([secret.0, serial]);
@@ -58,7 +61,7 @@ impl BurnRevealedValues {
         let public_key = PublicKey::from_secret(secret);
         let coords = public_key.0.to_affine().coordinates().unwrap();
 
-        let coin = poseidon_hash::<8>([
+        let coin = poseidon_hash::<9>([
             *coords.x(),
             *coords.y(),
             DrkValue::from(value),
@@ -66,6 +69,7 @@ impl BurnRevealedValues {
             serial,
             spend_hook,
             user_data,
+            DrkValue::from(lock_slot),
             coin_blind,
         ]);
 
@@ -96,6 +100,7 @@ impl BurnRevealedValues {
             spend_hook,
             user_data_enc,
             signature_public: PublicKey::from_secret(signature_secret),
+            lock_slot,
         }
     }
 
@@ -116,6 +121,7 @@ impl BurnRevealedValues {
             user_data_enc,
             *sig_coords.x(),
             *sig_coords.y(),
+            DrkValue::from(self.lock_slot),
         ]
     }
 }
@@ -131,6 +137,7 @@ pub fn create_burn_proof(
     spend_hook: DrkSpendHook,
     user_data: DrkUserData,
     user_data_blind: DrkUserDataBlind,
+    lock_slot: u64,
     coin_blind: DrkCoinBlind,
     secret: SecretKey,
     leaf_position: incrementalmerkletree::Position,
@@ -150,6 +157,7 @@ pub fn create_burn_proof(
         spend_hook,
         user_data,
         user_data_blind,
+        lock_slot,
         signature_secret,
     );
 
@@ -168,6 +176,7 @@ pub fn create_burn_proof(
         spend_hook: Value::known(spend_hook),
         user_data: Value::known(user_data),
         user_data_blind: Value::known(user_data_blind),
+        lock_slot: Value::known(DrkValue::from(lock_slot)),
         sig_secret: Value::known(signature_secret.0),
     };
 
@@ -7,6 +7,15 @@ pub use fixed_bases::{NullifierK, OrchardFixedBases, OrchardFixedBasesFull, Valu
 /// Domain prefix used for Schnorr signatures, with `hash_to_scalar`.
 pub const DRK_SCHNORR_DOMAIN: &[u8] = b"DarkFi:Schnorr";
 
+/// Domain prefix used for MuSig key aggregation coefficients, with `hash_to_scalar`.
+pub const DRK_MUSIG_KEY_DOMAIN: &[u8] = b"DarkFi:MuSigKey";
+
+/// Domain prefix used for FROST DKG proofs of knowledge, with `hash_to_scalar`.
+pub const DRK_FROST_DKG_DOMAIN: &[u8] = b"DarkFi:FrostDkg";
+
+/// Domain prefix used for FROST binding factors, with `hash_to_scalar`.
+pub const DRK_FROST_BINDING_DOMAIN: &[u8] = b"DarkFi:FrostBind";
+
 /// Domain prefix used for block hashes, with `hash_to_curve`.
 pub const BLOCK_HASH_DOMAIN: &str = "DarkFi:Block";
 
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use crypto_api_chachapoly::ChachaPolyIetf;
+use halo2_gadgets::ecc::chip::FixedPoint;
+use pasta_curves::{
+    group::{ff::Field, Group, GroupEncoding},
+    pallas,
+};
+use rand::rngs::OsRng;
+
+use super::{
+    evaluate, evaluate_commitments, participant_scalar, FrostParams, GroupKey, KeyShare,
+    ParticipantIndex,
+};
+use crate::{
+    crypto::{
+        constants::{NullifierK, DRK_FROST_DKG_DOMAIN},
+        diffie_hellman::{kdf_sapling, sapling_ka_agree},
+        keypair::{PublicKey, SecretKey},
+        note::AEAD_TAG_SIZE,
+        schnorr::Signature,
+        util::hash_to_scalar,
+    },
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    Error::{
+        FrostInvalidCommitment, FrostInvalidShare, FrostMissingParticipant, FrostUnknownParticipant,
+    },
+    Result,
+};
+
+/// First round message of a participant, broadcast to the others:
+/// commitments to the coefficients of its secret polynomial, and a proof
+/// of knowledge of the constant term, so that no participant can cancel
+/// out the contributions of the others to the group key.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct DkgCommitment {
+    pub index: ParticipantIndex,
+    pub commitments: Vec<pallas::Point>,
+    pub proof: Signature,
+}
+
+/// Second round message of a participant, sent to each of the others:
+/// the evaluation of its secret polynomial for the recipient, encrypted
+/// to the recipient's long-term key.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct DkgShare {
+    pub from: ParticipantIndex,
+    pub to: ParticipantIndex,
+    ciphertext: Vec<u8>,
+    ephem_public: PublicKey,
+}
+
+impl DkgShare {
+    fn encrypt(
+        from: ParticipantIndex,
+        to: ParticipantIndex,
+        share: pallas::Scalar,
+        public: &PublicKey,
+    ) -> Self {
+        let ephem_secret = SecretKey::random(&mut OsRng);
+        let ephem_public = PublicKey::from_secret(ephem_secret);
+        let shared_secret = sapling_ka_agree(&ephem_secret, public);
+        let key = kdf_sapling(&shared_secret, &ephem_public);
+
+        let input = serialize(&share);
+        let mut ciphertext = vec![0; input.len() + AEAD_TAG_SIZE];
+        assert_eq!(
+            ChachaPolyIetf::aead_cipher()
+                .seal_to(&mut ciphertext, &input, &Self::ad(from, to), key.as_ref(), &[0u8; 12])
+                .unwrap(),
+            input.len() + AEAD_TAG_SIZE
+        );
+
+        Self { from, to, ciphertext, ephem_public }
+    }
+
+    fn decrypt(&self, secret: &SecretKey) -> Result<pallas::Scalar> {
+        let shared_secret = sapling_ka_agree(secret, &self.ephem_public);
+        let key = kdf_sapling(&shared_secret, &self.ephem_public);
+
+        let mut plaintext = vec![0; self.ciphertext.len()];
+        let len = ChachaPolyIetf::aead_cipher()
+            .open_to(
+                &mut plaintext,
+                &self.ciphertext,
+                &Self::ad(self.from, self.to),
+                key.as_ref(),
+                &[0u8; 12],
+            )
+            .map_err(|_| FrostInvalidShare(self.from))?;
+
+        deserialize(&plaintext[..len])
+    }
+
+    /// Associated data binding the ciphertext to its sender and recipient
+    fn ad(from: ParticipantIndex, to: ParticipantIndex) -> [u8; 4] {
+        let mut ad = [0u8; 4];
+        ad[..2].copy_from_slice(&from.to_le_bytes());
+        ad[2..].copy_from_slice(&to.to_le_bytes());
+        ad
+    }
+}
+
+/// Challenge of the proof of knowledge of a participant's constant term.
+fn proof_challenge(
+    index: ParticipantIndex,
+    constant: &pallas::Point,
+    commit: &pallas::Point,
+) -> pallas::Scalar {
+    let mut data = index.to_le_bytes().to_vec();
+    data.extend_from_slice(&constant.to_bytes());
+    hash_to_scalar(DRK_FROST_DKG_DOMAIN, &data, &commit.to_bytes())
+}
+
+/// State of a participant during the distributed key generation. Each
+/// participant broadcasts its [`DkgCommitment`], then sends a [`DkgShare`]
+/// to each of the others, and finishes with its [`KeyShare`] once it got
+/// all of theirs.
+pub struct Dkg {
+    params: FrostParams,
+    index: ParticipantIndex,
+    /// Long-term secret key, decrypting the shares we receive
+    secret: SecretKey,
+    /// Coefficients of our secret polynomial
+    coefficients: Vec<pallas::Scalar>,
+    /// Coefficient commitments of all participants, ours included
+    commitments: BTreeMap<ParticipantIndex, Vec<pallas::Point>>,
+    /// Shares we received, ours included
+    shares: BTreeMap<ParticipantIndex, pallas::Scalar>,
+}
+
+impl Dkg {
+    /// Start the DKG as the participant with provided long-term secret key,
+    /// returning the first round message to broadcast.
+    pub fn new(params: FrostParams, secret: SecretKey) -> Result<(Self, DkgCommitment)> {
+        let index = match params.index_of(&PublicKey::from_secret(secret)) {
+            Some(i) => i,
+            None => return Err(FrostUnknownParticipant),
+        };
+
+        let generator = NullifierK.generator();
+        let coefficients: Vec<pallas::Scalar> =
+            (0..params.threshold).map(|_| pallas::Scalar::random(&mut OsRng)).collect();
+        let commitments: Vec<pallas::Point> = coefficients.iter().map(|&c| generator * c).collect();
+
+        let mask = pallas::Scalar::random(&mut OsRng);
+        let commit = generator * mask;
+        let challenge = proof_challenge(index, &commitments[0], &commit);
+        let proof = Signature { commit, response: mask + challenge * coefficients[0] };
+
+        let mut dkg = Self {
+            params,
+            index,
+            secret,
+            coefficients,
+            commitments: BTreeMap::new(),
+            shares: BTreeMap::new(),
+        };
+        dkg.commitments.insert(index, commitments.clone());
+        dkg.shares.insert(index, evaluate(&dkg.coefficients, participant_scalar(index)));
+
+        Ok((dkg, DkgCommitment { index, commitments, proof }))
+    }
+
+    /// Our participant index.
+    pub fn index(&self) -> ParticipantIndex {
+        self.index
+    }
+
+    /// Process the first round message of another participant.
+    pub fn receive_commitment(&mut self, commitment: DkgCommitment) -> Result<()> {
+        let index = commitment.index;
+        if self.params.public_of(index).is_none() {
+            return Err(FrostUnknownParticipant)
+        }
+
+        if commitment.commitments.len() != self.params.threshold as usize {
+            return Err(FrostInvalidCommitment(index))
+        }
+
+        let constant = &commitment.commitments[0];
+        let challenge = proof_challenge(index, constant, &commitment.proof.commit);
+        if NullifierK.generator() * commitment.proof.response - *constant * challenge !=
+            commitment.proof.commit
+        {
+            return Err(FrostInvalidCommitment(index))
+        }
+
+        self.commitments.insert(index, commitment.commitments);
+        Ok(())
+    }
+
+    /// Second round messages, one for each of the other participants.
+    pub fn shares(&self) -> Vec<DkgShare> {
+        (1..=self.params.participants.len() as ParticipantIndex)
+            .filter(|to| *to != self.index)
+            .map(|to| {
+                let share = evaluate(&self.coefficients, participant_scalar(to));
+                DkgShare::encrypt(self.index, to, share, self.params.public_of(to).unwrap())
+            })
+            .collect()
+    }
+
+    /// Process the second round message of another participant, checking
+    /// the share against the commitments the participant broadcast.
+    pub fn receive_share(&mut self, share: DkgShare) -> Result<()> {
+        if share.to != self.index {
+            return Err(FrostInvalidShare(share.from))
+        }
+
+        let commitments = match self.commitments.get(&share.from) {
+            Some(c) => c,
+            None => return Err(FrostMissingParticipant(share.from)),
+        };
+
+        let value = share.decrypt(&self.secret)?;
+        let expected = evaluate_commitments(commitments, participant_scalar(self.index));
+        if NullifierK.generator() * value != expected {
+            return Err(FrostInvalidShare(share.from))
+        }
+
+        self.shares.insert(share.from, value);
+        Ok(())
+    }
+
+    /// Finish the DKG once we got the messages of every participant,
+    /// returning our share of the group key.
+    pub fn finish(self) -> Result<KeyShare> {
+        let n = self.params.participants.len() as ParticipantIndex;
+        for index in 1..=n {
+            if !self.commitments.contains_key(&index) || !self.shares.contains_key(&index) {
+                return Err(FrostMissingParticipant(index))
+            }
+        }
+
+        let secret = self.shares.values().fold(pallas::Scalar::zero(), |acc, s| acc + s);
+
+        let public = self.commitments.values().fold(pallas::Point::identity(), |acc, c| acc + c[0]);
+        let verifying_shares = (1..=n)
+            .map(|index| {
+                let x = participant_scalar(index);
+                PublicKey(
+                    self.commitments
+                        .values()
+                        .fold(pallas::Point::identity(), |acc, c| acc + evaluate_commitments(c, x)),
+                )
+            })
+            .collect();
+
+        let group = GroupKey {
+            threshold: self.params.threshold,
+            public: PublicKey(public),
+            verifying_shares,
+        };
+
+        Ok(KeyShare { index: self.index, secret, group })
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    net,
+    util::serial::{SerialDecodable, SerialEncodable},
+};
+
+use super::{
+    dkg::{DkgCommitment, DkgShare},
+    sign::{SignatureShare, SigningCommitment},
+};
+
+impl net::Message for DkgCommitment {
+    fn name() -> &'static str {
+        "frost_dkg_commitment"
+    }
+}
+
+impl net::Message for DkgShare {
+    fn name() -> &'static str {
+        "frost_dkg_share"
+    }
+}
+
+/// Coordinator asking the participants to commit to nonces for signing a
+/// message. Sessions are identified by a random id chosen by the
+/// coordinator.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SignRequest {
+    pub session: u64,
+    pub message: Vec<u8>,
+}
+
+impl net::Message for SignRequest {
+    fn name() -> &'static str {
+        "frost_sign_request"
+    }
+}
+
+/// Participant answering a [`SignRequest`] with its nonce commitment.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SignCommitment {
+    pub session: u64,
+    pub commitment: SigningCommitment,
+}
+
+impl net::Message for SignCommitment {
+    fn name() -> &'static str {
+        "frost_sign_commitment"
+    }
+}
+
+/// Coordinator sending the commitments of the signers it picked, sorted
+/// by signer index, asking them for their signature shares.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SignPackage {
+    pub session: u64,
+    pub message: Vec<u8>,
+    pub commitments: Vec<SigningCommitment>,
+}
+
+impl net::Message for SignPackage {
+    fn name() -> &'static str {
+        "frost_sign_package"
+    }
+}
+
+/// Signer answering a [`SignPackage`] with its signature share.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SignShare {
+    pub session: u64,
+    pub share: SignatureShare,
+}
+
+impl net::Message for SignShare {
+    fn name() -> &'static str {
+        "frost_sign_share"
+    }
+}
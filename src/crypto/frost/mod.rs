@@ -0,0 +1,163 @@
+//! FROST threshold Schnorr signatures over pallas.
+//!
+//! A federation of `n` participants runs a distributed key generation
+//! ([`dkg`]), after which each of them holds a [`KeyShare`] of a group key
+//! that no participant knows on its own. Any `t` of them can then [`sign`]
+//! under the group key, so funds custodied by the group key survive up to
+//! `n - t` lost or unavailable participants, and `t - 1` colluding ones
+//! can't move them. Signatures are ordinary Schnorr signatures, verified
+//! with [`SchnorrPublic`](super::schnorr::SchnorrPublic).
+use std::path::Path;
+
+use pasta_curves::{
+    group::{ff::Field, Group},
+    pallas,
+};
+
+use crate::{
+    crypto::keypair::PublicKey,
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    Error::FrostInvalidThreshold,
+    Result,
+};
+
+/// Distributed key generation
+pub mod dkg;
+
+/// Threshold signing rounds
+pub mod sign;
+
+/// P2P messages of the DKG and signing rounds
+#[cfg(feature = "net")]
+pub mod message;
+
+/// Index of a participant, from 1 to the number of participants.
+pub type ParticipantIndex = u16;
+
+/// Federation of participants holding a group key: the number of them
+/// needed to sign, and their long-term public keys. The index of a
+/// participant is the position of its key, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct FrostParams {
+    pub threshold: u16,
+    pub participants: Vec<PublicKey>,
+}
+
+impl FrostParams {
+    pub fn new(threshold: u16, participants: Vec<PublicKey>) -> Result<Self> {
+        let n = participants.len();
+        if threshold == 0 || threshold as usize > n || n > ParticipantIndex::MAX as usize {
+            return Err(FrostInvalidThreshold(threshold, n))
+        }
+
+        Ok(Self { threshold, participants })
+    }
+
+    /// Index of the participant with the given long-term public key.
+    pub fn index_of(&self, public: &PublicKey) -> Option<ParticipantIndex> {
+        self.participants.iter().position(|p| p == public).map(|i| i as ParticipantIndex + 1)
+    }
+
+    /// Long-term public key of the participant with the given index.
+    pub fn public_of(&self, index: ParticipantIndex) -> Option<&PublicKey> {
+        (index as usize).checked_sub(1).and_then(|i| self.participants.get(i))
+    }
+}
+
+/// Public part of a group key, enough to check signature shares and
+/// aggregate them.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct GroupKey {
+    pub threshold: u16,
+    /// Public key verifying the signatures of the group
+    pub public: PublicKey,
+    /// Public keys of the shares of the participants, by index
+    pub verifying_shares: Vec<PublicKey>,
+}
+
+impl GroupKey {
+    /// Public key of the share of the participant with the given index.
+    pub fn verifying_share(&self, index: ParticipantIndex) -> Option<&PublicKey> {
+        (index as usize).checked_sub(1).and_then(|i| self.verifying_shares.get(i))
+    }
+}
+
+/// Share of a group key held by a participant, the outcome of the DKG.
+/// It is stored by the participant for as long as the group key is in
+/// use, and must be kept as secret as a private key.
+#[derive(Clone, SerialEncodable, SerialDecodable)]
+pub struct KeyShare {
+    pub index: ParticipantIndex,
+    pub(crate) secret: pallas::Scalar,
+    pub group: GroupKey,
+}
+
+impl KeyShare {
+    /// Write the key share to provided path.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serialize(self))?;
+        Ok(())
+    }
+
+    /// Read a key share from provided path.
+    pub fn load(path: &Path) -> Result<Self> {
+        deserialize(&std::fs::read(path)?)
+    }
+}
+
+/// Point at which the polynomials of the DKG are evaluated for the
+/// participant with the given index.
+fn participant_scalar(index: ParticipantIndex) -> pallas::Scalar {
+    pallas::Scalar::from(index as u64)
+}
+
+/// Evaluate the polynomial with provided coefficients, lowest degree first.
+fn evaluate(coefficients: &[pallas::Scalar], x: pallas::Scalar) -> pallas::Scalar {
+    coefficients.iter().rev().fold(pallas::Scalar::zero(), |acc, c| acc * x + c)
+}
+
+/// Evaluate the polynomial committed to by provided coefficient
+/// commitments, lowest degree first.
+fn evaluate_commitments(commitments: &[pallas::Point], x: pallas::Scalar) -> pallas::Point {
+    commitments.iter().rev().fold(pallas::Point::identity(), |acc, c| acc * x + c)
+}
+
+/// Lagrange coefficient of a signer for interpolating at 0 over the set
+/// of signers.
+fn lagrange_coefficient(index: ParticipantIndex, signers: &[ParticipantIndex]) -> pallas::Scalar {
+    let x = participant_scalar(index);
+    let mut numerator = pallas::Scalar::one();
+    let mut denominator = pallas::Scalar::one();
+
+    for other in signers.iter().filter(|other| **other != index) {
+        let x_other = participant_scalar(*other);
+        numerator *= x_other;
+        denominator *= x_other - x;
+    }
+
+    // Signers are distinct, so the denominator is never zero
+    numerator * denominator.invert().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_interpolation() {
+        let coefficients: Vec<pallas::Scalar> =
+            (0..3).map(|_| pallas::Scalar::random(&mut OsRng)).collect();
+
+        // Any 3 evaluations interpolate the constant term
+        let signers = [1, 4, 5];
+        let secret = signers.iter().fold(pallas::Scalar::zero(), |acc, i| {
+            acc + evaluate(&coefficients, participant_scalar(*i)) *
+                lagrange_coefficient(*i, &signers)
+        });
+        assert_eq!(secret, coefficients[0]);
+
+        assert!(FrostParams::new(0, vec![]).is_err());
+        assert!(FrostParams::new(3, vec![PublicKey(pallas::Point::generator()); 2]).is_err());
+    }
+}
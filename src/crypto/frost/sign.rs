@@ -0,0 +1,287 @@
+use halo2_gadgets::ecc::chip::FixedPoint;
+use pasta_curves::{
+    group::{ff::Field, Group, GroupEncoding},
+    pallas,
+};
+use rand::rngs::OsRng;
+
+use super::{lagrange_coefficient, GroupKey, KeyShare, ParticipantIndex};
+use crate::{
+    crypto::{
+        constants::{NullifierK, DRK_FROST_BINDING_DOMAIN, DRK_SCHNORR_DOMAIN},
+        schnorr::{SchnorrPublic, Signature},
+        util::hash_to_scalar,
+    },
+    util::serial::{serialize, SerialDecodable, SerialEncodable},
+    Error::{
+        FrostInvalidCommitment, FrostInvalidShare, FrostMissingParticipant, FrostNotEnoughSigners,
+        FrostUnknownParticipant,
+    },
+    Result,
+};
+
+/// Nonces of a signer for a single signing session. They are consumed by
+/// signing, as signing twice with them would leak the key share.
+pub struct SigningNonces {
+    hiding: pallas::Scalar,
+    binding: pallas::Scalar,
+}
+
+impl SigningNonces {
+    /// Draw the nonces of the signer with the given index, returning them
+    /// along with the commitment to send to the coordinator.
+    pub fn new(index: ParticipantIndex) -> (Self, SigningCommitment) {
+        let nonces = Self {
+            hiding: pallas::Scalar::random(&mut OsRng),
+            binding: pallas::Scalar::random(&mut OsRng),
+        };
+        let commitment = nonces.commitment(index);
+        (nonces, commitment)
+    }
+
+    fn commitment(&self, index: ParticipantIndex) -> SigningCommitment {
+        let generator = NullifierK.generator();
+        SigningCommitment {
+            index,
+            hiding: generator * self.hiding,
+            binding: generator * self.binding,
+        }
+    }
+}
+
+/// Commitment of a signer to its nonces, first round of the signing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SigningCommitment {
+    pub index: ParticipantIndex,
+    pub hiding: pallas::Point,
+    pub binding: pallas::Point,
+}
+
+/// Share of a signer in the group signature, second round of the signing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SignatureShare {
+    pub index: ParticipantIndex,
+    pub response: pallas::Scalar,
+}
+
+/// Values derived from the commitments of the signers and the message,
+/// identical for every signer of a session.
+struct Session {
+    signers: Vec<ParticipantIndex>,
+    /// Binding factors of the signers, binding each of them to the
+    /// commitments of all the others
+    binding_factors: Vec<pallas::Scalar>,
+    /// Sum of the nonce commitments of the signers
+    commit: pallas::Point,
+    challenge: pallas::Scalar,
+}
+
+impl Session {
+    /// Commitments must be sorted by signer index, with at least as many
+    /// signers as the threshold.
+    fn new(group: &GroupKey, commitments: &[SigningCommitment], message: &[u8]) -> Result<Self> {
+        if commitments.len() < group.threshold as usize {
+            return Err(FrostNotEnoughSigners(commitments.len(), group.threshold))
+        }
+
+        for pair in commitments.windows(2) {
+            if pair[0].index >= pair[1].index {
+                return Err(FrostInvalidCommitment(pair[1].index))
+            }
+        }
+        for commitment in commitments {
+            if group.verifying_share(commitment.index).is_none() {
+                return Err(FrostUnknownParticipant)
+            }
+        }
+
+        let encoded = serialize(&commitments.to_vec());
+        let binding_factors: Vec<pallas::Scalar> = commitments
+            .iter()
+            .map(|c| {
+                let mut data = c.index.to_le_bytes().to_vec();
+                data.extend_from_slice(message);
+                hash_to_scalar(DRK_FROST_BINDING_DOMAIN, &data, &encoded)
+            })
+            .collect();
+
+        let commit = commitments
+            .iter()
+            .zip(&binding_factors)
+            .fold(pallas::Point::identity(), |acc, (c, rho)| acc + c.hiding + c.binding * rho);
+
+        // Same challenge as single-signer signatures, so the result
+        // verifies as one
+        let challenge = hash_to_scalar(DRK_SCHNORR_DOMAIN, &commit.to_bytes(), message);
+
+        Ok(Self {
+            signers: commitments.iter().map(|c| c.index).collect(),
+            binding_factors,
+            commit,
+            challenge,
+        })
+    }
+
+    fn position(&self, index: ParticipantIndex) -> Result<usize> {
+        match self.signers.iter().position(|i| *i == index) {
+            Some(p) => Ok(p),
+            None => Err(FrostMissingParticipant(index)),
+        }
+    }
+}
+
+/// Sign the message with our key share, given the commitments of all
+/// signers of the session, ours included, sorted by signer index.
+pub fn sign(
+    share: &KeyShare,
+    nonces: SigningNonces,
+    commitments: &[SigningCommitment],
+    message: &[u8],
+) -> Result<SignatureShare> {
+    let session = Session::new(&share.group, commitments, message)?;
+    let position = session.position(share.index)?;
+    if commitments[position] != nonces.commitment(share.index) {
+        return Err(FrostInvalidCommitment(share.index))
+    }
+
+    let lambda = lagrange_coefficient(share.index, &session.signers);
+    let response = nonces.hiding +
+        nonces.binding * session.binding_factors[position] +
+        lambda * share.secret * session.challenge;
+
+    Ok(SignatureShare { index: share.index, response })
+}
+
+/// Verify the signature share of a signer, to find out which signer
+/// misbehaved when the group signature is invalid.
+pub fn verify_share(
+    group: &GroupKey,
+    commitments: &[SigningCommitment],
+    message: &[u8],
+    share: &SignatureShare,
+) -> bool {
+    let session = match Session::new(group, commitments, message) {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    let position = match session.position(share.index) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    let public = group.verifying_share(share.index).unwrap();
+    let lambda = lagrange_coefficient(share.index, &session.signers);
+    let commitment = &commitments[position];
+
+    NullifierK.generator() * share.response ==
+        commitment.hiding +
+            commitment.binding * session.binding_factors[position] +
+            public.0 * (lambda * session.challenge)
+}
+
+/// Aggregate the signature shares of all signers of the session into the
+/// group signature. Fails naming the first signer with an invalid share,
+/// if the signature doesn't verify.
+pub fn aggregate(
+    group: &GroupKey,
+    commitments: &[SigningCommitment],
+    shares: &[SignatureShare],
+    message: &[u8],
+) -> Result<Signature> {
+    let session = Session::new(group, commitments, message)?;
+    for index in &session.signers {
+        if !shares.iter().any(|share| share.index == *index) {
+            return Err(FrostMissingParticipant(*index))
+        }
+    }
+
+    let response = session
+        .signers
+        .iter()
+        .filter_map(|index| shares.iter().find(|share| share.index == *index))
+        .fold(pallas::Scalar::zero(), |acc, share| acc + share.response);
+    let signature = Signature { commit: session.commit, response };
+
+    if !group.public.verify(message, &signature) {
+        for share in shares {
+            if !verify_share(group, commitments, message, share) {
+                return Err(FrostInvalidShare(share.index))
+            }
+        }
+    }
+
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{dkg::Dkg, FrostParams},
+        *,
+    };
+    use crate::crypto::keypair::{PublicKey, SecretKey};
+
+    #[test]
+    fn test_frost() {
+        // 2-of-3 federation
+        let secrets: Vec<SecretKey> = (0..3).map(|_| SecretKey::random(&mut OsRng)).collect();
+        let publics = secrets.iter().map(|s| PublicKey::from_secret(*s)).collect();
+        let params = FrostParams::new(2, publics).unwrap();
+
+        let mut dkgs = vec![];
+        let mut broadcast = vec![];
+        for secret in &secrets {
+            let (dkg, commitment) = Dkg::new(params.clone(), *secret).unwrap();
+            dkgs.push(dkg);
+            broadcast.push(commitment);
+        }
+        for dkg in dkgs.iter_mut() {
+            for commitment in broadcast.iter().filter(|c| c.index != dkg.index()) {
+                dkg.receive_commitment(commitment.clone()).unwrap();
+            }
+        }
+
+        // Tampered proofs of knowledge are rejected
+        let mut forged = broadcast[1].clone();
+        forged.proof.response += pallas::Scalar::one();
+        assert!(dkgs[0].receive_commitment(forged).is_err());
+
+        let shares: Vec<_> = dkgs.iter().flat_map(|dkg| dkg.shares()).collect();
+        for dkg in dkgs.iter_mut() {
+            for share in shares.iter().filter(|s| s.to == dkg.index()) {
+                dkg.receive_share(share.clone()).unwrap();
+            }
+        }
+        let key_shares: Vec<KeyShare> = dkgs.into_iter().map(|dkg| dkg.finish().unwrap()).collect();
+        let group = key_shares[0].group.clone();
+        assert!(key_shares.iter().all(|share| share.group == group));
+
+        // Participants 1 and 3 sign
+        let message = b"Foo bar";
+        let signers = [&key_shares[0], &key_shares[2]];
+        let (nonces, commitments): (Vec<_>, Vec<_>) =
+            signers.iter().map(|share| SigningNonces::new(share.index)).unzip();
+        let sig_shares: Vec<SignatureShare> = signers
+            .iter()
+            .zip(nonces)
+            .map(|(share, nonces)| sign(share, nonces, &commitments, &message[..]).unwrap())
+            .collect();
+        for share in &sig_shares {
+            assert!(verify_share(&group, &commitments, &message[..], share));
+        }
+
+        // The group signature is an ordinary one
+        let signature = aggregate(&group, &commitments, &sig_shares, &message[..]).unwrap();
+        assert!(group.public.verify(&message[..], &signature));
+
+        let mut bad = sig_shares.clone();
+        bad[1].response += pallas::Scalar::one();
+        assert!(matches!(
+            aggregate(&group, &commitments, &bad, &message[..]),
+            Err(FrostInvalidShare(3))
+        ));
+
+        // A single signer is below the threshold
+        assert!(aggregate(&group, &commitments[..1], &sig_shares[..1], &message[..]).is_err());
+    }
+}
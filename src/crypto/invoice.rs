@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use bech32::{FromBase32, ToBase32, Variant};
+
+use crate::{
+    crypto::{
+        address::PaymentAddress,
+        keypair::{PublicKey, SecretKey},
+        schnorr::{SchnorrPublic, SchnorrSecret, Signature},
+        types::DrkTokenId,
+    },
+    util::{
+        serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+        time::Timestamp,
+    },
+    Error::{InvalidInvoice, InvoiceExpired},
+    Result,
+};
+
+/// Human readable part of encoded invoices.
+pub const INVOICE_HRP: &str = "drkinv";
+
+/// URI scheme invoices are prefixed with in QR codes.
+pub const INVOICE_URI_SCHEME: &str = "darkfi";
+
+/// Prefixed to the signed payload, so invoice signatures can't be passed
+/// off as transaction signatures.
+const INVOICE_SIGNATURE_DOMAIN: &[u8] = b"DarkFi:Invoice";
+
+/// Terms of a payment request: `amount` of `token_id` to pay to `address`
+/// before `expiry`, with `memo` as the memo of the payment.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct PaymentRequest {
+    /// One-time address of the recipient, so payments of different
+    /// invoices can't be linked
+    pub address: PaymentAddress,
+    pub token_id: DrkTokenId,
+    pub amount: u64,
+    pub memo: String,
+    pub expiry: Timestamp,
+}
+
+impl PaymentRequest {
+    /// Sign the request with the spend secret key of its address.
+    pub fn sign(self, secret: &SecretKey) -> Result<Invoice> {
+        if PublicKey::from_secret(*secret) != self.address.public {
+            return Err(InvalidInvoice("Signing key doesn't match the address".to_string()))
+        }
+
+        let signature = secret.sign(&self.signed_payload());
+        Ok(Invoice { request: self, signature })
+    }
+
+    fn signed_payload(&self) -> Vec<u8> {
+        [INVOICE_SIGNATURE_DOMAIN, &serialize(self)].concat()
+    }
+}
+
+/// Payment request signed by its recipient, proving it controls the
+/// address being paid to.
+///
+/// Invoices are shared as bech32m strings, or as `darkfi:` URIs in QR
+/// codes, which are uppercased to fit the QR alphanumeric mode.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct Invoice {
+    pub request: PaymentRequest,
+    pub signature: Signature,
+}
+
+impl Invoice {
+    /// Check the invoice signature, and that it did not expire.
+    pub fn verify(&self) -> Result<()> {
+        let payload = self.request.signed_payload();
+        if !self.request.address.public.verify(&payload, &self.signature) {
+            return Err(InvalidInvoice("Invalid signature".to_string()))
+        }
+        if self.request.expiry <= Timestamp::current_time() {
+            return Err(InvoiceExpired)
+        }
+        Ok(())
+    }
+
+    /// Payload of a QR code for the invoice.
+    pub fn to_qr_payload(&self) -> String {
+        format!("{}:{}", INVOICE_URI_SCHEME, self).to_uppercase()
+    }
+}
+
+impl std::fmt::Display for Invoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match bech32::encode(INVOICE_HRP, serialize(self).to_base32(), Variant::Bech32m) {
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => Err(std::fmt::Error),
+        }
+    }
+}
+
+impl FromStr for Invoice {
+    type Err = crate::Error;
+
+    /// Parse an invoice string or QR payload. The signature is checked,
+    /// but not the expiry, so expired invoices can still be inspected.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_lowercase();
+        let s = s.strip_prefix(&format!("{}:", INVOICE_URI_SCHEME)).unwrap_or(&s);
+
+        let (hrp, data, variant) = match bech32::decode(s) {
+            Ok(v) => v,
+            Err(e) => return Err(InvalidInvoice(e.to_string())),
+        };
+        if hrp != INVOICE_HRP || variant != Variant::Bech32m {
+            return Err(InvalidInvoice("Not an invoice".to_string()))
+        }
+
+        let bytes = match Vec::<u8>::from_base32(&data) {
+            Ok(v) => v,
+            Err(e) => return Err(InvalidInvoice(e.to_string())),
+        };
+        let invoice: Self = deserialize(&bytes)?;
+
+        match invoice.verify() {
+            Ok(()) | Err(InvoiceExpired) => Ok(invoice),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn test_invoice() {
+        let secret = SecretKey::random(&mut OsRng);
+        let request = PaymentRequest {
+            address: PaymentAddress::new(&secret),
+            token_id: pallas::Base::from(42),
+            amount: 1000,
+            memo: "Order #1".to_string(),
+            expiry: Timestamp(Timestamp::current_time().0 + 3600),
+        };
+
+        let other = SecretKey::random(&mut OsRng);
+        assert!(request.clone().sign(&other).is_err());
+
+        let invoice = request.sign(&secret).unwrap();
+        invoice.verify().unwrap();
+
+        let encoded = invoice.to_string();
+        assert!(encoded.starts_with("drkinv1"));
+        assert_eq!(Invoice::from_str(&encoded).unwrap(), invoice);
+        assert_eq!(Invoice::from_str(&invoice.to_qr_payload()).unwrap(), invoice);
+
+        // Tampered terms don't verify
+        let mut tampered = invoice.clone();
+        tampered.request.amount = 1;
+        assert!(Invoice::from_str(&tampered.to_string()).is_err());
+
+        // Expired invoices parse, but don't verify
+        let mut expired = invoice.request;
+        expired.expiry = Timestamp(0);
+        let expired = expired.sign(&secret).unwrap();
+        assert!(Invoice::from_str(&expired.to_string()).is_ok());
+        assert!(expired.verify().is_err());
+    }
+}
@@ -2,7 +2,7 @@ use std::{convert::TryFrom, io, str::FromStr};
 
 use halo2_gadgets::ecc::chip::FixedPoint;
 use pasta_curves::{
-    arithmetic::CurveAffine,
+    arithmetic::{CurveAffine, FieldExt},
     group::{
         ff::{Field, PrimeField},
         Curve, Group, GroupEncoding,
@@ -12,7 +12,11 @@ use pasta_curves::{
 use rand::RngCore;
 
 use crate::{
-    crypto::{address::Address, constants::NullifierK, util::mod_r_p},
+    crypto::{
+        address::Address,
+        constants::NullifierK,
+        util::{mod_r_p, poseidon_hash},
+    },
     util::serial::{Decodable, Encodable, ReadExt, SerialDecodable, SerialEncodable, WriteExt},
     Error, Result,
 };
@@ -37,6 +41,11 @@ impl Keypair {
     }
 }
 
+/// Domain separator for the derivation of diversified keys ("diversif")
+const DIVERSIFIER_DOMAIN: u64 = 0x6469766572736966;
+/// Domain separator for the derivation of incoming viewing keys ("viewkey_")
+const VIEW_KEY_DOMAIN: u64 = 0x766965776b65795f;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, SerialDecodable, SerialEncodable)]
 pub struct SecretKey(pub pallas::Base);
 
@@ -56,6 +65,29 @@ impl SecretKey {
             None => Err(Error::SecretKeyFromBytes),
         }
     }
+
+    /// Derive the secret key behind the diversified address with the given
+    /// index. Without this secret key, the public keys of diversified
+    /// addresses can't be linked to each other nor to their parent.
+    pub fn diversify(&self, diversifier: u64) -> Self {
+        let tweak = poseidon_hash::<3>([
+            pallas::Base::from(DIVERSIFIER_DOMAIN),
+            self.0,
+            pallas::Base::from(diversifier),
+        ]);
+        Self(self.0 + tweak)
+    }
+
+    /// Derive the incoming viewing key of this spend key. Notes of coins
+    /// sent to the key's [`PaymentAddress`] are encrypted to it, so it can
+    /// be handed to an auditor: it decrypts incoming notes, but it can't be
+    /// used to recover the spend key, spend the coins or compute their
+    /// nullifiers.
+    ///
+    /// [`PaymentAddress`]: crate::crypto::address::PaymentAddress
+    pub fn view_key(&self) -> Self {
+        Self(poseidon_hash::<2>([pallas::Base::from(VIEW_KEY_DOMAIN), self.0]))
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, SerialDecodable, SerialEncodable)]
@@ -116,6 +148,101 @@ impl TryFrom<Address> for PublicKey {
     }
 }
 
+/// Personalization of the hash deriving the master key from a seed
+const HD_MASTER_PERSONALIZATION: &[u8; 16] = b"DarkFi_HD_Master";
+/// Personalization of the hash deriving a child key from its parent
+const HD_CHILD_PERSONALIZATION: &[u8; 16] = b"DarkFi_HD_Child_";
+/// Personalization of the hash expanding key bytes into a [`SecretKey`]
+const HD_SECRET_PERSONALIZATION: &[u8; 16] = b"DarkFi_HD_Secret";
+/// Number of words of generated mnemonics
+const MNEMONIC_WORDS: usize = 24;
+
+/// Generate a new BIP39 mnemonic, the single backup of all the keys
+/// derived from it with [`ExtendedSecretKey`].
+pub fn generate_mnemonic(mut rng: impl RngCore) -> String {
+    let mut entropy = [0u8; MNEMONIC_WORDS / 3 * 4];
+    rng.fill_bytes(&mut entropy);
+    bip39::Mnemonic::from_entropy(&entropy).unwrap().to_string()
+}
+
+/// Compute the BIP39 seed of a mnemonic, protected by an optional passphrase.
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> Result<[u8; 64]> {
+    match bip39::Mnemonic::parse_normalized(mnemonic) {
+        Ok(v) => Ok(v.to_seed_normalized(passphrase)),
+        Err(e) => Err(Error::InvalidMnemonic(e.to_string())),
+    }
+}
+
+/// Node of a hierarchical deterministic key tree, in the style of BIP32.
+/// Pallas keys have no public child derivation here, so all child keys
+/// are hardened: deriving one requires the parent's secret key bytes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ExtendedSecretKey {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedSecretKey {
+    /// Derive the root of the key tree from a seed, e.g. from [`mnemonic_to_seed`].
+    pub fn master(seed: &[u8]) -> Self {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(64)
+            .personal(HD_MASTER_PERSONALIZATION)
+            .hash(seed);
+        Self::from_hash(hash.as_array())
+    }
+
+    fn from_hash(hash: &[u8; 64]) -> Self {
+        let mut key = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        key.copy_from_slice(&hash[..32]);
+        chain_code.copy_from_slice(&hash[32..]);
+        Self { key, chain_code }
+    }
+
+    /// Derive the child key with the given index.
+    pub fn derive_child(&self, index: u32) -> Self {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(64)
+            .key(&self.chain_code)
+            .personal(HD_CHILD_PERSONALIZATION)
+            .to_state()
+            .update(&self.key)
+            .update(&index.to_le_bytes())
+            .finalize();
+        Self::from_hash(hash.as_array())
+    }
+
+    /// Derive the key at the given path below this one, e.g. `m/0/3`.
+    /// Indexes may be marked hardened with `'`, though all of them are.
+    pub fn derive_path(&self, path: &str) -> Result<Self> {
+        let mut components = path.split('/');
+        if components.next() != Some("m") {
+            return Err(Error::InvalidDerivationPath(path.to_string()))
+        }
+
+        let mut key = *self;
+        for component in components {
+            let index = match component.trim_end_matches('\'').parse::<u32>() {
+                Ok(v) => v,
+                Err(_) => return Err(Error::InvalidDerivationPath(path.to_string())),
+            };
+            key = key.derive_child(index);
+        }
+
+        Ok(key)
+    }
+
+    /// The secret key of this node of the tree.
+    pub fn secret_key(&self) -> SecretKey {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(64)
+            .personal(HD_SECRET_PERSONALIZATION)
+            .hash(&self.key);
+        SecretKey(pallas::Base::from_bytes_wide(hash.as_array()))
+    }
+}
+
 impl Encodable for pallas::Base {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         s.write_slice(&self.to_repr()[..])?;
@@ -315,4 +442,24 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_hd_derivation() -> Result<()> {
+        let mnemonic = generate_mnemonic(&mut rand::rngs::OsRng);
+        assert_eq!(mnemonic.split_whitespace().count(), MNEMONIC_WORDS);
+        assert!(mnemonic_to_seed("not a mnemonic", "").is_err());
+
+        let master = ExtendedSecretKey::master(&mnemonic_to_seed(&mnemonic, "")?);
+        let other = ExtendedSecretKey::master(&mnemonic_to_seed(&mnemonic, "passphrase")?);
+        assert_ne!(master, other);
+
+        let child = master.derive_child(0).derive_child(3);
+        assert_eq!(master.derive_path("m/0'/3'")?, child);
+        assert_eq!(master.derive_path("m/0/3")?, child);
+        assert_ne!(master.derive_child(1).secret_key(), master.derive_child(0).secret_key());
+        assert!(master.derive_path("0/3").is_err());
+        assert!(master.derive_path("m/x").is_err());
+
+        Ok(())
+    }
 }
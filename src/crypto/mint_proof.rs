@@ -38,6 +38,7 @@ impl MintRevealedValues {
         serial: DrkSerial,
         spend_hook: DrkSpendHook,
         user_data: DrkUserData,
+        lock_slot: u64,
         coin_blind: DrkCoinBlind,
         public_key: PublicKey,
     ) -> Self {
@@ -46,7 +47,7 @@ impl MintRevealedValues {
 
         let coords = public_key.0.to_affine().coordinates().unwrap();
 
-        let coin = poseidon_hash::<8>([
+        let coin = poseidon_hash::<9>([
             *coords.x(),
             *coords.y(),
             DrkValue::from(value),
@@ -54,6 +55,7 @@ impl MintRevealedValues {
             serial,
             spend_hook,
             user_data,
+            DrkValue::from(lock_slot),
             coin_blind,
         ]);
 
@@ -84,6 +86,7 @@ pub fn create_mint_proof(
     serial: DrkSerial,
     spend_hook: DrkSpendHook,
     user_data: DrkUserData,
+    lock_slot: u64,
     coin_blind: DrkCoinBlind,
     public_key: PublicKey,
 ) -> Result<(Proof, MintRevealedValues)> {
@@ -95,6 +98,7 @@ pub fn create_mint_proof(
         serial,
         spend_hook,
         user_data,
+        lock_slot,
         coin_blind,
         public_key,
     );
@@ -110,6 +114,7 @@ pub fn create_mint_proof(
         coin_blind: Value::known(coin_blind),
         spend_hook: Value::known(spend_hook),
         user_data: Value::known(user_data),
+        lock_slot: Value::known(DrkValue::from(lock_slot)),
         value_blind: Value::known(value_blind),
         token_blind: Value::known(token_blind),
     };
@@ -2,6 +2,8 @@ pub mod address;
 pub mod coin;
 pub mod constants;
 pub mod diffie_hellman;
+pub mod frost;
+pub mod invoice;
 pub mod keypair;
 //pub mod loader;
 pub mod burn_proof;
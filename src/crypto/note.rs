@@ -21,6 +21,8 @@ pub struct Note {
     pub coin_blind: DrkCoinBlind,
     pub value_blind: DrkValueBlind,
     pub token_blind: DrkValueBlind,
+    /// Slot until which the coin can't be spent, 0 if it isn't locked
+    pub lock_slot: u64,
     pub memo: Vec<u8>,
 }
 
@@ -84,6 +86,7 @@ mod tests {
             coin_blind: DrkCoinBlind::random(&mut OsRng),
             value_blind: DrkValueBlind::random(&mut OsRng),
             token_blind: DrkValueBlind::random(&mut OsRng),
+            lock_slot: 0,
             memo: vec![32, 223, 231, 3, 1, 1],
         };
 
@@ -95,5 +98,12 @@ mod tests {
         assert_eq!(note.token_id, note2.token_id);
         assert_eq!(note.token_blind, note2.token_blind);
         assert_eq!(note.memo, note2.memo);
+
+        // Notes encrypted to the view key can't be decrypted with the spend key
+        let view_public = PublicKey::from_secret(keypair.secret.view_key());
+        let encrypted_note = note.encrypt(&view_public).unwrap();
+        assert!(encrypted_note.decrypt(&keypair.secret).is_err());
+        let note3 = encrypted_note.decrypt(&keypair.secret.view_key()).unwrap();
+        assert_eq!(note, note3);
     }
 }
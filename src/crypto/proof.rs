@@ -150,6 +150,7 @@ mod tests {
             serial,
             spend_hook,
             user_data,
+            0,
             coin_blind,
             public_key,
         )?;
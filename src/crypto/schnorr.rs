@@ -1,26 +1,28 @@
 use std::io;
 
 use halo2_gadgets::ecc::chip::FixedPoint;
+use halo2_proofs::arithmetic::best_multiexp;
 use pasta_curves::{
-    group::{ff::Field, Group, GroupEncoding},
+    group::{ff::Field, prime::PrimeCurveAffine, Curve, Group, GroupEncoding},
     pallas,
 };
 use rand::rngs::OsRng;
 
 use crate::{
     crypto::{
-        constants::{NullifierK, DRK_SCHNORR_DOMAIN},
+        constants::{NullifierK, DRK_MUSIG_KEY_DOMAIN, DRK_SCHNORR_DOMAIN},
         keypair::{PublicKey, SecretKey},
         util::{hash_to_scalar, mod_r_p},
     },
-    util::serial::{Decodable, Encodable},
+    util::serial::{Decodable, Encodable, SerialDecodable, SerialEncodable},
+    Error::{MuSigInvalidSignature, MuSigNonceMismatch, MuSigUnknownSigner},
     Result,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Signature {
-    commit: pallas::Point,
-    response: pallas::Scalar,
+    pub(crate) commit: pallas::Point,
+    pub(crate) response: pallas::Scalar,
 }
 
 impl Signature {
@@ -56,6 +58,216 @@ impl SchnorrPublic for PublicKey {
     }
 }
 
+/// Verify a batch of `(public key, message, signature)` tuples at once.
+/// Each verification equation is scaled by a random factor and all of them
+/// are summed up, so the whole batch is checked with a single multi-scalar
+/// multiplication. Returns `true` only if every signature is valid, but
+/// doesn't tell which one failed otherwise.
+pub fn batch_verify(batch: &[(&PublicKey, &[u8], &Signature)]) -> bool {
+    if batch.is_empty() {
+        return true
+    }
+
+    let mut coeffs = Vec::with_capacity(batch.len() * 2 + 1);
+    let mut points = Vec::with_capacity(batch.len() * 2);
+    let mut generator_coeff = pallas::Scalar::zero();
+
+    for (public, message, signature) in batch {
+        let factor = pallas::Scalar::random(&mut OsRng);
+        let challenge = hash_to_scalar(DRK_SCHNORR_DOMAIN, &signature.commit.to_bytes(), message);
+
+        // factor * (response * G - challenge * P - R) == 0
+        generator_coeff += factor * signature.response;
+        coeffs.push(-(factor * challenge));
+        points.push(public.0);
+        coeffs.push(-factor);
+        points.push(signature.commit);
+    }
+
+    let mut bases = vec![pallas::Affine::identity(); points.len()];
+    pallas::Point::batch_normalize(&points, &mut bases);
+    coeffs.push(generator_coeff);
+    bases.push(NullifierK.generator());
+
+    bool::from(best_multiexp(&coeffs, &bases).is_identity())
+}
+
+/// Key of an n-of-n MuSig signing group, aggregating the public keys of
+/// its signers. The aggregated key is an ordinary public key, and the
+/// signatures the group produces are ordinary Schnorr signatures, so
+/// neither can be told apart from single-signer ones.
+///
+/// Signing goes in three rounds over the same message:
+/// 1. Each signer draws a [`SecretNonce`] and sends the commitment to its
+///    [`PublicNonce`] to the others.
+/// 2. Once all commitments are in, each signer reveals its public nonce,
+///    checked with [`verify_nonces`].
+/// 3. Each signer sends its [`PartialSignature`], and anyone holding all
+///    of them can [`MuSigKey::aggregate`] the signature.
+///
+/// Committing to the nonces before revealing them keeps a signer from
+/// choosing its nonce after seeing the others'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuSigKey {
+    /// Public keys of the signers, sorted
+    signers: Vec<PublicKey>,
+    /// Key aggregation coefficients of the signers
+    coefficients: Vec<pallas::Scalar>,
+    public: PublicKey,
+}
+
+impl MuSigKey {
+    /// Aggregate the public keys of the signers, in any order. Each key
+    /// is weighted by a coefficient bound to the whole set, so no signer
+    /// can pick its key to cancel out the others'.
+    pub fn new(signers: &[PublicKey]) -> Self {
+        let mut signers = signers.to_vec();
+        signers.sort_by_key(|public| public.0.to_bytes());
+        signers.dedup();
+
+        let set: Vec<u8> = signers.iter().flat_map(|public| public.0.to_bytes()).collect();
+        let coefficients: Vec<pallas::Scalar> = signers
+            .iter()
+            .map(|public| hash_to_scalar(DRK_MUSIG_KEY_DOMAIN, &set, &public.0.to_bytes()))
+            .collect();
+
+        let public = signers
+            .iter()
+            .zip(&coefficients)
+            .fold(pallas::Point::identity(), |acc, (public, coeff)| acc + public.0 * coeff);
+
+        Self { signers, coefficients, public: PublicKey(public) }
+    }
+
+    /// Aggregated public key, verifying the signatures of the group.
+    pub fn public(&self) -> PublicKey {
+        self.public
+    }
+
+    /// Public keys of the signers, sorted.
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+
+    fn coefficient(&self, public: &PublicKey) -> Result<pallas::Scalar> {
+        match self.signers.iter().position(|signer| signer == public) {
+            Some(index) => Ok(self.coefficients[index]),
+            None => Err(MuSigUnknownSigner),
+        }
+    }
+
+    /// Challenge of the signature over the message, committing to the
+    /// sum of the nonces of all signers.
+    fn challenge(nonces: &[PublicNonce], message: &[u8]) -> (pallas::Point, pallas::Scalar) {
+        let commit = nonces.iter().fold(pallas::Point::identity(), |acc, nonce| acc + nonce.0);
+        (commit, hash_to_scalar(DRK_SCHNORR_DOMAIN, &commit.to_bytes(), message))
+    }
+
+    /// Sign the message with our share, given the public nonces of all
+    /// signers, ours included. The secret nonce is consumed, as signing
+    /// twice with it would leak the secret key.
+    pub fn partial_sign(
+        &self,
+        secret: &SecretKey,
+        nonce: SecretNonce,
+        nonces: &[PublicNonce],
+        message: &[u8],
+    ) -> Result<PartialSignature> {
+        let coefficient = self.coefficient(&PublicKey::from_secret(*secret))?;
+        let (_, challenge) = Self::challenge(nonces, message);
+        Ok(PartialSignature(nonce.0 + challenge * coefficient * mod_r_p(secret.0)))
+    }
+
+    /// Verify the partial signature of a signer, to find out which signer
+    /// misbehaved when the aggregated signature is invalid.
+    pub fn verify_partial(
+        &self,
+        public: &PublicKey,
+        nonce: &PublicNonce,
+        nonces: &[PublicNonce],
+        message: &[u8],
+        partial: &PartialSignature,
+    ) -> bool {
+        let coefficient = match self.coefficient(public) {
+            Ok(c) => c,
+            Err(_) => return false,
+        };
+        let (_, challenge) = Self::challenge(nonces, message);
+        NullifierK.generator() * partial.0 == nonce.0 + public.0 * (challenge * coefficient)
+    }
+
+    /// Aggregate the partial signatures of all signers over the message
+    /// into a signature verified by the aggregated key.
+    pub fn aggregate(
+        &self,
+        nonces: &[PublicNonce],
+        partials: &[PartialSignature],
+        message: &[u8],
+    ) -> Result<Signature> {
+        if nonces.len() != self.signers.len() || partials.len() != self.signers.len() {
+            return Err(MuSigInvalidSignature)
+        }
+
+        let (commit, _) = Self::challenge(nonces, message);
+        let response = partials.iter().fold(pallas::Scalar::zero(), |acc, p| acc + p.0);
+        let signature = Signature { commit, response };
+
+        if !self.public.verify(message, &signature) {
+            return Err(MuSigInvalidSignature)
+        }
+
+        Ok(signature)
+    }
+}
+
+/// Secret nonce of a signer, for a single MuSig signing session.
+pub struct SecretNonce(pallas::Scalar);
+
+impl SecretNonce {
+    pub fn random() -> Self {
+        Self(pallas::Scalar::random(&mut OsRng))
+    }
+
+    /// Public nonce, revealed to the other signers once they all
+    /// committed to theirs.
+    pub fn public(&self) -> PublicNonce {
+        PublicNonce(NullifierK.generator() * self.0)
+    }
+}
+
+/// Public nonce of a signer, for a single MuSig signing session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct PublicNonce(pub pallas::Point);
+
+impl PublicNonce {
+    /// Commitment to the nonce, sent to the other signers first.
+    pub fn commitment(&self) -> NonceCommitment {
+        NonceCommitment(blake3::hash(&self.0.to_bytes()))
+    }
+}
+
+/// Commitment to the public nonce of a signer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct NonceCommitment(pub blake3::Hash);
+
+/// Check the revealed public nonces against the commitments the signers
+/// sent first, in the same order.
+pub fn verify_nonces(commitments: &[NonceCommitment], nonces: &[PublicNonce]) -> Result<()> {
+    if commitments.len() != nonces.len() {
+        return Err(MuSigNonceMismatch)
+    }
+
+    if commitments.iter().zip(nonces).any(|(commitment, nonce)| &nonce.commitment() != commitment) {
+        return Err(MuSigNonceMismatch)
+    }
+
+    Ok(())
+}
+
+/// Share of a signer in a MuSig signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct PartialSignature(pub pallas::Scalar);
+
 impl Encodable for Signature {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -129,4 +341,67 @@ mod tests {
         let public = PublicKey::from_secret(secret);
         assert!(public.verify(&message[..], &signature));
     }
+
+    #[test]
+    fn test_schnorr_batch() {
+        let secrets: Vec<SecretKey> = (0..4).map(|_| SecretKey::random(&mut OsRng)).collect();
+        let publics: Vec<PublicKey> = secrets.iter().map(|s| PublicKey::from_secret(*s)).collect();
+        let messages: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 32]).collect();
+        let signatures: Vec<Signature> =
+            secrets.iter().zip(&messages).map(|(s, m)| s.sign(m)).collect();
+
+        let batch: Vec<(&PublicKey, &[u8], &Signature)> = publics
+            .iter()
+            .zip(&messages)
+            .zip(&signatures)
+            .map(|((p, m), s)| (p, &m[..], s))
+            .collect();
+        assert!(batch_verify(&batch));
+
+        // A single wrong message fails the whole batch
+        let mut bad = batch.clone();
+        bad[2].1 = &messages[3][..];
+        assert!(!batch_verify(&bad));
+    }
+
+    #[test]
+    fn test_musig() {
+        let message = b"Foo bar";
+        let secrets: Vec<SecretKey> = (0..3).map(|_| SecretKey::random(&mut OsRng)).collect();
+        let publics: Vec<PublicKey> = secrets.iter().map(|s| PublicKey::from_secret(*s)).collect();
+        let key = MuSigKey::new(&publics);
+
+        // Signer order doesn't change the aggregated key
+        let reversed: Vec<PublicKey> = publics.iter().rev().copied().collect();
+        assert_eq!(MuSigKey::new(&reversed).public(), key.public());
+
+        let secret_nonces: Vec<SecretNonce> =
+            secrets.iter().map(|_| SecretNonce::random()).collect();
+        let nonces: Vec<PublicNonce> = secret_nonces.iter().map(|n| n.public()).collect();
+        let commitments: Vec<NonceCommitment> = nonces.iter().map(|n| n.commitment()).collect();
+        assert!(verify_nonces(&commitments, &nonces).is_ok());
+        assert!(verify_nonces(&commitments[1..], &nonces[..2]).is_err());
+
+        let partials: Vec<PartialSignature> = secrets
+            .iter()
+            .zip(secret_nonces)
+            .map(|(s, n)| key.partial_sign(s, n, &nonces, &message[..]).unwrap())
+            .collect();
+        for ((public, nonce), partial) in publics.iter().zip(&nonces).zip(&partials) {
+            assert!(key.verify_partial(public, nonce, &nonces, &message[..], partial));
+        }
+
+        // The aggregated signature is an ordinary one
+        let signature = key.aggregate(&nonces, &partials, &message[..]).unwrap();
+        assert!(key.public().verify(&message[..], &signature));
+
+        let mut bad = partials.clone();
+        bad[1] = PartialSignature(bad[1].0 + pallas::Scalar::one());
+        assert!(!key.verify_partial(&publics[1], &nonces[1], &nonces, &message[..], &bad[1]));
+        assert!(key.aggregate(&nonces, &bad, &message[..]).is_err());
+        assert!(key.aggregate(&nonces, &partials[..2], &message[..]).is_err());
+
+        let outsider = SecretKey::random(&mut OsRng);
+        assert!(key.partial_sign(&outsider, SecretNonce::random(), &nonces, &message[..]).is_err());
+    }
 }
@@ -1,7 +1,17 @@
 use group::ff::PrimeField;
 
-use super::types::DrkTokenId;
-use crate::{util::NetworkName, Error, Result};
+use super::{keypair::PublicKey, types::DrkTokenId};
+use crate::{
+    util::{serial::serialize, NetworkName},
+    Error, Result,
+};
+
+/// Hashed into the IDs of tokens issued on DarkFi, keeping them apart from
+/// the IDs of bridged tokens.
+const NATIVE_TOKEN_DOMAIN: &[u8] = b"DarkFi:NativeToken";
+
+/// Address of DRK in the DarkFi token list
+pub const DRK_TOKEN_ADDRESS: &str = "A7f1RKsCUUHrSXA7a9ogmwg8p3bs6F47ggsW826HD4yd";
 
 pub fn generate_id(network: &NetworkName, token_str: &str) -> Result<DrkTokenId> {
     let mut net_bytes: Vec<u8> = network.to_string().as_bytes().to_vec();
@@ -11,6 +21,7 @@ pub fn generate_id(network: &NetworkName, token_str: &str) -> Result<DrkTokenId>
         NetworkName::Bitcoin => bs58::decode(token_str).into_vec()?,
         NetworkName::Ethereum => hex::decode(token_str.strip_prefix("0x").unwrap())?,
         NetworkName::Solana => bs58::decode(token_str).into_vec()?,
+        NetworkName::Monero => bs58::decode(token_str).into_vec()?,
     };
 
     net_bytes.append(&mut token_bytes);
@@ -23,6 +34,24 @@ pub fn generate_id(network: &NetworkName, token_str: &str) -> Result<DrkTokenId>
     Ok(DrkTokenId::from(u64::from_le_bytes(data)))
 }
 
+/// ID of DRK, the token transaction fees are paid in.
+pub fn drk_token_id() -> DrkTokenId {
+    generate_id(&NetworkName::DarkFi, DRK_TOKEN_ADDRESS).unwrap()
+}
+
+/// ID of a token issued on DarkFi by `authority`, which picks a different
+/// `nonce` for each of its tokens.
+pub fn native_token_id(authority: &PublicKey, nonce: u64) -> DrkTokenId {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(NATIVE_TOKEN_DOMAIN);
+    hasher.update(&serialize(authority));
+    hasher.update(&nonce.to_le_bytes());
+
+    // Same 64 bit range as `generate_id`
+    let data: [u8; 8] = hasher.finalize().as_bytes()[0..8].try_into().unwrap();
+    DrkTokenId::from(u64::from_le_bytes(data))
+}
+
 /// Parse a `DrkTokenId` from a base58-encoded string
 pub fn parse_b58(s: &str) -> Result<DrkTokenId> {
     let bytes = bs58::decode(s).into_vec()?;
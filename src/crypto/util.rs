@@ -1,5 +1,8 @@
 use blake2b_simd::Params;
-use halo2_gadgets::{ecc::chip::FixedPoint, poseidon::primitives as poseidon};
+use halo2_gadgets::{
+    ecc::chip::FixedPoint,
+    poseidon::primitives::{self as poseidon, Domain, Spec},
+};
 use pasta_curves::{
     arithmetic::{CurveExt, FieldExt},
     group::ff::PrimeField,
@@ -51,6 +54,78 @@ pub fn poseidon_hash<const N: usize>(messages: [pallas::Base; N]) -> pallas::Bas
         .hash(messages)
 }
 
+/// Poseidon domain for messages of arbitrary length, used with the sponge.
+/// Messages are padded with a one followed by zeros up to a multiple of
+/// the rate, so messages of different lengths never collide.
+#[derive(Clone, Copy, Debug)]
+pub struct VariableLength;
+
+impl Domain<pallas::Base, 2> for VariableLength {
+    type Padding = Vec<pallas::Base>;
+
+    fn name() -> String {
+        "VariableLength".to_string()
+    }
+
+    fn initial_capacity_element() -> pallas::Base {
+        // Kept apart from `ConstantLength`, which uses `L << 64`.
+        pallas::Base::from_u128(1 << 127)
+    }
+
+    fn padding(input_len: usize) -> Self::Padding {
+        let padded_len = (input_len / 2 + 1) * 2;
+        let mut padding = vec![pallas::Base::zero(); padded_len - input_len];
+        padding[0] = pallas::Base::one();
+        padding
+    }
+}
+
+/// Poseidon hash of an arbitrary number of elements, absorbed into a sponge.
+/// This matches the `poseidon_sponge` opcode of the zkvm.
+pub fn poseidon_sponge(messages: &[pallas::Base]) -> pallas::Base {
+    type S = poseidon::P128Pow5T3;
+    let (round_constants, mds, _) = <S as Spec<pallas::Base, 3, 2>>::constants();
+
+    let mut state = [pallas::Base::zero(); 3];
+    state[2] = VariableLength::initial_capacity_element();
+
+    let padding = VariableLength::padding(messages.len());
+    let input: Vec<pallas::Base> = messages.iter().copied().chain(padding).collect();
+
+    for chunk in input.chunks(2) {
+        for (word, value) in state.iter_mut().zip(chunk) {
+            *word += value;
+        }
+
+        // Full rounds, then partial rounds, then full rounds again.
+        let half_full = <S as Spec<pallas::Base, 3, 2>>::full_rounds() / 2;
+        let partial = <S as Spec<pallas::Base, 3, 2>>::partial_rounds();
+        for (round, rcs) in round_constants.iter().enumerate() {
+            for (word, rc) in state.iter_mut().zip(rcs) {
+                *word += rc;
+            }
+
+            if round < half_full || round >= half_full + partial {
+                for word in state.iter_mut() {
+                    *word = <S as Spec<pallas::Base, 3, 2>>::sbox(*word);
+                }
+            } else {
+                state[0] = <S as Spec<pallas::Base, 3, 2>>::sbox(state[0]);
+            }
+
+            let mut new_state = [pallas::Base::zero(); 3];
+            for (i, row) in mds.iter().enumerate() {
+                for (j, m) in row.iter().enumerate() {
+                    new_state[i] += m * state[j];
+                }
+            }
+            state = new_state;
+        }
+    }
+
+    state[0]
+}
+
 /// Converts from pallas::Base to pallas::Scalar (aka $x \pmod{r_\mathbb{P}}$).
 ///
 /// This requires no modular reduction because Pallas' base field is smaller than its
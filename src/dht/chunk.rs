@@ -0,0 +1,87 @@
+use crate::{
+    util::serial::{serialize, Bytes, SerialDecodable, SerialEncodable},
+    Error::InvalidDhtChunk,
+    Result,
+};
+
+/// Size of a chunk of a large value, well below the channel message
+/// size limit
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Record value describing a large value stored in chunks. Chunks are
+/// addressed by their hash, so they can be fetched from any node holding
+/// the manifest and verified on their own.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct Manifest {
+    /// Size of the whole value, in bytes
+    pub size: u64,
+    /// Hashes of the chunks, in order
+    pub chunks: Vec<blake3::Hash>,
+}
+
+impl Manifest {
+    /// Split a value in chunks, returning its manifest along with the
+    /// chunks, in order. Chunks share the buffer of the value.
+    pub fn split(value: &Bytes) -> (Self, Vec<Bytes>) {
+        let chunks: Vec<Bytes> = (0..value.len())
+            .step_by(CHUNK_SIZE)
+            .map(|start| value.slice(start..value.len().min(start + CHUNK_SIZE)))
+            .collect();
+        let hashes = chunks.iter().map(|c| blake3::hash(c)).collect();
+        (Self { size: value.len() as u64, chunks: hashes }, chunks)
+    }
+
+    /// Content hash of the value, the hash of its manifest. The same
+    /// content always gets the same hash, whoever publishes it.
+    pub fn hash(&self) -> blake3::Hash {
+        blake3::hash(&serialize(self))
+    }
+
+    /// Reassemble the value from its chunks, in order, verifying each of
+    /// them matches its hash.
+    pub fn reassemble<C: AsRef<[u8]>>(&self, chunks: &[C]) -> Result<Vec<u8>> {
+        if chunks.len() != self.chunks.len() {
+            return Err(InvalidDhtChunk)
+        }
+
+        let mut value = Vec::with_capacity(self.size as usize);
+        for (hash, chunk) in self.chunks.iter().zip(chunks) {
+            if &blake3::hash(chunk.as_ref()) != hash {
+                return Err(InvalidDhtChunk)
+            }
+            value.extend_from_slice(chunk.as_ref());
+        }
+
+        if value.len() as u64 != self.size {
+            return Err(InvalidDhtChunk)
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let value: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| i as u8).collect();
+        let (manifest, chunks) = Manifest::split(&Bytes::from(value.clone()));
+        assert_eq!(manifest.chunks.len(), 3);
+        assert_eq!(chunks[2].len(), 100);
+        assert_eq!(manifest.reassemble(&chunks).unwrap(), value);
+
+        // Chunks must come complete, in order and untampered
+        assert!(manifest.reassemble(&chunks[..2]).is_err());
+        let swapped = vec![chunks[1].clone(), chunks[0].clone(), chunks[2].clone()];
+        assert!(manifest.reassemble(&swapped).is_err());
+        let mut tampered: Vec<Vec<u8>> = chunks.iter().map(|c| c.to_vec()).collect();
+        tampered[2][0] ^= 1;
+        assert!(manifest.reassemble(&tampered).is_err());
+
+        let (empty, chunks) = Manifest::split(&Bytes::default());
+        assert!(empty.chunks.is_empty());
+        assert!(empty.reassemble(&chunks).unwrap().is_empty());
+    }
+}
@@ -1,84 +1,142 @@
 use async_executor::Executor;
 use async_std::sync::{Arc, RwLock};
 use chrono::Utc;
-use futures::{select, FutureExt};
+use ed25519_compact::KeyPair;
+use futures::future::join_all;
 use fxhash::FxHashMap;
-use log::{debug, error, warn};
-use rand::Rng;
-use std::{collections::HashSet, time::Duration};
+use log::{debug, error, info, warn};
+use std::collections::HashSet;
+use url::Url;
 
 use crate::{
     net,
-    net::P2pPtr,
-    util::{serial::serialize, sleep},
-    Error::{NetworkNotConnected, UnknownKey},
+    net::{ChannelPtr, Connector, P2pPtr, RequesterPtr, Session},
+    util::{
+        serial::{deserialize, serialize, Bytes},
+        sleep,
+    },
+    Error::{ConnectFailed, InvalidDhtChunk, InvalidDhtRecord, NetworkNotConnected, UnknownKey},
     Result,
 };
 
 use super::{
-    messages::{KeyRequest, KeyResponse, LookupMapRequest, LookupMapResponse, LookupRequest},
+    chunk::Manifest,
+    messages::{ChunkRequest, FindNodeRequest, FindValueRequest, LookupRequest},
     protocol::Protocol,
+    record::Record,
+    routing::{distance, Contact, RoutingTable, ALPHA, K},
 };
 
 // Constants configuration
-const REQUEST_TIMEOUT: u64 = 2400;
-const SEEN_DURATION: i64 = 120;
+pub(super) const REQUEST_TIMEOUT: u64 = 2400;
+/// Seconds to wait for a dialed node to announce itself
+const DIAL_TIMEOUT: u64 = 10;
+/// Seconds after which a bucket nothing was looked up in gets refreshed
+const REFRESH_INTERVAL: i64 = 3600;
+/// Default lifetime of an entry, in seconds
+pub const DEFAULT_TTL: u64 = 86400;
+/// Shortest lifetime of an entry, so it gets republished before expiring
+const MIN_TTL: u64 = 600;
+/// Longest lifetime of an entry
+const MAX_TTL: u64 = 7 * 86400;
+/// Seconds between checks for entries to expire or republish
+const MAINTENANCE_INTERVAL: u64 = 60;
+/// Number of chunks fetched in parallel
+const CHUNK_REQUESTS: usize = 8;
 
 /// Atomic pointer to DHT state
 pub type DhtPtr = Arc<RwLock<Dht>>;
 
-// TODO: proper errors
-// TODO: lookup table to be based on directly connected peers, not broadcast based
-// Using string in structures because we are at an external crate
-// and cant use blake3 serialization. To be replaced once merged with core src.
+/// A connected DHT node, along with the requesters to query it.
+#[derive(Clone)]
+pub struct DhtPeer {
+    pub contact: Contact,
+    pub channel: ChannelPtr,
+    pub(super) find_node: RequesterPtr<FindNodeRequest>,
+    pub(super) find_value: RequesterPtr<FindValueRequest>,
+    pub(super) find_chunk: RequesterPtr<ChunkRequest>,
+}
 
 /// Struct representing DHT state.
+///
+/// Nodes are organized as a Kademlia network: each node keeps a routing
+/// table of other nodes by XOR distance to its id, and values are found
+/// with iterative lookups converging towards the nodes closest to the key.
+/// Values stay on the node that inserted them, while the lookup map
+/// records of which nodes hold each key are kept by the [`K`] nodes
+/// closest to the key.
+///
+/// Every entry has a TTL. The original publisher of a value republishes
+/// it while it holds it, refreshing the lookup map records on the closest
+/// nodes, and everyone else drops the values and records whose TTL ran
+/// out. Values are signed by their publisher, whose public key hashes to
+/// its daemon id.
+///
+/// Values larger than a message can carry are split in chunks: the record
+/// holds a [`Manifest`] listing the chunk hashes, and the chunks are
+/// fetched in parallel from the nodes holding the record.
 pub struct Dht {
-    /// Daemon id
+    /// Daemon id, hash of the daemon public key
     pub id: blake3::Hash,
+    /// Daemon keypair, signing the records it publishes
+    keypair: KeyPair,
     /// Daemon hasmap
-    pub map: FxHashMap<blake3::Hash, Vec<u8>>,
+    pub map: FxHashMap<blake3::Hash, Record>,
     /// Network lookup map, containing nodes that holds each key
     pub lookup: FxHashMap<blake3::Hash, HashSet<blake3::Hash>>,
+    /// Keys we published, with their TTL, republished until removed
+    pub published: FxHashMap<blake3::Hash, u64>,
+    /// Chunks of the large values we hold, by hash
+    pub chunks: FxHashMap<blake3::Hash, Bytes>,
+    /// Manifests of the large values we hold, by key
+    manifests: FxHashMap<blake3::Hash, Manifest>,
+    /// Expiration timestamp of each lookup map record, by key and node id
+    lookup_expires: FxHashMap<(blake3::Hash, blake3::Hash), i64>,
+    /// Kademlia routing table
+    pub routing: RoutingTable,
+    /// Directly connected nodes, by id
+    pub peers: FxHashMap<blake3::Hash, DhtPeer>,
     /// P2P network pointer
     pub p2p: P2pPtr,
-    /// Channel to receive responses from P2P
-    p2p_recv_channel: async_channel::Receiver<KeyResponse>,
-    /// Stop signal channel to terminate background processes
-    stop_signal: async_channel::Receiver<()>,
-    /// Daemon seen requests/responses ids and timestamp,
-    /// to prevent rebroadcasting and loops
-    pub seen: FxHashMap<blake3::Hash, i64>,
+    /// Channel to request connections to nodes
+    dial_sender: async_channel::Sender<Url>,
 }
 
 impl Dht {
     pub async fn new(
         initial: Option<FxHashMap<blake3::Hash, HashSet<blake3::Hash>>>,
         p2p_ptr: P2pPtr,
-        stop_signal: async_channel::Receiver<()>,
         ex: Arc<Executor<'_>>,
     ) -> Result<DhtPtr> {
-        // Generate a random id
-        let mut rng = rand::thread_rng();
-        let n: u16 = rng.gen();
-        let id = blake3::hash(&serialize(&n));
+        // Generate a random keypair, identifying the daemon
+        let keypair = KeyPair::generate();
+        let id = blake3::hash(keypair.pk.as_ref());
         let map = FxHashMap::default();
         let lookup = match initial {
             Some(l) => l,
             None => FxHashMap::default(),
         };
+        let expiry = Utc::now().timestamp() + DEFAULT_TTL as i64;
+        let lookup_expires = lookup
+            .iter()
+            .flat_map(|(key, nodes)| nodes.iter().map(move |node| ((*key, *node), expiry)))
+            .collect();
         let p2p = p2p_ptr.clone();
-        let (p2p_send_channel, p2p_recv_channel) = async_channel::unbounded::<KeyResponse>();
-        let seen = FxHashMap::default();
+        let (dial_sender, dial_receiver) = async_channel::unbounded::<Url>();
 
         let dht = Arc::new(RwLock::new(Dht {
             id,
+            keypair,
             map,
             lookup,
+            published: FxHashMap::default(),
+            chunks: FxHashMap::default(),
+            manifests: FxHashMap::default(),
+            lookup_expires,
+            routing: RoutingTable::new(id),
+            peers: FxHashMap::default(),
             p2p,
-            p2p_recv_channel,
-            stop_signal,
-            seen,
+            dial_sender,
         }));
 
         // Registering P2P protocols
@@ -86,63 +144,40 @@ impl Dht {
         let _dht = dht.clone();
         registry
             .register(net::SESSION_ALL, move |channel, p2p_ptr| {
-                let sender = p2p_send_channel.clone();
                 let dht = _dht.clone();
-                async move { Protocol::init(channel, sender, dht, p2p_ptr).await.unwrap() }
+                async move { Protocol::init(channel, dht, p2p_ptr).await.unwrap() }
             })
             .await;
 
-        // Task to periodically clean up daemon seen messages
-        ex.spawn(prune_seen_messages(dht.clone())).detach();
+        // Task to connect to the nodes lookups need to query
+        ex.spawn(dial_loop(p2p_ptr, dial_receiver, ex.clone())).detach();
 
-        Ok(dht)
-    }
+        // Task to periodically refresh stale buckets
+        ex.spawn(refresh_buckets(dht.clone())).detach();
 
-    /// Store provided key value pair, update lookup map and broadcast new insert to network
-    pub async fn insert(
-        &mut self,
-        key: blake3::Hash,
-        value: Vec<u8>,
-    ) -> Result<Option<blake3::Hash>> {
-        self.map.insert(key, value);
+        // Task to periodically expire and republish entries
+        ex.spawn(maintain_entries(dht.clone())).detach();
 
-        if let Err(e) = self.lookup_insert(key, self.id) {
-            error!("Failed to insert record to lookup map: {}", e);
-            return Err(e)
-        };
-
-        let request = LookupRequest::new(self.id, key, 0);
-        if let Err(e) = self.p2p.broadcast(request).await {
-            error!("Failed broadcasting request: {}", e);
-            return Err(e)
-        }
-
-        Ok(Some(key))
+        Ok(dht)
     }
 
-    /// Remove provided key value pair and update lookup map
-    pub async fn remove(&mut self, key: blake3::Hash) -> Result<Option<blake3::Hash>> {
-        // Check if key value pair existed and act accordingly
-        match self.map.remove(&key) {
-            Some(_) => {
-                debug!("Key removed: {}", key);
-                let request = LookupRequest::new(self.id, key, 1);
-                if let Err(e) = self.p2p.broadcast(request).await {
-                    error!("Failed broadcasting request: {}", e);
-                    return Err(e)
-                }
-
-                self.lookup_remove(key, self.id)
+    /// Add a contact to the routing table. If its bucket is full, the
+    /// least recently seen contact makes room for it, unless we are still
+    /// connected to it: long lived nodes are favored.
+    pub fn add_contact(&mut self, contact: Contact) {
+        if let Some(oldest) = self.routing.insert(contact.clone()) {
+            if !self.peers.contains_key(&oldest.id) {
+                self.routing.replace(&oldest.id, contact);
             }
-            None => Ok(None),
         }
     }
 
-    /// Store provided key node pair in lookup map and update network
+    /// Store provided key node pair in lookup map, expiring after `ttl` seconds
     pub fn lookup_insert(
         &mut self,
         key: blake3::Hash,
         node_id: blake3::Hash,
+        ttl: u64,
     ) -> Result<Option<blake3::Hash>> {
         let mut lookup_set = match self.lookup.get(&key) {
             Some(s) => s.clone(),
@@ -152,6 +187,9 @@ impl Dht {
         lookup_set.insert(node_id);
         self.lookup.insert(key, lookup_set);
 
+        let expiry = Utc::now().timestamp() + ttl.min(MAX_TTL) as i64;
+        self.lookup_expires.insert((key, node_id), expiry);
+
         Ok(Some(key))
     }
 
@@ -170,10 +208,45 @@ impl Dht {
                 self.lookup.insert(key, lookup_set);
             }
         }
+        self.lookup_expires.remove(&(key, node_id));
 
         Ok(Some(key))
     }
 
+    /// Drop the values and lookup map records whose TTL ran out. Values we
+    /// published don't expire, as they get republished.
+    pub fn prune_expired(&mut self, now: i64) -> Result<()> {
+        let expired: Vec<blake3::Hash> = self
+            .map
+            .iter()
+            .filter(|(key, record)| record.is_expired(now) && !self.published.contains_key(*key))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            debug!("Value expired: {}", key);
+            self.map.remove(&key);
+            self.remove_chunks(key);
+        }
+
+        let expired: Vec<(blake3::Hash, blake3::Hash)> = self
+            .lookup_expires
+            .iter()
+            .filter(|(_, expiry)| **expiry <= now)
+            .map(|(record, _)| *record)
+            .collect();
+        for (key, node_id) in expired {
+            self.lookup_remove(key, node_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remaining seconds before the value of provided key expires
+    pub fn ttl(&self, key: blake3::Hash) -> Option<u64> {
+        let record = self.map.get(&key)?;
+        Some(record.remaining_ttl(Utc::now().timestamp()))
+    }
+
     /// Verify if provided key exists and return flag if local or in network
     pub fn contains_key(&self, key: blake3::Hash) -> Option<bool> {
         match self.lookup.contains_key(&key) {
@@ -184,132 +257,563 @@ impl Dht {
 
     /// Get key from local map, acting as daemon cache
     pub fn get(&self, key: blake3::Hash) -> Option<&Vec<u8>> {
-        self.map.get(&key)
+        self.map.get(&key).map(|record| &record.value)
+    }
+
+    /// Reassemble the large value of provided key from the local chunks.
+    pub fn get_chunked(&self, key: blake3::Hash) -> Option<Vec<u8>> {
+        let manifest = self.manifests.get(&key)?;
+        let chunks: Option<Vec<Bytes>> =
+            manifest.chunks.iter().map(|hash| self.chunks.get(hash).cloned()).collect();
+        manifest.reassemble(&chunks?).ok()
     }
 
-    /// Generate key request and broadcast it to the network
-    pub async fn request_key(&self, key: blake3::Hash) -> Result<()> {
-        // Verify the key exist in the lookup map.
-        let peers = match self.lookup.get(&key) {
-            Some(v) => v.clone(),
-            None => return Err(UnknownKey),
+    /// Store the chunks of a large value, along with its manifest.
+    fn store_chunks(&mut self, key: blake3::Hash, manifest: Manifest, chunks: Vec<Bytes>) {
+        for (hash, chunk) in manifest.chunks.iter().zip(chunks) {
+            self.chunks.insert(*hash, chunk);
+        }
+        self.manifests.insert(key, manifest);
+    }
+
+    /// Drop the chunks of the large value of provided key, unless another
+    /// value we hold shares them.
+    fn remove_chunks(&mut self, key: blake3::Hash) {
+        let manifest = match self.manifests.remove(&key) {
+            Some(m) => m,
+            None => return,
         };
 
-        debug!("Key is in peers: {:?}", peers);
+        for hash in manifest.chunks {
+            if !self.manifests.values().any(|m| m.chunks.contains(&hash)) {
+                self.chunks.remove(&hash);
+            }
+        }
+    }
+}
+
+/// Publish provided key value pair, and record us as its holder in the
+/// lookup map of the nodes closest to the key. The records are refreshed
+/// periodically, so they don't expire while we hold the value.
+pub async fn insert(
+    dht: DhtPtr,
+    key: blake3::Hash,
+    value: Vec<u8>,
+    ttl: u64,
+) -> Result<Option<blake3::Hash>> {
+    let ttl = ttl.clamp(MIN_TTL, MAX_TTL);
+    {
+        let mut dht = dht.write().await;
+        let record = Record::new(&dht.keypair, key, value, ttl);
+        dht.map.insert(key, record);
+        dht.published.insert(key, ttl);
+        let id = dht.id;
+        dht.lookup_insert(key, id, ttl)?;
+    }
+
+    publish_lookup(dht, key, 0, ttl).await?;
+    Ok(Some(key))
+}
+
+/// Cache provided record retrieved from the network until it expires,
+/// and record us as its holder until then. Records that aren't signed by
+/// their publisher are rejected.
+pub async fn cache(dht: DhtPtr, record: Record) -> Result<Option<blake3::Hash>> {
+    let now = Utc::now().timestamp();
+    if !record.is_valid(now) {
+        return Err(InvalidDhtRecord)
+    }
+
+    let key = record.key;
+    let ttl = record.remaining_ttl(now).min(MAX_TTL);
+    {
+        let mut dht = dht.write().await;
+        if dht.published.contains_key(&key) {
+            return Ok(None)
+        }
+        dht.map.insert(key, record);
+        let id = dht.id;
+        dht.lookup_insert(key, id, ttl)?;
+    }
+
+    publish_lookup(dht, key, 0, ttl).await?;
+    Ok(Some(key))
+}
 
-        // We retrieve p2p network connected channels, to verify if we
-        // are connected to a network.
-        // Using len here because is_empty() uses unstable library feature
-        // called 'exact_size_is_empty'.
-        if self.p2p.channels().lock().await.values().len() == 0 {
-            return Err(NetworkNotConnected)
+/// Remove provided key value pair, and update the lookup map of the nodes
+/// closest to the key.
+pub async fn remove(dht: DhtPtr, key: blake3::Hash) -> Result<Option<blake3::Hash>> {
+    {
+        let mut dht = dht.write().await;
+        if dht.map.remove(&key).is_none() {
+            return Ok(None)
         }
+        debug!("Key removed: {}", key);
+        dht.published.remove(&key);
+        dht.remove_chunks(key);
+        let id = dht.id;
+        dht.lookup_remove(key, id)?;
+    }
+
+    publish_lookup(dht, key, 1, 0).await?;
+    Ok(Some(key))
+}
 
-        // We create a key request, and broadcast it to the network
-        // We choose last known peer as request recipient
-        let peer = *peers.iter().last().unwrap();
-        let request = KeyRequest::new(self.id, peer, key);
-        // TODO: ask connected peers directly, not broadcast
-        if let Err(e) = self.p2p.broadcast(request).await {
-            error!("Failed broadcasting request: {}", e);
-            return Err(e)
+/// Retrieve the signed record of provided key, from the local map or the
+/// network. The network is queried with an iterative lookup, which stops
+/// at the first node holding the value, or asks the nodes listed as
+/// holders of the key in the lookup maps it collected. Records are
+/// verified before being returned.
+pub async fn get_value(dht: DhtPtr, key: blake3::Hash) -> Result<Option<Record>> {
+    if let Some(record) = dht.read().await.map.get(&key) {
+        return Ok(Some(record.clone()))
+    }
+
+    Ok(locate(dht, key).await?.map(|(record, _)| record))
+}
+
+/// Publish provided large value, split in chunks. The record of the key
+/// holds the value manifest, and the chunks are served to the nodes
+/// fetching them.
+pub async fn insert_file(
+    dht: DhtPtr,
+    key: blake3::Hash,
+    value: Bytes,
+    ttl: u64,
+) -> Result<Option<blake3::Hash>> {
+    let (manifest, chunks) = Manifest::split(&value);
+    let record_value = serialize(&manifest);
+    dht.write().await.store_chunks(key, manifest, chunks);
+    insert(dht, key, record_value, ttl).await
+}
+
+/// Cache provided large value retrieved from the network along with its
+/// manifest record, so we serve its chunks until the record expires.
+pub async fn cache_file(dht: DhtPtr, record: Record, value: Bytes) -> Result<Option<blake3::Hash>> {
+    let key = record.key;
+    let manifest: Manifest = deserialize(&record.value)?;
+    let (split, chunks) = Manifest::split(&value);
+    if split != manifest {
+        return Err(InvalidDhtChunk)
+    }
+
+    {
+        let mut dht = dht.write().await;
+        if dht.published.contains_key(&key) {
+            return Ok(None)
         }
+        dht.store_chunks(key, manifest, chunks);
+    }
 
-        Ok(())
+    let result = cache(dht.clone(), record).await;
+    if result.is_err() {
+        dht.write().await.remove_chunks(key);
     }
+    result
+}
 
-    /// Auxilary function to sync lookup map with network
-    pub async fn sync_lookup_map(&mut self) -> Result<()> {
-        debug!("Starting lookup map sync...");
-        let channels_map = self.p2p.channels().lock().await.clone();
-        let values = channels_map.values();
-        // Using len here because is_empty() uses unstable library feature
-        // called 'exact_size_is_empty'.
-        if values.len() != 0 {
-            // Node iterates the channel peers to ask for their lookup map
-            for channel in values {
-                // Communication setup
-                let msg_subsystem = channel.get_message_subsystem();
-                msg_subsystem.add_dispatch::<LookupMapResponse>().await;
-                let response_sub = channel.subscribe_msg::<LookupMapResponse>().await?;
-
-                // Node creates a `LookupMapRequest` and sends it
-                let order = LookupMapRequest::new(self.id);
-                channel.send(order).await?;
-
-                // Node stores response data.
-                let resp = response_sub.receive().await?;
-                if resp.lookup.is_empty() {
-                    warn!("Retrieved empty lookup map from an unsynced node, retrying...");
-                    continue
+/// Retrieve the large value of provided key along with its manifest
+/// record, from the local chunks or the network. Chunks are requested in
+/// parallel from the nodes holding the record, spread among them, and
+/// verified against the manifest before the value is reassembled.
+pub async fn get_file(dht: DhtPtr, key: blake3::Hash) -> Result<Option<(Record, Vec<u8>)>> {
+    {
+        let dht = dht.read().await;
+        if let Some(record) = dht.map.get(&key) {
+            if let Some(value) = dht.get_chunked(key) {
+                return Ok(Some((record.clone(), value)))
+            }
+        }
+    }
+
+    let (record, holders) = match locate(dht.clone(), key).await? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let manifest: Manifest = deserialize(&record.value)?;
+    let chunks = fetch_chunks(dht, &manifest, &holders, |_, _| {}).await?;
+
+    let value = manifest.reassemble(&chunks)?;
+    Ok(Some((record, value)))
+}
+
+/// Fetch the chunks of a manifest, in order, from the given holders.
+/// `progress` is called with the number of chunks fetched so far and
+/// their total, as they come in.
+pub(super) async fn fetch_chunks(
+    dht: DhtPtr,
+    manifest: &Manifest,
+    holders: &[Contact],
+    progress: impl Fn(usize, usize),
+) -> Result<Vec<Bytes>> {
+    let total = manifest.chunks.len();
+    let mut chunks = Vec::with_capacity(total);
+    for (batch, hashes) in manifest.chunks.chunks(CHUNK_REQUESTS).enumerate() {
+        let offset = batch * CHUNK_REQUESTS;
+        let requests = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| fetch_chunk(dht.clone(), *hash, holders, offset + i));
+        for chunk in join_all(requests).await {
+            chunks.push(chunk?);
+            progress(chunks.len(), total);
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Find the signed record of provided key in the network, along with the
+/// nodes holding it.
+pub(super) async fn locate(
+    dht: DhtPtr,
+    key: blake3::Hash,
+) -> Result<Option<(Record, Vec<Contact>)>> {
+    let id = dht.read().await.id;
+
+    let lookup = iterative_lookup(dht.clone(), key, true).await?;
+    if let Some(record) = lookup.value {
+        let mut holders = lookup.nodes;
+        let dht = dht.read().await;
+        for provider in &lookup.providers {
+            if let Some(contact) = dht.routing.get(provider) {
+                if !holders.contains(contact) {
+                    holders.push(contact.clone());
                 }
+            }
+        }
+        return Ok(Some((record, holders)))
+    }
 
-                // Store retrieved records
-                debug!("Processing received records");
-                for (k, v) in &resp.lookup {
-                    for node in v {
-                        self.lookup_insert(*k, *node)?;
-                    }
+    for provider in lookup.providers {
+        if provider == id {
+            continue
+        }
+
+        let known = dht.read().await.routing.get(&provider).cloned();
+        let contact = match known {
+            Some(c) => c,
+            None => match find_node(dht.clone(), provider).await?.into_iter().next() {
+                Some(c) if c.id == provider => c,
+                _ => continue,
+            },
+        };
+
+        match query(dht.clone(), &contact, key, true).await {
+            Ok(response) if response.value.is_some() => {
+                return Ok(response.value.map(|record| (record, vec![contact])))
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                debug!("Failed to query key holder {}: {}", provider, e);
+                continue
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Request a chunk from the nodes holding it, starting with the one at
+/// index `start`, until one serves it untampered.
+async fn fetch_chunk(
+    dht: DhtPtr,
+    hash: blake3::Hash,
+    holders: &[Contact],
+    start: usize,
+) -> Result<Bytes> {
+    for i in 0..holders.len() {
+        let contact = &holders[(start + i) % holders.len()];
+        let peer = match connect(dht.clone(), contact).await {
+            Ok(p) => p,
+            Err(e) => {
+                debug!("Failed connecting to chunk holder {}: {}", contact.id, e);
+                continue
+            }
+        };
+
+        match peer.find_chunk.request(ChunkRequest { hash }).await {
+            Ok(resp) => match resp.data {
+                Some(data) if blake3::hash(&data) == hash => return Ok(data),
+                Some(_) => warn!("Node {} served an invalid chunk {}", contact.id, hash),
+                None => continue,
+            },
+            Err(e) => debug!("Chunk request to {} failed: {}", contact.id, e),
+        }
+    }
+
+    Err(UnknownKey)
+}
+
+/// Iterative lookup of the [`K`] nodes closest to `target` in the network.
+pub async fn find_node(dht: DhtPtr, target: blake3::Hash) -> Result<Vec<Contact>> {
+    Ok(iterative_lookup(dht, target, false).await?.nodes)
+}
+
+/// Join the network by looking up our own id, which fills the routing
+/// table with our neighbours and lets them learn about us.
+pub async fn bootstrap(dht: DhtPtr) -> Result<()> {
+    if dht.read().await.peers.is_empty() {
+        return Err(NetworkNotConnected)
+    }
+
+    let id = dht.read().await.id;
+    let nodes = find_node(dht.clone(), id).await?;
+    info!("Bootstrapped DHT with {} close nodes", nodes.len());
+    Ok(())
+}
+
+/// Response of a node to a lookup query
+struct QueryResponse {
+    /// Verified record of the key
+    value: Option<Record>,
+    providers: Vec<blake3::Hash>,
+    nodes: Vec<Contact>,
+}
+
+/// Result of an iterative lookup
+struct LookupResult {
+    /// Closest nodes to the target that answered, closest first, or the
+    /// node that returned the value
+    nodes: Vec<Contact>,
+    value: Option<Record>,
+    providers: HashSet<blake3::Hash>,
+}
+
+/// Kademlia iterative lookup: starting from the closest nodes in our
+/// routing table, query [`ALPHA`] of the closest nodes not queried yet at
+/// a time, add the closer nodes they return, and stop once the [`K`]
+/// closest nodes have all been queried. Value lookups stop early when a
+/// node returns the value, returning that node only.
+async fn iterative_lookup(
+    dht: DhtPtr,
+    target: blake3::Hash,
+    find_value: bool,
+) -> Result<LookupResult> {
+    let (id, mut shortlist) = {
+        let mut dht = dht.write().await;
+        dht.routing.mark_lookup(&target);
+        (dht.id, dht.routing.closest(&target, K))
+    };
+
+    let mut queried = HashSet::new();
+    let mut answered = HashSet::new();
+    let mut providers = HashSet::new();
+    loop {
+        let round: Vec<Contact> =
+            shortlist.iter().filter(|c| !queried.contains(&c.id)).take(ALPHA).cloned().collect();
+        if round.is_empty() {
+            break
+        }
+
+        let queries = round.iter().map(|contact| query(dht.clone(), contact, target, find_value));
+        let responses = join_all(queries).await;
+
+        for (contact, response) in round.iter().zip(responses) {
+            queried.insert(contact.id);
+
+            let response = match response {
+                Ok(r) => r,
+                Err(e) => {
+                    debug!("Lookup query to {} failed: {}", contact.id, e);
+                    dht.write().await.routing.remove(&contact.id);
+                    shortlist.retain(|c| c.id != contact.id);
+                    continue
                 }
+            };
+            answered.insert(contact.id);
 
-                break
+            if response.value.is_some() {
+                let nodes = vec![contact.clone()];
+                return Ok(LookupResult { nodes, value: response.value, providers })
+            }
+            providers.extend(response.providers);
+
+            let mut dht = dht.write().await;
+            for node in response.nodes {
+                if node.id == id || shortlist.iter().any(|c| c.id == node.id) {
+                    continue
+                }
+                dht.add_contact(node.clone());
+                shortlist.push(node);
             }
-        } else {
-            warn!("Node is not connected to other nodes");
         }
 
-        debug!("Lookup map synced!");
-        Ok(())
+        shortlist.sort_unstable_by_key(|c| distance(&c.id, &target));
+        shortlist.truncate(K);
+    }
+
+    shortlist.retain(|c| answered.contains(&c.id));
+    Ok(LookupResult { nodes: shortlist, value: None, providers })
+}
+
+/// Send a lookup query to a node, connecting to it if needed.
+async fn query(
+    dht: DhtPtr,
+    contact: &Contact,
+    target: blake3::Hash,
+    find_value: bool,
+) -> Result<QueryResponse> {
+    let peer = connect(dht, contact).await?;
+
+    if find_value {
+        let resp = peer.find_value.request(FindValueRequest { key: target }).await?;
+        let value = match resp.record {
+            Some(record) if record.key == target && record.is_valid(Utc::now().timestamp()) => {
+                Some(record)
+            }
+            Some(_) => {
+                warn!("Node {} served an invalid record for key {}", contact.id, target);
+                None
+            }
+            None => None,
+        };
+        return Ok(QueryResponse { value, providers: resp.providers, nodes: resp.nodes })
     }
+
+    let resp = peer.find_node.request(FindNodeRequest { target }).await?;
+    Ok(QueryResponse { value: None, providers: vec![], nodes: resp.nodes })
 }
 
-// Auxilary function to wait for a key response from the P2P network.
-pub async fn waiting_for_response(dht: DhtPtr) -> Result<Option<KeyResponse>> {
-    let (p2p_recv_channel, stop_signal) = {
-        let _dht = dht.read().await;
-        (_dht.p2p_recv_channel.clone(), _dht.stop_signal.clone())
+/// Retrieve the connected peer of a contact, dialing it if we are not
+/// connected to it yet.
+async fn connect(dht: DhtPtr, contact: &Contact) -> Result<DhtPeer> {
+    let dial_sender = {
+        let dht = dht.read().await;
+        if let Some(peer) = dht.peers.get(&contact.id) {
+            return Ok(peer.clone())
+        }
+        dht.dial_sender.clone()
     };
-    let ex = Arc::new(async_executor::Executor::new());
-    let (timeout_s, timeout_r) = async_channel::unbounded::<()>();
-    ex.spawn(async move {
-        sleep(Duration::from_millis(REQUEST_TIMEOUT).as_secs()).await;
-        timeout_s.send(()).await.unwrap_or(());
-    })
-    .detach();
-
-    select! {
-        msg = p2p_recv_channel.recv().fuse() => {
-                let response = msg?;
-                return Ok(Some(response))
-        },
-        _ = stop_signal.recv().fuse() => {},
-        _ = timeout_r.recv().fuse() => {},
+
+    dial_sender.send(contact.addr.clone()).await?;
+
+    // Wait for the node to announce itself on the new channel
+    for _ in 0..DIAL_TIMEOUT {
+        sleep(1).await;
+        if let Some(peer) = dht.read().await.peers.get(&contact.id) {
+            return Ok(peer.clone())
+        }
+    }
+
+    Err(ConnectFailed)
+}
+
+/// Send a lookup map request to the nodes closest to the key.
+async fn publish_lookup(dht: DhtPtr, key: blake3::Hash, req_type: u8, ttl: u64) -> Result<()> {
+    let id = dht.read().await.id;
+    let nodes = find_node(dht.clone(), key).await?;
+
+    for node in nodes {
+        let peer = match connect(dht.clone(), &node).await {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed connecting to {}: {}", node.id, e);
+                continue
+            }
+        };
+
+        if let Err(e) = peer.channel.send(LookupRequest::new(id, key, req_type, ttl)).await {
+            error!("Failed sending lookup request: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Auxilary function to open the connections requested by lookups to the
+// nodes we are not connected to.
+async fn dial_loop(p2p: P2pPtr, receiver: async_channel::Receiver<Url>, ex: Arc<Executor<'_>>) {
+    let session = p2p.session_manual().await;
+    let connector = Arc::new(Connector::new(p2p.settings(), Arc::new(Arc::downgrade(&session))));
+
+    while let Ok(addr) = receiver.recv().await {
+        if p2p.exists(&addr).await || !p2p.add_pending(addr.clone()).await {
+            continue
+        }
+
+        let (p2p, session, connector, executor) =
+            (p2p.clone(), session.clone(), connector.clone(), ex.clone());
+        ex.spawn(async move {
+            debug!("Connecting to DHT node [{}]", addr);
+            match connector.connect(addr.clone()).await {
+                Ok(channel) => {
+                    if let Err(e) = session.register_channel(channel, executor).await {
+                        error!("Failed registering channel to [{}]: {}", addr, e);
+                    }
+                }
+                Err(e) => debug!("Unable to connect to DHT node [{}]: {}", addr, e),
+            }
+            p2p.remove_pending(&addr).await;
+        })
+        .detach();
     }
-    Ok(None)
 }
 
-// Auxilary function to periodically prun seen messages, based on when they were received.
-// This helps us to prevent broadcasting loops.
-async fn prune_seen_messages(dht: DhtPtr) {
+// Auxilary function to periodically refresh the buckets no lookup went
+// through lately, by looking up a random id in each of them.
+async fn refresh_buckets(dht: DhtPtr) {
     loop {
-        sleep(SEEN_DURATION as u64).await;
-        debug!("Pruning seen messages");
+        sleep(REFRESH_INTERVAL as u64).await;
 
-        let now = Utc::now().timestamp();
+        let targets: Vec<blake3::Hash> = {
+            let dht = dht.read().await;
+            let stale = dht.routing.stale_buckets(REFRESH_INTERVAL);
+            stale.into_iter().map(|index| dht.routing.random_id(index)).collect()
+        };
+        debug!("Refreshing {} stale buckets", targets.len());
 
-        let mut prune = vec![];
-        let map = dht.read().await.seen.clone();
-        for (k, v) in map.iter() {
-            if now - v > SEEN_DURATION {
-                prune.push(k);
+        for target in targets {
+            if let Err(e) = find_node(dht.clone(), target).await {
+                error!("Failed refreshing bucket: {}", e);
             }
         }
+    }
+}
 
-        let mut map = map.clone();
-        for i in prune {
-            map.remove(i);
-        }
+// Auxilary function to periodically drop expired entries, and republish
+// the ones we published before they expire.
+async fn maintain_entries(dht: DhtPtr) {
+    loop {
+        sleep(MAINTENANCE_INTERVAL).await;
+        let now = Utc::now().timestamp();
+
+        let republish: Vec<(blake3::Hash, u64)> = {
+            let mut dht = dht.write().await;
+            if let Err(e) = dht.prune_expired(now) {
+                error!("Failed pruning expired entries: {}", e);
+            }
+
+            // Republish entries past half their lifetime, signing them anew
+            let due: Vec<(blake3::Hash, u64)> = dht
+                .published
+                .iter()
+                .filter(|(key, ttl)| match dht.map.get(*key) {
+                    Some(record) => record.remaining_ttl(now) <= **ttl / 2,
+                    None => false,
+                })
+                .map(|(key, ttl)| (*key, *ttl))
+                .collect();
+
+            let id = dht.id;
+            for (key, ttl) in &due {
+                let value = dht.map[key].value.clone();
+                let record = Record::new(&dht.keypair, *key, value, *ttl);
+                dht.map.insert(*key, record);
+                if let Err(e) = dht.lookup_insert(*key, id, *ttl) {
+                    error!("Failed to insert record to lookup map: {}", e);
+                }
+            }
+            due
+        };
 
-        dht.write().await.seen = map;
+        if !republish.is_empty() {
+            debug!("Republishing {} entries", republish.len());
+        }
+        for (key, ttl) in republish {
+            if let Err(e) = publish_lookup(dht.clone(), key, 0, ttl).await {
+                error!("Failed republishing key {}: {}", key, e);
+            }
+        }
     }
 }
@@ -0,0 +1,72 @@
+use log::info;
+use std::path::Path;
+
+use crate::{
+    util::serial::{deserialize, Bytes},
+    Error::{InvalidDhtManifest, UnknownKey},
+    Result,
+};
+
+use super::{
+    chunk::Manifest,
+    dht::{cache_file, fetch_chunks, insert_file, locate, DhtPtr},
+};
+
+/// Progress of a file fetch, reported after each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Content hash of the file
+    pub hash: blake3::Hash,
+    /// Number of chunks fetched so far
+    pub chunks_done: usize,
+    /// Total number of chunks of the file
+    pub chunks_total: usize,
+}
+
+/// Publish the file at provided path, addressed by its content hash, and
+/// return the hash. The file is served by chunks to the nodes fetching
+/// it, as long as it stays published.
+pub async fn publish(dht: DhtPtr, path: &Path, ttl: u64) -> Result<blake3::Hash> {
+    let value = Bytes::from(std::fs::read(path)?);
+    let hash = Manifest::split(&value).0.hash();
+    insert_file(dht, hash, value, ttl).await?;
+    info!("Published {:?} as {}", path, hash);
+    Ok(hash)
+}
+
+/// Fetch the file with provided content hash from the network, write it
+/// to provided path and serve it in turn. The manifest is checked
+/// against the hash before any chunk is fetched, and each chunk against
+/// the manifest, so a file can be fetched from untrusted nodes.
+pub async fn fetch(
+    dht: DhtPtr,
+    hash: blake3::Hash,
+    path: &Path,
+    progress: impl Fn(Progress),
+) -> Result<()> {
+    let local = dht.read().await.get_chunked(hash);
+    if let Some(value) = local {
+        std::fs::write(path, value)?;
+        return Ok(())
+    }
+
+    let (record, holders) = match locate(dht.clone(), hash).await? {
+        Some(v) => v,
+        None => return Err(UnknownKey),
+    };
+    let manifest: Manifest = deserialize(&record.value)?;
+    if manifest.hash() != hash {
+        return Err(InvalidDhtManifest)
+    }
+
+    let chunks = fetch_chunks(dht.clone(), &manifest, &holders, |chunks_done, chunks_total| {
+        progress(Progress { hash, chunks_done, chunks_total })
+    })
+    .await?;
+    let value = manifest.reassemble(&chunks)?;
+
+    std::fs::write(path, &value)?;
+    cache_file(dht, record, Bytes::from(value)).await?;
+    info!("Fetched {} into {:?}", hash, path);
+    Ok(())
+}
@@ -1,147 +1,160 @@
-use fxhash::FxHashMap;
 use rand::Rng;
-use std::collections::HashSet;
+use url::Url;
 
 use crate::{
     net,
-    util::serial::{serialize, SerialDecodable, SerialEncodable},
+    util::serial::{serialize, Bytes, BytesReader, Decodable, SerialDecodable, SerialEncodable},
+    Result,
 };
 
-/// This struct represents a DHT key request
+use super::{record::Record, routing::Contact};
+
+/// This struct represents a daemon announcing itself to a peer
 #[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
-pub struct KeyRequest {
-    /// Request id    
+pub struct DhtAnnounce {
+    /// Daemon id
     pub id: blake3::Hash,
-    /// Daemon id requesting the key
-    pub from: blake3::Hash,
-    /// Daemon id holding the key
-    pub to: blake3::Hash,
-    /// Key entry
-    pub key: blake3::Hash,
+    /// Address the daemon can be reached at, if it accepts connections
+    pub addr: Option<Url>,
 }
 
-impl KeyRequest {
-    pub fn new(from: blake3::Hash, to: blake3::Hash, key: blake3::Hash) -> Self {
-        // Generate a random id
-        let mut rng = rand::thread_rng();
-        let n: u16 = rng.gen();
-        let id = blake3::hash(&serialize(&n));
-        Self { id, from, to, key }
+impl net::Message for DhtAnnounce {
+    fn name() -> &'static str {
+        "dhtannounce"
     }
 }
 
-impl net::Message for KeyRequest {
+/// This struct represents a request for the nodes closest to an id
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct FindNodeRequest {
+    /// Id to look up
+    pub target: blake3::Hash,
+}
+
+impl net::Message for FindNodeRequest {
     fn name() -> &'static str {
-        "keyrequest"
+        "findnoderequest"
     }
 }
 
-/// This struct represents a DHT key request response
-#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
-pub struct KeyResponse {
-    /// Response id
-    pub id: blake3::Hash,
-    /// Daemon id holding the key
-    pub from: blake3::Hash,
-    /// Daemon id holding the key
-    pub to: blake3::Hash,
-    /// Key entry
-    pub key: blake3::Hash,
-    /// Key value
-    pub value: Vec<u8>,
+impl net::Request for FindNodeRequest {
+    type Response = FindNodeResponse;
 }
 
-impl KeyResponse {
-    pub fn new(from: blake3::Hash, to: blake3::Hash, key: blake3::Hash, value: Vec<u8>) -> Self {
-        // Generate a random id
-        let mut rng = rand::thread_rng();
-        let n: u16 = rng.gen();
-        let id = blake3::hash(&serialize(&n));
-        Self { id, from, to, key, value }
-    }
+/// This struct represents a find node request response
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct FindNodeResponse {
+    /// Closest nodes to the target the daemon knows
+    pub nodes: Vec<Contact>,
 }
 
-impl net::Message for KeyResponse {
+impl net::Message for FindNodeResponse {
     fn name() -> &'static str {
-        "keyresponse"
+        "findnoderesponse"
     }
 }
 
-/// This struct represents a lookup map request
+/// This struct represents a request for a key's value
 #[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
-pub struct LookupRequest {
-    /// Request id    
-    pub id: blake3::Hash,
-    /// Daemon id executing the request
-    pub daemon: blake3::Hash,
+pub struct FindValueRequest {
     /// Key entry
     pub key: blake3::Hash,
-    /// Request type
-    pub req_type: u8, // 0 for insert, 1 for remove
 }
 
-impl LookupRequest {
-    pub fn new(daemon: blake3::Hash, key: blake3::Hash, req_type: u8) -> Self {
-        // Generate a random id
-        let mut rng = rand::thread_rng();
-        let n: u16 = rng.gen();
-        let id = blake3::hash(&serialize(&n));
-        Self { id, daemon, key, req_type }
+impl net::Message for FindValueRequest {
+    fn name() -> &'static str {
+        "findvaluerequest"
     }
 }
 
-impl net::Message for LookupRequest {
+impl net::Request for FindValueRequest {
+    type Response = FindValueResponse;
+}
+
+/// This struct represents a find value request response
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct FindValueResponse {
+    /// Signed key record, if the daemon holds it. Its timestamp and TTL
+    /// tell for how long it can be cached.
+    pub record: Option<Record>,
+    /// Daemon ids holding the key, from the daemon lookup map
+    pub providers: Vec<blake3::Hash>,
+    /// Closest nodes to the key the daemon knows
+    pub nodes: Vec<Contact>,
+}
+
+impl net::Message for FindValueResponse {
     fn name() -> &'static str {
-        "lookuprequest"
+        "findvalueresponse"
     }
 }
 
-/// Auxiliary structure used for lookup map syncing.
-#[derive(Debug, SerialEncodable, SerialDecodable)]
-pub struct LookupMapRequest {
-    /// Request id
-    pub id: blake3::Hash,
-    /// Daemon id executing the request
-    pub daemon: blake3::Hash,
+/// This struct represents a request for a chunk of a large value
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct ChunkRequest {
+    /// Chunk hash
+    pub hash: blake3::Hash,
 }
 
-impl LookupMapRequest {
-    pub fn new(daemon: blake3::Hash) -> Self {
-        // Generate a random id
-        let mut rng = rand::thread_rng();
-        let n: u16 = rng.gen();
-        let id = blake3::hash(&serialize(&n));
-        Self { id, daemon }
+impl net::Message for ChunkRequest {
+    fn name() -> &'static str {
+        "chunkrequest"
     }
 }
 
-impl net::Message for LookupMapRequest {
+impl net::Request for ChunkRequest {
+    type Response = ChunkResponse;
+}
+
+/// This struct represents a chunk request response
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct ChunkResponse {
+    /// Chunk data, if the daemon holds it
+    pub data: Option<Bytes>,
+}
+
+impl net::Message for ChunkResponse {
     fn name() -> &'static str {
-        "lookupmaprequest"
+        "chunkresponse"
+    }
+
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        let data = match bool::decode(&mut *reader)? {
+            true => Some(reader.read_bytes()?),
+            false => None,
+        };
+        Ok(Self { data })
     }
 }
 
-/// Auxiliary structure used for consensus syncing.
-#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
-pub struct LookupMapResponse {
+/// This struct represents a lookup map request, sent to the nodes
+/// closest to the key
+#[derive(Debug, Clone, SerialDecodable, SerialEncodable)]
+pub struct LookupRequest {
     /// Request id
     pub id: blake3::Hash,
-    /// Daemon lookup map, containing nodes that holds each key
-    pub lookup: FxHashMap<blake3::Hash, HashSet<blake3::Hash>>,
+    /// Daemon id executing the request
+    pub daemon: blake3::Hash,
+    /// Key entry
+    pub key: blake3::Hash,
+    /// Request type
+    pub req_type: u8, // 0 for insert, 1 for remove
+    /// Seconds before the record expires
+    pub ttl: u64,
 }
 
-impl LookupMapResponse {
-    pub fn new(lookup: FxHashMap<blake3::Hash, HashSet<blake3::Hash>>) -> Self {
+impl LookupRequest {
+    pub fn new(daemon: blake3::Hash, key: blake3::Hash, req_type: u8, ttl: u64) -> Self {
         // Generate a random id
         let mut rng = rand::thread_rng();
         let n: u16 = rng.gen();
         let id = blake3::hash(&serialize(&n));
-        Self { id, lookup }
+        Self { id, daemon, key, req_type, ttl }
     }
 }
 
-impl net::Message for LookupMapResponse {
+impl net::Message for LookupRequest {
     fn name() -> &'static str {
-        "lookupmapresponse"
+        "lookuprequest"
     }
 }
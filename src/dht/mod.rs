@@ -1,6 +1,20 @@
+pub mod chunk;
+pub use chunk::{Manifest, CHUNK_SIZE};
+
 pub mod dht;
-pub use dht::{waiting_for_response, Dht, DhtPtr};
+pub use dht::{
+    bootstrap, cache, cache_file, find_node, get_file, get_value, insert, insert_file, remove, Dht,
+    DhtPeer, DhtPtr, DEFAULT_TTL,
+};
+
+pub mod files;
 
 mod messages;
 
 mod protocol;
+
+pub mod record;
+pub use record::Record;
+
+pub mod routing;
+pub use routing::{Contact, RoutingTable};
@@ -3,160 +3,142 @@ use async_std::sync::Arc;
 use async_trait::async_trait;
 use chrono::Utc;
 use log::{debug, error};
+use std::time::Duration;
 
 use crate::{
     net::{
-        ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
-        ProtocolJobsManager, ProtocolJobsManagerPtr,
+        request::serve_requests, ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase,
+        ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr, Requester, RequesterPtr,
     },
     Result,
 };
 
 use super::{
-    dht::DhtPtr,
-    messages::{KeyRequest, KeyResponse, LookupMapRequest, LookupMapResponse, LookupRequest},
+    dht::{DhtPeer, DhtPtr, REQUEST_TIMEOUT},
+    messages::{
+        ChunkRequest, ChunkResponse, DhtAnnounce, FindNodeRequest, FindNodeResponse,
+        FindValueRequest, FindValueResponse, LookupRequest,
+    },
+    routing::{Contact, K},
 };
 
 pub struct Protocol {
     channel: ChannelPtr,
-    notify_queue_sender: async_channel::Sender<KeyResponse>,
-    req_sub: MessageSubscription<KeyRequest>,
-    resp_sub: MessageSubscription<KeyResponse>,
+    announce_sub: MessageSubscription<DhtAnnounce>,
     lookup_sub: MessageSubscription<LookupRequest>,
-    lookup_map_sub: MessageSubscription<LookupMapRequest>,
     jobsman: ProtocolJobsManagerPtr,
     dht: DhtPtr,
     p2p: P2pPtr,
 }
 
 impl Protocol {
-    pub async fn init(
-        channel: ChannelPtr,
-        notify_queue_sender: async_channel::Sender<KeyResponse>,
-        dht: DhtPtr,
-        p2p: P2pPtr,
-    ) -> Result<ProtocolBasePtr> {
+    pub async fn init(channel: ChannelPtr, dht: DhtPtr, p2p: P2pPtr) -> Result<ProtocolBasePtr> {
         debug!("Adding Protocol to the protocol registry");
         let msg_subsystem = channel.get_message_subsystem();
-        msg_subsystem.add_dispatch::<KeyRequest>().await;
-        msg_subsystem.add_dispatch::<KeyResponse>().await;
+        msg_subsystem.add_dispatch::<DhtAnnounce>().await;
         msg_subsystem.add_dispatch::<LookupRequest>().await;
-        msg_subsystem.add_dispatch::<LookupMapRequest>().await;
 
-        let req_sub = channel.subscribe_msg::<KeyRequest>().await?;
-        let resp_sub = channel.subscribe_msg::<KeyResponse>().await?;
+        let announce_sub = channel.subscribe_msg::<DhtAnnounce>().await?;
         let lookup_sub = channel.subscribe_msg::<LookupRequest>().await?;
-        let lookup_map_sub = channel.subscribe_msg::<LookupMapRequest>().await?;
 
         Ok(Arc::new(Self {
             channel: channel.clone(),
-            notify_queue_sender,
-            req_sub,
-            resp_sub,
+            announce_sub,
             lookup_sub,
-            lookup_map_sub,
             jobsman: ProtocolJobsManager::new("Protocol", channel),
             dht,
             p2p,
         }))
     }
 
-    async fn handle_receive_request(self: Arc<Self>) -> Result<()> {
-        debug!("Protocol::handle_receive_request() [START]");
-        let exclude_list = vec![self.channel.address()];
-        loop {
-            let req = match self.req_sub.receive().await {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Protocol::handle_receive_request(): recv fail: {}", e);
-                    continue
-                }
-            };
-
-            let req_copy = (*req).clone();
-            debug!("Protocol::handle_receive_request(): req: {:?}", req_copy);
+    async fn handle_receive_announce(
+        self: Arc<Self>,
+        find_node: RequesterPtr<FindNodeRequest>,
+        find_value: RequesterPtr<FindValueRequest>,
+        find_chunk: RequesterPtr<ChunkRequest>,
+    ) -> Result<()> {
+        debug!("Protocol::handle_receive_announce() [START]");
+        let announce = self.announce_sub.receive().await?;
+        debug!("Protocol::handle_receive_announce(): announce: {:?}", announce);
+
+        // Daemons that don't accept connections are only reachable
+        // through this channel
+        let addr = match &announce.addr {
+            Some(addr) => addr.clone(),
+            None => self.channel.address(),
+        };
+        let contact = Contact { id: announce.id, addr };
+
+        let peer = DhtPeer {
+            contact: contact.clone(),
+            channel: self.channel.clone(),
+            find_node,
+            find_value,
+            find_chunk,
+        };
+
+        let mut dht = self.dht.write().await;
+        if announce.id == dht.id {
+            debug!("Protocol::handle_receive_announce(): Connected to ourselves.");
+            return Ok(())
+        }
+        dht.peers.insert(announce.id, peer);
+        dht.add_contact(contact);
 
-            {
-                let dht = &mut self.dht.write().await;
-                if dht.seen.contains_key(&req_copy.id) {
-                    debug!(
-                        "Protocol::handle_receive_request(): We have already seen this request."
-                    );
-                    continue
-                }
+        Ok(())
+    }
 
-                dht.seen.insert(req_copy.id, Utc::now().timestamp());
+    async fn handle_receive_find_node(self: Arc<Self>) -> Result<()> {
+        debug!("Protocol::handle_receive_find_node() [START]");
+        let dht = self.dht.clone();
+        serve_requests(self.channel.clone(), move |req: FindNodeRequest| {
+            let dht = dht.clone();
+            async move {
+                debug!("Protocol::handle_receive_find_node(): req: {:?}", req);
+                let nodes = dht.read().await.routing.closest(&req.target, K);
+                Ok(FindNodeResponse { nodes })
             }
+        })
+        .await
+    }
 
-            let daemon = self.dht.read().await.id;
-            if daemon != req_copy.to {
-                if let Err(e) =
-                    self.p2p.broadcast_with_exclude(req_copy.clone(), &exclude_list).await
-                {
-                    error!("Protocol::handle_receive_response(): p2p broadcast fail: {}", e);
+    async fn handle_receive_find_value(self: Arc<Self>) -> Result<()> {
+        debug!("Protocol::handle_receive_find_value() [START]");
+        let dht = self.dht.clone();
+        serve_requests(self.channel.clone(), move |req: FindValueRequest| {
+            let dht = dht.clone();
+            async move {
+                debug!("Protocol::handle_receive_find_value(): req: {:?}", req);
+                let dht = dht.read().await;
+                let now = Utc::now().timestamp();
+                let record = dht.map.get(&req.key).filter(|r| r.is_valid(now)).cloned();
+                let providers = match dht.lookup.get(&req.key) {
+                    Some(s) => s.iter().copied().collect(),
+                    None => vec![],
                 };
-                continue
-            }
-
-            match self.dht.read().await.map.get(&req_copy.key) {
-                Some(value) => {
-                    let response =
-                        KeyResponse::new(daemon, req_copy.from, req_copy.key, value.clone());
-                    debug!("Protocol::handle_receive_request(): sending response: {:?}", response);
-                    if let Err(e) = self.channel.send(response).await {
-                        error!("Protocol::handle_receive_request(): p2p broadcast of response failed: {}", e);
-                    };
-                }
-                None => {
-                    error!("Protocol::handle_receive_request(): Requested key doesn't exist locally: {}", req_copy.key);
-                }
+                let nodes = dht.routing.closest(&req.key, K);
+                Ok(FindValueResponse { record, providers, nodes })
             }
-        }
+        })
+        .await
     }
 
-    async fn handle_receive_response(self: Arc<Self>) -> Result<()> {
-        debug!("Protocol::handle_receive_response() [START]");
-        let exclude_list = vec![self.channel.address()];
-        loop {
-            let resp = match self.resp_sub.receive().await {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Protocol::handle_receive_response(): recv fail: {}", e);
-                    continue
-                }
-            };
-
-            let resp_copy = (*resp).clone();
-            debug!("Protocol::handle_receive_response(): resp: {:?}", resp_copy);
-
-            {
-                let dht = &mut self.dht.write().await;
-                if dht.seen.contains_key(&resp_copy.id) {
-                    debug!(
-                        "Protocol::handle_receive_request(): We have already seen this request."
-                    );
-                    continue
-                }
-
-                dht.seen.insert(resp_copy.id, Utc::now().timestamp());
-            }
-
-            if self.dht.read().await.id != resp_copy.to {
-                if let Err(e) =
-                    self.p2p.broadcast_with_exclude(resp_copy.clone(), &exclude_list).await
-                {
-                    error!("Protocol::handle_receive_response(): p2p broadcast fail: {}", e);
-                };
-                continue
+    async fn handle_receive_chunk(self: Arc<Self>) -> Result<()> {
+        debug!("Protocol::handle_receive_chunk() [START]");
+        let dht = self.dht.clone();
+        serve_requests(self.channel.clone(), move |req: ChunkRequest| {
+            let dht = dht.clone();
+            async move {
+                debug!("Protocol::handle_receive_chunk(): req: {:?}", req);
+                let data = dht.read().await.chunks.get(&req.hash).cloned();
+                Ok(ChunkResponse { data })
             }
-
-            self.notify_queue_sender.send(resp_copy.clone()).await?;
-        }
+        })
+        .await
     }
 
     async fn handle_receive_lookup_request(self: Arc<Self>) -> Result<()> {
         debug!("Protocol::handle_receive_lookup_request() [START]");
-        let exclude_list = vec![self.channel.address()];
         loop {
             let req = match self.lookup_sub.receive().await {
                 Ok(v) => v,
@@ -169,71 +151,34 @@ impl Protocol {
             let req_copy = (*req).clone();
             debug!("Protocol::handle_receive_lookup_request(): req: {:?}", req_copy);
 
-            if !(0..=1).contains(&req_copy.req_type) {
-                debug!("Protocol::handle_receive_lookup_request(): Unknown request type.");
-                continue
-            }
-
-            {
-                let dht = &mut self.dht.write().await;
-                if dht.seen.contains_key(&req_copy.id) {
-                    debug!(
-                        "Protocol::handle_receive_request(): We have already seen this request."
-                    );
+            let result = match req_copy.req_type {
+                0 => self.dht.write().await.lookup_insert(
+                    req_copy.key,
+                    req_copy.daemon,
+                    req_copy.ttl,
+                ),
+                1 => self.dht.write().await.lookup_remove(req_copy.key, req_copy.daemon),
+                _ => {
+                    debug!("Protocol::handle_receive_lookup_request(): Unknown request type.");
                     continue
                 }
-
-                dht.seen.insert(req_copy.id, Utc::now().timestamp());
-            }
-
-            let result = match req_copy.req_type {
-                0 => self.dht.write().await.lookup_insert(req_copy.key, req_copy.daemon),
-                _ => self.dht.write().await.lookup_remove(req_copy.key, req_copy.daemon),
             };
 
             if let Err(e) = result {
                 error!("Protocol::handle_receive_lookup_request(): request action failed: {}", e);
-                continue
-            };
-
-            if let Err(e) = self.p2p.broadcast_with_exclude(req_copy, &exclude_list).await {
-                error!("Protocol::handle_receive_lookup_request(): p2p broadcast fail: {}", e);
             };
         }
     }
 
-    async fn handle_receive_lookup_map_request(self: Arc<Self>) -> Result<()> {
-        debug!("Protocol::handle_receive_lookup_map_request() [START]");
-        loop {
-            let req = match self.lookup_map_sub.receive().await {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Protocol::handle_receive_lookup_map_request(): recv fail: {}", e);
-                    continue
-                }
-            };
+    async fn remove_peer_on_stop(self: Arc<Self>) {
+        let stop_sub = match self.channel.subscribe_stop().await {
+            Ok(sub) => sub,
+            Err(_) => return,
+        };
+        stop_sub.receive().await;
 
-            debug!("Protocol::handle_receive_lookup_map_request(): req: {:?}", req);
-
-            {
-                let dht = &mut self.dht.write().await;
-                if dht.seen.contains_key(&req.id) {
-                    debug!(
-                        "Protocol::handle_receive_lookup_map_request(): We have already seen this request."
-                    );
-                    continue
-                }
-
-                dht.seen.insert(req.id, Utc::now().timestamp());
-            }
-
-            // Extra validations can be added here.
-            let lookup = self.dht.read().await.lookup.clone();
-            let response = LookupMapResponse::new(lookup);
-            if let Err(e) = self.channel.send(response).await {
-                error!("Protocol::handle_receive_lookup_map_request() channel send fail: {}", e);
-            };
-        }
+        let addr = self.channel.address();
+        self.dht.write().await.peers.retain(|_, peer| peer.channel.address() != addr);
     }
 }
 
@@ -241,17 +186,41 @@ impl Protocol {
 impl ProtocolBase for Protocol {
     async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
         debug!("Protocol::start() [START]");
+        let timeout = Duration::from_millis(REQUEST_TIMEOUT);
+        let find_node =
+            Requester::<FindNodeRequest>::new(self.channel.clone(), timeout, executor.clone())
+                .await?;
+        let find_value =
+            Requester::<FindValueRequest>::new(self.channel.clone(), timeout, executor.clone())
+                .await?;
+        let find_chunk =
+            Requester::<ChunkRequest>::new(self.channel.clone(), timeout, executor.clone()).await?;
+
         self.jobsman.clone().start(executor.clone());
-        self.jobsman.clone().spawn(self.clone().handle_receive_request(), executor.clone()).await;
-        self.jobsman.clone().spawn(self.clone().handle_receive_response(), executor.clone()).await;
         self.jobsman
             .clone()
-            .spawn(self.clone().handle_receive_lookup_request(), executor.clone())
+            .spawn(
+                self.clone().handle_receive_announce(find_node, find_value, find_chunk),
+                executor.clone(),
+            )
+            .await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_find_node(), executor.clone()).await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_find_value(), executor.clone())
             .await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_chunk(), executor.clone()).await;
         self.jobsman
             .clone()
-            .spawn(self.clone().handle_receive_lookup_map_request(), executor.clone())
+            .spawn(self.clone().handle_receive_lookup_request(), executor.clone())
             .await;
+        executor.spawn(self.clone().remove_peer_on_stop()).detach();
+
+        // Let the peer know who we are
+        let id = self.dht.read().await.id;
+        let addr = self.p2p.external_addr().await.first().cloned();
+        self.channel.send(DhtAnnounce { id, addr }).await?;
+
         debug!("Protocol::start() [END]");
         Ok(())
     }
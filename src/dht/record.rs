@@ -0,0 +1,104 @@
+use chrono::Utc;
+use ed25519_compact::{KeyPair, PublicKey, Signature};
+
+use crate::util::serial::{serialize, SerialDecodable, SerialEncodable};
+
+/// Seconds a record timestamp may be ahead of our clock
+const MAX_CLOCK_DRIFT: u64 = 60;
+
+/// A value stored in the DHT, signed by its publisher over the key, the
+/// value, the publication timestamp and the TTL. Nodes only store and
+/// serve records with a valid signature, so a record can't be altered
+/// or have its lifetime extended by anyone but its publisher.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct Record {
+    /// Key entry
+    pub key: blake3::Hash,
+    /// Key value
+    pub value: Vec<u8>,
+    /// UNIX timestamp of the publication
+    pub timestamp: u64,
+    /// Seconds the record lives after its publication
+    pub ttl: u64,
+    /// Publisher ed25519 public key
+    pub publisher: [u8; 32],
+    /// Publisher signature
+    pub signature: [u8; 64],
+}
+
+impl Record {
+    /// Create a record published now, signed with the given keypair.
+    pub fn new(keypair: &KeyPair, key: blake3::Hash, value: Vec<u8>, ttl: u64) -> Self {
+        let timestamp = Utc::now().timestamp() as u64;
+        let mut record =
+            Self { key, value, timestamp, ttl, publisher: *keypair.pk, signature: [0; 64] };
+        record.signature = *keypair.sk.sign(record.signed_data(), None);
+        record
+    }
+
+    fn signed_data(&self) -> Vec<u8> {
+        [
+            serialize(&self.key),
+            serialize(&self.value),
+            serialize(&self.timestamp),
+            serialize(&self.ttl),
+        ]
+        .concat()
+    }
+
+    /// Verify the publisher signature.
+    pub fn verify(&self) -> bool {
+        let publisher = PublicKey::new(self.publisher);
+        publisher.verify(self.signed_data(), &Signature::new(self.signature)).is_ok()
+    }
+
+    /// Check the record is correctly signed, and alive at the given time.
+    pub fn is_valid(&self, now: i64) -> bool {
+        self.timestamp <= now as u64 + MAX_CLOCK_DRIFT && !self.is_expired(now) && self.verify()
+    }
+
+    /// UNIX timestamp the record expires at
+    pub fn expiry(&self) -> i64 {
+        (self.timestamp + self.ttl) as i64
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry() <= now
+    }
+
+    /// Remaining seconds before the record expires
+    pub fn remaining_ttl(&self, now: i64) -> u64 {
+        (self.expiry() - now).max(0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let keypair = KeyPair::generate();
+        let key = blake3::hash(b"key");
+        let record = Record::new(&keypair, key, b"value".to_vec(), 3600);
+        let now = Utc::now().timestamp();
+
+        assert!(record.is_valid(now));
+        assert!(record.remaining_ttl(now) <= 3600);
+        assert!(!record.is_valid(now + 3600));
+
+        // Tampering breaks the signature
+        let mut poisoned = record.clone();
+        poisoned.value = b"poison".to_vec();
+        assert!(!poisoned.is_valid(now));
+
+        let mut extended = record.clone();
+        extended.ttl = 7200;
+        assert!(!extended.is_valid(now));
+
+        // Re-signing with another key doesn't pass as the publisher
+        let mut forged = Record::new(&KeyPair::generate(), key, b"poison".to_vec(), 3600);
+        forged.publisher = record.publisher;
+        assert!(!forged.is_valid(now));
+    }
+}
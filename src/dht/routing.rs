@@ -0,0 +1,220 @@
+use chrono::Utc;
+use rand::Rng;
+use url::Url;
+
+use crate::util::serial::{SerialDecodable, SerialEncodable};
+
+/// Number of contacts a bucket holds, and number of nodes a lookup returns
+pub const K: usize = 20;
+/// Number of nodes queried in parallel in each round of a lookup
+pub const ALPHA: usize = 3;
+/// Number of bits of a node id
+const ID_BITS: usize = 256;
+
+/// A DHT node, identified by its id, and the address it can be reached at.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SerialEncodable, SerialDecodable)]
+pub struct Contact {
+    /// Daemon id
+    pub id: blake3::Hash,
+    /// Daemon P2P address
+    pub addr: Url,
+}
+
+/// XOR distance between two ids, as a big-endian number.
+pub fn distance(a: &blake3::Hash, b: &blake3::Hash) -> [u8; 32] {
+    let mut ret = [0; 32];
+    for (i, byte) in ret.iter_mut().enumerate() {
+        *byte = a.as_bytes()[i] ^ b.as_bytes()[i];
+    }
+    ret
+}
+
+/// Index of the bucket `id` falls in, seen from `own`: the position of
+/// the highest bit of their distance. `None` if the ids are equal.
+fn bucket_index(own: &blake3::Hash, id: &blake3::Hash) -> Option<usize> {
+    let d = distance(own, id);
+    let (i, byte) = d.iter().enumerate().find(|(_, byte)| **byte != 0)?;
+    Some(ID_BITS - 1 - (i * 8 + byte.leading_zeros() as usize))
+}
+
+#[derive(Debug, Clone)]
+struct KBucket {
+    /// Contacts ordered from least to most recently seen
+    contacts: Vec<Contact>,
+    /// Timestamp of the last lookup of an id in this bucket
+    last_lookup: i64,
+}
+
+/// Kademlia routing table. Contacts are kept in buckets by their XOR
+/// distance to our own id: bucket `i` holds up to [`K`] contacts at a
+/// distance in `[2^i, 2^(i+1))`. We know many nodes close to us, and
+/// only a few of the ones further away.
+#[derive(Debug, Clone)]
+pub struct RoutingTable {
+    /// Daemon id
+    pub id: blake3::Hash,
+    buckets: Vec<KBucket>,
+}
+
+impl RoutingTable {
+    pub fn new(id: blake3::Hash) -> Self {
+        let now = Utc::now().timestamp();
+        let bucket = KBucket { contacts: vec![], last_lookup: now };
+        Self { id, buckets: vec![bucket; ID_BITS] }
+    }
+
+    /// Mark the contact as most recently seen, adding it if its bucket has
+    /// room. If the bucket is full, its least recently seen contact is
+    /// returned, for the caller to check if it's still alive and
+    /// [`RoutingTable::replace`] it otherwise.
+    pub fn insert(&mut self, contact: Contact) -> Option<Contact> {
+        let index = bucket_index(&self.id, &contact.id)?;
+        let bucket = &mut self.buckets[index];
+
+        if let Some(pos) = bucket.contacts.iter().position(|c| c.id == contact.id) {
+            bucket.contacts.remove(pos);
+            bucket.contacts.push(contact);
+            return None
+        }
+
+        if bucket.contacts.len() < K {
+            bucket.contacts.push(contact);
+            return None
+        }
+
+        Some(bucket.contacts[0].clone())
+    }
+
+    /// Replace a contact of a full bucket with a new one of the same bucket.
+    pub fn replace(&mut self, old: &blake3::Hash, contact: Contact) {
+        self.remove(old);
+        self.insert(contact);
+    }
+
+    /// Remove the contact with the given id.
+    pub fn remove(&mut self, id: &blake3::Hash) {
+        if let Some(index) = bucket_index(&self.id, id) {
+            self.buckets[index].contacts.retain(|c| &c.id != id);
+        }
+    }
+
+    /// Fetch the contact with the given id.
+    pub fn get(&self, id: &blake3::Hash) -> Option<&Contact> {
+        let index = bucket_index(&self.id, id)?;
+        self.buckets[index].contacts.iter().find(|c| &c.id == id)
+    }
+
+    /// The `n` known contacts closest to `target`, closest first.
+    pub fn closest(&self, target: &blake3::Hash, n: usize) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> =
+            self.buckets.iter().flat_map(|bucket| bucket.contacts.iter().cloned()).collect();
+        contacts.sort_unstable_by_key(|c| distance(&c.id, target));
+        contacts.truncate(n);
+        contacts
+    }
+
+    /// Number of contacts in the table.
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.contacts.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record a lookup of `target`, so its bucket doesn't need a refresh.
+    pub fn mark_lookup(&mut self, target: &blake3::Hash) {
+        if let Some(index) = bucket_index(&self.id, target) {
+            self.buckets[index].last_lookup = Utc::now().timestamp();
+        }
+    }
+
+    /// Indexes of the non-empty buckets nothing was looked up in for the
+    /// last `max_age` seconds.
+    pub fn stale_buckets(&self, max_age: i64) -> Vec<usize> {
+        let now = Utc::now().timestamp();
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| !bucket.contacts.is_empty() && now - bucket.last_lookup > max_age)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// A random id falling in the bucket with the given index, to refresh it.
+    pub fn random_id(&self, index: usize) -> blake3::Hash {
+        let mut d: [u8; 32] = rand::thread_rng().gen();
+
+        // Keep bit `index` as the highest bit set of the distance
+        let byte = ID_BITS / 8 - 1 - index / 8;
+        let bit = index % 8;
+        d[..byte].fill(0);
+        d[byte] &= (1 << bit) - 1;
+        d[byte] |= 1 << bit;
+
+        let mut id = [0; 32];
+        for (i, b) in id.iter_mut().enumerate() {
+            *b = self.id.as_bytes()[i] ^ d[i];
+        }
+        id.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(n: u64) -> Contact {
+        let addr = Url::parse(&format!("tcp://127.0.0.1:{}", 10000 + n)).unwrap();
+        Contact { id: blake3::hash(&n.to_le_bytes()), addr }
+    }
+
+    #[test]
+    fn test_routing_table() {
+        let own = blake3::hash(b"own");
+        let mut table = RoutingTable::new(own);
+
+        assert_eq!(bucket_index(&own, &own), None);
+        for index in [0, 7, 8, 100, 255] {
+            assert_eq!(bucket_index(&own, &table.random_id(index)), Some(index));
+        }
+
+        // Buckets hold at most K contacts, and report their oldest one
+        // once full
+        let index = 255;
+        let mut evicted = None;
+        let mut inserted = vec![];
+        for n in 0..1000 {
+            let c = contact(n);
+            if bucket_index(&own, &c.id) != Some(index) {
+                continue
+            }
+            match table.insert(c.clone()) {
+                Some(oldest) => {
+                    evicted = Some((oldest, c));
+                    break
+                }
+                None => inserted.push(c),
+            }
+        }
+        assert_eq!(inserted.len(), K);
+        let (oldest, newcomer) = evicted.unwrap();
+        assert_eq!(oldest, inserted[0]);
+
+        table.replace(&oldest.id, newcomer.clone());
+        assert!(table.get(&oldest.id).is_none());
+        assert_eq!(table.get(&newcomer.id), Some(&newcomer));
+        assert_eq!(table.len(), K);
+
+        // Closest contacts come sorted by distance
+        let target = blake3::hash(b"target");
+        let closest = table.closest(&target, 5);
+        assert_eq!(closest.len(), 5);
+        for pair in closest.windows(2) {
+            assert!(distance(&pair[0].id, &target) < distance(&pair[1].id, &target));
+        }
+
+        assert!(table.stale_buckets(-1).contains(&index));
+        assert!(table.stale_buckets(3600).is_empty());
+    }
+}
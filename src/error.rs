@@ -110,6 +110,9 @@ pub enum Error {
     #[error("Channel timed out")]
     ChannelTimeout,
 
+    #[error("Request timed out")]
+    RequestTimeout,
+
     #[error("Network service stopped")]
     NetworkServiceStopped,
 
@@ -122,12 +125,21 @@ pub enum Error {
     #[error("Accept a new tls connection from the listener {0} failed")]
     AcceptTlsConnectionFailed(String),
 
+    #[error("TLS certificate error: {0}")]
+    TlsCertificateError(String),
+
     #[error("Network operation failed")]
     NetworkOperationFailed,
 
+    #[error("No dispatcher for message: {0}")]
+    UnknownMessage(String),
+
     #[error("Malformed packet")]
     MalformedPacket,
 
+    #[error("Channel transport encryption mismatch")]
+    ChannelEncryptionMismatch,
+
     #[error("Socks proxy error: {0}")]
     SocksError(String),
 
@@ -148,6 +160,15 @@ pub enum Error {
     #[error("Tor error: {0}")]
     TorError(String),
 
+    #[error("I2P error: {0}")]
+    I2pError(String),
+
+    #[error("NAT traversal error: {0}")]
+    NatError(String),
+
+    #[error("Cannot derive an external address from {0}")]
+    InvalidExternalAddr(String),
+
     #[error("Node is not connected to other nodes.")]
     NetworkNotConnected,
 
@@ -161,6 +182,15 @@ pub enum Error {
     #[error("Unable to decrypt mint note")]
     NoteDecryptionFailed,
 
+    #[error("No key for messaging channel {0}")]
+    MissingChannelKey(String),
+
+    #[error("Unable to encrypt message")]
+    MessageEncryptionFailed,
+
+    #[error("Unable to decrypt message of channel {0}")]
+    MessageDecryptionFailed(String),
+
     #[error("No keypair file detected")]
     KeypairPathNotFound,
 
@@ -179,6 +209,45 @@ pub enum Error {
     #[error("Invalid DarkFi address")]
     InvalidAddress,
 
+    #[error("Invalid invoice: {0}")]
+    InvalidInvoice(String),
+
+    #[error("Invoice has expired")]
+    InvoiceExpired,
+
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("Invalid key derivation path: {0}")]
+    InvalidDerivationPath(String),
+
+    #[error("Public key is not one of the MuSig signers")]
+    MuSigUnknownSigner,
+
+    #[error("MuSig nonce doesn't match its commitment")]
+    MuSigNonceMismatch,
+
+    #[error("MuSig partial signatures don't aggregate into a valid signature")]
+    MuSigInvalidSignature,
+
+    #[error("Invalid FROST threshold {0} for {1} participants")]
+    FrostInvalidThreshold(u16, usize),
+
+    #[error("Unknown FROST participant")]
+    FrostUnknownParticipant,
+
+    #[error("Invalid FROST commitment from participant {0}")]
+    FrostInvalidCommitment(u16),
+
+    #[error("Invalid FROST share from participant {0}")]
+    FrostInvalidShare(u16),
+
+    #[error("Missing FROST participant {0}")]
+    FrostMissingParticipant(u16),
+
+    #[error("FROST signing with {0} signers, below the threshold of {1}")]
+    FrostNotEnoughSigners(usize, u16),
+
     #[cfg(feature = "futures-rustls")]
     #[error(transparent)]
     RustlsError(#[from] futures_rustls::rustls::Error),
@@ -201,6 +270,81 @@ pub enum Error {
     #[error("JSON-RPC error: {0}")]
     JsonRpcError(String),
 
+    #[error("Transaction rejected from mempool: {0}")]
+    MempoolRejected(String),
+
+    #[error("Transaction already in mempool")]
+    MempoolDuplicate,
+
+    #[error("Transaction conflicts with a mempool transaction")]
+    MempoolConflict,
+
+    #[error("Mempool is full")]
+    MempoolFull,
+
+    #[error("Invalid swap counterparty key")]
+    SwapInvalidKey,
+
+    #[error("Swap preimage doesn't match the hashlock")]
+    SwapInvalidPreimage,
+
+    #[error("Unknown swap lock")]
+    SwapUnknownLock,
+
+    #[error("Swap lock already exists")]
+    SwapLockExists,
+
+    #[error("Swap lock was already claimed or refunded")]
+    SwapLockSpent,
+
+    #[error("Swap timelock has not expired yet")]
+    SwapTimelockActive,
+
+    #[error("Swap timelock has expired")]
+    SwapTimelockExpired,
+
+    #[error("Invalid swap contract call: {0}")]
+    SwapInvalidCall(String),
+
+    #[error("Token is already registered")]
+    TokenAlreadyRegistered,
+
+    #[error("Token ticker {0} is already registered")]
+    TokenTickerTaken(String),
+
+    #[error("Invalid token metadata: {0}")]
+    TokenInvalidMetadata(String),
+
+    #[error("Invalid token registry call: {0}")]
+    TokenRegistryInvalidCall(String),
+
+    #[error("Unknown token")]
+    TokenUnknown,
+
+    #[error("Minting of the token is frozen")]
+    TokenMintingFrozen,
+
+    #[error("Mint exceeds the token's supply cap")]
+    TokenSupplyCapExceeded,
+
+    #[error("Invalid token issuance call: {0}")]
+    TokenIssuanceInvalidCall(String),
+
+    #[error("Invalid slashing evidence: {0}")]
+    InvalidEvidence(String),
+
+    #[error("Participant {0} is already slashed")]
+    ParticipantAlreadySlashed(String),
+
+    #[error("Invalid slashing call: {0}")]
+    SlashingInvalidCall(String),
+
+    #[error("Invalid circuit: {0}")]
+    InvalidCircuit(String),
+
+    #[error("Invalid circuit registry call: {0}")]
+    CircuitRegistryInvalidCall(String),
+
     // ===============
     // Database errors
     // ===============
@@ -227,6 +371,48 @@ pub enum Error {
     #[error("Block {0} metadata not found in database")]
     BlockMetadataNotFound(String),
 
+    #[error("Filter of block {0} not found in database")]
+    BlockFilterNotFound(String),
+
+    #[error("Filter of block in slot {0} does not match its filter header")]
+    BlockFilterMismatch(u64),
+
+    #[error("Coin not found in the Merkle tree")]
+    CoinNotFound,
+
+    #[error("Blockchain is missing blocks needed to rebuild the Merkle tree")]
+    MerkleTreeIncomplete,
+
+    #[error("Block in slot {0} does not match the checkpoint")]
+    CheckpointMismatch(u64),
+
+    #[error("Block in slot {0} would reorg past the last checkpoint")]
+    ReorgPastCheckpoint(u64),
+
+    #[error("Block in slot {0} does not extend the previous block")]
+    BlockNotExtending(u64),
+
+    #[error("Received block bodies don't match the requested headers")]
+    BlockBodiesMismatch,
+
+    #[error("No connected peer keeps the block body of slot {0}")]
+    BlockBodiesPruned(u64),
+
+    #[error("State snapshot file is invalid")]
+    SnapshotInvalid,
+
+    #[error("State snapshot checksum mismatch")]
+    SnapshotChecksumMismatch,
+
+    #[error("State snapshots can only be imported into an empty blockchain")]
+    SnapshotBlockchainNotEmpty,
+
+    #[error("State snapshot block of slot {0} doesn't match a trusted checkpoint")]
+    SnapshotUntrusted(u64),
+
+    #[error("State snapshot Merkle root mismatch")]
+    SnapshotRootMismatch,
+
     // =============
     // Wallet errors
     // =============
@@ -236,6 +422,12 @@ pub enum Error {
     #[error("Merkle tree already exists in wallet")]
     WalletTreeExists,
 
+    #[error("Wallet already has a seed")]
+    WalletSeedExists,
+
+    #[error("Wallet schema version {0} is newer than the latest known version {1}")]
+    WalletSchemaTooNew(u32, u32),
+
     // ===================
     // wasm runtime errors
     // ===================
@@ -259,6 +451,18 @@ pub enum Error {
     #[error("wasm runtime out of memory")]
     WasmerOomError,
 
+    #[cfg(feature = "wasm-runtime")]
+    #[error("Contract ran out of gas: limit {0}")]
+    WasmerOutOfGas(u64),
+
+    #[cfg(feature = "wasm-runtime")]
+    #[error("Invalid wasm contract module: {0}")]
+    WasmerInvalidModule(String),
+
+    #[cfg(feature = "wasm-runtime")]
+    #[error("Contract returned an error: {0}")]
+    WasmerContractError(String),
+
     // ====================
     // Miscellaneous errors
     // ====================
@@ -291,6 +495,9 @@ pub enum Error {
     #[error("Failed decoding bincode: {0}")]
     ZkasDecoderError(String),
 
+    #[error("Invalid zk witness: {0}")]
+    ZkWitnessInvalid(String),
+
     #[cfg(feature = "util")]
     #[error("System clock is not correct!")]
     InvalidClock,
@@ -310,11 +517,23 @@ pub enum Error {
     #[error(transparent)]
     ClientFailed(#[from] ClientFailed),
 
+    #[error(transparent)]
+    BuilderFailed(#[from] BuilderFailed),
+
     // ==============
     // DHT errors
     // ==============
     #[error("Did not find key")]
     UnknownKey,
+
+    #[error("DHT record signature or lifetime is invalid")]
+    InvalidDhtRecord,
+
+    #[error("DHT chunk doesn't match its manifest")]
+    InvalidDhtChunk,
+
+    #[error("DHT manifest doesn't match the content hash")]
+    InvalidDhtManifest,
 }
 
 /// Transaction verification errors
@@ -329,12 +548,27 @@ pub enum VerifyFailed {
     #[error("Invalid cashier/faucet public key for clear input {0}")]
     InvalidCashierOrFaucetKey(usize),
 
+    #[error("Clear input {0} mints an issued token without a mint call")]
+    UnbackedClearInput(usize),
+
+    #[error("Mint call {0} has no matching clear input")]
+    UnmatchedMint(usize),
+
+    #[error("Transaction escrow doesn't match its swap locks")]
+    UnmatchedEscrow,
+
+    #[error("Swap claim or refund call {0} has no matching clear input")]
+    UnmatchedRelease(usize),
+
     #[error("Invalid Merkle root for input {0}")]
     InvalidMerkle(usize),
 
     #[error("Nullifier already exists for input {0}")]
     NullifierExists(usize),
 
+    #[error("Input {0} spends a coin that is still time-locked")]
+    CoinLocked(usize),
+
     #[error("Invalid signature for input {0}")]
     InputSignature(usize),
 
@@ -344,6 +578,9 @@ pub enum VerifyFailed {
     #[error("Token commitments in inputs or outputs to not match")]
     TokenMismatch,
 
+    #[error("Transaction fee is not paid in DRK")]
+    FeeToken,
+
     #[error("Money in does not match money out (value commitments)")]
     MissingFunds,
 
@@ -356,6 +593,33 @@ pub enum VerifyFailed {
     #[error("Failed verifying zk proofs: {0}")]
     ProofVerifyFailed(String),
 
+    #[error("Transaction gas limit {0} exceeds the maximum")]
+    GasLimit(u64),
+
+    #[error("Invalid contract bincode for deploy {0}: {1}")]
+    InvalidContract(usize, String),
+
+    #[error("Contract already deployed for deploy {0}")]
+    ContractExists(usize),
+
+    #[error("Invalid signature for call public key {0}")]
+    CallSignature(usize),
+
+    #[error("Unknown contract for call {0}")]
+    UnknownContract(usize),
+
+    #[error("Unknown function {1} for call {0}")]
+    UnknownFunction(usize, u8),
+
+    #[error("Circuit {1} v{2} is not active for call {0}")]
+    InactiveCircuit(usize, String, u32),
+
+    #[error("Circuit {1} v{2} proof verification failure for call {0}")]
+    CallProof(usize, String, u32),
+
+    #[error("Contract call {0} failed: {1}")]
+    ContractCall(usize, String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -372,6 +636,9 @@ pub enum ClientFailed {
     #[error("Invalid amount: {0}")]
     InvalidAmount(u64),
 
+    #[error("Transaction has no recipients")]
+    NoRecipients,
+
     #[error("Internal error: {0}")]
     InternalError(String),
 
@@ -379,6 +646,28 @@ pub enum ClientFailed {
     VerifyError(String),
 }
 
+/// Transaction builder errors
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BuilderFailed {
+    #[error("Transaction has no inputs")]
+    NoInputs,
+
+    #[error("Transaction has no outputs")]
+    NoOutputs,
+
+    #[error("Inputs and outputs must all be of the same token")]
+    MixedTokens,
+
+    #[error("Fees can only be paid by transactions in DRK")]
+    FeeToken,
+
+    #[error("Transaction values overflow")]
+    ValueOverflow,
+
+    #[error("Inputs of {0} don't match outputs of {1} and fee of {2}")]
+    Unbalanced(u64, u64, u64),
+}
+
 impl From<Error> for VerifyFailed {
     fn from(err: Error) -> Self {
         Self::InternalError(err.to_string())
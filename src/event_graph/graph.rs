@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io,
+};
+
+use log::debug;
+
+use crate::{
+    util::{
+        serial::{deserialize, serialize, Decodable, Encodable},
+        time::Timestamp,
+    },
+    Error, Result,
+};
+
+const SLED_EVENTS_TREE: &[u8] = b"_events";
+
+/// Maximum number of parents an event can link to
+pub const MAX_PARENTS: usize = 16;
+/// Maximum number of events held while waiting for their parents
+pub const MAX_ORPHANS: usize = 1000;
+
+/// An event of the graph, carrying an application payload. Events link
+/// to the heads of the graph as their author saw it, so the graph records
+/// which events happened before which.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event<T> {
+    /// Hashes of the events this one follows
+    pub parents: Vec<blake3::Hash>,
+    /// UNIX timestamp, always later than the parents' ones
+    pub timestamp: u64,
+    /// Application data
+    pub payload: T,
+}
+
+impl<T: Encodable> Event<T> {
+    /// Hash identifying the event.
+    pub fn hash(&self) -> blake3::Hash {
+        blake3::hash(&serialize(self))
+    }
+
+    /// Key concurrent events are ordered by. Every node orders the same
+    /// events the same way, so the latest one by this key wins a conflict.
+    pub fn order_key(&self) -> (u64, [u8; 32]) {
+        (self.timestamp, *self.hash().as_bytes())
+    }
+}
+
+impl<T: Encodable> Encodable for Event<T> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.parents.encode(&mut s)?;
+        len += self.timestamp.encode(&mut s)?;
+        len += self.payload.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl<T: Decodable> Decodable for Event<T> {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            parents: Decodable::decode(&mut d)?,
+            timestamp: Decodable::decode(&mut d)?,
+            payload: Decodable::decode(&mut d)?,
+        })
+    }
+}
+
+/// Directed acyclic graph of hash-linked events, replicated between
+/// nodes with eventual consistency. Unlike Raft, it needs no leader or
+/// stable quorum: nodes create events whenever they want, and exchange
+/// the events they are missing when they meet.
+///
+/// Events are only added once all their parents are known. The ones that
+/// arrive before their parents are held as orphans in the meantime. Added
+/// events are kept in a `sled` tree, keyed by their hash.
+pub struct EventGraph<T> {
+    /// Events connected to the graph, by hash
+    events: HashMap<blake3::Hash, Event<T>>,
+    /// Events no other event follows yet
+    heads: HashSet<blake3::Hash>,
+    /// Events waiting for their parents, by hash
+    orphans: HashMap<blake3::Hash, Event<T>>,
+    tree: sled::Tree,
+}
+
+impl<T: Encodable + Decodable + Clone> EventGraph<T> {
+    /// Opens a new or existing `EventGraph` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_EVENTS_TREE)?;
+
+        let mut events = HashMap::new();
+        for item in tree.iter() {
+            let (key, value) = item?;
+            let hash_bytes: [u8; 32] = key.as_ref().try_into().unwrap();
+            events.insert(hash_bytes.into(), deserialize::<Event<T>>(&value)?);
+        }
+
+        let parents: HashSet<blake3::Hash> =
+            events.values().flat_map(|event| event.parents.iter().copied()).collect();
+        let heads = events.keys().filter(|hash| !parents.contains(hash)).copied().collect();
+
+        debug!(target: "event_graph", "Loaded {} events", events.len());
+        Ok(Self { events, heads, orphans: HashMap::new(), tree })
+    }
+
+    /// Create an event with the given payload following the current heads,
+    /// and add it to the graph.
+    pub fn new_event(&mut self, payload: T) -> Result<Event<T>> {
+        let mut parents = self.heads();
+        parents.truncate(MAX_PARENTS);
+
+        let now = Timestamp::current_time().0 as u64;
+        let timestamp = match parents.iter().map(|hash| self.events[hash].timestamp).max() {
+            Some(ts) => now.max(ts + 1),
+            None => now,
+        };
+
+        let event = Event { parents, timestamp, payload };
+        self.insert(event.clone())?;
+        Ok(event)
+    }
+
+    /// Add an event to the graph. Returns the events that got connected
+    /// to the graph, in causal order: the event itself if all its parents
+    /// are known, followed by the orphans that were waiting on it. Events
+    /// already seen return nothing.
+    pub fn insert(&mut self, event: Event<T>) -> Result<Vec<Event<T>>> {
+        let hash = event.hash();
+        if self.contains(&hash) {
+            return Ok(vec![])
+        }
+
+        if event.parents.len() > MAX_PARENTS {
+            return Err(Error::MalformedPacket)
+        }
+
+        let connected = event.parents.iter().all(|parent| self.events.contains_key(parent));
+        if !connected && self.orphans.len() >= MAX_ORPHANS {
+            debug!(target: "event_graph", "Orphan pool full, dropping event {}", hash);
+            return Ok(vec![])
+        }
+
+        self.orphans.insert(hash, event);
+
+        let mut added = vec![];
+        let mut ready = vec![hash];
+        while let Some(hash) = ready.pop() {
+            if !self.orphans[&hash].parents.iter().all(|parent| self.events.contains_key(parent)) {
+                continue
+            }
+
+            let event = self.orphans.remove(&hash).unwrap();
+            self.tree.insert(hash.as_bytes(), serialize(&event))?;
+            for parent in &event.parents {
+                self.heads.remove(parent);
+            }
+            self.heads.insert(hash);
+
+            // Orphans waiting on this event may have all their parents now
+            ready.extend(
+                self.orphans
+                    .iter()
+                    .filter(|(_, orphan)| orphan.parents.contains(&hash))
+                    .map(|(orphan_hash, _)| *orphan_hash),
+            );
+
+            self.events.insert(hash, event.clone());
+            added.push(event);
+        }
+
+        Ok(added)
+    }
+
+    /// Check if the event with the given hash was seen, even as an orphan.
+    pub fn contains(&self, hash: &blake3::Hash) -> bool {
+        self.events.contains_key(hash) || self.orphans.contains_key(hash)
+    }
+
+    /// Fetch the event with the given hash, if it's connected to the graph.
+    pub fn get(&self, hash: &blake3::Hash) -> Option<&Event<T>> {
+        self.events.get(hash)
+    }
+
+    /// Hashes of the events no other event follows yet, in ascending order.
+    pub fn heads(&self) -> Vec<blake3::Hash> {
+        let mut heads: Vec<blake3::Hash> = self.heads.iter().copied().collect();
+        heads.sort_unstable_by_key(|hash| *hash.as_bytes());
+        heads
+    }
+
+    /// Hashes of the parents the orphans are waiting for, to be fetched
+    /// from peers.
+    pub fn missing_parents(&self) -> Vec<blake3::Hash> {
+        let missing: HashSet<blake3::Hash> = self
+            .orphans
+            .values()
+            .flat_map(|orphan| orphan.parents.iter())
+            .filter(|parent| !self.contains(parent))
+            .copied()
+            .collect();
+        missing.into_iter().collect()
+    }
+
+    /// All events of the graph in a total order every node agrees on:
+    /// events come after their parents, and concurrent events are ordered
+    /// by their [`Event::order_key`].
+    pub fn ordered(&self) -> Vec<Event<T>> {
+        let mut children: HashMap<blake3::Hash, Vec<blake3::Hash>> = HashMap::new();
+        let mut pending: HashMap<blake3::Hash, usize> = HashMap::new();
+        let mut ready = BTreeSet::new();
+
+        for (hash, event) in &self.events {
+            pending.insert(*hash, event.parents.len());
+            for parent in &event.parents {
+                children.entry(*parent).or_default().push(*hash);
+            }
+            if event.parents.is_empty() {
+                ready.insert(event.order_key());
+            }
+        }
+
+        let mut ret = Vec::with_capacity(self.events.len());
+        while let Some(next) = ready.iter().next().copied() {
+            ready.remove(&next);
+            let hash = blake3::Hash::from(next.1);
+            for child in children.get(&hash).into_iter().flatten() {
+                let count = pending.get_mut(child).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(self.events[child].order_key());
+                }
+            }
+            ret.push(self.events[&hash].clone());
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_graph() -> Result<()> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let mut graph = EventGraph::<String>::new(&db)?;
+
+        let root = graph.new_event("root".to_string())?;
+        let root_hash = root.hash();
+        assert_eq!(graph.heads(), vec![root_hash]);
+
+        // Two concurrent events, received in reverse causal order
+        let a =
+            Event { parents: vec![root_hash], timestamp: root.timestamp + 1, payload: "a".into() };
+        let b = Event { parents: vec![a.hash()], timestamp: a.timestamp + 1, payload: "b".into() };
+        let c =
+            Event { parents: vec![root_hash], timestamp: root.timestamp + 1, payload: "c".into() };
+
+        assert!(graph.insert(b.clone())?.is_empty());
+        assert_eq!(graph.missing_parents(), vec![a.hash()]);
+        assert_eq!(graph.insert(a.clone())?, vec![a.clone(), b.clone()]);
+        assert!(graph.missing_parents().is_empty());
+        assert_eq!(graph.insert(c.clone())?, vec![c.clone()]);
+        assert!(graph.insert(c.clone())?.is_empty());
+
+        let mut heads = vec![b.hash(), c.hash()];
+        heads.sort_unstable_by_key(|hash| *hash.as_bytes());
+        assert_eq!(graph.heads(), heads);
+
+        // A new event merges both branches
+        let merge = graph.new_event("merge".to_string())?;
+        assert_eq!(merge.parents, heads);
+        assert!(merge.timestamp > b.timestamp);
+
+        let ordered: Vec<String> = graph.ordered().into_iter().map(|e| e.payload).collect();
+        assert_eq!(ordered.first().unwrap(), "root");
+        assert_eq!(ordered.last().unwrap(), "merge");
+        assert!(ordered.iter().position(|p| p == "a") < ordered.iter().position(|p| p == "b"));
+
+        // The graph is restored from the database
+        let restored = EventGraph::<String>::new(&db)?;
+        assert_eq!(restored.heads(), vec![merge.hash()]);
+        assert_eq!(restored.ordered(), graph.ordered());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+//! Eventual consistency sync over a DAG of hash-linked events, as an
+//! alternative to Raft for applications that don't need a single leader.
+//!
+//! Every node appends events to its own copy of the graph, and nodes
+//! exchange the events the others are missing through
+//! [`ProtocolEventGraph`]. Nodes that have seen the same events agree on
+//! their order, given by [`EventGraph::ordered`], so applications can
+//! merge concurrent updates deterministically.
+
+use async_std::sync::{Arc, Mutex};
+
+mod graph;
+pub use graph::{Event, EventGraph, MAX_ORPHANS, MAX_PARENTS};
+
+mod protocol_event;
+pub use protocol_event::{EventHeads, EventPut, EventReq, ProtocolEventGraph};
+
+pub type EventGraphPtr<T> = Arc<Mutex<EventGraph<T>>>;
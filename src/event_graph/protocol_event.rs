@@ -0,0 +1,205 @@
+use std::io;
+
+use async_executor::Executor;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use log::{debug, error};
+
+use crate::{
+    net,
+    util::serial::{Decodable, Encodable, SerialDecodable, SerialEncodable},
+    Result,
+};
+
+use super::{Event, EventGraphPtr};
+
+/// Maximum number of hashes a peer can ask events for at once
+const MAX_REQ_HASHES: usize = 1000;
+
+/// Carries an event of the graph, either newly created or requested.
+#[derive(Debug, Clone)]
+pub struct EventPut<T>(pub Event<T>);
+
+impl<T: Encodable> Encodable for EventPut<T> {
+    fn encode<S: io::Write>(&self, s: S) -> Result<usize> {
+        self.0.encode(s)
+    }
+}
+
+impl<T: Decodable> Decodable for EventPut<T> {
+    fn decode<D: io::Read>(d: D) -> Result<Self> {
+        Ok(Self(Decodable::decode(d)?))
+    }
+}
+
+impl<T: Encodable + Decodable + Send + Sync + 'static> net::Message for EventPut<T> {
+    fn name() -> &'static str {
+        "event_put"
+    }
+}
+
+/// Asks a peer for the events with the given hashes.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct EventReq {
+    pub hashes: Vec<blake3::Hash>,
+}
+
+impl net::Message for EventReq {
+    fn name() -> &'static str {
+        "event_req"
+    }
+}
+
+/// Advertises the heads of a node's graph, so the peer can ask for the
+/// ones it doesn't know.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct EventHeads {
+    pub hashes: Vec<blake3::Hash>,
+}
+
+impl net::Message for EventHeads {
+    fn name() -> &'static str {
+        "event_heads"
+    }
+}
+
+/// Keeps the event graph in sync with a peer. Heads are exchanged when
+/// the channel opens, new events are relayed to the other peers, and the
+/// parents of events that arrive before them are fetched from the peer
+/// that sent them, until the event connects to the graph.
+///
+/// Events that get connected to the graph are sent to `notify`, in causal
+/// order, for the application to apply.
+pub struct ProtocolEventGraph<T> {
+    channel: net::ChannelPtr,
+    jobsman: net::ProtocolJobsManagerPtr,
+    put_sub: net::MessageSubscription<EventPut<T>>,
+    req_sub: net::MessageSubscription<EventReq>,
+    heads_sub: net::MessageSubscription<EventHeads>,
+    graph: EventGraphPtr<T>,
+    p2p: net::P2pPtr,
+    notify: async_channel::Sender<Event<T>>,
+}
+
+impl<T: Encodable + Decodable + Clone + Send + Sync + 'static> ProtocolEventGraph<T> {
+    pub async fn init(
+        channel: net::ChannelPtr,
+        graph: EventGraphPtr<T>,
+        p2p: net::P2pPtr,
+        notify: async_channel::Sender<Event<T>>,
+    ) -> Result<net::ProtocolBasePtr> {
+        let msg_subsystem = channel.get_message_subsystem();
+        msg_subsystem.add_dispatch::<EventPut<T>>().await;
+        msg_subsystem.add_dispatch::<EventReq>().await;
+        msg_subsystem.add_dispatch::<EventHeads>().await;
+
+        let put_sub = channel.subscribe_msg::<EventPut<T>>().await?;
+        let req_sub = channel.subscribe_msg::<EventReq>().await?;
+        let heads_sub = channel.subscribe_msg::<EventHeads>().await?;
+
+        Ok(Arc::new(Self {
+            channel: channel.clone(),
+            jobsman: net::ProtocolJobsManager::new("ProtocolEventGraph", channel),
+            put_sub,
+            req_sub,
+            heads_sub,
+            graph,
+            p2p,
+            notify,
+        }))
+    }
+
+    async fn handle_receive_put(self: Arc<Self>) -> Result<()> {
+        debug!(target: "event_graph", "ProtocolEventGraph::handle_receive_put() [START]");
+        let exclude_list = vec![self.channel.address()];
+        loop {
+            let event = self.put_sub.receive().await?.0.clone();
+
+            let (added, missing) = {
+                let mut graph = self.graph.lock().await;
+                let missing: Vec<blake3::Hash> = event
+                    .parents
+                    .iter()
+                    .filter(|parent| !graph.contains(parent))
+                    .copied()
+                    .collect();
+                match graph.insert(event) {
+                    Ok(added) => (added, missing),
+                    Err(e) => {
+                        error!(target: "event_graph", "Rejected event: {}", e);
+                        continue
+                    }
+                }
+            };
+
+            for event in added {
+                self.notify.send(event.clone()).await?;
+                self.p2p.broadcast_with_exclude(EventPut(event), &exclude_list).await?;
+            }
+
+            // Walk back the missing history through the peer that sent it
+            if !missing.is_empty() {
+                debug!(target: "event_graph", "Requesting {} missing parents", missing.len());
+                self.channel.send(EventReq { hashes: missing }).await?;
+            }
+        }
+    }
+
+    async fn handle_receive_req(self: Arc<Self>) -> Result<()> {
+        debug!(target: "event_graph", "ProtocolEventGraph::handle_receive_req() [START]");
+        loop {
+            let req = self.req_sub.receive().await?;
+            if req.hashes.len() > MAX_REQ_HASHES {
+                error!(target: "event_graph", "Peer requested {} events", req.hashes.len());
+                continue
+            }
+
+            let events: Vec<Event<T>> = {
+                let graph = self.graph.lock().await;
+                req.hashes.iter().filter_map(|hash| graph.get(hash).cloned()).collect()
+            };
+
+            for event in events {
+                self.channel.send(EventPut(event)).await?;
+            }
+        }
+    }
+
+    async fn handle_receive_heads(self: Arc<Self>) -> Result<()> {
+        debug!(target: "event_graph", "ProtocolEventGraph::handle_receive_heads() [START]");
+        loop {
+            let heads = self.heads_sub.receive().await?;
+
+            let unknown: Vec<blake3::Hash> = {
+                let graph = self.graph.lock().await;
+                heads.hashes.iter().filter(|hash| !graph.contains(hash)).copied().collect()
+            };
+
+            if !unknown.is_empty() {
+                self.channel.send(EventReq { hashes: unknown }).await?;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Encodable + Decodable + Clone + Send + Sync + 'static> net::ProtocolBase
+    for ProtocolEventGraph<T>
+{
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!(target: "event_graph", "ProtocolEventGraph::start() [START]");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman.clone().spawn(self.clone().handle_receive_put(), executor.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_req(), executor.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_heads(), executor.clone()).await;
+
+        let heads = self.graph.lock().await.heads();
+        self.channel.send(EventHeads { hashes: heads }).await?;
+        debug!(target: "event_graph", "ProtocolEventGraph::start() [END]");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolEventGraph"
+    }
+}
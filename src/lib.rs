@@ -1,5 +1,7 @@
 pub mod error;
-pub use error::{ClientFailed, ClientResult, Error, Result, VerifyFailed, VerifyResult};
+pub use error::{
+    BuilderFailed, ClientFailed, ClientResult, Error, Result, VerifyFailed, VerifyResult,
+};
 
 #[cfg(feature = "blockchain")]
 pub mod blockchain;
@@ -16,6 +18,12 @@ pub mod zk;
 #[cfg(feature = "dht")]
 pub mod dht;
 
+#[cfg(feature = "event-graph")]
+pub mod event_graph;
+
+#[cfg(feature = "messaging")]
+pub mod messaging;
+
 #[cfg(feature = "net")]
 pub mod net;
 
@@ -28,6 +36,9 @@ pub mod raft;
 #[cfg(feature = "rpc")]
 pub mod rpc;
 
+#[cfg(feature = "swap")]
+pub mod swap;
+
 #[cfg(feature = "system")]
 pub mod system;
 
@@ -0,0 +1,179 @@
+use crypto_box::{
+    aead::{Aead, AeadCore},
+    rand_core::OsRng,
+    SalsaBox, SecretKey,
+};
+use fxhash::FxHashMap;
+
+use crate::{
+    util::serial::{deserialize, serialize, Decodable, Encodable},
+    Error, Result,
+};
+
+use super::EncryptedMessage;
+
+/// Length of the XSalsa20 nonces of [`SalsaBox`]
+const NONCE_LEN: usize = 24;
+
+/// Parse a secret key encoded in base58, as generated with
+/// `bs58::encode(secret.as_bytes())`.
+pub fn secret_from_str(s: &str) -> Result<SecretKey> {
+    let bytes: [u8; 32] = match bs58::decode(s).into_vec()?.try_into() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(Error::ParseFailed("Invalid channel secret")),
+    };
+    Ok(SecretKey::from(bytes))
+}
+
+fn salsa_box(secret: &SecretKey) -> SalsaBox {
+    SalsaBox::new(&secret.public_key(), secret)
+}
+
+/// Keys of a channel
+#[derive(Clone)]
+struct ChannelKeys {
+    /// Messages are encrypted with this one
+    current: SalsaBox,
+    /// Keys the current one replaced, newest first
+    old: Vec<SalsaBox>,
+}
+
+/// Payload decrypted from an [`EncryptedMessage`]
+#[derive(Debug, Clone)]
+pub struct Decrypted<T> {
+    pub payload: T,
+    /// Whether the message was encrypted with the current key of its
+    /// channel. Messages under a replaced key may come from members who
+    /// were removed from the channel.
+    pub current_key: bool,
+}
+
+/// Secret keys of the channels a node is a member of.
+#[derive(Clone, Default)]
+pub struct Keyring {
+    channels: FxHashMap<String, ChannelKeys>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the current key of a channel. The key it replaces is kept to
+    /// decrypt the channel history.
+    pub fn insert(&mut self, channel: &str, secret: &SecretKey) {
+        let current = salsa_box(secret);
+        match self.channels.get_mut(channel) {
+            Some(keys) => {
+                let old = std::mem::replace(&mut keys.current, current);
+                keys.old.insert(0, old);
+            }
+            None => {
+                self.channels.insert(channel.to_string(), ChannelKeys { current, old: vec![] });
+            }
+        }
+    }
+
+    /// Add a key a channel used before its current one, to decrypt its
+    /// history. The channel must have a current key already.
+    pub fn insert_old(&mut self, channel: &str, secret: &SecretKey) -> Result<()> {
+        match self.channels.get_mut(channel) {
+            Some(keys) => {
+                keys.old.push(salsa_box(secret));
+                Ok(())
+            }
+            None => Err(Error::MissingChannelKey(channel.to_string())),
+        }
+    }
+
+    /// Replace the key of a channel with a newly generated one, which is
+    /// returned to be shared with the members staying in the channel.
+    pub fn rotate(&mut self, channel: &str) -> Result<SecretKey> {
+        if !self.contains(channel) {
+            return Err(Error::MissingChannelKey(channel.to_string()))
+        }
+
+        let secret = SecretKey::generate(&mut OsRng);
+        self.insert(channel, &secret);
+        Ok(secret)
+    }
+
+    pub fn contains(&self, channel: &str) -> bool {
+        self.channels.contains_key(channel)
+    }
+
+    /// Names of the channels there are keys for
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.keys().cloned().collect()
+    }
+
+    /// Encrypt a payload with the current key of a channel.
+    pub fn encrypt<T: Encodable>(&self, channel: &str, payload: &T) -> Result<EncryptedMessage> {
+        let keys = match self.channels.get(channel) {
+            Some(keys) => keys,
+            None => return Err(Error::MissingChannelKey(channel.to_string())),
+        };
+
+        let nonce = SalsaBox::generate_nonce(&mut OsRng);
+        let payload = match keys.current.encrypt(&nonce, &serialize(payload)[..]) {
+            Ok(payload) => payload,
+            Err(_) => return Err(Error::MessageEncryptionFailed),
+        };
+
+        Ok(EncryptedMessage { channel: channel.to_string(), nonce: nonce.to_vec(), payload })
+    }
+
+    /// Decrypt a message with the current key of its channel, or one it
+    /// replaced.
+    pub fn decrypt<T: Decodable>(&self, msg: &EncryptedMessage) -> Result<Decrypted<T>> {
+        let keys = match self.channels.get(&msg.channel) {
+            Some(keys) => keys,
+            None => return Err(Error::MissingChannelKey(msg.channel.clone())),
+        };
+
+        // Reject malformed nonces before they reach the cipher
+        if msg.nonce.len() != NONCE_LEN {
+            return Err(Error::MessageDecryptionFailed(msg.channel.clone()))
+        }
+        let nonce = msg.nonce.as_slice().into();
+
+        let boxes = std::iter::once(&keys.current).chain(keys.old.iter());
+        for (i, salsa_box) in boxes.enumerate() {
+            if let Ok(plaintext) = salsa_box.decrypt(nonce, &msg.payload[..]) {
+                let payload = deserialize(&plaintext)?;
+                return Ok(Decrypted { payload, current_key: i == 0 })
+            }
+        }
+
+        Err(Error::MessageDecryptionFailed(msg.channel.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyring_rotation() -> Result<()> {
+        let mut keyring = Keyring::new();
+        keyring.insert("dev", &SecretKey::generate(&mut OsRng));
+        let removed_member = keyring.clone();
+
+        let before = keyring.encrypt("dev", &"hello".to_string())?;
+        keyring.rotate("dev")?;
+        let after = keyring.encrypt("dev", &"bye".to_string())?;
+
+        // History stays readable, new messages are out of reach of the
+        // removed member
+        let old: Decrypted<String> = keyring.decrypt(&before)?;
+        assert_eq!(old.payload, "hello");
+        assert!(!old.current_key);
+        let new: Decrypted<String> = keyring.decrypt(&after)?;
+        assert_eq!(new.payload, "bye");
+        assert!(new.current_key);
+        assert!(removed_member.decrypt::<String>(&after).is_err());
+
+        assert!(keyring.encrypt("ops", &0u8).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{
+    net,
+    util::serial::{serialize, SerialDecodable, SerialEncodable},
+};
+
+/// Application payload encrypted with the key of a channel.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct EncryptedMessage {
+    /// Name of the channel the message was sent to
+    pub channel: String,
+    pub nonce: Vec<u8>,
+    pub payload: Vec<u8>,
+}
+
+impl EncryptedMessage {
+    /// Hash identifying the message. Nonces are random, so two messages
+    /// never share an id even with the same payload.
+    pub fn id(&self) -> blake3::Hash {
+        blake3::hash(&serialize(self))
+    }
+}
+
+impl net::Message for EncryptedMessage {
+    fn name() -> &'static str {
+        "encrypted_msg"
+    }
+}
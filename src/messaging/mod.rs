@@ -0,0 +1,28 @@
+//! Building blocks for applications that broadcast end-to-end encrypted
+//! messages to the members of named channels, like chat rooms or shared
+//! task lists.
+//!
+//! Members of a channel share a secret key, kept in a [`Keyring`] along
+//! with the keys it replaced, so the channel history stays readable after
+//! a key rotation. Applications encrypt their payloads into
+//! [`EncryptedMessage`]s, which [`ProtocolEncryptedMessage`] propagates
+//! through the P2P network. Nodes keep the latest messages in a
+//! [`MessageStore`], which drops the ones already seen and replays the
+//! history to new peers. Nodes without the key of a channel still relay
+//! its messages.
+
+use async_std::sync::Arc;
+
+mod keyring;
+pub use keyring::{secret_from_str, Decrypted, Keyring};
+
+mod message;
+pub use message::EncryptedMessage;
+
+mod store;
+pub use store::MessageStore;
+
+mod protocol_message;
+pub use protocol_message::ProtocolEncryptedMessage;
+
+pub type MessageStorePtr = Arc<MessageStore>;
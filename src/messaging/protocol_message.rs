@@ -0,0 +1,85 @@
+use async_executor::Executor;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{net, Result};
+
+use super::{EncryptedMessage, MessageStorePtr};
+
+/// Number of history messages sent to a new peer
+const HISTORY_SYNC_LEN: usize = 100;
+
+/// Propagates the [`EncryptedMessage`]s of all channels. Messages not seen
+/// before are stored, passed to the application and relayed to the other
+/// peers. New peers get sent the latest messages of the history.
+pub struct ProtocolEncryptedMessage {
+    jobsman: net::ProtocolJobsManagerPtr,
+    msg_sub: net::MessageSubscription<EncryptedMessage>,
+    store: MessageStorePtr,
+    notify_sender: async_channel::Sender<EncryptedMessage>,
+    p2p: net::P2pPtr,
+    channel: net::ChannelPtr,
+}
+
+impl ProtocolEncryptedMessage {
+    pub async fn init(
+        channel: net::ChannelPtr,
+        store: MessageStorePtr,
+        notify_sender: async_channel::Sender<EncryptedMessage>,
+        p2p: net::P2pPtr,
+    ) -> net::ProtocolBasePtr {
+        let message_subsystem = channel.get_message_subsystem();
+        message_subsystem.add_dispatch::<EncryptedMessage>().await;
+
+        let msg_sub = channel
+            .subscribe_msg::<EncryptedMessage>()
+            .await
+            .expect("Missing EncryptedMessage dispatcher!");
+
+        Arc::new(Self {
+            jobsman: net::ProtocolJobsManager::new("ProtocolEncryptedMessage", channel.clone()),
+            msg_sub,
+            store,
+            notify_sender,
+            p2p,
+            channel,
+        })
+    }
+
+    async fn handle_receive_msg(self: Arc<Self>) -> Result<()> {
+        debug!(target: "messaging", "ProtocolEncryptedMessage::handle_receive_msg() [START]");
+        let exclude_list = vec![self.channel.address()];
+
+        for msg in self.store.last(HISTORY_SYNC_LEN)? {
+            self.channel.send(msg).await?;
+        }
+
+        loop {
+            let msg = self.msg_sub.receive().await?;
+            let msg = (*msg).clone();
+
+            if !self.store.insert(&msg)? {
+                continue
+            }
+
+            self.notify_sender.send(msg.clone()).await?;
+            self.p2p.broadcast_with_exclude(msg, &exclude_list).await?;
+        }
+    }
+}
+
+#[async_trait]
+impl net::ProtocolBase for ProtocolEncryptedMessage {
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!(target: "messaging", "ProtocolEncryptedMessage::start() [START]");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman.clone().spawn(self.clone().handle_receive_msg(), executor.clone()).await;
+        debug!(target: "messaging", "ProtocolEncryptedMessage::start() [END]");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolEncryptedMessage"
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::debug;
+
+use crate::{
+    util::serial::{deserialize, serialize},
+    Result,
+};
+
+use super::EncryptedMessage;
+
+const SLED_MESSAGES_TREE: &[u8] = b"_messages";
+const SLED_MESSAGE_ORDER_TREE: &[u8] = b"_message_order";
+
+/// History of the latest messages a node received, still encrypted, kept
+/// in `sled` trees. It also tells apart the messages seen before, so they
+/// are neither delivered nor relayed twice.
+///
+/// Messages past the history size are forgotten, so a peer replaying
+/// messages older than that would get them delivered again. Peers only
+/// replay their own history, which is expected to be of a similar size.
+pub struct MessageStore {
+    db: sled::Db,
+    /// Messages, by id
+    messages: sled::Tree,
+    /// Message ids, by arrival order
+    order: sled::Tree,
+    /// Number of messages kept
+    len: AtomicUsize,
+    max_messages: usize,
+}
+
+impl MessageStore {
+    /// Opens a new or existing `MessageStore` on the given sled database,
+    /// keeping up to `max_messages`.
+    pub fn new(db: &sled::Db, max_messages: usize) -> Result<Self> {
+        let messages = db.open_tree(SLED_MESSAGES_TREE)?;
+        let order = db.open_tree(SLED_MESSAGE_ORDER_TREE)?;
+        let len = AtomicUsize::new(order.len());
+
+        debug!(target: "messaging", "Loaded {} messages", order.len());
+        Ok(Self { db: db.clone(), messages, order, len, max_messages })
+    }
+
+    pub fn contains(&self, msg: &EncryptedMessage) -> Result<bool> {
+        Ok(self.messages.contains_key(msg.id().as_bytes())?)
+    }
+
+    /// Add a message to the history, dropping the oldest one once it's
+    /// full. Returns `false` if the message was seen before.
+    pub fn insert(&self, msg: &EncryptedMessage) -> Result<bool> {
+        let id = msg.id();
+        let swapped = self.messages.compare_and_swap(
+            id.as_bytes(),
+            None as Option<&[u8]>,
+            Some(serialize(msg)),
+        )?;
+        if swapped.is_err() {
+            return Ok(false)
+        }
+
+        let seq = self.db.generate_id()?;
+        self.order.insert(seq.to_be_bytes(), id.as_bytes())?;
+
+        if self.len.fetch_add(1, Ordering::SeqCst) >= self.max_messages {
+            if let Some((_, oldest)) = self.order.pop_min()? {
+                self.messages.remove(oldest)?;
+                self.len.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// The latest `n` messages, oldest first.
+    pub fn last(&self, n: usize) -> Result<Vec<EncryptedMessage>> {
+        let mut ret = vec![];
+        for item in self.order.iter().rev().take(n) {
+            let (_, id) = item?;
+            if let Some(msg) = self.messages.get(id)? {
+                ret.push(deserialize(&msg)?);
+            }
+        }
+        ret.reverse();
+        Ok(ret)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_store_history() -> Result<()> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let store = MessageStore::new(&db, 3)?;
+
+        let msgs: Vec<EncryptedMessage> = (0..5u8)
+            .map(|i| EncryptedMessage { channel: "dev".into(), nonce: vec![i], payload: vec![] })
+            .collect();
+
+        assert!(store.insert(&msgs[0])?);
+        assert!(!store.insert(&msgs[0])?);
+        for msg in &msgs[1..] {
+            assert!(store.insert(msg)?);
+        }
+
+        // Only the latest ones are kept, in order
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.last(10)?, msgs[2..].to_vec());
+        assert_eq!(store.last(1)?, msgs[4..].to_vec());
+        assert!(!store.contains(&msgs[0])?);
+        Ok(())
+    }
+}
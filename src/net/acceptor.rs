@@ -11,8 +11,8 @@ use crate::{
 };
 
 use super::{
-    Channel, ChannelPtr, SessionWeakPtr, TcpTransport, TorTransport, Transport, TransportListener,
-    TransportName,
+    Channel, ChannelPtr, I2pTransport, MemoryTransport, SessionWeakPtr, TcpTransport, TorTransport,
+    Transport, TransportListener, TransportName,
 };
 
 /// Atomic pointer to Acceptor class.
@@ -36,13 +36,17 @@ impl Acceptor {
     }
     /// Start accepting inbound socket connections. Creates a listener to start
     /// listening on a local socket address. Then runs an accept loop in a new
-    /// thread, erroring if a connection problem occurs.
+    /// thread, erroring if a connection problem occurs. If `transport_encryption`
+    /// is set, plaintext TCP listeners are upgraded with TLS.
     pub async fn start(
         self: Arc<Self>,
         accept_url: Url,
+        transport_encryption: bool,
         executor: Arc<Executor<'_>>,
     ) -> Result<()> {
-        let transport_name = TransportName::try_from(accept_url.clone())?;
+        let transport_name =
+            TransportName::try_from(accept_url.clone())?.with_encryption(transport_encryption);
+        let encrypted = transport_name.is_encrypted();
 
         macro_rules! accept {
             ($listener:expr, $transport:expr, $upgrade:expr) => {{
@@ -62,11 +66,11 @@ impl Acceptor {
 
                 match $upgrade {
                     None => {
-                        self.accept(Box::new(listener), executor);
+                        self.accept(Box::new(listener), encrypted, executor);
                     }
                     Some(u) if u == "tls" => {
                         let tls_listener = $transport.upgrade_listener(listener)?.await?;
-                        self.accept(Box::new(tls_listener), executor);
+                        self.accept(Box::new(tls_listener), encrypted, executor);
                     }
                     Some(u) => return Err(Error::UnsupportedTransportUpgrade(u)),
                 }
@@ -115,6 +119,33 @@ impl Acceptor {
 
                 accept!(listener, transport, upgrade);
             }
+            TransportName::I2p(upgrade) => {
+                let transport = I2pTransport::new(I2pTransport::get_env()?);
+                let listener = transport.clone().listen_on(accept_url.clone());
+
+                // Peers can only reach us through our session destination
+                let mut addr = transport.address().await?;
+                if upgrade.is_some() {
+                    addr.set_scheme("i2p+tls").unwrap();
+                }
+                info!("I2P address: {}", addr);
+                let session = self.session.lock().await.clone();
+                if let Some(session) = session.and_then(|s| s.upgrade()) {
+                    session.p2p().add_external_addr(addr).await;
+                }
+
+                accept!(listener, transport, upgrade);
+            }
+            TransportName::Memory => {
+                let listener = match MemoryTransport::new().listen(accept_url.clone()) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!("Bind listener to {} failed: {}", accept_url, err);
+                        return Err(Error::BindFailed(accept_url.as_str().into()))
+                    }
+                };
+                self.accept(Box::new(listener), encrypted, executor);
+            }
             _ => unimplemented!(),
         }
         Ok(())
@@ -133,10 +164,15 @@ impl Acceptor {
 
     /// Run the accept loop in a new thread and error if a connection problem
     /// occurs.
-    fn accept(self: Arc<Self>, listener: Box<dyn TransportListener>, executor: Arc<Executor<'_>>) {
+    fn accept(
+        self: Arc<Self>,
+        listener: Box<dyn TransportListener>,
+        encrypted: bool,
+        executor: Arc<Executor<'_>>,
+    ) {
         let self2 = self.clone();
         self.task.clone().start(
-            self.clone().run_accept_loop(listener),
+            self.clone().run_accept_loop(listener, encrypted),
             |result| self2.handle_stop(result),
             Error::NetworkServiceStopped,
             executor,
@@ -144,12 +180,16 @@ impl Acceptor {
     }
 
     /// Run the accept loop.
-    async fn run_accept_loop(self: Arc<Self>, listener: Box<dyn TransportListener>) -> Result<()> {
+    async fn run_accept_loop(
+        self: Arc<Self>,
+        listener: Box<dyn TransportListener>,
+        encrypted: bool,
+    ) -> Result<()> {
         loop {
             match listener.next().await {
                 Ok((stream, url)) => {
-                    let channel =
-                        Channel::new(stream, url, self.session.lock().await.clone().unwrap()).await;
+                    let session = self.session.lock().await.clone().unwrap();
+                    let channel = Channel::new(stream, url, encrypted, session).await;
                     self.channel_subscriber.notify(Ok(channel)).await;
                 }
                 Err(e) => {
@@ -0,0 +1,228 @@
+use async_std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+
+use fxhash::FxHashMap;
+use log::{debug, error, info, warn};
+use serde_json::json;
+use url::Url;
+
+use crate::{
+    util::{
+        expand_path,
+        file::{load_json_file, save_json_file},
+        time::unix_timestamp,
+    },
+    Result,
+};
+
+/// Atomic pointer to the ban manager.
+pub type BanManagerPtr = Arc<BanManager>;
+
+/// Kinds of protocol violations a peer can commit.
+#[derive(Clone, Debug)]
+pub enum Misbehavior {
+    /// Packet with wrong magic bytes or otherwise unreadable framing
+    MalformedPacket,
+    /// Payload that could not be decoded into its message type
+    InvalidMessage,
+    /// VarInt that was not encoded in its shortest form
+    NonMinimalVarInt,
+    /// Message no protocol is registered for
+    UnknownMessage,
+    /// Sending more messages than the protocol allows
+    Spam,
+    /// Application-defined violation with a custom score
+    Custom(u32),
+}
+
+impl Misbehavior {
+    /// Score added to the peer for this violation.
+    pub fn score(&self) -> u32 {
+        match self {
+            Self::MalformedPacket => 100,
+            Self::InvalidMessage => 20,
+            Self::NonMinimalVarInt => 50,
+            Self::UnknownMessage => 10,
+            Self::Spam => 10,
+            Self::Custom(score) => *score,
+        }
+    }
+}
+
+/// Tracks misbehavior scores per peer host and temporarily bans hosts whose
+/// score reaches the configured threshold. Bans are keyed by host so that a
+/// peer can't escape by reconnecting from another port.
+pub struct BanManager {
+    /// Accumulated misbehavior score per host
+    scores: Mutex<FxHashMap<String, u32>>,
+    /// Banned hosts and the UNIX timestamp their ban expires at
+    bans: Mutex<FxHashMap<String, u64>>,
+    /// Score at which a host gets banned
+    threshold: u32,
+    /// Duration of a ban in seconds
+    ban_seconds: u64,
+    /// Path the banlist is persisted to, if any
+    path: Option<PathBuf>,
+}
+
+impl BanManager {
+    /// Create a new ban manager. If `path` is given, previously persisted bans
+    /// are loaded from it and any change to the banlist is written back.
+    /// A banlist that fails to load is logged and ignored.
+    pub fn new(threshold: u32, ban_seconds: u64, path: Option<String>) -> Arc<Self> {
+        let path = path.and_then(|p| match expand_path(&p) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                error!(target: "net", "Invalid banlist path {}: {}", p, e);
+                None
+            }
+        });
+
+        let mut bans = FxHashMap::default();
+        if let Some(p) = &path {
+            if p.exists() {
+                match Self::load(p) {
+                    Ok(loaded) => {
+                        bans = loaded;
+                        info!(target: "net", "Loaded {} bans from {:?}", bans.len(), p);
+                    }
+                    Err(e) => error!(target: "net", "Failed loading banlist from {:?}: {}", p, e),
+                }
+            }
+        }
+
+        Arc::new(Self {
+            scores: Mutex::new(FxHashMap::default()),
+            bans: Mutex::new(bans),
+            threshold,
+            ban_seconds,
+            path,
+        })
+    }
+
+    /// Load persisted bans, dropping the ones that already expired.
+    fn load(path: &Path) -> Result<FxHashMap<String, u64>> {
+        let loaded: FxHashMap<String, u64> = load_json_file(path)?;
+        let now = unix_timestamp()?;
+        Ok(loaded.into_iter().filter(|(_, until)| *until > now).collect())
+    }
+
+    fn host_key(addr: &Url) -> String {
+        match addr.host_str() {
+            Some(host) => host.to_string(),
+            None => addr.to_string(),
+        }
+    }
+
+    /// Record a protocol violation for the given peer. Returns `true` if the
+    /// peer got banned as a result.
+    pub async fn misbehaving(&self, addr: &Url, misbehavior: Misbehavior) -> bool {
+        let key = Self::host_key(addr);
+
+        let score = {
+            let mut scores = self.scores.lock().await;
+            let score = scores.entry(key.clone()).or_insert(0);
+            *score = score.saturating_add(misbehavior.score());
+            *score
+        };
+
+        warn!(
+            target: "net",
+            "Peer {} misbehaving ({:?}), score is now {}/{}",
+            addr, misbehavior, score, self.threshold
+        );
+
+        if score < self.threshold {
+            return false
+        }
+
+        self.scores.lock().await.remove(&key);
+        self.ban(addr, self.ban_seconds).await;
+        true
+    }
+
+    /// Current misbehavior score of the host of the given address.
+    pub async fn score(&self, addr: &Url) -> u32 {
+        *self.scores.lock().await.get(&Self::host_key(addr)).unwrap_or(&0)
+    }
+
+    /// Ban the host of the given address for `seconds`.
+    pub async fn ban(&self, addr: &Url, seconds: u64) {
+        let key = Self::host_key(addr);
+        let until = unix_timestamp().unwrap_or(0) + seconds;
+        info!(target: "net", "Banning {} for {} seconds", key, seconds);
+        self.bans.lock().await.insert(key, until);
+        self.save().await;
+    }
+
+    /// Lift the ban on the host of the given address.
+    pub async fn unban(&self, addr: &Url) {
+        let key = Self::host_key(addr);
+        if self.bans.lock().await.remove(&key).is_some() {
+            info!(target: "net", "Unbanned {}", key);
+            self.save().await;
+        }
+    }
+
+    /// Check whether the host of the given address is currently banned.
+    /// Expired bans are cleared on lookup.
+    pub async fn is_banned(&self, addr: &Url) -> bool {
+        let key = Self::host_key(addr);
+        let now = unix_timestamp().unwrap_or(0);
+
+        let mut bans = self.bans.lock().await;
+        match bans.get(&key) {
+            Some(until) if *until > now => true,
+            Some(_) => {
+                debug!(target: "net", "Ban on {} expired", key);
+                bans.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Write the banlist to disk, if persistence is configured.
+    async fn save(&self) {
+        if let Some(path) = &self.path {
+            let bans = self.bans.lock().await.clone();
+            if let Err(e) = save_json_file(path, &bans) {
+                error!(target: "net", "Failed saving banlist to {:?}: {}", path, e);
+            }
+        }
+    }
+
+    pub async fn get_info(&self) -> serde_json::Value {
+        json!({
+            "threshold": self.threshold,
+            "ban_seconds": self.ban_seconds,
+            "scores": self.scores.lock().await.clone(),
+            "banned": self.bans.lock().await.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_threshold() {
+        smol::block_on(async {
+            let manager = BanManager::new(100, 60, None);
+            let peer = Url::parse("tcp://127.0.0.1:12345").unwrap();
+            let same_host = Url::parse("tcp://127.0.0.1:54321").unwrap();
+
+            assert!(!manager.misbehaving(&peer, Misbehavior::NonMinimalVarInt).await);
+            assert!(!manager.is_banned(&peer).await);
+            assert!(manager.misbehaving(&same_host, Misbehavior::NonMinimalVarInt).await);
+            assert!(manager.is_banned(&peer).await);
+
+            manager.unban(&peer).await;
+            assert!(!manager.is_banned(&same_host).await);
+
+            manager.ban(&peer, 0).await;
+            assert!(!manager.is_banned(&peer).await);
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use async_std::sync::{Arc, Mutex};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+use smol::Timer;
+
+/// Atomic pointer to a rate limiter.
+pub type RateLimiterPtr = Arc<RateLimiter>;
+
+/// Token bucket limiting the number of bytes per second going through it.
+/// The bucket holds at most one second worth of bytes. Writes larger than
+/// the available allowance are let through and put the bucket into debt,
+/// which the caller pays off by sleeping.
+pub struct RateLimiter {
+    /// Allowed bytes per second, 0 means unlimited
+    rate: u64,
+    /// Current allowance and the last time it was refilled
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter allowing `rate` bytes per second.
+    /// A rate of 0 disables limiting.
+    pub fn new(rate: u64) -> Arc<Self> {
+        Arc::new(Self { rate, bucket: Mutex::new((rate as f64, Instant::now())) })
+    }
+
+    /// Returns `true` if this limiter does not limit anything.
+    pub fn is_unlimited(&self) -> bool {
+        self.rate == 0
+    }
+
+    /// Take `bytes` from the bucket, sleeping until they are available.
+    pub async fn consume(&self, bytes: usize) {
+        if self.is_unlimited() {
+            return
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.1).as_secs_f64();
+            let rate = self.rate as f64;
+
+            bucket.0 = (bucket.0 + elapsed * rate).min(rate) - bytes as f64;
+            bucket.1 = now;
+
+            if bucket.0 < 0.0 {
+                Some(Duration::from_secs_f64(-bucket.0 / rate))
+            } else {
+                None
+            }
+        };
+
+        if let Some(wait) = wait {
+            Timer::after(wait).await;
+        }
+    }
+}
+
+/// Sent and received byte counters.
+#[derive(Default)]
+pub struct ByteCounter {
+    sent: AtomicU64,
+    recv: AtomicU64,
+}
+
+impl ByteCounter {
+    pub fn add_sent(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_recv(&self, bytes: usize) {
+        self.recv.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn recv(&self) -> u64 {
+        self.recv.load(Ordering::Relaxed)
+    }
+
+    pub fn get_info(&self) -> serde_json::Value {
+        json!({
+            "bytes_sent": self.sent(),
+            "bytes_recv": self.recv(),
+        })
+    }
+}
@@ -1,10 +1,16 @@
 use async_std::sync::{Arc, Mutex};
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
 
 use futures::{
     io::{ReadHalf, WriteHalf},
-    AsyncReadExt,
+    AsyncReadExt, AsyncWriteExt,
 };
-use log::{debug, error, info};
+use fxhash::FxHashMap;
+use log::{debug, error, info, warn};
 use rand::Rng;
 use serde_json::json;
 use smol::Executor;
@@ -12,14 +18,22 @@ use url::Url;
 
 use crate::{
     system::{StoppableTask, StoppableTaskPtr, Subscriber, SubscriberPtr, Subscription},
-    util::NanoTimestamp,
+    util::{
+        serial::{decode_limited, DecodeLimits},
+        NanoTimestamp,
+    },
     Error, Result,
 };
 
 use super::{
-    message,
+    ban_manager::Misbehavior,
+    bandwidth::{ByteCounter, RateLimiter, RateLimiterPtr},
+    dnet::{self, MessageCounter},
+    latency::LatencyStats,
+    message::{self, Message},
     message_subscriber::{MessageSubscription, MessageSubsystem},
-    Session, SessionBitflag, SessionWeakPtr, TransportStream,
+    send_queue::{QueuedPacket, SendQueue},
+    Session, SessionBitflag, SessionWeakPtr, Settings, TransportStream, UnknownMessagePolicy,
 };
 
 /// Atomic pointer to async channel.
@@ -30,8 +44,14 @@ struct ChannelInfo {
     remote_node_id: String,
     last_msg: String,
     last_status: String,
+    latency: LatencyStats,
     // Message log which is cleared on querying get_info
     log: Mutex<Vec<(NanoTimestamp, String, String)>>,
+    // Message counters by command, kept for the channel lifetime
+    messages: BTreeMap<String, MessageCounter>,
+    // Received messages without a dispatcher. Not counted by command, so
+    // peers can't grow the counters with made up ones.
+    unknown_messages: u64,
 }
 
 impl ChannelInfo {
@@ -41,7 +61,10 @@ impl ChannelInfo {
             remote_node_id: String::new(),
             last_msg: String::new(),
             last_status: String::new(),
+            latency: LatencyStats::new(),
             log: Mutex::new(Vec::new()),
+            messages: BTreeMap::new(),
+            unknown_messages: 0,
         }
     }
 
@@ -51,7 +74,9 @@ impl ChannelInfo {
             "remote_node_id": self.remote_node_id,
             "last_msg": self.last_msg,
             "last_status": self.last_status,
+            "latency": self.latency.get_info(),
             "log": self.log.lock().await.clone(),
+            "unknown_messages": self.unknown_messages,
         });
         self.log.lock().await.clear();
         result
@@ -63,21 +88,34 @@ pub struct Channel {
     reader: Mutex<ReadHalf<Box<dyn TransportStream>>>,
     writer: Mutex<WriteHalf<Box<dyn TransportStream>>>,
     address: Url,
+    encrypted: bool,
     message_subsystem: MessageSubsystem,
     stop_subscriber: SubscriberPtr<Error>,
     receive_task: StoppableTaskPtr,
+    send_task: StoppableTaskPtr,
+    send_queue: SendQueue,
     stopped: Mutex<bool>,
     info: Mutex<ChannelInfo>,
+    traffic: ByteCounter,
+    upload_limiter: RateLimiterPtr,
+    created: Instant,
+    compression: AtomicBool,
+    compression_threshold: usize,
+    /// Highest message format versions the remote peer announced
+    remote_versions: Mutex<FxHashMap<String, u8>>,
+    unknown_message_policy: UnknownMessagePolicy,
     session: SessionWeakPtr,
 }
 
 impl Channel {
     /// Sets up a new channel. Creates a reader and writer TCP stream and
     /// summons the message subscriber subsystem. Performs a network
-    /// handshake on the subsystem dispatchers.
+    /// handshake on the subsystem dispatchers. `encrypted` marks whether the
+    /// underlying stream is TLS-wrapped.
     pub async fn new(
         stream: Box<dyn TransportStream>,
         address: Url,
+        encrypted: bool,
         session: SessionWeakPtr,
     ) -> Arc<Self> {
         let (reader, writer) = stream.split();
@@ -87,25 +125,72 @@ impl Channel {
         let message_subsystem = MessageSubsystem::new();
         Self::setup_dispatchers(&message_subsystem).await;
 
+        let settings = match session.upgrade() {
+            Some(session) => session.p2p().settings(),
+            None => Arc::new(Settings::default()),
+        };
+        let send_queue = SendQueue::new(
+            settings.channel_queue_size,
+            Duration::from_secs(settings.channel_send_timeout_seconds.into()),
+        );
+
         Arc::new(Self {
             reader,
             writer,
             address,
+            encrypted,
             message_subsystem,
             stop_subscriber: Subscriber::new(),
             receive_task: StoppableTask::new(),
+            send_task: StoppableTask::new(),
+            send_queue,
             stopped: Mutex::new(false),
             info: Mutex::new(ChannelInfo::new()),
+            traffic: ByteCounter::default(),
+            upload_limiter: RateLimiter::new(settings.peer_upload_limit),
+            created: Instant::now(),
+            compression: AtomicBool::new(false),
+            compression_threshold: settings.compression_threshold as usize,
+            remote_versions: Mutex::new(FxHashMap::default()),
+            unknown_message_policy: settings.unknown_message_policy,
             session,
         })
     }
 
     pub async fn get_info(&self) -> serde_json::Value {
-        self.info.lock().await.get_info().await
+        let mut info = self.info.lock().await.get_info().await;
+        info["bytes_sent"] = json!(self.traffic.sent());
+        info["bytes_recv"] = json!(self.traffic.recv());
+        info["send_queue"] = self.send_queue.get_info();
+        info
+    }
+
+    /// Return the typed introspection info of this channel.
+    pub async fn dnet_info(&self) -> dnet::ChannelInfo {
+        let info = self.info.lock().await;
+        dnet::ChannelInfo {
+            addr: self.address.to_string(),
+            session: dnet::session_names(self.session_type_id()).join(","),
+            random_id: info.random_id,
+            remote_node_id: info.remote_node_id.clone(),
+            uptime: self.uptime().as_secs(),
+            last_msg: info.last_msg.clone(),
+            last_status: info.last_status.clone(),
+            bytes_sent: self.traffic.sent(),
+            bytes_recv: self.traffic.recv(),
+            latency: info.latency.average(),
+            messages: info.messages.clone(),
+            unknown_messages: info.unknown_messages,
+        }
+    }
+
+    /// Return the byte counters of this channel.
+    pub fn traffic(&self) -> &ByteCounter {
+        &self.traffic
     }
 
     /// Starts the channel. Runs a receive loop to start receiving messages or
-    /// handles a network failure.
+    /// handles a network failure, and a send loop writing out the send queue.
     pub fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) {
         debug!(target: "net", "Channel::start() [START, address={}]", self.address());
         let self2 = self.clone();
@@ -113,6 +198,13 @@ impl Channel {
             self.clone().main_receive_loop(),
             |result| self2.handle_stop(result),
             Error::NetworkServiceStopped,
+            executor.clone(),
+        );
+        self.send_task.clone().start(
+            self.clone().main_send_loop(),
+            // Errors are reported through the receive task
+            |_| async {},
+            Error::NetworkServiceStopped,
             executor,
         );
         debug!(target: "net", "Channel::start() [END, address={}]", self.address());
@@ -127,12 +219,29 @@ impl Channel {
             *self.stopped.lock().await = true;
 
             self.stop_subscriber.notify(Error::ChannelStopped).await;
+            self.send_queue.close();
             self.receive_task.stop().await;
+            self.send_task.stop().await;
             self.message_subsystem.trigger_error(Error::ChannelStopped).await;
             debug!(target: "net", "Channel::stop() [END, address={}]", self.address());
         }
     }
 
+    /// Tells the remote peer we are leaving, waits for the send queue to
+    /// reach the disconnect message, flushes whatever is still buffered in
+    /// the stream and stops the channel.
+    pub async fn disconnect(&self, reason: &str) {
+        debug!(target: "net", "Channel::disconnect() [address={}, reason={}]", self.address(), reason);
+        let msg = message::DisconnectMessage { reason: reason.to_string() };
+        let (done_send, done_recv) = async_channel::bounded(1);
+        if self.send_message(msg, Some(done_send)).await.is_ok() && done_recv.recv().await.is_ok() {
+            if let Err(e) = self.writer.lock().await.flush().await {
+                debug!(target: "net", "Failed flushing channel {}: {}", self.address(), e);
+            }
+        }
+        self.stop().await;
+    }
+
     /// Creates a subscription to a stopped signal.
     /// If the channel is stopped then this will return a ChannelStopped error.
     pub async fn subscribe_stop(&self) -> Result<Subscription<Error>> {
@@ -158,8 +267,9 @@ impl Channel {
     }
 
     /// Sends a message across a channel. Calls function 'send_message' that
-    /// creates a new payload and sends it over the TCP connection as a
-    /// packet. Returns an error if something goes wrong.
+    /// creates a new payload and queues it to be sent over the connection as
+    /// a packet. Returns an error if something goes wrong, or if the peer is
+    /// too slow to make room in its send queue in time.
     pub async fn send<M: message::Message>(&self, message: M) -> Result<()> {
         debug!(target: "net",
          "Channel::send() [START, command={:?}, address={}]",
@@ -175,7 +285,7 @@ impl Channel {
         }
 
         // Catch failure and stop channel, return a net error
-        let result = match self.send_message(message).await {
+        let result = match self.send_message(message, None).await {
             Ok(()) => Ok(()),
             Err(err) => {
                 error!("Channel send error for [{}]: {}", self.address(), err);
@@ -200,22 +310,68 @@ impl Channel {
 
     /// Implements send message functionality. Creates a new payload and encodes
     /// it. Then creates a message packet- the base type of the network- and
-    /// copies the payload into it. Then we send the packet over the TCP
-    /// stream.
-    async fn send_message<M: message::Message>(&self, message: M) -> Result<()> {
+    /// copies the payload into it. Then we queue the packet according to the
+    /// message priority. `done` is notified once the packet is written.
+    async fn send_message<M: message::Message>(
+        &self,
+        message: M,
+        done: Option<async_channel::Sender<()>>,
+    ) -> Result<()> {
+        let version = self.message_version::<M>().await;
         let mut payload = Vec::new();
-        message.encode(&mut payload)?;
-        let packet = message::Packet { command: String::from(M::name()), payload };
+        message.encode_versioned(version, &mut payload)?;
+        let mut packet = message::Packet::new(M::name(), payload);
+        packet.version = version;
+        if self.compression.load(Ordering::Relaxed) {
+            packet.compress(self.compression_threshold);
+        }
         let time = NanoTimestamp::current_time();
         //let time = time::unix_timestamp()?;
 
         {
             let info = &mut *self.info.lock().await;
             info.log.lock().await.push((time, "send".to_string(), packet.command.clone()));
+            info.messages.entry(packet.command.clone()).or_default().sent += 1;
+        }
+
+        self.send_queue.push(QueuedPacket { packet, done }, M::priority()).await
+    }
+
+    /// Run the send loop, writing out queued packets highest priority first.
+    async fn main_send_loop(self: Arc<Self>) -> Result<()> {
+        loop {
+            let item = self.send_queue.pop().await?;
+
+            if let Err(err) = self.write_packet(item.packet).await {
+                error!("Channel send error for [{}]: {}", self.address(), err);
+                self.stop().await;
+                return Err(Error::ChannelStopped)
+            }
+
+            if let Some(done) = item.done {
+                let _ = done.send(()).await;
+            }
+        }
+    }
+
+    /// Write a packet to the stream, respecting the upload limits.
+    async fn write_packet(&self, packet: message::Packet) -> Result<()> {
+        // Wait for the per-peer and the global upload budget
+        let size = packet.size();
+        self.upload_limiter.consume(size).await;
+        if let Some(session) = self.session.upgrade() {
+            session.p2p().upload_limiter().consume(size).await;
         }
 
         let stream = &mut *self.writer.lock().await;
-        message::send_packet(stream, packet).await
+        message::send_packet(stream, packet).await?;
+
+        self.traffic.add_sent(size);
+        if let Some(session) = self.session.upgrade() {
+            session.p2p().traffic().add_sent(size);
+        }
+
+        Ok(())
     }
 
     /// Subscribe to a messages on the message subsystem.
@@ -239,6 +395,46 @@ impl Channel {
         self.address.clone()
     }
 
+    /// Return how long ago this channel was created.
+    pub fn uptime(&self) -> Duration {
+        self.created.elapsed()
+    }
+
+    /// Enable or disable compression of outgoing payloads. Set by the version
+    /// handshake once both sides announced their support.
+    pub fn set_compression(&self, enabled: bool) {
+        self.compression.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Record the message format versions the remote peer announced in the
+    /// version handshake.
+    pub async fn set_remote_versions(&self, versions: Vec<(String, u8)>) {
+        *self.remote_versions.lock().await = versions.into_iter().collect();
+    }
+
+    /// Highest version of the format of message `M` both peers speak.
+    pub async fn message_version<M: Message>(&self) -> u8 {
+        match self.remote_versions.lock().await.get(M::name()) {
+            Some(version) => M::version().min(*version),
+            None => 0,
+        }
+    }
+
+    /// Returns `true` if the underlying transport stream is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Record a ping round-trip time for this channel.
+    pub async fn record_latency(&self, rtt: Duration) {
+        self.info.lock().await.latency.record(rtt);
+    }
+
+    /// Return the ping round-trip time statistics of this channel.
+    pub async fn latency(&self) -> LatencyStats {
+        self.info.lock().await.latency.clone()
+    }
+
     pub async fn remote_node_id(&self) -> String {
         self.info.lock().await.remote_node_id.clone()
     }
@@ -280,13 +476,22 @@ impl Channel {
         let reader = &mut *self.reader.lock().await;
 
         loop {
-            let packet = match message::read_packet(reader).await {
+            let mut packet = match message::read_packet(reader).await {
                 Ok(packet) => packet,
                 Err(err) => {
                     if Self::is_eof_error(err.clone()) {
                         info!("Inbound connection {} disconnected", self.address());
                     } else {
                         error!("Read error on channel: {}", err);
+                        match err {
+                            Error::MalformedPacket => {
+                                self.misbehaving(Misbehavior::MalformedPacket).await;
+                            }
+                            Error::NonMinimalVarInt => {
+                                self.misbehaving(Misbehavior::NonMinimalVarInt).await;
+                            }
+                            _ => {}
+                        }
                     }
                     debug!(target: "net",
                      "Channel::receive_loop() stopping channel {:?}",
@@ -296,6 +501,32 @@ impl Channel {
                     return Err(Error::ChannelStopped)
                 }
             };
+            self.traffic.add_recv(packet.size());
+            if let Some(session) = self.session.upgrade() {
+                session.p2p().traffic().add_recv(packet.size());
+            }
+
+            if let Err(err) = packet.decompress() {
+                error!("Failed decompressing packet from {}: {}", self.address(), err);
+                self.misbehaving(Misbehavior::MalformedPacket).await;
+                self.stop().await;
+                return Err(Error::ChannelStopped)
+            }
+
+            // The remote peer is leaving, no need to wait for the socket to close
+            if packet.command == message::DisconnectMessage::name() {
+                let reason = match decode_limited::<message::DisconnectMessage, _>(
+                    &packet.payload[..],
+                    DecodeLimits::default(),
+                ) {
+                    Ok(msg) => msg.reason,
+                    Err(_) => "unknown".to_string(),
+                };
+                info!("Peer {} disconnected: {}", self.address(), reason);
+                self.stop().await;
+                return Err(Error::ChannelStopped)
+            }
+
             {
                 let info = &mut *self.info.lock().await;
                 info.last_msg = packet.command.clone();
@@ -306,7 +537,55 @@ impl Channel {
             }
 
             // Send result to our subscribers
-            self.message_subsystem.notify(&packet.command, packet.payload).await;
+            let notified = self
+                .message_subsystem
+                .notify(&packet.command, packet.version, packet.payload)
+                .await;
+
+            {
+                let info = &mut *self.info.lock().await;
+                match notified {
+                    Err(Error::UnknownMessage(_)) => info.unknown_messages += 1,
+                    _ => {
+                        let counter = info.messages.entry(packet.command.clone()).or_default();
+                        counter.recv += 1;
+                        counter.invalid += notified.is_err() as u64;
+                    }
+                }
+            }
+
+            let misbehavior = match notified {
+                Ok(()) => continue,
+                Err(Error::UnknownMessage(command)) => match self.unknown_message_policy {
+                    UnknownMessagePolicy::Ignore => continue,
+                    UnknownMessagePolicy::Log => {
+                        warn!("Peer {} sent unknown message {:?}", self.address(), command);
+                        continue
+                    }
+                    UnknownMessagePolicy::Misbehave => {
+                        warn!("Peer {} sent unknown message {:?}", self.address(), command);
+                        Misbehavior::UnknownMessage
+                    }
+                },
+                Err(Error::NonMinimalVarInt) => Misbehavior::NonMinimalVarInt,
+                Err(_) => Misbehavior::InvalidMessage,
+            };
+
+            if self.misbehaving(misbehavior).await {
+                self.stop().await;
+                return Err(Error::ChannelStopped)
+            }
+        }
+    }
+
+    /// Report a protocol violation by the remote peer to the ban manager.
+    /// Returns `true` if the peer got banned.
+    pub async fn misbehaving(&self, misbehavior: Misbehavior) -> bool {
+        match self.session.upgrade() {
+            Some(session) => {
+                session.p2p().ban_manager().misbehaving(&self.address, misbehavior).await
+            }
+            None => false,
         }
     }
 
@@ -1,4 +1,4 @@
-use async_std::sync::Arc;
+use async_std::sync::{Arc, Mutex};
 use std::{env, time::Duration};
 
 use log::error;
@@ -7,25 +7,28 @@ use url::Url;
 use crate::{Error, Result};
 
 use super::{
-    Channel, ChannelPtr, SessionWeakPtr, SettingsPtr, TcpTransport, TorTransport, Transport,
-    TransportName,
+    Channel, ChannelPtr, I2pTransport, MemoryTransport, SessionWeakPtr, SettingsPtr, TcpTransport,
+    TorTransport, Transport, TransportName,
 };
 
 /// Create outbound socket connections.
 pub struct Connector {
     settings: SettingsPtr,
     pub session: SessionWeakPtr,
+    /// I2P transport, kept around so its session is reused across dials
+    i2p: Mutex<Option<I2pTransport>>,
 }
 
 impl Connector {
     /// Create a new connector with default network settings.
     pub fn new(settings: SettingsPtr, session: SessionWeakPtr) -> Self {
-        Self { settings, session }
+        Self { settings, session, i2p: Mutex::new(None) }
     }
 
     /// Establish an outbound connection.
     pub async fn connect(&self, connect_url: Url) -> Result<ChannelPtr> {
-        let transport_name = TransportName::try_from(connect_url.clone())?;
+        let transport_name = TransportName::try_from(connect_url.clone())?
+            .with_encryption(self.settings.transport_encryption);
         self.connect_channel(
             connect_url,
             transport_name,
@@ -57,12 +60,18 @@ impl Connector {
                 let channel = match $upgrade {
                     // session
                     None => {
-                        Channel::new(Box::new(stream?), connect_url.clone(), self.session.clone())
-                            .await
+                        Channel::new(
+                            Box::new(stream?),
+                            connect_url.clone(),
+                            false,
+                            self.session.clone(),
+                        )
+                        .await
                     }
                     Some(u) if u == "tls" => {
                         let stream = $transport.upgrade_dialer(stream?)?.await;
-                        Channel::new(Box::new(stream?), connect_url, self.session.clone()).await
+                        Channel::new(Box::new(stream?), connect_url, true, self.session.clone())
+                            .await
                     }
                     Some(u) => return Err(Error::UnsupportedTransportUpgrade(u)),
                 };
@@ -89,6 +98,29 @@ impl Connector {
 
                 connect!(stream, transport, upgrade)
             }
+            TransportName::I2p(upgrade) => {
+                let transport = {
+                    let mut i2p = self.i2p.lock().await;
+                    if i2p.is_none() {
+                        *i2p = Some(I2pTransport::new(I2pTransport::get_env()?));
+                    }
+                    i2p.clone().unwrap()
+                };
+
+                let stream = transport.clone().dial(connect_url.clone(), None);
+
+                connect!(stream, transport, upgrade)
+            }
+            TransportName::Memory => {
+                let stream = match MemoryTransport::new().dial(connect_url.clone()) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!("Connection to {}  failed: {}", connect_url, err);
+                        return Err(Error::ConnectFailed)
+                    }
+                };
+                Ok(Channel::new(Box::new(stream), connect_url, false, self.session.clone()).await)
+            }
             _ => unimplemented!(),
         }
     }
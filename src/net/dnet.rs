@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{SessionBitflag, SESSION_INBOUND, SESSION_MANUAL, SESSION_OUTBOUND, SESSION_SEED};
+
+/// Number of messages of a given command sent and received on a channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageCounter {
+    pub sent: u64,
+    pub recv: u64,
+    /// Received messages that could not be decoded
+    #[serde(default)]
+    pub invalid: u64,
+}
+
+/// State of a connected channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    /// Address of the remote peer
+    pub addr: String,
+    /// Session the channel belongs to
+    pub session: String,
+    pub random_id: u32,
+    pub remote_node_id: String,
+    /// Seconds since the channel was created
+    pub uptime: u64,
+    /// Command of the last message
+    pub last_msg: String,
+    /// Direction of the last message, `sent` or `recv`
+    pub last_status: String,
+    pub bytes_sent: u64,
+    pub bytes_recv: u64,
+    /// Average ping round-trip time, in milliseconds
+    pub latency: Option<u64>,
+    /// Message counters, by command
+    pub messages: BTreeMap<String, MessageCounter>,
+    /// Received messages no protocol is registered for
+    #[serde(default)]
+    pub unknown_messages: u64,
+}
+
+/// Channels accepted on an inbound address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboundInfo {
+    pub accept_addr: String,
+    /// Addresses of the connected channels
+    pub channels: Vec<String>,
+}
+
+/// State of an outbound connection slot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboundSlotInfo {
+    /// Address the slot is connecting or connected to
+    pub addr: Option<String>,
+    /// `open`, `pending` or `connected`
+    pub state: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionsInfo {
+    pub inbound: Vec<InboundInfo>,
+    pub outbound: Vec<OutboundSlotInfo>,
+}
+
+/// Protocol registered in the protocol registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolInfo {
+    /// Name of the protocol, known once it got attached to a channel
+    pub name: Option<String>,
+    /// Sessions the protocol is attached to
+    pub sessions: Vec<String>,
+}
+
+/// Addresses known to the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostsInfo {
+    /// Addresses of the channels awaiting connection
+    pub pending: Vec<String>,
+    /// Addresses in the hosts store
+    pub known: Vec<String>,
+}
+
+/// Introspection of the whole p2p network of a node, served by the
+/// `dnet` RPC namespace so monitoring tools see the same structure
+/// whatever daemon they query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    /// State of the p2p network: `open`, `start`, `started`, `run` or `stop`
+    pub state: String,
+    pub external_addr: Vec<String>,
+    pub channels: Vec<ChannelInfo>,
+    pub sessions: SessionsInfo,
+    pub hosts: HostsInfo,
+    pub protocols: Vec<ProtocolInfo>,
+}
+
+/// Names of the sessions included in provided session bitflag.
+pub fn session_names(flags: SessionBitflag) -> Vec<String> {
+    [
+        (SESSION_INBOUND, "inbound"),
+        (SESSION_OUTBOUND, "outbound"),
+        (SESSION_MANUAL, "manual"),
+        (SESSION_SEED, "seed"),
+    ]
+    .iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, name)| name.to_string())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::SESSION_ALL;
+
+    #[test]
+    fn test_session_names() {
+        assert_eq!(session_names(SESSION_ALL), vec!["inbound", "outbound", "manual", "seed"]);
+        assert_eq!(
+            session_names(!SESSION_SEED & SESSION_ALL),
+            vec!["inbound", "outbound", "manual"]
+        );
+        assert_eq!(session_names(SESSION_INBOUND), vec!["inbound"]);
+        assert!(session_names(0).is_empty());
+    }
+}
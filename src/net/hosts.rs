@@ -1,8 +1,14 @@
 use async_std::sync::{Arc, Mutex};
+use std::path::Path;
 
 use fxhash::FxHashSet;
 use url::Url;
 
+use crate::{
+    util::file::{load_json_file, save_json_file},
+    Result,
+};
+
 /// Pointer to hosts class.
 pub type HostsPtr = Arc<Hosts>;
 
@@ -39,4 +45,16 @@ impl Hosts {
     pub async fn is_empty(&self) -> bool {
         self.addrs.lock().await.is_empty()
     }
+
+    /// Add the hosts persisted in the given file to the host list.
+    pub async fn load_file(&self, path: &Path) -> Result<()> {
+        let addrs: Vec<Url> = load_json_file(path)?;
+        self.store(addrs).await;
+        Ok(())
+    }
+
+    /// Persist the host list to the given file.
+    pub async fn save_file(&self, path: &Path) -> Result<()> {
+        save_json_file(path, &self.load_all().await)
+    }
 }
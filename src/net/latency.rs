@@ -0,0 +1,95 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde_json::json;
+
+/// Number of round-trip samples kept per channel.
+const MAX_SAMPLES: usize = 64;
+
+/// Rolling window of ping round-trip times, in milliseconds.
+#[derive(Clone, Default)]
+pub struct LatencyStats {
+    samples: VecDeque<u64>,
+}
+
+impl LatencyStats {
+    pub fn new() -> Self {
+        Self { samples: VecDeque::with_capacity(MAX_SAMPLES) }
+    }
+
+    /// Record a new round-trip time, dropping the oldest sample if the
+    /// window is full.
+    pub fn record(&mut self, rtt: Duration) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt.as_millis() as u64);
+    }
+
+    /// Returns `true` if no samples have been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Most recent round-trip time.
+    pub fn last(&self) -> Option<u64> {
+        self.samples.back().copied()
+    }
+
+    /// Average round-trip time over the window.
+    pub fn average(&self) -> Option<u64> {
+        if self.samples.is_empty() {
+            return None
+        }
+        Some(self.samples.iter().sum::<u64>() / self.samples.len() as u64)
+    }
+
+    /// Round-trip time at the given percentile (0-100) over the window,
+    /// using the nearest-rank method.
+    pub fn percentile(&self, p: u8) -> Option<u64> {
+        if self.samples.is_empty() {
+            return None
+        }
+
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let p = p.min(100) as usize;
+        let rank = (p * sorted.len() + 99) / 100;
+        Some(sorted[rank.saturating_sub(1)])
+    }
+
+    pub fn get_info(&self) -> serde_json::Value {
+        json!({
+            "samples": self.samples.len(),
+            "last_ms": self.last(),
+            "avg_ms": self.average(),
+            "p50_ms": self.percentile(50),
+            "p90_ms": self.percentile(90),
+            "p99_ms": self.percentile(99),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats() {
+        let mut stats = LatencyStats::new();
+        assert!(stats.average().is_none());
+        assert!(stats.percentile(50).is_none());
+
+        for ms in 1..=100 {
+            stats.record(Duration::from_millis(ms));
+        }
+
+        // Only the last MAX_SAMPLES samples are kept
+        assert_eq!(stats.samples.len(), MAX_SAMPLES);
+        assert_eq!(stats.last(), Some(100));
+        assert_eq!(stats.percentile(0), Some(37));
+        assert_eq!(stats.percentile(50), Some(68));
+        assert_eq!(stats.percentile(100), Some(100));
+        assert_eq!(stats.average(), Some((37..=100).sum::<u64>() / 64));
+    }
+}
@@ -5,15 +5,70 @@ use log::debug;
 use url::Url;
 
 use crate::{
-    util::serial::{Decodable, Encodable, VarInt},
+    util::{
+        async_serial::{AsyncDecodable, AsyncEncodable},
+        serial::{BytesReader, Decodable, Encodable, VarInt},
+    },
     Error, Result,
 };
 
+use super::send_queue::MessagePriority;
+
 const MAGIC_BYTES: [u8; 4] = [0xd9, 0xef, 0xb6, 0x7d];
 
+/// Magic bytes of packets carrying a flags byte. Packets are only flagged
+/// once the version handshake enabled compression or a later message
+/// format, so peers predating flags keep reading the original framing.
+const MAGIC_BYTES_FLAGGED: [u8; 4] = [0xd9, 0xef, 0xb6, 0x7e];
+
+/// Packet flag marking an lz4 compressed payload.
+const FLAG_COMPRESSED: u8 = 0x01;
+
+/// Packet flag marking a payload encoded in a later version of its message
+/// format. The version byte follows the flags.
+const FLAG_VERSIONED: u8 = 0x02;
+
+/// Upper bound for the size of a decompressed payload, protecting against
+/// compression bombs.
+const MAX_DECOMPRESSED_SIZE: usize = 32 * 1024 * 1024;
+
+/// Upper bound for the length of a packet command.
+const MAX_COMMAND_LEN: usize = 255;
+
 /// Generic message template.
 pub trait Message: 'static + Encodable + Decodable + Send + Sync {
     fn name() -> &'static str;
+
+    /// Priority class of this message in the channel send queue.
+    fn priority() -> MessagePriority {
+        MessagePriority::Normal
+    }
+
+    /// Decode the message from the payload it was received in. Messages
+    /// carrying large byte strings can override this to read them with
+    /// [`BytesReader::read_bytes`], sharing the payload rather than copying.
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        Self::decode(reader)
+    }
+
+    /// Highest version of the message format this node speaks. Version 0 is
+    /// the original format. Channels send each message in the highest
+    /// version both peers announced in the version handshake.
+    fn version() -> u8 {
+        0
+    }
+
+    /// Encode the message in provided format version, which is never above
+    /// [`Message::version`].
+    fn encode_versioned<S: io::Write>(&self, _version: u8, s: S) -> Result<usize> {
+        self.encode(s)
+    }
+
+    /// Decode a message received in provided format version, which is never
+    /// above [`Message::version`].
+    fn decode_versioned(_version: u8, reader: &mut BytesReader) -> Result<Self> {
+        Self::decode_shared(reader)
+    }
 }
 
 /// Outbound keep-alive message.
@@ -38,45 +93,103 @@ pub struct AddrsMessage {
 /// Requests version information of outbound connection.
 pub struct VersionMessage {
     pub node_id: String,
+    /// Whether the sender sees this connection as transport-encrypted
+    pub encrypted: bool,
+    /// Whether the sender accepts compressed payloads
+    pub compression: bool,
+    /// Messages the sender speaks a later version of, with their highest
+    /// version. Empty for peers predating message versions.
+    pub features: Vec<(String, u8)>,
 }
 
 /// Sends version information to inbound connection. Response to VersionMessage.
 pub struct VerackMessage {}
 
+/// Tells the remote peer we are closing the connection.
+pub struct DisconnectMessage {
+    pub reason: String,
+}
+
 impl Message for PingMessage {
     fn name() -> &'static str {
         "ping"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::High
+    }
 }
 
 impl Message for PongMessage {
     fn name() -> &'static str {
         "pong"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::High
+    }
 }
 
 impl Message for GetAddrsMessage {
     fn name() -> &'static str {
         "getaddr"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Low
+    }
 }
 
 impl Message for AddrsMessage {
     fn name() -> &'static str {
         "addr"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Low
+    }
 }
 
 impl Message for VersionMessage {
     fn name() -> &'static str {
         "version"
     }
+
+    fn decode_shared(reader: &mut BytesReader) -> Result<Self> {
+        let node_id = Decodable::decode(&mut *reader)?;
+        let encrypted = Decodable::decode(&mut *reader)?;
+        let compression = Decodable::decode(&mut *reader)?;
+        // Older peers stop here
+        let features = match reader.remaining() {
+            0 => vec![],
+            _ => Decodable::decode(&mut *reader)?,
+        };
+        Ok(Self { node_id, encrypted, compression, features })
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::High
+    }
 }
 
 impl Message for VerackMessage {
     fn name() -> &'static str {
         "verack"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::High
+    }
+}
+
+impl Message for DisconnectMessage {
+    fn name() -> &'static str {
+        "disconnect"
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::High
+    }
 }
 
 impl Encodable for PingMessage {
@@ -138,13 +251,21 @@ impl Encodable for VersionMessage {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.node_id.encode(&mut s)?;
+        len += self.encrypted.encode(&mut s)?;
+        len += self.compression.encode(&mut s)?;
+        len += self.features.encode(&mut s)?;
         Ok(len)
     }
 }
 
 impl Decodable for VersionMessage {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
-        Ok(Self { node_id: Decodable::decode(&mut d)? })
+        Ok(Self {
+            node_id: Decodable::decode(&mut d)?,
+            encrypted: Decodable::decode(&mut d)?,
+            compression: Decodable::decode(&mut d)?,
+            features: Decodable::decode(&mut d)?,
+        })
     }
 }
 
@@ -160,15 +281,100 @@ impl Decodable for VerackMessage {
     }
 }
 
+impl Encodable for DisconnectMessage {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.reason.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for DisconnectMessage {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self { reason: Decodable::decode(&mut d)? })
+    }
+}
+
 /// Packets are the base type read from the network. Converted to messages and
 /// passed to event loop.
 pub struct Packet {
     pub command: String,
     pub payload: Vec<u8>,
+    /// Whether the payload is lz4 compressed
+    pub compressed: bool,
+    /// Version of the message format the payload is encoded in
+    pub version: u8,
+}
+
+impl Packet {
+    /// Create a new uncompressed packet, in the original message format.
+    pub fn new(command: &str, payload: Vec<u8>) -> Self {
+        Self { command: command.to_string(), payload, compressed: false, version: 0 }
+    }
+
+    /// Whether this packet is sent with a flags byte.
+    fn is_flagged(&self) -> bool {
+        self.compressed || self.version > 0
+    }
+
+    /// Number of bytes this packet occupies on the wire.
+    pub fn size(&self) -> usize {
+        MAGIC_BYTES.len() +
+            self.is_flagged() as usize +
+            (self.version > 0) as usize +
+            VarInt(self.command.len() as u64).length() +
+            self.command.len() +
+            VarInt(self.payload.len() as u64).length() +
+            self.payload.len()
+    }
+
+    /// Compress the payload if it is at least `threshold` bytes long and
+    /// compression actually makes it smaller.
+    pub fn compress(&mut self, threshold: usize) {
+        if self.compressed || self.payload.len() < threshold {
+            return
+        }
+
+        let compressed = lz4_flex::compress_prepend_size(&self.payload);
+        if compressed.len() < self.payload.len() {
+            debug!(
+                target: "net",
+                "compressed {} payload {} -> {} bytes",
+                self.command,
+                self.payload.len(),
+                compressed.len()
+            );
+            self.payload = compressed;
+            self.compressed = true;
+        }
+    }
+
+    /// Decompress the payload if it is compressed.
+    pub fn decompress(&mut self) -> Result<()> {
+        if !self.compressed {
+            return Ok(())
+        }
+
+        // The uncompressed size is prepended as a little-endian u32
+        if self.payload.len() < 4 {
+            return Err(Error::MalformedPacket)
+        }
+        let size = u32::from_le_bytes(self.payload[..4].try_into().unwrap()) as usize;
+        if size > MAX_DECOMPRESSED_SIZE {
+            return Err(Error::MalformedPacket)
+        }
+
+        self.payload = match lz4_flex::decompress_size_prepended(&self.payload) {
+            Ok(payload) => payload,
+            Err(_) => return Err(Error::MalformedPacket),
+        };
+        self.compressed = false;
+        Ok(())
+    }
 }
 
 /// Reads and decodes an inbound payload.
-pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result<Packet> {
+pub async fn read_packet<R: AsyncRead + Unpin + Send + Sized>(stream: &mut R) -> Result<Packet> {
     // Packets have a 4 byte header of magic digits
     // This is used for network debugging
     let mut magic = [0u8; 4];
@@ -177,12 +383,29 @@ pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result
     stream.read_exact(&mut magic).await?;
 
     debug!(target: "net", "read magic {:?}", magic);
-    if magic != MAGIC_BYTES {
+    let mut flags = [0u8; 1];
+    match magic {
+        MAGIC_BYTES => {}
+        MAGIC_BYTES_FLAGGED => stream.read_exact(&mut flags).await?,
+        _ => return Err(Error::MalformedPacket),
+    }
+    if flags[0] & !(FLAG_COMPRESSED | FLAG_VERSIONED) != 0 {
         return Err(Error::MalformedPacket)
     }
 
+    let mut version = [0u8; 1];
+    if flags[0] & FLAG_VERSIONED != 0 {
+        stream.read_exact(&mut version).await?;
+        if version[0] == 0 {
+            return Err(Error::MalformedPacket)
+        }
+    }
+
     // The type of the message
     let command_len = VarInt::decode_async(stream).await?.0 as usize;
+    if command_len > MAX_COMMAND_LEN {
+        return Err(Error::MalformedPacket)
+    }
     let mut cmd = vec![0u8; command_len];
     if command_len > 0 {
         stream.read_exact(&mut cmd).await?;
@@ -190,7 +413,11 @@ pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result
     let cmd = String::from_utf8(cmd)?;
     debug!(target: "net", "read command: {}", cmd);
 
+    // Payloads are only sent compressed when that makes them smaller
     let payload_len = VarInt::decode_async(stream).await?.0 as usize;
+    if payload_len > MAX_DECOMPRESSED_SIZE {
+        return Err(Error::MalformedPacket)
+    }
 
     // The message-dependent data (see message types)
     let mut payload = vec![0u8; payload_len];
@@ -199,16 +426,33 @@ pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result
     }
     debug!(target: "net", "read payload {} bytes", payload_len);
 
-    Ok(Packet { command: cmd, payload })
+    Ok(Packet {
+        command: cmd,
+        payload,
+        compressed: flags[0] & FLAG_COMPRESSED != 0,
+        version: version[0],
+    })
 }
 
 /// Sends an outbound packet by writing data to TCP stream.
-pub async fn send_packet<W: AsyncWrite + Unpin + Sized>(
+pub async fn send_packet<W: AsyncWrite + Unpin + Send + Sized>(
     stream: &mut W,
     packet: Packet,
 ) -> Result<()> {
     debug!(target: "net", "sending magic...");
-    stream.write_all(&MAGIC_BYTES).await?;
+    if packet.is_flagged() {
+        stream.write_all(&MAGIC_BYTES_FLAGGED).await?;
+        let mut flags = if packet.compressed { FLAG_COMPRESSED } else { 0 };
+        if packet.version > 0 {
+            flags |= FLAG_VERSIONED;
+        }
+        stream.write_all(&[flags]).await?;
+        if packet.version > 0 {
+            stream.write_all(&[packet.version]).await?;
+        }
+    } else {
+        stream.write_all(&MAGIC_BYTES).await?;
+    }
     debug!(target: "net", "sent magic...");
 
     VarInt(packet.command.len() as u64).encode_async(stream).await?;
@@ -216,7 +460,6 @@ pub async fn send_packet<W: AsyncWrite + Unpin + Sized>(
     stream.write_all(packet.command.as_bytes()).await?;
     debug!(target: "net", "sent command: {}", packet.command);
 
-    assert_eq!(std::mem::size_of::<usize>(), std::mem::size_of::<u64>());
     VarInt(packet.payload.len() as u64).encode_async(stream).await?;
 
     if !packet.payload.is_empty() {
@@ -226,3 +469,42 @@ pub async fn send_packet<W: AsyncWrite + Unpin + Sized>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::io::Cursor;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_versioned_packet() -> Result<()> {
+        // Packets in the original format keep the original framing
+        let packet = Packet::new("ping", vec![1, 2, 3]);
+        let size = packet.size();
+        let mut stream = Cursor::new(vec![]);
+        send_packet(&mut stream, packet).await?;
+        let bytes = stream.into_inner();
+        assert_eq!(bytes.len(), size);
+        assert_eq!((&bytes[..4], bytes[4]), (&MAGIC_BYTES[..], 4));
+        let packet = read_packet(&mut &bytes[..]).await?;
+        assert_eq!((packet.version, packet.payload), (0, vec![1, 2, 3]));
+
+        let mut packet = Packet::new("ping", vec![1, 2, 3]);
+        packet.version = 2;
+        let size = packet.size();
+        let mut stream = Cursor::new(vec![]);
+        send_packet(&mut stream, packet).await?;
+        let bytes = stream.into_inner();
+        assert_eq!(bytes.len(), size);
+        assert_eq!(&bytes[..4], &MAGIC_BYTES_FLAGGED[..]);
+        assert_eq!((bytes[4], bytes[5]), (FLAG_VERSIONED, 2));
+        let packet = read_packet(&mut &bytes[..]).await?;
+        assert_eq!((packet.version, packet.payload), (2, vec![1, 2, 3]));
+
+        // Version 0 is never flagged
+        let mut malformed = bytes.clone();
+        malformed[5] = 0;
+        assert!(read_packet(&mut &malformed[..]).await.is_err());
+        Ok(())
+    }
+}
@@ -1,13 +1,13 @@
 use async_std::sync::Mutex;
-use std::{any::Any, io, io::Cursor, sync::Arc};
+use std::{any::Any, io, sync::Arc};
 
 use async_trait::async_trait;
 use fxhash::FxHashMap;
-use log::{debug, error, warn};
+use log::{debug, error};
 use rand::Rng;
 
 use crate::{
-    util::serial::{Decodable, Encodable},
+    util::serial::{with_decode_limits, Bytes, BytesReader, Decodable, DecodeLimits, Encodable},
     Error, Result,
 };
 
@@ -45,7 +45,10 @@ impl<M: Message> MessageSubscription<M> {
 #[async_trait]
 /// Generic interface for message dispatcher.
 trait MessageDispatcherInterface: Send + Sync {
-    async fn trigger(&self, payload: Vec<u8>);
+    async fn trigger(&self, version: u8, payload: Vec<u8>) -> Result<()>;
+
+    /// Highest version of the message format
+    fn version(&self) -> u8;
 
     async fn trigger_error(&self, err: Error);
 
@@ -133,17 +136,28 @@ impl<M: Message> MessageDispatcher<M> {
 // Local implementation of the Message Dispatcher Interface.
 impl<M: Message> MessageDispatcherInterface for MessageDispatcher<M> {
     /// Internal function to deserialize data into a message type and dispatch it across subscriber channels.
-    async fn trigger(&self, payload: Vec<u8>) {
+    /// Returns the decoding error if the payload is not a valid message.
+    async fn trigger(&self, version: u8, payload: Vec<u8>) -> Result<()> {
+        if version > M::version() {
+            error!("Unsupported version {} of {} message. Dropping...", version, M::name());
+            return Err(Error::ParseFailed("unsupported message version"))
+        }
+
         // deserialize data into type
         // send down the pipes
-        let cursor = Cursor::new(payload);
-        match M::decode(cursor) {
+        let mut reader = BytesReader::new(Bytes::from(payload));
+        let decoded = with_decode_limits(DecodeLimits::default(), || {
+            M::decode_versioned(version, &mut reader)
+        });
+        match decoded {
             Ok(message) => {
                 let message = Ok(Arc::new(message));
-                self._trigger_all(message).await
+                self._trigger_all(message).await;
+                Ok(())
             }
             Err(err) => {
                 error!("Unable to decode data. Dropping...: {}", err);
+                Err(err)
             }
         }
     }
@@ -153,6 +167,10 @@ impl<M: Message> MessageDispatcherInterface for MessageDispatcher<M> {
         self._trigger_all(Err(err)).await;
     }
 
+    fn version(&self) -> u8 {
+        M::version()
+    }
+
     /// Converts to Any trait. Enables the dynamic modification of static types.
     fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
         self
@@ -200,22 +218,31 @@ impl MessageSubsystem {
         Ok(sub)
     }
 
-    /// Transmits a payload to a dispatcher. Returns an error if the payload
-    /// fails to transmit.
-    pub async fn notify(&self, command: &str, payload: Vec<u8>) {
+    /// Messages with a dispatcher whose format has later versions, with
+    /// their highest version. Announced to peers in the version handshake.
+    pub async fn versions(&self) -> Vec<(String, u8)> {
+        let mut versions: Vec<(String, u8)> = self
+            .dispatchers
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, dispatcher)| dispatcher.version() > 0)
+            .map(|(name, dispatcher)| (name.to_string(), dispatcher.version()))
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// Transmits a payload encoded in provided message format version to a
+    /// dispatcher. Returns an error if the payload could not be decoded into
+    /// its message type, or [`Error::UnknownMessage`] if no dispatcher was
+    /// added for the command.
+    pub async fn notify(&self, command: &str, version: u8, payload: Vec<u8>) -> Result<()> {
         let dispatcher = self.dispatchers.lock().await.get(command).cloned();
 
         match dispatcher {
-            Some(dispatcher) => {
-                dispatcher.trigger(payload).await;
-            }
-            None => {
-                warn!(
-                    target: "MessageSubsystem::notify",
-                    "MessageSubsystem::notify(\"{}\", payload) did not find a dispatcher",
-                    command
-                );
-            }
+            Some(dispatcher) => dispatcher.trigger(version, payload).await,
+            None => Err(Error::UnknownMessage(command.to_string())),
         }
     }
 
@@ -281,7 +308,7 @@ async fn _do_message_subscriber_test() {
     // receive message and publish
     //   1. based on string, lookup relevant dispatcher interface
     //   2. publish data there
-    subsystem.notify("verver", payload).await;
+    subsystem.notify("verver", 0, payload).await.unwrap();
 
     // receive
     //    1. do a get easy
@@ -289,6 +316,12 @@ async fn _do_message_subscriber_test() {
     assert_eq!(msg2.x, 110);
     println!("{}", msg2.x);
 
+    // Commands without a dispatcher are reported to the channel
+    assert!(matches!(
+        subsystem.notify("unknown", 0, vec![]).await,
+        Err(Error::UnknownMessage(command)) if command == "unknown"
+    ));
+
     subsystem.trigger_error(Error::ChannelStopped).await;
 
     let msg2 = sub.receive().await;
@@ -3,6 +3,16 @@
 /// connections and to handle network errors.
 pub mod acceptor;
 
+/// Peer misbehavior tracking. Protocol violations add to a per-host score,
+/// and hosts reaching the configured threshold are temporarily banned. The
+/// banlist can be persisted to disk so bans survive restarts.
+pub mod ban_manager;
+
+/// Bandwidth accounting. Implements byte counters and a token bucket rate
+/// limiter used to cap upload bandwidth per channel and across the whole
+/// p2p network.
+pub mod bandwidth;
+
 /// Async channel that handles the sending of messages across the network.
 /// Public interface is used to create new channels, to stop and start
 /// a channel, and to send messages.
@@ -10,6 +20,11 @@ pub mod acceptor;
 /// Implements message functionality and the message subscriber subsystem.
 pub mod channel;
 
+/// Typed introspection of the p2p network of a node: channels and their
+/// message counters, sessions, known hosts and registered protocols. Served
+/// over JSON-RPC by the `dnet` namespace.
+pub mod dnet;
+
 /// Handles the creation of outbound connections. Used to establish an outbound
 /// connection.
 pub mod connector;
@@ -40,6 +55,24 @@ pub mod hosts;
 /// process any kind of payload as a message.
 pub mod message_subscriber;
 
+/// Rolling ping round-trip time statistics kept for every channel, used to
+/// spot slow peers.
+pub mod latency;
+
+/// NAT traversal. Implements a NAT-PMP client that maps the ports of our
+/// inbound listeners on the gateway, so the node can advertise a reachable
+/// external address without it being configured manually.
+pub mod nat;
+
+/// Request-response layer on top of channels. Requests carry an id that the
+/// matching response echoes back, so several requests can be in flight on the
+/// same channel. Each request waits for its response with a timeout.
+pub mod request;
+
+/// Per-channel send queue. Messages are queued by priority class and written
+/// out by a dedicated task, so broadcasting never waits on a slow peer.
+pub mod send_queue;
+
 /// Defines how to decode generic messages as well as implementing the common
 /// network messages that are sent between nodes as described by the Protocol
 /// submodule.
@@ -89,19 +122,26 @@ pub mod settings;
 pub mod transport;
 
 pub use acceptor::{Acceptor, AcceptorPtr};
+pub use ban_manager::{BanManager, BanManagerPtr, Misbehavior};
+pub use bandwidth::{ByteCounter, RateLimiter, RateLimiterPtr};
 pub use channel::{Channel, ChannelPtr};
 pub use connector::Connector;
 pub use hosts::{Hosts, HostsPtr};
+pub use latency::LatencyStats;
 pub use message::Message;
 pub use message_subscriber::MessageSubscription;
 pub use p2p::{P2p, P2pPtr};
-pub use protocol::{ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr};
+pub use protocol::{
+    Gossip, GossipPtr, ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr,
+};
+pub use request::{Request, Requester, RequesterPtr};
+pub use send_queue::MessagePriority;
 pub use session::{
     Session, SessionBitflag, SessionWeakPtr, SESSION_ALL, SESSION_INBOUND, SESSION_MANUAL,
     SESSION_OUTBOUND, SESSION_SEED,
 };
-pub use settings::{Settings, SettingsPtr};
+pub use settings::{Settings, SettingsPtr, TransportSlots, UnknownMessagePolicy};
 pub use transport::{
-    TcpTransport, TorTransport, Transport, TransportListener, TransportName, TransportStream,
-    UnixTransport,
+    I2pTransport, MemoryTransport, TcpTransport, TorTransport, Transport, TransportListener,
+    TransportName, TransportStream, UnixTransport,
 };
@@ -0,0 +1,453 @@
+use async_std::{
+    future::timeout,
+    net::{TcpStream, UdpSocket},
+    sync::Arc,
+};
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use log::{debug, error, info, warn};
+use smol::Executor;
+use url::Url;
+
+use crate::{util::sleep, Error, Result};
+
+use super::P2pPtr;
+
+/// Port the NAT-PMP service listens on at the gateway.
+const NATPMP_PORT: u16 = 5351;
+/// Requested lifetime of a port mapping, in seconds.
+const MAPPING_LIFETIME: u32 = 7200;
+/// Number of times a request is sent before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+/// Timeout of the first attempt, doubled on every retry.
+const INITIAL_TIMEOUT_MS: u64 = 250;
+
+const OP_EXTERNAL_ADDRESS: u8 = 0;
+const OP_MAP_TCP: u8 = 2;
+const OP_RESPONSE: u8 = 128;
+
+/// Multicast address UPnP devices are discovered on with SSDP.
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+/// SSDP search for Internet gateway devices.
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+    HOST: 239.255.255.250:1900\r\n\
+    ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+    MAN: \"ssdp:discover\"\r\n\
+    MX: 2\r\n\r\n";
+/// UPnP services able to map ports, in order of preference.
+const UPNP_WAN_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+/// Upper bound for the size of a UPnP HTTP response.
+const MAX_HTTP_RESPONSE: u64 = 64 * 1024;
+
+/// Find the IPv4 default gateway by reading the kernel routing table.
+fn default_gateway() -> Result<Ipv4Addr> {
+    if !cfg!(target_os = "linux") {
+        return Err(Error::UnsupportedOS)
+    }
+
+    let routes = fs::read_to_string("/proc/net/route")?;
+    for line in routes.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            continue
+        }
+
+        // The gateway is printed as a host-endian hex dump of the address
+        let gateway = u32::from_str_radix(fields[2], 16)?;
+        return Ok(Ipv4Addr::from(gateway.to_le_bytes()))
+    }
+
+    Err(Error::NatError("No default gateway found".to_string()))
+}
+
+/// Minimal NAT-PMP (RFC 6886) client used to open a port on the gateway for
+/// our inbound listeners.
+pub struct NatPmp {
+    gateway: SocketAddr,
+}
+
+impl NatPmp {
+    /// Create a client talking to the default gateway.
+    pub fn new() -> Result<Self> {
+        let gateway = default_gateway()?;
+        Ok(Self { gateway: SocketAddr::new(gateway.into(), NATPMP_PORT) })
+    }
+
+    /// Send a request and wait for the response with the matching opcode,
+    /// retrying with exponential backoff.
+    async fn request(&self, request: &[u8], response_len: usize) -> Result<Vec<u8>> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let mut buf = vec![0u8; response_len];
+        let mut wait = Duration::from_millis(INITIAL_TIMEOUT_MS);
+
+        for _ in 0..MAX_ATTEMPTS {
+            socket.send_to(request, self.gateway).await?;
+
+            if let Ok(result) = timeout(wait, socket.recv_from(&mut buf)).await {
+                let (n, from) = result?;
+                if from != self.gateway || n < response_len {
+                    continue
+                }
+
+                if buf[0] != 0 || buf[1] != OP_RESPONSE + request[1] {
+                    return Err(Error::NatError("Unexpected NAT-PMP response".to_string()))
+                }
+
+                let result_code = u16::from_be_bytes([buf[2], buf[3]]);
+                if result_code != 0 {
+                    return Err(Error::NatError(format!("NAT-PMP result code {}", result_code)))
+                }
+
+                return Ok(buf)
+            }
+
+            wait *= 2;
+        }
+
+        Err(Error::NatError(format!("No NAT-PMP response from {}", self.gateway)))
+    }
+
+    /// Ask the gateway for its public IPv4 address.
+    pub async fn external_address(&self) -> Result<Ipv4Addr> {
+        let response = self.request(&[0, OP_EXTERNAL_ADDRESS], 12).await?;
+        Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+    }
+
+    /// Map the given internal TCP port. Returns the external port assigned by
+    /// the gateway, which may differ from the requested one.
+    pub async fn map_tcp_port(&self, internal_port: u16, lifetime: u32) -> Result<u16> {
+        let mut request = vec![0, OP_MAP_TCP, 0, 0];
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        // Suggest the same port externally
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        request.extend_from_slice(&lifetime.to_be_bytes());
+
+        let response = self.request(&request, 16).await?;
+        Ok(u16::from_be_bytes([response[10], response[11]]))
+    }
+}
+
+/// Return the header value of an HTTP-like response, like SSDP's.
+fn http_header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        match key.trim().eq_ignore_ascii_case(name) {
+            true => Some(value.trim()),
+            false => None,
+        }
+    })
+}
+
+/// Return the text of the first XML element with the given name, ignoring
+/// namespace prefixes. Gateway responses are flat enough not to need a
+/// full XML parser.
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+    loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+        let end = rest.find('>')?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let tag_name = tag.split_whitespace().next().unwrap_or("");
+        if tag_name.rsplit(':').next() != Some(name) {
+            continue
+        }
+
+        let close = rest.find("</")?;
+        return Some(rest[..close].trim())
+    }
+}
+
+/// Send an HTTP request to a UPnP device and return the response body,
+/// along with our local address on the connection.
+async fn http_request(
+    url: &Url,
+    method: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<(String, IpAddr)> {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return Err(Error::NatError(format!("No host in UPnP URL {}", url))),
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let local_ip = stream.local_addr()?.ip();
+
+    // HTTP/1.0 keeps the response unchunked
+    let mut request = format!(
+        "{} {} HTTP/1.0\r\nHost: {}:{}\r\nContent-Length: {}\r\n",
+        method,
+        &url[url::Position::BeforePath..],
+        host,
+        port,
+        body.len()
+    );
+    for (name, value) in headers {
+        request += &format!("{}: {}\r\n", name, value);
+    }
+    request += "\r\n";
+    request += body;
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = vec![];
+    stream.take(MAX_HTTP_RESPONSE).read_to_end(&mut response).await?;
+    let response = String::from_utf8(response)?;
+
+    let (head, body) = match response.split_once("\r\n\r\n") {
+        Some(v) => v,
+        None => return Err(Error::NatError("Malformed UPnP HTTP response".to_string())),
+    };
+    let status = head.lines().next().unwrap_or("");
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(Error::NatError(format!("UPnP request failed: {}", status)))
+    }
+
+    Ok((body.to_string(), local_ip))
+}
+
+/// Minimal UPnP Internet Gateway Device client, used when the gateway does
+/// not speak NAT-PMP.
+pub struct Upnp {
+    /// Control URL of the WAN connection service
+    control_url: Url,
+    /// Type of the WAN connection service
+    service: &'static str,
+    /// Our address on the gateway's network, the target of mappings
+    local_ip: IpAddr,
+}
+
+impl Upnp {
+    /// Find the gateway with SSDP and its WAN connection service.
+    pub async fn discover() -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let mut buf = vec![0u8; 2048];
+        let mut wait = Duration::from_millis(INITIAL_TIMEOUT_MS);
+
+        for _ in 0..MAX_ATTEMPTS {
+            socket.send_to(SSDP_SEARCH.as_bytes(), SSDP_ADDR).await?;
+
+            if let Ok(result) = timeout(wait, socket.recv_from(&mut buf)).await {
+                let (n, from) = result?;
+                let response = String::from_utf8_lossy(&buf[..n]);
+                if let Some(location) = http_header(&response, "location") {
+                    debug!(target: "net", "UPnP: found gateway {} at {}", from, location);
+                    return Self::from_location(&Url::parse(location)?).await
+                }
+                continue
+            }
+
+            wait *= 2;
+        }
+
+        Err(Error::NatError("No UPnP gateway found".to_string()))
+    }
+
+    /// Read the device description of the gateway for the control URL of
+    /// its WAN connection service.
+    async fn from_location(location: &Url) -> Result<Self> {
+        let (description, local_ip) = http_request(location, "GET", &[], "").await?;
+
+        for service in UPNP_WAN_SERVICES {
+            // The control URL follows the service type within its element
+            let start = match description.find(service) {
+                Some(start) => start,
+                None => continue,
+            };
+
+            if let Some(control_url) = xml_element(&description[start..], "controlURL") {
+                let control_url = location.join(control_url)?;
+                return Ok(Self { control_url, service, local_ip })
+            }
+        }
+
+        Err(Error::NatError("No WAN connection service on the UPnP gateway".to_string()))
+    }
+
+    /// Invoke a SOAP action of the WAN connection service.
+    async fn soap(&self, action: &str, args: &[(&str, String)]) -> Result<String> {
+        let mut body = format!(
+            "<?xml version=\"1.0\"?>\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:{} xmlns:u=\"{}\">",
+            action, self.service
+        );
+        for (name, value) in args {
+            body += &format!("<{0}>{1}</{0}>", name, value);
+        }
+        body += &format!("</u:{}></s:Body></s:Envelope>", action);
+
+        let soap_action = format!("\"{}#{}\"", self.service, action);
+        let headers =
+            [("Content-Type", "text/xml; charset=\"utf-8\""), ("SOAPAction", &soap_action)];
+        let (response, _) = http_request(&self.control_url, "POST", &headers, &body).await?;
+        Ok(response)
+    }
+
+    /// Ask the gateway for its public IPv4 address.
+    pub async fn external_address(&self) -> Result<Ipv4Addr> {
+        let response = self.soap("GetExternalIPAddress", &[]).await?;
+        match xml_element(&response, "NewExternalIPAddress") {
+            Some(addr) => Ok(addr.parse()?),
+            None => Err(Error::NatError("No external address in UPnP response".to_string())),
+        }
+    }
+
+    /// Map the given internal TCP port to the same external port.
+    pub async fn map_tcp_port(&self, internal_port: u16, lifetime: u32) -> Result<u16> {
+        let args = [
+            ("NewRemoteHost", String::new()),
+            ("NewExternalPort", internal_port.to_string()),
+            ("NewProtocol", "TCP".to_string()),
+            ("NewInternalPort", internal_port.to_string()),
+            ("NewInternalClient", self.local_ip.to_string()),
+            ("NewEnabled", "1".to_string()),
+            ("NewPortMappingDescription", "darkfi".to_string()),
+            ("NewLeaseDuration", lifetime.to_string()),
+        ];
+        self.soap("AddPortMapping", &args).await?;
+        Ok(internal_port)
+    }
+}
+
+/// Port mapping protocol spoken by the gateway.
+enum PortMapper {
+    NatPmp(NatPmp),
+    Upnp(Upnp),
+}
+
+impl PortMapper {
+    /// Find a way to map ports on the gateway, preferring NAT-PMP, and
+    /// return it along with the gateway's public address.
+    async fn discover() -> Result<(Self, Ipv4Addr)> {
+        let natpmp_err = match NatPmp::new() {
+            Ok(natpmp) => match natpmp.external_address().await {
+                Ok(external_ip) => return Ok((Self::NatPmp(natpmp), external_ip)),
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+        debug!(target: "net", "NAT-PMP unavailable ({}), trying UPnP", natpmp_err);
+
+        let upnp = Upnp::discover().await?;
+        let external_ip = upnp.external_address().await?;
+        Ok((Self::Upnp(upnp), external_ip))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::NatPmp(_) => "NAT-PMP",
+            Self::Upnp(_) => "UPnP",
+        }
+    }
+
+    async fn map_tcp_port(&self, internal_port: u16, lifetime: u32) -> Result<u16> {
+        match self {
+            Self::NatPmp(natpmp) => natpmp.map_tcp_port(internal_port, lifetime).await,
+            Self::Upnp(upnp) => upnp.map_tcp_port(internal_port, lifetime).await,
+        }
+    }
+}
+
+/// Map the ports of our TCP inbound listeners on the gateway, with NAT-PMP
+/// or UPnP, and advertise the resulting public addresses. Mappings are
+/// renewed in the background for as long as `p2p` is alive.
+pub async fn start_port_mapping(p2p: P2pPtr, executor: Arc<Executor<'_>>) -> Result<()> {
+    let (mapper, external_ip) = PortMapper::discover().await?;
+    info!(target: "net", "{}: external address is {}", mapper.name(), external_ip);
+
+    let mut ports = vec![];
+    for inbound in &p2p.settings().inbound {
+        if !inbound.scheme().starts_with("tcp") && inbound.scheme() != "tls" {
+            debug!(target: "net", "{}: skipping non-TCP inbound {}", mapper.name(), inbound);
+            continue
+        }
+
+        let port = match inbound.port() {
+            Some(port) => port,
+            None => continue,
+        };
+
+        let external_port = mapper.map_tcp_port(port, MAPPING_LIFETIME).await?;
+        info!(
+            target: "net",
+            "{}: mapped {} to {}:{}",
+            mapper.name(),
+            inbound,
+            external_ip,
+            external_port
+        );
+
+        let mut external_addr = inbound.clone();
+        if external_addr.set_ip_host(external_ip.into()).is_err() ||
+            external_addr.set_port(Some(external_port)).is_err()
+        {
+            return Err(Error::InvalidExternalAddr(inbound.to_string()))
+        }
+        p2p.add_external_addr(external_addr).await;
+
+        ports.push(port);
+    }
+
+    if ports.is_empty() {
+        warn!(target: "net", "{}: no TCP inbound addresses to map", mapper.name());
+        return Ok(())
+    }
+
+    let p2p = Arc::downgrade(&p2p);
+    executor
+        .spawn(async move {
+            loop {
+                sleep((MAPPING_LIFETIME / 2).into()).await;
+                if p2p.upgrade().is_none() {
+                    return
+                }
+
+                for port in &ports {
+                    if let Err(e) = mapper.map_tcp_port(*port, MAPPING_LIFETIME).await {
+                        error!(
+                            target: "net",
+                            "{}: failed renewing mapping of {}: {}",
+                            mapper.name(),
+                            port,
+                            e
+                        );
+                    }
+                }
+            }
+        })
+        .detach();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upnp_parsing() {
+        let ssdp = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+            Location: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+        assert_eq!(http_header(ssdp, "location"), Some("http://192.168.1.1:5000/rootDesc.xml"));
+        assert_eq!(http_header(ssdp, "server"), None);
+
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+            <NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(xml_element(response, "NewExternalIPAddress"), Some("203.0.113.7"));
+        assert_eq!(xml_element(response, "controlURL"), None);
+    }
+}
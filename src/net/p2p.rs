@@ -1,19 +1,28 @@
-use async_std::sync::{Arc, Mutex};
-use std::fmt;
+use async_std::{
+    future::timeout,
+    sync::{Arc, Mutex},
+};
+use std::{fmt, time::Duration};
 
 use async_executor::Executor;
+use futures::future::join_all;
 use fxhash::{FxHashMap, FxHashSet};
-use log::debug;
+use log::{debug, error, info, warn};
 use serde_json::json;
 use url::Url;
 
 use crate::{
     system::{Subscriber, SubscriberPtr, Subscription},
+    util::expand_path,
     Result,
 };
 
 use super::{
+    ban_manager::{BanManager, BanManagerPtr},
+    bandwidth::{ByteCounter, RateLimiter, RateLimiterPtr},
+    dnet,
     message::Message,
+    nat,
     protocol::{register_default_protocols, ProtocolRegistry},
     session::{InboundSession, ManualSession, OutboundSession, SeedSyncSession, Session},
     Channel, ChannelPtr, Hosts, HostsPtr, Settings, SettingsPtr,
@@ -35,6 +44,8 @@ enum P2pState {
     Started,
     // p2p is running and the network is active.
     Run,
+    // p2p is shutting down or has been shut down.
+    Stop,
 }
 
 impl fmt::Display for P2pState {
@@ -47,6 +58,7 @@ impl fmt::Display for P2pState {
                 Self::Start => "start",
                 Self::Started => "started",
                 Self::Run => "run",
+                Self::Stop => "stop",
             }
         )
     }
@@ -69,6 +81,15 @@ pub struct P2p {
 
     state: Mutex<P2pState>,
 
+    // Traffic accounting across all channels
+    traffic: ByteCounter,
+    upload_limiter: RateLimiterPtr,
+
+    ban_manager: BanManagerPtr,
+
+    // Configured external addresses, plus the ones discovered at runtime
+    external_addr: Mutex<Vec<Url>>,
+
     settings: SettingsPtr,
 }
 
@@ -94,6 +115,14 @@ impl P2p {
             session_inbound: Mutex::new(None),
             session_outbound: Mutex::new(None),
             state: Mutex::new(P2pState::Open),
+            traffic: ByteCounter::default(),
+            upload_limiter: RateLimiter::new(settings.upload_limit),
+            ban_manager: BanManager::new(
+                settings.ban_threshold,
+                settings.ban_seconds,
+                settings.banlist_path.clone(),
+            ),
+            external_addr: Mutex::new(settings.external_addr.clone()),
             settings,
         });
 
@@ -103,6 +132,18 @@ impl P2p {
         *self_.session_inbound.lock().await = Some(InboundSession::new(parent.clone()).await);
         *self_.session_outbound.lock().await = Some(OutboundSession::new(parent));
 
+        if let Some(path) = &self_.settings.hosts_path {
+            match expand_path(path) {
+                Ok(path) if path.exists() => {
+                    if let Err(e) = self_.hosts.load_file(&path).await {
+                        error!(target: "net", "Failed loading hosts from {:?}: {}", path, e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => error!(target: "net", "Invalid hosts path {}: {}", path, e),
+            }
+        }
+
         register_default_protocols(self_.clone()).await;
 
         self_
@@ -111,11 +152,46 @@ impl P2p {
     pub async fn get_info(&self) -> serde_json::Value {
         // Building ext_addr_vec string
         let mut ext_addr_vec = vec![];
-        for ext_addr in &self.settings.external_addr {
+        for ext_addr in &self.external_addr().await {
             ext_addr_vec.push(ext_addr.as_ref().to_string());
         }
 
+        // Peers sorted by the amount of traffic we sent them
+        let mut peers = vec![];
+        for channel in self.channels.lock().await.values() {
+            let traffic = channel.traffic();
+            peers.push((channel.address().to_string(), traffic.sent(), traffic.recv()));
+        }
+        peers.sort_by(|a, b| b.1.cmp(&a.1));
+        let peers: Vec<serde_json::Value> = peers
+            .into_iter()
+            .map(|(addr, sent, recv)| json!({"addr": addr, "bytes_sent": sent, "bytes_recv": recv}))
+            .collect();
+
+        // Peers sorted by their average ping round-trip time, slowest first
+        let mut latencies = vec![];
+        for channel in self.channels.lock().await.values() {
+            let latency = channel.latency().await;
+            if latency.is_empty() {
+                continue
+            }
+            latencies.push((channel.address().to_string(), latency));
+        }
+        latencies.sort_by(|a, b| b.1.average().cmp(&a.1.average()));
+        let latencies: Vec<serde_json::Value> = latencies
+            .into_iter()
+            .map(|(addr, latency)| json!({"addr": addr, "latency": latency.get_info()}))
+            .collect();
+
         json!({
+            "latency": latencies,
+            "bandwidth": {
+                "total": self.traffic.get_info(),
+                "peer_upload_limit": self.settings.peer_upload_limit,
+                "upload_limit": self.settings.upload_limit,
+                "peers": peers,
+            },
+            "bans": self.ban_manager.get_info().await,
             "external_addr": format!("{:?}", ext_addr_vec),
             "session_manual": self.session_manual().await.get_info().await,
             "session_inbound": self.session_inbound().await.get_info().await,
@@ -124,12 +200,62 @@ impl P2p {
         })
     }
 
+    /// Return the typed introspection info of the p2p network, served by
+    /// the `dnet` RPC namespace.
+    pub async fn dnet_info(&self) -> dnet::NodeInfo {
+        let external_addr =
+            self.external_addr().await.iter().map(|addr| addr.to_string()).collect();
+
+        let mut channels = vec![];
+        for channel in self.channels.lock().await.values() {
+            channels.push(channel.dnet_info().await);
+        }
+        channels.sort_by(|a, b| a.addr.cmp(&b.addr));
+
+        let sessions = dnet::SessionsInfo {
+            inbound: self.session_inbound().await.dnet_info().await,
+            outbound: self.session_outbound().await.dnet_info().await,
+        };
+
+        let hosts = dnet::HostsInfo {
+            pending: self.pending.lock().await.iter().map(|addr| addr.to_string()).collect(),
+            known: self.hosts.load_all().await.iter().map(|addr| addr.to_string()).collect(),
+        };
+
+        let protocols = self
+            .protocol_registry
+            .protocols()
+            .await
+            .into_iter()
+            .map(|(name, session_flags)| dnet::ProtocolInfo {
+                name: name.map(String::from),
+                sessions: dnet::session_names(session_flags),
+            })
+            .collect();
+
+        dnet::NodeInfo {
+            state: self.state.lock().await.to_string(),
+            external_addr,
+            channels,
+            sessions,
+            hosts,
+            protocols,
+        }
+    }
+
     /// Invoke startup and seeding sequence. Call from constructing thread.
     pub async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
         debug!(target: "net", "P2p::start() [BEGIN]");
 
         *self.state.lock().await = P2pState::Start;
 
+        // Open our inbound ports on the gateway before announcing ourselves
+        if self.settings.nat_traversal {
+            if let Err(e) = nat::start_port_mapping(self.clone(), executor.clone()).await {
+                warn!(target: "net", "NAT traversal failed: {}", e);
+            }
+        }
+
         // Start seed session
         let seed = SeedSyncSession::new(Arc::downgrade(&self));
         // This will block until all seed queries have finished
@@ -170,14 +296,9 @@ impl P2p {
         outbound.clone().start(executor.clone()).await?;
 
         let stop_sub = self.subscribe_stop().await;
-        // Wait for stop signal
+        // Wait for stop signal. The sessions are stopped by stop().
         stop_sub.receive().await;
 
-        // Stop the sessions
-        manual.stop().await;
-        inbound.stop().await;
-        outbound.stop().await;
-
         debug!(target: "net", "P2p::run() [END]");
         Ok(())
     }
@@ -192,7 +313,7 @@ impl P2p {
         {
             debug!(target: "net", "P2p::wait_for_outbound(): seeds are configured, waiting for outbound initialization...");
 
-            let self_inbound_addr = self.settings().external_addr.clone();
+            let self_inbound_addr = self.external_addr().await;
             let addrs = self.hosts().load_all().await;
 
             // Enable outbound channel subscriber notifications
@@ -225,8 +346,49 @@ impl P2p {
         Ok(())
     }
 
+    /// Gracefully shut down the network. Stops accepting and making new
+    /// connections, tells connected peers we are leaving while giving
+    /// pending sends `shutdown_timeout_seconds` to drain, persists the host
+    /// list and finally notifies the stop subscribers. Resolves once the
+    /// shutdown is complete, so daemons can await it before exiting.
     pub async fn stop(&self) {
-        self.stop_subscriber.notify(()).await
+        {
+            let mut state = self.state.lock().await;
+            if let P2pState::Stop = *state {
+                return
+            }
+            *state = P2pState::Stop;
+        }
+        info!(target: "net", "Shutting down P2P network");
+
+        // Stop accepting inbound connections first, then stop dialing
+        self.session_inbound().await.stop().await;
+        self.session_outbound().await.stop().await;
+        self.session_manual().await.stop().await;
+
+        let channels: Vec<ChannelPtr> = self.channels.lock().await.values().cloned().collect();
+        let disconnects = channels.iter().map(|channel| channel.disconnect("shutting down"));
+        let drain_timeout = Duration::from_secs(self.settings.shutdown_timeout_seconds.into());
+        if timeout(drain_timeout, join_all(disconnects)).await.is_err() {
+            warn!(target: "net", "Timed out draining channels, closing them anyway");
+        }
+        for channel in &channels {
+            channel.stop().await;
+        }
+
+        if let Some(path) = &self.settings.hosts_path {
+            match expand_path(path) {
+                Ok(path) => {
+                    if let Err(e) = self.hosts.save_file(&path).await {
+                        error!(target: "net", "Failed saving hosts to {:?}: {}", path, e);
+                    }
+                }
+                Err(e) => error!(target: "net", "Invalid hosts path {}: {}", path, e),
+            }
+        }
+
+        self.stop_subscriber.notify(()).await;
+        info!(target: "net", "P2P network shut down");
     }
 
     /// Broadcasts a message across all channels.
@@ -294,6 +456,34 @@ impl P2p {
         self.hosts.clone()
     }
 
+    /// Return the byte counters of the whole p2p network.
+    pub fn traffic(&self) -> &ByteCounter {
+        &self.traffic
+    }
+
+    /// Return the global upload rate limiter.
+    pub fn upload_limiter(&self) -> RateLimiterPtr {
+        self.upload_limiter.clone()
+    }
+
+    /// Return our external addresses, both configured and discovered.
+    pub async fn external_addr(&self) -> Vec<Url> {
+        self.external_addr.lock().await.clone()
+    }
+
+    /// Add a discovered external address to be advertised to peers.
+    pub async fn add_external_addr(&self, addr: Url) {
+        let mut external_addr = self.external_addr.lock().await;
+        if !external_addr.contains(&addr) {
+            external_addr.push(addr);
+        }
+    }
+
+    /// Return an atomic pointer to the ban manager.
+    pub fn ban_manager(&self) -> BanManagerPtr {
+        self.ban_manager.clone()
+    }
+
     pub fn protocol_registry(&self) -> &ProtocolRegistry {
         &self.protocol_registry
     }
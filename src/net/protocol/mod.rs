@@ -9,6 +9,15 @@
 /// address information to their local store.
 pub mod protocol_address;
 
+/// Generic gossip protocol. Applications register their own message type and
+/// get flooding over the whole network for free: messages are deduplicated by
+/// the hash of their payload, carry a TTL limiting how many hops they travel,
+/// and are relayed to every peer except the one they came from.
+///
+/// The wrapped message is sent under its own name, so a message type should
+/// either be gossiped or dispatched directly, not both.
+pub mod protocol_gossip;
+
 /// Manages the tasks for the network protocol. Used by other connection
 /// protocols to handle asynchronous task execution across the network. Runs all
 /// tasks that are handed to it on an executor that has stopping functionality.
@@ -50,6 +59,7 @@ pub mod protocol_base;
 pub mod protocol_registry;
 
 pub use protocol_address::ProtocolAddress;
+pub use protocol_gossip::{Gossip, GossipMessage, GossipPtr, ProtocolGossip};
 pub use protocol_jobs_manager::{ProtocolJobsManager, ProtocolJobsManagerPtr};
 pub use protocol_ping::ProtocolPing;
 pub use protocol_seed::ProtocolSeed;
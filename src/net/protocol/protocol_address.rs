@@ -1,15 +1,15 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use async_trait::async_trait;
 use log::debug;
 use smol::Executor;
 
-use crate::{util::async_util, Result};
+use crate::{util::async_util, Error, Result};
 
 use super::{
     super::{
-        message, message_subscriber::MessageSubscription, ChannelPtr, HostsPtr, P2pPtr,
-        SettingsPtr, SESSION_OUTBOUND,
+        message, message_subscriber::MessageSubscription, ChannelPtr, HostsPtr, P2p, P2pPtr,
+        SESSION_OUTBOUND,
     },
     ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr,
 };
@@ -23,14 +23,14 @@ pub struct ProtocolAddress {
     get_addrs_sub: MessageSubscription<message::GetAddrsMessage>,
     hosts: HostsPtr,
     jobsman: ProtocolJobsManagerPtr,
-    settings: SettingsPtr,
+    /// Weak, as `p2p` holds the channel this protocol runs on
+    p2p: Weak<P2p>,
 }
 
 impl ProtocolAddress {
     /// Create a new address protocol. Makes an address and get-address
     /// subscription and adds them to the address protocol instance.
     pub async fn init(channel: ChannelPtr, p2p: P2pPtr) -> ProtocolBasePtr {
-        let settings = p2p.settings();
         let hosts = p2p.hosts();
 
         // Creates a subscription to address message.
@@ -53,10 +53,14 @@ impl ProtocolAddress {
             get_addrs_sub,
             hosts,
             jobsman: ProtocolJobsManager::new("ProtocolAddress", channel),
-            settings,
+            p2p: Arc::downgrade(&p2p),
         })
     }
 
+    fn p2p(&self) -> Result<P2pPtr> {
+        self.p2p.upgrade().ok_or(Error::NetworkNotConnected)
+    }
+
     /// Handles receiving the address message. Loops to continually recieve
     /// address messages on the address subsciption. Adds the recieved
     /// addresses to the list of hosts.
@@ -104,7 +108,7 @@ impl ProtocolAddress {
     async fn send_my_addrs(self: Arc<Self>) -> Result<()> {
         debug!(target: "net", "ProtocolAddress::send_addrs() [START]");
         loop {
-            let addrs = self.settings.external_addr.clone();
+            let addrs = self.p2p()?.external_addr().await;
             let addr_msg = message::AddrsMessage { addrs };
             self.channel.clone().send(addr_msg).await?;
             async_util::sleep(SEND_ADDR_SLEEP_SECONDS).await;
@@ -122,7 +126,7 @@ impl ProtocolBase for ProtocolAddress {
 
         // if it's an outbound session + has an external address
         // send our address
-        if type_id == SESSION_OUTBOUND && !self.settings.external_addr.is_empty() {
+        if type_id == SESSION_OUTBOUND && !self.p2p()?.external_addr().await.is_empty() {
             self.jobsman.clone().start(executor.clone());
             self.jobsman.clone().spawn(self.clone().send_my_addrs(), executor.clone()).await;
         }
@@ -0,0 +1,251 @@
+use async_std::sync::{Arc, Mutex, Weak};
+use std::{collections::VecDeque, io};
+
+use async_trait::async_trait;
+use fxhash::FxHashSet;
+use log::debug;
+use smol::Executor;
+use url::Url;
+
+use crate::{
+    system::{Subscriber, SubscriberPtr, Subscription},
+    util::serial::{serialize, BytesReader, Decodable, Encodable},
+    Error, Result,
+};
+
+use super::{
+    super::{
+        message::Message, message_subscriber::MessageSubscription, send_queue::MessagePriority,
+        session::SessionBitflag, ChannelPtr, P2p, P2pPtr,
+    },
+    ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr,
+};
+
+/// Number of message hashes remembered for deduplication.
+const SEEN_CAPACITY: usize = 8192;
+
+/// Envelope carrying an application message through the gossip network.
+/// It goes on the wire under the name of the wrapped message.
+#[derive(Clone)]
+pub struct GossipMessage<M: Message + Clone> {
+    /// Remaining number of hops this message may travel
+    pub ttl: u8,
+    pub payload: M,
+}
+
+impl<M: Message + Clone> Message for GossipMessage<M> {
+    fn name() -> &'static str {
+        M::name()
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Low
+    }
+
+    fn version() -> u8 {
+        M::version()
+    }
+
+    fn encode_versioned<S: io::Write>(&self, version: u8, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.ttl.encode(&mut s)?;
+        len += self.payload.encode_versioned(version, &mut s)?;
+        Ok(len)
+    }
+
+    fn decode_versioned(version: u8, reader: &mut BytesReader) -> Result<Self> {
+        Ok(Self {
+            ttl: Decodable::decode(&mut *reader)?,
+            payload: M::decode_versioned(version, reader)?,
+        })
+    }
+}
+
+impl<M: Message + Clone> Encodable for GossipMessage<M> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.ttl.encode(&mut s)?;
+        len += self.payload.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl<M: Message + Clone> Decodable for GossipMessage<M> {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self { ttl: Decodable::decode(&mut d)?, payload: Decodable::decode(&mut d)? })
+    }
+}
+
+/// Bounded set of message hashes, forgetting the oldest ones first.
+struct SeenMessages {
+    hashes: FxHashSet<blake3::Hash>,
+    order: VecDeque<blake3::Hash>,
+}
+
+impl SeenMessages {
+    fn new() -> Self {
+        Self { hashes: FxHashSet::default(), order: VecDeque::with_capacity(SEEN_CAPACITY) }
+    }
+
+    /// Insert a hash. Returns `false` if it was already present.
+    fn insert(&mut self, hash: blake3::Hash) -> bool {
+        if !self.hashes.insert(hash) {
+            return false
+        }
+
+        if self.order.len() == SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash);
+        true
+    }
+}
+
+pub type GossipPtr<M> = Arc<Gossip<M>>;
+
+/// Gossip state shared by all channels for one message type. Messages are
+/// deduplicated by the hash of their payload, delivered once to subscribers
+/// and relayed to every other peer until their TTL runs out.
+pub struct Gossip<M: Message + Clone> {
+    /// Weak, as the protocol registry of `p2p` holds this gossip
+    p2p: Weak<P2p>,
+    seen: Mutex<SeenMessages>,
+    max_ttl: u8,
+    subscriber: SubscriberPtr<M>,
+}
+
+impl<M: Message + Clone> Gossip<M> {
+    /// Register gossip of `M` on the sessions selected by `session_flags`.
+    /// Messages broadcast from this node travel at most `max_ttl` hops.
+    pub async fn register(p2p: P2pPtr, session_flags: SessionBitflag, max_ttl: u8) -> Arc<Self> {
+        let gossip = Arc::new(Self {
+            p2p: Arc::downgrade(&p2p),
+            seen: Mutex::new(SeenMessages::new()),
+            max_ttl,
+            subscriber: Subscriber::new(),
+        });
+
+        let gossip_ = gossip.clone();
+        p2p.protocol_registry()
+            .register(session_flags, move |channel, _p2p| {
+                let gossip = gossip_.clone();
+                async move { ProtocolGossip::init(channel, gossip).await }
+            })
+            .await;
+
+        gossip
+    }
+
+    fn p2p(&self) -> Result<P2pPtr> {
+        self.p2p.upgrade().ok_or(Error::NetworkNotConnected)
+    }
+
+    /// Subscribe to messages received from the network. Each message is
+    /// delivered once, no matter how many peers relay it.
+    pub async fn subscribe(&self) -> Subscription<M> {
+        self.subscriber.clone().subscribe().await
+    }
+
+    /// Broadcast a message originating from this node.
+    pub async fn broadcast(&self, payload: M) -> Result<()> {
+        self.seen.lock().await.insert(blake3::hash(&serialize(&payload)));
+        self.p2p()?.broadcast(GossipMessage { ttl: self.max_ttl, payload }).await
+    }
+
+    /// Handle a message received from `from`. New messages are delivered to
+    /// subscribers and relayed to every peer except the sender.
+    async fn handle(&self, msg: GossipMessage<M>, from: Url) -> Result<()> {
+        let hash = blake3::hash(&serialize(&msg.payload));
+        if !self.seen.lock().await.insert(hash) {
+            debug!(target: "net", "Gossip: dropping already seen {} {}", M::name(), hash.to_hex());
+            return Ok(())
+        }
+
+        self.subscriber.notify(msg.payload.clone()).await;
+
+        // Don't let peers extend the reach of a message beyond our own limit
+        let ttl = msg.ttl.min(self.max_ttl);
+        if ttl <= 1 {
+            debug!(target: "net", "Gossip: TTL of {} {} expired", M::name(), hash.to_hex());
+            return Ok(())
+        }
+
+        let relay = GossipMessage { ttl: ttl - 1, payload: msg.payload };
+        self.p2p()?.broadcast_with_exclude(relay, &[from]).await
+    }
+}
+
+/// Per-channel protocol feeding received gossip messages into the shared
+/// [`Gossip`] state.
+pub struct ProtocolGossip<M: Message + Clone> {
+    channel: ChannelPtr,
+    msg_sub: MessageSubscription<GossipMessage<M>>,
+    gossip: GossipPtr<M>,
+    jobsman: ProtocolJobsManagerPtr,
+}
+
+impl<M: Message + Clone> ProtocolGossip<M> {
+    pub async fn init(channel: ChannelPtr, gossip: GossipPtr<M>) -> ProtocolBasePtr {
+        let message_subsytem = channel.get_message_subsystem();
+        message_subsytem.add_dispatch::<GossipMessage<M>>().await;
+
+        let msg_sub = channel
+            .clone()
+            .subscribe_msg::<GossipMessage<M>>()
+            .await
+            .expect("Missing gossip dispatcher!");
+
+        Arc::new(Self {
+            channel: channel.clone(),
+            msg_sub,
+            gossip,
+            jobsman: ProtocolJobsManager::new("ProtocolGossip", channel),
+        })
+    }
+
+    async fn handle_receive_msg(self: Arc<Self>) -> Result<()> {
+        debug!(target: "net", "ProtocolGossip::handle_receive_msg() [START]");
+        loop {
+            let msg = self.msg_sub.receive().await?;
+            self.gossip.handle((*msg).clone(), self.channel.address()).await?;
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Message + Clone> ProtocolBase for ProtocolGossip<M> {
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!(target: "net", "ProtocolGossip::start() [START]");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman.clone().spawn(self.clone().handle_receive_msg(), executor.clone()).await;
+        debug!(target: "net", "ProtocolGossip::start() [END]");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolGossip"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_messages_capacity() {
+        let mut seen = SeenMessages::new();
+        let first = blake3::hash(&0usize.to_le_bytes());
+        assert!(seen.insert(first));
+        assert!(!seen.insert(first));
+
+        for i in 1..=SEEN_CAPACITY {
+            assert!(seen.insert(blake3::hash(&i.to_le_bytes())));
+        }
+
+        // The oldest hash was forgotten to make room
+        assert_eq!(seen.order.len(), SEEN_CAPACITY);
+        assert!(seen.insert(first));
+    }
+}
@@ -77,9 +77,10 @@ impl ProtocolPing {
                 self.channel.stop().await;
                 return Err(Error::ChannelStopped)
             }
-            let duration = start.elapsed().as_millis();
+            let rtt = start.elapsed();
+            self.channel.record_latency(rtt).await;
             debug!(target: "net", "Received Pong message {}ms from [{:?}]",
-                   duration, self.channel.address());
+                   rtt.as_millis(), self.channel.address());
         }
     }
 
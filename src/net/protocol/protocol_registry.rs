@@ -14,6 +14,8 @@ type Constructor =
 
 pub struct ProtocolRegistry {
     protocol_constructors: Mutex<Vec<(SessionBitflag, Constructor)>>,
+    // Protocol names, in registration order, learned on their first attach
+    protocol_names: Mutex<Vec<Option<&'static str>>>,
 }
 
 impl Default for ProtocolRegistry {
@@ -24,7 +26,10 @@ impl Default for ProtocolRegistry {
 
 impl ProtocolRegistry {
     pub fn new() -> Self {
-        Self { protocol_constructors: Mutex::new(Vec::new()) }
+        Self {
+            protocol_constructors: Mutex::new(Vec::new()),
+            protocol_names: Mutex::new(Vec::new()),
+        }
     }
 
     // add_protocol()?
@@ -37,6 +42,19 @@ impl ProtocolRegistry {
             Box::pin(constructor(channel, p2p)) as BoxFuture<'static, ProtocolBasePtr>
         };
         self.protocol_constructors.lock().await.push((session_flags, Box::new(constructor)));
+        self.protocol_names.lock().await.push(None);
+    }
+
+    /// Return the registered protocols along with the sessions they are
+    /// attached to. Names are only known once a protocol got attached.
+    pub async fn protocols(&self) -> Vec<(Option<&'static str>, SessionBitflag)> {
+        let constructors = self.protocol_constructors.lock().await;
+        let names = self.protocol_names.lock().await;
+        constructors
+            .iter()
+            .zip(names.iter())
+            .map(|((session_flags, _), name)| (*name, *session_flags))
+            .collect()
     }
 
     pub async fn attach(
@@ -46,7 +64,9 @@ impl ProtocolRegistry {
         p2p: P2pPtr,
     ) -> Vec<ProtocolBasePtr> {
         let mut protocols: Vec<ProtocolBasePtr> = Vec::new();
-        for (session_flags, construct) in self.protocol_constructors.lock().await.iter() {
+        for (index, (session_flags, construct)) in
+            self.protocol_constructors.lock().await.iter().enumerate()
+        {
             // Skip protocols that are not registered for this session
             if selector_id & session_flags == 0 {
                 debug!("Skipping {selector_id:#b}, {session_flags:#b}");
@@ -55,6 +75,7 @@ impl ProtocolRegistry {
 
             let protocol: ProtocolBasePtr = construct(channel.clone(), p2p.clone()).await;
             debug!(target: "net", "Attached {}", protocol.name());
+            self.protocol_names.lock().await[index] = Some(protocol.name());
 
             protocols.push(protocol)
         }
@@ -1,15 +1,13 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use async_trait::async_trait;
 use log::debug;
 use smol::Executor;
 
-use crate::Result;
+use crate::{Error, Result};
 
 use super::{
-    super::{
-        message, message_subscriber::MessageSubscription, ChannelPtr, HostsPtr, P2pPtr, SettingsPtr,
-    },
+    super::{message, message_subscriber::MessageSubscription, ChannelPtr, HostsPtr, P2p, P2pPtr},
     ProtocolBase, ProtocolBasePtr,
 };
 
@@ -17,7 +15,8 @@ use super::{
 pub struct ProtocolSeed {
     channel: ChannelPtr,
     hosts: HostsPtr,
-    settings: SettingsPtr,
+    /// Weak, as `p2p` holds the channel this protocol runs on
+    p2p: Weak<P2p>,
     addr_sub: MessageSubscription<message::AddrsMessage>,
 }
 
@@ -25,7 +24,6 @@ impl ProtocolSeed {
     /// Create a new seed protocol.
     pub async fn init(channel: ChannelPtr, p2p: P2pPtr) -> ProtocolBasePtr {
         let hosts = p2p.hosts();
-        let settings = p2p.settings();
 
         //// Create a subscription to address message.
         let addr_sub = channel
@@ -34,7 +32,11 @@ impl ProtocolSeed {
             .await
             .expect("Missing addr dispatcher!");
 
-        Arc::new(Self { channel, hosts, settings, addr_sub })
+        Arc::new(Self { channel, hosts, p2p: Arc::downgrade(&p2p), addr_sub })
+    }
+
+    fn p2p(&self) -> Result<P2pPtr> {
+        self.p2p.upgrade().ok_or(Error::NetworkNotConnected)
     }
 
     /// Sends own external addresses over a channel. Imports own external addresses
@@ -42,11 +44,11 @@ impl ProtocolSeed {
     /// sends it out over the channel.
     pub async fn send_self_address(&self) -> Result<()> {
         // Do nothing if external addresses are not configured
-        if self.settings.external_addr.is_empty() {
+        let addrs = self.p2p()?.external_addr().await;
+        if addrs.is_empty() {
             return Ok(())
         }
 
-        let addrs = self.settings.external_addr.clone();
         debug!(target: "net", "ProtocolSeed::send_own_address() addrs={:?}", addrs);
         let addrs = message::AddrsMessage { addrs };
         self.channel.clone().send(addrs).await
@@ -75,7 +75,12 @@ impl ProtocolVersion {
     /// Send version info and wait for version acknowledgement.
     async fn send_version(self: Arc<Self>) -> Result<()> {
         debug!(target: "net", "ProtocolVersion::send_version() [START]");
-        let version = message::VersionMessage { node_id: self.settings.node_id.clone() };
+        let version = message::VersionMessage {
+            node_id: self.settings.node_id.clone(),
+            encrypted: self.channel.is_encrypted(),
+            compression: self.settings.compression,
+            features: self.channel.get_message_subsystem().versions().await,
+        };
         self.channel.clone().send(version).await?;
 
         // Wait for version acknowledgement
@@ -93,6 +98,26 @@ impl ProtocolVersion {
         self.channel.set_remote_node_id(version.node_id.clone()).await;
 
         // Check the message is OK
+        if version.encrypted != self.channel.is_encrypted() {
+            error!(
+                target: "net",
+                "Encryption mismatch with {}: local={}, remote={}",
+                self.channel.address(),
+                self.channel.is_encrypted(),
+                version.encrypted,
+            );
+            return Err(Error::ChannelEncryptionMismatch)
+        }
+
+        if self.settings.transport_encryption && !self.channel.is_encrypted() {
+            warn!(target: "net", "Channel {} is not encrypted", self.channel.address());
+        }
+
+        // Messages are sent in the highest format version both sides speak
+        self.channel.set_remote_versions(version.features.clone()).await;
+
+        // Only compress outgoing payloads if both sides agree on it
+        self.channel.set_compression(self.settings.compression && version.compression);
 
         // Send version acknowledgement
         let verack = message::VerackMessage {};
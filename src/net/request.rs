@@ -0,0 +1,236 @@
+use async_std::{
+    future::timeout,
+    sync::{Arc, Mutex},
+};
+use std::{
+    future::Future,
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use fxhash::FxHashMap;
+use log::{debug, error};
+use smol::Executor;
+
+use crate::{
+    util::serial::{BytesReader, Decodable, Encodable},
+    Error, Result,
+};
+
+use super::{message::Message, send_queue::MessagePriority, ChannelPtr};
+
+/// A message expecting a typed response from the remote peer.
+pub trait Request: Message + Clone {
+    type Response: Message + Clone;
+}
+
+/// Request tagged with an id the response is correlated with.
+/// It goes on the wire under the name of the wrapped request.
+pub struct RequestMessage<R: Request> {
+    pub id: u64,
+    pub request: R,
+}
+
+/// Response carrying the id of the request it answers.
+/// It goes on the wire under the name of the wrapped response.
+pub struct ResponseMessage<R: Request> {
+    pub id: u64,
+    pub response: R::Response,
+}
+
+impl<R: Request> Message for RequestMessage<R> {
+    fn name() -> &'static str {
+        R::name()
+    }
+
+    fn priority() -> MessagePriority {
+        R::priority()
+    }
+
+    fn version() -> u8 {
+        R::version()
+    }
+
+    fn encode_versioned<S: io::Write>(&self, version: u8, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.id.encode(&mut s)?;
+        len += self.request.encode_versioned(version, &mut s)?;
+        Ok(len)
+    }
+
+    fn decode_versioned(version: u8, reader: &mut BytesReader) -> Result<Self> {
+        Ok(Self {
+            id: Decodable::decode(&mut *reader)?,
+            request: R::decode_versioned(version, reader)?,
+        })
+    }
+}
+
+impl<R: Request> Message for ResponseMessage<R> {
+    fn name() -> &'static str {
+        R::Response::name()
+    }
+
+    fn priority() -> MessagePriority {
+        R::Response::priority()
+    }
+
+    fn version() -> u8 {
+        R::Response::version()
+    }
+
+    fn encode_versioned<S: io::Write>(&self, version: u8, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.id.encode(&mut s)?;
+        len += self.response.encode_versioned(version, &mut s)?;
+        Ok(len)
+    }
+
+    fn decode_versioned(version: u8, reader: &mut BytesReader) -> Result<Self> {
+        Ok(Self {
+            id: Decodable::decode(&mut *reader)?,
+            response: R::Response::decode_versioned(version, reader)?,
+        })
+    }
+}
+
+impl<R: Request> Encodable for RequestMessage<R> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.id.encode(&mut s)?;
+        len += self.request.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl<R: Request> Decodable for RequestMessage<R> {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self { id: Decodable::decode(&mut d)?, request: Decodable::decode(&mut d)? })
+    }
+}
+
+impl<R: Request> Encodable for ResponseMessage<R> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.id.encode(&mut s)?;
+        len += self.response.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl<R: Request> Decodable for ResponseMessage<R> {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self { id: Decodable::decode(&mut d)?, response: Decodable::decode(&mut d)? })
+    }
+}
+
+pub type RequesterPtr<R> = Arc<Requester<R>>;
+
+/// Sends requests of type `R` over a channel and matches the responses to
+/// their callers. Any number of requests can be in flight concurrently.
+pub struct Requester<R: Request> {
+    channel: ChannelPtr,
+    /// Callers waiting for a response, by request id
+    pending: Mutex<FxHashMap<u64, async_channel::Sender<R::Response>>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl<R: Request> Requester<R> {
+    /// Create a requester on the given channel. Requests fail with
+    /// [`Error::RequestTimeout`] if no response arrives within `timeout`.
+    ///
+    /// This adds the dispatcher for the response type, so it should only be
+    /// called once per channel and request type.
+    pub async fn new(
+        channel: ChannelPtr,
+        timeout: Duration,
+        executor: Arc<Executor<'_>>,
+    ) -> Result<Arc<Self>> {
+        channel.get_message_subsystem().add_dispatch::<ResponseMessage<R>>().await;
+        let response_sub = channel.subscribe_msg::<ResponseMessage<R>>().await?;
+
+        let self_ = Arc::new(Self {
+            channel,
+            pending: Mutex::new(FxHashMap::default()),
+            next_id: AtomicU64::new(0),
+            timeout,
+        });
+
+        let requester = self_.clone();
+        executor
+            .spawn(async move {
+                loop {
+                    let msg = match response_sub.receive().await {
+                        Ok(msg) => msg,
+                        Err(_) => break,
+                    };
+
+                    match requester.pending.lock().await.remove(&msg.id) {
+                        Some(sender) => {
+                            let _ = sender.send(msg.response.clone()).await;
+                        }
+                        None => {
+                            debug!(target: "net", "Dropping response {} to unknown request {}",
+                                   R::Response::name(), msg.id);
+                        }
+                    }
+                }
+
+                // Channel stopped: wake up everyone still waiting
+                requester.pending.lock().await.clear();
+                response_sub.unsubscribe().await;
+            })
+            .detach();
+
+        Ok(self_)
+    }
+
+    /// Send a request and wait for its response.
+    pub async fn request(&self, request: R) -> Result<R::Response> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = async_channel::bounded(1);
+        self.pending.lock().await.insert(id, sender);
+
+        if let Err(e) = self.channel.send(RequestMessage { id, request }).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e)
+        }
+
+        let result = timeout(self.timeout, receiver.recv()).await;
+        self.pending.lock().await.remove(&id);
+
+        match result {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(Error::ChannelStopped),
+            Err(_) => Err(Error::RequestTimeout),
+        }
+    }
+}
+
+/// Answer requests of type `R` arriving on the given channel with `handler`,
+/// until the channel stops. Requests the handler fails on are logged and left
+/// unanswered, so the remote side times out.
+///
+/// This adds the dispatcher for the request type, so it should only be called
+/// once per channel and request type.
+pub async fn serve_requests<R, F, Fut>(channel: ChannelPtr, handler: F) -> Result<()>
+where
+    R: Request,
+    F: Fn(R) -> Fut,
+    Fut: Future<Output = Result<R::Response>>,
+{
+    channel.get_message_subsystem().add_dispatch::<RequestMessage<R>>().await;
+    let request_sub = channel.subscribe_msg::<RequestMessage<R>>().await?;
+
+    loop {
+        let msg = request_sub.receive().await?;
+        match handler(msg.request.clone()).await {
+            Ok(response) => channel.send(ResponseMessage::<R> { id: msg.id, response }).await?,
+            Err(e) => {
+                error!(target: "net", "Failed handling request {} {}: {}", R::name(), msg.id, e)
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use async_std::future::timeout;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::debug;
+use serde_json::json;
+
+use crate::{Error, Result};
+
+use super::message::Packet;
+
+/// Priority class of a message in the channel send queue. Higher classes are
+/// always written to the socket first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessagePriority {
+    /// Consensus votes and connection control messages
+    High = 0,
+    /// Blocks and other application messages
+    Normal = 1,
+    /// Gossip and address relay. Dropped when the queue is full.
+    Low = 2,
+}
+
+const PRIORITIES: [MessagePriority; 3] =
+    [MessagePriority::High, MessagePriority::Normal, MessagePriority::Low];
+
+impl MessagePriority {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+}
+
+/// Packet waiting to be written, with an optional notification for when it
+/// is out.
+pub struct QueuedPacket {
+    pub packet: Packet,
+    pub done: Option<async_channel::Sender<()>>,
+}
+
+/// Bounded per-channel send queue with one lane per priority class.
+///
+/// When a lane is full, low priority packets are dropped right away, while
+/// other packets wait for room up to the send timeout. A peer that can't keep
+/// up for that long makes the push fail, so broadcasters never stall behind it.
+pub struct SendQueue {
+    lanes: Vec<(async_channel::Sender<QueuedPacket>, async_channel::Receiver<QueuedPacket>)>,
+    /// One token per queued packet, so the writer knows when to look
+    ready: (async_channel::Sender<()>, async_channel::Receiver<()>),
+    dropped: [AtomicU64; 3],
+    send_timeout: Duration,
+}
+
+impl SendQueue {
+    /// Create a queue holding up to `capacity` packets per priority class.
+    pub fn new(capacity: usize, send_timeout: Duration) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            lanes: PRIORITIES.iter().map(|_| async_channel::bounded(capacity)).collect(),
+            ready: async_channel::unbounded(),
+            dropped: Default::default(),
+            send_timeout,
+        }
+    }
+
+    /// Queue a packet. Returns [`Error::ChannelTimeout`] if a high or normal
+    /// priority packet could not be queued within the send timeout.
+    pub async fn push(&self, item: QueuedPacket, priority: MessagePriority) -> Result<()> {
+        let lane = &self.lanes[priority as usize].0;
+
+        if priority == MessagePriority::Low {
+            if lane.try_send(item).is_err() {
+                debug!(target: "net", "Send queue full, dropping low priority packet");
+                self.dropped[priority as usize].fetch_add(1, Ordering::Relaxed);
+                return Ok(())
+            }
+        } else {
+            match timeout(self.send_timeout, lane.send(item)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) => return Err(Error::ChannelStopped),
+                Err(_) => {
+                    self.dropped[priority as usize].fetch_add(1, Ordering::Relaxed);
+                    return Err(Error::ChannelTimeout)
+                }
+            }
+        }
+
+        self.ready.0.send(()).await.map_err(|_| Error::ChannelStopped)
+    }
+
+    /// Wait for the next packet to write, highest priority first.
+    pub async fn pop(&self) -> Result<QueuedPacket> {
+        self.ready.1.recv().await.map_err(|_| Error::ChannelStopped)?;
+
+        for (_, lane) in &self.lanes {
+            if let Ok(item) = lane.try_recv() {
+                return Ok(item)
+            }
+        }
+
+        // Every token matches a queued packet, so this is unreachable
+        // unless the queue got closed meanwhile.
+        Err(Error::ChannelStopped)
+    }
+
+    /// Close the queue, failing pending and future pushes.
+    pub fn close(&self) {
+        for (sender, _) in &self.lanes {
+            sender.close();
+        }
+        self.ready.0.close();
+    }
+
+    /// Number of packets waiting in the given priority class.
+    pub fn depth(&self, priority: MessagePriority) -> usize {
+        self.lanes[priority as usize].1.len()
+    }
+
+    pub fn get_info(&self) -> serde_json::Value {
+        let mut info = serde_json::Map::new();
+        for priority in PRIORITIES {
+            info.insert(
+                priority.name().to_string(),
+                json!({
+                    "depth": self.depth(priority),
+                    "dropped": self.dropped[priority as usize].load(Ordering::Relaxed),
+                }),
+            );
+        }
+        serde_json::Value::Object(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(command: &str) -> QueuedPacket {
+        QueuedPacket { packet: Packet::new(command, vec![]), done: None }
+    }
+
+    #[test]
+    fn test_send_queue_priorities() {
+        smol::block_on(async {
+            let queue = SendQueue::new(1, Duration::from_millis(10));
+
+            queue.push(item("gossip"), MessagePriority::Low).await.unwrap();
+            queue.push(item("block"), MessagePriority::Normal).await.unwrap();
+            queue.push(item("vote"), MessagePriority::High).await.unwrap();
+
+            // Full lanes drop low priority packets and time out the others
+            queue.push(item("gossip2"), MessagePriority::Low).await.unwrap();
+            assert!(queue.push(item("vote2"), MessagePriority::High).await.is_err());
+
+            assert_eq!(queue.pop().await.unwrap().packet.command, "vote");
+            assert_eq!(queue.pop().await.unwrap().packet.command, "block");
+            assert_eq!(queue.pop().await.unwrap().packet.command, "gossip");
+            assert_eq!(queue.get_info()["low"]["dropped"], 1);
+        });
+    }
+}
@@ -3,9 +3,10 @@ use async_std::sync::{Arc, Mutex, Weak};
 use async_executor::Executor;
 use async_trait::async_trait;
 use fxhash::FxHashMap;
-use log::{error, info};
+use log::{error, info, warn};
+use rand::seq::SliceRandom;
 use serde_json::json;
-use url::Url;
+use url::{Host, Url};
 
 use crate::{
     system::{StoppableTask, StoppableTaskPtr},
@@ -13,7 +14,7 @@ use crate::{
 };
 
 use super::{
-    super::{Acceptor, AcceptorPtr, ChannelPtr, P2p},
+    super::{dnet, Acceptor, AcceptorPtr, ChannelPtr, P2p},
     Session, SessionBitflag, SESSION_INBOUND,
 };
 
@@ -105,7 +106,8 @@ impl InboundSession {
         *acceptor.session.lock().await = Some(Arc::new(parent));
 
         // Start listener
-        let result = acceptor.clone().start(accept_addr, executor).await;
+        let transport_encryption = self.p2p().settings().transport_encryption;
+        let result = acceptor.clone().start(accept_addr, transport_encryption, executor).await;
         if let Err(err) = result.clone() {
             error!(target: "net", "#{} error starting listener: {}", index, err);
         }
@@ -140,6 +142,17 @@ impl InboundSession {
         channel: ChannelPtr,
         executor: Arc<Executor<'_>>,
     ) -> Result<()> {
+        if self.p2p().ban_manager().is_banned(&channel.address()).await {
+            warn!(target: "net", "#{} refusing banned peer [{}]", index, channel.address());
+            channel.stop().await;
+            return Ok(())
+        }
+
+        let limit = self.p2p().settings().inbound_connections as usize;
+        if limit > 0 && self.connections_count().await >= limit {
+            self.evict_channel().await;
+        }
+
         info!(target: "net", "#{} connected inbound [{}]", index, channel.address());
 
         self.clone().register_channel(channel.clone(), executor.clone()).await?;
@@ -149,6 +162,49 @@ impl InboundSession {
         Ok(())
     }
 
+    /// Return the number of currently connected inbound channels.
+    async fn connections_count(&self) -> usize {
+        self.connect_infos.lock().await.iter().map(|infos| infos.len()).sum()
+    }
+
+    /// Make room for a new inbound connection. Drops a peer with the highest
+    /// misbehavior score. Among peers with equal scores, one from the network
+    /// group holding the most inbound slots is picked at random, so an
+    /// attacker can't take over the slots by connecting from a single network.
+    async fn evict_channel(&self) {
+        let ban_manager = self.p2p().ban_manager();
+
+        let mut peers = vec![];
+        let mut groups: FxHashMap<String, usize> = FxHashMap::default();
+        for infos in self.connect_infos.lock().await.iter() {
+            for info in infos.values() {
+                let address = info.channel.address();
+                let score = ban_manager.score(&address).await;
+                let group = netgroup(&address);
+                *groups.entry(group.clone()).or_default() += 1;
+                peers.push((score, group, info.channel.clone()));
+            }
+        }
+
+        let rank = |(score, group, _): &(u32, String, ChannelPtr)| (*score, groups[group]);
+        let worst = match peers.iter().map(rank).max() {
+            Some(v) => v,
+            None => return,
+        };
+        peers.retain(|peer| rank(peer) == worst);
+
+        if let Some((score, _, channel)) = peers.choose(&mut rand::thread_rng()) {
+            info!(
+                target: "net",
+                "Inbound slots full, evicting [{}] (score={}, uptime={}s)",
+                channel.address(),
+                score,
+                channel.uptime().as_secs()
+            );
+            channel.stop().await;
+        }
+    }
+
     async fn manage_channel_for_get_info(&self, index: usize, channel: ChannelPtr) {
         let key = channel.address();
         self.connect_infos.lock().await[index]
@@ -162,6 +218,21 @@ impl InboundSession {
 
         self.connect_infos.lock().await[index].remove(&key);
     }
+
+    /// Return the channels accepted on each inbound address.
+    pub async fn dnet_info(&self) -> Vec<dnet::InboundInfo> {
+        let connect_infos = self.connect_infos.lock().await;
+        self.p2p()
+            .settings()
+            .inbound
+            .iter()
+            .zip(connect_infos.iter())
+            .map(|(accept_addr, infos)| dnet::InboundInfo {
+                accept_addr: accept_addr.to_string(),
+                channels: infos.keys().map(|addr| addr.to_string()).collect(),
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -189,3 +260,20 @@ impl Session for InboundSession {
         SESSION_INBOUND
     }
 }
+
+/// Network group of a peer address: the /16 of IPv4 hosts, the /32 of IPv6
+/// hosts, and the host itself otherwise.
+fn netgroup(address: &Url) -> String {
+    match address.host() {
+        Some(Host::Ipv4(ip)) => {
+            let octets = ip.octets();
+            format!("{}.{}", octets[0], octets[1])
+        }
+        Some(Host::Ipv6(ip)) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}", segments[0], segments[1])
+        }
+        Some(Host::Domain(domain)) => domain.to_string(),
+        None => String::new(),
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt;
 
 use async_executor::Executor;
 use async_trait::async_trait;
-use log::{debug, info};
+use log::{debug, info, warn};
 use rand::seq::SliceRandom;
 use serde_json::json;
 use url::Url;
@@ -15,7 +15,7 @@ use crate::{
 };
 
 use super::{
-    super::{ChannelPtr, Connector, P2p},
+    super::{dnet, ChannelPtr, Connector, P2p, TransportName},
     Session, SessionBitflag, SESSION_OUTBOUND,
 };
 
@@ -100,6 +100,16 @@ impl OutboundSession {
     pub async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
         let slots_count = self.p2p().settings().outbound_connections;
         info!(target: "net", "Starting {} outbound connection slots.", slots_count);
+
+        let dedicated: u32 =
+            self.p2p().settings().outbound_transport_slots.iter().map(|a| a.slots).sum();
+        if dedicated > slots_count {
+            warn!(
+                target: "net",
+                "{} outbound slots are dedicated to transports, but only {} are configured",
+                dedicated, slots_count
+            );
+        }
         // Activate mutex lock on connection slots.
         let mut connect_slots = self.connect_slots.lock().await;
 
@@ -212,7 +222,7 @@ impl OutboundSession {
     async fn load_address(&self, slot_number: u32) -> Result<Url> {
         loop {
             let p2p = self.p2p();
-            let self_inbound_addr = p2p.settings().external_addr.clone();
+            let self_inbound_addr = p2p.external_addr().await;
 
             let mut addrs;
 
@@ -223,6 +233,23 @@ impl OutboundSession {
 
             addrs.shuffle(&mut rand::thread_rng());
 
+            // Slots dedicated to a transport only dial hosts of that transport.
+            // Other slots dial the configured transports, most preferred first.
+            // The sort is stable, so hosts stay shuffled within a transport.
+            let settings = p2p.settings();
+            let transports = match settings.slot_transport(slot_number) {
+                Some(transport) => vec![transport.to_string()],
+                None => settings.outbound_transports.clone(),
+            };
+            if !transports.is_empty() {
+                let preference = |addr: &Url| {
+                    let name = TransportName::try_from(addr.clone()).ok()?.base_name();
+                    transports.iter().position(|t| t == name)
+                };
+                addrs.retain(|addr| preference(addr).is_some());
+                addrs.sort_by_key(preference);
+            }
+
             for addr in addrs {
                 if p2p.exists(&addr).await {
                     continue
@@ -251,6 +278,19 @@ impl OutboundSession {
         self.channel_subscriber.clone().subscribe().await
     }
 
+    /// Return the state of each connection slot.
+    pub async fn dnet_info(&self) -> Vec<dnet::OutboundSlotInfo> {
+        self.slot_info
+            .lock()
+            .await
+            .iter()
+            .map(|info| dnet::OutboundSlotInfo {
+                addr: info.addr.as_ref().map(|addr| addr.to_string()),
+                state: info.state.to_string(),
+            })
+            .collect()
+    }
+
     /// Enable channel_subscriber notifications.
     pub async fn enable_notify(self: Arc<Self>) {
         *self.notify.lock().await = true;
@@ -1,5 +1,6 @@
 use async_std::{
     future::timeout,
+    net::ToSocketAddrs,
     sync::{Arc, Weak},
 };
 use futures::future;
@@ -11,13 +12,16 @@ use log::*;
 use serde_json::json;
 use url::Url;
 
-use crate::Result;
+use crate::{Error, Result};
 
 use super::{
-    super::{Connector, P2p},
+    super::{transport::socket_addr_to_url, Connector, P2p},
     Session, SessionBitflag, SESSION_SEED,
 };
 
+/// URL scheme of seeds that are resolved through DNS.
+const DNS_SEED_SCHEME: &str = "dnsseed";
+
 /// Defines seed connections session.
 pub struct SeedSyncSession {
     p2p: Weak<P2p>,
@@ -38,8 +42,9 @@ impl SeedSyncSession {
         if settings.seeds.is_empty() {
             warn!("Skipping seed sync process since no seeds are configured.");
             // Store external addresses in hosts explicitly
-            if !settings.external_addr.is_empty() {
-                self.p2p().hosts().store(settings.external_addr.clone()).await
+            let external_addr = self.p2p().external_addr().await;
+            if !external_addr.is_empty() {
+                self.p2p().hosts().store(external_addr).await
             }
 
             return Ok(())
@@ -47,12 +52,52 @@ impl SeedSyncSession {
 
         // if cached addresses then quit
 
+        // DNS seeds are resolved directly into candidate peers. Static seeds
+        // are only contacted if DNS seeding didn't yield any hosts.
+        let (dns_seeds, seeds): (Vec<Url>, Vec<Url>) =
+            settings.seeds.iter().cloned().partition(|seed| seed.scheme() == DNS_SEED_SCHEME);
+
+        if !dns_seeds.is_empty() {
+            let query_timeout = Duration::from_secs(settings.seed_query_timeout_seconds.into());
+            let mut tasks = Vec::new();
+            for seed in &dns_seeds {
+                tasks.push(async move {
+                    match timeout(query_timeout, Self::resolve_dns_seed(seed)).await {
+                        Ok(Ok(addrs)) => {
+                            info!("DNS seed [{}] resolved to {} hosts", seed, addrs.len());
+                            addrs
+                        }
+                        Ok(Err(err)) => {
+                            warn!("DNS seed [{}] failed for reason {}", seed, err);
+                            vec![]
+                        }
+                        Err(_) => {
+                            error!("DNS seed [{}] timed out", seed);
+                            vec![]
+                        }
+                    }
+                });
+            }
+
+            let addrs: Vec<Url> = future::join_all(tasks).await.into_iter().flatten().collect();
+            if !addrs.is_empty() {
+                self.p2p().hosts().store(addrs).await;
+            }
+
+            if !self.p2p().hosts().is_empty().await {
+                debug!(target: "net", "SeedSyncSession::start() [END]");
+                return Ok(())
+            }
+
+            warn!("DNS seeding yielded no hosts, falling back to static seeds");
+        }
+
         let mut tasks = Vec::new();
 
         // This loops through all the seeds and tries to start them.
         // If the seed_query_timeout_seconds times out before they are finished,
         // it will return an error.
-        for (i, seed) in settings.seeds.iter().enumerate() {
+        for (i, seed) in seeds.iter().enumerate() {
             let ex2 = executor.clone();
             let self2 = self.clone();
             let sett2 = settings.clone();
@@ -87,6 +132,27 @@ impl SeedSyncSession {
         Ok(())
     }
 
+    /// Resolve a `dnsseed://host:port` URL into the `tcp://` addresses of
+    /// the peers it points to.
+    async fn resolve_dns_seed(seed: &Url) -> Result<Vec<Url>> {
+        let host = match seed.host_str() {
+            Some(host) => host,
+            None => return Err(Error::UrlParse(format!("Missing host in DNS seed {}", seed))),
+        };
+
+        let port = match seed.port() {
+            Some(port) => port,
+            None => return Err(Error::UrlParse(format!("Missing port in DNS seed {}", seed))),
+        };
+
+        let mut addrs = vec![];
+        for addr in (host, port).to_socket_addrs().await? {
+            addrs.push(socket_addr_to_url(addr, "tcp")?);
+        }
+
+        Ok(addrs)
+    }
+
     /// Connects to a seed socket address.
     async fn start_seed(
         self: Arc<Self>,
@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use structopt::StructOpt;
 use structopt_toml::StructOptToml;
 use url::Url;
@@ -8,10 +8,77 @@ use url::Url;
 /// Atomic pointer to network settings.
 pub type SettingsPtr = Arc<Settings>;
 
+/// Number of outbound slots dedicated to a transport, written as
+/// `transport:slots` (e.g. `tor:4`).
+#[derive(Clone, Debug)]
+pub struct TransportSlots {
+    pub transport: String,
+    pub slots: u32,
+}
+
+impl FromStr for TransportSlots {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let (transport, slots) = match s.split_once(':') {
+            Some(v) => v,
+            None => return Err(crate::Error::ParseFailed("Expected transport:slots")),
+        };
+
+        Ok(Self { transport: transport.to_string(), slots: slots.parse()? })
+    }
+}
+
+impl<'de> Deserialize<'de> for TransportSlots {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How a channel handles messages no protocol is registered for. They may
+/// come from newer peers, so they are only logged by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownMessagePolicy {
+    /// Drop them silently
+    Ignore,
+    /// Drop them with a warning
+    Log,
+    /// Drop them and report the peer to the ban manager
+    Misbehave,
+}
+
+impl Default for UnknownMessagePolicy {
+    fn default() -> Self {
+        Self::Log
+    }
+}
+
+impl FromStr for UnknownMessagePolicy {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "log" => Ok(Self::Log),
+            "misbehave" => Ok(Self::Misbehave),
+            _ => Err(crate::Error::ParseFailed("Expected ignore, log or misbehave")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownMessagePolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// Default settings for the network. Can be manually configured.
 #[derive(Clone, Debug)]
 pub struct Settings {
     pub inbound: Vec<Url>,
+    pub inbound_connections: u32,
     pub outbound_connections: u32,
     pub manual_attempt_limit: u32,
     pub seed_query_timeout_seconds: u32,
@@ -22,13 +89,30 @@ pub struct Settings {
     pub external_addr: Vec<Url>,
     pub peers: Vec<Url>,
     pub seeds: Vec<Url>,
+    pub outbound_transports: Vec<String>,
+    pub outbound_transport_slots: Vec<TransportSlots>,
     pub node_id: String,
+    pub transport_encryption: bool,
+    pub peer_upload_limit: u64,
+    pub upload_limit: u64,
+    pub ban_threshold: u32,
+    pub ban_seconds: u64,
+    pub banlist_path: Option<String>,
+    pub compression: bool,
+    pub compression_threshold: u32,
+    pub nat_traversal: bool,
+    pub hosts_path: Option<String>,
+    pub shutdown_timeout_seconds: u32,
+    pub channel_queue_size: usize,
+    pub channel_send_timeout_seconds: u32,
+    pub unknown_message_policy: UnknownMessagePolicy,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             inbound: Vec::new(),
+            inbound_connections: 0,
             outbound_connections: 0,
             manual_attempt_limit: 0,
             seed_query_timeout_seconds: 8,
@@ -39,11 +123,42 @@ impl Default for Settings {
             external_addr: Vec::new(),
             peers: Vec::new(),
             seeds: Vec::new(),
+            outbound_transports: Vec::new(),
+            outbound_transport_slots: Vec::new(),
             node_id: String::new(),
+            transport_encryption: true,
+            peer_upload_limit: 0,
+            upload_limit: 0,
+            ban_threshold: 100,
+            ban_seconds: 86400,
+            banlist_path: None,
+            compression: true,
+            compression_threshold: 1024,
+            nat_traversal: false,
+            hosts_path: None,
+            shutdown_timeout_seconds: 5,
+            channel_queue_size: 256,
+            channel_send_timeout_seconds: 10,
+            unknown_message_policy: UnknownMessagePolicy::Log,
         }
     }
 }
 
+impl Settings {
+    /// Transport the given outbound slot is dedicated to, if any. Slots are
+    /// handed out in the order of `outbound_transport_slots`.
+    pub fn slot_transport(&self, slot: u32) -> Option<&str> {
+        let mut end = 0;
+        for allocation in &self.outbound_transport_slots {
+            end += allocation.slots;
+            if slot < end {
+                return Some(&allocation.transport)
+            }
+        }
+        None
+    }
+}
+
 /// Defines the network settings.
 #[derive(Clone, Debug, Deserialize, StructOpt, StructOptToml)]
 #[structopt()]
@@ -53,6 +168,10 @@ pub struct SettingsOpt {
     #[structopt(long = "accept")]
     pub inbound: Vec<Url>,
 
+    /// Maximum number of inbound connections (0 for unlimited)
+    #[structopt(long = "inbound-slots")]
+    pub inbound_connections: Option<u32>,
+
     /// Connection slots
     #[structopt(long = "slots")]
     pub outbound_connections: Option<u32>,
@@ -67,11 +186,24 @@ pub struct SettingsOpt {
     #[structopt(long)]
     pub peers: Vec<Url>,
 
-    /// Seed nodes to connect to
+    /// Seed nodes to connect to, `dnsseed://host:port` URLs are resolved
+    /// through DNS into peers
     #[serde(default)]
     #[structopt(long)]
     pub seeds: Vec<Url>,
 
+    /// Transports outbound slots may dial, in order of preference
+    /// (e.g. `i2p`, `tor`, `tcp`). Any transport is used when empty.
+    #[serde(default)]
+    #[structopt(long)]
+    pub outbound_transports: Vec<String>,
+
+    /// Outbound slots dedicated to a transport, as `transport:slots`
+    /// (e.g. `tcp:4`, `tor:4`). Remaining slots follow `outbound_transports`.
+    #[serde(default)]
+    #[structopt(long = "transport-slots")]
+    pub outbound_transport_slots: Vec<TransportSlots>,
+
     #[structopt(skip)]
     pub manual_attempt_limit: Option<u32>,
     #[structopt(skip)]
@@ -88,12 +220,67 @@ pub struct SettingsOpt {
     #[serde(default)]
     #[structopt(skip)]
     pub node_id: String,
+
+    /// Wrap plaintext transports with TLS (enabled by default)
+    #[structopt(skip)]
+    pub transport_encryption: Option<bool>,
+
+    /// Upload limit per peer in bytes per second (0 for unlimited)
+    #[structopt(long)]
+    pub peer_upload_limit: Option<u64>,
+
+    /// Global upload limit in bytes per second (0 for unlimited)
+    #[structopt(long)]
+    pub upload_limit: Option<u64>,
+
+    /// Path to the file where banned peers are persisted
+    #[structopt(long)]
+    pub banlist_path: Option<String>,
+
+    #[structopt(skip)]
+    pub ban_threshold: Option<u32>,
+    #[structopt(skip)]
+    pub ban_seconds: Option<u64>,
+
+    /// Compress message payloads when the peer supports it (enabled by default)
+    #[structopt(skip)]
+    pub compression: Option<bool>,
+    /// Payloads smaller than this many bytes are sent uncompressed
+    #[structopt(skip)]
+    pub compression_threshold: Option<u32>,
+
+    /// Map inbound ports on the gateway with NAT-PMP or UPnP and advertise the
+    /// external address
+    #[serde(default)]
+    #[structopt(long)]
+    pub nat_traversal: bool,
+
+    /// Path to the file where known hosts are persisted across restarts
+    #[structopt(long)]
+    pub hosts_path: Option<String>,
+
+    #[structopt(skip)]
+    pub shutdown_timeout_seconds: Option<u32>,
+
+    /// Number of queued messages per priority class and channel
+    #[structopt(skip)]
+    pub channel_queue_size: Option<usize>,
+    /// Seconds a message may wait for room in a full send queue before the
+    /// peer is considered too slow and disconnected
+    #[structopt(skip)]
+    pub channel_send_timeout_seconds: Option<u32>,
+
+    /// How to handle messages no protocol is registered for: `ignore`,
+    /// `log` (default) or `misbehave`
+    #[structopt(long)]
+    pub unknown_message_policy: Option<UnknownMessagePolicy>,
 }
 
 impl From<SettingsOpt> for Settings {
     fn from(settings_opt: SettingsOpt) -> Self {
         Self {
             inbound: settings_opt.inbound,
+            inbound_connections: settings_opt.inbound_connections.unwrap_or(0),
             outbound_connections: settings_opt.outbound_connections.unwrap_or(0),
             manual_attempt_limit: settings_opt.manual_attempt_limit.unwrap_or(0),
             seed_query_timeout_seconds: settings_opt.seed_query_timeout_seconds.unwrap_or(8),
@@ -104,7 +291,23 @@ impl From<SettingsOpt> for Settings {
             external_addr: settings_opt.external_addr,
             peers: settings_opt.peers,
             seeds: settings_opt.seeds,
+            outbound_transports: settings_opt.outbound_transports,
+            outbound_transport_slots: settings_opt.outbound_transport_slots,
             node_id: settings_opt.node_id,
+            transport_encryption: settings_opt.transport_encryption.unwrap_or(true),
+            peer_upload_limit: settings_opt.peer_upload_limit.unwrap_or(0),
+            upload_limit: settings_opt.upload_limit.unwrap_or(0),
+            ban_threshold: settings_opt.ban_threshold.unwrap_or(100),
+            ban_seconds: settings_opt.ban_seconds.unwrap_or(86400),
+            banlist_path: settings_opt.banlist_path,
+            compression: settings_opt.compression.unwrap_or(true),
+            compression_threshold: settings_opt.compression_threshold.unwrap_or(1024),
+            nat_traversal: settings_opt.nat_traversal,
+            hosts_path: settings_opt.hosts_path,
+            shutdown_timeout_seconds: settings_opt.shutdown_timeout_seconds.unwrap_or(5),
+            channel_queue_size: settings_opt.channel_queue_size.unwrap_or(256),
+            channel_send_timeout_seconds: settings_opt.channel_send_timeout_seconds.unwrap_or(10),
+            unknown_message_policy: settings_opt.unknown_message_policy.unwrap_or_default(),
         }
     }
 }
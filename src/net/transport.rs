@@ -16,9 +16,15 @@ pub use tcp::TcpTransport;
 mod tor;
 pub use tor::TorTransport;
 
+mod i2p;
+pub use i2p::{I2pListener, I2pTransport};
+
 mod unix;
 pub use unix::UnixTransport;
 
+mod memory;
+pub use memory::{MemoryListener, MemoryStream, MemoryTransport};
+
 /// A helper function to convert SocketAddr to Url and add scheme
 pub(crate) fn socket_addr_to_url(addr: SocketAddr, scheme: &str) -> Result<Url> {
     let url = Url::parse(&format!("{}://{}", scheme, addr))?;
@@ -39,7 +45,9 @@ pub enum TransportName {
     Tcp(Option<String>),
     Tor(Option<String>),
     Nym(Option<String>),
+    I2p(Option<String>),
     Unix,
+    Memory,
 }
 
 impl TryFrom<Url> for TransportName {
@@ -53,13 +61,49 @@ impl TryFrom<Url> for TransportName {
             "tor+tls" => Self::Tor(Some("tls".into())),
             "nym" => Self::Nym(None),
             "nym+tls" => Self::Nym(Some("tls".into())),
+            "i2p" => Self::I2p(None),
+            "i2p+tls" => Self::I2p(Some("tls".into())),
             "unix" => Self::Unix,
+            "mem" => Self::Memory,
             n => return Err(crate::Error::UnsupportedTransport(n.into())),
         };
         Ok(transport_name)
     }
 }
 
+impl TransportName {
+    /// Upgrade plaintext TCP to TLS when transport encryption is enabled.
+    /// Transports that already provide their own encryption are left as-is.
+    pub fn with_encryption(self, transport_encryption: bool) -> Self {
+        match self {
+            Self::Tcp(None) if transport_encryption => Self::Tcp(Some("tls".into())),
+            other => other,
+        }
+    }
+
+    /// Returns `true` if streams of this transport are TLS-wrapped.
+    pub fn is_encrypted(&self) -> bool {
+        match self {
+            Self::Tcp(upgrade) | Self::Tor(upgrade) | Self::Nym(upgrade) | Self::I2p(upgrade) => {
+                upgrade.as_deref() == Some("tls")
+            }
+            Self::Unix | Self::Memory => false,
+        }
+    }
+
+    /// Name of the underlying transport, regardless of any upgrade.
+    pub fn base_name(&self) -> &'static str {
+        match self {
+            Self::Tcp(_) => "tcp",
+            Self::Tor(_) => "tor",
+            Self::Nym(_) => "nym",
+            Self::I2p(_) => "i2p",
+            Self::Unix => "unix",
+            Self::Memory => "mem",
+        }
+    }
+}
+
 /// The `Transport` trait serves as a base for implementing transport protocols.
 /// Base transports can optionally be upgraded with TLS in order to support encryption.
 /// The implementation of our TLS authentication can be found in the
@@ -0,0 +1,349 @@
+use async_std::{
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+use std::{pin::Pin, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use futures_rustls::{TlsAcceptor, TlsStream};
+use fxhash::FxHashMap;
+use log::{debug, error, info};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{Error, Result};
+
+use super::{TlsUpgrade, Transport, TransportListener, TransportStream};
+
+/// Highest SAM protocol version we speak.
+const SAM_VERSION: &str = "3.1";
+
+/// Implements communication over the I2P network through a SAM v3 bridge.
+///
+/// The I2P router must be running with the SAM bridge enabled. Its url is
+/// passed to the constructor, e.g. `tcp://127.0.0.1:7656`.
+///
+/// ## Sessions
+///
+/// All streams go through a SAM session, which owns a transient I2P
+/// destination. The session is created on first use and lives as long as the
+/// transport or any of its clones. Our own address is the `.b32.i2p` name of
+/// that destination.
+///
+/// ## Listening
+///
+/// The host and port of the url passed to [listen_on][transportlisten] are
+/// ignored, as I2P has no notion of local addresses: peers reach us through
+/// the session destination, e.g. `i2p://localhost` is a valid accept url.
+///
+/// [transportlisten]: Transport
+#[derive(Clone)]
+pub struct I2pTransport {
+    sam_url: Url,
+    session: Arc<Mutex<Option<Arc<SamSession>>>>,
+}
+
+/// An open SAM session. Dropping it closes the control socket, which makes
+/// the router tear down the session and its destination.
+struct SamSession {
+    id: String,
+    address: Url,
+    _control: TcpStream,
+}
+
+/// Listener accepting streams addressed to our session destination.
+pub struct I2pListener {
+    sam_url: Url,
+    session: Arc<SamSession>,
+}
+
+impl I2pTransport {
+    /// Creates a new I2pTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `sam_url` - url of the SAM bridge. For example tcp://127.0.0.1:7656
+    pub fn new(sam_url: Url) -> Self {
+        Self { sam_url, session: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Query the environment for the SAM bridge url, or fallback to the default
+    pub fn get_env() -> Result<Url> {
+        Ok(Url::parse(
+            &std::env::var("DARKFI_I2P_SAM_URL")
+                .unwrap_or_else(|_| "tcp://127.0.0.1:7656".to_string()),
+        )?)
+    }
+
+    /// Returns our `i2p://<name>.b32.i2p` address, creating the session if
+    /// needed.
+    pub async fn address(&self) -> Result<Url> {
+        Ok(self.session().await?.address.clone())
+    }
+
+    async fn session(&self) -> Result<Arc<SamSession>> {
+        let mut session = self.session.lock().await;
+        if let Some(session) = &*session {
+            return Ok(session.clone())
+        }
+
+        let new_session = Arc::new(SamSession::create(&self.sam_url).await?);
+        info!(target: "net", "I2P session {} created: {}", new_session.id, new_session.address);
+        *session = Some(new_session.clone());
+        Ok(new_session)
+    }
+
+    pub async fn do_dial(self, url: Url) -> Result<TcpStream> {
+        let destination = match url.host_str() {
+            Some(host) => host.to_string(),
+            None => return Err(Error::I2pError(format!("No destination in {}", url))),
+        };
+
+        let session = self.session().await?;
+        let mut stream = sam_connect(&self.sam_url).await?;
+        let cmd =
+            format!("STREAM CONNECT ID={} DESTINATION={} SILENT=false\n", session.id, destination);
+        sam_command(&mut stream, &cmd).await?;
+        debug!(target: "net", "I2P stream to {} established", destination);
+        Ok(stream)
+    }
+
+    pub async fn do_listen(self) -> Result<I2pListener> {
+        let session = self.session().await?;
+        info!(target: "net", "Listening for I2P connections on {}", session.address);
+        Ok(I2pListener { sam_url: self.sam_url, session })
+    }
+}
+
+impl SamSession {
+    async fn create(sam_url: &Url) -> Result<Self> {
+        let id = format!("darkfi-{:08x}", rand::thread_rng().gen::<u32>());
+
+        let mut control = sam_connect(sam_url).await?;
+        let cmd = format!(
+            "SESSION CREATE STYLE=STREAM ID={} DESTINATION=TRANSIENT SIGNATURE_TYPE=EdDSA_SHA512_Ed25519\n",
+            id
+        );
+        sam_command(&mut control, &cmd).await?;
+
+        let reply = sam_command(&mut control, "NAMING LOOKUP NAME=ME\n").await?;
+        let destination = match reply.get("VALUE") {
+            Some(v) => v,
+            None => return Err(Error::I2pError("No destination in naming reply".to_string())),
+        };
+
+        let address = Url::parse(&format!("i2p://{}", b32_address(destination)?))?;
+        Ok(Self { id, address, _control: control })
+    }
+}
+
+impl I2pListener {
+    /// Waits for an incoming stream. Returns it along with the address of the
+    /// remote destination.
+    async fn accept(&self) -> Result<(TcpStream, Url)> {
+        let mut stream = sam_connect(&self.sam_url).await?;
+        let cmd = format!("STREAM ACCEPT ID={} SILENT=false\n", self.session.id);
+        sam_command(&mut stream, &cmd).await?;
+
+        // Once a peer connects, SAM sends its destination before the data
+        let line = read_line(&mut stream).await?;
+        let destination = match line.split_whitespace().next() {
+            Some(d) => d,
+            None => return Err(Error::I2pError("Empty destination on accept".to_string())),
+        };
+
+        let url = Url::parse(&format!("i2p://{}", b32_address(destination)?))?;
+        Ok((stream, url))
+    }
+}
+
+#[async_trait]
+impl TransportListener for I2pListener {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        match self.accept().await {
+            Ok((stream, url)) => Ok((Box::new(stream), url)),
+            Err(err) => {
+                error!("Error listening for I2P connections: {}", err);
+                Err(Error::AcceptConnectionFailed(self.session.address.to_string()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TransportListener for (TlsAcceptor, I2pListener) {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        let (stream, url) = match self.1.accept().await {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Error listening for I2P connections: {}", err);
+                return Err(Error::AcceptConnectionFailed(self.1.session.address.to_string()))
+            }
+        };
+
+        let stream = self.0.accept(stream).await;
+
+        if let Err(err) = stream {
+            error!("Error wraping the connection {} with tls: {}", url, err);
+            return Err(Error::AcceptTlsConnectionFailed(self.1.session.address.to_string()))
+        }
+
+        let mut url = url;
+        url.set_scheme("i2p+tls").unwrap();
+        Ok((Box::new(TlsStream::Server(stream?)), url))
+    }
+}
+
+impl Transport for I2pTransport {
+    type Acceptor = I2pListener;
+    type Connector = TcpStream;
+
+    type Listener = Pin<Box<dyn Future<Output = Result<Self::Acceptor>> + Send>>;
+    type Dial = Pin<Box<dyn Future<Output = Result<Self::Connector>> + Send>>;
+
+    type TlsListener = Pin<Box<dyn Future<Output = Result<(TlsAcceptor, Self::Acceptor)>> + Send>>;
+    type TlsDialer = Pin<Box<dyn Future<Output = Result<TlsStream<Self::Connector>>> + Send>>;
+
+    fn listen_on(self, url: Url) -> Result<Self::Listener> {
+        match url.scheme() {
+            "i2p" | "i2p+tls" => {}
+            x => return Err(Error::UnsupportedTransport(x.to_string())),
+        }
+        Ok(Box::pin(self.do_listen()))
+    }
+
+    fn upgrade_listener(self, acceptor: Self::Acceptor) -> Result<Self::TlsListener> {
+        let tlsupgrade = TlsUpgrade::new();
+        Ok(Box::pin(tlsupgrade.upgrade_listener_tls(acceptor)))
+    }
+
+    fn dial(self, url: Url, _timeout: Option<Duration>) -> Result<Self::Dial> {
+        match url.scheme() {
+            "i2p" | "i2p+tls" => {}
+            x => return Err(Error::UnsupportedTransport(x.to_string())),
+        }
+        Ok(Box::pin(self.do_dial(url)))
+    }
+
+    fn upgrade_dialer(self, connector: Self::Connector) -> Result<Self::TlsDialer> {
+        let tlsupgrade = TlsUpgrade::new();
+        Ok(Box::pin(tlsupgrade.upgrade_dialer_tls(connector)))
+    }
+}
+
+/// Open a connection to the SAM bridge and perform the version handshake.
+async fn sam_connect(sam_url: &Url) -> Result<TcpStream> {
+    let socket_addr = sam_url.socket_addrs(|| None)?[0];
+    let mut stream = TcpStream::connect(socket_addr).await?;
+    let hello = format!("HELLO VERSION MIN=3.0 MAX={}\n", SAM_VERSION);
+    sam_command(&mut stream, &hello).await?;
+    Ok(stream)
+}
+
+/// Send a command and parse the `KEY=VALUE` pairs of the reply, failing
+/// unless the bridge reports `RESULT=OK`.
+async fn sam_command(stream: &mut TcpStream, cmd: &str) -> Result<FxHashMap<String, String>> {
+    stream.write_all(cmd.as_bytes()).await?;
+    let line = read_line(stream).await?;
+
+    let mut reply = FxHashMap::default();
+    for token in line.split_whitespace() {
+        if let Some((key, value)) = token.split_once('=') {
+            reply.insert(key.to_string(), value.trim_matches('"').to_string());
+        }
+    }
+
+    match reply.get("RESULT").map(|r| r.as_str()) {
+        Some("OK") => Ok(reply),
+        _ => Err(Error::I2pError(format!("SAM bridge replied: {}", line))),
+    }
+}
+
+/// Read a single line from the bridge. Reads byte by byte so nothing past the
+/// newline is consumed, as the stream carries raw data right after it.
+async fn read_line(stream: &mut TcpStream) -> Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        if stream.read(&mut byte).await? == 0 {
+            return Err(Error::I2pError("SAM bridge closed the connection".to_string()))
+        }
+        if byte[0] == b'\n' {
+            break
+        }
+        line.push(byte[0]);
+    }
+
+    String::from_utf8(line).map_err(|_| Error::I2pError("Invalid SAM reply".to_string()))
+}
+
+/// Compute the `<name>.b32.i2p` address of a base64 encoded destination:
+/// the lowercase, unpadded base32 encoding of its SHA-256 hash.
+fn b32_address(destination: &str) -> Result<String> {
+    let bytes = i2p_base64_decode(destination)?;
+    let hash = Sha256::digest(&bytes);
+    Ok(format!("{}.b32.i2p", base32_encode(&hash)))
+}
+
+/// Decode I2P's base64 variant, which uses `-` and `~` instead of `+` and `/`.
+fn i2p_base64_decode(s: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for c in s.trim_end_matches('=').bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'~' => 63,
+            _ => return Err(Error::I2pError("Invalid destination encoding".to_string())),
+        };
+
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for byte in data {
+        acc = (acc << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((acc >> bits) & 31) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        out.push(ALPHABET[((acc << (5 - bits)) & 31) as usize] as char);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(i2p_base64_decode("aGVsbG8-fg~~").unwrap(), b"hello>~\x0f\xff");
+        assert_eq!(base32_encode(b"hello"), "nbswy3dp");
+        assert_eq!(base32_encode(&[0u8; 32]).len(), 52);
+    }
+}
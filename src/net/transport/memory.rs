@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncWrite, Stream};
+use log::{debug, error};
+use url::Url;
+
+use super::{TransportListener, TransportStream};
+use crate::{Error, Result};
+
+/// Listeners bound in this process, by the host of their `mem://` URL
+static LISTENERS: Mutex<Option<HashMap<String, async_channel::Sender<MemoryStream>>>> =
+    Mutex::new(None);
+
+/// Number of connections dialed so far, used to give each dialer a
+/// distinct URL
+static DIALED: AtomicU64 = AtomicU64::new(0);
+
+/// Host of a `mem://` URL, which is the name the listener is bound under.
+fn memory_url_to_name(url: &Url) -> Result<String> {
+    match url.scheme() {
+        "mem" => {}
+        x => return Err(Error::UnsupportedTransport(x.to_string())),
+    }
+
+    match url.host_str() {
+        Some(host) if !host.is_empty() => Ok(host.to_string()),
+        _ => Err(Error::UnsupportedTransport(url.to_string())),
+    }
+}
+
+/// One end of an in-process duplex stream. Bytes written to one end can be
+/// read from the other, in order. Closing an end makes the other one read
+/// EOF once it drained what was written before.
+pub struct MemoryStream {
+    tx: async_channel::Sender<Vec<u8>>,
+    rx: async_channel::Receiver<Vec<u8>>,
+    /// Last chunk received, and how much of it was read already
+    buf: Vec<u8>,
+    pos: usize,
+    /// URL the other end sees this one as
+    url: Url,
+}
+
+impl MemoryStream {
+    /// Create the two connected ends of a stream, the first one known to the
+    /// second as `url_a`, and the second one to the first as `url_b`.
+    pub fn pair(url_a: Url, url_b: Url) -> (Self, Self) {
+        let (tx_a, rx_b) = async_channel::unbounded();
+        let (tx_b, rx_a) = async_channel::unbounded();
+        let a = Self { tx: tx_a, rx: rx_a, buf: vec![], pos: 0, url: url_a };
+        let b = Self { tx: tx_b, rx: rx_b, buf: vec![], pos: 0, url: url_b };
+        (a, b)
+    }
+}
+
+impl AsyncRead for MemoryStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        while self.pos == self.buf.len() {
+            match Pin::new(&mut self.rx).poll_next(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                // The other end is gone
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for MemoryStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0))
+        }
+
+        match self.tx.try_send(buf.to_vec()) {
+            Ok(()) => Poll::Ready(Ok(buf.len())),
+            Err(_) => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.tx.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl TransportStream for MemoryStream {}
+
+/// Listener bound to a `mem://` URL. The name is released when it's dropped.
+pub struct MemoryListener {
+    name: String,
+    rx: async_channel::Receiver<MemoryStream>,
+}
+
+#[async_trait]
+impl TransportListener for MemoryListener {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        let stream = match self.rx.recv().await {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Error listening for connections: {}", err);
+                return Err(Error::AcceptConnectionFailed(format!("mem://{}", self.name)))
+            }
+        };
+        let url = stream.url.clone();
+        Ok((Box::new(stream), url))
+    }
+}
+
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        if let Some(listeners) = LISTENERS.lock().unwrap().as_mut() {
+            listeners.remove(&self.name);
+        }
+    }
+}
+
+/// Transport connecting peers of the same process over in-memory streams,
+/// addressed by mock URLs like `mem://node1`. It binds no sockets, so P2P
+/// networks can be tested in isolation.
+#[derive(Default, Copy, Clone)]
+pub struct MemoryTransport {}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn listen(self, url: Url) -> Result<MemoryListener> {
+        let name = memory_url_to_name(&url)?;
+
+        let mut listeners = LISTENERS.lock().unwrap();
+        let listeners = listeners.get_or_insert_with(HashMap::new);
+        if listeners.contains_key(&name) {
+            error!("{} is already bound", url);
+            return Err(Error::BindFailed(url.to_string()))
+        }
+
+        let (tx, rx) = async_channel::unbounded();
+        listeners.insert(name.clone(), tx);
+
+        debug!("{} transport: listening on {}", url.scheme(), name);
+        Ok(MemoryListener { name, rx })
+    }
+
+    pub fn dial(self, url: Url) -> Result<MemoryStream> {
+        let name = memory_url_to_name(&url)?;
+
+        let listener = match LISTENERS.lock().unwrap().as_ref().and_then(|l| l.get(&name)) {
+            Some(tx) => tx.clone(),
+            None => return Err(Error::ConnectFailed),
+        };
+
+        let n = DIALED.fetch_add(1, Ordering::Relaxed);
+        let dialer_url = Url::parse(&format!("mem://dialer{}", n))?;
+        let (ours, theirs) = MemoryStream::pair(url.clone(), dialer_url);
+        if listener.try_send(theirs).is_err() {
+            return Err(Error::ConnectFailed)
+        }
+
+        debug!("{} transport: dialing to {}", url.scheme(), name);
+        Ok(ours)
+    }
+}
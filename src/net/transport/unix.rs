@@ -1,16 +1,41 @@
 use async_std::os::unix::net::{UnixListener, UnixStream};
+use std::{
+    fs,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::PathBuf,
+};
 
 use async_trait::async_trait;
 use log::{debug, error};
 use url::Url;
 
 use super::{TransportListener, TransportStream};
-use crate::{Error, Result};
+use crate::{util::expand_path, Error, Result};
+
+/// Permissions applied to a freshly bound socket file. Only the owner is
+/// allowed to connect.
+const SOCKET_PERMISSIONS: u32 = 0o600;
 
 fn unix_socket_addr_to_string(addr: std::os::unix::net::SocketAddr) -> String {
     addr.as_pathname().unwrap_or(&std::path::PathBuf::from("")).to_str().unwrap_or("").into()
 }
 
+/// Convert a `unix://` URL into the filesystem path of the socket, expanding
+/// a leading `~` into the home directory.
+fn unix_url_to_path(url: &Url) -> Result<PathBuf> {
+    let path = match url.host_str() {
+        // unix://~/foo.sock and unix://foo.sock put the first component into the host
+        Some(host) => format!("{}{}", host, url.path()),
+        None => url.path().to_string(),
+    };
+
+    if path.is_empty() {
+        return Err(Error::UnsupportedTransport(url.to_string()))
+    }
+
+    expand_path(&path)
+}
+
 #[async_trait]
 impl TransportListener for UnixListener {
     async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
@@ -23,7 +48,12 @@ impl TransportListener for UnixListener {
                 )))
             }
         };
-        let url = Url::parse(&unix_socket_addr_to_string(peer_addr))?;
+        // Client sockets are usually unnamed, so fall back to our own path.
+        let mut peer = unix_socket_addr_to_string(peer_addr);
+        if peer.is_empty() {
+            peer = unix_socket_addr_to_string(self.local_addr()?);
+        }
+        let url = Url::parse(&format!("unix://{}", peer))?;
         Ok((Box::new(stream), url))
     }
 }
@@ -47,8 +77,26 @@ impl UnixTransport {
             return Err(Error::UnsupportedOS)
         }
 
-        let listener = UnixListener::bind(url.as_str()).await?;
-        debug!("{} transport: listening on {}", url.scheme(), url);
+        let path = unix_url_to_path(&url)?;
+
+        // Remove a stale socket left behind by a previous run, but never
+        // clobber anything that isn't a socket.
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                error!("Refusing to replace non-socket file {:?}", path);
+                return Err(Error::BindFailed(url.to_string()))
+            }
+            fs::remove_file(&path)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(&path).await?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(SOCKET_PERMISSIONS))?;
+
+        debug!("{} transport: listening on {:?}", url.scheme(), path);
         Ok(listener)
     }
 
@@ -62,8 +110,9 @@ impl UnixTransport {
             return Err(Error::UnsupportedOS)
         }
 
-        let stream = UnixStream::connect(url.as_str()).await?;
-        debug!("{} transport: dialing to {}", url.scheme(), url);
+        let path = unix_url_to_path(&url)?;
+        let stream = UnixStream::connect(&path).await?;
+        debug!("{} transport: dialing to {:?}", url.scheme(), path);
         Ok(stream)
     }
 }
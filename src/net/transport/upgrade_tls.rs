@@ -1,6 +1,6 @@
-use std::time::SystemTime;
+use std::{fs::File, io::BufReader, path::Path, time::SystemTime};
 
-use async_std::{net::TcpListener, sync::Arc};
+use async_std::sync::Arc;
 use futures::prelude::*;
 use futures_rustls::{
     rustls,
@@ -13,9 +13,9 @@ use futures_rustls::{
     },
     TlsAcceptor, TlsConnector, TlsStream,
 };
-use rustls_pemfile::pkcs8_private_keys;
+use rustls_pemfile::{certs, pkcs8_private_keys};
 
-use crate::Result;
+use crate::{Error, Result};
 
 const CIPHER_SUITE: &str = "TLS13_CHACHA20_POLY1305_SHA256";
 
@@ -114,10 +114,56 @@ impl TlsUpgrade {
         Self { server_config, client_config }
     }
 
-    pub async fn upgrade_listener_tls(
-        self,
-        listener: TcpListener,
-    ) -> Result<(TlsAcceptor, TcpListener)> {
+    /// Create a TLS upgrade that presents the given PEM-encoded certificate
+    /// chain and PKCS#8 private key instead of an ephemeral self-signed one.
+    /// Clients are not required to authenticate, which makes this suitable
+    /// for serving regular TLS clients.
+    pub fn with_certificate(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        let mut cert_reader = BufReader::new(File::open(cert_path)?);
+        let certificates: Vec<Certificate> = certs(&mut cert_reader)
+            .map_err(|e| Error::TlsCertificateError(e.to_string()))?
+            .into_iter()
+            .map(Certificate)
+            .collect();
+
+        if certificates.is_empty() {
+            return Err(Error::TlsCertificateError(format!(
+                "No certificates found in {:?}",
+                cert_path
+            )))
+        }
+
+        let mut key_reader = BufReader::new(File::open(key_path)?);
+        let mut keys = pkcs8_private_keys(&mut key_reader)
+            .map_err(|e| Error::TlsCertificateError(e.to_string()))?;
+
+        if keys.is_empty() {
+            return Err(Error::TlsCertificateError(format!(
+                "No PKCS#8 private key found in {:?}",
+                key_path
+            )))
+        }
+        let secret_key = rustls::PrivateKey(keys.remove(0));
+
+        let server_config = Arc::new(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(certificates, secret_key)?,
+        );
+
+        let server_cert_verifier = Arc::new(ServerCertificateVerifier {});
+        let client_config = Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(server_cert_verifier)
+                .with_no_client_auth(),
+        );
+
+        Ok(Self { server_config, client_config })
+    }
+
+    pub async fn upgrade_listener_tls<L>(self, listener: L) -> Result<(TlsAcceptor, L)> {
         Ok((TlsAcceptor::from(self.server_config), listener))
     }
 
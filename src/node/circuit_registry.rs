@@ -0,0 +1,495 @@
+use std::{collections::HashMap, sync::Arc};
+
+use halo2_proofs::{plonk, poly::commitment::Params};
+use log::{debug, info, warn};
+
+use crate::{
+    blockchain::{circuitstore::circuit_registry_id, CircuitRecord, CircuitStore},
+    crypto::{keypair::PublicKey, proof::VerifyingKey},
+    node::dispatch::{Dispatcher, NativeContract, Writes},
+    runtime::import::StateReader,
+    tx::ContractCall,
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    zk::{vm::ZkCircuit, vm_stack::empty_witnesses},
+    zkas::decoder::ZkBinary,
+    Error::{CircuitRegistryInvalidCall, InvalidCircuit},
+    Result,
+};
+
+/// Longest name a circuit can be registered under.
+pub const MAX_CIRCUIT_NAME_LEN: usize = 64;
+
+/// Largest `k` parameter a circuit can be registered with, bounding the
+/// cost of building its keys.
+pub const MAX_K_PARAM: u32 = 20;
+
+/// Number of slots proofs of a circuit version are still accepted for once
+/// the version after it activates, giving wallets time to upgrade.
+pub const CIRCUIT_TRANSITION_SLOTS: u64 = 100;
+
+/// Prefix of the state keys holding circuit versions waiting for approval.
+const PROPOSAL_KEY_PREFIX: &[u8] = b"proposal:";
+
+/// Functions of the circuit registry contract, used as the `func_id` of
+/// its calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CircuitRegistryFunction {
+    Register = 0x00,
+    Approve = 0x01,
+}
+
+impl TryFrom<u8> for CircuitRegistryFunction {
+    type Error = crate::Error;
+
+    fn try_from(b: u8) -> Result<Self> {
+        match b {
+            0x00 => Ok(Self::Register),
+            0x01 => Ok(Self::Approve),
+            _ => Err(CircuitRegistryInvalidCall(format!("Unknown function {}", b))),
+        }
+    }
+}
+
+/// Call data approving a proposed circuit version.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct CircuitVersion {
+    pub name: String,
+    pub version: u32,
+}
+
+/// A circuit version proposed to the registry, registered once enough
+/// authorities approved it.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct CircuitProposal {
+    pub circuit: CircuitRecord,
+    /// Authorities who approved the proposal, starting with its proposer
+    pub approvals: Vec<PublicKey>,
+}
+
+impl CircuitProposal {
+    /// State key of the proposal for a circuit version.
+    pub fn key(name: &str, version: u32) -> Vec<u8> {
+        [PROPOSAL_KEY_PREFIX, name.as_bytes(), &[0], &version.to_be_bytes()].concat()
+    }
+}
+
+/// Call proposing a circuit version, signed by a registry authority.
+pub fn register_circuit_call(circuit: &CircuitRecord, authority: PublicKey) -> ContractCall {
+    ContractCall {
+        contract_id: circuit_registry_id(),
+        func_id: CircuitRegistryFunction::Register as u8,
+        call_data: serialize(circuit),
+        proofs: vec![],
+        signature_public: vec![authority],
+    }
+}
+
+/// Call approving a proposed circuit version, signed by a registry authority.
+pub fn approve_circuit_call(name: &str, version: u32, authority: PublicKey) -> ContractCall {
+    ContractCall {
+        contract_id: circuit_registry_id(),
+        func_id: CircuitRegistryFunction::Approve as u8,
+        call_data: serialize(&CircuitVersion { name: name.to_string(), version }),
+        proofs: vec![],
+        signature_public: vec![authority],
+    }
+}
+
+/// Check a circuit is well formed, without looking at the registry.
+pub fn validate_circuit(circuit: &CircuitRecord) -> Result<ZkBinary> {
+    if circuit.name.is_empty() || circuit.name.len() > MAX_CIRCUIT_NAME_LEN {
+        return Err(InvalidCircuit(format!("Name must be 1 to {} characters", MAX_CIRCUIT_NAME_LEN)))
+    }
+    if !circuit.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(InvalidCircuit("Name must be alphanumeric, '-' or '_'".to_string()))
+    }
+    if circuit.k_param == 0 || circuit.k_param > MAX_K_PARAM {
+        return Err(InvalidCircuit(format!("k must be 1 to {}", MAX_K_PARAM)))
+    }
+
+    match ZkBinary::decode(&circuit.zkas_bincode) {
+        Ok(zkbin) => Ok(zkbin),
+        Err(e) => Err(InvalidCircuit(e.to_string())),
+    }
+}
+
+/// Native contract registering zkas circuits on chain, so validators agree
+/// on which circuits exist, and on the version a proof is verified with.
+///
+/// Circuits are registered by governance: a registry authority proposes a
+/// version, which is registered once `threshold` authorities approved it.
+/// An authority key can be the key of a DAO, whose members then vote on
+/// each approval. A registered version can't be replaced. Changed circuits
+/// are registered as the next version of their name, activating at a
+/// later slot than the version before it, which stays verifiable for
+/// [`CIRCUIT_TRANSITION_SLOTS`] after that.
+pub struct CircuitRegistry {
+    authorities: Vec<PublicKey>,
+    threshold: usize,
+}
+
+impl CircuitRegistry {
+    /// Registry where any single authority can register circuits.
+    pub fn new(authorities: Vec<PublicKey>) -> Self {
+        Self::with_threshold(authorities, 1)
+    }
+
+    /// Registry where circuits need the approval of `threshold` authorities.
+    pub fn with_threshold(authorities: Vec<PublicKey>, threshold: usize) -> Self {
+        Self { authorities, threshold: threshold.max(1) }
+    }
+
+    /// Register the functions of the contract with the dispatcher.
+    pub fn register(self, dispatcher: &mut Dispatcher) {
+        let registry = Arc::new(self);
+        let approve = registry.clone();
+        dispatcher.register_function(
+            circuit_registry_id(),
+            CircuitRegistryFunction::Register as u8,
+            "CircuitRegistry::register()",
+            move |call, circuit, state, slot| registry.register_circuit(call, circuit, state, slot),
+        );
+        dispatcher.register_function(
+            circuit_registry_id(),
+            CircuitRegistryFunction::Approve as u8,
+            "CircuitRegistry::approve()",
+            move |call, id, state, slot| approve.approve_circuit(call, id, state, slot),
+        );
+    }
+
+    fn signer(&self, call: &ContractCall) -> Result<PublicKey> {
+        if call.signature_public.len() != 1 || !self.authorities.contains(&call.signature_public[0])
+        {
+            return Err(CircuitRegistryInvalidCall(
+                "Call must be signed by a registry authority".to_string(),
+            ))
+        }
+        Ok(call.signature_public[0])
+    }
+
+    /// Version the next circuit registered under `name` must have.
+    fn next_version(name: &str, state: &dyn StateReader) -> Result<u32> {
+        match state.get(&CircuitRecord::version_key(name))? {
+            Some(bytes) => Ok(deserialize::<u32>(&bytes)? + 1),
+            None => Ok(0),
+        }
+    }
+
+    /// Circuits only activate at a later slot than the one they are
+    /// registered in, so no proof verified before can be affected.
+    fn check_activation(circuit: &CircuitRecord, slot: u64) -> Result<()> {
+        if circuit.activation_slot <= slot {
+            return Err(InvalidCircuit(format!(
+                "Circuit {} v{} must activate after slot {}",
+                circuit.name, circuit.version, slot
+            )))
+        }
+        Ok(())
+    }
+
+    /// Propose a circuit version. A new proposal for a version replaces
+    /// the pending one, along with its approvals.
+    fn register_circuit(
+        &self,
+        call: &ContractCall,
+        circuit: CircuitRecord,
+        state: &dyn StateReader,
+        slot: u64,
+    ) -> Result<Writes> {
+        let signer = self.signer(call)?;
+        validate_circuit(&circuit)?;
+        Self::check_activation(&circuit, slot)?;
+
+        // Versions count up from 0 without gaps
+        let next_version = Self::next_version(&circuit.name, state)?;
+        if circuit.version != next_version {
+            return Err(InvalidCircuit(format!(
+                "Circuit {} must be registered as version {}",
+                circuit.name, next_version
+            )))
+        }
+
+        // Each version activates after the one it replaces
+        if next_version > 0 {
+            let key = CircuitRecord::key(&circuit.name, next_version - 1);
+            let previous: CircuitRecord = match state.get(&key)? {
+                Some(bytes) => deserialize(&bytes)?,
+                None => return Err(InvalidCircuit(format!("Circuit {} is missing", circuit.name))),
+            };
+            if circuit.activation_slot <= previous.activation_slot {
+                return Err(InvalidCircuit(format!(
+                    "Circuit {} must activate after slot {}",
+                    circuit.name, previous.activation_slot
+                )))
+            }
+        }
+
+        debug!(target: "circuit_registry", "Proposing circuit {} v{}", circuit.name, circuit.version);
+        Ok(self.settle(CircuitProposal { circuit, approvals: vec![signer] }))
+    }
+
+    fn approve_circuit(
+        &self,
+        call: &ContractCall,
+        id: CircuitVersion,
+        state: &dyn StateReader,
+        slot: u64,
+    ) -> Result<Writes> {
+        let signer = self.signer(call)?;
+
+        if id.version != Self::next_version(&id.name, state)? {
+            return Err(InvalidCircuit(format!(
+                "Circuit {} v{} is not waiting for approval",
+                id.name, id.version
+            )))
+        }
+
+        let mut proposal: CircuitProposal =
+            match state.get(&CircuitProposal::key(&id.name, id.version))? {
+                Some(bytes) => deserialize(&bytes)?,
+                None => {
+                    return Err(InvalidCircuit(format!(
+                        "Circuit {} v{} was not proposed",
+                        id.name, id.version
+                    )))
+                }
+            };
+        if proposal.approvals.contains(&signer) {
+            return Err(CircuitRegistryInvalidCall("Authority already approved".to_string()))
+        }
+        Self::check_activation(&proposal.circuit, slot)?;
+
+        debug!(target: "circuit_registry", "Approving circuit {} v{}", id.name, id.version);
+        proposal.approvals.push(signer);
+        Ok(self.settle(proposal))
+    }
+
+    /// Write a proposal, registering its circuit if it has enough approvals.
+    /// Registered proposals are kept, and can't be approved anymore since
+    /// their version is taken.
+    fn settle(&self, proposal: CircuitProposal) -> Writes {
+        let circuit = &proposal.circuit;
+        let mut writes =
+            vec![(CircuitProposal::key(&circuit.name, circuit.version), serialize(&proposal))];
+
+        if proposal.approvals.len() >= self.threshold {
+            debug!(target: "circuit_registry", "Registering circuit {} v{}", circuit.name, circuit.version);
+            writes.push((CircuitRecord::key(&circuit.name, circuit.version), serialize(circuit)));
+            writes.push((CircuitRecord::version_key(&circuit.name), serialize(&circuit.version)));
+        }
+
+        writes
+    }
+}
+
+impl NativeContract for CircuitRegistry {
+    fn execute(&self, call: &ContractCall, state: &dyn StateReader, slot: u64) -> Result<Writes> {
+        match CircuitRegistryFunction::try_from(call.func_id)? {
+            CircuitRegistryFunction::Register => {
+                self.register_circuit(call, deserialize(&call.call_data)?, state, slot)
+            }
+            CircuitRegistryFunction::Approve => {
+                self.approve_circuit(call, deserialize(&call.call_data)?, state, slot)
+            }
+        }
+    }
+}
+
+/// Verifying keys of the circuits registered on chain, by name and version.
+///
+/// Keys are built as soon as a version gets registered, ahead of its
+/// activation, so nodes switch over without rebuilding them at the
+/// activation slot. A version stops verifying [`CIRCUIT_TRANSITION_SLOTS`]
+/// after the next one activated, and its key is then dropped.
+#[derive(Clone, Default)]
+pub struct CircuitTable {
+    keys: HashMap<(String, u32), Arc<VerifyingKey>>,
+    /// Activation slots of all registered versions, including retired ones
+    activations: HashMap<(String, u32), u64>,
+}
+
+impl CircuitTable {
+    /// Build the verifying keys of the registered circuits not loaded yet,
+    /// leaving out the ones retired as of `slot`. This is done on startup,
+    /// and whenever circuits get registered. Circuits whose keys can't be
+    /// built, like ones registered with a `k` too small for them, are left
+    /// out, so no proof verifies against them.
+    pub fn load(&mut self, store: &CircuitStore, slot: u64) -> Result<()> {
+        let circuits = store.get_all()?;
+        for circuit in &circuits {
+            self.activations
+                .insert((circuit.name.clone(), circuit.version), circuit.activation_slot);
+        }
+
+        for circuit in circuits {
+            let id = (circuit.name.clone(), circuit.version);
+            if self.keys.contains_key(&id) || self.is_retired(&id, slot) {
+                continue
+            }
+
+            let zkbin = validate_circuit(&circuit)?;
+            let zkcircuit = ZkCircuit::new(empty_witnesses(&zkbin), zkbin);
+            let params = Params::new(circuit.k_param);
+            let vk = match plonk::keygen_vk(&params, &zkcircuit) {
+                Ok(vk) => vk,
+                Err(e) => {
+                    warn!("Failed building keys of circuit {} v{}: {:?}", id.0, id.1, e);
+                    continue
+                }
+            };
+
+            info!("Loaded circuit {} v{}", circuit.name, circuit.version);
+            self.keys.insert(id, Arc::new(VerifyingKey { params, vk }));
+        }
+
+        Ok(())
+    }
+
+    /// Drop the keys of the versions retired as of `slot`.
+    pub fn retire(&mut self, slot: u64) {
+        let retired: Vec<(String, u32)> =
+            self.keys.keys().filter(|id| self.is_retired(id, slot)).cloned().collect();
+        for id in retired {
+            info!("Retired circuit {} v{}", id.0, id.1);
+            self.keys.remove(&id);
+        }
+    }
+
+    /// Whether the transition window after the activation of the version
+    /// following `id` is over at `slot`.
+    fn is_retired(&self, id: &(String, u32), slot: u64) -> bool {
+        match self.activations.get(&(id.0.clone(), id.1 + 1)) {
+            Some(next) => slot >= next.saturating_add(CIRCUIT_TRANSITION_SLOTS),
+            None => false,
+        }
+    }
+
+    /// Verifying key of a version of the circuit registered under `name`.
+    pub fn verifying_key(&self, name: &str, version: u32) -> Option<Arc<VerifyingKey>> {
+        self.keys.get(&(name.to_string(), version)).cloned()
+    }
+
+    /// Verifying key of a version of the circuit registered under `name`,
+    /// if proofs of that version are accepted at `slot`: the version is
+    /// activated, and not past its transition window.
+    pub fn verifying_key_at(
+        &self,
+        name: &str,
+        version: u32,
+        slot: u64,
+    ) -> Option<Arc<VerifyingKey>> {
+        let id = (name.to_string(), version);
+        match self.activations.get(&id) {
+            Some(activation) if slot >= *activation && !self.is_retired(&id, slot) => {
+                self.keys.get(&id).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Names and versions of the loaded circuits.
+    pub fn circuits(&self) -> Vec<(String, u32)> {
+        let mut ret: Vec<(String, u32)> = self.keys.keys().cloned().collect();
+        ret.sort();
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{crypto::keypair::Keypair, node::dispatch::MemoryState, zkas};
+
+    fn compile(source: &str) -> Vec<u8> {
+        let lexer = zkas::Lexer::new("foo.zk", source.chars());
+        let tokens = lexer.lex();
+        let parser = zkas::Parser::new("foo.zk", source.chars(), tokens);
+        let (constants, witnesses, statements) = parser.parse();
+        let mut analyzer =
+            zkas::Analyzer::new("foo.zk", source.chars(), constants, witnesses, statements);
+        analyzer.analyze_types();
+        let compiler = zkas::Compiler::new(
+            "foo.zk",
+            source.chars(),
+            analyzer.constants,
+            analyzer.witnesses,
+            analyzer.statements,
+            analyzer.literals,
+            false,
+        );
+        compiler.compile()
+    }
+
+    #[test]
+    fn test_circuit_registry() {
+        let authority = Keypair::random(&mut OsRng).public;
+        let registry = CircuitRegistry::new(vec![authority]);
+        let state = MemoryState::default();
+
+        let circuit = CircuitRecord {
+            name: "foo".to_string(),
+            version: 0,
+            k_param: 11,
+            activation_slot: 10,
+            zkas_bincode: compile(include_str!("../../bin/daod/proof/foo.zk")),
+        };
+
+        let call = register_circuit_call(&circuit, Keypair::random(&mut OsRng).public);
+        assert!(registry.execute(&call, &state, 0).is_err());
+        let invalid = CircuitRecord { zkas_bincode: vec![0; 3], ..circuit.clone() };
+        assert!(registry.execute(&register_circuit_call(&invalid, authority), &state, 0).is_err());
+
+        // Circuits activate after the slot they're registered in
+        assert!(registry.execute(&register_circuit_call(&circuit, authority), &state, 10).is_err());
+
+        let writes =
+            registry.execute(&register_circuit_call(&circuit, authority), &state, 0).unwrap();
+        state.apply(writes);
+
+        // Versions can't be replaced nor skipped
+        assert!(registry.execute(&register_circuit_call(&circuit, authority), &state, 0).is_err());
+        let skipped = CircuitRecord { version: 2, ..circuit.clone() };
+        assert!(registry.execute(&register_circuit_call(&skipped, authority), &state, 0).is_err());
+        let early = CircuitRecord { version: 1, ..circuit.clone() };
+        assert!(registry.execute(&register_circuit_call(&early, authority), &state, 0).is_err());
+        let next = CircuitRecord { version: 1, activation_slot: 50, ..circuit };
+        assert!(registry.execute(&register_circuit_call(&next, authority), &state, 0).is_ok());
+    }
+
+    #[test]
+    fn test_circuit_upgrade_approval() {
+        let authorities: Vec<PublicKey> =
+            (0..3).map(|_| Keypair::random(&mut OsRng).public).collect();
+        let registry = CircuitRegistry::with_threshold(authorities.clone(), 2);
+        let state = MemoryState::default();
+
+        let circuit = CircuitRecord {
+            name: "foo".to_string(),
+            version: 0,
+            k_param: 11,
+            activation_slot: 10,
+            zkas_bincode: compile(include_str!("../../bin/daod/proof/foo.zk")),
+        };
+
+        // A proposal isn't registered until enough authorities approve it
+        let writes =
+            registry.execute(&register_circuit_call(&circuit, authorities[0]), &state, 0).unwrap();
+        assert_eq!(writes.len(), 1);
+        state.apply(writes);
+
+        let approve = approve_circuit_call("foo", 0, authorities[0]);
+        assert!(registry.execute(&approve, &state, 0).is_err());
+        let approve = approve_circuit_call("foo", 0, authorities[1]);
+        assert!(registry.execute(&approve, &state, 10).is_err());
+        let writes = registry.execute(&approve, &state, 0).unwrap();
+        assert!(writes.iter().any(|(k, _)| k == &CircuitRecord::key("foo", 0)));
+        state.apply(writes);
+
+        // Registered versions can't be approved anymore
+        let approve = approve_circuit_call("foo", 0, authorities[2]);
+        assert!(registry.execute(&approve, &state, 0).is_err());
+    }
+}
@@ -6,29 +6,52 @@ use pasta_curves::group::ff::PrimeField;
 
 use super::state::{state_transition, State};
 use crate::{
+    blockchain::BlockFilter,
+    consensus::EquivocationEvidence,
     crypto::{
-        address::Address,
+        address::{Address, PaymentAddress},
         coin::Coin,
         constants::MERKLE_DEPTH,
-        keypair::{Keypair, PublicKey},
+        invoice::{Invoice, PaymentRequest},
+        keypair::{Keypair, PublicKey, SecretKey},
         merkle_node::MerkleNode,
         proof::ProvingKey,
+        token_id::drk_token_id,
         types::DrkTokenId,
         OwnCoin,
     },
     tx::{
         builder::{
-            TransactionBuilder, TransactionBuilderClearInputInfo, TransactionBuilderInputInfo,
-            TransactionBuilderOutputInfo,
+            TransactionBuilder, TransactionBuilderCallInfo, TransactionBuilderClearInputInfo,
+            TransactionBuilderInputInfo, TransactionBuilderOutputInfo, TransactionProver,
         },
         Transaction,
     },
-    util::serial::Encodable,
-    wallet::walletdb::{Balances, WalletPtr},
-    zk::circuit::{BurnContract, MintContract},
-    ClientFailed, ClientResult, Result,
+    util::{
+        serial::{serialize, Encodable},
+        time::Timestamp,
+    },
+    wallet::walletdb::{Balances, CoinInfo, HistoryEntry, TokenBalance, WalletPtr},
+    zk::{
+        circuit::{BurnContract, MintContract},
+        keycache::KeyCache,
+    },
+    ClientFailed, ClientResult,
+    Error::InvalidInvoice,
+    Result,
 };
 
+/// A payee of a transaction, receiving `value` tokens along with an
+/// optional memo encrypted to them.
+#[derive(Clone, Debug)]
+pub struct TransactionRecipient {
+    pub address: PaymentAddress,
+    pub value: u64,
+    pub memo: Vec<u8>,
+    /// Slot until which the paid coin can't be spent, 0 for no lock
+    pub lock_slot: u64,
+}
+
 /// The Client structure, used for transaction operations.
 /// This includes, receiving, broadcasting, and building.
 pub struct Client {
@@ -40,9 +63,6 @@ pub struct Client {
 
 impl Client {
     pub async fn new(wallet: WalletPtr) -> Result<Self> {
-        // Initialize or load the wallet
-        wallet.init_db().await?;
-
         // Get default keypair or create one
         let main_keypair = wallet.get_default_keypair_or_create_one().await?;
         info!(target: "client", "Main keypair: {}", Address::from(main_keypair.public));
@@ -62,25 +82,37 @@ impl Client {
     }
 
     // TODO: Better function name
+    #[allow(clippy::too_many_arguments)]
     async fn build_slab_from_tx(
         &self,
-        pubkey: PublicKey,
+        recipients: Vec<TransactionRecipient>,
+        calls: Vec<TransactionBuilderCallInfo>,
         value: u64,
+        fee: u64,
         token_id: DrkTokenId,
         clear_input: bool,
         state: Arc<Mutex<State>>,
     ) -> ClientResult<(Transaction, Vec<Coin>)> {
         debug!("build_slab_from_tx(): Begin building slab from tx");
-        let mut clear_inputs = vec![];
-        let mut inputs = vec![];
-        let mut outputs = vec![];
+        let mut builder = TransactionBuilder::new();
+        builder.set_fee(fee);
+        for call in calls {
+            builder.add_call(call);
+        }
         let mut coins = vec![];
 
+        // The fee is paid on top of the recipients' value
+        let total = match value.checked_add(fee) {
+            Some(v) => v,
+            None => return Err(ClientFailed::InvalidAmount(fee)),
+        };
+
         if clear_input {
             debug!("build_slab_from_tx(): Building clear input");
             let signature_secret = self.main_keypair.lock().await.secret;
-            let input = TransactionBuilderClearInputInfo { value, token_id, signature_secret };
-            clear_inputs.push(input);
+            let input =
+                TransactionBuilderClearInputInfo { value: total, token_id, signature_secret };
+            builder.add_clear_input(input);
         } else {
             debug!("build_slab_from_tx(): Building tx inputs");
             let mut inputs_value = 0;
@@ -88,11 +120,16 @@ impl Client {
             let own_coins = self.wallet.get_own_coins().await?;
 
             for own_coin in own_coins.iter() {
-                if inputs_value >= value {
-                    debug!("build_slab_from_tx(): inputs_value >= value");
+                if inputs_value >= total {
+                    debug!("build_slab_from_tx(): inputs_value >= total");
                     break
                 }
 
+                // Time-locked coins aren't spendable yet
+                if own_coin.note.lock_slot > state_m.slot {
+                    continue
+                }
+
                 let leaf_position = own_coin.leaf_position;
                 let root = state_m.tree.root(0).unwrap();
                 let merkle_path = state_m.tree.authentication_path(leaf_position, &root).unwrap();
@@ -105,36 +142,49 @@ impl Client {
                     note: own_coin.note.clone(),
                 };
 
-                inputs.push(input);
+                builder.add_input(input);
                 coins.push(own_coin.coin);
             }
             // Release state lock
             drop(state_m);
 
-            if inputs_value < value {
+            if inputs_value < total {
                 error!("build_slab_from_tx(): Not enough value to build tx inputs");
                 return Err(ClientFailed::NotEnoughValue(inputs_value))
             }
 
-            if inputs_value > value {
-                let return_value = inputs_value - value;
-                outputs.push(TransactionBuilderOutputInfo {
+            // A transaction needs an output, so one without recipients
+            // always gets a change output
+            if inputs_value > total || recipients.is_empty() {
+                let return_value = inputs_value - total;
+                let change_address = PaymentAddress::new(&self.main_keypair.lock().await.secret);
+                builder.add_output(TransactionBuilderOutputInfo {
                     value: return_value,
                     token_id,
-                    public: self.main_keypair.lock().await.public,
+                    public: change_address.public,
+                    view_public: change_address.view_public,
+                    memo: vec![],
+                    lock_slot: 0,
                 });
             }
 
             debug!("build_slab_from_tx(): Finished building inputs");
         }
 
-        outputs.push(TransactionBuilderOutputInfo { value, token_id, public: pubkey });
-        let builder = TransactionBuilder { clear_inputs, inputs, outputs };
-        let mut tx_data = vec![];
+        // All outputs get their mint proofs created in the same builder pass
+        for recipient in recipients {
+            builder.add_output(TransactionBuilderOutputInfo {
+                value: recipient.value,
+                token_id,
+                public: recipient.address.public,
+                view_public: recipient.address.view_public,
+                memo: recipient.memo,
+                lock_slot: recipient.lock_slot,
+            });
+        }
 
-        let mint_pk = self.mint_pk.get_or_create(Client::build_mint_pk);
-        let burn_pk = self.burn_pk.get_or_create(Client::build_burn_pk);
-        let tx = builder.build(mint_pk, burn_pk)?;
+        let mut tx_data = vec![];
+        let tx = builder.build_with_prover(self)?;
         tx.encode(&mut tx_data)?;
 
         // Check if state transition is valid before broadcasting
@@ -148,30 +198,77 @@ impl Client {
     }
 
     /// Build a transaction given the required parameters and state machine.
+    /// `fee` is paid for the transaction's inclusion, on top of `amount`.
     pub async fn build_transaction(
         &self,
-        pubkey: PublicKey,
+        address: PaymentAddress,
         amount: u64,
+        fee: u64,
         token_id: DrkTokenId,
         clear_input: bool,
         state: Arc<Mutex<State>>,
     ) -> ClientResult<Transaction> {
+        let recipient = TransactionRecipient { address, value: amount, memo: vec![], lock_slot: 0 };
+        self.build_multi_transaction(vec![recipient], fee, token_id, clear_input, state).await
+    }
+
+    /// Build a transaction locking `amount` of the wallet's coins until
+    /// `lock_slot`, by paying them to the main address in a time-locked
+    /// coin, e.g. to stake them.
+    pub async fn build_lock_transaction(
+        &self,
+        amount: u64,
+        fee: u64,
+        token_id: DrkTokenId,
+        lock_slot: u64,
+        state: Arc<Mutex<State>>,
+    ) -> ClientResult<Transaction> {
+        let address = PaymentAddress::new(&self.main_keypair.lock().await.secret);
+        let recipient = TransactionRecipient { address, value: amount, memo: vec![], lock_slot };
+        self.build_multi_transaction(vec![recipient], fee, token_id, false, state).await
+    }
+
+    /// Build a single transaction paying each of the given recipients,
+    /// instead of requiring one transaction per payee. The inputs are
+    /// selected to cover the sum of all the payments and the fee.
+    pub async fn build_multi_transaction(
+        &self,
+        recipients: Vec<TransactionRecipient>,
+        fee: u64,
+        token_id: DrkTokenId,
+        clear_input: bool,
+        state: Arc<Mutex<State>>,
+    ) -> ClientResult<Transaction> {
+        if recipients.is_empty() {
+            return Err(ClientFailed::NoRecipients)
+        }
+
+        let mut amount: u64 = 0;
+        for recipient in recipients.iter() {
+            if recipient.value == 0 {
+                return Err(ClientFailed::InvalidAmount(0))
+            }
+
+            amount = match amount.checked_add(recipient.value) {
+                Some(v) => v,
+                None => return Err(ClientFailed::InvalidAmount(recipient.value)),
+            };
+        }
+
         debug!(
-            "send(): Sending {} {} tokens",
+            "send(): Sending {} {} tokens to {} recipients",
             amount,
-            bs58::encode(token_id.to_repr()).into_string()
+            bs58::encode(token_id.to_repr()).into_string(),
+            recipients.len()
         );
 
-        if amount == 0 {
-            return Err(ClientFailed::InvalidAmount(0))
-        }
-
         if !self.wallet.token_id_exists(token_id).await? && !clear_input {
             return Err(ClientFailed::NotEnoughValue(amount))
         }
 
-        let (tx, coins) =
-            self.build_slab_from_tx(pubkey, amount, token_id, clear_input, state).await?;
+        let (tx, coins) = self
+            .build_slab_from_tx(recipients, vec![], amount, fee, token_id, clear_input, state)
+            .await?;
         for coin in coins.iter() {
             // TODO: This should be more robust. In case our transaction is denied,
             // we want to revert to be able to send again.
@@ -182,8 +279,32 @@ impl Client {
         Ok(tx)
     }
 
-    pub async fn init_db(&self) -> Result<()> {
-        self.wallet.init_db().await
+    /// Build a transaction submitting equivocation evidence, to get the
+    /// offending leaders slashed. The wallet's DRK coins only pay `fee`,
+    /// which can't be zero as a transaction must spend some input.
+    pub async fn build_evidence_transaction(
+        &self,
+        evidence: Vec<EquivocationEvidence>,
+        fee: u64,
+        state: Arc<Mutex<State>>,
+    ) -> ClientResult<Transaction> {
+        if fee == 0 {
+            return Err(ClientFailed::InvalidAmount(0))
+        }
+
+        let calls = evidence
+            .iter()
+            .map(|e| TransactionBuilderCallInfo { call: e.call(), signature_secrets: vec![] })
+            .collect();
+
+        let (tx, coins) =
+            self.build_slab_from_tx(vec![], calls, 0, fee, drk_token_id(), false, state).await?;
+        for coin in coins.iter() {
+            self.wallet.confirm_spend_coin(coin).await?;
+        }
+
+        debug!("build_evidence_transaction(): Built tx for {} evidence", evidence.len());
+        Ok(tx)
     }
 
     pub async fn get_own_coins(&self) -> Result<Vec<OwnCoin>> {
@@ -214,15 +335,87 @@ impl Client {
         Ok(())
     }
 
-    pub async fn keygen(&self) -> Result<Address> {
+    pub async fn keygen(&self) -> Result<PaymentAddress> {
         let kp = self.wallet.keygen().await?;
-        Ok(Address::from(kp.public))
+        Ok(PaymentAddress::new(&kp.secret))
+    }
+
+    /// Fetch the mnemonic the wallet's keys are derived from.
+    pub async fn get_mnemonic(&self) -> Result<String> {
+        self.wallet.get_mnemonic().await
+    }
+
+    /// Derive a new diversified receive address of the main keypair.
+    pub async fn new_diversified_address(&self) -> Result<PaymentAddress> {
+        let keypair = *self.main_keypair.lock().await;
+        self.wallet.new_diversified_address(&keypair).await
+    }
+
+    /// Create an invoice requesting `amount` of `token_id`, to be paid to a
+    /// new diversified address before `expiry`.
+    pub async fn create_invoice(
+        &self,
+        token_id: DrkTokenId,
+        amount: u64,
+        memo: String,
+        expiry: Timestamp,
+    ) -> Result<Invoice> {
+        let address = self.new_diversified_address().await?;
+        let keypairs = self.wallet.get_diversified_keypairs().await?;
+        let keypair = match keypairs.iter().find(|kp| kp.public == address.public) {
+            Some(kp) => kp,
+            None => return Err(InvalidInvoice("Diversified key not found".to_string())),
+        };
+
+        PaymentRequest { address, token_id, amount, memo, expiry }.sign(&keypair.secret)
+    }
+
+    /// Secret keys to decrypt incoming notes with, including the ones of
+    /// diversified addresses.
+    pub async fn get_scanning_secrets(&self) -> Result<Vec<SecretKey>> {
+        self.wallet.get_scanning_secrets().await
+    }
+
+    /// Items to match compact block filters against: the serialized coins
+    /// and nullifiers of the wallet's unspent coins, so a light wallet finds
+    /// the blocks confirming its transactions and spending its coins.
+    /// Coins paid to the wallet by others can't be matched, as they are
+    /// only found by decrypting their notes.
+    pub async fn get_filter_items(&self) -> Result<Vec<Vec<u8>>> {
+        let mut items = vec![];
+        for own_coin in self.wallet.get_own_coins().await? {
+            items.push(serialize(&own_coin.coin));
+            items.push(serialize(&own_coin.nullifier));
+        }
+        Ok(items)
+    }
+
+    /// Check if the compact filter of the block with the given headerhash
+    /// matches any of the wallet's coins or nullifiers.
+    pub async fn matches_filter(
+        &self,
+        block_hash: &blake3::Hash,
+        filter: &BlockFilter,
+    ) -> Result<bool> {
+        Ok(filter.match_any(block_hash, &self.get_filter_items().await?))
     }
 
     pub async fn get_balances(&self) -> Result<Balances> {
         self.wallet.get_balances().await
     }
 
+    pub async fn get_token_balances(&self, slot: u64) -> Result<Vec<TokenBalance>> {
+        self.wallet.get_token_balances(slot).await
+    }
+
+    pub async fn get_history(&self, before: Option<u64>, limit: u32) -> Result<Vec<HistoryEntry>> {
+        self.wallet.get_history(before, limit).await
+    }
+
+    pub async fn get_coin(&self, coin: &Coin) -> Result<Option<CoinInfo>> {
+        self.wallet.get_coin(coin).await
+    }
+
     pub async fn get_coins_valtok(
         &self,
         value: u64,
@@ -238,11 +431,21 @@ impl Client {
 
     fn build_mint_pk() -> ProvingKey {
         debug!("Building proving key for MintContract");
-        ProvingKey::build(11, &MintContract::default())
+        KeyCache::default().proving_key(11, &MintContract::default())
     }
 
     fn build_burn_pk() -> ProvingKey {
         debug!("Building proving key for BurnContract");
-        ProvingKey::build(11, &BurnContract::default())
+        KeyCache::default().proving_key(11, &BurnContract::default())
+    }
+}
+
+impl TransactionProver for Client {
+    fn mint_pk(&self) -> &ProvingKey {
+        self.mint_pk.get_or_create(Client::build_mint_pk)
+    }
+
+    fn burn_pk(&self) -> &ProvingKey {
+        self.burn_pk.get_or_create(Client::build_burn_pk)
     }
 }
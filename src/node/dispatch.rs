@@ -0,0 +1,205 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::{debug, error};
+
+use super::state::ProgramState;
+use crate::{
+    blockchain::StateWrite,
+    runtime::{import::StateReader, util::serialize_payload, vm_runtime::Runtime},
+    tx::ContractCall,
+    util::serial::{deserialize, Decodable},
+    Result, VerifyFailed, VerifyResult,
+};
+
+/// State writes of a contract call, as key/value pairs.
+pub type Writes = Vec<(Vec<u8>, Vec<u8>)>;
+
+type FunctionHandler =
+    Arc<dyn Fn(&ContractCall, &dyn StateReader, u64) -> Result<Writes> + Send + Sync>;
+
+/// Function of a native contract registered with the [`Dispatcher`].
+#[derive(Clone)]
+struct NativeFunction {
+    /// Name the function is logged under, like `Slashing::equivocation()`
+    name: &'static str,
+    /// Decodes the call data and runs the state transition
+    handler: FunctionHandler,
+}
+
+/// Handler of the calls to a contract implemented natively in the node.
+pub trait NativeContract: Send + Sync {
+    /// Execute a call validated at `slot`, reading the chain state through
+    /// `state`, which is scoped to this contract. Returns the key/value
+    /// pairs to write to the contract's state, or an error if the call is
+    /// invalid.
+    fn execute(&self, call: &ContractCall, state: &dyn StateReader, slot: u64) -> Result<Writes>;
+}
+
+/// Routes contract calls to their handler: the functions of native
+/// contracts registered by `(contract_id, func_id)`, native contracts
+/// registered as a whole, or deployed wasm contracts run in the wasm
+/// runtime.
+#[derive(Clone, Default)]
+pub struct Dispatcher {
+    functions: HashMap<([u8; 32], u8), NativeFunction>,
+    native: HashMap<[u8; 32], Arc<dyn NativeContract>>,
+}
+
+impl Dispatcher {
+    /// Register a native contract handler under the given contract ID.
+    pub fn register_native(&mut self, contract_id: [u8; 32], handler: Arc<dyn NativeContract>) {
+        self.native.insert(contract_id, handler);
+    }
+
+    /// Register the state transition of a native contract function. Its
+    /// call data is decoded as a `T` before being passed to `handler`
+    /// along with the slot the call is validated at, so calls with
+    /// malformed data fail before reaching it. Once a contract has a
+    /// function registered, calls to its other function IDs fail.
+    pub fn register_function<T, F>(
+        &mut self,
+        contract_id: [u8; 32],
+        func_id: u8,
+        name: &'static str,
+        handler: F,
+    ) where
+        T: Decodable + 'static,
+        F: Fn(&ContractCall, T, &dyn StateReader, u64) -> Result<Writes> + Send + Sync + 'static,
+    {
+        let handler = move |call: &ContractCall, state: &dyn StateReader, slot: u64| {
+            let call_data: T = deserialize(&call.call_data)?;
+            handler(call, call_data, state, slot)
+        };
+
+        self.functions
+            .insert((contract_id, func_id), NativeFunction { name, handler: Arc::new(handler) });
+    }
+
+    /// Name of a registered native contract function.
+    pub fn function_name(&self, contract_id: &[u8; 32], func_id: u8) -> Option<&'static str> {
+        self.functions.get(&(*contract_id, func_id)).map(|f| f.name)
+    }
+
+    fn has_functions(&self, contract_id: &[u8; 32]) -> bool {
+        self.functions.keys().any(|(id, _)| id == contract_id)
+    }
+
+    /// Execute the given calls in order on top of `state`, with the wasm
+    /// ones sharing `gas_limit`. Each call sees the writes of the calls
+    /// before it. Returns the state writes of all calls.
+    pub fn dispatch<S: ProgramState>(
+        &self,
+        state: &S,
+        calls: &[ContractCall],
+        gas_limit: u64,
+    ) -> VerifyResult<Vec<StateWrite>> {
+        let mut contract_state = state.contract_state();
+        let mut gas_used = 0;
+        let mut ret = vec![];
+
+        for (i, call) in calls.iter().enumerate() {
+            let reader = state.state_reader(contract_state.clone(), call.contract_id);
+
+            let result = if let Some(function) =
+                self.functions.get(&(call.contract_id, call.func_id))
+            {
+                debug!(target: "dispatch", "Executing {} for call {}", function.name, i);
+                (function.handler)(call, reader.as_ref(), state.slot())
+            } else if self.has_functions(&call.contract_id) {
+                error!(target: "dispatch", "Unknown function {} for call {}", call.func_id, i);
+                return Err(VerifyFailed::UnknownFunction(i, call.func_id))
+            } else if let Some(handler) = self.native.get(&call.contract_id) {
+                debug!(target: "dispatch", "Executing native contract call {}", i);
+                handler.execute(call, reader.as_ref(), state.slot())
+            } else if let Some(wasm_bincode) = state.contract_bincode(&call.contract_id) {
+                debug!(target: "dispatch", "Executing wasm contract call {}", i);
+                Self::execute_wasm(call, &wasm_bincode, reader, gas_limit - gas_used, &mut gas_used)
+            } else {
+                error!(target: "dispatch", "Unknown contract for call {}", i);
+                return Err(VerifyFailed::UnknownContract(i))
+            };
+
+            let writes = match result {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "dispatch", "Contract call {} failed: {}", i, e);
+                    return Err(VerifyFailed::ContractCall(i, e.to_string()))
+                }
+            };
+
+            let writes: Vec<StateWrite> = writes
+                .into_iter()
+                .map(|(key, value)| StateWrite {
+                    contract_id: call.contract_id,
+                    key,
+                    value: Some(value),
+                })
+                .collect();
+
+            contract_state.apply(&writes);
+            ret.extend(writes);
+        }
+
+        Ok(ret)
+    }
+
+    /// Run a call in the wasm runtime. The entrypoint payload is the
+    /// function ID followed by the call data.
+    fn execute_wasm(
+        call: &ContractCall,
+        wasm_bincode: &[u8],
+        reader: Arc<dyn StateReader>,
+        gas_limit: u64,
+        gas_used: &mut u64,
+    ) -> Result<Writes> {
+        let mut runtime = Runtime::with_state(wasm_bincode, gas_limit, reader)?;
+
+        let mut payload = vec![call.func_id];
+        payload.extend_from_slice(&call.call_data);
+        let result = runtime.run(&serialize_payload(&payload));
+
+        *gas_used += runtime.gas_used();
+        result?;
+        Ok(runtime.take_writes())
+    }
+}
+
+/// Contract state held in memory, applying the writes of each call.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryState(std::sync::Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+#[cfg(test)]
+impl MemoryState {
+    /// Write the given key/value pairs to the state.
+    pub(crate) fn apply(&self, writes: Writes) {
+        self.0.lock().unwrap().extend(writes);
+    }
+
+    /// Execute a call to `contract` and apply its writes.
+    pub(crate) fn execute(
+        &self,
+        contract: &dyn NativeContract,
+        call: &ContractCall,
+        slot: u64,
+    ) -> Result<()> {
+        let writes = contract.execute(call, self, slot)?;
+        self.apply(writes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl StateReader for MemoryState {
+    fn merkle_root_exists(&self, _root: &[u8; 32]) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn nullifier_exists(&self, _nullifier: &[u8; 32]) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.lock().unwrap().get(key).cloned())
+    }
+}
@@ -0,0 +1,317 @@
+use group::ff::PrimeField;
+use log::debug;
+
+use crate::{
+    crypto::{keypair::PublicKey, token_id::native_token_id, types::DrkTokenId},
+    node::dispatch::{Dispatcher, NativeContract, Writes},
+    runtime::import::StateReader,
+    tx::ContractCall,
+    util::serial::{deserialize, serialize, SerialDecodable, SerialEncodable},
+    Error::{
+        TokenAlreadyRegistered, TokenIssuanceInvalidCall, TokenMintingFrozen,
+        TokenSupplyCapExceeded, TokenUnknown,
+    },
+    Result,
+};
+
+/// Hashed into the ID the token issuance contract is registered under.
+const TOKEN_ISSUANCE_DOMAIN: &[u8] = b"DarkFi:TokenIssuance";
+
+/// ID of the native token issuance contract.
+pub fn token_issuance_id() -> [u8; 32] {
+    *blake3::hash(TOKEN_ISSUANCE_DOMAIN).as_bytes()
+}
+
+/// Functions of the token issuance contract, used as the `func_id` of its
+/// calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IssuanceFunction {
+    Create = 0x00,
+    Mint = 0x01,
+    Freeze = 0x02,
+}
+
+impl TryFrom<u8> for IssuanceFunction {
+    type Error = crate::Error;
+
+    fn try_from(b: u8) -> Result<Self> {
+        match b {
+            0x00 => Ok(Self::Create),
+            0x01 => Ok(Self::Mint),
+            0x02 => Ok(Self::Freeze),
+            _ => Err(TokenIssuanceInvalidCall(format!("Unknown function {}", b))),
+        }
+    }
+}
+
+/// Terms of a token's issuance: `authority` can mint it, up to
+/// `max_supply` if there is one.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct IssuanceParams {
+    pub token_id: DrkTokenId,
+    pub authority: PublicKey,
+    pub max_supply: Option<u64>,
+    /// Nonce the ID of a native token is derived with, unused for bridged
+    /// tokens
+    pub nonce: u64,
+}
+
+impl IssuanceParams {
+    /// Terms of a new token issued on DarkFi by `authority`.
+    pub fn native(authority: PublicKey, nonce: u64, max_supply: Option<u64>) -> Self {
+        let token_id = native_token_id(&authority, nonce);
+        Self { token_id, authority, max_supply, nonce }
+    }
+
+    /// Terms of a wrapped token of another network, minted by the cashier
+    /// `authority` against deposits.
+    pub fn bridged(token_id: DrkTokenId, authority: PublicKey, max_supply: Option<u64>) -> Self {
+        Self { token_id, authority, max_supply, nonce: 0 }
+    }
+
+    /// Call creating the token, signed by its authority.
+    pub fn create_call(&self) -> ContractCall {
+        self.call(IssuanceFunction::Create, serialize(self))
+    }
+
+    /// Call minting `value` of the token, signed by its authority. The
+    /// transaction must carry a clear input of the same value and token,
+    /// also signed by the authority, which creates the new supply.
+    pub fn mint_call(&self, value: u64) -> ContractCall {
+        let mint = TokenMint { token_id: self.token_id, value };
+        self.call(IssuanceFunction::Mint, serialize(&mint))
+    }
+
+    /// Call permanently disabling minting of the token, signed by its
+    /// authority.
+    pub fn freeze_call(&self) -> ContractCall {
+        self.call(IssuanceFunction::Freeze, serialize(&self.token_id))
+    }
+
+    fn call(&self, func: IssuanceFunction, call_data: Vec<u8>) -> ContractCall {
+        ContractCall {
+            contract_id: token_issuance_id(),
+            func_id: func as u8,
+            call_data,
+            proofs: vec![],
+            signature_public: vec![self.authority],
+        }
+    }
+}
+
+/// Call data of [`IssuanceFunction::Mint`].
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TokenMint {
+    pub token_id: DrkTokenId,
+    pub value: u64,
+}
+
+/// A token as kept in the contract state, under its ID.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct IssuedToken {
+    pub params: IssuanceParams,
+    /// Total value minted so far
+    pub supply: u64,
+    pub frozen: bool,
+}
+
+/// Native contract tracking the supply of tokens, so that issuance is
+/// auditable from the chain state.
+///
+/// Minting creates new supply through clear inputs, which the state
+/// transition only accepts for tokens created here when paired with a
+/// mint call of the same value signed by the token's authority. The
+/// minted amounts are public so the supply cap can be enforced, while
+/// the coins they are minted into stay shielded like any other.
+///
+/// Native tokens can be created by anyone, under an ID derived from their
+/// authority. Tokens of other networks can only be created by cashiers.
+pub struct TokenIssuance {
+    cashiers: Vec<PublicKey>,
+}
+
+impl TokenIssuance {
+    pub fn new(cashiers: Vec<PublicKey>) -> Self {
+        Self { cashiers }
+    }
+
+    /// Read a token from the contract state.
+    pub fn get_token(
+        state: &dyn StateReader,
+        token_id: &DrkTokenId,
+    ) -> Result<Option<IssuedToken>> {
+        match state.get(&token_id.to_repr())? {
+            Some(bytes) => Ok(Some(deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Mint calls among `calls`, with their index and signer. Malformed
+    /// ones are left out, they fail when the calls are dispatched.
+    pub fn mints(calls: &[ContractCall]) -> Vec<(usize, TokenMint, PublicKey)> {
+        calls
+            .iter()
+            .enumerate()
+            .filter(|(_, call)| {
+                call.contract_id == token_issuance_id() &&
+                    call.func_id == IssuanceFunction::Mint as u8 &&
+                    call.signature_public.len() == 1
+            })
+            .filter_map(|(i, call)| {
+                let mint = deserialize(&call.call_data).ok()?;
+                Some((i, mint, call.signature_public[0]))
+            })
+            .collect()
+    }
+
+    fn existing_token(state: &dyn StateReader, token_id: &DrkTokenId) -> Result<IssuedToken> {
+        match Self::get_token(state, token_id)? {
+            Some(token) if token.frozen => Err(TokenMintingFrozen),
+            Some(token) => Ok(token),
+            None => Err(TokenUnknown),
+        }
+    }
+
+    fn check_signer(call: &ContractCall, signer: &PublicKey) -> Result<()> {
+        if call.signature_public != [*signer] {
+            return Err(TokenIssuanceInvalidCall(
+                "Call must be signed by the token authority".to_string(),
+            ))
+        }
+        Ok(())
+    }
+
+    /// Register the functions of the contract with the dispatcher.
+    pub fn register(self, dispatcher: &mut Dispatcher) {
+        let id = token_issuance_id();
+        dispatcher.register_function(
+            id,
+            IssuanceFunction::Create as u8,
+            "TokenIssuance::create()",
+            move |call, params, state, _| self.create(call, params, state),
+        );
+        dispatcher.register_function(
+            id,
+            IssuanceFunction::Mint as u8,
+            "TokenIssuance::mint()",
+            |call, mint, state, _| Self::mint(call, mint, state),
+        );
+        dispatcher.register_function(
+            id,
+            IssuanceFunction::Freeze as u8,
+            "TokenIssuance::freeze()",
+            |call, token_id, state, _| Self::freeze(call, token_id, state),
+        );
+    }
+
+    fn create(
+        &self,
+        call: &ContractCall,
+        params: IssuanceParams,
+        state: &dyn StateReader,
+    ) -> Result<Writes> {
+        Self::check_signer(call, &params.authority)?;
+
+        if params.token_id != native_token_id(&params.authority, params.nonce) &&
+            !self.cashiers.contains(&params.authority)
+        {
+            return Err(TokenIssuanceInvalidCall(
+                "Only cashiers can create tokens of other networks".to_string(),
+            ))
+        }
+        if Self::get_token(state, &params.token_id)?.is_some() {
+            return Err(TokenAlreadyRegistered)
+        }
+
+        debug!(target: "issuance", "Creating token {:?}", params.token_id);
+        Ok(Self::write(&IssuedToken { params, supply: 0, frozen: false }))
+    }
+
+    fn mint(call: &ContractCall, mint: TokenMint, state: &dyn StateReader) -> Result<Writes> {
+        let mut token = Self::existing_token(state, &mint.token_id)?;
+        Self::check_signer(call, &token.params.authority)?;
+
+        token.supply = match token.supply.checked_add(mint.value) {
+            Some(v) if token.params.max_supply.map_or(true, |max| v <= max) => v,
+            _ => return Err(TokenSupplyCapExceeded),
+        };
+
+        debug!(target: "issuance", "Minting {} of token {:?}", mint.value, mint.token_id);
+        Ok(Self::write(&token))
+    }
+
+    fn freeze(
+        call: &ContractCall,
+        token_id: DrkTokenId,
+        state: &dyn StateReader,
+    ) -> Result<Writes> {
+        let mut token = Self::existing_token(state, &token_id)?;
+        Self::check_signer(call, &token.params.authority)?;
+
+        debug!(target: "issuance", "Freezing token {:?}", token_id);
+        token.frozen = true;
+        Ok(Self::write(&token))
+    }
+
+    fn write(token: &IssuedToken) -> Writes {
+        vec![(token.params.token_id.to_repr().to_vec(), serialize(token))]
+    }
+}
+
+impl NativeContract for TokenIssuance {
+    fn execute(&self, call: &ContractCall, state: &dyn StateReader, _slot: u64) -> Result<Writes> {
+        match IssuanceFunction::try_from(call.func_id)? {
+            IssuanceFunction::Create => self.create(call, deserialize(&call.call_data)?, state),
+            IssuanceFunction::Mint => Self::mint(call, deserialize(&call.call_data)?, state),
+            IssuanceFunction::Freeze => Self::freeze(call, deserialize(&call.call_data)?, state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{crypto::keypair::Keypair, node::dispatch::MemoryState};
+
+    #[test]
+    fn test_token_issuance() {
+        let authority = Keypair::random(&mut OsRng).public;
+        let cashier = Keypair::random(&mut OsRng).public;
+        let contract = TokenIssuance::new(vec![cashier]);
+        let state = MemoryState::default();
+
+        let params = IssuanceParams::native(authority, 0, Some(100));
+        assert!(state.execute(&contract, &params.mint_call(1), 0).is_err());
+        state.execute(&contract, &params.create_call(), 0).unwrap();
+        assert!(state.execute(&contract, &params.create_call(), 0).is_err());
+
+        // Supply is capped
+        state.execute(&contract, &params.mint_call(60), 0).unwrap();
+        assert!(state.execute(&contract, &params.mint_call(41), 0).is_err());
+        state.execute(&contract, &params.mint_call(40), 0).unwrap();
+        let token = TokenIssuance::get_token(&state, &params.token_id).unwrap().unwrap();
+        assert_eq!(token.supply, 100);
+
+        // Only the authority mints, and only until frozen
+        let uncapped = IssuanceParams::native(authority, 1, None);
+        state.execute(&contract, &uncapped.create_call(), 0).unwrap();
+        let mut call = uncapped.mint_call(u64::MAX);
+        call.signature_public = vec![cashier];
+        assert!(state.execute(&contract, &call, 0).is_err());
+        state.execute(&contract, &uncapped.mint_call(u64::MAX), 0).unwrap();
+        assert!(state.execute(&contract, &uncapped.mint_call(1), 0).is_err());
+
+        state.execute(&contract, &params.freeze_call(), 0).unwrap();
+        assert!(state.execute(&contract, &params.mint_call(0), 0).is_err());
+
+        // Tokens of other networks are created by cashiers
+        let bridged = IssuanceParams::bridged(pallas::Base::from(1), authority, None);
+        assert!(state.execute(&contract, &bridged.create_call(), 0).is_err());
+        let bridged = IssuanceParams::bridged(pallas::Base::from(1), cashier, None);
+        state.execute(&contract, &bridged.create_call(), 0).unwrap();
+    }
+}
@@ -1,10 +1,22 @@
+use std::sync::Arc;
+
 use incrementalmerkletree::{bridgetree::BridgeTree, Tree};
 use log::debug;
 
-use super::state::{ProgramState, State, StateUpdate};
-use crate::crypto::{
-    constants::MERKLE_DEPTH, keypair::PublicKey, merkle_node::MerkleNode, nullifier::Nullifier,
-    proof::VerifyingKey,
+use super::{
+    circuit_registry::CircuitTable,
+    dispatch::Dispatcher,
+    state::{ProgramState, State, StateUpdate},
+};
+use crate::{
+    blockchain::ContractStateOverlay,
+    crypto::{
+        constants::MERKLE_DEPTH, keypair::PublicKey, merkle_node::MerkleNode, nullifier::Nullifier,
+        proof::VerifyingKey,
+    },
+    runtime::import::StateReader,
+    util::serial::serialize,
+    Result,
 };
 
 /// In-memory state extension for state transition validations
@@ -18,6 +30,12 @@ pub struct MemoryState {
     pub merkle_roots: Vec<MerkleNode>,
     /// Nullifiers prevent double-spending
     pub nullifiers: Vec<Nullifier>,
+    /// Deployed contracts, with their wasm bincode
+    pub contracts: Vec<([u8; 32], Vec<u8>)>,
+    /// Contract state written on top of the canonical one
+    pub contract_state: ContractStateOverlay,
+    /// Slot transactions are validated at
+    pub slot: u64,
 }
 
 impl ProgramState for MemoryState {
@@ -37,6 +55,38 @@ impl ProgramState for MemoryState {
         self.nullifiers.contains(nullifier) || self.canon.nullifier_exists(nullifier)
     }
 
+    fn contract_exists(&self, contract_id: &[u8; 32]) -> bool {
+        self.contracts.iter().any(|(id, _)| id == contract_id) ||
+            self.canon.contract_exists(contract_id)
+    }
+
+    fn contract_bincode(&self, contract_id: &[u8; 32]) -> Option<Vec<u8>> {
+        match self.contracts.iter().find(|(id, _)| id == contract_id) {
+            Some((_, wasm_bincode)) => Some(wasm_bincode.clone()),
+            None => self.canon.contract_bincode(contract_id),
+        }
+    }
+
+    fn contract_state(&self) -> ContractStateOverlay {
+        self.contract_state.clone()
+    }
+
+    fn state_reader(
+        &self,
+        contract_state: ContractStateOverlay,
+        contract_id: [u8; 32],
+    ) -> Arc<dyn StateReader> {
+        Arc::new(MemoryStateReader {
+            canon: self.canon.state_reader(contract_state, contract_id),
+            merkle_roots: self.merkle_roots.iter().map(serialize).collect(),
+            nullifiers: self.nullifiers.iter().map(serialize).collect(),
+        })
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        self.canon.dispatcher()
+    }
+
     fn mint_vk(&self) -> &VerifyingKey {
         self.canon.mint_vk()
     }
@@ -44,15 +94,26 @@ impl ProgramState for MemoryState {
     fn burn_vk(&self) -> &VerifyingKey {
         self.canon.burn_vk()
     }
+
+    fn circuits(&self) -> &CircuitTable {
+        self.canon.circuits()
+    }
+
+    fn slot(&self) -> u64 {
+        self.slot
+    }
 }
 
 impl MemoryState {
     pub fn new(canon_state: State) -> Self {
         Self {
+            contract_state: canon_state.contract_state(),
+            slot: canon_state.slot,
             canon: canon_state.clone(),
             tree: canon_state.tree,
             merkle_roots: vec![],
             nullifiers: vec![],
+            contracts: vec![],
         }
     }
 
@@ -61,6 +122,10 @@ impl MemoryState {
         let mut nfs = update.nullifiers.clone();
         self.nullifiers.append(&mut nfs);
 
+        self.contracts.extend(update.contracts.iter().cloned());
+        self.contract_state.apply(&update.state_writes);
+        self.slot = update.slot;
+
         debug!(target: "state_apply", "(in-memory) Update Merkle tree and witnesses");
         for coin in update.coins {
             let node = MerkleNode(coin.0);
@@ -71,3 +136,31 @@ impl MemoryState {
         debug!(target: "state_apply", "(in-memory) Finished apply() successfully.");
     }
 }
+
+/// [`StateReader`] over a [`MemoryState`], seeing the Merkle roots and
+/// nullifiers that aren't in the canonical state yet.
+struct MemoryStateReader {
+    canon: Arc<dyn StateReader>,
+    merkle_roots: Vec<Vec<u8>>,
+    nullifiers: Vec<Vec<u8>>,
+}
+
+impl StateReader for MemoryStateReader {
+    fn merkle_root_exists(&self, root: &[u8; 32]) -> Result<bool> {
+        if self.merkle_roots.iter().any(|r| r == root) {
+            return Ok(true)
+        }
+        self.canon.merkle_root_exists(root)
+    }
+
+    fn nullifier_exists(&self, nullifier: &[u8; 32]) -> Result<bool> {
+        if self.nullifiers.iter().any(|n| n == nullifier) {
+            return Ok(true)
+        }
+        self.canon.nullifier_exists(nullifier)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.canon.get(key)
+    }
+}
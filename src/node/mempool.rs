@@ -0,0 +1,407 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+};
+
+use log::debug;
+
+use crate::{
+    tx::Transaction,
+    util::serial::{serialize, Encodable},
+    Error, Result,
+};
+
+/// Default maximum size of the mempool, in serialized bytes.
+pub const DEFAULT_MEMPOOL_SIZE: usize = 32 * 1024 * 1024;
+
+/// Ordering key of a mempool transaction: highest fee per byte first,
+/// then oldest first.
+type OrderKey = (Reverse<u64>, u64, blake3::Hash);
+
+/// Transaction waiting in the mempool, along with its admission data.
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    /// Fee paid by the transaction
+    pub fee: u64,
+    /// Serialized size of the transaction
+    pub size: usize,
+    /// Whether the transaction was created by this node
+    pub local: bool,
+    /// Admission sequence number
+    seq: u64,
+}
+
+/// Fee per kilobyte of a transaction of `size` serialized bytes paying
+/// `fee`, used to rank transactions of different sizes.
+pub fn fee_rate(fee: u64, size: usize) -> u64 {
+    fee.saturating_mul(1000) / size.max(1) as u64
+}
+
+impl MempoolEntry {
+    fn fee_rate(&self) -> u64 {
+        fee_rate(self.fee, self.size)
+    }
+
+    fn key(&self, hash: blake3::Hash) -> OrderKey {
+        (Reverse(self.fee_rate()), self.seq, hash)
+    }
+}
+
+/// Pool of unconfirmed transactions waiting for block inclusion.
+///
+/// Transactions spending the same nullifier as one already in the pool
+/// are rejected. Once the pool is full, transactions with the lowest fee
+/// rate are evicted to make room for better paying ones.
+pub struct Mempool {
+    /// Transactions by hash
+    txs: HashMap<blake3::Hash, MempoolEntry>,
+    /// Transactions in block inclusion order
+    order: BTreeSet<OrderKey>,
+    /// Nullifiers spent by pool transactions, and the spending transaction
+    nullifiers: HashMap<[u8; 32], blake3::Hash>,
+    /// Maximum total serialized size of the pool
+    max_size: usize,
+    /// Current total serialized size of the pool
+    size: usize,
+    next_seq: u64,
+}
+
+impl Mempool {
+    /// Create an empty mempool holding at most `max_size` bytes of transactions.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            txs: HashMap::new(),
+            order: BTreeSet::new(),
+            nullifiers: HashMap::new(),
+            max_size,
+            size: 0,
+            next_seq: 0,
+        }
+    }
+
+    /// Check the transaction is well formed, independently of the chain state.
+    fn check(&self, tx: &Transaction, size: usize) -> Result<()> {
+        if tx.outputs.is_empty() || (tx.inputs.is_empty() && tx.clear_inputs.is_empty()) {
+            return Err(Error::MempoolRejected("transaction has no inputs or outputs".to_string()))
+        }
+
+        if size > self.max_size {
+            return Err(Error::MempoolRejected("transaction exceeds mempool size".to_string()))
+        }
+
+        let mut nullifiers: Vec<[u8; 32]> =
+            tx.inputs.iter().map(|input| input.revealed.nullifier.to_bytes()).collect();
+        nullifiers.sort_unstable();
+        nullifiers.dedup();
+        if nullifiers.len() != tx.inputs.len() {
+            return Err(Error::MempoolRejected("transaction spends a nullifier twice".to_string()))
+        }
+
+        Ok(())
+    }
+
+    /// Admit a transaction paying `fee` into the pool. Its state transition
+    /// is expected to have been validated by the caller. Returns the hashes
+    /// of any transactions evicted to make room.
+    pub fn insert(&mut self, tx: Transaction, fee: u64) -> Result<Vec<blake3::Hash>> {
+        self.insert_entry(tx, fee, false)
+    }
+
+    /// Admit a transaction created by this node. These are rebroadcast
+    /// until they get included in a block.
+    pub fn insert_local(&mut self, tx: Transaction, fee: u64) -> Result<Vec<blake3::Hash>> {
+        self.insert_entry(tx, fee, true)
+    }
+
+    fn insert_entry(
+        &mut self,
+        tx: Transaction,
+        fee: u64,
+        local: bool,
+    ) -> Result<Vec<blake3::Hash>> {
+        let hash = blake3::hash(&serialize(&tx));
+        if self.txs.contains_key(&hash) {
+            return Err(Error::MempoolDuplicate)
+        }
+
+        let size = tx.encode(&mut std::io::sink())?;
+        self.check(&tx, size)?;
+
+        for input in &tx.inputs {
+            if self.nullifiers.contains_key(&input.revealed.nullifier.to_bytes()) {
+                return Err(Error::MempoolConflict)
+            }
+        }
+
+        let entry = MempoolEntry { tx, fee, size, local, seq: self.next_seq };
+
+        // Find the cheapest transactions to evict, and only go ahead if the
+        // new one pays more than all of them.
+        let mut evicted = vec![];
+        let mut freed = 0;
+        for key in self.order.iter().rev() {
+            if self.size - freed + size <= self.max_size {
+                break
+            }
+            if key.0 .0 >= entry.fee_rate() {
+                return Err(Error::MempoolFull)
+            }
+            freed += self.txs[&key.2].size;
+            evicted.push(key.2);
+        }
+
+        for evicted_hash in &evicted {
+            debug!("Mempool::insert(): Evicting {}", evicted_hash);
+            self.remove(evicted_hash);
+        }
+
+        for input in &entry.tx.inputs {
+            self.nullifiers.insert(input.revealed.nullifier.to_bytes(), hash);
+        }
+        self.order.insert(entry.key(hash));
+        self.size += size;
+        self.next_seq += 1;
+        self.txs.insert(hash, entry);
+
+        Ok(evicted)
+    }
+
+    /// Remove a transaction from the pool, returning it if it was present.
+    pub fn remove(&mut self, hash: &blake3::Hash) -> Option<Transaction> {
+        let entry = self.txs.remove(hash)?;
+
+        self.order.remove(&entry.key(*hash));
+        for input in &entry.tx.inputs {
+            self.nullifiers.remove(&input.revealed.nullifier.to_bytes());
+        }
+        self.size -= entry.size;
+
+        Some(entry.tx)
+    }
+
+    /// Remove transactions included in a block, along with any pool
+    /// transactions conflicting with them.
+    pub fn remove_txs(&mut self, txs: &[Transaction]) {
+        for tx in txs {
+            self.remove(&blake3::hash(&serialize(tx)));
+
+            for input in &tx.inputs {
+                if let Some(hash) = self.nullifiers.get(&input.revealed.nullifier.to_bytes()) {
+                    let hash = *hash;
+                    debug!("Mempool::remove_txs(): Removing conflicting tx {}", hash);
+                    self.remove(&hash);
+                }
+            }
+        }
+    }
+
+    /// Check if the pool contains the given transaction hash.
+    pub fn contains(&self, hash: &blake3::Hash) -> bool {
+        self.txs.contains_key(hash)
+    }
+
+    /// Retrieve a transaction by its hash.
+    pub fn get(&self, hash: &blake3::Hash) -> Option<&Transaction> {
+        self.txs.get(hash).map(|entry| &entry.tx)
+    }
+
+    /// Iterate over pool transactions in block inclusion order.
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.iter_entries().map(|entry| &entry.tx)
+    }
+
+    /// Retrieve the transactions created by this node.
+    pub fn local_txs(&self) -> Vec<Transaction> {
+        self.iter_entries().filter(|entry| entry.local).map(|entry| entry.tx.clone()).collect()
+    }
+
+    fn iter_entries(&self) -> impl Iterator<Item = &MempoolEntry> {
+        self.order.iter().map(move |key| &self.txs[&key.2])
+    }
+
+    /// Number of transactions in the pool.
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Total serialized size of the pool transactions.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::{
+        group::{ff::Field, Group},
+        pallas,
+    };
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{
+        crypto::{
+            coin::Coin, keypair::Keypair, merkle_node::MerkleNode, note::Note,
+            nullifier::Nullifier, schnorr::Signature, BurnRevealedValues, MintRevealedValues,
+            Proof,
+        },
+        tx::{TransactionInput, TransactionOutput},
+    };
+
+    /// Transaction spending the given nullifiers, with proofs left out.
+    /// Transactions spending the same number of nullifiers have the same size.
+    fn test_tx(nullifiers: &[u64], fee: u64) -> Transaction {
+        let keypair = Keypair::random(&mut OsRng);
+
+        let inputs = nullifiers
+            .iter()
+            .map(|nullifier| TransactionInput {
+                burn_proof: Proof::new(vec![]),
+                revealed: BurnRevealedValues {
+                    value_commit: pallas::Point::identity(),
+                    token_commit: pallas::Point::identity(),
+                    nullifier: Nullifier(pallas::Base::from(*nullifier)),
+                    merkle_root: MerkleNode(pallas::Base::zero()),
+                    spend_hook: pallas::Base::zero(),
+                    user_data_enc: pallas::Base::zero(),
+                    signature_public: keypair.public,
+                    lock_slot: 0,
+                },
+                signature: Signature::dummy(),
+            })
+            .collect();
+
+        let note = Note {
+            serial: pallas::Base::zero(),
+            value: 0,
+            token_id: pallas::Base::zero(),
+            coin_blind: pallas::Base::zero(),
+            value_blind: pallas::Scalar::zero(),
+            token_blind: pallas::Scalar::zero(),
+            lock_slot: 0,
+            memo: vec![],
+        };
+        let output = TransactionOutput {
+            mint_proof: Proof::new(vec![]),
+            revealed: MintRevealedValues {
+                value_commit: pallas::Point::identity(),
+                token_commit: pallas::Point::identity(),
+                coin: Coin(pallas::Base::zero()),
+            },
+            enc_note: note.encrypt(&keypair.public).unwrap(),
+        };
+
+        Transaction {
+            clear_inputs: vec![],
+            inputs,
+            outputs: vec![output],
+            deploys: vec![],
+            calls: vec![],
+            call_signatures: vec![],
+            gas_limit: 0,
+            fee,
+            fee_token_blind: None,
+            escrow: None,
+        }
+    }
+
+    fn tx_size(tx: &Transaction) -> usize {
+        tx.encode(&mut std::io::sink()).unwrap()
+    }
+
+    #[test]
+    fn test_mempool_insert() -> Result<()> {
+        let mut mempool = Mempool::new(DEFAULT_MEMPOOL_SIZE);
+
+        let low = test_tx(&[1], 10);
+        let high = test_tx(&[2], 20);
+        assert!(mempool.insert(low.clone(), low.fee)?.is_empty());
+        assert!(mempool.insert_local(high.clone(), high.fee)?.is_empty());
+        assert!(matches!(mempool.insert(low.clone(), low.fee), Err(Error::MempoolDuplicate)));
+
+        // Best paying transactions come first
+        assert_eq!(mempool.iter().cloned().collect::<Vec<_>>(), vec![high.clone(), low.clone()]);
+        assert_eq!(mempool.local_txs(), vec![high.clone()]);
+        assert_eq!((mempool.len(), mempool.size()), (2, tx_size(&low) + tx_size(&high)));
+
+        let mut empty = test_tx(&[3], 10);
+        empty.outputs.clear();
+        assert!(matches!(mempool.insert(empty, 10), Err(Error::MempoolRejected(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_conflicts() -> Result<()> {
+        let mut mempool = Mempool::new(DEFAULT_MEMPOOL_SIZE);
+        mempool.insert(test_tx(&[1, 2], 10), 10)?;
+
+        // Spending a nullifier already spent in the pool
+        let conflicting = test_tx(&[2, 3], 100);
+        assert!(matches!(mempool.insert(conflicting, 100), Err(Error::MempoolConflict)));
+
+        // Spending a nullifier twice in the same transaction
+        let double_spend = test_tx(&[4, 4], 10);
+        assert!(matches!(mempool.insert(double_spend, 10), Err(Error::MempoolRejected(_))));
+
+        assert_eq!(mempool.len(), 1);
+        mempool.insert(test_tx(&[3], 10), 10)?;
+        assert_eq!(mempool.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_eviction() -> Result<()> {
+        let txs: Vec<Transaction> =
+            [10, 20, 5, 30].iter().map(|fee| test_tx(&[*fee], *fee)).collect();
+        let size = tx_size(&txs[0]);
+        let mut mempool = Mempool::new(2 * size);
+
+        mempool.insert(txs[0].clone(), txs[0].fee)?;
+        mempool.insert(txs[1].clone(), txs[1].fee)?;
+
+        // A full pool only takes transactions paying more than the cheapest
+        assert!(matches!(mempool.insert(txs[2].clone(), txs[2].fee), Err(Error::MempoolFull)));
+
+        let evicted = mempool.insert(txs[3].clone(), txs[3].fee)?;
+        assert_eq!(evicted, vec![blake3::hash(&serialize(&txs[0]))]);
+        assert_eq!(
+            mempool.iter().cloned().collect::<Vec<_>>(),
+            vec![txs[3].clone(), txs[1].clone()]
+        );
+        assert_eq!(mempool.size(), 2 * size);
+
+        // The nullifier of the evicted transaction can be spent again
+        let respend = test_tx(&[10], 40);
+        assert_eq!(mempool.insert(respend, 40)?, vec![blake3::hash(&serialize(&txs[1]))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_remove_txs() -> Result<()> {
+        let mut mempool = Mempool::new(DEFAULT_MEMPOOL_SIZE);
+        let included = test_tx(&[1], 10);
+        let conflicting = test_tx(&[2], 10);
+        let pending = test_tx(&[3], 10);
+        for tx in [&included, &conflicting, &pending] {
+            mempool.insert(tx.clone(), tx.fee)?;
+        }
+
+        // A block including one pool transaction, and another spending the
+        // nullifier of a second one
+        let block_txs = vec![included.clone(), test_tx(&[2], 10)];
+        mempool.remove_txs(&block_txs);
+
+        assert_eq!(mempool.iter().cloned().collect::<Vec<_>>(), vec![pending.clone()]);
+        assert_eq!(mempool.size(), tx_size(&pending));
+
+        // Its nullifiers are free again
+        mempool.insert(test_tx(&[1, 2], 10), 10)?;
+        assert_eq!(mempool.len(), 2);
+        Ok(())
+    }
+}
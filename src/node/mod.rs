@@ -4,5 +4,23 @@ pub use client::Client;
 pub mod state;
 pub use state::State;
 
+pub mod dispatch;
+pub use dispatch::Dispatcher;
+
 pub mod memorystate;
 pub use memorystate::MemoryState;
+
+pub mod mempool;
+pub use mempool::Mempool;
+
+pub mod orphanpool;
+pub use orphanpool::OrphanPool;
+
+pub mod issuance;
+pub use issuance::TokenIssuance;
+
+pub mod token_registry;
+pub use token_registry::TokenRegistry;
+
+pub mod circuit_registry;
+pub use circuit_registry::{CircuitRegistry, CircuitTable};
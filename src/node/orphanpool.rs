@@ -0,0 +1,104 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{tx::Transaction, util::serial::serialize};
+
+/// Default time an orphan transaction is held for before being dropped.
+pub const DEFAULT_ORPHAN_WINDOW: Duration = Duration::from_secs(600);
+/// Default maximum number of orphan transactions held.
+pub const DEFAULT_ORPHAN_COUNT: usize = 1000;
+
+/// Pool of transactions that can't be validated yet, because they reference
+/// Merkle roots we don't know about. Usually those spend coins created by
+/// transactions in blocks we haven't received yet, so orphans are validated
+/// again as new blocks arrive, until their window runs out.
+pub struct OrphanPool {
+    /// Orphan transactions by hash, with their arrival time
+    txs: HashMap<blake3::Hash, (Transaction, Instant)>,
+    /// Transaction hashes in arrival order
+    order: VecDeque<blake3::Hash>,
+    /// Time an orphan is held for
+    window: Duration,
+    /// Maximum number of orphans held
+    max_count: usize,
+}
+
+impl OrphanPool {
+    pub fn new(window: Duration, max_count: usize) -> Self {
+        Self { txs: HashMap::new(), order: VecDeque::new(), window, max_count }
+    }
+
+    /// Set the time orphans are held for.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Hold a transaction that arrived at `received`. The oldest orphan is
+    /// dropped if the pool is full. Returns `false` if it was already held.
+    pub fn insert(&mut self, tx: Transaction, received: Instant) -> bool {
+        let hash = blake3::hash(&serialize(&tx));
+        if self.txs.contains_key(&hash) {
+            return false
+        }
+
+        if self.txs.len() >= self.max_count {
+            if let Some(oldest) = self.order.pop_front() {
+                debug!("OrphanPool::insert(): Pool full, dropping {}", oldest);
+                self.txs.remove(&oldest);
+            }
+        }
+
+        self.txs.insert(hash, (tx, received));
+        self.order.push_back(hash);
+        true
+    }
+
+    /// Check if the pool holds the given transaction hash.
+    pub fn contains(&self, hash: &blake3::Hash) -> bool {
+        self.txs.contains_key(hash)
+    }
+
+    /// Drop orphans held for longer than the window.
+    /// Returns the number of dropped transactions.
+    pub fn expire(&mut self) -> usize {
+        let mut expired = 0;
+        while let Some(hash) = self.order.front() {
+            if self.txs[hash].1.elapsed() < self.window {
+                break
+            }
+            let hash = self.order.pop_front().unwrap();
+            self.txs.remove(&hash);
+            expired += 1;
+        }
+
+        if expired > 0 {
+            debug!("OrphanPool::expire(): Dropped {} expired orphans", expired);
+        }
+        expired
+    }
+
+    /// Take all orphans out of the pool in arrival order, along with their
+    /// arrival time, so they can be validated again.
+    pub fn drain(&mut self) -> Vec<(Transaction, Instant)> {
+        let mut ret = Vec::with_capacity(self.order.len());
+        for hash in self.order.drain(..) {
+            if let Some(entry) = self.txs.remove(&hash) {
+                ret.push(entry);
+            }
+        }
+        ret
+    }
+
+    /// Number of orphan transactions held.
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+}
@@ -1,9 +1,19 @@
+use std::sync::Arc;
+
 use incrementalmerkletree::{bridgetree::BridgeTree, Tree};
 use lazy_init::Lazy;
 use log::{debug, error};
 
+use super::{
+    circuit_registry::CircuitTable,
+    dispatch::Dispatcher,
+    issuance::{token_issuance_id, TokenIssuance},
+};
 use crate::{
-    blockchain::{nfstore::NullifierStore, rootstore::RootStore},
+    blockchain::{
+        circuitstore::circuit_registry_id, nfstore::NullifierStore, rootstore::RootStore,
+        CircuitStore, ContractStateOverlay, ContractStateStore, ContractStore, StateWrite,
+    },
     crypto::{
         coin::Coin,
         constants::MERKLE_DEPTH,
@@ -14,6 +24,11 @@ use crate::{
         proof::VerifyingKey,
         OwnCoin,
     },
+    runtime::{
+        import::{ChainStateReader, StateReader},
+        vm_runtime::Runtime,
+    },
+    swap::htlc::{htlc_contract_id, HtlcContract},
     tx::Transaction,
     wallet::walletdb::WalletPtr,
     zk::circuit::{BurnContract, MintContract},
@@ -30,10 +45,31 @@ pub trait ProgramState {
     fn is_valid_merkle(&self, merkle: &MerkleNode) -> bool;
     /// Check if the nullifier has been seen already
     fn nullifier_exists(&self, nullifier: &Nullifier) -> bool;
+    /// Check if a contract is already deployed under the given ID
+    fn contract_exists(&self, contract_id: &[u8; 32]) -> bool;
+    /// Retrieve the wasm bincode of a deployed contract
+    fn contract_bincode(&self, contract_id: &[u8; 32]) -> Option<Vec<u8>>;
+    /// Contract key/value state as of this state, for native contracts and
+    /// the wasm runtime to read from and write into
+    fn contract_state(&self) -> ContractStateOverlay;
+    /// Read access to this state with the given contract state on top,
+    /// scoped to a single contract
+    fn state_reader(
+        &self,
+        contract_state: ContractStateOverlay,
+        contract_id: [u8; 32],
+    ) -> Arc<dyn StateReader>;
+    /// Dispatcher routing contract calls to their handlers
+    fn dispatcher(&self) -> &Dispatcher;
     /// Mint proof verification key
     fn mint_vk(&self) -> &VerifyingKey;
     /// Burn proof verification key
     fn burn_vk(&self) -> &VerifyingKey;
+    /// Verifying keys of the circuits registered on chain
+    fn circuits(&self) -> &CircuitTable;
+    /// Slot transactions are validated at. Coins time-locked past it
+    /// can't be spent.
+    fn slot(&self) -> u64;
 }
 
 /// A struct representing a state update.
@@ -46,22 +82,107 @@ pub struct StateUpdate {
     pub coins: Vec<Coin>,
     /// All encrypted notes in a transaction
     pub enc_notes: Vec<EncryptedNote>,
+    /// Contracts deployed in a transaction, with their wasm bincode
+    pub contracts: Vec<([u8; 32], Vec<u8>)>,
+    /// Contract state written by a transaction's contract calls
+    pub state_writes: Vec<StateWrite>,
+    /// Slot the transaction was validated at
+    pub slot: u64,
 }
 
 /// State transition function
 pub fn state_transition<S: ProgramState>(state: &S, tx: Transaction) -> VerifyResult<StateUpdate> {
-    // Check the public keys in the clear inputs to see if they're coming
-    // from a valid cashier or faucet.
+    transition(state, tx, true)
+}
+
+/// State transition function for a transaction whose mint and burn proofs
+/// and signatures were already verified, for example by
+/// [`verify_transactions`] along with the rest of a block.
+///
+/// [`verify_transactions`]: crate::tx::verify_transactions
+pub fn state_transition_verified<S: ProgramState>(
+    state: &S,
+    tx: Transaction,
+) -> VerifyResult<StateUpdate> {
+    transition(state, tx, false)
+}
+
+fn transition<S: ProgramState>(
+    state: &S,
+    tx: Transaction,
+    verify: bool,
+) -> VerifyResult<StateUpdate> {
+    // Swap locks hold exactly the value the transaction escrows
+    debug!(target: "state_transition", "Iterate swap locks");
+    let locks = HtlcContract::locks(&tx.calls);
+    let locked = locks.iter().try_fold(0u64, |total, (_, params)| match &tx.escrow {
+        Some(escrow) if escrow.token_id == params.token_id => total.checked_add(params.value),
+        _ => None,
+    });
+    match (&tx.escrow, locked) {
+        (None, Some(0)) => {}
+        (Some(escrow), Some(value)) if escrow.value == value => {}
+        _ => {
+            error!(target: "state_transition", "Escrow doesn't match the swap locks");
+            return Err(VerifyFailed::UnmatchedEscrow)
+        }
+    }
+
+    // Clear inputs of tokens created in the issuance contract must be
+    // backed by a mint call of their authority, which enforces the supply
+    // cap. Clear inputs can also release the funds of a swap lock, to the
+    // party claiming or refunding it. Other clear inputs must come from a
+    // valid cashier or faucet.
     debug!(target: "state_transition", "Iterate clear_inputs");
+    let issuance = state.state_reader(state.contract_state(), token_issuance_id());
+    let htlc = state.state_reader(state.contract_state(), htlc_contract_id());
+    let mut mints = TokenIssuance::mints(&tx.calls);
+    let mut releases = match HtlcContract::releases(&tx.calls, htlc.as_ref()) {
+        Ok(v) => v,
+        Err(e) => return Err(VerifyFailed::InternalError(e.to_string())),
+    };
     for (i, input) in tx.clear_inputs.iter().enumerate() {
         let pk = &input.signature_public;
-        // TODO: this depends on the token ID
+
+        if let Some(pos) = mints.iter().position(|(_, mint, signer)| {
+            mint.token_id == input.token_id && mint.value == input.value && signer == pk
+        }) {
+            mints.remove(pos);
+            continue
+        }
+
+        if let Some(pos) = releases.iter().position(|(_, params, party)| {
+            params.token_id == input.token_id && params.value == input.value && party == pk
+        }) {
+            releases.remove(pos);
+            continue
+        }
+
+        match TokenIssuance::get_token(issuance.as_ref(), &input.token_id) {
+            Ok(None) => {}
+            Ok(Some(_)) => {
+                error!(target: "state_transition", "Clear input {} has no mint call", i);
+                return Err(VerifyFailed::UnbackedClearInput(i))
+            }
+            Err(e) => return Err(VerifyFailed::InternalError(e.to_string())),
+        }
+
         if !state.is_valid_cashier_public_key(pk) && !state.is_valid_faucet_public_key(pk) {
             error!(target: "state_transition", "Invalid pubkey for clear input: {:?}", pk);
             return Err(VerifyFailed::InvalidCashierOrFaucetKey(i))
         }
     }
 
+    if let Some((i, _, _)) = mints.first() {
+        error!(target: "state_transition", "Mint call {} has no clear input", i);
+        return Err(VerifyFailed::UnmatchedMint(*i))
+    }
+
+    if let Some((i, _, _)) = releases.first() {
+        error!(target: "state_transition", "Swap release call {} has no clear input", i);
+        return Err(VerifyFailed::UnmatchedRelease(*i))
+    }
+
     // Nullifiers in the transaction
     let mut nullifiers = Vec::with_capacity(tx.inputs.len());
 
@@ -88,18 +209,85 @@ pub fn state_transition<S: ProgramState>(state: &S, tx: Transaction) -> VerifyRe
             return Err(VerifyFailed::NullifierExists(i))
         }
 
+        // Time-locked coins can't be spent before their lock slot.
+        if input.revealed.lock_slot > state.slot() {
+            error!(
+                target: "state_transition",
+                "Coin locked until slot {} (input {})", input.revealed.lock_slot, i
+            );
+            return Err(VerifyFailed::CoinLocked(i))
+        }
+
         nullifiers.push(input.revealed.nullifier);
     }
 
-    debug!(target: "state_transition", "Verifying zk proofs");
-    match tx.verify(state.mint_vk(), state.burn_vk()) {
-        Ok(()) => debug!(target: "state_transition", "Verified successfully"),
-        Err(e) => {
-            error!(target: "state_transition", "Failed verifying zk proofs: {}", e);
-            return Err(VerifyFailed::ProofVerifyFailed(e.to_string()))
+    // Deployed contracts must be runnable, and their IDs unused.
+    let mut contracts = Vec::with_capacity(tx.deploys.len());
+
+    debug!(target: "state_transition", "Iterate deploys");
+    for (i, deploy) in tx.deploys.iter().enumerate() {
+        if let Err(e) = Runtime::validate(&deploy.wasm_bincode) {
+            error!(target: "state_transition", "Invalid contract bincode (deploy {}): {}", i, e);
+            return Err(VerifyFailed::InvalidContract(i, e.to_string()))
+        }
+
+        let contract_id = deploy.contract_id();
+        if state.contract_exists(&contract_id) || contracts.iter().any(|(id, _)| id == &contract_id)
+        {
+            error!(target: "state_transition", "Contract already deployed (deploy {})", i);
+            return Err(VerifyFailed::ContractExists(i))
+        }
+
+        contracts.push((contract_id, deploy.wasm_bincode.clone()));
+    }
+
+    if verify {
+        debug!(target: "state_transition", "Verifying zk proofs");
+        match tx.verify(state.mint_vk(), state.burn_vk()) {
+            Ok(()) => debug!(target: "state_transition", "Verified successfully"),
+            Err(e) => {
+                error!(target: "state_transition", "Failed verifying zk proofs: {}", e);
+                return Err(VerifyFailed::ProofVerifyFailed(e.to_string()))
+            }
+        }
+    }
+
+    // Call proofs must be of a circuit version accepted at this slot, and
+    // verify against its key. They're not covered by the verification of
+    // the mint and burn proofs, so they're verified here either way.
+    debug!(target: "state_transition", "Iterate call proofs");
+    for (i, call) in tx.calls.iter().enumerate() {
+        for proof in &call.proofs {
+            let circuits = state.circuits();
+            let vk = match circuits.verifying_key_at(&proof.circuit, proof.version, state.slot()) {
+                Some(vk) => vk,
+                None => {
+                    error!(
+                        target: "state_transition",
+                        "Inactive circuit {} v{} (call {})", proof.circuit, proof.version, i
+                    );
+                    return Err(VerifyFailed::InactiveCircuit(
+                        i,
+                        proof.circuit.clone(),
+                        proof.version,
+                    ))
+                }
+            };
+
+            if let Err(e) = proof.proof.verify(&vk, &proof.public_inputs) {
+                error!(
+                    target: "state_transition",
+                    "Failed verifying {} v{} proof (call {}): {}",
+                    proof.circuit, proof.version, i, e
+                );
+                return Err(VerifyFailed::CallProof(i, proof.circuit.clone(), proof.version))
+            }
         }
     }
 
+    debug!(target: "state_transition", "Dispatch contract calls");
+    let state_writes = state.dispatcher().dispatch(state, &tx.calls, tx.gas_limit)?;
+
     // Newly created coins for this transaction
     let mut coins = Vec::with_capacity(tx.outputs.len());
     let mut enc_notes = Vec::with_capacity(tx.outputs.len());
@@ -109,7 +297,7 @@ pub fn state_transition<S: ProgramState>(state: &S, tx: Transaction) -> VerifyRe
         enc_notes.push(output.enc_note);
     }
 
-    Ok(StateUpdate { nullifiers, coins, enc_notes })
+    Ok(StateUpdate { nullifiers, coins, enc_notes, contracts, state_writes, slot: state.slot() })
 }
 
 /// Struct holding the state which we can apply a [`StateUpdate`] onto.
@@ -122,6 +310,14 @@ pub struct State {
     pub merkle_roots: RootStore,
     /// Nullifiers prevent double-spending
     pub nullifiers: NullifierStore,
+    /// Deployed contracts
+    pub contracts: ContractStore,
+    /// Key/value state of deployed contracts
+    pub contract_state: ContractStateStore,
+    /// Dispatcher routing contract calls to their handlers
+    pub dispatcher: Dispatcher,
+    /// Verifying keys of the circuits registered on chain
+    pub circuits: CircuitTable,
     /// List of Cashier public keys
     pub cashier_pubkeys: Vec<PublicKey>,
     /// List of Faucet public keys
@@ -130,6 +326,8 @@ pub struct State {
     pub mint_vk: Lazy<VerifyingKey>,
     /// Verifying key for the Burn ZK proof
     pub burn_vk: Lazy<VerifyingKey>,
+    /// Slot the last applied state update was validated at
+    pub slot: u64,
 }
 
 impl State {
@@ -145,6 +343,20 @@ impl State {
         debug!("Existing nullifiers: {:#?}", self.nullifiers.get_all()?);
         debug!("Update's nullifiers: {:#?}", update.nullifiers);
         self.nullifiers.insert(&update.nullifiers)?;
+        wallet.confirm_nullifiers(&update.nullifiers, update.slot).await?;
+
+        debug!(target: "state_apply", "Register deployed contracts");
+        self.contracts.insert(&update.contracts)?;
+
+        debug!(target: "state_apply", "Write contract state");
+        self.contract_state.apply(&update.state_writes)?;
+        self.slot = update.slot;
+
+        if update.state_writes.iter().any(|w| w.contract_id == circuit_registry_id()) {
+            debug!(target: "state_apply", "Load registered circuits");
+            self.circuits.load(&CircuitStore::new(self.contract_state.clone()), self.slot)?;
+        }
+        self.circuits.retire(self.slot);
 
         debug!(target: "state_apply", "Update Merkle tree and witnesses");
         for (coin, enc_note) in update.coins.into_iter().zip(update.enc_notes.iter()) {
@@ -177,7 +389,7 @@ impl State {
                     // for them to be accepted.
                     // Don't trust - verify.
 
-                    wallet.put_own_coin(own_coin).await?;
+                    wallet.put_own_coin(own_coin, self.slot).await?;
 
                     if let Some(ch) = notify.clone() {
                         debug!(target: "state_apply", "Send a notification");
@@ -186,19 +398,44 @@ impl State {
                     }
                 }
             }
-
-            // Save updated merkle tree into the wallet.
-            wallet.put_tree(&self.tree).await?;
         }
 
         debug!(target: "state_apply", "Finished apply() successfully.");
         Ok(())
     }
 
+    /// Append the coins of an already applied transaction to the Merkle
+    /// tree, marking our own coins for witnessing. This is used to bring a
+    /// tree checkpoint up to date, so nothing else is touched.
+    pub fn replay_tx(&mut self, tx: &Transaction, secret_keys: &[SecretKey]) {
+        for output in &tx.outputs {
+            self.tree.append(&MerkleNode(output.revealed.coin.0));
+
+            if secret_keys
+                .iter()
+                .any(|secret| State::try_decrypt_note(&output.enc_note, *secret).is_some())
+            {
+                self.tree.witness();
+            }
+        }
+    }
+
+    /// Load a deployed contract into a wasm runtime, so it can be called
+    /// using at most `gas_limit` gas. Returns `None` if no contract is
+    /// deployed under the given ID.
+    pub fn load_contract(&self, contract_id: &[u8; 32], gas_limit: u64) -> Result<Option<Runtime>> {
+        match self.contracts.get(contract_id)? {
+            Some(wasm_bincode) => Ok(Some(Runtime::new(&wasm_bincode, gas_limit)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Try to decrypt a note with the incoming viewing key of `secret`,
+    /// falling back to `secret` itself for notes sent to plain addresses.
     pub fn try_decrypt_note(ciphertext: &EncryptedNote, secret: SecretKey) -> Option<Note> {
-        match ciphertext.decrypt(&secret) {
+        match ciphertext.decrypt(&secret.view_key()) {
             Ok(note) => Some(note),
-            Err(_) => None,
+            Err(_) => ciphertext.decrypt(&secret).ok(),
         }
     }
 }
@@ -232,6 +469,41 @@ impl ProgramState for State {
         false
     }
 
+    fn contract_exists(&self, contract_id: &[u8; 32]) -> bool {
+        debug!(target: "state_transition", "Checking if contract exists");
+        if let Ok(exists) = self.contracts.contains(contract_id) {
+            return exists
+        }
+        // FIXME: An error here means a db issue
+        false
+    }
+
+    fn contract_bincode(&self, contract_id: &[u8; 32]) -> Option<Vec<u8>> {
+        // FIXME: An error here means a db issue
+        self.contracts.get(contract_id).unwrap_or(None)
+    }
+
+    fn contract_state(&self) -> ContractStateOverlay {
+        ContractStateOverlay::new(self.contract_state.clone())
+    }
+
+    fn state_reader(
+        &self,
+        contract_state: ContractStateOverlay,
+        contract_id: [u8; 32],
+    ) -> Arc<dyn StateReader> {
+        Arc::new(ChainStateReader::from_stores(
+            self.merkle_roots.clone(),
+            self.nullifiers.clone(),
+            contract_state,
+            contract_id,
+        ))
+    }
+
+    fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
     fn mint_vk(&self) -> &VerifyingKey {
         self.mint_vk.get_or_create(build_mint_vk)
     }
@@ -239,6 +511,14 @@ impl ProgramState for State {
     fn burn_vk(&self) -> &VerifyingKey {
         self.burn_vk.get_or_create(build_burn_vk)
     }
+
+    fn circuits(&self) -> &CircuitTable {
+        &self.circuits
+    }
+
+    fn slot(&self) -> u64 {
+        self.slot
+    }
 }
 
 fn build_mint_vk() -> VerifyingKey {
@@ -0,0 +1,262 @@
+use std::io;
+
+use group::ff::PrimeField;
+use log::debug;
+
+use crate::{
+    crypto::{keypair::PublicKey, types::DrkTokenId},
+    node::dispatch::{Dispatcher, NativeContract, Writes},
+    runtime::import::StateReader,
+    tx::ContractCall,
+    util::{
+        net_name::NetworkName,
+        parse::MAX_DECIMALS,
+        serial::{deserialize, serialize, Decodable, Encodable, SerialDecodable, SerialEncodable},
+    },
+    Error::{
+        TokenAlreadyRegistered, TokenInvalidMetadata, TokenRegistryInvalidCall, TokenTickerTaken,
+    },
+    Result,
+};
+
+/// Hashed into the ID the token registry contract is registered under.
+const TOKEN_REGISTRY_DOMAIN: &[u8] = b"DarkFi:TokenRegistry";
+
+/// Prefix of the state keys mapping tickers to token IDs.
+const TICKER_KEY_PREFIX: &[u8] = b"ticker:";
+
+/// Longest ticker a token can be registered under.
+pub const MAX_TICKER_LEN: usize = 12;
+
+/// ID of the native token registry contract.
+pub fn token_registry_id() -> [u8; 32] {
+    *blake3::hash(TOKEN_REGISTRY_DOMAIN).as_bytes()
+}
+
+/// Functions of the token registry contract, used as the `func_id` of its
+/// calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TokenRegistryFunction {
+    Register = 0x00,
+}
+
+impl TryFrom<u8> for TokenRegistryFunction {
+    type Error = crate::Error;
+
+    fn try_from(b: u8) -> Result<Self> {
+        match b {
+            0x00 => Ok(Self::Register),
+            _ => Err(TokenRegistryInvalidCall(format!("Unknown function {}", b))),
+        }
+    }
+}
+
+/// How the supply of a token comes to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuancePolicy {
+    /// Wrapped asset of another network, minted by cashiers against
+    /// deposits on it
+    Bridged(NetworkName),
+    /// Issued on DarkFi, up to `max_supply` if there is one
+    Native { max_supply: Option<u64> },
+}
+
+impl Encodable for IssuancePolicy {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        match self {
+            Self::Bridged(network) => Ok(0u8.encode(&mut s)? + network.encode(&mut s)?),
+            Self::Native { max_supply } => Ok(1u8.encode(&mut s)? + max_supply.encode(&mut s)?),
+        }
+    }
+}
+
+impl Decodable for IssuancePolicy {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let tag: u8 = Decodable::decode(&mut d)?;
+        match tag {
+            0 => Ok(Self::Bridged(Decodable::decode(&mut d)?)),
+            1 => Ok(Self::Native { max_supply: Decodable::decode(&mut d)? }),
+            _ => Err(TokenInvalidMetadata(format!("Unknown issuance policy {}", tag))),
+        }
+    }
+}
+
+/// Registry entry of a token.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TokenMetadata {
+    pub token_id: DrkTokenId,
+    /// Uppercase alphanumeric ticker, unique in the registry
+    pub ticker: String,
+    /// Decimal places of the token's amounts on DarkFi
+    pub decimals: u16,
+    pub issuance: IssuancePolicy,
+}
+
+impl TokenMetadata {
+    /// Check the metadata is well formed.
+    pub fn validate(&self) -> Result<()> {
+        if self.ticker.is_empty() || self.ticker.len() > MAX_TICKER_LEN {
+            return Err(TokenInvalidMetadata(format!(
+                "Ticker must be 1 to {} characters",
+                MAX_TICKER_LEN
+            )))
+        }
+        if !self.ticker.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Err(TokenInvalidMetadata("Ticker must be uppercase alphanumeric".to_string()))
+        }
+        if self.decimals > MAX_DECIMALS {
+            return Err(TokenInvalidMetadata(format!("Decimals can't exceed {}", MAX_DECIMALS)))
+        }
+        if self.issuance == IssuancePolicy::Bridged(NetworkName::DarkFi) {
+            return Err(TokenInvalidMetadata("DarkFi tokens can't be bridged".to_string()))
+        }
+        Ok(())
+    }
+
+    /// Call registering the token, signed by a registry authority.
+    pub fn register_call(&self, authority: PublicKey) -> ContractCall {
+        ContractCall {
+            contract_id: token_registry_id(),
+            func_id: TokenRegistryFunction::Register as u8,
+            call_data: serialize(self),
+            proofs: vec![],
+            signature_public: vec![authority],
+        }
+    }
+}
+
+/// Native contract mapping token IDs to their [`TokenMetadata`].
+///
+/// Tokens are registered by governance: registrations must be signed by
+/// one of the registry's authority keys. Entries are immutable once
+/// registered, as changing the decimals of a token would change the value
+/// of every existing note of it.
+pub struct TokenRegistry {
+    authorities: Vec<PublicKey>,
+}
+
+impl TokenRegistry {
+    pub fn new(authorities: Vec<PublicKey>) -> Self {
+        Self { authorities }
+    }
+
+    /// Read the metadata of a token from the registry state.
+    pub fn get_token(
+        state: &dyn StateReader,
+        token_id: &DrkTokenId,
+    ) -> Result<Option<TokenMetadata>> {
+        match state.get(&token_id.to_repr())? {
+            Some(bytes) => Ok(Some(deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the metadata of the token registered under `ticker`.
+    pub fn get_token_by_ticker(
+        state: &dyn StateReader,
+        ticker: &str,
+    ) -> Result<Option<TokenMetadata>> {
+        match state.get(&ticker_key(ticker))? {
+            Some(bytes) => Self::get_token(state, &deserialize(&bytes)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Register the functions of the contract with the dispatcher.
+    pub fn register(self, dispatcher: &mut Dispatcher) {
+        dispatcher.register_function(
+            token_registry_id(),
+            TokenRegistryFunction::Register as u8,
+            "TokenRegistry::register()",
+            move |call, token, state, _| self.register_token(call, token, state),
+        );
+    }
+
+    fn register_token(
+        &self,
+        call: &ContractCall,
+        token: TokenMetadata,
+        state: &dyn StateReader,
+    ) -> Result<Writes> {
+        if call.signature_public.len() != 1 || !self.authorities.contains(&call.signature_public[0])
+        {
+            return Err(TokenRegistryInvalidCall(
+                "Call must be signed by a registry authority".to_string(),
+            ))
+        }
+
+        token.validate()?;
+        if Self::get_token(state, &token.token_id)?.is_some() {
+            return Err(TokenAlreadyRegistered)
+        }
+        let ticker_key = ticker_key(&token.ticker);
+        if state.get(&ticker_key)?.is_some() {
+            return Err(TokenTickerTaken(token.ticker))
+        }
+
+        debug!(target: "token_registry", "Registering token {}", token.ticker);
+        Ok(vec![
+            (token.token_id.to_repr().to_vec(), serialize(&token)),
+            (ticker_key, serialize(&token.token_id)),
+        ])
+    }
+}
+
+fn ticker_key(ticker: &str) -> Vec<u8> {
+    [TICKER_KEY_PREFIX, ticker.as_bytes()].concat()
+}
+
+impl NativeContract for TokenRegistry {
+    fn execute(&self, call: &ContractCall, state: &dyn StateReader, _slot: u64) -> Result<Writes> {
+        match TokenRegistryFunction::try_from(call.func_id)? {
+            TokenRegistryFunction::Register => {
+                self.register_token(call, deserialize(&call.call_data)?, state)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{crypto::keypair::Keypair, node::dispatch::MemoryState};
+
+    #[test]
+    fn test_token_registry() {
+        let authority = Keypair::random(&mut OsRng).public;
+        let registry = TokenRegistry::new(vec![authority]);
+        let state = MemoryState::default();
+
+        let token = TokenMetadata {
+            token_id: pallas::Base::from(1),
+            ticker: "WBTC".to_string(),
+            decimals: 8,
+            issuance: IssuancePolicy::Bridged(NetworkName::Bitcoin),
+        };
+
+        let mut call = token.register_call(authority);
+        call.signature_public = vec![Keypair::random(&mut OsRng).public];
+        assert!(registry.execute(&call, &state, 0).is_err());
+
+        let writes = registry.execute(&token.register_call(authority), &state, 0).unwrap();
+        state.apply(writes);
+        assert_eq!(
+            TokenRegistry::get_token_by_ticker(&state, "WBTC").unwrap(),
+            Some(token.clone())
+        );
+
+        // IDs and tickers are unique
+        assert!(registry.execute(&token.register_call(authority), &state, 0).is_err());
+        let other = TokenMetadata { token_id: pallas::Base::from(2), ..token.clone() };
+        assert!(registry.execute(&other.register_call(authority), &state, 0).is_err());
+
+        let invalid = TokenMetadata { ticker: "wbtc".to_string(), ..other };
+        assert!(invalid.validate().is_err());
+        let invalid = TokenMetadata { decimals: MAX_DECIMALS + 1, ..token };
+        assert!(invalid.validate().is_err());
+    }
+}
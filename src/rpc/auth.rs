@@ -0,0 +1,120 @@
+//! JSON-RPC method authentication with API tokens.
+use fxhash::FxHashMap;
+use log::info;
+use toml::Value;
+
+use crate::Result;
+
+/// Method a connection calls with a token as its only parameter to
+/// authenticate the requests that follow.
+pub const AUTH_METHOD: &str = "auth";
+
+/// API tokens, each with the methods it grants access to.
+///
+/// A permission is either a method name (`wallet.get_balances`), a
+/// namespace (`blockchain.*`), or `*` for every method. Connections that
+/// did not authenticate can't call anything but [`AUTH_METHOD`].
+#[derive(Debug, Clone, Default)]
+pub struct RpcAuth {
+    tokens: FxHashMap<String, Vec<String>>,
+}
+
+impl RpcAuth {
+    pub fn new(tokens: FxHashMap<String, Vec<String>>) -> Self {
+        Self { tokens }
+    }
+
+    /// Parse the API tokens declared in a TOML config, returning `None`
+    /// when there are none, leaving the methods open:
+    ///
+    /// ```toml
+    /// [rpc_token."0b6c..."]
+    /// permissions = ["ping", "blockchain.*", "wallet.get_balances"]
+    /// ```
+    pub fn from_config(data: &str) -> Result<Option<Self>> {
+        let mut tokens = FxHashMap::default();
+
+        if let Value::Table(map) = toml::from_str(data)? {
+            if let Some(Value::Table(declared)) = map.get("rpc_token") {
+                for (token, table) in declared {
+                    let permissions: Vec<String> = match table.get("permissions") {
+                        Some(Value::Array(perms)) => {
+                            perms.iter().filter_map(|p| p.as_str()).map(String::from).collect()
+                        }
+                        _ => vec![],
+                    };
+                    info!("Found JSON-RPC token with permissions: {:?}", permissions);
+                    tokens.insert(token.clone(), permissions);
+                }
+            }
+        }
+
+        if tokens.is_empty() {
+            return Ok(None)
+        }
+
+        Ok(Some(Self::new(tokens)))
+    }
+
+    /// Check if the token is declared.
+    pub fn is_token(&self, token: &str) -> bool {
+        self.tokens.contains_key(token)
+    }
+
+    /// Check if the token grants access to the method.
+    pub fn permits(&self, token: Option<&str>, method: &str) -> bool {
+        let permissions = match token.and_then(|t| self.tokens.get(t)) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        permissions.iter().any(|p| permission_matches(p, method))
+    }
+}
+
+fn permission_matches(permission: &str, method: &str) -> bool {
+    if permission == "*" || permission == method {
+        return true
+    }
+
+    match permission.strip_suffix(".*") {
+        Some(namespace) => match method.strip_prefix(namespace) {
+            Some(rest) => rest.starts_with('.'),
+            None => false,
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_auth() {
+        let config = r#"
+            [rpc_token."reader"]
+            permissions = ["ping", "blockchain.*", "wallet.get_balances"]
+
+            [rpc_token."admin"]
+            permissions = ["*"]
+        "#;
+        let auth = RpcAuth::from_config(config).unwrap().unwrap();
+
+        assert!(auth.is_token("reader"));
+        assert!(!auth.is_token("nobody"));
+
+        assert!(auth.permits(Some("reader"), "ping"));
+        assert!(auth.permits(Some("reader"), "blockchain.get_slot"));
+        assert!(auth.permits(Some("reader"), "wallet.get_balances"));
+        assert!(!auth.permits(Some("reader"), "wallet.keygen"));
+        assert!(!auth.permits(Some("reader"), "tx.transfer"));
+        assert!(!auth.permits(Some("reader"), "blockchainfoo.bar"));
+        assert!(auth.permits(Some("admin"), "tx.transfer"));
+
+        assert!(!auth.permits(None, "ping"));
+        assert!(!auth.permits(Some("nobody"), "ping"));
+
+        assert!(RpcAuth::from_config("rpc_listen = \"tcp://127.0.0.1:8340\"").unwrap().is_none());
+    }
+}
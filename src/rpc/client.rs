@@ -7,7 +7,10 @@ use log::{debug, error};
 use serde_json::{json, Value};
 use url::Url;
 
-use super::jsonrpc::{ErrorCode, JsonError, JsonRequest, JsonResult};
+use super::{
+    auth::AUTH_METHOD,
+    jsonrpc::{ErrorCode, JsonError, JsonRequest, JsonResult},
+};
 use crate::{
     net::{
         transport::Transport, TcpTransport, TorTransport, TransportName, TransportStream,
@@ -92,6 +95,14 @@ impl RpcClient {
         }
     }
 
+    /// Authenticate the connection with an API token, granting the
+    /// requests that follow the methods the token permits.
+    pub async fn auth(&self, token: &str) -> Result<()> {
+        let req = JsonRequest::new(AUTH_METHOD, json!([token]));
+        self.request(req).await?;
+        Ok(())
+    }
+
     /// Oneshot send a given JSON-RPC request over the instantiated client
     /// and close the channels on reply.
     pub async fn oneshot_request(&self, value: JsonRequest) -> Result<Value> {
@@ -0,0 +1,59 @@
+//! `dnet` JSON-RPC namespace, serving the typed introspection info of the
+//! p2p network of a node. Daemons route the methods of the namespace here,
+//! so every node answers monitoring tools with the same structure.
+use serde_json::{json, Value};
+
+use super::{
+    client::RpcClient,
+    jsonrpc::{ErrorCode, JsonError, JsonRequest, JsonResponse, JsonResult},
+};
+use crate::{
+    net::{dnet::NodeInfo, P2pPtr},
+    Result,
+};
+
+/// Prefix of the methods of the namespace.
+pub const DNET_PREFIX: &str = "dnet.";
+
+/// Check if the method belongs to the `dnet` namespace.
+pub fn is_dnet_method(method: &str) -> bool {
+    method.starts_with(DNET_PREFIX)
+}
+
+/// Handle a request to the `dnet` namespace:
+///
+/// * `dnet.get_info`: the whole [`NodeInfo`]
+/// * `dnet.get_channels`: the connected channels and their message counters
+/// * `dnet.get_sessions`: the inbound addresses and outbound slots
+/// * `dnet.get_hosts`: the pending and known host addresses
+/// * `dnet.get_protocols`: the protocol registry contents
+///
+/// ```text
+/// --> {"jsonrpc": "2.0", "method": "dnet.get_channels", "params": [], "id": 42}
+/// <-- {"jsonrpc": "2.0", "result": [{"addr": "tcp://...", ...}], "id": 42}
+/// ```
+pub async fn handle_request(p2p: &P2pPtr, req: JsonRequest) -> JsonResult {
+    let method = match req.method.as_str() {
+        Some(m) => m,
+        None => return JsonError::new(ErrorCode::MethodNotFound, None, req.id).into(),
+    };
+
+    let info = p2p.dnet_info().await;
+    let result = match method.strip_prefix(DNET_PREFIX) {
+        Some("get_info") => json!(info),
+        Some("get_channels") => json!(info.channels),
+        Some("get_sessions") => json!(info.sessions),
+        Some("get_hosts") => json!(info.hosts),
+        Some("get_protocols") => json!(info.protocols),
+        _ => return JsonError::new(ErrorCode::MethodNotFound, None, req.id).into(),
+    };
+
+    JsonResponse::new(result, req.id).into()
+}
+
+/// Fetch the introspection info of the node behind provided client.
+pub async fn get_info(client: &RpcClient) -> Result<NodeInfo> {
+    let req = JsonRequest::new("dnet.get_info", json!([]));
+    let rep: Value = client.request(req).await?;
+    Ok(serde_json::from_value(rep)?)
+}
@@ -3,6 +3,8 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+use crate::Error;
+
 /// JSON-RPC error codes.
 /// The error codes from and including -32768 to -32000 are reserved for pre-defined errors.
 #[derive(Debug, Clone)]
@@ -45,6 +47,241 @@ impl ErrorCode {
     }
 }
 
+impl From<ServerErrorCode> for ErrorCode {
+    fn from(code: ServerErrorCode) -> Self {
+        Self::ServerError(code.code())
+    }
+}
+
+/// Registry of the server error codes used by the daemons. Codes must
+/// never be renumbered, as clients match on them.
+///
+/// Codes predating the registry keep the values their daemon gave them,
+/// which may be shared between daemons. New codes are taken from the
+/// `-32000` to `-32099` range JSON-RPC leaves to servers, in namespaces
+/// telling which subsystem failed:
+///
+/// * `-32000` to `-32019`: request handling, common to all daemons
+/// * `-32020` to `-32039`: wallet
+/// * `-32040` to `-32059`: network
+/// * `-32060` to `-32079`: consensus and blockchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerErrorCode {
+    // Request handling
+    ParseError,
+    Nan,
+    LessThanNegOne,
+    InvalidAddressParam,
+    InvalidAmountParam,
+    InvalidToken,
+    Unauthorized,
+    RequestTimeout,
+    InvalidInvoice,
+    InvoiceExpired,
+
+    // Wallet
+    Keygen,
+    KeypairFetch,
+    KeypairNotFound,
+    InvalidKeypair,
+    DecryptionFailed,
+    WalletFailed,
+
+    // Network
+    NetworkNameError,
+    TxBroadcastFail,
+    NetworkFailed,
+
+    // Consensus and blockchain
+    NotYetSynced,
+    UnknownSlot,
+    UnknownCoin,
+    TxBuildFail,
+    TxRejected,
+    NotFound,
+    UnknownToken,
+
+    // faucetd
+    AmountExceedsLimit,
+    TimeLimitReached,
+    FaucetParseError,
+
+    // DHT daemons
+    UnknownKey,
+    QueryFailed,
+    KeyInsertFail,
+    KeyRemoveFail,
+    FileGenerationFail,
+}
+
+impl ServerErrorCode {
+    pub fn code(&self) -> i64 {
+        match self {
+            // darkfid
+            Self::Keygen => -32101,
+            Self::Nan => -32102,
+            Self::LessThanNegOne => -32103,
+            Self::KeypairFetch => -32104,
+            Self::KeypairNotFound => -32105,
+            Self::InvalidKeypair => -32106,
+            Self::UnknownSlot => -32107,
+            Self::TxBuildFail => -32108,
+            Self::NetworkNameError => -32109,
+            Self::ParseError => -32110,
+            Self::TxBroadcastFail => -32111,
+            Self::NotYetSynced => -32112,
+            Self::InvalidAddressParam => -32113,
+            Self::InvalidAmountParam => -32114,
+            Self::DecryptionFailed => -32115,
+            Self::UnknownCoin => -32116,
+
+            // faucetd
+            Self::AmountExceedsLimit => -32107,
+            Self::TimeLimitReached => -32108,
+            Self::FaucetParseError => -32109,
+
+            // DHT daemons
+            Self::UnknownKey => -35107,
+            Self::QueryFailed => -35108,
+            Self::KeyInsertFail => -35110,
+            Self::KeyRemoveFail => -35111,
+            Self::FileGenerationFail => -35113,
+
+            // Request handling
+            Self::InvalidToken => -32000,
+            Self::Unauthorized => -32001,
+            Self::RequestTimeout => -32002,
+            Self::InvalidInvoice => -32003,
+            Self::InvoiceExpired => -32004,
+
+            // Wallet
+            Self::WalletFailed => -32020,
+
+            // Network
+            Self::NetworkFailed => -32040,
+
+            // Consensus and blockchain
+            Self::TxRejected => -32060,
+            Self::NotFound => -32061,
+            Self::UnknownToken => -32062,
+        }
+    }
+
+    pub fn desc(&self) -> String {
+        let desc = match self {
+            Self::ParseError => "Parse error",
+            Self::Nan => "Not a number",
+            Self::LessThanNegOne => "Number cannot be lower than -1",
+            Self::InvalidAddressParam => "Invalid address parameter",
+            Self::InvalidAmountParam => "Invalid amount parameter",
+            Self::InvalidToken => "Invalid API token",
+            Self::Unauthorized => "Method not permitted",
+            Self::RequestTimeout => "Request timed out",
+            Self::InvalidInvoice => "Invalid invoice",
+            Self::InvoiceExpired => "Invoice expired",
+
+            Self::Keygen => "Failed generating keypair",
+            Self::KeypairFetch => "Failed fetching keypairs from wallet",
+            Self::KeypairNotFound => "Keypair not found",
+            Self::InvalidKeypair => "Invalid keypair",
+            Self::DecryptionFailed => "Decryption failed",
+            Self::WalletFailed => "Wallet operation failed",
+
+            Self::NetworkNameError => "Unknown network name",
+            Self::TxBroadcastFail => "Failed broadcasting transaction",
+            Self::NetworkFailed => "Network operation failed",
+
+            Self::NotYetSynced => "Blockchain not yet synced",
+            Self::UnknownSlot => "Did not find slot",
+            Self::UnknownCoin => "Coin not found in the Merkle tree",
+            Self::TxBuildFail => "Failed building transaction",
+            Self::TxRejected => "Transaction rejected",
+            Self::NotFound => "Not found in the blockchain",
+            Self::UnknownToken => "Token not found in the registry",
+
+            Self::AmountExceedsLimit => "Amount requested is higher than the faucet limit",
+            Self::TimeLimitReached => "Timeout not expired, try again later",
+            Self::FaucetParseError => "Parse error",
+
+            Self::UnknownKey => "Did not find key",
+            Self::QueryFailed => "Failed to query key",
+            Self::KeyInsertFail => "Failed to insert key",
+            Self::KeyRemoveFail => "Failed to remove key",
+            Self::FileGenerationFail => "Failed to generate file for key",
+        };
+
+        desc.to_string()
+    }
+}
+
+/// Registered server error code of a library error, for the failures
+/// clients can act upon.
+fn registered_code(err: &Error) -> Option<ServerErrorCode> {
+    let code = match err {
+        Error::NoteDecryptionFailed => ServerErrorCode::DecryptionFailed,
+        Error::KeypairPathNotFound => ServerErrorCode::KeypairNotFound,
+        Error::WalletEmptyPassword | Error::WalletTreeExists | Error::WalletSeedExists => {
+            ServerErrorCode::WalletFailed
+        }
+
+        Error::ConnectFailed |
+        Error::ConnectTimeout |
+        Error::ChannelStopped |
+        Error::ChannelTimeout |
+        Error::RequestTimeout |
+        Error::NetworkServiceStopped |
+        Error::NetworkOperationFailed |
+        Error::NetworkNotConnected => ServerErrorCode::NetworkFailed,
+
+        Error::MempoolRejected(_) |
+        Error::MempoolDuplicate |
+        Error::MempoolConflict |
+        Error::MempoolFull |
+        Error::VerifyFailed(_) => ServerErrorCode::TxRejected,
+        Error::SlotNotFound(_) => ServerErrorCode::UnknownSlot,
+        Error::CoinNotFound => ServerErrorCode::UnknownCoin,
+        Error::TransactionNotFound(_) |
+        Error::HeaderNotFound(_) |
+        Error::BlockNotFound(_) |
+        Error::BlockMetadataNotFound(_) |
+        Error::BlockFilterNotFound(_) => ServerErrorCode::NotFound,
+
+        Error::UnknownKey => ServerErrorCode::UnknownKey,
+
+        Error::InvalidInvoice(_) => ServerErrorCode::InvalidInvoice,
+        Error::InvoiceExpired => ServerErrorCode::InvoiceExpired,
+
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// Whether a library error comes from malformed input, which is the
+/// caller's fault.
+fn is_invalid_params(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::ParseFailed(_) |
+            Error::ParseIntError(_) |
+            Error::ParseFloatError(_) |
+            Error::UrlParse(_) |
+            Error::AddrParseError(_) |
+            Error::TokenParseError |
+            Error::TryFromSliceError(_) |
+            Error::DecodeError(_) |
+            Error::Utf8Error(_) |
+            Error::StrUtf8Error(_) |
+            Error::PublicKeyFromBytes |
+            Error::SecretKeyFromBytes |
+            Error::PublicKeyFromStr |
+            Error::SecretKeyFromStr |
+            Error::InvalidAddress |
+            Error::InvalidMnemonic(_) |
+            Error::InvalidDerivationPath(_)
+    )
+}
+
 /// Wrapping enum around the possible JSON-RPC object types.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -150,6 +387,9 @@ pub struct JsonErrorVal {
     pub code: Value,
     /// Error message
     pub message: Value,
+    /// Additional information about the error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 impl JsonError {
@@ -157,8 +397,38 @@ impl JsonError {
         let error = JsonErrorVal {
             code: json!(c.code()),
             message: if m.is_none() { json!(c.desc()) } else { json!(m.unwrap()) },
+            data: None,
         };
 
         Self { jsonrpc: json!("2.0"), error, id }
     }
+
+    /// Error object carrying a data payload with details about the error
+    pub fn with_data(c: ErrorCode, m: Option<String>, data: Value, id: Value) -> Self {
+        let mut err = Self::new(c, m, id);
+        err.error.data = Some(data);
+        err
+    }
+
+    /// Error object of a library error, with its registered code if any,
+    /// and the error itself as data payload.
+    pub fn from_error(err: &Error, id: Value) -> Self {
+        let data = json!({ "error": err.to_string() });
+
+        if let Some(code) = registered_code(err) {
+            return Self::with_data(code.into(), Some(code.desc()), data, id)
+        }
+
+        let code = if is_invalid_params(err) {
+            ErrorCode::InvalidParams
+        } else {
+            ErrorCode::InternalError
+        };
+        Self::with_data(code, None, data, id)
+    }
+}
+
+/// Build the JSON-RPC error object of a registered server error.
+pub fn server_error(e: ServerErrorCode, id: Value) -> JsonResult {
+    JsonError::new(e.into(), Some(e.desc()), id).into()
 }
@@ -1,9 +1,15 @@
 /// JSON-RPC primitives
 pub mod jsonrpc;
 
+/// JSON-RPC method authentication
+pub mod auth;
+
 /// Client-side JSON-RPC implementation
 pub mod client;
 
+/// Node introspection JSON-RPC namespace
+pub mod dnet;
+
 /// Server-side JSON-RPC implementation
 pub mod server;
 
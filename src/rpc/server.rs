@@ -1,25 +1,161 @@
 //! JSON-RPC server-side implementation.
-use async_std::sync::Arc;
+use async_std::{future::timeout, sync::Arc};
 use async_trait::async_trait;
-use futures::{AsyncReadExt, AsyncWriteExt};
+use futures::{future::join_all, AsyncReadExt, AsyncWriteExt};
 use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use smol::{lock::Semaphore, Timer};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
 use url::Url;
 
-use super::jsonrpc::{JsonRequest, JsonResult};
+use super::{
+    auth::{RpcAuth, AUTH_METHOD},
+    jsonrpc::{
+        server_error, ErrorCode, JsonError, JsonRequest, JsonResponse, JsonResult, ServerErrorCode,
+    },
+};
 use crate::{
     net::{
-        transport::Transport, TcpTransport, TorTransport, TransportListener, TransportName,
-        TransportStream, UnixTransport,
+        transport::{TlsUpgrade, Transport},
+        TcpTransport, TorTransport, TransportListener, TransportName, TransportStream,
+        UnixTransport,
     },
     Error, Result,
 };
 
+/// Wait after a failed accept, doubled on every failure in a row
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+/// Upper bound for the wait after failed accepts
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
 /// Asynchronous trait implementing a handler for incoming JSON-RPC requests.
 /// Can be used by matching on methods and branching out to functions that
 /// handle respective methods.
 #[async_trait]
 pub trait RequestHandler: Sync + Send {
     async fn handle_request(&self, req: JsonRequest) -> JsonResult;
+
+    /// API tokens the methods are restricted to. Without them, every
+    /// connection can call every method.
+    fn auth(&self) -> Option<&RpcAuth> {
+        None
+    }
+
+    /// Bounds on the requests the server processes for this handler.
+    fn limits(&self) -> RpcLimits {
+        RpcLimits::default()
+    }
+}
+
+/// Bounds on the work a JSON-RPC server takes on, protecting the daemon
+/// behind it from overload.
+#[derive(Debug, Clone)]
+pub struct RpcLimits {
+    /// Requests processed at once, across all connections
+    pub max_requests: usize,
+    /// Requests of a single connection's batch processed at once
+    pub max_connection_requests: usize,
+    /// Time a request may run before it's cancelled with an error
+    pub request_timeout: Duration,
+    /// Requests running longer than this get logged
+    pub slow_request: Duration,
+}
+
+impl Default for RpcLimits {
+    fn default() -> Self {
+        Self {
+            max_requests: 64,
+            max_connection_requests: 8,
+            request_timeout: Duration::from_secs(60),
+            slow_request: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Limits of a running server, shared by its connections
+struct ServerLimits {
+    limits: RpcLimits,
+    /// Permits for the requests processed at once
+    requests: Semaphore,
+}
+
+impl ServerLimits {
+    fn new(limits: RpcLimits) -> Self {
+        let requests = Semaphore::new(limits.max_requests.max(1));
+        Self { limits, requests }
+    }
+}
+
+/// Run a request within the server limits: wait for a free slot, cancel
+/// it with an error once it runs past the timeout, and log it if slow.
+async fn run_request(
+    req: JsonRequest,
+    rh: &Arc<impl RequestHandler + 'static>,
+    token: &mut Option<String>,
+    peer_addr: &Url,
+    limits: &ServerLimits,
+) -> JsonResult {
+    let _permit = limits.requests.acquire().await;
+
+    let id = req.id.clone();
+    let method = req.method.as_str().unwrap_or_default().to_string();
+    let start = Instant::now();
+
+    let reply =
+        match timeout(limits.limits.request_timeout, dispatch(req, rh, token, peer_addr)).await {
+            Ok(reply) => reply,
+            Err(_) => {
+                warn!("JSON-RPC request {} from {} timed out", method, peer_addr);
+                return server_error(ServerErrorCode::RequestTimeout, id)
+            }
+        };
+
+    let elapsed = start.elapsed();
+    if elapsed > limits.limits.slow_request {
+        warn!("JSON-RPC slow request {} from {} took {:?}", method, peer_addr, elapsed);
+    }
+
+    reply
+}
+
+/// Dispatch a request to the [`RequestHandler`], enforcing its token
+/// permissions before. The [`AUTH_METHOD`] request authenticates the
+/// connection with a token, kept in `token` for the requests after it.
+async fn dispatch(
+    req: JsonRequest,
+    rh: &Arc<impl RequestHandler + 'static>,
+    token: &mut Option<String>,
+    peer_addr: &Url,
+) -> JsonResult {
+    let auth = match rh.auth() {
+        Some(auth) => auth,
+        None => return rh.handle_request(req).await,
+    };
+
+    let method = req.method.as_str().unwrap_or_default();
+    if method == AUTH_METHOD {
+        let given = req.params.as_array().and_then(|p| p.first()).and_then(|t| t.as_str());
+        return match given {
+            Some(t) if auth.is_token(t) => {
+                *token = Some(t.to_string());
+                JsonResponse::new(json!(true), req.id).into()
+            }
+            _ => {
+                warn!("JSON-RPC server received invalid token from {}", peer_addr);
+                server_error(ServerErrorCode::InvalidToken, req.id)
+            }
+        }
+    }
+
+    if !auth.permits(token.as_deref(), method) {
+        warn!("JSON-RPC server denied method {} to {}", method, peer_addr);
+        return server_error(ServerErrorCode::Unauthorized, req.id)
+    }
+
+    rh.handle_request(req).await
 }
 
 /// Internal accept function that runs inside a loop for accepting incoming
@@ -28,7 +164,11 @@ async fn accept(
     mut stream: Box<dyn TransportStream>,
     peer_addr: Url,
     rh: Arc<impl RequestHandler + 'static>,
+    limits: Arc<ServerLimits>,
 ) -> Result<()> {
+    // Token the connection authenticated with
+    let mut token = None;
+
     loop {
         // Nasty size
         let mut buf = vec![0; 2048 * 10];
@@ -46,7 +186,7 @@ async fn accept(
             }
         };
 
-        let r: JsonRequest = match serde_json::from_slice(&buf[0..n]) {
+        let r: Value = match serde_json::from_slice(&buf[0..n]) {
             Ok(r) => {
                 debug!(target: "jsonrpc-server", "{} --> {}", peer_addr, String::from_utf8_lossy(&buf));
                 r
@@ -58,8 +198,28 @@ async fn accept(
             }
         };
 
-        let reply = rh.handle_request(r).await;
-        let j = serde_json::to_string(&reply).unwrap();
+        let j = match r {
+            Value::Array(batch) => {
+                match handle_batch(batch, &rh, &mut token, &peer_addr, &limits).await {
+                    Some(replies) => serde_json::to_string(&replies).unwrap(),
+                    // A batch of notifications gets no reply at all
+                    None => continue,
+                }
+            }
+            r => {
+                let r: JsonRequest = match serde_json::from_value(r) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        warn!("JSON-RPC server received invalid request from {}: {}", peer_addr, e);
+                        debug!(target: "jsonrpc-server", "Closed connection for {}", peer_addr);
+                        break
+                    }
+                };
+
+                let reply = run_request(r, &rh, &mut token, &peer_addr, &limits).await;
+                serde_json::to_string(&reply).unwrap()
+            }
+        };
         debug!(target: "jsonrpc-server", "{} <-- {}", peer_addr, j);
 
         if let Err(e) = stream.write_all(j.as_bytes()).await {
@@ -72,18 +232,149 @@ async fn accept(
     Ok(())
 }
 
-/// Wrapper function around [`accept()`] to take the incoming connection and
-/// pass it forward.
+/// Element of a JSON-RPC 2.0 batch
+#[derive(Clone)]
+enum BatchItem {
+    Request(JsonRequest),
+    /// Request without an id, which gets no reply
+    Notification(JsonRequest),
+    /// Not a request object, replied to with the id it had, if any
+    Invalid(Value),
+}
+
+impl BatchItem {
+    fn parse(mut item: Value) -> Self {
+        let notification = match item.as_object_mut() {
+            Some(obj) if !obj.contains_key("id") => {
+                obj.insert("id".to_string(), Value::Null);
+                true
+            }
+            _ => false,
+        };
+
+        // Try to keep the id of malformed requests so the client can correlate.
+        let id = item.get("id").cloned().unwrap_or(Value::Null);
+        match serde_json::from_value::<JsonRequest>(item) {
+            Ok(req) if notification => Self::Notification(req),
+            Ok(req) => Self::Request(req),
+            Err(_) => Self::Invalid(id),
+        }
+    }
+
+    fn is_auth(&self) -> bool {
+        match self {
+            Self::Request(req) | Self::Notification(req) => {
+                req.method.as_str() == Some(AUTH_METHOD)
+            }
+            Self::Invalid(_) => false,
+        }
+    }
+
+    /// Run the request, returning its reply unless it's a notification.
+    async fn run(
+        self,
+        rh: &Arc<impl RequestHandler + 'static>,
+        token: &mut Option<String>,
+        peer_addr: &Url,
+        limits: &ServerLimits,
+    ) -> Option<JsonResult> {
+        match self {
+            Self::Request(req) => Some(run_request(req, rh, token, peer_addr, limits).await),
+            Self::Notification(req) => {
+                run_request(req, rh, token, peer_addr, limits).await;
+                None
+            }
+            Self::Invalid(id) => Some(JsonError::new(ErrorCode::InvalidRequest, None, id).into()),
+        }
+    }
+}
+
+/// Handle a JSON-RPC 2.0 batch. Elements are dispatched to the
+/// [`RequestHandler`] up to the connection limit at a time, and a reply is
+/// returned for each of them but notifications, in order. Elements that
+/// are not valid request objects get an `InvalidRequest` error in their
+/// place, and an empty batch yields a single error object. A batch made
+/// only of notifications returns nothing. An authentication request runs
+/// alone, as it changes the token of the requests after it.
+async fn handle_batch(
+    batch: Vec<Value>,
+    rh: &Arc<impl RequestHandler + 'static>,
+    token: &mut Option<String>,
+    peer_addr: &Url,
+    limits: &ServerLimits,
+) -> Option<Value> {
+    if batch.is_empty() {
+        return Some(json!(JsonError::new(ErrorCode::InvalidRequest, None, Value::Null)))
+    }
+
+    let items: Vec<BatchItem> = batch.into_iter().map(BatchItem::parse).collect();
+
+    let mut replies = Vec::with_capacity(items.len());
+    let mut i = 0;
+    while i < items.len() {
+        if items[i].is_auth() {
+            replies.extend(items[i].clone().run(rh, token, peer_addr, limits).await);
+            i += 1;
+            continue
+        }
+
+        let n = items[i..]
+            .iter()
+            .take(limits.limits.max_connection_requests.max(1))
+            .take_while(|item| !item.is_auth())
+            .count();
+
+        let current: &Option<String> = token;
+        let requests = items[i..i + n].iter().cloned().map(|item| {
+            let mut token = current.clone();
+            async move { item.run(rh, &mut token, peer_addr, limits).await }
+        });
+        replies.extend(join_all(requests).await.into_iter().flatten());
+        i += n;
+    }
+
+    match replies.is_empty() {
+        true => None,
+        false => Some(json!(replies)),
+    }
+}
+
+/// Wrapper function around [`accept()`] to take the incoming connections and
+/// serve each of them in its own task. A failed accept (e.g. a client
+/// aborting the TLS handshake) is logged and does not bring down the listener.
+/// Failures in a row back off up to [`MAX_ACCEPT_BACKOFF`], so persistent
+/// ones like running out of file descriptors don't spin the loop.
 async fn run_accept_loop(
     listener: Box<dyn TransportListener>,
     rh: Arc<impl RequestHandler + 'static>,
 ) -> Result<()> {
-    while let Ok((stream, peer_addr)) = listener.next().await {
+    let limits = Arc::new(ServerLimits::new(rh.limits()));
+    let mut backoff = Duration::ZERO;
+
+    loop {
+        let (stream, peer_addr) = match listener.next().await {
+            Ok(v) => v,
+            Err(e) => {
+                backoff = (backoff * 2).clamp(MIN_ACCEPT_BACKOFF, MAX_ACCEPT_BACKOFF);
+                warn!(
+                    "JSON-RPC server failed accepting connection, retrying in {:?}: {}",
+                    backoff, e
+                );
+                Timer::after(backoff).await;
+                continue
+            }
+        };
+        backoff = Duration::ZERO;
+
         info!("JSON-RPC server accepted connection from {}", peer_addr);
-        accept(stream, peer_addr, rh.clone()).await?;
+        let (rh, limits) = (rh.clone(), limits.clone());
+        smol::spawn(async move {
+            if let Err(e) = accept(stream, peer_addr.clone(), rh, limits).await {
+                error!("JSON-RPC server connection to {} failed: {}", peer_addr, e);
+            }
+        })
+        .detach();
     }
-
-    Ok(())
 }
 
 /// Start a JSON-RPC server bound to the given accept URL and use the given
@@ -149,6 +440,7 @@ pub async fn listen_and_serve(
                 error!("JSON-RPC Unix socket bind to {} failed: {}", accept_url, err);
                 return Err(Error::BindFailed(accept_url.as_str().into()))
             }
+            info!("JSON-RPC listener bound to {}", accept_url);
             run_accept_loop(Box::new(listener?), rh).await?;
         }
         _ => unimplemented!(),
@@ -156,3 +448,61 @@ pub async fn listen_and_serve(
 
     Ok(())
 }
+
+/// Start a TLS-terminated JSON-RPC server bound to the given TCP accept URL,
+/// presenting the PEM certificate chain and PKCS#8 key found at the given
+/// paths. Use [`listen_and_serve()`] with a `tcp+tls://` URL for an
+/// ephemeral self-signed certificate instead.
+pub async fn listen_and_serve_tls(
+    accept_url: Url,
+    cert_path: &Path,
+    key_path: &Path,
+    rh: Arc<impl RequestHandler + 'static>,
+) -> Result<()> {
+    debug!(target: "jsonrpc-server", "Trying to bind TLS listener on {}", accept_url);
+
+    match TransportName::try_from(accept_url.clone())? {
+        TransportName::Tcp(_) => {}
+        _ => return Err(Error::UnsupportedTransport(accept_url.scheme().to_string())),
+    }
+
+    let tls = match TlsUpgrade::with_certificate(cert_path, key_path) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("JSON-RPC server failed loading TLS certificate: {}", e);
+            return Err(e)
+        }
+    };
+
+    let transport = TcpTransport::new(None, 1024);
+    let listener = match transport.listen_on(accept_url.clone())?.await {
+        Ok(v) => v,
+        Err(err) => {
+            error!("JSON-RPC listener bind to {} failed: {}", accept_url, err);
+            return Err(Error::BindFailed(accept_url.as_str().into()))
+        }
+    };
+
+    let tls_listener = tls.upgrade_listener_tls(listener).await?;
+    info!("JSON-RPC TLS listener bound to {}", accept_url);
+    run_accept_loop(Box::new(tls_listener), rh).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_item_parse() {
+        let req = json!({"jsonrpc": "2.0", "id": 1, "method": "ping", "params": []});
+        assert!(matches!(BatchItem::parse(req), BatchItem::Request(r) if r.id == json!(1)));
+
+        let notif = json!({"jsonrpc": "2.0", "method": "ping", "params": []});
+        assert!(matches!(BatchItem::parse(notif), BatchItem::Notification(_)));
+
+        // Invalid objects are replied to even without an id
+        let invalid = json!({"jsonrpc": "2.0", "id": 2});
+        assert!(matches!(BatchItem::parse(invalid), BatchItem::Invalid(id) if id == json!(2)));
+        assert!(matches!(BatchItem::parse(json!(1)), BatchItem::Invalid(Value::Null)));
+    }
+}
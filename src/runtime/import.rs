@@ -0,0 +1,254 @@
+//! Host functions giving contracts access to the chain state.
+//!
+//! Contracts can read the chain state and write into their own key/value
+//! state. Writes are collected by the runtime and only take effect once
+//! the caller applies them. Every call is charged a fixed amount of gas on top of the metered wasm
+//! operators, plus a per-byte amount for the data copied in or out of the
+//! guest memory. Values too large to return directly are placed in a
+//! return data buffer, which the contract copies out with
+//! `drk_get_return_data_`.
+use log::error;
+use wasmer::{RuntimeError, Value};
+
+use super::{memory::MemoryManipulation, vm_runtime::Env};
+use crate::Result;
+
+/// Gas charged for every host function call
+pub const HOST_CALL_GAS: u64 = 100;
+/// Gas charged per byte copied between the host and the guest
+pub const HOST_BYTE_GAS: u64 = 1;
+
+/// Returned when the runtime has no state attached
+pub const NO_STATE: i64 = -1;
+/// Returned when the guest passed an invalid memory range
+pub const INVALID_MEMORY: i64 = -2;
+/// Returned when the state lookup failed
+pub const STATE_ERROR: i64 = -3;
+/// Returned by `drk_db_get_` when the key does not exist
+pub const KEY_NOT_FOUND: i64 = -4;
+
+/// Read access to the chain state, as seen by a single contract.
+/// Key/value lookups are scoped to that contract's own state.
+pub trait StateReader: Send + Sync {
+    /// Check if the given serialized Merkle root has been seen on chain.
+    fn merkle_root_exists(&self, root: &[u8; 32]) -> Result<bool>;
+    /// Check if the given serialized nullifier has been published.
+    fn nullifier_exists(&self, nullifier: &[u8; 32]) -> Result<bool>;
+    /// Retrieve the value under the given key of the contract's state.
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+}
+
+#[cfg(feature = "blockchain")]
+pub use chain::ChainStateReader;
+
+#[cfg(feature = "blockchain")]
+mod chain {
+    use super::StateReader;
+    use crate::{
+        blockchain::{Blockchain, ContractStateOverlay, NullifierStore, RootStore},
+        util::serial::deserialize,
+        Result,
+    };
+
+    /// [`StateReader`] backed by the blockchain sled trees.
+    pub struct ChainStateReader {
+        merkle_roots: RootStore,
+        nullifiers: NullifierStore,
+        contract_state: ContractStateOverlay,
+        contract_id: [u8; 32],
+    }
+
+    impl ChainStateReader {
+        /// Create a reader over the given blockchain, scoped to `contract_id`.
+        pub fn new(blockchain: &Blockchain, contract_id: [u8; 32]) -> Self {
+            let contract_state = ContractStateOverlay::new(blockchain.contract_state.clone());
+            Self::with_overlay(blockchain, contract_state, contract_id)
+        }
+
+        /// Create a reader over the given blockchain, seeing the contract
+        /// state changes of `contract_state` that aren't finalized yet.
+        pub fn with_overlay(
+            blockchain: &Blockchain,
+            contract_state: ContractStateOverlay,
+            contract_id: [u8; 32],
+        ) -> Self {
+            Self::from_stores(
+                blockchain.merkle_roots.clone(),
+                blockchain.nullifiers.clone(),
+                contract_state,
+                contract_id,
+            )
+        }
+
+        /// Create a reader over the given stores, scoped to `contract_id`.
+        pub fn from_stores(
+            merkle_roots: RootStore,
+            nullifiers: NullifierStore,
+            contract_state: ContractStateOverlay,
+            contract_id: [u8; 32],
+        ) -> Self {
+            Self { merkle_roots, nullifiers, contract_state, contract_id }
+        }
+    }
+
+    impl StateReader for ChainStateReader {
+        fn merkle_root_exists(&self, root: &[u8; 32]) -> Result<bool> {
+            self.merkle_roots.contains(&deserialize(root)?)
+        }
+
+        fn nullifier_exists(&self, nullifier: &[u8; 32]) -> Result<bool> {
+            self.nullifiers.contains(&deserialize(nullifier)?)
+        }
+
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.contract_state.get(&self.contract_id, key)
+        }
+    }
+}
+
+/// Subtract `cost` from the remaining gas, trapping if not enough is left.
+fn charge_gas(env: &Env, cost: u64) -> std::result::Result<(), RuntimeError> {
+    let remaining = env.remaining_points.get_ref().unwrap();
+    let points = remaining.get().unwrap_i64() as u64;
+
+    if points < cost {
+        remaining.set(Value::I64(0))?;
+        env.points_exhausted.get_ref().unwrap().set(Value::I32(1))?;
+        return Err(RuntimeError::new("Gas exhausted in host function"))
+    }
+
+    remaining.set(Value::I64((points - cost) as i64))
+}
+
+fn read_hash(env: &Env, ptr: u32) -> Option<[u8; 32]> {
+    let bytes = env.memory.get_ref().unwrap().read(ptr, 32)?;
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(bytes);
+    Some(ret)
+}
+
+fn exists_result(result: Result<bool>) -> i64 {
+    match result {
+        Ok(v) => v as i64,
+        Err(e) => {
+            error!(target: "wasm-runtime", "State lookup failed: {}", e);
+            STATE_ERROR
+        }
+    }
+}
+
+/// Host function checking if the 32-byte Merkle root at `ptr` exists.
+/// Returns 1 if it does, 0 if not, or a negative error code.
+pub(crate) fn drk_merkle_root_exists(
+    env: &Env,
+    ptr: u32,
+) -> std::result::Result<i64, RuntimeError> {
+    charge_gas(env, HOST_CALL_GAS + 32 * HOST_BYTE_GAS)?;
+
+    let state = match &env.state {
+        Some(v) => v,
+        None => return Ok(NO_STATE),
+    };
+
+    let root = match read_hash(env, ptr) {
+        Some(v) => v,
+        None => return Ok(INVALID_MEMORY),
+    };
+
+    Ok(exists_result(state.merkle_root_exists(&root)))
+}
+
+/// Host function checking if the 32-byte nullifier at `ptr` exists.
+/// Returns 1 if it does, 0 if not, or a negative error code.
+pub(crate) fn drk_nullifier_exists(env: &Env, ptr: u32) -> std::result::Result<i64, RuntimeError> {
+    charge_gas(env, HOST_CALL_GAS + 32 * HOST_BYTE_GAS)?;
+
+    let state = match &env.state {
+        Some(v) => v,
+        None => return Ok(NO_STATE),
+    };
+
+    let nullifier = match read_hash(env, ptr) {
+        Some(v) => v,
+        None => return Ok(INVALID_MEMORY),
+    };
+
+    Ok(exists_result(state.nullifier_exists(&nullifier)))
+}
+
+/// Host function looking up the key at `ptr` in the contract's state.
+/// On success, the value is placed in the return data buffer and its
+/// length is returned. Otherwise a negative error code is returned.
+pub(crate) fn drk_db_get(env: &Env, ptr: u32, len: u32) -> std::result::Result<i64, RuntimeError> {
+    charge_gas(env, HOST_CALL_GAS + len as u64 * HOST_BYTE_GAS)?;
+
+    let state = match &env.state {
+        Some(v) => v,
+        None => return Ok(NO_STATE),
+    };
+
+    let key = match env.memory.get_ref().unwrap().read(ptr, len as usize) {
+        Some(v) => v,
+        None => return Ok(INVALID_MEMORY),
+    };
+
+    // The contract's own writes in this run take precedence
+    let written = env.writes.lock().unwrap().iter().rev().find(|(k, _)| k == key).cloned();
+    let value = match written {
+        Some((_, value)) => Ok(Some(value)),
+        None => state.get(key),
+    };
+
+    let value = match value {
+        Ok(Some(v)) => v,
+        Ok(None) => return Ok(KEY_NOT_FOUND),
+        Err(e) => {
+            error!(target: "wasm-runtime", "State lookup failed: {}", e);
+            return Ok(STATE_ERROR)
+        }
+    };
+
+    charge_gas(env, value.len() as u64 * HOST_BYTE_GAS)?;
+    let len = value.len() as i64;
+    *env.return_data.lock().unwrap() = value;
+    Ok(len)
+}
+
+/// Host function writing the value at `val_ptr` under the key at `key_ptr`
+/// of the contract's state. Returns 0 on success, or a negative error code.
+pub(crate) fn drk_db_set(
+    env: &Env,
+    key_ptr: u32,
+    key_len: u32,
+    val_ptr: u32,
+    val_len: u32,
+) -> std::result::Result<i64, RuntimeError> {
+    charge_gas(env, HOST_CALL_GAS + (key_len as u64 + val_len as u64) * HOST_BYTE_GAS)?;
+
+    let memory = env.memory.get_ref().unwrap();
+    let key = match memory.read(key_ptr, key_len as usize) {
+        Some(v) => v.to_vec(),
+        None => return Ok(INVALID_MEMORY),
+    };
+    let value = match memory.read(val_ptr, val_len as usize) {
+        Some(v) => v.to_vec(),
+        None => return Ok(INVALID_MEMORY),
+    };
+
+    env.writes.lock().unwrap().push((key, value));
+    Ok(0)
+}
+
+/// Host function copying the return data buffer into guest memory at `ptr`.
+/// The guest must have allocated as many bytes as the preceding call
+/// returned. Returns 0 on success, or a negative error code.
+pub(crate) fn drk_get_return_data(env: &Env, ptr: u32) -> std::result::Result<i64, RuntimeError> {
+    let data = std::mem::take(&mut *env.return_data.lock().unwrap());
+    charge_gas(env, HOST_CALL_GAS + data.len() as u64 * HOST_BYTE_GAS)?;
+
+    if !data.is_empty() && env.memory.get_ref().unwrap().write(ptr, &data).is_err() {
+        return Ok(INVALID_MEMORY)
+    }
+
+    Ok(0)
+}
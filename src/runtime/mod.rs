@@ -1,3 +1,4 @@
+pub mod import;
 pub mod memory;
 pub mod util;
 pub mod vm_runtime;
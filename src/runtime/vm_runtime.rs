@@ -1,10 +1,10 @@
 use std::sync::{Arc, Mutex};
 
-use drk_sdk::entrypoint;
+use drk_sdk::{entrypoint, error::ContractError};
 use log::debug;
 use wasmer::{
-    imports, wasmparser::Operator, CompilerConfig, Function, HostEnvInitError, Instance, LazyInit,
-    Memory, Module, Store, Universal, Value, WasmerEnv,
+    imports, wasmparser::Operator, CompilerConfig, ExternType, Function, Global, HostEnvInitError,
+    Instance, LazyInit, Memory, Module, Store, Universal, Value, WasmerEnv,
 };
 use wasmer_compiler_singlepass::Singlepass;
 use wasmer_middlewares::{
@@ -12,8 +12,15 @@ use wasmer_middlewares::{
     Metering,
 };
 
-use super::{memory::MemoryManipulation, util::drk_log};
-use crate::Result;
+use super::{
+    import::{
+        drk_db_get, drk_db_set, drk_get_return_data, drk_merkle_root_exists, drk_nullifier_exists,
+        StateReader,
+    },
+    memory::MemoryManipulation,
+    util::drk_log,
+};
+use crate::{Error, Result};
 
 /// Function name in our wasm module that allows us to allocate some memory.
 const WASM_MEM_ALLOC: &str = "__drkruntime_mem_alloc";
@@ -21,13 +28,33 @@ const WASM_MEM_ALLOC: &str = "__drkruntime_mem_alloc";
 const MEMORY: &str = "memory";
 /// Hardcoded entrypoint function of a contract
 const ENTRYPOINT: &str = "entrypoint";
-/// Gas limit for a contract
-const GAS_LIMIT: u64 = 200000;
+/// Default gas limit for a contract
+pub const DEFAULT_GAS_LIMIT: u64 = 200000;
+/// Host functions a contract may import from the `env` namespace
+const HOST_FUNCTIONS: &[&str] = &[
+    "drk_log_",
+    "drk_merkle_root_exists_",
+    "drk_nullifier_exists_",
+    "drk_db_get_",
+    "drk_db_set_",
+    "drk_get_return_data_",
+];
+/// Globals injected by the metering middleware
+const REMAINING_POINTS: &str = "wasmer_metering_remaining_points";
+const POINTS_EXHAUSTED: &str = "wasmer_metering_points_exhausted";
 
 #[derive(Clone)]
 pub struct Env {
     pub logs: Arc<Mutex<Vec<String>>>,
     pub memory: LazyInit<Memory>,
+    /// Chain state the contract can read through host functions
+    pub state: Option<Arc<dyn StateReader>>,
+    /// Data returned by the last host function call
+    pub return_data: Arc<Mutex<Vec<u8>>>,
+    /// Key/value pairs written to the contract's state, in order
+    pub writes: Arc<Mutex<Vec<(Vec<u8>, Vec<u8>)>>>,
+    pub remaining_points: LazyInit<Global>,
+    pub points_exhausted: LazyInit<Global>,
 }
 
 impl WasmerEnv for Env {
@@ -37,45 +64,121 @@ impl WasmerEnv for Env {
     ) -> std::result::Result<(), HostEnvInitError> {
         let memory: Memory = instance.exports.get_with_generics_weak(MEMORY)?;
         self.memory.initialize(memory);
+        let remaining_points: Global = instance.exports.get_with_generics_weak(REMAINING_POINTS)?;
+        self.remaining_points.initialize(remaining_points);
+        let points_exhausted: Global = instance.exports.get_with_generics_weak(POINTS_EXHAUSTED)?;
+        self.points_exhausted.initialize(points_exhausted);
         Ok(())
     }
 }
 
+/// Cost of a wasm operator. Memory growth is priced higher than other
+/// instructions, as it makes the host allocate.
+fn operator_cost(operator: &Operator) -> u64 {
+    match operator {
+        Operator::MemoryGrow { .. } => 1000,
+        _ => 1,
+    }
+}
+
 pub struct Runtime {
     pub(crate) instance: Instance,
     pub(crate) env: Env,
+    gas_limit: u64,
 }
 
 impl Runtime {
-    /// Create a new wasm runtime instance that contains the given wasm module.
-    pub fn new(wasm_bytes: &[u8]) -> Result<Self> {
-        // This function will be called for each `Operator` encountered during
-        // the wasm module execution. It should return the cost of the operator
-        // that it received as its first argument.
-        let cost_function = |operator: &Operator| -> u64 {
-            match operator {
-                Operator::LocalGet { .. } => 1,
-                Operator::I32Const { .. } => 1,
-                Operator::I32Add { .. } => 2,
-                _ => 0,
+    /// Create a new wasm runtime instance that contains the given wasm module,
+    /// allowed to consume at most `gas_limit` gas per run. The contract has no
+    /// access to the chain state, and the state host functions return an
+    /// error code.
+    pub fn new(wasm_bytes: &[u8], gas_limit: u64) -> Result<Self> {
+        Self::build(wasm_bytes, gas_limit, None)
+    }
+
+    /// Create a new wasm runtime instance that contains the given wasm module,
+    /// giving the contract read access to the given state.
+    pub fn with_state(
+        wasm_bytes: &[u8],
+        gas_limit: u64,
+        state: Arc<dyn StateReader>,
+    ) -> Result<Self> {
+        Self::build(wasm_bytes, gas_limit, Some(state))
+    }
+
+    /// Check that the given bincode is a contract module the runtime can
+    /// run: it must compile, import nothing but our host functions, and
+    /// export the entrypoint, linear memory and allocator we rely on.
+    pub fn validate(wasm_bytes: &[u8]) -> Result<()> {
+        let store = Self::store(DEFAULT_GAS_LIMIT);
+        let module = Module::new(&store, wasm_bytes)?;
+
+        for import in module.imports() {
+            let known = import.module() == "env" && HOST_FUNCTIONS.contains(&import.name());
+            if !known || !matches!(import.ty(), ExternType::Function(_)) {
+                return Err(Error::WasmerInvalidModule(format!(
+                    "Unknown import {}::{}",
+                    import.module(),
+                    import.name()
+                )))
             }
-        };
+        }
+
+        for name in [ENTRYPOINT, WASM_MEM_ALLOC, MEMORY] {
+            let export = match module.exports().find(|e| e.name() == name) {
+                Some(v) => v,
+                None => return Err(Error::WasmerInvalidModule(format!("Missing export {}", name))),
+            };
+
+            let valid = match export.ty() {
+                ExternType::Memory(_) => name == MEMORY,
+                ExternType::Function(_) => name != MEMORY,
+                _ => false,
+            };
+
+            if !valid {
+                return Err(Error::WasmerInvalidModule(format!("Invalid export {}", name)))
+            }
+        }
 
-        // `Metering` needs to be conigured with a limit and a cost function.
+        Ok(())
+    }
+
+    /// Create a store compiling modules with gas metering.
+    fn store(gas_limit: u64) -> Store {
+        // `Metering` needs to be configured with a limit and a cost function.
         // For each `Operator`, the metering middleware will call the cost
-        // function and subtract the cost from the remaining points.
-        let metering = Arc::new(Metering::new(GAS_LIMIT, cost_function));
+        // function and subtract the cost from the remaining points. Every
+        // instruction is counted, so the gas used by a contract is the same
+        // on every validator.
+        let metering = Arc::new(Metering::new(gas_limit, operator_cost));
 
         // Define the compiler and middleware, engine, and store
         let mut compiler = Singlepass::new();
         compiler.push_middleware(metering);
-        let store = Store::new(&Universal::new(compiler).engine());
+        Store::new(&Universal::new(compiler).engine())
+    }
+
+    fn build(
+        wasm_bytes: &[u8],
+        gas_limit: u64,
+        state: Option<Arc<dyn StateReader>>,
+    ) -> Result<Self> {
+        let store = Self::store(gas_limit);
 
         debug!(target: "wasm-runtime", "Compiling module...");
         let module = Module::new(&store, wasm_bytes)?;
 
         debug!(target: "wasm-runtime", "Importing functions...");
-        let env = Env { logs: Arc::new(Mutex::new(vec![])), memory: LazyInit::new() };
+        let env = Env {
+            logs: Arc::new(Mutex::new(vec![])),
+            memory: LazyInit::new(),
+            state,
+            return_data: Arc::new(Mutex::new(vec![])),
+            writes: Arc::new(Mutex::new(vec![])),
+            remaining_points: LazyInit::new(),
+            points_exhausted: LazyInit::new(),
+        };
         let import_object = imports! {
             "env" => {
                 "drk_log_" => Function::new_native_with_env(
@@ -83,13 +186,38 @@ impl Runtime {
                     env.clone(),
                     drk_log,
                 ),
+                "drk_merkle_root_exists_" => Function::new_native_with_env(
+                    &store,
+                    env.clone(),
+                    drk_merkle_root_exists,
+                ),
+                "drk_nullifier_exists_" => Function::new_native_with_env(
+                    &store,
+                    env.clone(),
+                    drk_nullifier_exists,
+                ),
+                "drk_db_get_" => Function::new_native_with_env(
+                    &store,
+                    env.clone(),
+                    drk_db_get,
+                ),
+                "drk_db_set_" => Function::new_native_with_env(
+                    &store,
+                    env.clone(),
+                    drk_db_set,
+                ),
+                "drk_get_return_data_" => Function::new_native_with_env(
+                    &store,
+                    env.clone(),
+                    drk_get_return_data,
+                ),
             }
         };
 
         debug!(target: "wasm-runtime", "Instantiating module...");
         let instance = Instance::new(&module, &import_object)?;
 
-        Ok(Self { instance, env })
+        Ok(Self { instance, env, gas_limit })
     }
 
     /// Run the hardcoded `ENTRYPOINT` function with the given payload as input.
@@ -114,6 +242,9 @@ impl Runtime {
             Err(e) => {
                 self.print_logs();
                 debug!(target: "wasm-runtime", "{}", self.gas_info());
+                if let MeteringPoints::Exhausted = get_remaining_points(&self.instance) {
+                    return Err(Error::WasmerOutOfGas(self.gas_limit))
+                }
                 return Err(e.into())
             }
         };
@@ -128,11 +259,15 @@ impl Runtime {
 
         match retval {
             entrypoint::SUCCESS => Ok(()),
-            // _ => Err(ContractError(retval)),
-            _ => todo!(),
+            _ => Err(Error::WasmerContractError(ContractError::from(retval).to_string())),
         }
     }
 
+    /// Take the key/value pairs the contract wrote to its state, in order.
+    pub fn take_writes(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        std::mem::take(&mut *self.env.writes.lock().unwrap())
+    }
+
     fn print_logs(&self) {
         let logs = self.env.logs.lock().unwrap();
         for msg in logs.iter() {
@@ -140,15 +275,23 @@ impl Runtime {
         }
     }
 
+    /// Gas consumed by the contract so far.
+    pub fn gas_used(&self) -> u64 {
+        match get_remaining_points(&self.instance) {
+            MeteringPoints::Remaining(rem) => self.gas_limit - rem,
+            MeteringPoints::Exhausted => self.gas_limit,
+        }
+    }
+
     fn gas_info(&self) -> String {
         let remaining_points = get_remaining_points(&self.instance);
 
         match remaining_points {
             MeteringPoints::Remaining(rem) => {
-                format!("Gas used: {}/{}", GAS_LIMIT - rem, GAS_LIMIT)
+                format!("Gas used: {}/{}", self.gas_limit - rem, self.gas_limit)
             }
             MeteringPoints::Exhausted => {
-                format!("Gas fully exhausted: {}/{}", GAS_LIMIT + 1, GAS_LIMIT)
+                format!("Gas fully exhausted: {}/{}", self.gas_limit + 1, self.gas_limit)
             }
         }
     }
@@ -1,6 +1,7 @@
 pub mod entrypoint;
 pub mod error;
 pub mod log;
+pub mod state;
 
 // Set up global allocator by default
 #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,97 @@
+//! Access to the chain state through the runtime host functions.
+//! Outside of the wasm runtime there is no state, and every call fails.
+use super::error::ContractError;
+
+/// Check if the given serialized Merkle root has been seen on chain.
+pub fn merkle_root_exists(root: &[u8; 32]) -> Result<bool, ContractError> {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        return to_bool(drk_merkle_root_exists_(root.as_ptr()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = root;
+        Err(ContractError::Internal)
+    }
+}
+
+/// Check if the given serialized nullifier has been published.
+pub fn nullifier_exists(nullifier: &[u8; 32]) -> Result<bool, ContractError> {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        return to_bool(drk_nullifier_exists_(nullifier.as_ptr()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = nullifier;
+        Err(ContractError::Internal)
+    }
+}
+
+/// Retrieve the value under the given key of this contract's state.
+pub fn db_get(key: &[u8]) -> Result<Option<Vec<u8>>, ContractError> {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        let len = drk_db_get_(key.as_ptr(), key.len());
+        if len == KEY_NOT_FOUND {
+            return Ok(None)
+        }
+        if len < 0 {
+            return Err(ContractError::Internal)
+        }
+
+        let mut value = vec![0u8; len as usize];
+        if drk_get_return_data_(value.as_mut_ptr()) < 0 {
+            return Err(ContractError::Internal)
+        }
+        return Ok(Some(value))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = key;
+        Err(ContractError::Internal)
+    }
+}
+
+/// Write a value under the given key of this contract's state. The write
+/// only takes effect if the contract call succeeds.
+pub fn db_set(key: &[u8], value: &[u8]) -> Result<(), ContractError> {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        if drk_db_set_(key.as_ptr(), key.len(), value.as_ptr(), value.len()) < 0 {
+            return Err(ContractError::Internal)
+        }
+        return Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = (key, value);
+        Err(ContractError::Internal)
+    }
+}
+
+/// Returned by `drk_db_get_` when the key does not exist
+#[cfg(target_arch = "wasm32")]
+const KEY_NOT_FOUND: i64 = -4;
+
+#[cfg(target_arch = "wasm32")]
+fn to_bool(ret: i64) -> Result<bool, ContractError> {
+    match ret {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ContractError::Internal),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn drk_merkle_root_exists_(ptr: *const u8) -> i64;
+    fn drk_nullifier_exists_(ptr: *const u8) -> i64;
+    fn drk_db_get_(ptr: *const u8, len: usize) -> i64;
+    fn drk_db_set_(key_ptr: *const u8, key_len: usize, val_ptr: *const u8, val_len: usize) -> i64;
+    fn drk_get_return_data_(ptr: *mut u8) -> i64;
+}
@@ -0,0 +1,162 @@
+use sha2::{Digest, Sha256};
+
+use super::{Hashlock, SwapSecret};
+use crate::{Error::SwapInvalidKey, Result};
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_1: u8 = 0x51;
+const OP_IF: u8 = 0x63;
+const OP_ELSE: u8 = 0x67;
+const OP_ENDIF: u8 = 0x68;
+const OP_DROP: u8 = 0x75;
+const OP_SIZE: u8 = 0x82;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_SHA256: u8 = 0xa8;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+
+/// Hash time-locked contract on the Bitcoin side of a swap, paid to as a
+/// P2WSH output. The claim path takes a signature of `claim` and the
+/// secret, the refund path a signature of `refund` once the chain's median
+/// time passed `timelock`:
+///
+/// ```text
+/// OP_IF
+///     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <hashlock> OP_EQUALVERIFY <claim>
+/// OP_ELSE
+///     <timelock> OP_CHECKLOCKTIMEVERIFY OP_DROP <refund>
+/// OP_ENDIF
+/// OP_CHECKSIG
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitcoinHtlc {
+    pub hashlock: Hashlock,
+    /// Compressed secp256k1 public key of the claiming party
+    pub claim: [u8; 33],
+    /// Compressed secp256k1 public key of the locking party
+    pub refund: [u8; 33],
+    /// UNIX timestamp after which the lock can be refunded
+    pub timelock: u32,
+}
+
+impl BitcoinHtlc {
+    pub fn new(hashlock: Hashlock, claim: &[u8], refund: &[u8], timelock: u32) -> Result<Self> {
+        Ok(Self { hashlock, claim: parse_pubkey(claim)?, refund: parse_pubkey(refund)?, timelock })
+    }
+
+    /// Witness script of the contract.
+    pub fn script(&self) -> Vec<u8> {
+        let mut script = vec![OP_IF, OP_SIZE];
+        push_int(&mut script, 32);
+        script.extend_from_slice(&[OP_EQUALVERIFY, OP_SHA256]);
+        push_data(&mut script, &self.hashlock);
+        script.push(OP_EQUALVERIFY);
+        push_data(&mut script, &self.claim);
+        script.push(OP_ELSE);
+        push_int(&mut script, self.timelock as i64);
+        script.extend_from_slice(&[OP_CHECKLOCKTIMEVERIFY, OP_DROP]);
+        push_data(&mut script, &self.refund);
+        script.extend_from_slice(&[OP_ENDIF, OP_CHECKSIG]);
+        script
+    }
+
+    /// P2WSH output script the locking party pays to.
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        let mut script = vec![OP_0];
+        push_data(&mut script, &Sha256::digest(self.script()));
+        script
+    }
+
+    /// Witness stack spending the contract through the claim path, given
+    /// the claiming party's DER signature with its sighash byte.
+    pub fn claim_witness(&self, signature: &[u8], secret: &SwapSecret) -> Vec<Vec<u8>> {
+        vec![signature.to_vec(), secret.0.to_vec(), vec![OP_1], self.script()]
+    }
+
+    /// Witness stack spending the contract through the refund path, given
+    /// the locking party's DER signature with its sighash byte.
+    pub fn refund_witness(&self, signature: &[u8]) -> Vec<Vec<u8>> {
+        vec![signature.to_vec(), vec![], self.script()]
+    }
+
+    /// Find the secret in the witness of a transaction input spending the
+    /// contract. Returns `None` if the input was a refund.
+    pub fn extract_secret(&self, witness: &[Vec<u8>]) -> Option<SwapSecret> {
+        witness.iter().filter(|item| item.len() == 32).find_map(|item| {
+            let mut preimage = [0u8; 32];
+            preimage.copy_from_slice(item);
+            SwapSecret::from_preimage(preimage, &self.hashlock).ok()
+        })
+    }
+}
+
+fn parse_pubkey(bytes: &[u8]) -> Result<[u8; 33]> {
+    if bytes.len() != 33 || (bytes[0] != 0x02 && bytes[0] != 0x03) {
+        return Err(SwapInvalidKey)
+    }
+
+    let mut key = [0u8; 33];
+    key.copy_from_slice(bytes);
+    Ok(key)
+}
+
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    // Pushes in this module never exceed 75 bytes
+    assert!(data.len() < OP_PUSHDATA1 as usize);
+    script.push(data.len() as u8);
+    script.extend_from_slice(data);
+}
+
+/// Push a number in its minimal script encoding: small numbers as opcodes,
+/// others little endian with the sign in the most significant bit.
+fn push_int(script: &mut Vec<u8>, value: i64) {
+    if (1..=16).contains(&value) {
+        script.push(OP_1 + value as u8 - 1);
+        return
+    }
+
+    let mut abs = value.unsigned_abs();
+    let mut bytes = vec![];
+    while abs > 0 {
+        bytes.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+
+    if let Some(last) = bytes.last_mut() {
+        if *last & 0x80 != 0 {
+            bytes.push(if value < 0 { 0x80 } else { 0x00 });
+        } else if value < 0 {
+            *last |= 0x80;
+        }
+    }
+
+    push_data(script, &bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_htlc_script() {
+        let secret = SwapSecret::random();
+        let claim = [0x02; 33];
+        let refund = [0x03; 33];
+        let htlc = BitcoinHtlc::new(secret.hashlock(), &claim, &refund, 1_700_000_000).unwrap();
+        assert!(BitcoinHtlc::new(secret.hashlock(), &[0x04; 65], &refund, 0).is_err());
+
+        let script = htlc.script();
+        // 1_700_000_000 = 0x6553f100, pushed as 4 little endian bytes
+        assert!(script.windows(6).any(|w| w == [0x04, 0x00, 0xf1, 0x53, 0x65, 0xb1]));
+        assert_eq!(script.last(), Some(&OP_CHECKSIG));
+
+        let script_pubkey = htlc.script_pubkey();
+        assert_eq!(script_pubkey.len(), 34);
+        assert_eq!(&script_pubkey[..2], &[OP_0, 32]);
+
+        let witness = htlc.claim_witness(&[0x30; 72], &secret);
+        assert!(htlc.extract_secret(&witness) == Some(secret));
+        assert!(htlc.extract_secret(&htlc.refund_witness(&[0x30; 72])).is_none());
+    }
+}
@@ -0,0 +1,344 @@
+use pasta_curves::pallas;
+
+use super::{Hashlock, SwapSecret};
+use crate::{
+    crypto::keypair::PublicKey,
+    tx::ContractCall,
+    util::serial::{serialize, SerialDecodable, SerialEncodable},
+    Error::SwapInvalidCall,
+    Result,
+};
+
+#[cfg(feature = "node")]
+pub use contract::HtlcContract;
+
+/// Hashed into the ID the HTLC contract is registered under.
+const HTLC_CONTRACT_DOMAIN: &[u8] = b"DarkFi:HtlcContract";
+
+/// ID of the native HTLC contract.
+pub fn htlc_contract_id() -> [u8; 32] {
+    *blake3::hash(HTLC_CONTRACT_DOMAIN).as_bytes()
+}
+
+/// Functions of the HTLC contract, used as the `func_id` of its calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HtlcFunction {
+    Lock = 0x00,
+    Claim = 0x01,
+    Refund = 0x02,
+}
+
+impl TryFrom<u8> for HtlcFunction {
+    type Error = crate::Error;
+
+    fn try_from(b: u8) -> Result<Self> {
+        match b {
+            0x00 => Ok(Self::Lock),
+            0x01 => Ok(Self::Claim),
+            0x02 => Ok(Self::Refund),
+            _ => Err(SwapInvalidCall(format!("Unknown function {}", b))),
+        }
+    }
+}
+
+/// Terms of a lock on the DarkFi side of a swap: `value` of `token_id`,
+/// claimable by `recipient` with the secret of `hashlock` before the
+/// `timelock` slot, and refundable to `refund` from it on.
+///
+/// The transaction carrying the lock call moves the value into escrow,
+/// and the one carrying the claim or refund call releases it through a
+/// clear input signed by the claiming party.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct HtlcParams {
+    pub hashlock: Hashlock,
+    pub value: u64,
+    pub token_id: pallas::Base,
+    pub recipient: PublicKey,
+    pub refund: PublicKey,
+    pub timelock: u64,
+}
+
+impl HtlcParams {
+    /// ID of the lock in the contract state.
+    pub fn id(&self) -> [u8; 32] {
+        *blake3::hash(&serialize(self)).as_bytes()
+    }
+
+    /// Call locking the funds, signed by the locking party. Its
+    /// transaction must escrow `value` of `token_id`.
+    pub fn lock_call(&self) -> ContractCall {
+        Self::call(HtlcFunction::Lock, serialize(self), self.refund)
+    }
+
+    /// Call claiming the funds with the secret, signed by the recipient.
+    /// Publishing it reveals the secret to the locking party. Its
+    /// transaction must have a clear input of `value` of `token_id`
+    /// signed by the recipient.
+    pub fn claim_call(&self, secret: &SwapSecret) -> ContractCall {
+        let claim = HtlcClaim { id: self.id(), secret: *secret };
+        Self::call(HtlcFunction::Claim, serialize(&claim), self.recipient)
+    }
+
+    /// Call refunding the funds once the timelock expired, signed by the
+    /// locking party. Its transaction must have a clear input of `value`
+    /// of `token_id` signed by the locking party.
+    pub fn refund_call(&self) -> ContractCall {
+        Self::call(HtlcFunction::Refund, serialize(&self.id()), self.refund)
+    }
+
+    fn call(func: HtlcFunction, call_data: Vec<u8>, signer: PublicKey) -> ContractCall {
+        ContractCall {
+            contract_id: htlc_contract_id(),
+            func_id: func as u8,
+            call_data,
+            proofs: vec![],
+            signature_public: vec![signer],
+        }
+    }
+}
+
+/// Call data of [`HtlcFunction::Claim`].
+#[derive(Clone, SerialEncodable, SerialDecodable)]
+pub struct HtlcClaim {
+    pub id: [u8; 32],
+    pub secret: SwapSecret,
+}
+
+/// A lock as kept in the contract state, under its ID.
+#[derive(Clone, SerialEncodable, SerialDecodable)]
+pub struct HtlcLock {
+    pub params: HtlcParams,
+    /// Secret revealed by the claim, if the lock was claimed
+    pub secret: Option<SwapSecret>,
+    pub refunded: bool,
+}
+
+impl HtlcLock {
+    pub fn is_spent(&self) -> bool {
+        self.secret.is_some() || self.refunded
+    }
+}
+
+#[cfg(feature = "node")]
+mod contract {
+    use log::debug;
+
+    use super::{htlc_contract_id, HtlcClaim, HtlcFunction, HtlcLock, HtlcParams};
+    use crate::{
+        crypto::keypair::PublicKey,
+        node::dispatch::NativeContract,
+        runtime::import::StateReader,
+        swap::SwapSecret,
+        tx::ContractCall,
+        util::serial::{deserialize, serialize},
+        Error::{
+            SwapInvalidCall, SwapLockExists, SwapLockSpent, SwapTimelockActive,
+            SwapTimelockExpired, SwapUnknownLock,
+        },
+        Result,
+    };
+
+    /// Native contract holding the funds of swap locks in escrow, enforcing
+    /// their claim and refund conditions, and publishing the secrets of
+    /// claimed ones for the locking parties to pick up.
+    ///
+    /// Locked value leaves the locking transaction as its escrow, and is
+    /// released into the claiming or refunding transaction as a clear
+    /// input. The state transition matches both against the calls here,
+    /// see [`HtlcContract::locks`] and [`HtlcContract::releases`].
+    #[derive(Default)]
+    pub struct HtlcContract;
+
+    impl HtlcContract {
+        /// Read a lock from the contract state.
+        pub fn get_lock(state: &dyn StateReader, id: &[u8; 32]) -> Result<Option<HtlcLock>> {
+            match state.get(id)? {
+                Some(bytes) => Ok(Some(deserialize(&bytes)?)),
+                None => Ok(None),
+            }
+        }
+
+        /// Lock calls among `calls`, with their index. Malformed ones are
+        /// left out, they fail when the calls are dispatched.
+        pub fn locks(calls: &[ContractCall]) -> Vec<(usize, HtlcParams)> {
+            Self::calls(calls, HtlcFunction::Lock)
+                .filter_map(|(i, call)| Some((i, deserialize(&call.call_data).ok()?)))
+                .collect()
+        }
+
+        /// Claim and refund calls among `calls`, with their index, the terms
+        /// of the lock they release, and the party the funds are released
+        /// to. Calls of unknown locks are left out, they fail when the
+        /// calls are dispatched.
+        pub fn releases(
+            calls: &[ContractCall],
+            state: &dyn StateReader,
+        ) -> Result<Vec<(usize, HtlcParams, PublicKey)>> {
+            let mut ret = vec![];
+
+            let claims = Self::calls(calls, HtlcFunction::Claim).filter_map(|(i, call)| {
+                let claim: HtlcClaim = deserialize(&call.call_data).ok()?;
+                Some((i, claim.id))
+            });
+            let refunds = Self::calls(calls, HtlcFunction::Refund)
+                .filter_map(|(i, call)| Some((i, deserialize(&call.call_data).ok()?)));
+
+            for (i, id) in claims.chain(refunds) {
+                if let Some(lock) = Self::get_lock(state, &id)? {
+                    let party = if calls[i].func_id == HtlcFunction::Claim as u8 {
+                        lock.params.recipient
+                    } else {
+                        lock.params.refund
+                    };
+                    ret.push((i, lock.params, party));
+                }
+            }
+
+            Ok(ret)
+        }
+
+        fn calls(
+            calls: &[ContractCall],
+            func: HtlcFunction,
+        ) -> impl Iterator<Item = (usize, &ContractCall)> {
+            calls.iter().enumerate().filter(move |(_, call)| {
+                call.contract_id == htlc_contract_id() && call.func_id == func as u8
+            })
+        }
+
+        fn unspent_lock(state: &dyn StateReader, id: &[u8; 32]) -> Result<HtlcLock> {
+            match Self::get_lock(state, id)? {
+                Some(lock) if lock.is_spent() => Err(SwapLockSpent),
+                Some(lock) => Ok(lock),
+                None => Err(SwapUnknownLock),
+            }
+        }
+
+        fn check_signer(call: &ContractCall, signer: &PublicKey) -> Result<()> {
+            if call.signature_public != [*signer] {
+                return Err(SwapInvalidCall("Call must be signed by the lock party".to_string()))
+            }
+            Ok(())
+        }
+    }
+
+    impl NativeContract for HtlcContract {
+        fn execute(
+            &self,
+            call: &ContractCall,
+            state: &dyn StateReader,
+            slot: u64,
+        ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            let (id, lock) = match HtlcFunction::try_from(call.func_id)? {
+                HtlcFunction::Lock => {
+                    let params: HtlcParams = deserialize(&call.call_data)?;
+                    Self::check_signer(call, &params.refund)?;
+
+                    let id = params.id();
+                    if Self::get_lock(state, &id)?.is_some() {
+                        return Err(SwapLockExists)
+                    }
+                    if params.timelock <= slot {
+                        return Err(SwapTimelockExpired)
+                    }
+
+                    debug!(target: "htlc", "Locking {} under {}", params.value, hex::encode(id));
+                    (id, HtlcLock { params, secret: None, refunded: false })
+                }
+
+                HtlcFunction::Claim => {
+                    let claim: HtlcClaim = deserialize(&call.call_data)?;
+                    let mut lock = Self::unspent_lock(state, &claim.id)?;
+                    Self::check_signer(call, &lock.params.recipient)?;
+
+                    if lock.params.timelock <= slot {
+                        return Err(SwapTimelockExpired)
+                    }
+                    let secret = SwapSecret::from_preimage(claim.secret.0, &lock.params.hashlock)?;
+
+                    debug!(target: "htlc", "Claiming {}", hex::encode(claim.id));
+                    lock.secret = Some(secret);
+                    (claim.id, lock)
+                }
+
+                HtlcFunction::Refund => {
+                    let id: [u8; 32] = deserialize(&call.call_data)?;
+                    let mut lock = Self::unspent_lock(state, &id)?;
+                    Self::check_signer(call, &lock.params.refund)?;
+
+                    if lock.params.timelock > slot {
+                        return Err(SwapTimelockActive)
+                    }
+
+                    debug!(target: "htlc", "Refunding {}", hex::encode(id));
+                    lock.refunded = true;
+                    (id, lock)
+                }
+            };
+
+            Ok(vec![(id.to_vec(), serialize(&lock))])
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use pasta_curves::pallas;
+        use rand::rngs::OsRng;
+
+        use super::*;
+        use crate::{crypto::keypair::Keypair, node::dispatch::MemoryState};
+
+        #[test]
+        fn test_htlc_contract() {
+            let secret = SwapSecret::random();
+            let recipient = Keypair::random(&mut OsRng).public;
+            let refund = Keypair::random(&mut OsRng).public;
+
+            let mut params = HtlcParams {
+                hashlock: secret.hashlock(),
+                value: 42,
+                token_id: pallas::Base::from(1),
+                recipient,
+                refund,
+                timelock: 100,
+            };
+
+            let state = MemoryState::default();
+            state.execute(&HtlcContract, &params.lock_call(), 10).unwrap();
+            assert!(state.execute(&HtlcContract, &params.lock_call(), 10).is_err());
+
+            // Refunds wait for the timelock, claims need the secret
+            assert!(state.execute(&HtlcContract, &params.refund_call(), 99).is_err());
+            assert!(state
+                .execute(&HtlcContract, &params.claim_call(&SwapSecret::random()), 10)
+                .is_err());
+            assert!(state.execute(&HtlcContract, &params.claim_call(&secret), 100).is_err());
+
+            let mut call = params.claim_call(&secret);
+            call.signature_public = vec![refund];
+            assert!(state.execute(&HtlcContract, &call, 10).is_err());
+
+            // Claims release the funds to the recipient
+            let calls = [params.claim_call(&secret)];
+            let releases = HtlcContract::releases(&calls, &state).unwrap();
+            assert!(releases.len() == 1 && releases[0].1 == params && releases[0].2 == recipient);
+
+            state.execute(&HtlcContract, &params.claim_call(&secret), 10).unwrap();
+            let lock = HtlcContract::get_lock(&state, &params.id()).unwrap().unwrap();
+            assert!(lock.secret == Some(secret));
+            assert!(state.execute(&HtlcContract, &params.claim_call(&secret), 10).is_err());
+
+            // Expired locks can't be created, others are refunded after
+            // their timelock
+            assert!(state
+                .execute(&HtlcParams { value: 1, ..params.clone() }.lock_call(), 100)
+                .is_err());
+            params.value = 2;
+            state.execute(&HtlcContract, &params.lock_call(), 10).unwrap();
+            assert!(HtlcContract::locks(&[params.lock_call()])[0].1 == params);
+            state.execute(&HtlcContract, &params.refund_call(), 100).unwrap();
+            assert!(state.execute(&HtlcContract, &params.claim_call(&secret), 10).is_err());
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::Hashlock;
+use crate::{
+    crypto::keypair::PublicKey,
+    net,
+    util::{
+        net_name::NetworkName,
+        serial::{SerialDecodable, SerialEncodable},
+        time::Timestamp,
+    },
+};
+
+/// Funds on one side of a swap. The token is the token ID on DarkFi, and
+/// empty for the native coin of other networks.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SwapAsset {
+    pub network: NetworkName,
+    pub token: String,
+    pub amount: u64,
+}
+
+/// Keys of a party, one per swapped network: its DarkFi public key, and
+/// its compressed secp256k1 public key on Bitcoin.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SwapKeys {
+    pub darkfi: PublicKey,
+    pub bitcoin: Vec<u8>,
+}
+
+/// Offer of a maker, broadcast to the network: `give` in exchange for
+/// `want`, locked under `hashlock`. Swaps are identified by a random ID
+/// chosen by the maker.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SwapOffer {
+    pub id: u64,
+    pub give: SwapAsset,
+    pub want: SwapAsset,
+    pub hashlock: Hashlock,
+    pub maker: SwapKeys,
+    /// Expiry of the maker's lock, which is created first
+    pub maker_timelock: Timestamp,
+    /// Expiry of the taker's lock, before the maker's one
+    pub taker_timelock: Timestamp,
+}
+
+impl net::Message for SwapOffer {
+    fn name() -> &'static str {
+        "swap_offer"
+    }
+}
+
+/// Taker accepting an offer, sent to the maker.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SwapAccept {
+    pub id: u64,
+    pub taker: SwapKeys,
+}
+
+impl net::Message for SwapAccept {
+    fn name() -> &'static str {
+        "swap_accept"
+    }
+}
+
+/// Party announcing its lock was published, identified by its
+/// transaction hash on the given network.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SwapLocked {
+    pub id: u64,
+    pub network: NetworkName,
+    pub tx_hash: Vec<u8>,
+}
+
+impl net::Message for SwapLocked {
+    fn name() -> &'static str {
+        "swap_locked"
+    }
+}
+
+/// Party withdrawing from a swap before locking, or telling the other it
+/// will refund.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct SwapCancel {
+    pub id: u64,
+    pub reason: String,
+}
+
+impl net::Message for SwapCancel {
+    fn name() -> &'static str {
+        "swap_cancel"
+    }
+}
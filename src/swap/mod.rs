@@ -0,0 +1,110 @@
+//! Atomic swaps between DarkFi and Bitcoin.
+//!
+//! Swaps are hash time-locked. The maker draws a [`SwapSecret`] and
+//! publishes an offer carrying its hashlock. Both sides then lock their
+//! funds under the hashlock, claimable by the counterparty with the
+//! secret, or refundable by their owner once a timelock expires:
+//!
+//! 1. The maker locks first, with the longer timelock.
+//! 2. The taker waits for the maker's lock to be confirmed, and locks its
+//!    side with the shorter timelock.
+//! 3. The maker claims the taker's lock, revealing the secret on chain.
+//! 4. The taker picks up the secret and claims the maker's lock before
+//!    the maker can refund it.
+//!
+//! Either side stops at any step by waiting for its timelock and taking
+//! its refund. The DarkFi side is locked with the [`htlc`] contract, the
+//! Bitcoin side with a [`bitcoin`] script, and each party follows the
+//! other chain through a [`ChainWatch`](watch::ChainWatch).
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    util::serial::{SerialDecodable, SerialEncodable},
+    Error::SwapInvalidPreimage,
+    Result,
+};
+
+/// Bitcoin HTLC scripts
+pub mod bitcoin;
+
+/// DarkFi HTLC contract
+pub mod htlc;
+
+/// P2P messages negotiating a swap
+pub mod message;
+
+/// Monitoring of the counterparty chain
+pub mod watch;
+
+/// SHA-256 hash of a [`SwapSecret`]. SHA-256 is used rather than blake3 so
+/// that Bitcoin scripts can check the secret.
+pub type Hashlock = [u8; 32];
+
+/// Hash the given preimage into its hashlock.
+pub fn hashlock(preimage: &[u8]) -> Hashlock {
+    Sha256::digest(preimage).into()
+}
+
+/// Secret of a swap, known to the maker until it claims the taker's lock.
+#[derive(Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SwapSecret(pub [u8; 32]);
+
+impl SwapSecret {
+    pub fn random() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    /// Recover a secret revealed on chain, checking it against the hashlock.
+    pub fn from_preimage(preimage: [u8; 32], lock: &Hashlock) -> Result<Self> {
+        let secret = Self(preimage);
+        if &secret.hashlock() != lock {
+            return Err(SwapInvalidPreimage)
+        }
+        Ok(secret)
+    }
+
+    pub fn hashlock(&self) -> Hashlock {
+        hashlock(&self.0)
+    }
+}
+
+/// Progress of a swap, as seen by either party.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapState {
+    /// The offer was published and waits for a taker
+    Offered,
+    /// A taker accepted the offer, the maker is locking
+    Accepted,
+    /// The maker's lock is confirmed, the taker is locking
+    MakerLocked,
+    /// Both locks are confirmed, the maker can claim
+    TakerLocked,
+    /// The maker claimed the taker's lock, revealing the secret
+    MakerClaimed,
+    /// The taker claimed the maker's lock, the swap is complete
+    Completed,
+    /// The swap was aborted and the locked funds refunded
+    Refunded,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_secret() {
+        let secret = SwapSecret::random();
+        let lock = secret.hashlock();
+        assert!(SwapSecret::from_preimage(secret.0, &lock).is_ok());
+        assert!(SwapSecret::from_preimage([0u8; 32], &lock).is_err());
+
+        // Test vector from FIPS 180-2
+        assert_eq!(
+            hex::encode(hashlock(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
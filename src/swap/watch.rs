@@ -0,0 +1,50 @@
+use async_trait::async_trait;
+
+use super::{Hashlock, SwapSecret};
+use crate::{util::net_name::NetworkName, Result};
+
+/// Output locking the counterparty's side of a swap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOutput {
+    /// Hash of the transaction creating the output
+    pub tx_hash: Vec<u8>,
+    /// Index of the output in the transaction
+    pub index: u32,
+    pub amount: u64,
+}
+
+/// How a lock was spent.
+#[derive(Clone, PartialEq, Eq)]
+pub enum LockSpend {
+    /// Claimed by the counterparty, revealing the swap secret
+    Claimed(SwapSecret),
+    /// Refunded to the locking party
+    Refunded,
+}
+
+/// Monitoring of the counterparty chain of a swap. Implementations wrap a
+/// node or indexer of the network, so that swaps work with any backend
+/// providing them, such as a Bitcoin Core node or an Electrum server.
+#[async_trait]
+pub trait ChainWatch: Send + Sync {
+    /// Network being watched.
+    fn network(&self) -> NetworkName;
+
+    /// Median time of the chain tip, which is what timelocks are checked
+    /// against.
+    async fn median_time(&self) -> Result<i64>;
+
+    /// Wait for an output paying at least `amount` to `script_pubkey` to
+    /// have `confirmations` confirmations.
+    async fn wait_lock(
+        &self,
+        script_pubkey: &[u8],
+        amount: u64,
+        confirmations: u32,
+    ) -> Result<LockOutput>;
+
+    /// Wait for the given lock output to be spent, returning how. The
+    /// secret of a claim must match `hashlock`, see
+    /// [`BitcoinHtlc::extract_secret`](super::bitcoin::BitcoinHtlc::extract_secret).
+    async fn wait_spend(&self, lock: &LockOutput, hashlock: &Hashlock) -> Result<LockSpend>;
+}
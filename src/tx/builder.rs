@@ -3,7 +3,8 @@ use rand::rngs::OsRng;
 
 use super::{
     partial::{PartialTransaction, PartialTransactionClearInput, PartialTransactionInput},
-    Transaction, TransactionClearInput, TransactionInput, TransactionOutput,
+    ContractCall, ContractDeploy, Transaction, TransactionClearInput, TransactionEscrow,
+    TransactionInput, TransactionOutput,
 };
 use crate::{
     crypto::{
@@ -13,20 +14,88 @@ use crate::{
         mint_proof::create_mint_proof,
         note::Note,
         proof::ProvingKey,
-        schnorr::SchnorrSecret,
+        schnorr::{SchnorrPublic, SchnorrSecret, Signature},
+        token_id::drk_token_id,
         types::{
             DrkCoinBlind, DrkSerial, DrkSpendHook, DrkTokenId, DrkUserData, DrkUserDataBlind,
             DrkValueBlind,
         },
     },
     util::serial::Encodable,
-    Result,
+    zk::{
+        circuit::{BurnContract, MintContract},
+        keycache::KeyCache,
+    },
+    BuilderFailed, Error, Result, VerifyFailed,
 };
 
+/// Proving keys transactions get their mint and burn proofs created with.
+pub trait TransactionProver {
+    fn mint_pk(&self) -> &ProvingKey;
+    fn burn_pk(&self) -> &ProvingKey;
+}
+
+/// Mint and burn proving keys, for wallets without keys of their own.
+pub struct ProvingKeys {
+    pub mint_pk: ProvingKey,
+    pub burn_pk: ProvingKey,
+}
+
+impl ProvingKeys {
+    /// Build the proving keys, from the on-disk [`KeyCache`] when possible.
+    pub fn build() -> Self {
+        let cache = KeyCache::default();
+        Self {
+            mint_pk: cache.proving_key(11, &MintContract::default()),
+            burn_pk: cache.proving_key(11, &BurnContract::default()),
+        }
+    }
+}
+
+impl TransactionProver for ProvingKeys {
+    fn mint_pk(&self) -> &ProvingKey {
+        &self.mint_pk
+    }
+
+    fn burn_pk(&self) -> &ProvingKey {
+        &self.burn_pk
+    }
+}
+
+/// Builds transfers of a single token, from clear or anonymous inputs to
+/// anonymous outputs, along with contract deploys and calls. This is the
+/// one path wallets should construct spends through:
+///
+/// ```ignore
+/// let mut builder = TransactionBuilder::new();
+/// builder.add_input(input);
+/// builder.add_output(output);
+/// builder.set_fee(fee);
+/// let tx = builder.build_with_prover(&ProvingKeys::build())?;
+/// ```
+///
+/// The inputs must add up to the outputs, the fee and the escrow, which is
+/// checked before any proof is created.
+#[derive(Default)]
 pub struct TransactionBuilder {
     pub clear_inputs: Vec<TransactionBuilderClearInputInfo>,
+    /// Clear inputs signed cooperatively, after the transaction is built
+    pub multisig_inputs: Vec<TransactionBuilderMultisigInputInfo>,
     pub inputs: Vec<TransactionBuilderInputInfo>,
     pub outputs: Vec<TransactionBuilderOutputInfo>,
+    /// Contracts to deploy
+    pub deploys: Vec<ContractDeploy>,
+    /// Contract function calls
+    pub calls: Vec<TransactionBuilderCallInfo>,
+    /// Maximum gas the transaction's contract calls may consume
+    pub gas_limit: u64,
+    /// Fee paid for the transaction's inclusion. Fees are paid in DRK, so
+    /// transactions of other tokens can't pay any. The inputs must exceed
+    /// the outputs and escrow by exactly this value.
+    pub fee: u64,
+    /// Value moved into contract escrow, in the transaction's token, on
+    /// top of the fee
+    pub escrow: u64,
 }
 
 pub struct TransactionBuilderClearInputInfo {
@@ -35,6 +104,14 @@ pub struct TransactionBuilderClearInputInfo {
     pub signature_secret: SecretKey,
 }
 
+/// Clear input signed by a group of signers, with the aggregated key of a
+/// [`MuSigKey`](crate::crypto::schnorr::MuSigKey).
+pub struct TransactionBuilderMultisigInputInfo {
+    pub value: u64,
+    pub token_id: DrkTokenId,
+    pub signature_public: PublicKey,
+}
+
 pub struct TransactionBuilderInputInfo {
     pub leaf_position: incrementalmerkletree::Position,
     pub merkle_path: Vec<MerkleNode>,
@@ -42,13 +119,124 @@ pub struct TransactionBuilderInputInfo {
     pub note: Note,
 }
 
+pub struct TransactionBuilderCallInfo {
+    pub call: ContractCall,
+    /// Secret keys signing for the call, setting its `signature_public`
+    pub signature_secrets: Vec<SecretKey>,
+}
+
 pub struct TransactionBuilderOutputInfo {
     pub value: u64,
     pub token_id: DrkTokenId,
     pub public: PublicKey,
+    /// Public key the output's note is encrypted to, the recipient's
+    /// incoming viewing key, or `public` for plain addresses
+    pub view_public: PublicKey,
+    /// Memo for the recipient, encrypted in the output's note
+    pub memo: Vec<u8>,
+    /// Slot until which the output coin can't be spent, 0 for no lock
+    pub lock_slot: u64,
 }
 
 impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an input signed in the clear, like a cashier deposit.
+    pub fn add_clear_input(&mut self, input: TransactionBuilderClearInputInfo) {
+        self.clear_inputs.push(input);
+    }
+
+    /// Add an input signed cooperatively, once the transaction is built.
+    pub fn add_multisig_input(&mut self, input: TransactionBuilderMultisigInputInfo) {
+        self.multisig_inputs.push(input);
+    }
+
+    /// Add an input spending an owned coin.
+    pub fn add_input(&mut self, input: TransactionBuilderInputInfo) {
+        self.inputs.push(input);
+    }
+
+    pub fn add_output(&mut self, output: TransactionBuilderOutputInfo) {
+        self.outputs.push(output);
+    }
+
+    pub fn add_deploy(&mut self, deploy: ContractDeploy) {
+        self.deploys.push(deploy);
+    }
+
+    pub fn add_call(&mut self, call: TransactionBuilderCallInfo) {
+        self.calls.push(call);
+    }
+
+    pub fn set_fee(&mut self, fee: u64) {
+        self.fee = fee;
+    }
+
+    pub fn set_escrow(&mut self, escrow: u64) {
+        self.escrow = escrow;
+    }
+
+    pub fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.gas_limit = gas_limit;
+    }
+
+    /// Check the transaction can be built: it has inputs and outputs of
+    /// a single token, and its inputs add up to its outputs, fee and escrow.
+    pub fn check(&self) -> std::result::Result<(), BuilderFailed> {
+        let clear_inputs = self
+            .clear_inputs
+            .iter()
+            .map(|i| (i.value, i.token_id))
+            .chain(self.multisig_inputs.iter().map(|i| (i.value, i.token_id)));
+        let inputs: Vec<(u64, DrkTokenId)> = clear_inputs
+            .chain(self.inputs.iter().map(|i| (i.note.value, i.note.token_id)))
+            .collect();
+        let outputs: Vec<(u64, DrkTokenId)> =
+            self.outputs.iter().map(|o| (o.value, o.token_id)).collect();
+
+        if inputs.is_empty() {
+            return Err(BuilderFailed::NoInputs)
+        }
+        if outputs.is_empty() {
+            return Err(BuilderFailed::NoOutputs)
+        }
+
+        // Inputs and outputs share a single token blind
+        let token_id = inputs[0].1;
+        if inputs.iter().chain(outputs.iter()).any(|(_, t)| t != &token_id) {
+            return Err(BuilderFailed::MixedTokens)
+        }
+
+        let sum = |values: &[(u64, DrkTokenId)]| {
+            values.iter().try_fold(0u64, |total, (value, _)| total.checked_add(*value))
+        };
+        let inputs_value = sum(&inputs).ok_or(BuilderFailed::ValueOverflow)?;
+        let outputs_value = sum(&outputs)
+            .and_then(|total| total.checked_add(self.escrow))
+            .and_then(|total| total.checked_add(self.fee))
+            .ok_or(BuilderFailed::ValueOverflow)?;
+        if inputs_value != outputs_value {
+            return Err(BuilderFailed::Unbalanced(inputs_value, outputs_value - self.fee, self.fee))
+        }
+
+        if self.fee > 0 && token_id != drk_token_id() {
+            return Err(BuilderFailed::FeeToken)
+        }
+
+        Ok(())
+    }
+
+    /// Build and sign the transaction with the given prover's keys. It
+    /// must not have multisig inputs.
+    pub fn build_with_prover<P: TransactionProver + ?Sized>(
+        self,
+        prover: &P,
+    ) -> Result<Transaction> {
+        self.build(prover.mint_pk(), prover.burn_pk())
+    }
+
     fn compute_remainder_blind(
         clear_inputs: &[PartialTransactionClearInput],
         input_blinds: &[DrkValueBlind],
@@ -71,24 +259,46 @@ impl TransactionBuilder {
         total
     }
 
+    /// Build and sign the transaction. It must not have multisig inputs.
     pub fn build(self, mint_pk: &ProvingKey, burn_pk: &ProvingKey) -> Result<Transaction> {
-        assert!(self.clear_inputs.len() + self.inputs.len() > 0);
+        self.build_unsigned(mint_pk, burn_pk)?.sign(vec![])
+    }
+
+    /// Build the transaction, leaving it to be signed. The signers of the
+    /// multisig inputs sign its [`UnsignedTransaction::data`] meanwhile.
+    pub fn build_unsigned(
+        self,
+        mint_pk: &ProvingKey,
+        burn_pk: &ProvingKey,
+    ) -> Result<UnsignedTransaction> {
+        self.check()?;
 
         let mut clear_inputs = vec![];
         let token_blind = DrkValueBlind::random(&mut OsRng);
-        for input in &self.clear_inputs {
-            let signature_public = PublicKey::from_secret(input.signature_secret);
+        let escrow = (self.escrow > 0).then(|| TransactionEscrow {
+            value: self.escrow,
+            token_id: self.outputs[0].token_id,
+            token_blind,
+        });
+        let fee_token_blind = (self.fee > 0).then(|| token_blind);
+        let clear_input_infos = self
+            .clear_inputs
+            .iter()
+            .map(|i| (i.value, i.token_id, PublicKey::from_secret(i.signature_secret)))
+            .chain(self.multisig_inputs.iter().map(|i| (i.value, i.token_id, i.signature_public)));
+        for (value, token_id, signature_public) in clear_input_infos {
             let value_blind = DrkValueBlind::random(&mut OsRng);
 
             let clear_input = PartialTransactionClearInput {
-                value: input.value,
-                token_id: input.token_id,
+                value,
+                token_id,
                 value_blind,
                 token_blind,
                 signature_public,
             };
             clear_inputs.push(clear_input);
         }
+        let clear_input_secrets = self.clear_inputs.iter().map(|i| i.signature_secret).collect();
 
         let mut inputs = vec![];
         let mut input_blinds = vec![];
@@ -114,6 +324,7 @@ impl TransactionBuilder {
                 spend_hook,
                 user_data,
                 user_data_blind,
+                input.note.lock_slot,
                 input.note.coin_blind,
                 input.secret,
                 input.leaf_position,
@@ -130,9 +341,7 @@ impl TransactionBuilder {
 
         let mut outputs = vec![];
         let mut output_blinds = vec![];
-        // This value_blind calc assumes there will always be at least a single output
-        assert!(!self.outputs.is_empty());
-
+        // The remainder blind goes to the last output, checked to exist above
         for (i, output) in self.outputs.iter().enumerate() {
             let value_blind = if i == self.outputs.len() - 1 {
                 Self::compute_remainder_blind(&clear_inputs, &input_blinds, &output_blinds)
@@ -157,6 +366,7 @@ impl TransactionBuilder {
                 serial,
                 spend_hook,
                 user_data,
+                output.lock_slot,
                 coin_blind,
                 output.public,
             )?;
@@ -169,37 +379,221 @@ impl TransactionBuilder {
                 coin_blind,
                 value_blind,
                 token_blind,
-                memo: vec![],
+                lock_slot: output.lock_slot,
+                memo: output.memo.clone(),
             };
 
-            let encrypted_note = note.encrypt(&output.public)?;
+            let encrypted_note = note.encrypt(&output.view_public)?;
 
             let output = TransactionOutput { mint_proof, revealed, enc_note: encrypted_note };
             outputs.push(output);
         }
 
-        let partial_tx = PartialTransaction { clear_inputs, inputs, outputs };
+        let mut calls = vec![];
+        let mut call_secrets = vec![];
+        for info in self.calls {
+            let mut call = info.call;
+            call.signature_public = info
+                .signature_secrets
+                .iter()
+                .map(|secret| PublicKey::from_secret(*secret))
+                .collect();
+            call_secrets.extend(info.signature_secrets);
+            calls.push(call);
+        }
+
+        let partial_tx = PartialTransaction {
+            clear_inputs,
+            inputs,
+            outputs,
+            deploys: self.deploys,
+            calls,
+            gas_limit: self.gas_limit,
+            fee: self.fee,
+            fee_token_blind,
+            escrow,
+        };
+
+        let mut data = vec![];
+        partial_tx.encode(&mut data)?;
+
+        Ok(UnsignedTransaction {
+            partial_tx,
+            data,
+            clear_input_secrets,
+            input_secrets: signature_secrets,
+            call_secrets,
+        })
+    }
+}
+
+/// Transaction built but not signed yet, waiting for the signatures of its
+/// multisig inputs.
+pub struct UnsignedTransaction {
+    partial_tx: PartialTransaction,
+    data: Vec<u8>,
+    clear_input_secrets: Vec<SecretKey>,
+    input_secrets: Vec<SecretKey>,
+    call_secrets: Vec<SecretKey>,
+}
+
+impl UnsignedTransaction {
+    /// Message every signature of the transaction signs.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Public keys the multisig inputs must be signed with, in order.
+    pub fn multisig_publics(&self) -> Vec<PublicKey> {
+        self.partial_tx.clear_inputs[self.clear_input_secrets.len()..]
+            .iter()
+            .map(|input| input.signature_public)
+            .collect()
+    }
+
+    /// Sign the transaction, given the signatures of its multisig inputs,
+    /// in order. Each of them is checked against its input's public key.
+    pub fn sign(self, multisig_signatures: Vec<Signature>) -> Result<Transaction> {
+        let multisig_publics = self.multisig_publics();
+        let offset = self.clear_input_secrets.len();
+        if multisig_signatures.len() != multisig_publics.len() {
+            let index = offset + multisig_signatures.len().min(multisig_publics.len());
+            return Err(Error::VerifyFailed(VerifyFailed::ClearInputSignature(index)))
+        }
+        for (i, (public, signature)) in
+            multisig_publics.iter().zip(&multisig_signatures).enumerate()
+        {
+            if !public.verify(&self.data, signature) {
+                return Err(Error::VerifyFailed(VerifyFailed::ClearInputSignature(offset + i)))
+            }
+        }
 
-        let mut unsigned_tx_data = vec![];
-        partial_tx.encode(&mut unsigned_tx_data)?;
+        let data = &self.data[..];
+        let signatures = self
+            .clear_input_secrets
+            .iter()
+            .map(|secret| secret.sign(data))
+            .chain(multisig_signatures);
 
         let mut clear_inputs = vec![];
-        for (input, info) in partial_tx.clear_inputs.into_iter().zip(self.clear_inputs) {
-            let secret = info.signature_secret;
-            let signature = secret.sign(&unsigned_tx_data[..]);
+        for (input, signature) in self.partial_tx.clear_inputs.into_iter().zip(signatures) {
             let input = TransactionClearInput::from_partial(input, signature);
             clear_inputs.push(input);
         }
 
         let mut inputs = vec![];
-        for (input, signature_secret) in
-            partial_tx.inputs.into_iter().zip(signature_secrets.into_iter())
+        for (input, signature_secret) in self.partial_tx.inputs.into_iter().zip(self.input_secrets)
         {
-            let signature = signature_secret.sign(&unsigned_tx_data[..]);
+            let signature = signature_secret.sign(data);
             let input = TransactionInput::from_partial(input, signature);
             inputs.push(input);
         }
 
-        Ok(Transaction { clear_inputs, inputs, outputs: partial_tx.outputs })
+        let call_signatures = self.call_secrets.iter().map(|secret| secret.sign(data)).collect();
+
+        Ok(Transaction {
+            clear_inputs,
+            inputs,
+            outputs: self.partial_tx.outputs,
+            deploys: self.partial_tx.deploys,
+            calls: self.partial_tx.calls,
+            call_signatures,
+            gas_limit: self.partial_tx.gas_limit,
+            fee: self.partial_tx.fee,
+            fee_token_blind: self.partial_tx.fee_token_blind,
+            escrow: self.partial_tx.escrow,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::pallas;
+
+    use super::*;
+    use crate::crypto::keypair::Keypair;
+
+    fn clear_input(value: u64, token_id: DrkTokenId) -> TransactionBuilderClearInputInfo {
+        TransactionBuilderClearInputInfo {
+            value,
+            token_id,
+            signature_secret: SecretKey::random(&mut OsRng),
+        }
+    }
+
+    fn output(value: u64, token_id: DrkTokenId) -> TransactionBuilderOutputInfo {
+        let public = Keypair::random(&mut OsRng).public;
+        TransactionBuilderOutputInfo {
+            value,
+            token_id,
+            public,
+            view_public: public,
+            memo: vec![],
+            lock_slot: 0,
+        }
+    }
+
+    #[test]
+    fn test_builder_check() {
+        let token_id = drk_token_id();
+
+        let mut builder = TransactionBuilder::new();
+        assert!(matches!(builder.check(), Err(BuilderFailed::NoInputs)));
+
+        builder.add_clear_input(clear_input(60, token_id));
+        builder.add_multisig_input(TransactionBuilderMultisigInputInfo {
+            value: 40,
+            token_id,
+            signature_public: Keypair::random(&mut OsRng).public,
+        });
+        assert!(matches!(builder.check(), Err(BuilderFailed::NoOutputs)));
+
+        builder.add_output(output(90, token_id));
+        assert!(matches!(builder.check(), Err(BuilderFailed::Unbalanced(100, 90, 0))));
+
+        // The fee makes up the difference
+        builder.set_fee(10);
+        assert!(builder.check().is_ok());
+        builder.set_fee(20);
+        assert!(matches!(builder.check(), Err(BuilderFailed::Unbalanced(100, 90, 20))));
+    }
+
+    #[test]
+    fn test_builder_check_mixed_tokens() {
+        let mut builder = TransactionBuilder::new();
+        builder.add_clear_input(clear_input(10, pallas::Base::from(1)));
+        builder.add_output(output(10, pallas::Base::from(2)));
+        assert!(matches!(builder.check(), Err(BuilderFailed::MixedTokens)));
+
+        let mut builder = TransactionBuilder::new();
+        builder.add_clear_input(clear_input(5, pallas::Base::from(1)));
+        builder.add_clear_input(clear_input(5, pallas::Base::from(2)));
+        builder.add_output(output(10, pallas::Base::from(1)));
+        assert!(matches!(builder.check(), Err(BuilderFailed::MixedTokens)));
+
+        // Fees are only paid in DRK
+        let mut builder = TransactionBuilder::new();
+        builder.add_clear_input(clear_input(10, pallas::Base::from(1)));
+        builder.add_output(output(9, pallas::Base::from(1)));
+        builder.set_fee(1);
+        assert!(matches!(builder.check(), Err(BuilderFailed::FeeToken)));
+    }
+
+    #[test]
+    fn test_builder_check_overflow() {
+        let token_id = pallas::Base::from(42);
+
+        let mut builder = TransactionBuilder::new();
+        builder.add_clear_input(clear_input(u64::MAX, token_id));
+        builder.add_clear_input(clear_input(1, token_id));
+        builder.add_output(output(1, token_id));
+        assert!(matches!(builder.check(), Err(BuilderFailed::ValueOverflow)));
+
+        // Outputs and fee overflowing together
+        let mut builder = TransactionBuilder::new();
+        builder.add_clear_input(clear_input(u64::MAX, token_id));
+        builder.add_output(output(u64::MAX, token_id));
+        builder.set_fee(1);
+        assert!(matches!(builder.check(), Err(BuilderFailed::ValueOverflow)));
     }
 }
@@ -1,7 +1,7 @@
 use std::io;
 
 use log::error;
-use pasta_curves::group::Group;
+use pasta_curves::group::{ff::Field, Group};
 
 use crate::{
     crypto::{
@@ -11,8 +11,9 @@ use crate::{
         note::EncryptedNote,
         proof::VerifyingKey,
         schnorr,
-        schnorr::SchnorrPublic,
-        types::{DrkTokenId, DrkValueBlind, DrkValueCommit},
+        schnorr::{batch_verify, SchnorrPublic},
+        token_id::drk_token_id,
+        types::{DrkCircuitField, DrkTokenId, DrkValueBlind, DrkValueCommit},
         util::{pedersen_commitment_base, pedersen_commitment_u64},
         BurnRevealedValues, MintRevealedValues, Proof,
     },
@@ -23,6 +24,10 @@ use crate::{
 pub mod builder;
 pub mod partial;
 
+/// Upper bound on the gas limit of a single transaction, so no transaction
+/// can keep validators busy for longer than this.
+pub const MAX_GAS_LIMIT: u64 = 10_000_000;
+
 /// A DarkFi transaction
 #[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
 pub struct Transaction {
@@ -32,6 +37,25 @@ pub struct Transaction {
     pub inputs: Vec<TransactionInput>,
     /// Anonymous outputs
     pub outputs: Vec<TransactionOutput>,
+    /// Contracts deployed by the transaction
+    pub deploys: Vec<ContractDeploy>,
+    /// Contract function calls, executed in order
+    pub calls: Vec<ContractCall>,
+    /// Signatures of the call public keys, in call order
+    pub call_signatures: Vec<schnorr::Signature>,
+    /// Maximum gas the transaction's contract calls may consume
+    pub gas_limit: u64,
+    /// Value left out of the outputs and burned, paid for the
+    /// transaction's inclusion. Validators include transactions
+    /// paying the highest fee per byte first.
+    pub fee: u64,
+    /// Blinding factor of the transaction's token commitments, revealed
+    /// to prove the fee is paid in DRK, so fees of different transactions
+    /// compare. Required whenever `fee` isn't zero.
+    pub fee_token_blind: Option<DrkValueBlind>,
+    /// Value left out of the outputs and held by the transaction's
+    /// contract calls, like the funds of a swap lock
+    pub escrow: Option<TransactionEscrow>,
 }
 
 /// A transaction's clear input
@@ -73,9 +97,151 @@ pub struct TransactionOutput {
     pub enc_note: EncryptedNote,
 }
 
+/// Value a transaction moves into contract escrow. It's revealed in the
+/// clear along with its token, so the contracts holding it can account
+/// for it, and released by later transactions through clear inputs.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TransactionEscrow {
+    /// Escrowed value (amount)
+    pub value: u64,
+    /// Escrowed token ID
+    pub token_id: DrkTokenId,
+    /// Blinding factor for `token_id`, shared by the transaction's token
+    /// commitments
+    pub token_blind: DrkValueBlind,
+}
+
+/// Deployment of a wasm contract. Once the transaction is applied, the
+/// contract is registered under its contract ID and can be called by
+/// later transactions.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct ContractDeploy {
+    /// Compiled wasm bincode of the contract
+    pub wasm_bincode: Vec<u8>,
+    /// Deployer-chosen salt, so the same bincode can be deployed more than once
+    pub salt: [u8; 32],
+}
+
+impl ContractDeploy {
+    /// Contract ID the contract gets registered under, derived from the
+    /// salt and the bincode.
+    pub fn contract_id(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.salt);
+        hasher.update(&self.wasm_bincode);
+        *hasher.finalize().as_bytes()
+    }
+}
+
+/// A call to a function of a native or wasm contract. Calls are routed
+/// to their contract by the node's dispatcher, and the contract handler
+/// interprets the call data and verifies the proofs.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct ContractCall {
+    /// Contract the call is routed to
+    pub contract_id: [u8; 32],
+    /// Function of the contract being called
+    pub func_id: u8,
+    /// Serialized call data
+    pub call_data: Vec<u8>,
+    /// Zero-knowledge proofs over the call data
+    pub proofs: Vec<CallProof>,
+    /// Public keys that must sign the transaction for the call to be valid
+    pub signature_public: Vec<PublicKey>,
+}
+
+/// A zk proof of a contract call, along with the registered circuit it
+/// was made for. Validators pick the verifying key by the declared
+/// version, so proofs of a replaced circuit version keep verifying during
+/// its transition window.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct CallProof {
+    /// Name the circuit is registered under
+    pub circuit: String,
+    /// Version of the circuit the proof was made with
+    pub version: u32,
+    pub proof: Proof,
+    /// Public inputs the proof is verified over, which the called
+    /// contract checks against its call data
+    pub public_inputs: Vec<DrkCircuitField>,
+}
+
+/// Verify the zk proofs and signatures of independent transactions. The
+/// proofs are verified concurrently, spreading them over the available CPU
+/// cores, and the signatures of all transactions in a single batch. Results
+/// are returned in the order of the given transactions.
+pub fn verify_transactions(
+    txs: &[Transaction],
+    mint_vk: &VerifyingKey,
+    burn_vk: &VerifyingKey,
+) -> Vec<VerifyResult<()>> {
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut results: Vec<VerifyResult<()>> = if txs.len() <= 1 || threads == 1 {
+        txs.iter().map(|tx| tx.verify_proofs(mint_vk, burn_vk)).collect()
+    } else {
+        let chunk_size = (txs.len() + threads - 1) / threads;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = txs
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|tx| tx.verify_proofs(mint_vk, burn_vk))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    };
+
+    let mut signed = vec![];
+    for (i, tx) in txs.iter().enumerate() {
+        if results[i].is_ok() {
+            match tx.signatures() {
+                Ok((data, signatures)) => signed.push((i, data, signatures)),
+                Err(e) => results[i] = Err(e),
+            }
+        }
+    }
+
+    let batch: Vec<(&PublicKey, &[u8], &schnorr::Signature)> = signed
+        .iter()
+        .flat_map(|(_, data, signatures)| {
+            signatures.iter().map(move |(public, signature)| (*public, &data[..], *signature))
+        })
+        .collect();
+    if batch_verify(&batch) {
+        return results
+    }
+
+    for (i, data, _) in &signed {
+        results[*i] = txs[*i].find_bad_signature(data);
+    }
+    results
+}
+
 impl Transaction {
     /// Verify the transaction
     pub fn verify(&self, mint_vk: &VerifyingKey, burn_vk: &VerifyingKey) -> VerifyResult<()> {
+        self.verify_proofs(mint_vk, burn_vk)?;
+
+        // All signatures are checked in one go, and only if the batch fails
+        // they are checked one by one to find the culprit.
+        let (data, signatures) = self.signatures()?;
+        let batch: Vec<(&PublicKey, &[u8], &schnorr::Signature)> =
+            signatures.iter().map(|(public, signature)| (*public, &data[..], *signature)).collect();
+        if batch_verify(&batch) {
+            return Ok(())
+        }
+
+        self.find_bad_signature(&data)
+    }
+
+    /// Verify everything about the transaction but its signatures.
+    fn verify_proofs(&self, mint_vk: &VerifyingKey, burn_vk: &VerifyingKey) -> VerifyResult<()> {
         // Transaction must have minimum 1 clear or anon input, and 1 output
         if self.clear_inputs.len() + self.inputs.len() == 0 {
             error!("tx::verify(): Missing inputs");
@@ -85,6 +251,10 @@ impl Transaction {
             error!("tx::verify(): Missing outputs");
             return Err(VerifyFailed::LackingOutputs)
         }
+        if self.gas_limit > MAX_GAS_LIMIT {
+            error!("tx::verify(): Gas limit {} over maximum", self.gas_limit);
+            return Err(VerifyFailed::GasLimit(self.gas_limit))
+        }
 
         // Accumulator for the value commitments
         let mut valcom_total = DrkValueCommit::identity();
@@ -116,6 +286,14 @@ impl Transaction {
             }
         }
 
+        // The fee is revealed in the clear, so its commitment has no blind
+        valcom_total -= pedersen_commitment_u64(self.fee, DrkValueBlind::zero());
+
+        // So is the escrowed value
+        if let Some(escrow) = &self.escrow {
+            valcom_total -= pedersen_commitment_u64(escrow.value, DrkValueBlind::zero());
+        }
+
         // If the accumulator is not back in its initial state,
         // there's a value mismatch.
         if valcom_total != DrkValueCommit::identity() {
@@ -129,13 +307,49 @@ impl Transaction {
             return Err(VerifyFailed::TokenMismatch)
         }
 
-        // Verify the available signatures
+        // Verify that the fee is paid in DRK
+        if self.fee > 0 && !self.verify_fee_token() {
+            error!("tx::verify(): Fee not paid in DRK");
+            return Err(VerifyFailed::FeeToken)
+        }
+
+        Ok(())
+    }
+
+    /// Message the transaction's signatures are made over, along with each
+    /// signature and the public key it's verified against.
+    fn signatures(&self) -> VerifyResult<(Vec<u8>, Vec<(&PublicKey, &schnorr::Signature)>)> {
         let mut unsigned_tx_data = vec![];
         self.encode_without_signature(&mut unsigned_tx_data)?;
 
+        let call_publics: Vec<&PublicKey> =
+            self.calls.iter().flat_map(|call| &call.signature_public).collect();
+        if call_publics.len() != self.call_signatures.len() {
+            error!("tx::verify(): Call signature count mismatch");
+            return Err(VerifyFailed::CallSignature(self.call_signatures.len()))
+        }
+
+        let signatures = self
+            .clear_inputs
+            .iter()
+            .map(|input| (&input.signature_public, &input.signature))
+            .chain(
+                self.inputs
+                    .iter()
+                    .map(|input| (&input.revealed.signature_public, &input.signature)),
+            )
+            .chain(call_publics.into_iter().zip(&self.call_signatures))
+            .collect();
+
+        Ok((unsigned_tx_data, signatures))
+    }
+
+    /// Verify the signatures one by one, after a batch including them failed,
+    /// to find the invalid one.
+    fn find_bad_signature(&self, unsigned_tx_data: &[u8]) -> VerifyResult<()> {
         for (i, input) in self.clear_inputs.iter().enumerate() {
             let public = &input.signature_public;
-            if !public.verify(&unsigned_tx_data[..], &input.signature) {
+            if !public.verify(unsigned_tx_data, &input.signature) {
                 error!("tx::verify(): Failed to verify Clear Input signature {}", i);
                 return Err(VerifyFailed::ClearInputSignature(i))
             }
@@ -143,12 +357,20 @@ impl Transaction {
 
         for (i, input) in self.inputs.iter().enumerate() {
             let public = &input.revealed.signature_public;
-            if !public.verify(&unsigned_tx_data[..], &input.signature) {
+            if !public.verify(unsigned_tx_data, &input.signature) {
                 error!("tx::verify(): Failed to verify Input signature {}", i);
                 return Err(VerifyFailed::InputSignature(i))
             }
         }
 
+        let call_publics = self.calls.iter().flat_map(|call| &call.signature_public);
+        for (i, (public, signature)) in call_publics.zip(&self.call_signatures).enumerate() {
+            if !public.verify(unsigned_tx_data, signature) {
+                error!("tx::verify(): Failed to verify Call signature {}", i);
+                return Err(VerifyFailed::CallSignature(i))
+            }
+        }
+
         Ok(())
     }
 
@@ -156,7 +378,13 @@ impl Transaction {
         let mut len = 0;
         len += self.clear_inputs.encode_without_signature(&mut s)?;
         len += self.inputs.encode_without_signature(&mut s)?;
-        len += self.outputs.encode(s)?;
+        len += self.outputs.encode(&mut s)?;
+        len += self.deploys.encode(&mut s)?;
+        len += self.calls.encode(&mut s)?;
+        len += self.gas_limit.encode(&mut s)?;
+        len += self.fee.encode(&mut s)?;
+        len += self.fee_token_blind.encode(&mut s)?;
+        len += self.escrow.encode(s)?;
         Ok(len)
     }
 
@@ -174,8 +402,24 @@ impl Transaction {
             self.clear_inputs.iter().any(|input| {
                 pedersen_commitment_base(input.token_id, input.token_blind) != token_commit_value
             });
+
+        failed = failed ||
+            self.escrow.iter().any(|escrow| {
+                pedersen_commitment_base(escrow.token_id, escrow.token_blind) != token_commit_value
+            });
         !failed
     }
+
+    /// Check the revealed fee token blind opens the token commitments to DRK.
+    fn verify_fee_token(&self) -> bool {
+        match self.fee_token_blind {
+            Some(blind) => {
+                pedersen_commitment_base(drk_token_id(), blind) ==
+                    self.outputs[0].revealed.token_commit
+            }
+            None => false,
+        }
+    }
 }
 
 impl TransactionClearInput {
@@ -192,7 +436,22 @@ impl TransactionClearInput {
             signature,
         }
     }
+}
+
+impl TransactionInput {
+    pub fn from_partial(
+        partial: partial::PartialTransactionInput,
+        signature: schnorr::Signature,
+    ) -> Self {
+        Self { burn_proof: partial.burn_proof, revealed: partial.revealed, signature }
+    }
+}
 
+trait EncodableWithoutSignature {
+    fn encode_without_signature<S: io::Write>(&self, s: S) -> Result<usize>;
+}
+
+impl EncodableWithoutSignature for TransactionClearInput {
     fn encode_without_signature<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.value.encode(&mut s)?;
@@ -204,14 +463,7 @@ impl TransactionClearInput {
     }
 }
 
-impl TransactionInput {
-    pub fn from_partial(
-        partial: partial::PartialTransactionInput,
-        signature: schnorr::Signature,
-    ) -> Self {
-        Self { burn_proof: partial.burn_proof, revealed: partial.revealed, signature }
-    }
-
+impl EncodableWithoutSignature for TransactionInput {
     fn encode_without_signature<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.burn_proof.encode(&mut s)?;
@@ -220,24 +472,13 @@ impl TransactionInput {
     }
 }
 
-trait EncodableWithoutSignature {
-    fn encode_without_signature<S: io::Write>(&self, s: S) -> Result<usize>;
-}
-
-macro_rules! impl_vec_without_signature {
-    ($type: ty) => {
-        impl EncodableWithoutSignature for Vec<$type> {
-            #[inline]
-            fn encode_without_signature<S: io::Write>(&self, mut s: S) -> Result<usize> {
-                let mut len = 0;
-                len += VarInt(self.len() as u64).encode(&mut s)?;
-                for c in self.iter() {
-                    len += c.encode_without_signature(&mut s)?;
-                }
-                Ok(len)
-            }
+impl<T: EncodableWithoutSignature> EncodableWithoutSignature for Vec<T> {
+    fn encode_without_signature<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += VarInt(self.len() as u64).encode(&mut s)?;
+        for c in self.iter() {
+            len += c.encode_without_signature(&mut s)?;
         }
-    };
+        Ok(len)
+    }
 }
-impl_vec_without_signature!(TransactionClearInput);
-impl_vec_without_signature!(TransactionInput);
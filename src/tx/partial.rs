@@ -1,4 +1,4 @@
-use super::TransactionOutput;
+use super::{ContractCall, ContractDeploy, TransactionEscrow, TransactionOutput};
 use crate::{
     crypto::{
         keypair::PublicKey,
@@ -13,6 +13,12 @@ pub struct PartialTransaction {
     pub clear_inputs: Vec<PartialTransactionClearInput>,
     pub inputs: Vec<PartialTransactionInput>,
     pub outputs: Vec<TransactionOutput>,
+    pub deploys: Vec<ContractDeploy>,
+    pub calls: Vec<ContractCall>,
+    pub gas_limit: u64,
+    pub fee: u64,
+    pub fee_token_blind: Option<DrkValueBlind>,
+    pub escrow: Option<TransactionEscrow>,
 }
 
 #[derive(Clone, SerialEncodable, SerialDecodable)]
@@ -1,10 +1,36 @@
+use std::mem;
+
+use async_trait::async_trait;
 use futures::prelude::*;
 
-use super::{endian, serial::VarInt};
+use super::{
+    endian,
+    serial::{DecodeLimits, VarInt},
+};
 use crate::{Error, Result};
 
-impl VarInt {
-    pub async fn encode_async<W: AsyncWrite + Unpin>(&self, stream: &mut W) -> Result<usize> {
+/// Data which can be encoded straight into an async stream, in the same
+/// format as [`Encodable`](super::serial::Encodable).
+#[async_trait]
+pub trait AsyncEncodable {
+    /// Encode an object into the stream. Returns the number of bytes written
+    /// on success.
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize>;
+}
+
+/// Data which can be decoded straight from an async stream, in the same
+/// format as [`Decodable`](super::serial::Decodable). Collection lengths are
+/// checked against the default [`DecodeLimits`], as streams are expected to
+/// come from the network.
+#[async_trait]
+pub trait AsyncDecodable: Sized {
+    /// Decode an object from the stream
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self>;
+}
+
+#[async_trait]
+impl AsyncEncodable for VarInt {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
         match self.0 {
             0..=0xFC => {
                 AsyncWriteExt::write_u8(stream, self.0 as u8).await?;
@@ -27,8 +53,11 @@ impl VarInt {
             }
         }
     }
+}
 
-    pub async fn decode_async<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Self> {
+#[async_trait]
+impl AsyncDecodable for VarInt {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
         let n = AsyncReadExt::read_u8(stream).await?;
         match n {
             0xFF => {
@@ -60,6 +89,181 @@ impl VarInt {
     }
 }
 
+/// Decode the length prefix of a collection, checking it against the default
+/// limits.
+async fn decode_len_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<u64> {
+    let len = VarInt::decode_async(stream).await?.0;
+    if len > DecodeLimits::default().max_len {
+        return Err(Error::ParseFailed("collection length exceeds limit"))
+    }
+    Ok(len)
+}
+
+// Primitive types
+macro_rules! impl_int_async_encodable {
+    ($ty:ident) => {
+        #[async_trait]
+        impl AsyncEncodable for $ty {
+            #[inline]
+            async fn encode_async<W: AsyncWrite + Unpin + Send>(
+                &self,
+                stream: &mut W,
+            ) -> Result<usize> {
+                stream.write_all(&self.to_le_bytes()).await?;
+                Ok(mem::size_of::<$ty>())
+            }
+        }
+
+        #[async_trait]
+        impl AsyncDecodable for $ty {
+            #[inline]
+            async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+                let mut bytes = [0u8; mem::size_of::<$ty>()];
+                stream.read_exact(&mut bytes).await?;
+                Ok($ty::from_le_bytes(bytes))
+            }
+        }
+    };
+}
+
+impl_int_async_encodable!(u8);
+impl_int_async_encodable!(u16);
+impl_int_async_encodable!(u32);
+impl_int_async_encodable!(u64);
+impl_int_async_encodable!(u128);
+impl_int_async_encodable!(i8);
+impl_int_async_encodable!(i16);
+impl_int_async_encodable!(i32);
+impl_int_async_encodable!(i64);
+impl_int_async_encodable!(i128);
+
+#[async_trait]
+impl AsyncEncodable for bool {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        (*self as u8).encode_async(stream).await
+    }
+}
+
+#[async_trait]
+impl AsyncDecodable for bool {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        Ok(u8::decode_async(stream).await? != 0)
+    }
+}
+
+#[async_trait]
+impl<const N: usize> AsyncEncodable for [u8; N] {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        stream.write_all(&self[..]).await?;
+        Ok(N)
+    }
+}
+
+#[async_trait]
+impl<const N: usize> AsyncDecodable for [u8; N] {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        let mut ret = [0u8; N];
+        stream.read_exact(&mut ret).await?;
+        Ok(ret)
+    }
+}
+
+#[async_trait]
+impl AsyncEncodable for blake3::Hash {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        self.as_bytes().encode_async(stream).await
+    }
+}
+
+#[async_trait]
+impl AsyncDecodable for blake3::Hash {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        Ok(<[u8; 32]>::decode_async(stream).await?.into())
+    }
+}
+
+// Strings are read in one go rather than byte per byte
+#[async_trait]
+impl AsyncEncodable for String {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        let vi_len = VarInt(self.len() as u64).encode_async(stream).await?;
+        stream.write_all(self.as_bytes()).await?;
+        Ok(vi_len + self.len())
+    }
+}
+
+#[async_trait]
+impl AsyncDecodable for String {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        let len = decode_len_async(stream).await? as usize;
+        let mut bytes = vec![];
+        stream.take(len as u64).read_to_end(&mut bytes).await?;
+        if bytes.len() != len {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        }
+        String::from_utf8(bytes).map_err(|_| Error::ParseFailed("String was not valid UTF8"))
+    }
+}
+
+// Options
+#[async_trait]
+impl<T: AsyncEncodable + Sync> AsyncEncodable for Option<T> {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        match self {
+            Some(v) => Ok(true.encode_async(stream).await? + v.encode_async(stream).await?),
+            None => false.encode_async(stream).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: AsyncDecodable + Send> AsyncDecodable for Option<T> {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        match bool::decode_async(stream).await? {
+            true => Ok(Some(T::decode_async(stream).await?)),
+            false => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: AsyncEncodable + Sync> AsyncEncodable for Vec<T> {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        let mut len = VarInt(self.len() as u64).encode_async(stream).await?;
+        for val in self {
+            len += val.encode_async(stream).await?;
+        }
+        Ok(len)
+    }
+}
+
+#[async_trait]
+impl<T: AsyncDecodable + Send> AsyncDecodable for Vec<T> {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        let len = decode_len_async(stream).await?;
+        // Grow as elements are actually read, rather than trusting the prefix
+        let mut ret = vec![];
+        for _ in 0..len {
+            ret.push(T::decode_async(stream).await?);
+        }
+        Ok(ret)
+    }
+}
+
+#[async_trait]
+impl<T0: AsyncEncodable + Sync, T1: AsyncEncodable + Sync> AsyncEncodable for (T0, T1) {
+    async fn encode_async<W: AsyncWrite + Unpin + Send>(&self, stream: &mut W) -> Result<usize> {
+        Ok(self.0.encode_async(stream).await? + self.1.encode_async(stream).await?)
+    }
+}
+
+#[async_trait]
+impl<T0: AsyncDecodable + Send, T1: AsyncDecodable + Send> AsyncDecodable for (T0, T1) {
+    async fn decode_async<R: AsyncRead + Unpin + Send>(stream: &mut R) -> Result<Self> {
+        Ok((T0::decode_async(stream).await?, T1::decode_async(stream).await?))
+    }
+}
+
 macro_rules! async_encoder_fn {
     ($name:ident, $val_type:ty, $writefn:ident) => {
         #[inline]
@@ -105,3 +309,37 @@ impl AsyncWriteExt {
         stream.write_all(&[v]).await.map_err(|e| Error::Io(e.kind()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::io::Cursor;
+
+    use super::*;
+    use crate::util::serial::{deserialize, serialize};
+
+    #[async_std::test]
+    async fn async_serial_matches_serial() -> Result<()> {
+        let data: (Vec<(u64, String)>, Option<[u8; 4]>) = (
+            vec![(0, String::new()), (0x10000, "ohai".to_string()), (u64::MAX, "a".repeat(300))],
+            Some([1, 2, 3, 4]),
+        );
+
+        let mut stream = Cursor::new(vec![]);
+        let len = data.encode_async(&mut stream).await?;
+        let bytes = stream.into_inner();
+        assert_eq!(len, bytes.len());
+        assert_eq!(bytes, serialize(&data));
+
+        let decoded =
+            <(Vec<(u64, String)>, Option<[u8; 4]>)>::decode_async(&mut &bytes[..]).await?;
+        assert_eq!(decoded, data);
+        assert_eq!(deserialize::<(Vec<(u64, String)>, Option<[u8; 4]>)>(&bytes)?, data);
+
+        // Truncated streams fail
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(<(Vec<(u64, String)>, Option<[u8; 4]>)>::decode_async(&mut &truncated[..])
+            .await
+            .is_err());
+        Ok(())
+    }
+}
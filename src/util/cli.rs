@@ -1,17 +1,26 @@
 use std::{
-    env, fs,
-    io::Write,
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
     marker::PhantomData,
     path::{Path, PathBuf},
     str,
+    str::FromStr,
+    sync::Mutex,
     time::Duration,
 };
 
+use chrono::Utc;
 use indicatif::{ProgressBar, ProgressStyle};
-use serde::{de::DeserializeOwned, Serialize};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
 use simplelog::ConfigBuilder;
 use termion::color;
 
+use super::path::expand_path;
 use crate::{Error, Result};
 
 #[derive(Clone, Default)]
@@ -82,6 +91,241 @@ pub fn get_log_config() -> simplelog::Config {
     }
 }
 
+/// Output format of the log lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Logging settings of a daemon, read from the `[log]` table of its
+/// TOML config:
+///
+/// ```toml
+/// [log]
+/// format = "json"
+/// file = "~/.local/darkfi/darkfid.log"
+/// max_size = 10485760
+/// max_files = 5
+///
+/// [log.targets]
+/// "darkfi::net" = "warn"
+/// "darkfi::consensus" = "debug"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Output format
+    pub format: LogFormat,
+    /// Level of the targets logging with a given prefix, overriding the
+    /// verbosity level. The longest matching prefix applies.
+    pub targets: BTreeMap<String, String>,
+    /// Log file path, defaulting to `DARKFI_LOG` or `~/.local/darkfi.log`
+    pub file: Option<String>,
+    /// Size in bytes past which the log file gets rotated, 0 to never rotate
+    pub max_size: u64,
+    /// Number of rotated log files kept
+    pub max_files: usize,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            targets: BTreeMap::new(),
+            file: None,
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+impl LogSettings {
+    /// Parse the `[log]` table of a TOML config, using the defaults if
+    /// there is none.
+    pub fn from_config(data: &str) -> Result<Self> {
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct Config {
+            log: LogSettings,
+        }
+
+        let config: Config = toml::from_str(data)?;
+        Ok(config.log)
+    }
+}
+
+/// Log file rotated once it grows past a size: `file` is renamed to
+/// `file.1`, `file.1` to `file.2`, and so on, dropping the oldest.
+struct RotatingFile {
+    path: PathBuf,
+    file: fs::File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size, max_size, max_files })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(&self.path);
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        self.file =
+            fs::OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.max_size > 0 && self.size >= self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Daemon logger, writing to the terminal and a rotated log file, with
+/// per-target level filters.
+pub struct Logger {
+    level: LevelFilter,
+    /// Target prefixes and their level, longest first
+    targets: Vec<(String, LevelFilter)>,
+    format: LogFormat,
+    file: Mutex<RotatingFile>,
+}
+
+impl Logger {
+    pub fn new(level: LevelFilter, settings: &LogSettings) -> Result<Self> {
+        let mut targets = Vec::with_capacity(settings.targets.len());
+        for (target, level) in &settings.targets {
+            let level = match LevelFilter::from_str(level) {
+                Ok(l) => l,
+                Err(_) => return Err(Error::ConfigInvalid),
+            };
+            targets.push((target.clone(), level));
+        }
+        targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+
+        let path = match &settings.file {
+            Some(p) => p.clone(),
+            None => match env::var("DARKFI_LOG") {
+                Ok(p) => p,
+                Err(_) => "~/.local/darkfi.log".into(),
+            },
+        };
+        let file = RotatingFile::open(expand_path(&path)?, settings.max_size, settings.max_files)?;
+
+        Ok(Self { level, targets, format: settings.format, file: Mutex::new(file) })
+    }
+
+    /// Level of a target: the one of its longest configured prefix, or
+    /// the verbosity level.
+    fn target_level(&self, target: &str) -> LevelFilter {
+        for (prefix, level) in &self.targets {
+            if target == prefix ||
+                (target.starts_with(prefix.as_str()) && target[prefix.len()..].starts_with("::"))
+            {
+                return *level
+            }
+        }
+        self.level
+    }
+
+    /// Most verbose level any target logs at
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.level, std::cmp::max)
+    }
+
+    fn format_record(&self, record: &Record) -> String {
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        match self.format {
+            LogFormat::Text => {
+                format!("{} [{}] {}: {}", timestamp, record.level(), record.target(), record.args())
+            }
+            LogFormat::Json => json!({
+                "timestamp": timestamp.to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.target_level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return
+        }
+
+        let line = self.format_record(record);
+        match record.level() {
+            log::Level::Error | log::Level::Warn => eprintln!("{}", line),
+            _ => println!("{}", line),
+        }
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(e) = file.write_line(&line) {
+                eprintln!("Failed writing to log file: {}", e);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Set up the daemon logger, with the given verbosity level and the
+/// logging settings of the given TOML config.
+pub fn init_logger(level: LevelFilter, config: &str) -> Result<()> {
+    let settings = LogSettings::from_config(config)?;
+    let logger = Logger::new(level, &settings)?;
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(logger))?;
+    Ok(())
+}
+
 pub const ANSI_LOGO: &str = include_str!("../../contrib/darkfi.ansi");
 
 #[macro_export]
@@ -107,7 +351,6 @@ macro_rules! cli_desc {
 /// darkfi = { path = "../../", features = ["util"] }
 /// easy-parallel = "3.2.0"
 /// futures-lite = "1.12.0"
-/// simplelog = "0.12.0-alpha1"
 ///
 /// # Argument parsing
 /// serde = "1.0.136"
@@ -125,8 +368,8 @@ macro_rules! cli_desc {
 /// use darkfi::{
 ///     async_daemonize, cli_desc,
 ///     util::{
-///         cli::{get_log_config, get_log_level, spawn_config},
-///         path::get_config_path, expand_path
+///         cli::{get_log_level, spawn_config},
+///         path::get_config_path
 ///     },
 ///     Result,
 /// };
@@ -160,31 +403,11 @@ macro_rules! async_daemonize {
             let args = Args::from_args_with_toml("").unwrap();
             let cfg_path = get_config_path(args.config, CONFIG_FILE)?;
             spawn_config(&cfg_path, CONFIG_FILE_CONTENTS.as_bytes())?;
-            let args = Args::from_args_with_toml(&std::fs::read_to_string(cfg_path)?).unwrap();
+            let toml_contents = std::fs::read_to_string(cfg_path)?;
+            let args = Args::from_args_with_toml(&toml_contents).unwrap();
 
             let log_level = get_log_level(args.verbose.into());
-            let log_config = get_log_config();
-
-            let log_file_path = match std::env::var("DARKFI_LOG") {
-                Ok(p) => p,
-                Err(_) => {
-                    std::fs::create_dir_all(expand_path("~/.local")?)?;
-                    "~/.local/darkfi.log".into()
-                }
-            };
-
-            let log_file_path = expand_path(&log_file_path)?;
-            let log_file = std::fs::File::create(log_file_path)?;
-
-            simplelog::CombinedLogger::init(vec![
-                simplelog::TermLogger::new(
-                    log_level,
-                    log_config.clone(),
-                    simplelog::TerminalMode::Mixed,
-                    simplelog::ColorChoice::Auto,
-                ),
-                simplelog::WriteLogger::new(log_level, log_config, log_file),
-            ])?;
+            darkfi::util::cli::init_logger(log_level, &toml_contents)?;
 
             // https://docs.rs/smol/latest/smol/struct.Executor.html#examples
             let ex = Arc::new(async_executor::Executor::new());
@@ -196,6 +196,7 @@ pub fn struct_de(input: &ItemStruct, cratename: Ident) -> syn::Result<TokenStrea
     Ok(quote! {
         impl #cratename::util::serial::Decodable for #name #where_clause {
             fn decode<D: std::io::Read>(mut d: D) -> #cratename::Result<Self> {
+                let _depth = #cratename::util::serial::DepthGuard::enter()?;
                 Ok(#return_value)
             }
         }
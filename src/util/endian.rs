@@ -56,11 +56,11 @@ define_be_to_array!(u32_to_array_be, u32, 4);
 define_slice_to_le!(slice_to_u16_le, u16);
 define_slice_to_le!(slice_to_u32_le, u32);
 define_slice_to_le!(slice_to_u64_le, u64);
-define_slice_to_le!(slice_to_usize_le, usize);
+define_slice_to_le!(slice_to_u128_le, u128);
 define_le_to_array!(u16_to_array_le, u16, 2);
 define_le_to_array!(u32_to_array_le, u32, 4);
 define_le_to_array!(u64_to_array_le, u64, 8);
-define_le_to_array!(usize_to_array_le, usize, usize::BITS as usize / 8);
+define_le_to_array!(u128_to_array_le, u128, 16);
 
 #[inline]
 pub fn i16_to_array_le(val: i16) -> [u8; 2] {
@@ -87,6 +87,14 @@ pub fn i64_to_array_le(val: i64) -> [u8; 8] {
     u64_to_array_le(val as u64)
 }
 #[inline]
+pub fn slice_to_i128_le(slice: &[u8]) -> i128 {
+    slice_to_u128_le(slice) as i128
+}
+#[inline]
+pub fn i128_to_array_le(val: i128) -> [u8; 16] {
+    u128_to_array_le(val as u128)
+}
+#[inline]
 pub fn f64_to_array_le(val: f64) -> [u8; 8] {
     assert_eq!(::std::mem::size_of::<f64>(), 8);
     val.to_le_bytes()
@@ -16,7 +16,7 @@ pub mod time;
 pub use async_util::sleep;
 
 pub use net_name::NetworkName;
-pub use parse::{decode_base10, encode_base10};
+pub use parse::{decode_base10, decode_token_amount, encode_base10, encode_token_amount};
 pub use path::{expand_path, join_config_path, load_keypair_to_str};
 pub use time::{check_clock, unix_timestamp, NanoTimestamp, Timestamp};
 
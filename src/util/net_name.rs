@@ -13,6 +13,7 @@ pub enum NetworkName {
     Solana,
     Bitcoin,
     Ethereum,
+    Monero,
 }
 
 impl std::fmt::Display for NetworkName {
@@ -30,6 +31,9 @@ impl std::fmt::Display for NetworkName {
             Self::Ethereum => {
                 write!(f, "Ethereum")
             }
+            Self::Monero => {
+                write!(f, "Monero")
+            }
         }
     }
 }
@@ -43,6 +47,7 @@ impl FromStr for NetworkName {
             "sol" | "solana" => Ok(NetworkName::Solana),
             "btc" | "bitcoin" => Ok(NetworkName::Bitcoin),
             "eth" | "ethereum" => Ok(NetworkName::Ethereum),
+            "xmr" | "monero" => Ok(NetworkName::Monero),
             _ => Err(crate::Error::UnsupportedCoinNetwork),
         }
     }
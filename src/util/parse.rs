@@ -2,6 +2,10 @@ use std::{iter::FromIterator, str::FromStr};
 
 use crate::{Error, Result};
 
+/// Most decimal places a token can have, so that a whole unit of it still
+/// fits in a `u64`.
+pub const MAX_DECIMALS: u16 = 19;
+
 fn is_digit(c: char) -> bool {
     ('0'..='9').contains(&c)
 }
@@ -70,6 +74,27 @@ pub fn encode_base10(amount: u64, decimal_places: usize) -> String {
     String::from_iter(&s).trim_end_matches('0').trim_end_matches('.').to_string()
 }
 
+/// Encode an amount of a token with the given decimals, as found in the
+/// token registry. Fails if the decimals can't be those of a valid token.
+pub fn encode_token_amount(amount: u64, decimals: u16) -> Result<String> {
+    check_decimals(decimals)?;
+    Ok(encode_base10(amount, decimals as usize))
+}
+
+/// Decode an amount of a token with the given decimals, refusing to round
+/// off digits the token can't represent.
+pub fn decode_token_amount(amount: &str, decimals: u16) -> Result<u64> {
+    check_decimals(decimals)?;
+    decode_base10(amount, decimals as usize, true)
+}
+
+fn check_decimals(decimals: u16) -> Result<()> {
+    if decimals > MAX_DECIMALS {
+        return Err(Error::ParseFailed("Token decimals out of range"))
+    }
+    Ok(())
+}
+
 pub fn truncate(amount: u64, decimals: u16, token_decimals: u16) -> Result<u64> {
     let mut amount: Vec<char> = amount.to_string().chars().collect();
 
@@ -90,7 +115,10 @@ pub fn truncate(amount: u64, decimals: u16, token_decimals: u16) -> Result<u64>
 
 #[cfg(test)]
 mod tests {
-    use super::{decode_base10, encode_base10, truncate};
+    use super::{
+        decode_base10, decode_token_amount, encode_base10, encode_token_amount, truncate,
+        MAX_DECIMALS,
+    };
 
     #[test]
     fn test_decode_base10() {
@@ -110,6 +138,17 @@ mod tests {
         assert_eq!("0.00002343", &encode_base10(2343, 8));
     }
 
+    #[test]
+    fn test_token_amount() {
+        assert_eq!("1.5", &encode_token_amount(150, 2).unwrap());
+        assert_eq!(150, decode_token_amount("1.5", 2).unwrap());
+        assert!(decode_token_amount("1.505", 2).is_err());
+
+        // A whole unit fits at the maximum decimals, but not past it
+        assert_eq!(10u64.pow(MAX_DECIMALS as u32), decode_token_amount("1", MAX_DECIMALS).unwrap());
+        assert!(encode_token_amount(1, MAX_DECIMALS + 1).is_err());
+    }
+
     #[test]
     fn test_truncate() {
         // Token decimals is equal to 8
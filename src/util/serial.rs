@@ -1,19 +1,25 @@
-use fxhash::FxHashMap;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashSet},
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    hash::{BuildHasher, Hash},
     io,
     io::{Cursor, Read, Write},
     mem,
     net::{IpAddr, SocketAddr},
+    ops::{Deref, Range},
     path::PathBuf,
     str::FromStr,
+    sync::Arc,
 };
 
 use url::Url;
 
 pub use darkfi_derive::{SerialDecodable, SerialEncodable};
 
+#[cfg(feature = "async-runtime")]
+pub use super::async_serial::{AsyncDecodable, AsyncEncodable};
+
 use super::endian;
 use crate::{Error, Result};
 
@@ -53,10 +59,193 @@ pub fn deserialize_partial<T: Decodable>(data: &[u8]) -> Result<(T, usize)> {
     Ok((rv, consumed))
 }
 
+/// Upper bound on the memory preallocated for a decoded collection. Larger
+/// collections grow as their elements are actually read, so a bogus length
+/// prefix can't make us allocate more than the data we received.
+const MAX_PREALLOC_BYTES: usize = 64 * 1024;
+
+/// Limits enforced while decoding untrusted data, see [`deserialize_limited`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum number of elements of a collection, or bytes of a string
+    pub max_len: u64,
+    /// Maximum nesting depth of decoded structures
+    pub max_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    /// Limits for data received from the network. No collection can be
+    /// longer than the largest packet payload we accept.
+    fn default() -> Self {
+        Self { max_len: 32 * 1024 * 1024, max_depth: 64 }
+    }
+}
+
+thread_local! {
+    /// Limits of the decoding in progress on this thread, if any
+    static LIMITS: Cell<Option<DecodeLimits>> = Cell::new(None);
+    /// Current nesting depth of the decoding in progress on this thread
+    static DEPTH: Cell<usize> = Cell::new(0);
+    /// Whether the decoding in progress on this thread only accepts
+    /// canonical encodings
+    static STRICT: Cell<bool> = Cell::new(false);
+}
+
+/// Sets the decoding limits of this thread, restoring the previous ones when
+/// dropped.
+struct LimitsGuard {
+    limits: Option<DecodeLimits>,
+    depth: usize,
+}
+
+impl LimitsGuard {
+    fn set(limits: DecodeLimits) -> Self {
+        let prev_limits = LIMITS.with(|l| l.replace(Some(limits)));
+        let prev_depth = DEPTH.with(|d| d.replace(0));
+        Self { limits: prev_limits, depth: prev_depth }
+    }
+}
+
+impl Drop for LimitsGuard {
+    fn drop(&mut self) {
+        LIMITS.with(|l| l.set(self.limits));
+        DEPTH.with(|d| d.set(self.depth));
+    }
+}
+
+/// Marks one more level of nesting in the decoding in progress, failing once
+/// the depth limit is exceeded. The level is left when the guard is dropped.
+/// Without active limits this is a no-op.
+pub struct DepthGuard {
+    entered: bool,
+}
+
+impl DepthGuard {
+    pub fn enter() -> Result<Self> {
+        let max_depth = match LIMITS.with(Cell::get) {
+            Some(limits) => limits.max_depth,
+            None => return Ok(Self { entered: false }),
+        };
+
+        let depth = DEPTH.with(|d| {
+            d.set(d.get() + 1);
+            d.get()
+        });
+        let guard = Self { entered: true };
+
+        if depth > max_depth {
+            return Err(Error::ParseFailed("nesting depth exceeds limit"))
+        }
+
+        Ok(guard)
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        if self.entered {
+            DEPTH.with(|d| d.set(d.get() - 1));
+        }
+    }
+}
+
+/// Deserialize an object from untrusted data, enforcing `limits` on it and
+/// everything it contains. Will error if said deserialization doesn't
+/// consume the entire vector.
+pub fn deserialize_limited<T: Decodable>(data: &[u8], limits: DecodeLimits) -> Result<T> {
+    let _limits = LimitsGuard::set(limits);
+    deserialize(data)
+}
+
+/// Decode an object from an untrusted reader, enforcing `limits` on it and
+/// everything it contains.
+pub fn decode_limited<T: Decodable, D: io::Read>(d: D, limits: DecodeLimits) -> Result<T> {
+    with_decode_limits(limits, || T::decode(d))
+}
+
+/// Run provided decoding with `limits` enforced on everything it decodes.
+pub fn with_decode_limits<T>(limits: DecodeLimits, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let _limits = LimitsGuard::set(limits);
+    f()
+}
+
+/// Sets strict decoding on this thread, restoring the previous mode when
+/// dropped.
+struct StrictGuard {
+    strict: bool,
+}
+
+impl StrictGuard {
+    fn set() -> Self {
+        Self { strict: STRICT.with(|s| s.replace(true)) }
+    }
+}
+
+impl Drop for StrictGuard {
+    fn drop(&mut self) {
+        STRICT.with(|s| s.set(self.strict));
+    }
+}
+
+fn is_strict() -> bool {
+    STRICT.with(Cell::get)
+}
+
+/// Deserialize an object, rejecting any encoding that isn't the one its
+/// encoder would produce: booleans other than 0 and 1, and map or set keys
+/// out of order. Non-minimal VarInts and trailing bytes are always rejected.
+pub fn deserialize_strict<T: Decodable>(data: &[u8]) -> Result<T> {
+    let _strict = StrictGuard::set();
+    deserialize(data)
+}
+
+/// Check that `data` is exactly the encoding of `value`.
+pub fn ensure_canonical<T: Encodable + ?Sized>(value: &T, data: &[u8]) -> Result<()> {
+    if serialize(value) != data {
+        return Err(Error::ParseFailed("non-canonical encoding"))
+    }
+    Ok(())
+}
+
+/// Deserialize an object in strict mode, then check it encodes back to the
+/// same bytes. Data that gets hashed by consensus, like blocks and
+/// transactions, must be decoded this way so its hash can't be changed by
+/// re-encoding it.
+pub fn deserialize_canonical<T: Decodable + Encodable>(data: &[u8]) -> Result<T> {
+    let value: T = deserialize_strict(data)?;
+    ensure_canonical(&value, data)?;
+    Ok(value)
+}
+
+/// Decode the rest of a message payload with [`deserialize_canonical`].
+pub fn decode_canonical<T: Decodable + Encodable>(reader: &mut BytesReader) -> Result<T> {
+    let data = reader.buf.slice(reader.pos..reader.buf.len());
+    reader.pos = reader.buf.len();
+    deserialize_canonical(&data)
+}
+
+/// Decode the length prefix of a collection, checking it against the active
+/// limits.
+pub fn decode_len<D: io::Read>(d: D) -> Result<u64> {
+    let len = VarInt::decode(d)?.0;
+    if let Some(limits) = LIMITS.with(Cell::get) {
+        if len > limits.max_len {
+            return Err(Error::ParseFailed("collection length exceeds limit"))
+        }
+    }
+    Ok(len)
+}
+
+/// Capacity to preallocate for a collection of `len` elements of type `T`.
+fn prealloc_capacity<T>(len: u64) -> usize {
+    let max = MAX_PREALLOC_BYTES / mem::size_of::<T>().max(1);
+    (len as usize).min(max)
+}
+
 /// Extensions of `Write` to encode data as per Bitcoin consensus
 pub trait WriteExt {
-    /// Output a platform-specific uint
-    fn write_usize(&mut self, v: usize) -> Result<()>;
+    /// Output a 128-bit uint
+    fn write_u128(&mut self, v: u128) -> Result<()>;
     /// Output a 64-bit uint
     fn write_u64(&mut self, v: u64) -> Result<()>;
     /// Output a 32-bit uint
@@ -66,6 +255,8 @@ pub trait WriteExt {
     /// Output a 8-bit uint
     fn write_u8(&mut self, v: u8) -> Result<()>;
 
+    /// Output a 128-bit int
+    fn write_i128(&mut self, v: i128) -> Result<()>;
     /// Output a 64-bit int
     fn write_i64(&mut self, v: i64) -> Result<()>;
     /// Output a 32-bit int
@@ -89,8 +280,8 @@ pub trait WriteExt {
 
 /// Extensions of `Read` to decode data as per Bitcoin consensus
 pub trait ReadExt {
-    /// Read a platform-specific uint
-    fn read_usize(&mut self) -> Result<usize>;
+    /// Read a 128-bit uint
+    fn read_u128(&mut self) -> Result<u128>;
     /// Read a 64-bit uint
     fn read_u64(&mut self) -> Result<u64>;
     /// Read a 32-bit uint
@@ -100,6 +291,8 @@ pub trait ReadExt {
     /// Read a 8-bit uint
     fn read_u8(&mut self) -> Result<u8>;
 
+    /// Read a 128-bit int
+    fn read_i128(&mut self) -> Result<i128>;
     /// Read a 64-bit int
     fn read_i64(&mut self) -> Result<i64>;
     /// Read a 32-bit int
@@ -143,10 +336,11 @@ macro_rules! decoder_fn {
 }
 
 impl<W: Write> WriteExt for W {
-    encoder_fn!(write_usize, usize, usize_to_array_le);
+    encoder_fn!(write_u128, u128, u128_to_array_le);
     encoder_fn!(write_u64, u64, u64_to_array_le);
     encoder_fn!(write_u32, u32, u32_to_array_le);
     encoder_fn!(write_u16, u16, u16_to_array_le);
+    encoder_fn!(write_i128, i128, i128_to_array_le);
     encoder_fn!(write_i64, i64, i64_to_array_le);
     encoder_fn!(write_i32, i32, i32_to_array_le);
     encoder_fn!(write_i16, i16, i16_to_array_le);
@@ -172,10 +366,11 @@ impl<W: Write> WriteExt for W {
 }
 
 impl<R: Read> ReadExt for R {
-    decoder_fn!(read_usize, usize, slice_to_usize_le, usize::BITS as usize / 8);
+    decoder_fn!(read_u128, u128, slice_to_u128_le, 16);
     decoder_fn!(read_u64, u64, slice_to_u64_le, 8);
     decoder_fn!(read_u32, u32, slice_to_u32_le, 4);
     decoder_fn!(read_u16, u16, slice_to_u16_le, 2);
+    decoder_fn!(read_i128, i128, slice_to_i128_le, 16);
     decoder_fn!(read_i64, i64, slice_to_i64_le, 8);
     decoder_fn!(read_i32, i32, slice_to_i32_le, 4);
     decoder_fn!(read_i16, i16, slice_to_i16_le, 2);
@@ -244,11 +439,28 @@ impl_int_encodable!(u8, read_u8, write_u8);
 impl_int_encodable!(u16, read_u16, write_u16);
 impl_int_encodable!(u32, read_u32, write_u32);
 impl_int_encodable!(u64, read_u64, write_u64);
-impl_int_encodable!(usize, read_usize, write_usize);
+impl_int_encodable!(u128, read_u128, write_u128);
 impl_int_encodable!(i8, read_i8, write_i8);
 impl_int_encodable!(i16, read_i16, write_i16);
 impl_int_encodable!(i32, read_i32, write_i32);
 impl_int_encodable!(i64, read_i64, write_i64);
+impl_int_encodable!(i128, read_i128, write_i128);
+
+// usize goes on the wire as a u64, so encodings don't depend on the
+// platform. Decoding fails for values the platform usize can't hold.
+impl Encodable for usize {
+    #[inline]
+    fn encode<S: io::Write>(&self, s: S) -> Result<usize> {
+        (*self as u64).encode(s)
+    }
+}
+
+impl Decodable for usize {
+    #[inline]
+    fn decode<D: io::Read>(d: D) -> Result<Self> {
+        usize::try_from(u64::decode(d)?).map_err(|_| Error::ParseFailed("usize out of range"))
+    }
+}
 
 impl VarInt {
     /// Gets the length of this VarInt when encoded.
@@ -366,7 +578,15 @@ impl Encodable for bool {
 impl Decodable for bool {
     #[inline]
     fn decode<D: io::Read>(mut d: D) -> Result<bool> {
-        ReadExt::read_bool(&mut d)
+        if !is_strict() {
+            return ReadExt::read_bool(&mut d)
+        }
+
+        match ReadExt::read_u8(&mut d)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::ParseFailed("non-canonical boolean")),
+        }
     }
 }
 
@@ -440,6 +660,7 @@ impl<T: Encodable> Encodable for Option<T> {
 }
 impl<T: Decodable> Decodable for Option<T> {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let _depth = DepthGuard::enter()?;
         let valid: bool = Decodable::decode(&mut d)?;
         let mut val: Option<T> = None;
 
@@ -463,8 +684,9 @@ impl<T: Encodable> Encodable for Vec<T> {
 }
 impl<T: Decodable> Decodable for Vec<T> {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
-        let len = VarInt::decode(&mut d)?.0;
-        let mut ret = Vec::with_capacity(len as usize);
+        let _depth = DepthGuard::enter()?;
+        let len = decode_len(&mut d)?;
+        let mut ret = Vec::with_capacity(prealloc_capacity::<T>(len));
         for _ in 0..len {
             ret.push(Decodable::decode(&mut d)?);
         }
@@ -581,6 +803,115 @@ impl Decodable for Box<[u8]> {
     }
 }
 
+/// Immutable byte string backed by a shared buffer. Clones and slices point
+/// into the same buffer rather than copying it, so large payloads can be
+/// passed around, and byte strings read out of them with [`BytesReader`],
+/// without copies. Encoded like a `Vec<u8>`.
+#[derive(Clone, Default)]
+pub struct Bytes {
+    buf: Arc<Vec<u8>>,
+    start: usize,
+    end: usize,
+}
+
+impl Bytes {
+    /// Bytes in provided range of this byte string, sharing its buffer.
+    /// Panics if the range is out of bounds, like slicing does.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.len());
+        Self { buf: self.buf.clone(), start: self.start + range.start, end: self.start + range.end }
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(buf: Vec<u8>) -> Self {
+        let end = buf.len();
+        Self { buf: Arc::new(buf), start: 0, end }
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[self.start..self.end]
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Self) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl Eq for Bytes {}
+
+impl std::fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bytes({} bytes)", self.len())
+    }
+}
+
+impl Encodable for Bytes {
+    #[inline]
+    fn encode<S: io::Write>(&self, s: S) -> Result<usize> {
+        encode_with_size(self, s)
+    }
+}
+
+impl Decodable for Bytes {
+    /// Copies the bytes out of the reader. Use [`BytesReader::read_bytes`]
+    /// to share the buffer they are read from instead.
+    #[inline]
+    fn decode<D: io::Read>(d: D) -> Result<Self> {
+        <Vec<u8>>::decode(d).map(From::from)
+    }
+}
+
+/// Reader over a [`Bytes`] buffer, which hands out the byte strings it
+/// contains as slices of the buffer.
+pub struct BytesReader {
+    buf: Bytes,
+    pos: usize,
+}
+
+impl BytesReader {
+    pub fn new(buf: Bytes) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Read a byte string, encoded like a `Vec<u8>`, without copying it.
+    pub fn read_bytes(&mut self) -> Result<Bytes> {
+        let len = decode_len(&mut *self)? as usize;
+        if len > self.remaining() {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof))
+        }
+
+        let bytes = self.buf.slice(self.pos..self.pos + len);
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+}
+
+impl io::Read for BytesReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.buf[self.pos..]).read(out)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
 impl<T: Encodable> Encodable for Box<T> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -605,49 +936,139 @@ impl Decodable for blake3::Hash {
     }
 }
 
-impl<T: Encodable> Encodable for HashSet<T> {
+// Collections
+//
+// Encodings are canonical: hash collections are written in the order of
+// their serialized keys, ordered collections in the order of their keys,
+// and decoding fails on duplicate keys.
+
+/// Serialize the keys of a hash collection, in canonical order.
+fn sorted_keys<'a, K: Encodable + 'a, V: 'a>(
+    entries: impl Iterator<Item = (&'a K, V)>,
+) -> Vec<(Vec<u8>, V)> {
+    let mut keys: Vec<(Vec<u8>, V)> = entries.map(|(k, v)| (serialize(k), v)).collect();
+    keys.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    keys
+}
+
+/// Reader keeping a copy of the bytes read through it
+struct Recorder<R> {
+    inner: R,
+    buf: Vec<u8>,
+}
+
+impl<R: io::Read> io::Read for Recorder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(out)?;
+        self.buf.extend_from_slice(&out[..n]);
+        Ok(n)
+    }
+}
+
+/// Decode a key of a hash collection. In strict mode, keys must come in
+/// ascending order of their encoding, as [`sorted_keys`] writes them.
+fn decode_hash_key<T: Decodable, D: io::Read>(d: D, last: &mut Option<Vec<u8>>) -> Result<T> {
+    if !is_strict() {
+        return T::decode(d)
+    }
+
+    let mut recorder = Recorder { inner: d, buf: vec![] };
+    let key = T::decode(&mut recorder)?;
+    if last.as_ref().map_or(false, |last| *last >= recorder.buf) {
+        return Err(Error::ParseFailed("keys not in canonical order"))
+    }
+    *last = Some(recorder.buf);
+    Ok(key)
+}
+
+/// In strict mode, check a key of a B-tree collection comes after the ones
+/// decoded before it, as they are encoded in order.
+fn check_btree_key<T: Ord>(key: &T, last: Option<&T>) -> Result<()> {
+    if is_strict() && last.map_or(false, |last| key <= last) {
+        return Err(Error::ParseFailed("keys not in canonical order"))
+    }
+    Ok(())
+}
+
+impl<T: Encodable, H> Encodable for HashSet<T, H> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += VarInt(self.len() as u64).encode(&mut s)?;
-        for c in self.iter() {
-            len += c.encode(&mut s)?;
+        for (key, _) in sorted_keys(self.iter().map(|k| (k, ()))) {
+            s.write_slice(&key)?;
+            len += key.len();
         }
         Ok(len)
     }
 }
 
-impl<T: Decodable + std::cmp::Eq + std::hash::Hash> Decodable for HashSet<T> {
+impl<T: Decodable + Eq + Hash, H: BuildHasher + Default> Decodable for HashSet<T, H> {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
-        let len = VarInt::decode(&mut d)?.0;
-        let mut ret = HashSet::new();
+        let len = decode_len(&mut d)?;
+        let mut ret = HashSet::with_capacity_and_hasher(prealloc_capacity::<T>(len), H::default());
+        let mut last = None;
         for _ in 0..len {
-            let entry: T = Decodable::decode(&mut d)?;
-            ret.insert(entry);
+            if !ret.insert(decode_hash_key(&mut d, &mut last)?) {
+                return Err(Error::ParseFailed("duplicate entry in set"))
+            }
         }
         Ok(ret)
     }
 }
 
-impl<T: Encodable, U: Encodable> Encodable for FxHashMap<T, U> {
+impl<T: Encodable, U: Encodable, H> Encodable for HashMap<T, U, H> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += VarInt(self.len() as u64).encode(&mut s)?;
+        for (key, value) in sorted_keys(self.iter()) {
+            s.write_slice(&key)?;
+            len += key.len();
+            len += value.encode(&mut s)?;
+        }
+        Ok(len)
+    }
+}
+
+impl<T: Decodable + Eq + Hash, U: Decodable, H: BuildHasher + Default> Decodable
+    for HashMap<T, U, H>
+{
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let len = decode_len(&mut d)?;
+        let mut ret =
+            HashMap::with_capacity_and_hasher(prealloc_capacity::<(T, U)>(len), H::default());
+        let mut last = None;
+        for _ in 0..len {
+            let key: T = decode_hash_key(&mut d, &mut last)?;
+            let entry: U = Decodable::decode(&mut d)?;
+            if ret.insert(key, entry).is_some() {
+                return Err(Error::ParseFailed("duplicate key in map"))
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl<T: Encodable> Encodable for BTreeSet<T> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += VarInt(self.len() as u64).encode(&mut s)?;
         for c in self.iter() {
-            len += c.0.encode(&mut s)?;
-            len += c.1.encode(&mut s)?;
+            len += c.encode(&mut s)?;
         }
         Ok(len)
     }
 }
 
-impl<T: Decodable + std::cmp::Eq + std::hash::Hash, U: Decodable> Decodable for FxHashMap<T, U> {
+impl<T: Decodable + Ord> Decodable for BTreeSet<T> {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
-        let len = VarInt::decode(&mut d)?.0;
-        let mut ret = FxHashMap::default();
+        let len = decode_len(&mut d)?;
+        let mut ret = BTreeSet::new();
         for _ in 0..len {
             let key: T = Decodable::decode(&mut d)?;
-            let entry: U = Decodable::decode(&mut d)?;
-            ret.insert(key, entry);
+            check_btree_key(&key, ret.iter().next_back())?;
+            if !ret.insert(key) {
+                return Err(Error::ParseFailed("duplicate entry in set"))
+            }
         }
         Ok(ret)
     }
@@ -665,14 +1086,17 @@ impl<T: Encodable, U: Encodable> Encodable for BTreeMap<T, U> {
     }
 }
 
-impl<T: Decodable + std::cmp::Ord, U: Decodable> Decodable for BTreeMap<T, U> {
+impl<T: Decodable + Ord, U: Decodable> Decodable for BTreeMap<T, U> {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
-        let len = VarInt::decode(&mut d)?.0;
+        let len = decode_len(&mut d)?;
         let mut ret = BTreeMap::new();
         for _ in 0..len {
             let key: T = Decodable::decode(&mut d)?;
+            check_btree_key(&key, ret.keys().next_back())?;
             let entry: U = Decodable::decode(&mut d)?;
-            ret.insert(key, entry);
+            if ret.insert(key, entry).is_some() {
+                return Err(Error::ParseFailed("duplicate key in map"))
+            }
         }
         Ok(ret)
     }
@@ -720,9 +1144,11 @@ macro_rules! encode_payload {
 #[cfg(test)]
 mod tests {
     use super::{
-        deserialize, deserialize_partial,
+        deserialize, deserialize_canonical, deserialize_limited, deserialize_partial,
+        deserialize_strict,
         endian::{u16_to_array_le, u32_to_array_le, u64_to_array_le},
-        serialize, Encodable, Error, Result, SerialDecodable, SerialEncodable, VarInt,
+        ensure_canonical, serialize, Bytes, BytesReader, Decodable, DecodeLimits, Encodable, Error,
+        Result, SerialDecodable, SerialEncodable, VarInt,
     };
     use std::{io, mem::discriminant};
 
@@ -756,6 +1182,10 @@ mod tests {
         assert_eq!(serialize(&5000i32), vec![136u8, 19, 0, 0]);
         assert_eq!(serialize(&500000i32), vec![32u8, 161, 7, 0]);
         assert_eq!(serialize(&168430090i32), vec![10u8, 10, 10, 10]);
+        // usize, the same as u64 on every platform
+        assert_eq!(serialize(&1usize), vec![1u8, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(serialize(&5000usize), serialize(&5000u64));
+        assert_eq!(deserialize::<usize>(&serialize(&5000u64)).ok(), Some(5000usize));
         // u64
         assert_eq!(serialize(&1u64), vec![1u8, 0, 0, 0, 0, 0, 0, 0]);
         assert_eq!(serialize(&256u64), vec![0u8, 1, 0, 0, 0, 0, 0, 0]);
@@ -977,6 +1407,16 @@ mod tests {
         );
         let failurei64: Result<i64> = deserialize(&[1u8, 2, 3, 4, 5, 6, 7]);
         assert!(failurei64.is_err());
+
+        // u128 and i128
+        let mut bytes = vec![0xABu8, 0xCD];
+        bytes.resize(16, 0);
+        assert_eq!(deserialize(&bytes).ok(), Some(0xCDABu128));
+        assert_eq!(deserialize(&[0xFFu8; 16]).ok(), Some(-1i128));
+        assert_eq!(deserialize::<u128>(&serialize(&u128::MAX)).ok(), Some(u128::MAX));
+        assert_eq!(deserialize::<i128>(&serialize(&i128::MIN)).ok(), Some(i128::MIN));
+        let failurei128: Result<i128> = deserialize(&[0u8; 15]);
+        assert!(failurei128.is_err());
     }
 
     #[test]
@@ -1063,4 +1503,113 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn deserialize_limited_test() {
+        let limits = DecodeLimits { max_len: 16, max_depth: 2 };
+
+        // A huge length prefix fails without allocating for it
+        let huge = serialize(&VarInt(u64::MAX));
+        assert!(deserialize::<Vec<u64>>(&huge).is_err());
+        assert_eq!(
+            discriminant(&deserialize_limited::<Vec<u64>>(&huge, limits).unwrap_err()),
+            discriminant(&Error::ParseFailed(""))
+        );
+
+        let short = vec![1u8; 16];
+        assert_eq!(deserialize_limited::<Vec<u8>>(&serialize(&short), limits).unwrap(), short);
+        let long = "a".repeat(17);
+        assert!(deserialize_limited::<String>(&serialize(&long), limits).is_err());
+        assert_eq!(deserialize::<String>(&serialize(&long)).unwrap(), long);
+
+        let shallow = vec![vec![1u8]];
+        assert!(deserialize_limited::<Vec<Vec<u8>>>(&serialize(&shallow), limits).is_ok());
+        let deep = vec![vec![vec![1u8]]];
+        assert!(deserialize_limited::<Vec<Vec<Vec<u8>>>>(&serialize(&deep), limits).is_err());
+        assert_eq!(deserialize::<Vec<Vec<Vec<u8>>>>(&serialize(&deep)).unwrap(), deep);
+    }
+
+    #[test]
+    fn bytes_reader_test() -> Result<()> {
+        let payload = serialize(&(7u64, vec![1u8, 2, 3], vec![4u8; 300]));
+        let mut reader = BytesReader::new(Bytes::from(payload.clone()));
+
+        let n: u64 = Decodable::decode(&mut reader)?;
+        let first = reader.read_bytes()?;
+        let second = reader.read_bytes()?;
+        assert_eq!(n, 7);
+        assert_eq!(&first[..], &[1, 2, 3]);
+        assert_eq!(&second[..], &[4u8; 300][..]);
+        assert_eq!(reader.remaining(), 0);
+
+        // Same encoding as a `Vec<u8>`
+        assert_eq!(serialize(&second), serialize(&vec![4u8; 300]));
+        assert_eq!(deserialize::<Bytes>(&serialize(&first))?, first);
+
+        // Byte strings can't run past the buffer
+        let mut reader = BytesReader::new(Bytes::from(payload[..payload.len() - 1].to_vec()));
+        let _: u64 = Decodable::decode(&mut reader)?;
+        reader.read_bytes()?;
+        assert!(reader.read_bytes().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_collections_test() -> Result<()> {
+        use fxhash::FxHashMap;
+        use std::collections::{BTreeMap, HashSet};
+
+        // Hash collections encode the same whatever their iteration order
+        let a: HashSet<u64> = (0..100).collect();
+        let b: HashSet<u64> = (0..100).rev().collect();
+        assert_eq!(serialize(&a), serialize(&b));
+        assert_eq!(deserialize::<HashSet<u64>>(&serialize(&a))?, a);
+
+        let map: FxHashMap<String, u32> = (0..50).map(|i| (i.to_string(), i)).collect();
+        let bytes = serialize(&map);
+        assert_eq!(deserialize::<FxHashMap<String, u32>>(&bytes)?, map);
+        // Same encoding as the ordered map of the same serialized keys
+        let mut sorted: Vec<(Vec<u8>, u32)> = map.iter().map(|(k, v)| (serialize(k), *v)).collect();
+        sorted.sort();
+        let mut expected = serialize(&VarInt(50));
+        for (k, v) in sorted {
+            expected.extend(k);
+            expected.extend(serialize(&v));
+        }
+        assert_eq!(bytes, expected);
+
+        // Duplicate keys are rejected
+        let dup = serialize(&vec![(1u8, 2u8), (1u8, 3u8)]);
+        assert!(deserialize::<BTreeMap<u8, u8>>(&dup).is_err());
+        assert!(deserialize::<FxHashMap<u8, u8>>(&dup).is_err());
+        assert!(deserialize::<HashSet<u8>>(&serialize(&vec![7u8, 7u8])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn strict_decoding_test() -> Result<()> {
+        use std::collections::{BTreeMap, HashMap};
+
+        // Non-canonical booleans only pass in lenient mode
+        assert!(deserialize::<bool>(&[2u8])?);
+        assert!(deserialize_strict::<bool>(&[2u8]).is_err());
+        assert!(deserialize_strict::<bool>(&[1u8])?);
+
+        // Keys out of order
+        let unordered = serialize(&vec![(2u8, 0u8), (1u8, 0u8)]);
+        assert_eq!(deserialize::<HashMap<u8, u8>>(&unordered)?.len(), 2);
+        assert!(deserialize_strict::<HashMap<u8, u8>>(&unordered).is_err());
+        assert!(deserialize_strict::<BTreeMap<u8, u8>>(&unordered).is_err());
+        let map: HashMap<u8, u8> = (0..20).map(|i| (i, i)).collect();
+        assert_eq!(deserialize_canonical::<HashMap<u8, u8>>(&serialize(&map))?, map);
+
+        // Strict mode doesn't leak out of the call
+        assert!(deserialize::<bool>(&[2u8]).is_ok());
+
+        // Re-encoding catches what no decoder checks on its own
+        let tuple = serialize(&(true, vec![1u8, 2]));
+        assert!(deserialize_canonical::<(bool, Vec<u8>)>(&tuple).is_ok());
+        assert!(ensure_canonical(&(false, vec![1u8, 2]), &tuple).is_err());
+        Ok(())
+    }
 }
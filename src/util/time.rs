@@ -97,24 +97,32 @@ async fn peer_request(peers: &Vec<Url>) -> Result<Option<Timestamp>> {
     let peer = peers.choose(&mut rand::thread_rng());
     match peer {
         None => Ok(None),
-        Some(p) => {
-            // Create rpc client
-            let rpc_client = RpcClient::new(p.clone()).await?;
+        Some(p) => Ok(Some(peer_clock(p).await?)),
+    }
+}
 
-            // Execute request
-            let req = JsonRequest::new("clock", json!([]));
-            let rep = rpc_client.oneshot_request(req).await?;
+/// Retrieve the current system clock of a network peer over its JSON-RPC endpoint.
+pub async fn peer_clock(peer: &Url) -> Result<Timestamp> {
+    // Create rpc client
+    let rpc_client = RpcClient::new(peer.clone()).await?;
 
-            // Parse response
-            let timestamp: Timestamp = serde_json::from_value(rep)?;
+    // Execute request
+    let req = JsonRequest::new("clock", json!([]));
+    let rep = rpc_client.oneshot_request(req).await?;
 
-            Ok(Some(timestamp))
-        }
-    }
+    // Parse response
+    let timestamp: Timestamp = serde_json::from_value(rep)?;
+
+    Ok(timestamp)
 }
 
 // Raw ntp request execution
 async fn ntp_request() -> Result<Timestamp> {
+    Ok(Timestamp(ntp_time_millis().await? / 1000))
+}
+
+/// Query the NTP pool for the current time, in milliseconds since the Unix epoch.
+pub async fn ntp_time_millis() -> Result<i64> {
     // Create socket
     let sock = UdpSocket::bind("0.0.0.0:0")?;
     sock.set_read_timeout(Some(Duration::from_secs(5)))?;
@@ -125,13 +133,15 @@ async fn ntp_request() -> Result<Timestamp> {
     packet[0] = (3 << 6) | (4 << 3) | 3;
     sock.send_to(&packet, NTP_ADDRESS)?;
 
-    // Parse response
+    // Parse response transmit timestamp, seconds and fraction of a second
     sock.recv(&mut packet[..])?;
     let (bytes, _) = packet[40..44].split_at(mem::size_of::<u32>());
-    let num = u32::from_be_bytes(bytes.try_into().unwrap());
-    let timestamp = Timestamp(num as i64 - EPOCH);
+    let secs = u32::from_be_bytes(bytes.try_into().unwrap());
+    let (bytes, _) = packet[44..48].split_at(mem::size_of::<u32>());
+    let fraction = u32::from_be_bytes(bytes.try_into().unwrap());
 
-    Ok(timestamp)
+    let millis = (fraction as i64 * 1000) >> 32;
+    Ok((secs as i64 - EPOCH) * 1000 + millis)
 }
 
 // This is a very simple check to verify that system time is correct.
@@ -0,0 +1,126 @@
+//! Versioned schema migrations of the wallet database.
+//!
+//! The schema version of a database is kept in its `user_version` pragma.
+//! Opening a wallet applies every migration newer than that version, each
+//! in its own transaction, so an older wallet is upgraded in place.
+use log::info;
+use sqlx::{Connection, Row, SqlitePool};
+
+use crate::{Error::WalletSchemaTooNew, Result};
+
+/// A step of the wallet schema history.
+pub struct Migration {
+    /// Schema version after applying the migration
+    pub version: u32,
+    pub description: &'static str,
+    /// SQL statements, executed in order
+    pub statements: &'static [&'static str],
+}
+
+/// Migrations of the wallet schema, in increasing version order. Released
+/// migrations must never be edited, schema changes go in a new one.
+pub const WALLET_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        statements: &[
+            include_str!("../../script/sql/tree.sql"),
+            include_str!("../../script/sql/keys.sql"),
+            include_str!("../../script/sql/diversified_keys.sql"),
+            include_str!("../../script/sql/seed.sql"),
+            include_str!("../../script/sql/coins.sql"),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "Coin time-locks",
+        statements: &["ALTER TABLE coins ADD COLUMN lock_slot INTEGER NOT NULL DEFAULT 0;"],
+    },
+    Migration {
+        version: 3,
+        description: "Coin history and balance indexes",
+        statements: &[
+            "ALTER TABLE coins ADD COLUMN received_slot INTEGER NOT NULL DEFAULT 0;",
+            "ALTER TABLE coins ADD COLUMN spent_slot INTEGER;",
+            "CREATE INDEX IF NOT EXISTS coins_spent_slot ON coins(spent_slot);",
+            "CREATE INDEX IF NOT EXISTS coins_nullifier ON coins(nullifier);",
+            include_str!("../../script/sql/history.sql"),
+            // Coins received before the history was kept, in arrival order
+            "INSERT OR IGNORE INTO history (coin, kind, value, token_id, slot)
+             SELECT coin, 'receive', value, token_id, 0 FROM coins ORDER BY rowid;",
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "Note scanning cursor and wallet birthday",
+        statements: &[
+            include_str!("../../script/sql/scan_state.sql"),
+            "INSERT OR IGNORE INTO scan_state (id, birthday_slot, scanned_slot) VALUES (0, 0, 0);",
+        ],
+    },
+];
+
+/// Return the schema version of the database.
+pub async fn schema_version(pool: &SqlitePool) -> Result<u32> {
+    let row = sqlx::query("PRAGMA user_version").fetch_one(pool).await?;
+    Ok(row.get::<u32, _>(0))
+}
+
+/// Apply the migrations newer than the schema version of the database,
+/// returning the resulting version. Fails if the database was written by a
+/// newer version of the software, rather than risk corrupting it.
+pub async fn migrate(pool: &SqlitePool, migrations: &[Migration]) -> Result<u32> {
+    let mut version = schema_version(pool).await?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if version > latest {
+        return Err(WalletSchemaTooNew(version, latest))
+    }
+
+    let mut conn = pool.acquire().await?;
+    for migration in migrations.iter().filter(|m| m.version > version) {
+        info!("Applying wallet migration {}: {}", migration.version, migration.description);
+
+        let mut tx = conn.begin().await?;
+        for statement in migration.statements {
+            sqlx::query(statement).execute(&mut tx).await?;
+        }
+        // Pragmas can't take bound parameters
+        sqlx::query(&format!("PRAGMA user_version = {}", migration.version))
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[async_std::test]
+    async fn test_migrations() -> Result<()> {
+        // Versions must be strictly increasing
+        for pair in WALLET_MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+
+        // Each connection to an in-memory database gets its own database
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await?;
+        assert_eq!(schema_version(&pool).await?, 0);
+
+        let latest = WALLET_MIGRATIONS.last().unwrap().version;
+        assert_eq!(migrate(&pool, WALLET_MIGRATIONS).await?, latest);
+        // Migrating again is a no-op
+        assert_eq!(migrate(&pool, WALLET_MIGRATIONS).await?, latest);
+
+        // Databases from the future are refused
+        sqlx::query(&format!("PRAGMA user_version = {}", latest + 1)).execute(&pool).await?;
+        assert!(migrate(&pool, WALLET_MIGRATIONS).await.is_err());
+
+        Ok(())
+    }
+}
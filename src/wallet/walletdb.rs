@@ -6,16 +6,18 @@ use incrementalmerkletree::bridgetree::BridgeTree;
 use log::{debug, error, info, LevelFilter};
 use rand::rngs::OsRng;
 use sqlx::{
-    sqlite::{SqliteConnectOptions, SqliteJournalMode},
-    ConnectOptions, Row, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+    ConnectOptions, Connection, Row, SqlitePool,
 };
 
 use crate::{
     crypto::{
-        address::Address,
+        address::{Address, PaymentAddress},
         coin::Coin,
         constants::MERKLE_DEPTH,
-        keypair::{Keypair, PublicKey, SecretKey},
+        keypair::{
+            generate_mnemonic, mnemonic_to_seed, ExtendedSecretKey, Keypair, PublicKey, SecretKey,
+        },
         merkle_node::MerkleNode,
         note::Note,
         nullifier::Nullifier,
@@ -26,10 +28,17 @@ use crate::{
         expand_path,
         serial::{deserialize, serialize},
     },
-    Error::{WalletEmptyPassword, WalletTreeExists},
+    Error::{WalletEmptyPassword, WalletSeedExists, WalletTreeExists},
     Result,
 };
 
+use super::migrations::{migrate, WALLET_MIGRATIONS};
+
+/// Maximum number of open connections to a wallet database. Queries run
+/// on the connection's own thread, so a long query only holds up the
+/// connection it runs on.
+pub const WALLET_MAX_CONNECTIONS: u32 = 4;
+
 pub type WalletPtr = Arc<WalletDb>;
 
 #[derive(Clone, Debug)]
@@ -37,6 +46,8 @@ pub struct Balance {
     pub token_id: DrkTokenId,
     pub value: u64,
     pub nullifier: Nullifier,
+    /// Slot until which the coin can't be spent
+    pub lock_slot: u64,
 }
 
 #[derive(Clone, Debug)]
@@ -44,10 +55,98 @@ pub struct Balances {
     pub list: Vec<Balance>,
 }
 
+impl Balances {
+    /// Balances of the coins spendable at `slot`.
+    pub fn liquid(&self, slot: u64) -> impl Iterator<Item = &Balance> {
+        self.list.iter().filter(move |b| b.lock_slot <= slot)
+    }
+
+    /// Balances of the coins still locked at `slot`.
+    pub fn locked(&self, slot: u64) -> impl Iterator<Item = &Balance> {
+        self.list.iter().filter(move |b| b.lock_slot > slot)
+    }
+}
+
+/// Balances of the wallet's coins of a token, as of a slot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenBalance {
+    pub token_id: DrkTokenId,
+    /// Coins spendable at the slot
+    pub confirmed: u64,
+    /// Coins spent by transactions that didn't make it into a block yet
+    pub unconfirmed: u64,
+    /// Coins time-locked past the slot
+    pub locked: u64,
+}
+
+/// Whether a history entry is a coin received or spent by the wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryKind {
+    Receive,
+    Spend,
+}
+
+impl HistoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Receive => "receive",
+            Self::Spend => "spend",
+        }
+    }
+}
+
+/// A coin received or spent, as recorded in the wallet history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Position of the entry in the history, increasing with each entry
+    pub id: u64,
+    pub coin: Coin,
+    pub kind: HistoryKind,
+    pub value: u64,
+    pub token_id: DrkTokenId,
+    /// Slot of the block the coin was received or spent in
+    pub slot: u64,
+}
+
+/// A coin of the wallet, along with its spending status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoinInfo {
+    pub own_coin: OwnCoin,
+    /// Whether the coin was spent, in a block or by a pending transaction
+    pub is_spent: bool,
+    /// Slot of the block the coin was received in
+    pub received_slot: u64,
+    /// Slot of the block the coin was spent in
+    pub spent_slot: Option<u64>,
+}
+
 pub struct WalletDb {
     pub conn: SqlitePool,
 }
 
+/// Parse a row of the `coins` table.
+fn own_coin_from_row(row: &SqliteRow) -> Result<OwnCoin> {
+    let coin = deserialize(row.get("coin"))?;
+
+    // Note
+    let serial = deserialize(row.get("serial"))?;
+    let coin_blind = deserialize(row.get("coin_blind"))?;
+    let value_blind = deserialize(row.get("valcom_blind"))?;
+    let value = deserialize(row.get("value"))?;
+    let token_id = deserialize(row.get("token_id"))?;
+    let token_blind = deserialize(row.get("token_blind"))?;
+    let lock_slot = row.get::<i64, _>("lock_slot") as u64;
+    let memo = deserialize(row.get("memo"))?;
+    let note =
+        Note { serial, value, token_id, coin_blind, value_blind, token_blind, lock_slot, memo };
+
+    let secret = deserialize(row.get("secret"))?;
+    let nullifier = deserialize(row.get("nullifier"))?;
+    let leaf_position = deserialize(row.get("leaf_position"))?;
+
+    Ok(OwnCoin { coin, note, secret, nullifier, leaf_position })
+}
+
 /// Helper function to initialize `WalletPtr`
 pub async fn init_wallet(wallet_path: &str, wallet_pass: &str) -> Result<WalletPtr> {
     let expanded = expand_path(wallet_path)?;
@@ -57,6 +156,8 @@ pub async fn init_wallet(wallet_path: &str, wallet_pass: &str) -> Result<WalletP
 }
 
 impl WalletDb {
+    /// Open the wallet database at provided path, creating it if missing,
+    /// and migrate its schema to the latest version.
     pub async fn new(path: &str, password: &str) -> Result<WalletPtr> {
         if password.trim().is_empty() {
             error!("Password is empty. You must set a password to use the wallet.");
@@ -74,39 +175,86 @@ impl WalletDb {
         let mut connect_opts = SqliteConnectOptions::from_str(path)?
             .pragma("key", password.to_string())
             .create_if_missing(true)
-            .journal_mode(SqliteJournalMode::Off);
+            .journal_mode(SqliteJournalMode::Wal);
 
         connect_opts.log_statements(LevelFilter::Trace);
         connect_opts.log_slow_statements(LevelFilter::Trace, Duration::from_micros(10));
 
-        let conn = SqlitePool::connect_with(connect_opts).await?;
-
+        // Each connection to an in-memory database gets its own database
+        let max_connections = if path == "sqlite::memory:" { 1 } else { WALLET_MAX_CONNECTIONS };
+        let conn = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(connect_opts)
+            .await?;
         info!("Opened connection at path {}", path);
+
+        let version = migrate(&conn, WALLET_MIGRATIONS).await?;
+        info!("Wallet schema at version {}", version);
+
         Ok(Arc::new(WalletDb { conn }))
     }
 
-    pub async fn init_db(&self) -> Result<()> {
-        info!("Initializing wallet database");
-        let tree = include_str!("../../script/sql/tree.sql");
-        let keys = include_str!("../../script/sql/keys.sql");
-        let coins = include_str!("../../script/sql/coins.sql");
+    /// Fetch the mnemonic all of the wallet's generated keys are derived
+    /// from, creating one if the wallet doesn't have a seed yet. It is a
+    /// backup of the wallet's keys, except for imported ones.
+    pub async fn get_mnemonic(&self) -> Result<String> {
+        debug!("Returning wallet mnemonic");
+        let mut conn = self.conn.acquire().await?;
+
+        if let Some(row) =
+            sqlx::query("SELECT mnemonic FROM seed").fetch_optional(&mut conn).await?
+        {
+            return Ok(row.get("mnemonic"))
+        }
+
+        debug!("Generating wallet mnemonic");
+        let mnemonic = generate_mnemonic(&mut OsRng);
+        sqlx::query("INSERT INTO seed(mnemonic, next_index) VALUES (?1, 0)")
+            .bind(mnemonic.clone())
+            .execute(&mut conn)
+            .await?;
+
+        Ok(mnemonic)
+    }
+
+    /// Restore the keys of a wallet from its mnemonic, deriving its first
+    /// `num_keys` keypairs. The wallet must not have a seed already.
+    pub async fn restore_from_mnemonic(&self, mnemonic: &str, num_keys: u32) -> Result<()> {
+        debug!("Restoring wallet from mnemonic");
+        let master = ExtendedSecretKey::master(&mnemonic_to_seed(mnemonic, "")?);
 
         let mut conn = self.conn.acquire().await?;
+        if sqlx::query("SELECT mnemonic FROM seed").fetch_optional(&mut conn).await?.is_some() {
+            return Err(WalletSeedExists)
+        }
 
-        debug!("Initializing merkle tree table");
-        sqlx::query(tree).execute(&mut conn).await?;
+        sqlx::query("INSERT INTO seed(mnemonic, next_index) VALUES (?1, ?2)")
+            .bind(mnemonic)
+            .bind(num_keys)
+            .execute(&mut conn)
+            .await?;
+        drop(conn);
 
-        debug!("Initializing keys table");
-        sqlx::query(keys).execute(&mut conn).await?;
+        for index in 0..num_keys {
+            self.put_keypair(&Keypair::new(master.derive_child(index).secret_key())).await?;
+        }
 
-        debug!("Initializing coins table");
-        sqlx::query(coins).execute(&mut conn).await?;
         Ok(())
     }
 
+    /// Derive the next keypair from the wallet's seed, at path `m/<index>'`.
     pub async fn keygen(&self) -> Result<Keypair> {
         debug!("Attempting to generate keypairs");
-        let keypair = Keypair::random(&mut OsRng);
+        let mnemonic = self.get_mnemonic().await?;
+        let master = ExtendedSecretKey::master(&mnemonic_to_seed(&mnemonic, "")?);
+
+        let mut conn = self.conn.acquire().await?;
+        let row = sqlx::query("SELECT next_index FROM seed").fetch_one(&mut conn).await?;
+        let index: u32 = row.get("next_index");
+        sqlx::query("UPDATE seed SET next_index = next_index + 1").execute(&mut conn).await?;
+        drop(conn);
+
+        let keypair = Keypair::new(master.derive_child(index).secret_key());
         self.put_keypair(&keypair).await?;
         Ok(keypair)
     }
@@ -204,6 +352,112 @@ impl WalletDb {
         Ok(keypairs)
     }
 
+    /// Derive a new diversified address of the given keypair, unlinkable to
+    /// its other addresses, and keep its key around for note scanning.
+    pub async fn new_diversified_address(&self, keypair: &Keypair) -> Result<PaymentAddress> {
+        debug!("Deriving a new diversified address");
+        let mut conn = self.conn.acquire().await?;
+
+        let parent = serialize(&keypair.public);
+        let row = sqlx::query(
+            "SELECT COALESCE(MAX(diversifier) + 1, 0) AS next FROM diversified_keys WHERE parent = ?1",
+        )
+        .bind(parent.clone())
+        .fetch_one(&mut conn)
+        .await?;
+        let diversifier: i64 = row.get("next");
+
+        let secret = keypair.secret.diversify(diversifier as u64);
+        let public = PublicKey::from_secret(secret);
+
+        sqlx::query(
+            "INSERT INTO diversified_keys(public, secret, parent, diversifier) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(serialize(&public))
+        .bind(serialize(&secret))
+        .bind(parent)
+        .bind(diversifier)
+        .execute(&mut conn)
+        .await?;
+
+        Ok(PaymentAddress::new(&secret))
+    }
+
+    /// Fetch the keypairs of all diversified addresses handed out so far.
+    pub async fn get_diversified_keypairs(&self) -> Result<Vec<Keypair>> {
+        debug!("Returning diversified keypairs");
+        let mut conn = self.conn.acquire().await?;
+
+        let mut keypairs = vec![];
+
+        for row in sqlx::query("SELECT * FROM diversified_keys").fetch_all(&mut conn).await? {
+            let public: PublicKey = deserialize(row.get("public"))?;
+            let secret: SecretKey = deserialize(row.get("secret"))?;
+            keypairs.push(Keypair { public, secret });
+        }
+
+        Ok(keypairs)
+    }
+
+    /// Fetch the secret keys incoming notes should be decrypted with:
+    /// those of our keypairs and of their diversified addresses.
+    pub async fn get_scanning_secrets(&self) -> Result<Vec<SecretKey>> {
+        let mut secrets: Vec<SecretKey> =
+            self.get_keypairs().await?.iter().map(|x| x.secret).collect();
+        secrets.extend(self.get_diversified_keypairs().await?.iter().map(|x| x.secret));
+        Ok(secrets)
+    }
+
+    /// Slot the wallet was created at. Blocks before it can't hold coins
+    /// of the wallet, so they aren't scanned for its notes.
+    pub async fn get_birthday_slot(&self) -> Result<u64> {
+        let mut conn = self.conn.acquire().await?;
+        let row = sqlx::query("SELECT birthday_slot FROM scan_state WHERE id = 0;")
+            .fetch_one(&mut conn)
+            .await?;
+        Ok(row.get::<i64, _>("birthday_slot") as u64)
+    }
+
+    pub async fn set_birthday_slot(&self, slot: u64) -> Result<()> {
+        debug!("Setting wallet birthday to slot {}", slot);
+        let mut conn = self.conn.acquire().await?;
+        sqlx::query("UPDATE scan_state SET birthday_slot = ?1 WHERE id = 0;")
+            .bind(slot as i64)
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Slot the oldest unspent coin of the wallet was received in, if it
+    /// has any.
+    pub async fn get_unspent_received_slot(&self) -> Result<Option<u64>> {
+        let mut conn = self.conn.acquire().await?;
+        let row = sqlx::query("SELECT MIN(received_slot) AS slot FROM coins WHERE is_spent = 0;")
+            .fetch_one(&mut conn)
+            .await?;
+        Ok(row.get::<Option<i64>, _>("slot").map(|s| s as u64))
+    }
+
+    /// Slot up to which blocks were scanned for the wallet's notes.
+    pub async fn get_scanned_slot(&self) -> Result<u64> {
+        let mut conn = self.conn.acquire().await?;
+        let row = sqlx::query("SELECT scanned_slot FROM scan_state WHERE id = 0;")
+            .fetch_one(&mut conn)
+            .await?;
+        Ok(row.get::<i64, _>("scanned_slot") as u64)
+    }
+
+    /// Move the scanning cursor forward to `slot`. It never goes back, as
+    /// rescans of older blocks scan up to the last one.
+    pub async fn set_scanned_slot(&self, slot: u64) -> Result<()> {
+        let mut conn = self.conn.acquire().await?;
+        sqlx::query("UPDATE scan_state SET scanned_slot = MAX(scanned_slot, ?1) WHERE id = 0;")
+            .bind(slot as i64)
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+
     pub async fn tree_gen(&self) -> Result<BridgeTree<MerkleNode, MERKLE_DEPTH>> {
         debug!("Attempting to generate merkle tree");
         let mut conn = self.conn.acquire().await?;
@@ -261,25 +515,7 @@ impl WalletDb {
 
         let mut own_coins = vec![];
         for row in rows {
-            let coin = deserialize(row.get("coin"))?;
-
-            // Note
-            let serial = deserialize(row.get("serial"))?;
-            let coin_blind = deserialize(row.get("coin_blind"))?;
-            let value_blind = deserialize(row.get("valcom_blind"))?;
-            let value = deserialize(row.get("value"))?;
-            let token_id = deserialize(row.get("token_id"))?;
-            let token_blind = deserialize(row.get("token_blind"))?;
-            let memo = deserialize(row.get("memo"))?;
-            let note = Note { serial, value, token_id, coin_blind, value_blind, token_blind, memo };
-
-            let secret = deserialize(row.get("secret"))?;
-            let nullifier = deserialize(row.get("nullifier"))?;
-            let leaf_position = deserialize(row.get("leaf_position"))?;
-
-            let oc = OwnCoin { coin, note, secret, nullifier, leaf_position };
-
-            own_coins.push(oc);
+            own_coins.push(own_coin_from_row(&row)?);
         }
 
         Ok(own_coins)
@@ -319,33 +555,16 @@ impl WalletDb {
         };
 
         let mut coins = vec![];
-
         for row in rows {
-            let coin = deserialize(row.get("coin"))?;
-
-            // Note
-            let serial = deserialize(row.get("serial"))?;
-            let coin_blind = deserialize(row.get("coin_blind"))?;
-            let value_blind = deserialize(row.get("valcom_blind"))?;
-            let value = deserialize(row.get("value"))?;
-            let token_id = deserialize(row.get("token_id"))?;
-            let token_blind = deserialize(row.get("token_blind"))?;
-            let memo = deserialize(row.get("memo"))?;
-            let note = Note { serial, value, token_id, coin_blind, value_blind, token_blind, memo };
-
-            let secret = deserialize(row.get("secret"))?;
-            let nullifier = deserialize(row.get("nullifier"))?;
-            let leaf_position = deserialize(row.get("leaf_position"))?;
-
-            let oc = OwnCoin { coin, note, secret, nullifier, leaf_position };
-
-            coins.push(oc);
+            coins.push(own_coin_from_row(&row)?);
         }
 
         Ok(coins)
     }
 
-    pub async fn put_own_coin(&self, own_coin: OwnCoin) -> Result<()> {
+    /// Store a coin received in the block of the given slot, and record it
+    /// in the history.
+    pub async fn put_own_coin(&self, own_coin: OwnCoin, slot: u64) -> Result<()> {
         debug!("Putting own coin into wallet database");
 
         let coin = serialize(&own_coin.coin.to_bytes());
@@ -359,34 +578,143 @@ impl WalletDb {
         let nullifier = serialize(&own_coin.nullifier);
         let leaf_position = serialize(&own_coin.leaf_position);
         let memo = serialize(&own_coin.note.memo);
+        let lock_slot = own_coin.note.lock_slot as i64;
         let is_spent: u8 = 0;
+        let slot = slot as i64;
 
         let mut conn = self.conn.acquire().await?;
+        let mut tx = conn.begin().await?;
         sqlx::query(
             "INSERT OR REPLACE INTO coins
             (coin, serial, coin_blind, valcom_blind, token_blind, value,
-             token_id, secret, is_spent, nullifier, leaf_position, memo)
+             token_id, secret, is_spent, nullifier, leaf_position, memo, lock_slot,
+             received_slot)
             VALUES
-             (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12);",
+             (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14);",
         )
-        .bind(coin)
+        .bind(&coin)
         .bind(serial)
         .bind(coin_blind)
         .bind(value_blind)
         .bind(token_blind)
-        .bind(value)
-        .bind(token_id)
+        .bind(&value)
+        .bind(&token_id)
         .bind(secret)
         .bind(is_spent)
         .bind(nullifier)
         .bind(leaf_position)
         .bind(memo)
-        .execute(&mut conn)
+        .bind(lock_slot)
+        .bind(slot)
+        .execute(&mut tx)
         .await?;
 
+        // A coin stored again, like on a rescan, keeps its history entry
+        sqlx::query(
+            "INSERT OR IGNORE INTO history (coin, kind, value, token_id, slot)
+             VALUES (?1, 'receive', ?2, ?3, ?4);",
+        )
+        .bind(coin)
+        .bind(value)
+        .bind(token_id)
+        .bind(slot)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Mark the coins with the given nullifiers as spent in the block of
+    /// the given slot, and record them in the history. Nullifiers of coins
+    /// the wallet doesn't own are ignored.
+    pub async fn confirm_nullifiers(&self, nullifiers: &[Nullifier], slot: u64) -> Result<()> {
+        let slot = slot as i64;
+
+        let mut conn = self.conn.acquire().await?;
+        let mut tx = conn.begin().await?;
+        for nullifier in nullifiers {
+            let nullifier = serialize(nullifier);
+            let spent = sqlx::query(
+                "UPDATE coins SET is_spent = 1, spent_slot = ?1
+                 WHERE nullifier = ?2 AND spent_slot IS NULL;",
+            )
+            .bind(slot)
+            .bind(&nullifier)
+            .execute(&mut tx)
+            .await?;
+            if spent.rows_affected() == 0 {
+                continue
+            }
+
+            debug!("Confirmed spend of coin with nullifier {:?}", nullifier);
+            sqlx::query(
+                "INSERT OR IGNORE INTO history (coin, kind, value, token_id, slot)
+                 SELECT coin, 'spend', value, token_id, ?1 FROM coins WHERE nullifier = ?2;",
+            )
+            .bind(slot)
+            .bind(nullifier)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
         Ok(())
     }
 
+    /// Fetch a coin of the wallet, spent or not.
+    pub async fn get_coin(&self, coin: &Coin) -> Result<Option<CoinInfo>> {
+        let mut conn = self.conn.acquire().await?;
+        let row = match sqlx::query("SELECT * FROM coins WHERE coin = ?1;")
+            .bind(serialize(&coin.to_bytes()))
+            .fetch_optional(&mut conn)
+            .await?
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        Ok(Some(CoinInfo {
+            own_coin: own_coin_from_row(&row)?,
+            is_spent: row.get::<bool, _>("is_spent"),
+            received_slot: row.get::<i64, _>("received_slot") as u64,
+            spent_slot: row.get::<Option<i64>, _>("spent_slot").map(|s| s as u64),
+        }))
+    }
+
+    /// Fetch up to `limit` history entries, newest first, starting before
+    /// the entry `before`, or with the latest entry if it's `None`. Entries
+    /// keep their position as the history grows, so the id of the last
+    /// entry of a page is the cursor of the next one.
+    pub async fn get_history(&self, before: Option<u64>, limit: u32) -> Result<Vec<HistoryEntry>> {
+        let before = before.map_or(i64::MAX, |id| id as i64);
+
+        let mut conn = self.conn.acquire().await?;
+        let rows = sqlx::query("SELECT * FROM history WHERE id < ?1 ORDER BY id DESC LIMIT ?2;")
+            .bind(before)
+            .bind(limit)
+            .fetch_all(&mut conn)
+            .await?;
+
+        let mut entries = vec![];
+        for row in rows {
+            let kind = match row.get::<&str, _>("kind") {
+                "spend" => HistoryKind::Spend,
+                _ => HistoryKind::Receive,
+            };
+            entries.push(HistoryEntry {
+                id: row.get::<i64, _>("id") as u64,
+                coin: deserialize(row.get("coin"))?,
+                kind,
+                value: deserialize(row.get("value"))?,
+                token_id: deserialize(row.get("token_id"))?,
+                slot: row.get::<i64, _>("slot") as u64,
+            });
+        }
+
+        Ok(entries)
+    }
+
     pub async fn remove_own_coins(&self) -> Result<()> {
         debug!("Removing own coins from wallet database");
         let mut conn = self.conn.acquire().await?;
@@ -429,10 +757,12 @@ impl WalletDb {
         let is_spent = 0;
 
         let mut conn = self.conn.acquire().await?;
-        let rows = sqlx::query("SELECT value, token_id, nullifier FROM coins WHERE is_spent = ?1;")
-            .bind(is_spent)
-            .fetch_all(&mut conn)
-            .await?;
+        let rows = sqlx::query(
+            "SELECT value, token_id, nullifier, lock_slot FROM coins WHERE is_spent = ?1;",
+        )
+        .bind(is_spent)
+        .fetch_all(&mut conn)
+        .await?;
 
         debug!("Found {} rows", rows.len());
 
@@ -441,12 +771,52 @@ impl WalletDb {
             let value = deserialize(row.get("value"))?;
             let token_id = deserialize(row.get("token_id"))?;
             let nullifier = deserialize(row.get("nullifier"))?;
-            list.push(Balance { token_id, value, nullifier });
+            let lock_slot = row.get::<i64, _>("lock_slot") as u64;
+            list.push(Balance { token_id, value, nullifier, lock_slot });
         }
 
         Ok(Balances { list })
     }
 
+    /// Balances of the coins not spent in a block, per token, as of `slot`.
+    /// Coins spent in blocks are skipped through the `spent_slot` index,
+    /// so the wallet's spending history doesn't slow this down.
+    pub async fn get_token_balances(&self, slot: u64) -> Result<Vec<TokenBalance>> {
+        let mut conn = self.conn.acquire().await?;
+        let rows = sqlx::query(
+            "SELECT value, token_id, lock_slot, is_spent FROM coins WHERE spent_slot IS NULL;",
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut balances: Vec<TokenBalance> = vec![];
+        for row in rows {
+            let value: u64 = deserialize(row.get("value"))?;
+            let token_id: DrkTokenId = deserialize(row.get("token_id"))?;
+            let lock_slot = row.get::<i64, _>("lock_slot") as u64;
+
+            let balance = match balances.iter().position(|b| b.token_id == token_id) {
+                Some(i) => &mut balances[i],
+                None => {
+                    let balance =
+                        TokenBalance { token_id, confirmed: 0, unconfirmed: 0, locked: 0 };
+                    balances.push(balance);
+                    balances.last_mut().unwrap()
+                }
+            };
+
+            if row.get::<bool, _>("is_spent") {
+                balance.unconfirmed += value;
+            } else if lock_slot > slot {
+                balance.locked += value;
+            } else {
+                balance.confirmed += value;
+            }
+        }
+
+        Ok(balances)
+    }
+
     pub async fn get_token_id(&self) -> Result<Vec<DrkTokenId>> {
         debug!("Getting token ID");
         let is_spent = 0;
@@ -505,7 +875,7 @@ mod tests {
 
     const WPASS: &str = "darkfi";
 
-    fn dummy_coin(s: &SecretKey, v: u64, t: &DrkTokenId) -> OwnCoin {
+    fn dummy_coin(s: &SecretKey, v: u64, t: &DrkTokenId, lock_slot: u64) -> OwnCoin {
         let serial = DrkSerial::random(&mut OsRng);
         let note = Note {
             serial,
@@ -514,6 +884,7 @@ mod tests {
             coin_blind: DrkCoinBlind::random(&mut OsRng),
             value_blind: DrkValueBlind::random(&mut OsRng),
             token_blind: DrkValueBlind::random(&mut OsRng),
+            lock_slot,
             memo: vec![],
         };
 
@@ -529,9 +900,6 @@ mod tests {
         let wallet = WalletDb::new("sqlite::memory:", WPASS).await?;
         let keypair = Keypair::random(&mut OsRng);
 
-        // init_db()
-        wallet.init_db().await?;
-
         // tree_gen()
         let mut tree1 = wallet.tree_gen().await?;
 
@@ -540,25 +908,25 @@ mod tests {
 
         let token_id = DrkTokenId::random(&mut OsRng);
 
-        let c0 = dummy_coin(&keypair.secret, 69, &token_id);
-        let c1 = dummy_coin(&keypair.secret, 420, &token_id);
-        let c2 = dummy_coin(&keypair.secret, 42, &token_id);
-        let c3 = dummy_coin(&keypair.secret, 11, &token_id);
+        let c0 = dummy_coin(&keypair.secret, 69, &token_id, 0);
+        let c1 = dummy_coin(&keypair.secret, 420, &token_id, 0);
+        let c2 = dummy_coin(&keypair.secret, 42, &token_id, 100);
+        let c3 = dummy_coin(&keypair.secret, 11, &token_id, 0);
 
         // put_own_coin()
-        wallet.put_own_coin(c0.clone()).await?;
+        wallet.put_own_coin(c0.clone(), 1).await?;
         tree1.append(&MerkleNode::from_coin(&c0.coin));
         tree1.witness();
 
-        wallet.put_own_coin(c1.clone()).await?;
+        wallet.put_own_coin(c1.clone(), 2).await?;
         tree1.append(&MerkleNode::from_coin(&c1.coin));
         tree1.witness();
 
-        wallet.put_own_coin(c2.clone()).await?;
+        wallet.put_own_coin(c2.clone(), 3).await?;
         tree1.append(&MerkleNode::from_coin(&c2.coin));
         tree1.witness();
 
-        wallet.put_own_coin(c3.clone()).await?;
+        wallet.put_own_coin(c3.clone(), 4).await?;
         tree1.append(&MerkleNode::from_coin(&c3.coin));
         tree1.witness();
 
@@ -583,6 +951,9 @@ mod tests {
         assert_eq!(balances.list[1].value, 420);
         assert_eq!(balances.list[2].value, 42);
         assert_eq!(balances.list[3].token_id, token_id);
+        assert_eq!(balances.liquid(99).map(|b| b.value).sum::<u64>(), 500);
+        assert_eq!(balances.locked(99).map(|b| b.value).sum::<u64>(), 42);
+        assert_eq!(balances.locked(100).count(), 0);
 
         /////////////////
         //// keypair ////
@@ -599,6 +970,24 @@ mod tests {
         // get default keypair
         assert_eq!(keypair2, wallet.get_default_keypair_or_create_one().await?);
 
+        // seed
+        let keypair3 = wallet.keygen().await?;
+        let mnemonic = wallet.get_mnemonic().await?;
+        assert!(wallet.restore_from_mnemonic(&mnemonic, 1).await.is_err());
+        let restored = WalletDb::new("sqlite::memory:", WPASS).await?;
+        restored.restore_from_mnemonic(&mnemonic, 1).await?;
+        assert_eq!(restored.get_keypairs().await?, vec![keypair3]);
+        assert_ne!(restored.keygen().await?, keypair3);
+
+        // diversified addresses
+        let div0 = wallet.new_diversified_address(&keypair2).await?;
+        let div1 = wallet.new_diversified_address(&keypair2).await?;
+        assert_eq!(div0, PaymentAddress::diversified(&keypair2.secret, 0));
+        assert_eq!(div1, PaymentAddress::diversified(&keypair2.secret, 1));
+        let secrets = wallet.get_scanning_secrets().await?;
+        assert_eq!(secrets.len(), 5);
+        assert!(secrets.contains(&keypair2.secret.diversify(1)));
+
         // get_own_coins()
         let own_coins = wallet.get_own_coins().await?;
         assert_eq!(own_coins.len(), 4);
@@ -618,6 +1007,37 @@ mod tests {
         let root3 = tree3.root(0).unwrap();
         assert_eq!(root2, root3);
 
+        // get_token_balances()
+        wallet.confirm_spend_coin(&c0.coin).await?;
+        wallet.confirm_nullifiers(&[c1.nullifier], 5).await?;
+        let balances = wallet.get_token_balances(99).await?;
+        assert_eq!(
+            balances,
+            vec![TokenBalance { token_id, confirmed: 11, unconfirmed: 69, locked: 42 }]
+        );
+
+        // get_history()
+        let page = wallet.get_history(None, 2).await?;
+        assert_eq!(page.len(), 2);
+        assert_eq!((page[0].coin, page[0].kind, page[0].slot), (c1.coin, HistoryKind::Spend, 5));
+        assert_eq!((page[1].coin, page[1].kind), (c3.coin, HistoryKind::Receive));
+        let page = wallet.get_history(Some(page[1].id), 10).await?;
+        let coins: Vec<Coin> = page.iter().map(|e| e.coin).collect();
+        assert_eq!(coins, vec![c2.coin, c1.coin, c0.coin]);
+
+        // scan state
+        assert_eq!((wallet.get_birthday_slot().await?, wallet.get_scanned_slot().await?), (0, 0));
+        wallet.set_birthday_slot(7).await?;
+        wallet.set_scanned_slot(9).await?;
+        wallet.set_scanned_slot(8).await?;
+        assert_eq!((wallet.get_birthday_slot().await?, wallet.get_scanned_slot().await?), (7, 9));
+
+        // get_coin()
+        let info = wallet.get_coin(&c1.coin).await?.unwrap();
+        assert_eq!(info.own_coin, c1);
+        assert_eq!((info.received_slot, info.spent_slot), (2, Some(5)));
+        assert!(wallet.get_coin(&Coin(pallas::Base::random(&mut OsRng))).await?.is_none());
+
         Ok(())
     }
 }
@@ -96,6 +96,7 @@ const BURN_MERKLEROOT_OFFSET: usize = 5;
 const BURN_USERDATA_OFFSET: usize = 6;
 const BURN_SIGKEYX_OFFSET: usize = 7;
 const BURN_SIGKEYY_OFFSET: usize = 8;
+const BURN_LOCKSLOT_OFFSET: usize = 9;
 
 #[derive(Default, Debug)]
 pub struct BurnContract {
@@ -114,6 +115,9 @@ pub struct BurnContract {
     pub user_data: Value<pallas::Base>,
     /// Blinding factor for the encrypted user_data
     pub user_data_blind: Value<pallas::Base>,
+    /// Slot until which the coin can't be spent, revealed so the validator
+    /// can check it has passed
+    pub lock_slot: Value<pallas::Base>,
     pub sig_secret: Value<pallas::Base>,
 }
 
@@ -323,6 +327,13 @@ impl Circuit<pallas::Base> for BurnContract {
             self.coin_blind,
         )?;
 
+        let lock_slot = assign_free_advice(
+            layouter.namespace(|| "load lock_slot"),
+            config.advices[0],
+            self.lock_slot,
+        )?;
+        layouter.constrain_instance(lock_slot.cell(), config.primary, BURN_LOCKSLOT_OFFSET)?;
+
         let public_key = {
             let nullifier_k = NullifierK;
             let nullifier_k = FixedPointBaseField::from_inner(ecc_chip.clone(), nullifier_k);
@@ -335,14 +346,23 @@ impl Circuit<pallas::Base> for BurnContract {
         // Coin hash
         // =========
         let coin = {
-            let poseidon_message =
-                [pub_x, pub_y, value, token, serial, spend_hook, user_data.clone(), coin_blind];
+            let poseidon_message = [
+                pub_x,
+                pub_y,
+                value,
+                token,
+                serial,
+                spend_hook,
+                user_data.clone(),
+                lock_slot,
+                coin_blind,
+            ];
 
             let poseidon_hasher = PoseidonHash::<
                 _,
                 _,
                 poseidon::P128Pow5T3,
-                poseidon::ConstantLength<8>,
+                poseidon::ConstantLength<9>,
                 3,
                 2,
             >::init(
@@ -563,6 +583,7 @@ mod tests {
         let user_data = pallas::Base::random(&mut OsRng);
         let user_data_blind = pallas::Base::random(&mut OsRng);
         let sig_secret = SecretKey::random(&mut OsRng);
+        let lock_slot = pallas::Base::from(1000);
 
         let coin2 = {
             let coords = PublicKey::from_secret(secret).0.to_affine().coordinates().unwrap();
@@ -574,9 +595,10 @@ mod tests {
                 serial,
                 spend_hook,
                 user_data,
+                lock_slot,
                 coin_blind,
             ];
-            poseidon::Hash::<_, P128Pow5T3, ConstantLength<8>, 3, 2>::init().hash(msg)
+            poseidon::Hash::<_, P128Pow5T3, ConstantLength<9>, 3, 2>::init().hash(msg)
         };
 
         let mut tree = BridgeTree::<MerkleNode, 32>::new(100);
@@ -623,6 +645,7 @@ mod tests {
             user_data_enc,
             *sig_coords.x(),
             *sig_coords.y(),
+            lock_slot,
         ];
 
         let circuit = BurnContract {
@@ -638,6 +661,7 @@ mod tests {
             spend_hook: Value::known(spend_hook),
             user_data: Value::known(user_data),
             user_data_blind: Value::known(user_data_blind),
+            lock_slot: Value::known(lock_slot),
             sig_secret: Value::known(sig_secret.0),
         };
 
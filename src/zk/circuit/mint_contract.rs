@@ -70,6 +70,8 @@ pub struct MintContract {
     pub spend_hook: Value<pallas::Base>,
     /// Data passed from this coin to the invoked contract
     pub user_data: Value<pallas::Base>,
+    /// Slot until which this coin can't be spent, 0 if it isn't locked
+    pub lock_slot: Value<pallas::Base>,
     /// Random blinding factor for value commitment
     pub value_blind: Value<pallas::Scalar>,
     /// Random blinding factor for the token ID
@@ -217,6 +219,12 @@ impl Circuit<pallas::Base> for MintContract {
             self.user_data,
         )?;
 
+        let lock_slot = assign_free_advice(
+            layouter.namespace(|| "load lock_slot"),
+            config.advices[6],
+            self.lock_slot,
+        )?;
+
         let coin_blind = assign_free_advice(
             layouter.namespace(|| "load coin_blind"),
             config.advices[6],
@@ -235,6 +243,7 @@ impl Circuit<pallas::Base> for MintContract {
                 serial,
                 spend_hook,
                 user_data,
+                lock_slot,
                 coin_blind,
             ];
 
@@ -242,7 +251,7 @@ impl Circuit<pallas::Base> for MintContract {
                 _,
                 _,
                 poseidon::P128Pow5T3,
-                poseidon::ConstantLength<8>,
+                poseidon::ConstantLength<9>,
                 3,
                 2,
             >::init(
@@ -388,6 +397,7 @@ mod tests {
         let coords = public_key.0.to_affine().coordinates().unwrap();
         let spend_hook = pallas::Base::random(&mut OsRng);
         let user_data = pallas::Base::random(&mut OsRng);
+        let lock_slot = pallas::Base::from(1000);
 
         let msg = [
             *coords.x(),
@@ -397,9 +407,10 @@ mod tests {
             serial,
             spend_hook,
             user_data,
+            lock_slot,
             coin_blind,
         ];
-        let coin = poseidon::Hash::<_, P128Pow5T3, ConstantLength<8>, 3, 2>::init().hash(msg);
+        let coin = poseidon::Hash::<_, P128Pow5T3, ConstantLength<9>, 3, 2>::init().hash(msg);
 
         let value_commit = pedersen_commitment_u64(value, value_blind);
         let value_coords = value_commit.to_affine().coordinates().unwrap();
@@ -419,6 +430,7 @@ mod tests {
             coin_blind: Value::known(coin_blind),
             spend_hook: Value::known(spend_hook),
             user_data: Value::known(user_data),
+            lock_slot: Value::known(lock_slot),
             value_blind: Value::known(value_blind),
             token_blind: Value::known(token_blind),
         };
@@ -0,0 +1,196 @@
+//! Circuit debugging helpers, used to trace the execution of a zkas
+//! circuit with a given set of witnesses.
+use std::sync::{Arc, Mutex};
+
+use halo2_proofs::{
+    circuit::Value,
+    dev::MockProver,
+    pasta::{
+        group::ff::{Field, PrimeField},
+        pallas,
+    },
+};
+use pasta_curves::arithmetic::CurveAffine;
+
+use super::{
+    testing::{mock_prove, CircuitFailure},
+    vm::ZkCircuit,
+    vm_stack::{StackVar, Witness},
+};
+use crate::{
+    crypto::merkle_node::MerkleNode,
+    zkas::{Opcode, VarType, ZkBinary},
+    Error::ZkWitnessInvalid as WitnessErr,
+    Result,
+};
+
+/// A single executed opcode of a circuit trace.
+#[derive(Clone, Debug)]
+pub struct TraceEntry {
+    /// The executed opcode
+    pub opcode: Opcode,
+    /// Description of the opcode arguments
+    pub args: Vec<String>,
+    /// Stack index and description of the result, if the opcode returns one
+    pub result: Option<(usize, String)>,
+    /// Value constrained to a public input, if the opcode does so
+    pub public_input: Option<pallas::Base>,
+}
+
+/// Shared trace the VM appends entries to while synthesizing.
+pub type Tracer = Arc<Mutex<Vec<TraceEntry>>>;
+
+/// Result of tracing a circuit with [`trace_circuit`].
+#[derive(Clone, Debug)]
+pub struct CircuitTrace {
+    pub entries: Vec<TraceEntry>,
+    /// Public inputs computed by the circuit, in order
+    pub public_inputs: Vec<pallas::Base>,
+    /// The first unsatisfied constraint, if any
+    pub failure: Option<CircuitFailure>,
+}
+
+/// Extract a value if it's known.
+pub(crate) fn known<V>(value: Value<V>) -> Option<V> {
+    let mut ret = None;
+    value.map(|v| ret = Some(v));
+    ret
+}
+
+fn fmt_known<V: std::fmt::Debug>(value: Value<V>) -> String {
+    match known(value) {
+        Some(v) => format!("{:?}", v),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Describe a stack value for a circuit trace.
+pub(crate) fn describe(var: &StackVar) -> String {
+    match var {
+        StackVar::EcPoint(v) => format!(
+            "EcPoint({}, {})",
+            fmt_known(v.inner().x().value().copied()),
+            fmt_known(v.inner().y().value().copied())
+        ),
+        StackVar::EcFixedPoint(_) => "EcFixedPoint".to_string(),
+        StackVar::EcFixedPointShort(_) => "EcFixedPointShort".to_string(),
+        StackVar::EcFixedPointBase(_) => "EcFixedPointBase".to_string(),
+        StackVar::Base(v) => format!("Base({})", fmt_known(v.value().copied())),
+        StackVar::Scalar(v) => format!("Scalar({})", fmt_known(*v)),
+        StackVar::MerklePath(_) => "MerklePath".to_string(),
+        StackVar::Uint32(v) => format!("Uint32({})", fmt_known(*v)),
+        StackVar::Uint64(v) => format!("Uint64({})", fmt_known(*v)),
+    }
+}
+
+/// Parse a field element from a JSON number, or from a hex string in the
+/// same big-endian form field elements are printed in.
+fn parse_field<F: PrimeField<Repr = [u8; 32]>>(value: &serde_json::Value) -> Result<F> {
+    if let Some(v) = value.as_u64() {
+        return Ok(F::from(v))
+    }
+
+    let hex_str = match value.as_str() {
+        Some(v) => v.trim_start_matches("0x"),
+        None => return Err(WitnessErr(format!("Expected a number or hex string, got {}", value))),
+    };
+
+    let mut repr = [0u8; 32];
+    match hex::decode(hex_str) {
+        Ok(bytes) if bytes.len() == 32 => repr.copy_from_slice(&bytes),
+        _ => return Err(WitnessErr(format!("Invalid field element hex string: {}", value))),
+    }
+    repr.reverse();
+
+    Option::from(F::from_repr(repr))
+        .ok_or_else(|| WitnessErr(format!("Value is not a field element: {}", value)))
+}
+
+fn parse_uint(value: &serde_json::Value) -> Result<u64> {
+    value.as_u64().ok_or_else(|| WitnessErr(format!("Expected an unsigned integer, got {}", value)))
+}
+
+/// Parse the witnesses of a circuit from a JSON array, with one element per
+/// witness in the order of the `contract` section:
+/// * `Base`, `Scalar`: a number or hex string
+/// * `Uint32`, `Uint64`: a number
+/// * `MerklePath`: an array of 32 `Base` elements
+/// * `EcPoint`: an array of the `[x, y]` affine coordinates
+pub fn witnesses_from_json(zkbin: &ZkBinary, json: &str) -> Result<Vec<Witness>> {
+    let json: serde_json::Value = serde_json::from_str(json)?;
+    let values = match json.as_array() {
+        Some(v) => v,
+        None => return Err(WitnessErr("Witnesses must be a JSON array".to_string())),
+    };
+
+    if values.len() != zkbin.witnesses.len() {
+        return Err(WitnessErr(format!(
+            "Expected {} witnesses, got {}",
+            zkbin.witnesses.len(),
+            values.len()
+        )))
+    }
+
+    let mut ret = Vec::with_capacity(values.len());
+    for (typ, value) in zkbin.witnesses.iter().zip(values) {
+        let witness = match typ {
+            VarType::Base => Witness::Base(Value::known(parse_field(value)?)),
+            VarType::Scalar => Witness::Scalar(Value::known(parse_field(value)?)),
+            VarType::Uint32 => {
+                let v = parse_uint(value)?;
+                let v = u32::try_from(v)
+                    .map_err(|_| WitnessErr(format!("Uint32 out of range: {}", v)))?;
+                Witness::Uint32(Value::known(v))
+            }
+            VarType::Uint64 => Witness::Uint64(Value::known(parse_uint(value)?)),
+            VarType::MerklePath => {
+                let nodes = match value.as_array() {
+                    Some(v) if v.len() == 32 => v,
+                    _ => return Err(WitnessErr("MerklePath must have 32 elements".to_string())),
+                };
+                let mut path = [MerkleNode(pallas::Base::zero()); 32];
+                for (node, v) in path.iter_mut().zip(nodes) {
+                    *node = MerkleNode(parse_field(v)?);
+                }
+                Witness::MerklePath(Value::known(path))
+            }
+            VarType::EcPoint => {
+                let coords = match value.as_array() {
+                    Some(v) if v.len() == 2 => v,
+                    _ => return Err(WitnessErr("EcPoint must be an [x, y] array".to_string())),
+                };
+                let point =
+                    pallas::Affine::from_xy(parse_field(&coords[0])?, parse_field(&coords[1])?);
+                let point: Option<pallas::Affine> = point.into();
+                match point {
+                    Some(p) => Witness::EcPoint(Value::known(pallas::Point::from(p))),
+                    None => return Err(WitnessErr(format!("Not a curve point: {}", value))),
+                }
+            }
+            x => return Err(WitnessErr(format!("Unsupported witness type {:?}", x))),
+        };
+
+        ret.push(witness);
+    }
+
+    Ok(ret)
+}
+
+/// Run a circuit with the given witnesses under the `MockProver`, recording
+/// every executed opcode. The public inputs are taken from the trace, so
+/// the reported failure, if any, comes from the circuit's own constraints.
+pub fn trace_circuit(k: u32, zkbin: &ZkBinary, witnesses: Vec<Witness>) -> Result<CircuitTrace> {
+    let tracer = Tracer::default();
+    let mut circuit = ZkCircuit::new(witnesses.clone(), zkbin.clone());
+    circuit.set_tracer(tracer.clone());
+
+    // The first run only computes the trace, as the public inputs aren't
+    // known until the circuit has been executed.
+    MockProver::run(k, &circuit, vec![vec![]])?;
+    let entries = tracer.lock().unwrap().clone();
+    let public_inputs: Vec<pallas::Base> = entries.iter().filter_map(|e| e.public_input).collect();
+
+    let failure = mock_prove(k, zkbin, witnesses, &public_inputs)?.into_iter().next();
+
+    Ok(CircuitTrace { entries, public_inputs, failure })
+}
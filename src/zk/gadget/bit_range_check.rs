@@ -0,0 +1,117 @@
+use group::ff::{Field, PrimeFieldBits};
+use halo2_proofs::{
+    circuit::{AssignedCell, Chip, Layouter, Region, Value},
+    pasta::pallas,
+    plonk,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Expression, Selector},
+    poly::Rotation,
+};
+
+/// Maximum bit-width that can be checked. Above this, the decomposition
+/// could wrap around the field modulus.
+pub const MAX_RANGE_CHECK_BITS: usize = 253;
+
+#[derive(Clone, Debug)]
+pub struct BitRangeCheckConfig {
+    pub z: Column<Advice>,
+    pub s_bit: Selector,
+}
+
+/// Range check of an arbitrary bit-width, decomposing the value into bits
+/// with a running sum. Every row costs a single bit, so the fixed-width
+/// lookup chips should be preferred where they fit.
+#[derive(Clone, Debug)]
+pub struct BitRangeCheckChip {
+    config: BitRangeCheckConfig,
+}
+
+impl Chip<pallas::Base> for BitRangeCheckChip {
+    type Config = BitRangeCheckConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl BitRangeCheckChip {
+    pub fn construct(config: BitRangeCheckConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<pallas::Base>,
+        z: Column<Advice>,
+    ) -> BitRangeCheckConfig {
+        // Enable permutation on z column
+        meta.enable_equality(z);
+
+        let s_bit = meta.selector();
+
+        meta.create_gate("Bit decomposition: z_curr - 2 * z_next is boolean", |meta| {
+            let s_bit = meta.query_selector(s_bit);
+            let z_curr = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+
+            //    z_next = (z_curr - b_i) / 2
+            // => b_i = z_curr - (z_next * 2)
+            let bit = z_curr - z_next * pallas::Base::from(2);
+            let one = Expression::Constant(pallas::Base::one());
+
+            Constraints::with_selector(s_bit, Some(bit.clone() * (one - bit)))
+        });
+
+        BitRangeCheckConfig { z, s_bit }
+    }
+
+    fn decompose(
+        &self,
+        region: &mut Region<'_, pallas::Base>,
+        z_0: AssignedCell<pallas::Base, pallas::Base>,
+        num_bits: usize,
+    ) -> Result<(), plonk::Error> {
+        if num_bits == 0 || num_bits > MAX_RANGE_CHECK_BITS {
+            return Err(plonk::Error::Synthesis)
+        }
+
+        let bits = z_0
+            .value()
+            .map(|v| v.to_le_bits().into_iter().take(num_bits).collect::<Vec<bool>>())
+            .transpose_vec(num_bits);
+        let two_inverse = Value::known(pallas::Base::from(2).invert().unwrap());
+
+        let mut z = z_0;
+        for (i, bit) in bits.iter().enumerate() {
+            self.config.s_bit.enable(region, i)?;
+
+            let bit = bit.map(|b| pallas::Base::from(b as u64));
+            // z_next = (z_curr - b_i) / 2
+            let z_next = (z.value().copied() - bit) * two_inverse;
+            z = region.assign_advice(|| format!("z_{}", i + 1), self.config.z, i + 1, || z_next)?;
+        }
+
+        // Constrain the remaining bits to be zero
+        region.constrain_constant(z.cell(), pallas::Base::zero())?;
+
+        Ok(())
+    }
+
+    pub fn copy_range_check(
+        &self,
+        mut layouter: impl Layouter<pallas::Base>,
+        value: AssignedCell<pallas::Base, pallas::Base>,
+        num_bits: usize,
+    ) -> Result<(), plonk::Error> {
+        layouter.assign_region(
+            || format!("copy {}-bit range check", num_bits),
+            |mut region: Region<'_, pallas::Base>| {
+                let z_0 = value.copy_advice(|| "z_0", &mut region, self.config.z, 0)?;
+                self.decompose(&mut region, z_0, num_bits)
+            },
+        )
+    }
+}
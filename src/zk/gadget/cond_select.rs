@@ -0,0 +1,97 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Chip, Layouter},
+    plonk,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Expression, Selector},
+    poly::Rotation,
+};
+use pasta_curves::pallas;
+
+#[derive(Clone, Debug)]
+pub struct CondSelectConfig {
+    cond: Column<Advice>,
+    a: Column<Advice>,
+    b: Column<Advice>,
+    out: Column<Advice>,
+    q_select: Selector,
+}
+
+/// Chip selecting `a` if `cond` is 1, and `b` if `cond` is 0.
+/// `cond` is constrained to be boolean.
+pub struct CondSelectChip {
+    config: CondSelectConfig,
+}
+
+impl Chip<pallas::Base> for CondSelectChip {
+    type Config = CondSelectConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl CondSelectChip {
+    pub fn configure(
+        meta: &mut ConstraintSystem<pallas::Base>,
+        cond: Column<Advice>,
+        a: Column<Advice>,
+        b: Column<Advice>,
+        out: Column<Advice>,
+    ) -> CondSelectConfig {
+        let q_select = meta.selector();
+
+        meta.create_gate("Conditional selection: out = cond ? a : b", |meta| {
+            let q_select = meta.query_selector(q_select);
+            let cond = meta.query_advice(cond, Rotation::cur());
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            let out = meta.query_advice(out, Rotation::cur());
+
+            let one = Expression::Constant(pallas::Base::one());
+            let bool_check = cond.clone() * (one - cond.clone());
+
+            Constraints::with_selector(
+                q_select,
+                [("bool_check", bool_check), ("select", cond * (a - b.clone()) + b - out)],
+            )
+        });
+
+        CondSelectConfig { cond, a, b, out, q_select }
+    }
+
+    pub fn construct(config: CondSelectConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn select(
+        &self,
+        mut layouter: impl Layouter<pallas::Base>,
+        cond: &AssignedCell<pallas::Base, pallas::Base>,
+        a: &AssignedCell<pallas::Base, pallas::Base>,
+        b: &AssignedCell<pallas::Base, pallas::Base>,
+    ) -> Result<AssignedCell<pallas::Base, pallas::Base>, plonk::Error> {
+        layouter.assign_region(
+            || "out = cond ? a : b",
+            |mut region| {
+                self.config.q_select.enable(&mut region, 0)?;
+
+                cond.copy_advice(|| "copy cond", &mut region, self.config.cond, 0)?;
+                a.copy_advice(|| "copy a", &mut region, self.config.a, 0)?;
+                b.copy_advice(|| "copy b", &mut region, self.config.b, 0)?;
+
+                let out = cond.value().zip(a.value()).zip(b.value()).map(|((cond, a), b)| {
+                    if *cond == pallas::Base::one() {
+                        *a
+                    } else {
+                        *b
+                    }
+                });
+                region.assign_advice(|| "out", self.config.out, 0, || out)
+            },
+        )
+    }
+}
@@ -9,3 +9,9 @@ pub mod less_than;
 
 /// is_zero comparison gadget
 pub mod is_zero;
+
+/// Conditional selection gadget
+pub mod cond_select;
+
+/// Arbitrary bit-width range check gadget
+pub mod bit_range_check;
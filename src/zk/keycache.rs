@@ -0,0 +1,241 @@
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use halo2_proofs::{
+    circuit::Value,
+    plonk,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Fixed, FloorPlanner,
+        Instance, Selector,
+    },
+    poly::commitment::Params,
+};
+use log::{debug, warn};
+use pasta_curves::vesta;
+
+use crate::crypto::{
+    proof::{ProvingKey, VerifyingKey},
+    types::DrkCircuitField,
+};
+
+/// Cache of circuit proving keys on disk, so they don't have to be generated
+/// on every run. Entries are keyed by a fingerprint of the whole circuit (see
+/// [`Fingerprint`]) and the `k` parameter, so changed circuits never load
+/// stale keys.
+///
+/// Cached keys are only used for proving, where a bad entry can at worst
+/// make our own proofs fail. Verifying keys used to validate other nodes'
+/// transactions are always built from the circuit.
+#[derive(Debug, Clone)]
+pub struct KeyCache {
+    dir: Option<PathBuf>,
+}
+
+impl Default for KeyCache {
+    /// Cache in the user cache directory. Caching is disabled if there
+    /// is none.
+    fn default() -> Self {
+        Self { dir: dirs::cache_dir().map(|dir| dir.join("darkfi").join("zk")) }
+    }
+}
+
+impl KeyCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir: Some(dir) }
+    }
+
+    /// Path of the cache entry for the given circuit and `k`.
+    fn entry_path<C: Circuit<DrkCircuitField>>(&self, k: u32, c: &C) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let fingerprint = Fingerprint::of(k, c)?;
+        Some(dir.join(format!("{}_{}.vk", fingerprint.to_hex(), k)))
+    }
+
+    /// Load the parameters and verifying key of the given circuit.
+    fn load<C: Circuit<DrkCircuitField>>(&self, path: &Path) -> io::Result<VerifyingKey> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+
+        let params = Params::<vesta::Affine>::read(&mut reader)?;
+        let vk = plonk::VerifyingKey::read::<_, C>(&mut reader, &params)?;
+
+        debug!("KeyCache::load(): Loaded keys from {:?}", path);
+        Ok(VerifyingKey { params, vk })
+    }
+
+    /// Write the parameters and verifying key of the given circuit. The file
+    /// is written aside and moved in place, so readers never see a partial one.
+    fn store(&self, path: &Path, key: &VerifyingKey) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+        key.params.write(&mut writer)?;
+        key.vk.write(&mut writer)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+
+        debug!("KeyCache::store(): Stored keys to {:?}", path);
+        Ok(())
+    }
+
+    /// Retrieve the parameters and verifying key the proving key is derived
+    /// from, generating and caching them if they're not cached yet.
+    fn proving_vk<C: Circuit<DrkCircuitField>>(&self, k: u32, c: &C) -> VerifyingKey {
+        let path = match self.entry_path(k, c) {
+            Some(path) => path,
+            None => return VerifyingKey::build(k, c),
+        };
+
+        if let Ok(key) = self.load::<C>(&path) {
+            return key
+        }
+
+        let key = VerifyingKey::build(k, c);
+        if let Err(e) = self.store(&path, &key) {
+            warn!("KeyCache::proving_vk(): Failed caching keys: {}", e);
+        }
+        key
+    }
+
+    /// Retrieve the proving key of the given circuit, derived from the cached
+    /// parameters and verifying key.
+    pub fn proving_key<C: Circuit<DrkCircuitField>>(&self, k: u32, c: &C) -> ProvingKey {
+        let VerifyingKey { params, vk } = self.proving_vk(k, c);
+        let pk = plonk::keygen_pk(&params, vk, c).unwrap();
+        ProvingKey { params, pk }
+    }
+}
+
+/// Fingerprint of everything circuit keys are generated from: the constraint
+/// system built by `configure()`, and the selectors, fixed column values and
+/// copy constraints laid out by `synthesize()`. Witness values don't go into
+/// the keys and are ignored.
+struct Fingerprint(blake3::Hasher);
+
+impl Fingerprint {
+    /// Fingerprint the given circuit and `k`. Returns `None` if the circuit
+    /// fails to synthesize, in which case key generation fails as well.
+    fn of<C: Circuit<DrkCircuitField>>(k: u32, c: &C) -> Option<blake3::Hash> {
+        let mut cs = ConstraintSystem::<DrkCircuitField>::default();
+        let config = C::configure(&mut cs);
+
+        let mut fp = Self(blake3::Hasher::new());
+        fp.0.update(env!("CARGO_PKG_VERSION").as_bytes());
+        fp.0.update(&k.to_le_bytes());
+        fp.0.update(format!("{:?}", cs).as_bytes());
+
+        C::FloorPlanner::synthesize(&mut fp, c, config, cs.constants().clone()).ok()?;
+        Some(fp.0.finalize())
+    }
+
+    fn record(&mut self, entry: String) {
+        self.0.update(entry.as_bytes());
+        self.0.update(&[0]);
+    }
+}
+
+impl Assignment<DrkCircuitField> for Fingerprint {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(
+        &mut self,
+        _: A,
+        selector: &Selector,
+        row: usize,
+    ) -> Result<(), plonk::Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.record(format!("selector {:?} {}", selector, row));
+        Ok(())
+    }
+
+    fn query_instance(
+        &self,
+        _: Column<Instance>,
+        _: usize,
+    ) -> Result<Value<DrkCircuitField>, plonk::Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        _: usize,
+        _: V,
+    ) -> Result<(), plonk::Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<DrkCircuitField>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Fixed>,
+        row: usize,
+        to: V,
+    ) -> Result<(), plonk::Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<DrkCircuitField>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let value = to().map(|v| Into::<Assigned<DrkCircuitField>>::into(v).evaluate());
+        self.record(format!("fixed {:?} {} {:?}", column, row, value));
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        left_column: Column<Any>,
+        left_row: usize,
+        right_column: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), plonk::Error> {
+        self.record(format!(
+            "copy {:?} {} {:?} {}",
+            left_column, left_row, right_column, right_row
+        ));
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        column: Column<Fixed>,
+        row: usize,
+        to: Value<Assigned<DrkCircuitField>>,
+    ) -> Result<(), plonk::Error> {
+        let value = to.map(|v| v.evaluate());
+        self.record(format!("fill {:?} {} {:?}", column, row, value));
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
@@ -8,6 +8,15 @@ pub mod circuit;
 /// ZK gadget implementations
 pub mod gadget;
 
+/// Circuit keys disk cache
+pub mod keycache;
+
+/// Circuit execution tracing
+pub mod debug;
+
+/// MockProver test harness for zkas circuits
+pub mod testing;
+
 use halo2_proofs::{
     arithmetic::Field,
     circuit::{AssignedCell, Layouter, Value},
@@ -15,6 +24,19 @@ use halo2_proofs::{
     plonk::{Advice, Assigned, Column},
 };
 
+/// Assign a constant to an advice cell, constrained to the value fixed in
+/// the circuit's constants column.
+pub fn assign_constant<F: Field>(
+    mut layouter: impl Layouter<F>,
+    column: Column<Advice>,
+    value: F,
+) -> Result<AssignedCell<F, F>, plonk::Error> {
+    layouter.assign_region(
+        || "load constant",
+        |mut region| region.assign_advice_from_constant(|| "load constant", column, 0, value),
+    )
+}
+
 //pub(in crate::zk) fn assign_free_advice<F: Field, V: Copy>(
 pub fn assign_free_advice<F: Field, V: Copy>(
     mut layouter: impl Layouter<F>,
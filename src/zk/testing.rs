@@ -0,0 +1,73 @@
+//! Test harness for zkas circuits. Circuits are run under halo2's
+//! `MockProver`, so they can be checked against their witnesses and
+//! public inputs without generating keys and proofs.
+use std::fmt;
+
+use halo2_proofs::{
+    dev::{MockProver, VerifyFailure},
+    pasta::pallas,
+};
+
+use super::vm::{Witness, ZkCircuit};
+use crate::{zkas::ZkBinary, Result};
+
+/// Kind of constraint a circuit failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// A cell used by an active gate was never assigned
+    CellNotAssigned,
+    /// A gate constraint isn't satisfied
+    Constraint,
+    /// A gate constraint can't be evaluated, because a cell it uses is poisoned
+    ConstraintPoisoned,
+    /// A looked up value is missing from its table
+    Lookup,
+    /// A copy constraint, e.g. against a public input, isn't satisfied
+    Permutation,
+}
+
+/// A constraint failure reported by the `MockProver`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitFailure {
+    pub kind: FailureKind,
+    /// The failure as described by halo2, including its location
+    pub description: String,
+}
+
+impl From<VerifyFailure> for CircuitFailure {
+    fn from(failure: VerifyFailure) -> Self {
+        let kind = match failure {
+            VerifyFailure::CellNotAssigned { .. } => FailureKind::CellNotAssigned,
+            VerifyFailure::ConstraintNotSatisfied { .. } => FailureKind::Constraint,
+            VerifyFailure::ConstraintPoisoned { .. } => FailureKind::ConstraintPoisoned,
+            VerifyFailure::Lookup { .. } => FailureKind::Lookup,
+            VerifyFailure::Permutation { .. } => FailureKind::Permutation,
+        };
+
+        Self { kind, description: failure.to_string() }
+    }
+}
+
+impl fmt::Display for CircuitFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Run a compiled circuit with the given witnesses and public inputs under
+/// the `MockProver`, with `2^k` rows. Returns all the failed constraints,
+/// so an empty vector means the circuit is satisfied.
+pub fn mock_prove(
+    k: u32,
+    zkbin: &ZkBinary,
+    witnesses: Vec<Witness>,
+    public_inputs: &[pallas::Base],
+) -> Result<Vec<CircuitFailure>> {
+    let circuit = ZkCircuit::new(witnesses, zkbin.clone());
+    let prover = MockProver::run(k, &circuit, vec![public_inputs.to_vec()])?;
+
+    match prover.verify() {
+        Ok(()) => Ok(vec![]),
+        Err(failures) => Ok(failures.into_iter().map(CircuitFailure::from).collect()),
+    }
+}
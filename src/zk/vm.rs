@@ -4,8 +4,9 @@ use halo2_gadgets::{
         FixedPoint, FixedPointBaseField, FixedPointShort, Point, ScalarFixed, ScalarFixedShort,
     },
     poseidon::{
-        primitives as poseidon, Hash as PoseidonHash, Pow5Chip as PoseidonChip,
-        Pow5Config as PoseidonConfig,
+        primitives::{self as poseidon, Domain},
+        Hash as PoseidonHash, PaddedWord, Pow5Chip as PoseidonChip, Pow5Config as PoseidonConfig,
+        Sponge,
     },
     sinsemilla::{
         chip::{SinsemillaChip, SinsemillaConfig},
@@ -26,18 +27,25 @@ use log::{debug, error};
 
 pub use super::vm_stack::{StackVar, Witness};
 use super::{
-    assign_free_advice,
+    assign_constant, assign_free_advice,
+    debug::{describe, known, TraceEntry, Tracer},
     gadget::{
         arithmetic::{ArithChip, ArithConfig, ArithInstruction},
+        bit_range_check::{BitRangeCheckChip, BitRangeCheckConfig},
+        cond_select::{CondSelectChip, CondSelectConfig},
         less_than::{LessThanChip, LessThanConfig},
         native_range_check::{NativeRangeCheckChip, NativeRangeCheckConfig},
     },
 };
 use crate::{
-    crypto::constants::{
-        sinsemilla::{OrchardCommitDomains, OrchardHashDomains},
-        util::gen_const_array,
-        NullifierK, OrchardFixedBases, OrchardFixedBasesFull, ValueCommitV, MERKLE_DEPTH_ORCHARD,
+    crypto::{
+        constants::{
+            sinsemilla::{OrchardCommitDomains, OrchardHashDomains},
+            util::gen_const_array,
+            NullifierK, OrchardFixedBases, OrchardFixedBasesFull, ValueCommitV,
+            MERKLE_DEPTH_ORCHARD,
+        },
+        util::VariableLength,
     },
     zkas::{
         types::{LitType, StackType},
@@ -56,9 +64,11 @@ pub struct VmConfig {
     _sinsemilla_cfg2: SinsemillaConfig<OrchardHashDomains, OrchardCommitDomains, OrchardFixedBases>,
     poseidon_config: PoseidonConfig<pallas::Base, 3, 2>,
     arith_config: ArithConfig,
+    cond_select_config: CondSelectConfig,
     native_64_range_check_config: NativeRangeCheckConfig<3, 64, 22>,
     native_253_range_check_config: NativeRangeCheckConfig<3, 253, 85>,
     lessthan_config: LessThanConfig<3, 253, 85>,
+    bit_range_check_config: BitRangeCheckConfig,
 }
 
 impl VmConfig {
@@ -85,6 +95,10 @@ impl VmConfig {
     fn arithmetic_chip(&self) -> ArithChip {
         ArithChip::construct(self.arith_config.clone())
     }
+
+    fn cond_select_chip(&self) -> CondSelectChip {
+        CondSelectChip::construct(self.cond_select_config.clone())
+    }
 }
 
 pub struct ZkCircuit {
@@ -92,6 +106,7 @@ pub struct ZkCircuit {
     witnesses: Vec<Witness>,
     literals: Vec<(LitType, String)>,
     opcodes: Vec<(Opcode, Vec<(StackType, usize)>)>,
+    tracer: Option<Tracer>,
 }
 
 impl ZkCircuit {
@@ -99,7 +114,12 @@ impl ZkCircuit {
         let constants = circuit_code.constants.iter().map(|x| x.1.clone()).collect();
         #[allow(clippy::map_clone)]
         let literals = circuit_code.literals.iter().map(|x| x.clone()).collect();
-        Self { constants, witnesses, literals, opcodes: circuit_code.opcodes }
+        Self { constants, witnesses, literals, opcodes: circuit_code.opcodes, tracer: None }
+    }
+
+    /// Record every executed opcode into `tracer` when synthesizing.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
     }
 }
 
@@ -113,6 +133,7 @@ impl Circuit<pallas::Base> for ZkCircuit {
             witnesses: self.witnesses.clone(),
             literals: self.literals.clone(),
             opcodes: self.opcodes.clone(),
+            tracer: self.tracer.clone(),
         }
     }
 
@@ -185,6 +206,10 @@ impl Circuit<pallas::Base> for ZkCircuit {
         // Configuration for the Arithmetic chip
         let arith_config = ArithChip::configure(meta, advices[7], advices[8], advices[6]);
 
+        // Configuration for the conditional selection chip
+        let cond_select_config =
+            CondSelectChip::configure(meta, advices[5], advices[6], advices[7], advices[8]);
+
         // Configuration for a Sinsemilla hash instantiation and a
         // Merkle hash instantiation using this Sinsemilla instance.
         // Since the Sinsemilla config uses only 5 advice columns,
@@ -232,6 +257,9 @@ impl Circuit<pallas::Base> for ZkCircuit {
             k_values_table_253,
         );
 
+        // Range checks of widths other than 64 and 253 bits
+        let bit_range_check_config = BitRangeCheckChip::configure(meta, advices[9]);
+
         VmConfig {
             primary,
             advices,
@@ -242,9 +270,11 @@ impl Circuit<pallas::Base> for ZkCircuit {
             _sinsemilla_cfg2,
             poseidon_config,
             arith_config,
+            cond_select_config,
             native_64_range_check_config,
             native_253_range_check_config,
             lessthan_config,
+            bit_range_check_config,
         }
     }
 
@@ -255,6 +285,12 @@ impl Circuit<pallas::Base> for ZkCircuit {
     ) -> std::result::Result<(), plonk::Error> {
         debug!("Entering synthesize()");
 
+        // The floor planner can synthesize more than once, and only the
+        // last pass is traced.
+        if let Some(tracer) = &self.tracer {
+            tracer.lock().unwrap().clear();
+        }
+
         // ===================
         // VM Setup
         //====================
@@ -294,12 +330,19 @@ impl Circuit<pallas::Base> for ZkCircuit {
             config.native_253_range_check_config.k_values_table,
         )?;
 
+        // Construct the arbitrary bit-width range check chip.
+        let bit_range_check_chip =
+            BitRangeCheckChip::construct(config.bit_range_check_config.clone());
+
         // Construct the ECC chip.
         let ecc_chip = config.ecc_chip();
 
         // Construct the Arithmetic chip.
         let arith_chip = config.arithmetic_chip();
 
+        // Construct the conditional selection chip.
+        let cond_select_chip = config.cond_select_chip();
+
         // ==========================
         // Constants setup
         // ==========================
@@ -421,6 +464,8 @@ impl Circuit<pallas::Base> for ZkCircuit {
         // =============================
         // TODO: Copy constraints
         for opcode in &self.opcodes {
+            let stack_len = stack.len();
+
             match opcode.0 {
                 Opcode::EcAdd => {
                     debug!("Executing `EcAdd{:?}` opcode", opcode.1);
@@ -571,6 +616,38 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     vla!(args, a, b, c, 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16);
                 }
 
+                Opcode::PoseidonSponge => {
+                    debug!("Executing `PoseidonSponge{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let mut sponge =
+                        Sponge::<_, _, poseidon::P128Pow5T3, _, VariableLength, 3, 2>::new(
+                            config.poseidon_chip(),
+                            layouter.namespace(|| "PoseidonSponge init"),
+                        )?;
+
+                    for idx in args {
+                        sponge.absorb(
+                            layouter.namespace(|| "PoseidonSponge absorb"),
+                            PaddedWord::Message(stack[idx.1].clone().into()),
+                        )?;
+                    }
+
+                    for padding in VariableLength::padding(args.len()) {
+                        sponge.absorb(
+                            layouter.namespace(|| "PoseidonSponge pad"),
+                            PaddedWord::Padding(padding),
+                        )?;
+                    }
+
+                    let mut sponge =
+                        sponge.finish_absorbing(layouter.namespace(|| "PoseidonSponge finish"))?;
+                    let hash = sponge.squeeze(layouter.namespace(|| "PoseidonSponge squeeze"))?;
+
+                    debug!("Pushing hash to stack index {}", stack.len());
+                    stack.push(StackVar::Base(hash));
+                }
+
                 Opcode::MerkleRoot => {
                     debug!("Executing `MerkleRoot{:?}` opcode", opcode.1);
                     let args = &opcode.1;
@@ -633,6 +710,25 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     stack.push(StackVar::Base(difference));
                 }
 
+                Opcode::CondSelect => {
+                    debug!("Executing `CondSelect{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let cond = &stack[args[0].1].clone().into();
+                    let a = &stack[args[1].1].clone().into();
+                    let b = &stack[args[2].1].clone().into();
+
+                    let selected = cond_select_chip.select(
+                        layouter.namespace(|| "CondSelect()"),
+                        cond,
+                        a,
+                        b,
+                    )?;
+
+                    debug!("Pushing selection to stack index {}", stack.len());
+                    stack.push(StackVar::Base(selected));
+                }
+
                 Opcode::WitnessBase => {
                     debug!("Executing `WitnessBase{:?}` opcode", opcode.1);
                     //let args = &opcode.1;
@@ -650,6 +746,22 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     stack.push(StackVar::Base(witness));
                 }
 
+                Opcode::LiteralBase => {
+                    debug!("Executing `LiteralBase{:?}` opcode", opcode.1);
+
+                    let lit = litstack[literals_offset];
+                    literals_offset += 1;
+
+                    let constant = assign_constant(
+                        layouter.namespace(|| "Load literal"),
+                        config.advices[0],
+                        pallas::Base::from(lit),
+                    )?;
+
+                    debug!("Pushing constant to stack index {}", stack.len());
+                    stack.push(StackVar::Base(constant));
+                }
+
                 Opcode::RangeCheck => {
                     debug!("Executing `RangeCheck{:?}` opcode", opcode.1);
                     let args = &opcode.1;
@@ -675,8 +787,11 @@ impl Circuit<pallas::Base> for ZkCircuit {
                             )?;
                         }
                         x => {
-                            error!("Unsupported bit-range {} for range_check", x);
-                            return Err(plonk::Error::Synthesis)
+                            bit_range_check_chip.copy_range_check(
+                                layouter.namespace(|| format!("copy range check {}", x)),
+                                arg.into(),
+                                x as usize,
+                            )?;
                         }
                     }
                 }
@@ -717,6 +832,38 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     return Err(plonk::Error::Synthesis)
                 }
             }
+
+            if let Some(tracer) = &self.tracer {
+                let args = opcode
+                    .1
+                    .iter()
+                    .map(|(typ, idx)| match typ {
+                        StackType::Var => format!("stack[{}] = {}", idx, describe(&stack[*idx])),
+                        StackType::Lit => format!("literal {}", litstack[*idx]),
+                    })
+                    .collect();
+
+                let result = if stack.len() > stack_len {
+                    Some((stack_len, describe(&stack[stack_len])))
+                } else {
+                    None
+                };
+
+                let public_input = match opcode.0 {
+                    Opcode::ConstrainInstance => {
+                        let var: AssignedCell<Fp, Fp> = stack[opcode.1[0].1].clone().into();
+                        known(var.value().copied())
+                    }
+                    _ => None,
+                };
+
+                tracer.lock().unwrap().push(TraceEntry {
+                    opcode: opcode.0,
+                    args,
+                    result,
+                    public_input,
+                });
+            }
         }
 
         debug!("Exiting synthesize() successfully");
@@ -78,12 +78,15 @@ impl Analyzer {
             match &statement.opcode {
                 Opcode::RangeCheck => {
                     if let Arg::Lit(arg0) = &statement.rhs[0] {
-                        if &arg0.name != "64" && &arg0.name != "253" {
-                            self.error.abort(
-                                "Supported range checks are only 64 and 253 bits.",
+                        // 64 and 253 bits use lookup tables, other widths
+                        // are decomposed bit by bit.
+                        match arg0.name.parse::<u64>() {
+                            Ok(1..=253) => {}
+                            _ => self.error.abort(
+                                "Supported range checks are between 1 and 253 bits.",
                                 arg0.line,
                                 arg0.column,
-                            );
+                            ),
                         }
                     } else {
                         self.error.abort(
@@ -343,6 +346,12 @@ impl Analyzer {
     }
 
     fn lookup_var(&self, name: &str) -> Option<Var> {
+        // Assigned variables are looked up first, as they can shadow
+        // other names (e.g. when assigned in an unrolled loop).
+        if let Some(r) = self.lookup_stack(name) {
+            return Some(Var::Variable(r))
+        }
+
         if let Some(r) = self.lookup_constant(name) {
             return Some(Var::Constant(r))
         }
@@ -351,10 +360,6 @@ impl Analyzer {
             return Some(Var::Witness(r))
         }
 
-        if let Some(r) = self.lookup_stack(name) {
-            return Some(Var::Variable(r))
-        }
-
         None
     }
 
@@ -379,7 +384,7 @@ impl Analyzer {
     }
 
     fn lookup_stack(&self, name: &str) -> Option<Variable> {
-        for i in &self.stack {
+        for i in self.stack.iter().rev() {
             if i.name == name {
                 return Some(i.clone())
             }
@@ -424,7 +429,7 @@ impl Analyzer {
             for arg in &i.rhs {
                 if let Arg::Var(arg) = arg {
                     print!("Looking up `{}` on the stack... ", arg.name);
-                    if let Some(index) = stack.iter().position(|&r| r == &arg.name) {
+                    if let Some(index) = stack.iter().rposition(|&r| r == &arg.name) {
                         println!("Found at stack index {}", index);
                     } else {
                         self.error.abort(
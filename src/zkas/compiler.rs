@@ -77,13 +77,6 @@ impl Compiler {
 
         bincode.extend_from_slice(b".circuit");
         for i in &self.statements {
-            match i.typ {
-                StatementType::Assign => tmp_stack.push(&i.lhs.as_ref().unwrap().name),
-                // In case of a simple call, we don't append anything to the stack
-                StatementType::Call => {}
-                _ => unreachable!(),
-            }
-
             bincode.push(i.opcode as u8);
             bincode.extend_from_slice(&serialize(&VarInt(i.rhs.len() as u64)));
 
@@ -118,6 +111,15 @@ impl Compiler {
                     _ => unreachable!(),
                 };
             }
+
+            // The result is pushed after the arguments are looked up, so an
+            // assignment can reference the variable it's shadowing.
+            match i.typ {
+                StatementType::Assign => tmp_stack.push(&i.lhs.as_ref().unwrap().name),
+                // In case of a simple call, we don't append anything to the stack
+                StatementType::Call => {}
+                _ => unreachable!(),
+            }
         }
 
         // If we're not doing debug info, we're done here and can return.
@@ -130,8 +132,10 @@ impl Compiler {
         bincode
     }
 
+    /// Find the latest stack reference of `name`, as later assignments
+    /// shadow earlier ones.
     fn lookup_stack(stack: &[&str], name: &str) -> Option<usize> {
-        for (idx, n) in stack.iter().enumerate() {
+        for (idx, n) in stack.iter().enumerate().rev() {
             if n == &name {
                 return Some(idx)
             }
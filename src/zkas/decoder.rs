@@ -22,6 +22,10 @@ fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 
 impl ZkBinary {
     pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < MAGIC_BYTES.len() + 1 {
+            return Err(ZkasErr("Binary is too short.".to_string()))
+        }
+
         let magic_bytes = &bytes[0..4];
         if magic_bytes != MAGIC_BYTES {
             return Err(ZkasErr("Magic bytes are incorrect.".to_string()))
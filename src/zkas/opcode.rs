@@ -28,6 +28,9 @@ pub enum Opcode {
     /// Poseidon hash of N Base field elements
     PoseidonHash = 0x10,
 
+    /// Poseidon hash of N Base field elements, absorbed into a sponge
+    PoseidonSponge = 0x11,
+
     /// Calculate Merkle root, given a position, Merkle path, and an element
     MerkleRoot = 0x20,
 
@@ -40,9 +43,15 @@ pub enum Opcode {
     /// Base field element subtraction
     BaseSub = 0x32,
 
+    /// Select one of two Base field elements, given a boolean condition
+    CondSelect = 0x33,
+
     /// Witness an unsigned integer into a Base field element
     WitnessBase = 0x40,
 
+    /// Load an unsigned integer as a Base field element fixed in the circuit
+    LiteralBase = 0x41,
+
     /// Range check a Base field element, given bit-width (up to 253)
     RangeCheck = 0x50,
 
@@ -66,11 +75,14 @@ impl Opcode {
             "ec_get_x" => Some(Self::EcGetX),
             "ec_get_y" => Some(Self::EcGetY),
             "poseidon_hash" => Some(Self::PoseidonHash),
+            "poseidon_sponge" => Some(Self::PoseidonSponge),
             "merkle_root" => Some(Self::MerkleRoot),
             "base_add" => Some(Self::BaseAdd),
             "base_mul" => Some(Self::BaseMul),
             "base_sub" => Some(Self::BaseSub),
+            "cond_select" => Some(Self::CondSelect),
             "witness_base" => Some(Self::WitnessBase),
+            "literal_base" => Some(Self::LiteralBase),
             "range_check" => Some(Self::RangeCheck),
             "less_than" => Some(Self::LessThan),
             "constrain_instance" => Some(Self::ConstrainInstance),
@@ -88,11 +100,14 @@ impl Opcode {
             0x08 => Some(Self::EcGetX),
             0x09 => Some(Self::EcGetY),
             0x10 => Some(Self::PoseidonHash),
+            0x11 => Some(Self::PoseidonSponge),
             0x20 => Some(Self::MerkleRoot),
             0x30 => Some(Self::BaseAdd),
             0x31 => Some(Self::BaseMul),
             0x32 => Some(Self::BaseSub),
+            0x33 => Some(Self::CondSelect),
             0x40 => Some(Self::WitnessBase),
+            0x41 => Some(Self::LiteralBase),
             0x50 => Some(Self::RangeCheck),
             0x51 => Some(Self::LessThan),
             0xf0 => Some(Self::ConstrainInstance),
@@ -125,6 +140,8 @@ impl Opcode {
 
             Opcode::PoseidonHash => (vec![VarType::Base], vec![VarType::BaseArray]),
 
+            Opcode::PoseidonSponge => (vec![VarType::Base], vec![VarType::BaseArray]),
+
             Opcode::MerkleRoot => {
                 (vec![VarType::Base], vec![VarType::Uint32, VarType::MerklePath, VarType::Base])
             }
@@ -135,8 +152,14 @@ impl Opcode {
 
             Opcode::BaseSub => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
 
+            Opcode::CondSelect => {
+                (vec![VarType::Base], vec![VarType::Base, VarType::Base, VarType::Base])
+            }
+
             Opcode::WitnessBase => (vec![VarType::Base], vec![VarType::Uint64]),
 
+            Opcode::LiteralBase => (vec![VarType::Base], vec![VarType::Uint64]),
+
             Opcode::RangeCheck => (vec![], vec![VarType::Uint64, VarType::Base]),
 
             Opcode::LessThan => (vec![], vec![VarType::Base, VarType::Base]),
@@ -23,6 +23,12 @@ const VALID_ECFIXEDPOINTSHORT: [&str; 1] = ["VALUE_COMMIT_VALUE"];
 /// Valid EcFixedPointBase constant names supported by the VM.
 const VALID_ECFIXEDPOINTBASE: [&str; 1] = ["NULLIFIER_K"];
 
+/// Keyword opening a bounded loop in the circuit section.
+const REPEAT_KEYWORD: &str = "repeat";
+
+/// Maximum number of iterations of a single loop.
+const MAX_REPEAT_COUNT: usize = 1024;
+
 pub struct Parser {
     tokens: Vec<Token>,
     error: ErrorEmitter,
@@ -54,6 +60,7 @@ impl Parser {
         let mut circuit_stmts = vec![];
         let mut ast_inner = IndexMap::new();
         let mut ast = IndexMap::new();
+        let mut named_literals = IndexMap::new();
 
         if self.tokens[0].token_type != TokenType::Symbol {
             self.error.abort(
@@ -69,16 +76,16 @@ impl Parser {
             // the sections we must be declaring in our source code.
             // When we find one, we'll take all the tokens found in
             // the section and place them in their respective vec.
-            // NOTE: Currently this logic depends on the fact that
-            // the sections are closed off with braces. This should
-            // be revisited later when we decide to add other lang
-            // functionality that also depends on using braces.
+            // NOTE: Sections are closed off with braces, so we keep
+            // track of the nesting depth to allow braced blocks like
+            // loops inside of them.
             if !declaring_constant && !declaring_contract && !declaring_circuit {
                 //
                 // We use this macro to avoid code repetition in the following
                 // match statement for soaking up the section tokens.
                 macro_rules! absorb_inner_tokens {
                     ($v:ident) => {
+                        let mut depth = 0;
                         for inner in iter.by_ref() {
                             if KEYWORDS.contains(&inner.token.as_str()) &&
                                 inner.token_type == TokenType::Symbol
@@ -91,8 +98,15 @@ impl Parser {
                             }
 
                             $v.push(inner.clone());
-                            if inner.token_type == TokenType::RightBrace {
-                                break
+                            match inner.token_type {
+                                TokenType::LeftBrace => depth += 1,
+                                TokenType::RightBrace => {
+                                    depth -= 1;
+                                    if depth <= 0 {
+                                        break
+                                    }
+                                }
+                                _ => {}
                             }
                         }
                     };
@@ -150,13 +164,11 @@ impl Parser {
                 let mut constants_map = IndexMap::new();
                 // This is everything between the braces: { ... }
                 let mut constant_inner = constant_tokens[2..constant_tokens.len() - 1].iter();
-                while let Some((typ, name, comma)) = constant_inner.next_tuple() {
-                    if comma.token_type != TokenType::Comma {
-                        self.error.abort("Separator is not a comma.", comma.line, comma.column);
-                    }
-
+                while let Some((typ, name, sep)) = constant_inner.next_tuple() {
                     // No variable shadowing
-                    if constants_map.contains_key(name.token.as_str()) {
+                    if constants_map.contains_key(name.token.as_str()) ||
+                        named_literals.contains_key(name.token.as_str())
+                    {
                         self.error.abort(
                             &format!(
                                 "Section `constant` already contains the token `{}`.",
@@ -167,11 +179,43 @@ impl Parser {
                         );
                     }
 
+                    // A named literal is declared as `<Type> <name> = <value>,`
+                    // and gets substituted wherever it's used in the circuit.
+                    if sep.token_type == TokenType::Assign {
+                        let (value, comma) = match constant_inner.next_tuple() {
+                            Some(v) => v,
+                            None => {
+                                self.error.abort(
+                                    &format!("Missing value for named literal `{}`.", name.token),
+                                    sep.line,
+                                    sep.column,
+                                );
+                                unreachable!();
+                            }
+                        };
+
+                        if comma.token_type != TokenType::Comma {
+                            self.error.abort("Separator is not a comma.", comma.line, comma.column);
+                        }
+
+                        named_literals
+                            .insert(name.token.clone(), self.parse_named_literal(typ, name, value));
+                        continue
+                    }
+
+                    if sep.token_type != TokenType::Comma {
+                        self.error.abort("Separator is not a comma.", sep.line, sep.column);
+                    }
+
                     constants_map.insert(name.token.clone(), (name.clone(), typ.clone()));
                 }
 
                 if constant_inner.next().is_some() {
-                    self.error.abort("Internal error, leftovers in 'constant' iterator", 0, 0);
+                    self.error.abort(
+                        "Invalid number of elements in 'constant' section. Must be '<Type> <name>' or '<Type> <name> = <value>' separated with a comma ','.",
+                        constant_tokens[0].line,
+                        constant_tokens[0].column,
+                    );
                 }
 
                 ast_inner.insert("constant".to_string(), constants_map);
@@ -226,6 +270,9 @@ impl Parser {
                     self.error.abort("Duplicate `circuit` section found.", t.line, t.column);
                 }
 
+                // Loops are unrolled before anything else, so the rest of
+                // the toolchain only ever sees a flat list of statements.
+                circuit_tokens = self.unroll_loops(&circuit_tokens);
                 self.check_section_structure("circuit", circuit_tokens.clone());
                 check_namespace!(circuit_tokens);
 
@@ -268,7 +315,7 @@ impl Parser {
             self.parse_ast_contract(c)
         };
 
-        let statements = self.parse_ast_circuit(circuit_stmts);
+        let statements = self.parse_ast_circuit(circuit_stmts, &named_literals);
         if statements.is_empty() {
             self.error.abort("Circuit section is empty.", 0, 0);
         }
@@ -302,7 +349,14 @@ impl Parser {
         }
 
         match section {
-            "constant" | "contract" => {
+            // The constant section can also hold named literals, so its
+            // element count is checked while it's being parsed.
+            "constant" => {
+                if tokens.len() == 3 {
+                    self.error.warn(&format!("{} section is empty.", section), 0, 0);
+                }
+            }
+            "contract" => {
                 if tokens.len() == 3 {
                     self.error.warn(&format!("{} section is empty.", section), 0, 0);
                 }
@@ -332,6 +386,108 @@ impl Parser {
         };
     }
 
+    /// Expand `repeat N { ... }` blocks into N copies of their statements.
+    /// Variables assigned inside of a loop shadow the ones assigned in
+    /// previous iterations, so they can be used as accumulators.
+    fn unroll_loops(&self, tokens: &[Token]) -> Vec<Token> {
+        let mut ret = vec![];
+
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let token = &tokens[idx];
+            if token.token_type != TokenType::Symbol || token.token != REPEAT_KEYWORD {
+                ret.push(token.clone());
+                idx += 1;
+                continue
+            }
+
+            let count = match tokens.get(idx + 1) {
+                Some(t) if t.token_type == TokenType::Number => match t.token.parse::<usize>() {
+                    Ok(v) if v > 0 && v <= MAX_REPEAT_COUNT => v,
+                    _ => {
+                        self.error.abort(
+                            &format!(
+                                "Loop count must be between 1 and {}, got `{}`.",
+                                MAX_REPEAT_COUNT, t.token
+                            ),
+                            t.line,
+                            t.column,
+                        );
+                        unreachable!();
+                    }
+                },
+                _ => {
+                    self.error.abort(
+                        "Expected a loop count after `repeat`.",
+                        token.line,
+                        token.column,
+                    );
+                    unreachable!();
+                }
+            };
+
+            match tokens.get(idx + 2) {
+                Some(t) if t.token_type == TokenType::LeftBrace => {}
+                _ => self.error.abort(
+                    "Loop body must be opened with a left brace '{'",
+                    token.line,
+                    token.column,
+                ),
+            }
+
+            // Find the brace closing this loop's body.
+            let mut depth = 0;
+            let mut end = None;
+            for (i, t) in tokens.iter().enumerate().skip(idx + 2) {
+                match t.token_type {
+                    TokenType::LeftBrace => depth += 1,
+                    TokenType::RightBrace => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = Some(i);
+                            break
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let end = match end {
+                Some(v) => v,
+                None => {
+                    self.error.abort(
+                        "Loop body must be closed with a right brace '}'",
+                        token.line,
+                        token.column,
+                    );
+                    unreachable!();
+                }
+            };
+
+            // Nested loops get unrolled first.
+            let body = self.unroll_loops(&tokens[idx + 3..end]);
+            if body.is_empty() {
+                self.error.abort("Loop body is empty.", token.line, token.column);
+            }
+
+            if body.last().unwrap().token_type != TokenType::Semicolon {
+                self.error.abort(
+                    "Loop body does not end with a semicolon.",
+                    tokens[end].line,
+                    tokens[end].column,
+                );
+            }
+
+            for _ in 0..count {
+                ret.extend_from_slice(&body);
+            }
+
+            idx = end + 1;
+        }
+
+        ret
+    }
+
     fn parse_ast_constants(&self, ast: &IndexMap<String, (Token, Token)>) -> Vec<Constant> {
         let mut ret = vec![];
 
@@ -441,6 +597,58 @@ impl Parser {
         ret
     }
 
+    fn parse_named_literal(&self, typ: &Token, name: &Token, value: &Token) -> Literal {
+        if name.token_type != TokenType::Symbol {
+            self.error.abort(
+                &format!("Named literal `{}` is not a symbol.", name.token),
+                name.line,
+                name.column,
+            );
+        }
+
+        if Opcode::from_name(&name.token).is_some() {
+            self.error.abort(
+                &format!("Named literal `{}` shadows an opcode.", name.token),
+                name.line,
+                name.column,
+            );
+        }
+
+        // Valid literal types, these have to be supported by the VM's
+        // literal stack.
+        let lit_type = match typ.token.as_str() {
+            "Uint64" => LitType::Uint64,
+            x => {
+                self.error.abort(
+                    &format!("`{}` is an unsupported literal type.", x),
+                    typ.line,
+                    typ.column,
+                );
+                unreachable!();
+            }
+        };
+
+        if value.token_type != TokenType::Number {
+            self.error.abort(
+                &format!("Value of named literal `{}` is not a number.", name.token),
+                value.line,
+                value.column,
+            );
+        }
+
+        if let Err(e) = value.token.parse::<u64>() {
+            self.error.abort(
+                &format!("Failed to convert literal into u64: {}", e),
+                value.line,
+                value.column,
+            );
+        }
+
+        // The literal keeps the value as its name, as that's what gets
+        // written into the `.literal` section of the binary.
+        Literal { name: value.token.clone(), typ: lit_type, line: name.line, column: name.column }
+    }
+
     fn parse_ast_contract(&self, ast: &IndexMap<String, (Token, Token)>) -> Vec<Witness> {
         let mut ret = vec![];
 
@@ -531,7 +739,11 @@ impl Parser {
         ret
     }
 
-    fn parse_ast_circuit(&self, statements: Vec<Vec<Token>>) -> Vec<Statement> {
+    fn parse_ast_circuit(
+        &self,
+        statements: Vec<Vec<Token>>,
+        named_literals: &IndexMap<String, Literal>,
+    ) -> Vec<Statement> {
         // The statement layouts/syntax in the language are as follows:
         //
         // C = poseidon_hash(pub_x, pub_y, value, token, serial, coin_blind);
@@ -567,7 +779,13 @@ impl Parser {
         //
         // The literal type is used only in the function call's scope, but
         // the result is then accessible on the stack to be used by further
-        // computation.
+        // computation. Literals can also be named in the constant section:
+        //
+        //      constant "Foo" { Uint64 VALUE_BITS = 64, }
+        //            range_check(VALUE_BITS, value);
+        //
+        // Named literals are substituted by their value right here, so
+        // for the rest of the toolchain they're just like any literal.
         //
         // Regarding multiple return values from opcodes, this is perhaps
         // not necessary for the current language scope, as this is a low
@@ -673,7 +891,7 @@ impl Parser {
 
                 // TODO: MAKE SURE IT'S A SYMBOL
                 if let Some(op) = Opcode::from_name(func_name) {
-                    let rhs = self.parse_function_call(token, &mut iter);
+                    let rhs = self.parse_function_call(token, &mut iter, named_literals);
                     stmt.opcode = op;
                     stmt.rhs = rhs;
                 } else {
@@ -696,6 +914,7 @@ impl Parser {
         &self,
         token: &Token,
         iter: &mut Peekable<std::slice::Iter<'_, Token>>,
+        named_literals: &IndexMap<String, Literal>,
     ) -> Vec<Arg> {
         if let Some(next_token) = iter.peek() {
             if next_token.token_type != TokenType::LeftParen {
@@ -732,7 +951,7 @@ impl Parser {
                     }
 
                     // Recurse this function to get the params of the nested one.
-                    let args = self.parse_function_call(arg, iter);
+                    let args = self.parse_function_call(arg, iter, named_literals);
 
                     // Then we assign a "fake" variable that serves as a stack
                     // reference.
@@ -768,6 +987,13 @@ impl Parser {
             if let Some(sep) = iter.next() {
                 // See if we have a variable or a literal type.
                 match arg.token_type {
+                    TokenType::Symbol if named_literals.contains_key(&arg.token) => {
+                        let mut lit = named_literals[&arg.token].clone();
+                        lit.line = arg.line;
+                        lit.column = arg.column;
+                        ret.push(Arg::Lit(lit))
+                    }
+
                     TokenType::Symbol => ret.push(Arg::Var(Variable {
                         name: arg.token.clone(),
                         typ: VarType::Dummy,
@@ -4,6 +4,7 @@ use darkfi::{
         Proof,
     },
     zk::{
+        testing::{mock_prove, FailureKind},
         vm::{Witness, ZkCircuit},
         vm_stack::empty_witnesses,
     },
@@ -66,3 +67,23 @@ fn arithmetic_proof() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn arithmetic_mock_prover() -> Result<()> {
+    let bincode = include_bytes!("../proof/arithmetic.zk.bin");
+    let zkbin = ZkBinary::decode(bincode)?;
+
+    let a = pallas::Base::from(42);
+    let b = pallas::Base::from(69);
+    let witnesses = vec![Witness::Base(Value::known(a)), Witness::Base(Value::known(b))];
+
+    let public_inputs = vec![a + b, a * b, a - b];
+    assert!(mock_prove(13, &zkbin, witnesses.clone(), &public_inputs)?.is_empty());
+
+    // A wrong sum breaks the copy constraint to the public input
+    let public_inputs = vec![a + b + pallas::Base::from(1), a * b, a - b];
+    let failures = mock_prove(13, &zkbin, witnesses, &public_inputs)?;
+    assert!(failures.iter().any(|f| f.kind == FailureKind::Permutation));
+
+    Ok(())
+}
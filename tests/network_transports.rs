@@ -8,7 +8,9 @@ use async_std::{
 };
 use url::Url;
 
-use darkfi::net::transport::{TcpTransport, TorTransport, Transport};
+use darkfi::net::transport::{
+    MemoryTransport, TcpTransport, TorTransport, Transport, TransportListener,
+};
 
 #[async_std::test]
 async fn tcp_transport() {
@@ -36,6 +38,33 @@ async fn tcp_transport() {
     assert_eq!(buf, payload);
 }
 
+#[async_std::test]
+async fn memory_transport() {
+    let memory = MemoryTransport::new();
+    let url = Url::parse("mem://memory_transport").unwrap();
+
+    let listener = memory.listen(url.clone()).unwrap();
+    assert!(memory.listen(url.clone()).is_err());
+
+    let payload = b"ohai mem";
+
+    let mut client = memory.dial(url.clone()).unwrap();
+    let (mut server, _) = listener.next().await.unwrap();
+    client.write_all(payload).await.unwrap();
+    let mut buf = vec![0_u8; 8];
+    server.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, payload);
+
+    // Closing one end is seen as EOF on the other
+    server.close().await.unwrap();
+    assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+
+    // The name is released once the listener is dropped
+    drop(listener);
+    assert!(memory.dial(url.clone()).is_err());
+    assert!(memory.listen(url).is_ok());
+}
+
 #[async_std::test]
 async fn tcp_tls_transport() {
     let tcp = TcpTransport::new(None, 1024);
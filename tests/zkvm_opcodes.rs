@@ -16,7 +16,7 @@ use darkfi::{
         keypair::{PublicKey, SecretKey},
         merkle_node::MerkleNode,
         proof::{ProvingKey, VerifyingKey},
-        util::pedersen_commitment_u64,
+        util::{pedersen_commitment_u64, poseidon_sponge},
         Proof,
     },
     zk::{
@@ -81,6 +81,8 @@ fn zkvm_opcodes() -> Result<()> {
     let d_m = [pallas::Base::one(), blind, *value_coords.x(), *value_coords.y()];
     let d = poseidon::Hash::<_, P128Pow5T3, ConstantLength<4>, 3, 2>::init().hash(d_m);
 
+    let e = poseidon_sponge(&[pallas::Base::one(), blind, a, b, secret]);
+
     let public = PublicKey::from_secret(SecretKey(secret));
     let public_coords = public.0.to_affine().coordinates().unwrap();
 
@@ -92,6 +94,9 @@ fn zkvm_opcodes() -> Result<()> {
         root.0,
         *public_coords.x(),
         *public_coords.y(),
+        a,
+        pallas::Base::from(3),
+        e,
     ];
 
     let circuit = ZkCircuit::new(prover_witnesses, zkbin.clone());